@@ -0,0 +1,165 @@
+//! Wasmer will let you easily run Wasm module in a Rust host.
+//!
+//! This example is a template for writing your own middleware that
+//! rewrites the code of a module:
+//!
+//!   1. How to inject a global into a module and export it
+//!   2. How to keep per-function state in a function middleware
+//!   3. How to insert instructions in front of a function body
+//!
+//! The middleware counts how many times the functions of the module
+//! have been called.
+//!
+//! You can run the example directly by executing in Wasmer root:
+//!
+//! ```shell
+//! cargo run --example middleware-call-counter --release --features "singlepass"
+//! ```
+//!
+//! Ready?
+
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::Operator;
+use wasmer::{
+    imports, wat2wasm, AsStoreMut, CompilerConfig, ExportIndex, FunctionMiddleware, GlobalIndex,
+    GlobalInit, GlobalType, Instance, LocalFunctionIndex, MiddlewareError, MiddlewareReaderState,
+    Module, ModuleInfo, ModuleMiddleware, Mutability, Store, Type, TypedFunction, Value,
+};
+use wasmer_compiler_singlepass::Singlepass;
+
+/// The name under which the counter global is exported.
+const CALL_COUNT_EXPORT: &str = "call_count";
+
+/// The module-level middleware.
+///
+/// It is shared by all the compilation threads, so any state is
+/// behind a lock. The global is only known once the module has been
+/// transformed.
+#[derive(Debug, Default)]
+struct CallCounter {
+    global_index: Mutex<Option<GlobalIndex>>,
+}
+
+impl ModuleMiddleware for CallCounter {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionCallCounter {
+            global_index: self.global_index.lock().unwrap().unwrap(),
+            entered: false,
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        // Append a global holding the counter, initialized to 0...
+        let global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I64Const(0));
+
+        // ...and export it so that the host can read it.
+        module_info.exports.insert(
+            CALL_COUNT_EXPORT.to_string(),
+            ExportIndex::Global(global_index),
+        );
+
+        *self.global_index.lock().unwrap() = Some(global_index);
+    }
+}
+
+/// The function-level middleware, one is created per function.
+#[derive(Debug)]
+struct FunctionCallCounter {
+    global_index: GlobalIndex,
+    /// Whether the first operator of the body has been seen.
+    entered: bool,
+}
+
+impl FunctionMiddleware for FunctionCallCounter {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+
+            // globals[global_index] += 1;
+            state.extend(&[
+                Operator::GlobalGet {
+                    global_index: self.global_index.as_u32(),
+                },
+                Operator::I64Const { value: 1 },
+                Operator::I64Add,
+                Operator::GlobalSet {
+                    global_index: self.global_index.as_u32(),
+                },
+            ]);
+        }
+
+        // Always forward the original operator.
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+fn get_call_count(store: &mut impl AsStoreMut, instance: &Instance) -> i64 {
+    match instance
+        .exports
+        .get_global(CALL_COUNT_EXPORT)
+        .unwrap()
+        .get(store)
+    {
+        Value::I64(count) => count,
+        value => panic!("unexpected value for the call counter: {:?}", value),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    // Let's declare the Wasm module.
+    let wasm_bytes = wat2wasm(
+        br#"
+(module
+  (func $double (param i32) (result i32)
+    local.get 0
+    i32.const 2
+    i32.mul)
+  (func $quadruple (export "quadruple") (param i32) (result i32)
+    local.get 0
+    call $double
+    call $double))
+"#,
+    )?;
+
+    // Let's register our middleware on the compiler.
+    let mut compiler_config = Singlepass::default();
+    compiler_config.push_middleware(Arc::new(CallCounter::default()));
+    let mut store = Store::new(compiler_config);
+
+    println!("Compiling module...");
+    let module = Module::new(&store, wasm_bytes)?;
+
+    println!("Instantiating module...");
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let quadruple: TypedFunction<i32, i32> = instance
+        .exports
+        .get_function("quadruple")?
+        .typed(&mut store)?;
+
+    println!("Calling `quadruple` function...");
+    assert_eq!(quadruple.call(&mut store, 3)?, 12);
+
+    // `quadruple` has been called once, and it called `double` twice.
+    let call_count = get_call_count(&mut store, &instance);
+    println!("Functions called: {}", call_count);
+    assert_eq!(call_count, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_middleware_call_counter() -> anyhow::Result<()> {
+    main()
+}
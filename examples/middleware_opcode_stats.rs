@@ -0,0 +1,141 @@
+//! Wasmer will let you easily run Wasm module in a Rust host.
+//!
+//! This example is a template for writing your own middleware that
+//! only analyzes the code of a module, without changing it:
+//!
+//!   1. How to share state between the host and a middleware
+//!   2. How to keep per-function state in a function middleware
+//!   3. How to detect the end of a function body
+//!
+//! The middleware counts, at compile time, how many times each kind of
+//! operator appears in the module.
+//!
+//! You can run the example directly by executing in Wasmer root:
+//!
+//! ```shell
+//! cargo run --example middleware-opcode-stats --release --features "singlepass"
+//! ```
+//!
+//! Ready?
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::Operator;
+use wasmer::{
+    wat2wasm, CompilerConfig, FunctionMiddleware, LocalFunctionIndex, MiddlewareError,
+    MiddlewareReaderState, Module, ModuleMiddleware, Store,
+};
+use wasmer_compiler_singlepass::Singlepass;
+
+type Stats = Arc<Mutex<BTreeMap<String, u64>>>;
+
+/// The module-level middleware, holding the statistics shared with
+/// the host.
+#[derive(Debug, Default)]
+struct OpcodeStats {
+    stats: Stats,
+}
+
+impl ModuleMiddleware for OpcodeStats {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionOpcodeStats {
+            stats: self.stats.clone(),
+            local_stats: BTreeMap::new(),
+            depth: 0,
+        })
+    }
+}
+
+/// The function-level middleware, one is created per function.
+///
+/// Counts are first gathered locally, then merged into the shared
+/// statistics once the function body is complete, so that the lock is
+/// only taken once per function.
+#[derive(Debug)]
+struct FunctionOpcodeStats {
+    stats: Stats,
+    local_stats: BTreeMap<String, u64>,
+    /// Number of blocks currently open, the function body being one.
+    depth: usize,
+}
+
+impl FunctionMiddleware for FunctionOpcodeStats {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        // Use the name of the variant as the operator kind.
+        let debug = format!("{:?}", operator);
+        let kind = debug
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        *self.local_stats.entry(kind.to_string()).or_default() += 1;
+
+        match operator {
+            Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => self.depth += 1,
+            // The last `end` closes the function body.
+            Operator::End if self.depth == 0 => {
+                let mut stats = self.stats.lock().unwrap();
+                for (kind, count) in std::mem::take(&mut self.local_stats) {
+                    *stats.entry(kind).or_default() += count;
+                }
+            }
+            Operator::End => self.depth -= 1,
+            _ => {}
+        }
+
+        // This middleware doesn't change the code.
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    // Let's declare the Wasm module.
+    let wasm_bytes = wat2wasm(
+        br#"
+(module
+  (func $fac (export "fac") (param i64) (result i64)
+    local.get 0
+    i64.eqz
+    if (result i64)
+      i64.const 1
+    else
+      local.get 0
+      local.get 0
+      i64.const 1
+      i64.sub
+      call $fac
+      i64.mul
+    end))
+"#,
+    )?;
+
+    // Let's register our middleware on the compiler, keeping a
+    // handle on the statistics.
+    let middleware = Arc::new(OpcodeStats::default());
+    let stats = middleware.stats.clone();
+    let mut compiler_config = Singlepass::default();
+    compiler_config.push_middleware(middleware);
+    let store = Store::new(compiler_config);
+
+    println!("Compiling module...");
+    let _module = Module::new(&store, wasm_bytes)?;
+
+    let stats = stats.lock().unwrap();
+    for (kind, count) in stats.iter() {
+        println!("{:>12}: {}", kind, count);
+    }
+    assert_eq!(stats.get("LocalGet"), Some(&3));
+    assert_eq!(stats.get("End"), Some(&2));
+
+    Ok(())
+}
+
+#[test]
+fn test_middleware_opcode_stats() -> anyhow::Result<()> {
+    main()
+}
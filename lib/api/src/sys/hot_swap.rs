@@ -0,0 +1,305 @@
+use crate::sys::externals::Extern;
+use crate::sys::instance::InstantiationError;
+use crate::sys::mem_access::MemoryAccessError;
+use crate::sys::module::Module;
+use crate::sys::store::AsStoreMut;
+use crate::sys::RuntimeError;
+use std::fmt;
+use thiserror::Error;
+use wasmer_types::{ExportIndex, ExternType, GlobalType, ModuleInfo, Mutability};
+use wasmer_vm::{InstanceHandle, MemoryError, StoreHandle};
+
+/// The number of bytes copied at once between two memories.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A difference between two modules preventing one from replacing the
+/// other in a running instance, see [`Instance::hot_swap`].
+///
+/// [`Instance::hot_swap`]: crate::Instance::hot_swap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotSwapIncompatibility {
+    /// An export of the running module is missing.
+    MissingExport(String),
+    /// An export changed type.
+    Export {
+        /// The name of the export.
+        name: String,
+        /// Its type in the running module.
+        old: ExternType,
+        /// Its type in the new module.
+        new: ExternType,
+    },
+    /// An import of both modules changed type.
+    Import {
+        /// The module of the import.
+        module: String,
+        /// The name of the import.
+        name: String,
+        /// Its type in the running module.
+        old: ExternType,
+        /// Its type in the new module.
+        new: ExternType,
+    },
+    /// The modules don't define as many memories, or a memory is
+    /// shared in one and not the other.
+    Memories,
+    /// The modules don't define the same globals.
+    Globals {
+        /// The globals defined by the running module.
+        old: Vec<GlobalType>,
+        /// The globals defined by the new module.
+        new: Vec<GlobalType>,
+    },
+}
+
+impl fmt::Display for HotSwapIncompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingExport(name) => write!(f, "the export `{}` is missing", name),
+            Self::Export { name, old, new } => {
+                write!(
+                    f,
+                    "the export `{}` was {} and is {}",
+                    name,
+                    DisplayExternType(old),
+                    DisplayExternType(new)
+                )
+            }
+            Self::Import {
+                module,
+                name,
+                old,
+                new,
+            } => write!(
+                f,
+                "the import `{}`.`{}` was {} and is {}",
+                module,
+                name,
+                DisplayExternType(old),
+                DisplayExternType(new)
+            ),
+            Self::Memories => write!(f, "the modules don't define the same memories"),
+            Self::Globals { old, new } => write!(
+                f,
+                "the globals were {:?} and are {:?}",
+                old.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                new.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ),
+        }
+    }
+}
+
+struct DisplayExternType<'a>(&'a ExternType);
+
+impl fmt::Display for DisplayExternType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            ExternType::Function(ty) => write!(f, "the function {}", ty),
+            ExternType::Global(ty) => write!(f, "the global {}", ty),
+            ExternType::Table(ty) => write!(f, "the table {}", ty),
+            ExternType::Memory(ty) => write!(f, "the memory {}", ty),
+        }
+    }
+}
+
+/// An error while swapping the module of an instance, see
+/// [`Instance::hot_swap`].
+///
+/// The instance is left untouched.
+///
+/// [`Instance::hot_swap`]: crate::Instance::hot_swap
+#[derive(Error, Debug)]
+pub enum HotSwapError {
+    /// The new module can't replace the running one.
+    #[error("the module is incompatible with the instance: {}", display_list(.0))]
+    Incompatible(Vec<HotSwapIncompatibility>),
+
+    /// The new module failed to instantiate.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+
+    /// A memory of the new instance can't hold the contents of the
+    /// running one.
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+
+    /// The contents of a memory failed to be copied.
+    #[error(transparent)]
+    MemoryAccess(#[from] MemoryAccessError),
+
+    /// The value of a global failed to be copied.
+    #[error(transparent)]
+    Global(#[from] RuntimeError),
+}
+
+fn display_list(incompatibilities: &[HotSwapIncompatibility]) -> String {
+    incompatibilities
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Lists what prevents `new` from replacing `old` in a running
+/// instance.
+pub(crate) fn incompatibilities(old: &Module, new: &Module) -> Vec<HotSwapIncompatibility> {
+    let mut incompatibilities = Vec::new();
+
+    for export in old.exports() {
+        match new.exports().find(|e| e.name() == export.name()) {
+            None => incompatibilities.push(HotSwapIncompatibility::MissingExport(
+                export.name().to_string(),
+            )),
+            Some(new_export) if !compatible(export.ty(), new_export.ty()) => incompatibilities
+                .push(HotSwapIncompatibility::Export {
+                    name: export.name().to_string(),
+                    old: export.ty().clone(),
+                    new: new_export.ty().clone(),
+                }),
+            Some(_) => {}
+        }
+    }
+
+    for import in old.imports() {
+        let new_import = new
+            .imports()
+            .find(|i| i.module() == import.module() && i.name() == import.name());
+        match new_import {
+            Some(new_import) if !compatible(import.ty(), new_import.ty()) => incompatibilities
+                .push(HotSwapIncompatibility::Import {
+                    module: import.module().to_string(),
+                    name: import.name().to_string(),
+                    old: import.ty().clone(),
+                    new: new_import.ty().clone(),
+                }),
+            _ => {}
+        }
+    }
+
+    let (old_info, new_info) = (old.info(), new.info());
+    let local_memories = |info: &ModuleInfo| {
+        info.memories
+            .values()
+            .skip(info.num_imported_memories)
+            .map(|memory| memory.shared)
+            .collect::<Vec<_>>()
+    };
+    if local_memories(old_info) != local_memories(new_info) {
+        incompatibilities.push(HotSwapIncompatibility::Memories);
+    }
+
+    let local_globals = |info: &ModuleInfo| {
+        info.globals
+            .values()
+            .skip(info.num_imported_globals)
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    let (old_globals, new_globals) = (local_globals(old_info), local_globals(new_info));
+    if old_globals != new_globals {
+        incompatibilities.push(HotSwapIncompatibility::Globals {
+            old: old_globals,
+            new: new_globals,
+        });
+    }
+
+    incompatibilities
+}
+
+/// Whether an extern of type `new` can stand for one of type `old`.
+/// The limits of memories and tables may change: the tables are not
+/// transferred, and the memories are grown as needed.
+fn compatible(old: &ExternType, new: &ExternType) -> bool {
+    match (old, new) {
+        (ExternType::Memory(old), ExternType::Memory(new)) => old.shared == new.shared,
+        (ExternType::Table(old), ExternType::Table(new)) => old.ty == new.ty,
+        (old, new) => old == new,
+    }
+}
+
+/// Copies the contents of the memories and the values of the mutable
+/// globals defined by `old`, an instance of `old_module`, into `new`,
+/// an instance of the compatible `new_module`.
+///
+/// The modules may not import as many memories or globals, so the
+/// ones they define are matched by their position among the defined
+/// ones, not by their index.
+pub(crate) fn transfer(
+    store: &mut impl AsStoreMut,
+    old_module: &Module,
+    new_module: &Module,
+    old: &StoreHandle<InstanceHandle>,
+    new: &StoreHandle<InstanceHandle>,
+) -> Result<(), HotSwapError> {
+    let (old_info, new_info) = (old_module.info(), new_module.info());
+
+    let memories = old_info
+        .memories
+        .keys()
+        .skip(old_info.num_imported_memories)
+        .zip(
+            new_info
+                .memories
+                .keys()
+                .skip(new_info.num_imported_memories),
+        );
+    for (old_index, new_index) in memories {
+        let (old, new) = match (
+            lookup(store, old, ExportIndex::Memory(old_index)),
+            lookup(store, new, ExportIndex::Memory(new_index)),
+        ) {
+            (Extern::Memory(old), Extern::Memory(new)) => (old, new),
+            _ => unreachable!("the externs are memories"),
+        };
+
+        let (old_size, new_size) = (old.view(store).size(), new.view(store).size());
+        if old_size > new_size {
+            new.grow(store, old_size - new_size)?;
+        }
+
+        let (old_view, new_view) = (old.view(store), new.view(store));
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let len = old_view.data_size();
+        let mut offset = 0;
+        while offset < len {
+            let chunk = &mut chunk[..(len - offset).min(CHUNK_SIZE as u64) as usize];
+            old_view.read(offset, chunk)?;
+            new_view.write(offset, chunk)?;
+            offset += chunk.len() as u64;
+        }
+    }
+
+    let globals = old_info
+        .globals
+        .iter()
+        .skip(old_info.num_imported_globals)
+        .zip(new_info.globals.keys().skip(new_info.num_imported_globals));
+    for ((old_index, ty), new_index) in globals {
+        if ty.mutability != Mutability::Var {
+            continue;
+        }
+        let (old, new) = match (
+            lookup(store, old, ExportIndex::Global(old_index)),
+            lookup(store, new, ExportIndex::Global(new_index)),
+        ) {
+            (Extern::Global(old), Extern::Global(new)) => (old, new),
+            _ => unreachable!("the externs are globals"),
+        };
+
+        let value = old.get(store);
+        new.set(store, value)?;
+    }
+
+    Ok(())
+}
+
+fn lookup(
+    store: &mut impl AsStoreMut,
+    instance: &StoreHandle<InstanceHandle>,
+    index: ExportIndex,
+) -> Extern {
+    let export = instance
+        .get_mut(store.objects_mut())
+        .lookup_by_declaration(index);
+    Extern::from_vm_extern(store, export)
+}
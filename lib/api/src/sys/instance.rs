@@ -1,5 +1,6 @@
 use crate::sys::exports::Exports;
 use crate::sys::externals::Extern;
+use crate::sys::hot_swap::{self, HotSwapError, HotSwapIncompatibility};
 use crate::sys::imports::Imports;
 use crate::sys::module::Module;
 use crate::sys::{LinkError, RuntimeError};
@@ -175,10 +176,74 @@ impl Instance {
         Ok(instance)
     }
 
+    /// Creates an `Instance` from the `handle` of an instance of
+    /// `module`, and its exports.
+    pub(crate) fn from_handle(
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        handle: InstanceHandle,
+        exports: Exports,
+    ) -> Self {
+        Self {
+            _handle: StoreHandle::new(store.objects_mut(), handle),
+            module: module.clone(),
+            exports,
+        }
+    }
+
     /// Gets the [`Module`] associated with this instance.
     pub fn module(&self) -> &Module {
         &self.module
     }
+
+    /// Lists what prevents `module` from replacing the module of this
+    /// instance with [`Instance::hot_swap`]; it's empty if it can.
+    ///
+    /// The exports of the instance must keep their type, the imports
+    /// shared by both modules too, and the modules must define the
+    /// same globals and as many memories.
+    pub fn hot_swap_incompatibilities(&self, module: &Module) -> Vec<HotSwapIncompatibility> {
+        hot_swap::incompatibilities(&self.module, module)
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Replaces the code of this instance with the one of `module`,
+    /// keeping its state: `module` is instantiated with `imports`, then
+    /// the memories and the mutable globals defined by the instance are
+    /// copied into the new instance, which takes its place.
+    ///
+    /// This is experimental, and meant for edit-and-continue
+    /// workflows. The memories and globals defined by the modules are
+    /// matched by their order, whatever the imports, so `module`
+    /// should lay its state out like the running one.
+    /// The start function of `module` runs before the state is copied,
+    /// and the state it writes is overwritten. The tables aren't
+    /// transferred, and the externs previously exported by the instance
+    /// keep referring to the old one.
+    ///
+    /// ## Errors
+    ///
+    /// The function returns [`HotSwapError::Incompatible`] if
+    /// [`Instance::hot_swap_incompatibilities`] isn't empty, and fails
+    /// if the new instance can't be created or can't hold the state.
+    /// The instance is then left untouched.
+    pub fn hot_swap(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        imports: &Imports,
+    ) -> Result<(), HotSwapError> {
+        let incompatibilities = self.hot_swap_incompatibilities(module);
+        if !incompatibilities.is_empty() {
+            return Err(HotSwapError::Incompatible(incompatibilities));
+        }
+
+        let new = Self::new(store, module, imports)?;
+        hot_swap::transfer(store, &self.module, module, &self._handle, &new._handle)?;
+        *self = new;
+
+        Ok(())
+    }
 }
 
 impl fmt::Debug for Instance {
@@ -0,0 +1,180 @@
+use crate::sys::exports::Exports;
+use crate::sys::externals::Extern;
+use crate::sys::imports::Imports;
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::module::Module;
+use crate::sys::store::AsStoreMut;
+use std::fmt;
+use wasmer_types::{
+    DataInitializer, DataInitializerLocation, ExportIndex, MemoryIndex, OwnedDataInitializer,
+};
+
+/// A template to create many instances of a [`Module`] with the same
+/// imports.
+///
+/// The work shared by the instances is done once, when the template
+/// is created: the imports are resolved, the exports are indexed, and
+/// the data segments are merged into a memory image, copied as is in
+/// the memories of each instance.
+///
+/// ## Example
+///
+/// ```
+/// # use wasmer::{imports, InstanceTemplate, Module, Store};
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let module = Module::new(&store, "(module (memory (export \"memory\") 1) (data (i32.const 0) \"hello\"))")?;
+/// let template = InstanceTemplate::new(&module, &imports! {})?;
+///
+/// for _ in 0..10 {
+///     let instance = template.instantiate(&mut store)?;
+///     # let memory = instance.exports.get_memory("memory")?;
+///     # let mut hello = [0; 5];
+///     # memory.view(&store).read(0, &mut hello)?;
+///     # assert_eq!(&hello, b"hello");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct InstanceTemplate {
+    module: Module,
+    imports: Vec<Extern>,
+    exports: Vec<(String, ExportIndex)>,
+    /// The data segments merged into disjoint runs, or `None` if they
+    /// must be applied one by one, e.g. because their offsets depend
+    /// on globals.
+    memory_image: Option<Vec<OwnedDataInitializer>>,
+}
+
+impl InstanceTemplate {
+    /// Creates a template to instantiate `module` with `imports`.
+    ///
+    /// ## Errors
+    ///
+    /// The function can return link errors, like [`Instance::new`].
+    pub fn new(module: &Module, imports: &Imports) -> Result<Self, InstantiationError> {
+        let imports = imports
+            .imports_for_module(module)
+            .map_err(InstantiationError::Link)?;
+        let exports = module
+            .info()
+            .exports
+            .iter()
+            .map(|(name, index)| (name.clone(), *index))
+            .collect();
+
+        Ok(Self {
+            module: module.clone(),
+            imports,
+            exports,
+            memory_image: memory_image(module),
+        })
+    }
+
+    /// Creates a new instance from the template.
+    ///
+    /// ## Errors
+    ///
+    /// The function can return [`InstantiationError`]s, like
+    /// [`Instance::new`]. The imports must come from `store`.
+    pub fn instantiate(&self, store: &mut impl AsStoreMut) -> Result<Instance, InstantiationError> {
+        let data_initializers = self.memory_image.as_ref().map(|memory_image| {
+            memory_image
+                .iter()
+                .map(|run| DataInitializer {
+                    location: run.location.clone(),
+                    data: &run.data,
+                })
+                .collect::<Vec<_>>()
+        });
+        let mut handle = self.module.instantiate_with_data(
+            store,
+            &self.imports,
+            data_initializers.as_deref(),
+        )?;
+        let exports = self
+            .exports
+            .iter()
+            .map(|(name, index)| {
+                let export = handle.lookup_by_declaration(*index);
+                (name.clone(), Extern::from_vm_extern(store, export))
+            })
+            .collect::<Exports>();
+
+        Ok(Instance::from_handle(store, &self.module, handle, exports))
+    }
+
+    /// Gets the [`Module`] of the template.
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+}
+
+impl fmt::Debug for InstanceTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InstanceTemplate")
+            .field("module", &self.module)
+            .finish()
+    }
+}
+
+/// Merges the data segments of `module` into disjoint runs, applying
+/// them in order.
+///
+/// It gives up when the segments could trap, or don't have a
+/// constant offset in a memory defined by the module: their effect
+/// then depends on the instance.
+fn memory_image(module: &Module) -> Option<Vec<OwnedDataInitializer>> {
+    let info = module.info();
+    let segments = module.data_initializers();
+
+    let mut bounds = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let location = &segment.location;
+        info.local_memory_index(location.memory_index)?;
+        if location.base.is_some() {
+            return None;
+        }
+
+        let end = location.offset.checked_add(segment.data.len())?;
+        if end > info.memories[location.memory_index].minimum.bytes().0 {
+            return None;
+        }
+        if !segment.data.is_empty() {
+            bounds.push((location.memory_index, location.offset, end));
+        }
+    }
+
+    // Merge the overlapping and adjacent segments.
+    bounds.sort_unstable();
+    let mut runs: Vec<(MemoryIndex, usize, usize)> = Vec::new();
+    for (memory_index, start, end) in bounds {
+        match runs.last_mut() {
+            Some(run) if run.0 == memory_index && start <= run.2 => run.2 = run.2.max(end),
+            _ => runs.push((memory_index, start, end)),
+        }
+    }
+
+    let mut image = runs
+        .iter()
+        .map(|(memory_index, start, end)| OwnedDataInitializer {
+            location: DataInitializerLocation {
+                memory_index: *memory_index,
+                base: None,
+                offset: *start,
+            },
+            data: vec![0; end - start].into_boxed_slice(),
+        })
+        .collect::<Vec<_>>();
+    for segment in segments.iter().filter(|segment| !segment.data.is_empty()) {
+        let location = &segment.location;
+        let run = runs.partition_point(|(memory_index, start, _)| {
+            (*memory_index, *start) <= (location.memory_index, location.offset)
+        }) - 1;
+        let offset = location.offset - image[run].location.offset;
+        image[run].data[offset..offset + segment.data.len()].copy_from_slice(&segment.data);
+    }
+
+    Some(image)
+}
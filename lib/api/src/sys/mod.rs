@@ -2,8 +2,10 @@ mod exports;
 mod extern_ref;
 mod externals;
 mod function_env;
+mod hot_swap;
 mod imports;
 mod instance;
+mod instance_template;
 mod mem_access;
 mod module;
 mod native;
@@ -20,8 +22,10 @@ pub use crate::sys::externals::{
     WasmTypeList,
 };
 pub use crate::sys::function_env::{FunctionEnv, FunctionEnvMut};
+pub use crate::sys::hot_swap::{HotSwapError, HotSwapIncompatibility};
 pub use crate::sys::imports::Imports;
 pub use crate::sys::instance::{Instance, InstantiationError};
+pub use crate::sys::instance_template::InstanceTemplate;
 pub use crate::sys::mem_access::{MemoryAccessError, WasmRef, WasmSlice, WasmSliceIter};
 pub use crate::sys::module::Module;
 pub use crate::sys::native::TypedFunction;
@@ -46,9 +50,9 @@ pub use wasmer_types::{
 };
 
 pub use wasmer_types::{
-    Bytes, CompileError, DeserializeError, ExportIndex, GlobalInit, LocalFunctionIndex,
-    MiddlewareError, Pages, ParseCpuFeatureError, SerializeError, ValueType, WasmError, WasmResult,
-    WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    Bytes, CompileError, DeserializeError, ExportIndex, GlobalIndex, GlobalInit,
+    LocalFunctionIndex, MiddlewareError, ModuleInfo, Pages, ParseCpuFeatureError, SerializeError,
+    ValueType, WasmError, WasmResult, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
 
 // TODO: should those be moved into wasmer::vm as well?
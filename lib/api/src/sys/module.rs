@@ -13,7 +13,8 @@ use wasmer_compiler::ArtifactCreate;
 #[cfg(feature = "wat")]
 use wasmer_types::WasmError;
 use wasmer_types::{
-    CompileError, DeserializeError, ExportsIterator, ImportsIterator, ModuleInfo, SerializeError,
+    CompileError, DataInitializer, DeserializeError, ExportsIterator, ImportsIterator, ModuleInfo,
+    OwnedDataInitializer, SerializeError,
 };
 use wasmer_types::{ExportType, ImportType};
 use wasmer_vm::InstanceHandle;
@@ -347,6 +348,18 @@ impl Module {
         &self,
         store: &mut impl AsStoreMut,
         imports: &[crate::Extern],
+    ) -> Result<InstanceHandle, InstantiationError> {
+        self.instantiate_with_data(store, imports, None)
+    }
+
+    /// Like [`Module::instantiate`], but initializes the memories with
+    /// `data_initializers`, if any, rather than with the data segments
+    /// of the module. They must leave the memories in the same state.
+    pub(crate) fn instantiate_with_data(
+        &self,
+        store: &mut impl AsStoreMut,
+        imports: &[crate::Extern],
+        data_initializers: Option<&[DataInitializer<'_>]>,
     ) -> Result<InstanceHandle, InstantiationError> {
         // Ensure all imports come from the same context.
         for import in imports {
@@ -371,10 +384,17 @@ impl Module {
             // of this steps traps, we still need to keep the instance alive
             // as some of the Instance elements may have placed in other
             // instance tables.
-            self.artifact.finish_instantiation(
-                store.as_store_ref().signal_handler(),
-                &mut instance_handle,
-            )?;
+            let trap_handler = store.as_store_ref().signal_handler();
+            match data_initializers {
+                Some(data_initializers) => self.artifact.finish_instantiation_with_data(
+                    trap_handler,
+                    &mut instance_handle,
+                    data_initializers,
+                )?,
+                None => self
+                    .artifact
+                    .finish_instantiation(trap_handler, &mut instance_handle)?,
+            }
 
             Ok(instance_handle)
         }
@@ -504,6 +524,11 @@ impl Module {
     pub fn info(&self) -> &ModuleInfo {
         &self.module_info
     }
+
+    /// The data segments of the module.
+    pub(crate) fn data_initializers(&self) -> &[OwnedDataInitializer] {
+        self.artifact.data_initializers()
+    }
 }
 
 impl fmt::Debug for Module {
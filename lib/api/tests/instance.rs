@@ -78,3 +78,308 @@ fn unit_native_function_env() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(feature = "sys")]
+#[test]
+fn instances_of_a_template_are_independent() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (import "host" "offset" (global $offset i32))
+  (memory (export "memory") 1)
+  (global $started (export "started") (mut i32) (i32.const 0))
+  ;; The overlapping and adjacent segments are merged, the last
+  ;; one winning.
+  (data (i32.const 0) "hello")
+  (data (i32.const 3) "p!")
+  (data (i32.const 5) "?")
+  (data (i32.const 100) "world")
+  (func (export "set") (param i32 i32)
+    local.get 0
+    local.get 1
+    i32.store8)
+  (func $start
+    i32.const 1
+    global.set $started)
+  (start $start))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let offset = Global::new(&mut store, Value::I32(0));
+    let template = InstanceTemplate::new(&module, &imports! { "host" => { "offset" => offset } })
+        .map_err(|e| format!("{e:?}"))?;
+
+    let read = |store: &Store, instance: &Instance, offset: u64, len: usize| {
+        let memory = instance.exports.get_memory("memory").unwrap();
+        let mut bytes = vec![0; len];
+        memory.view(store).read(offset, &mut bytes).unwrap();
+        bytes
+    };
+
+    let first = template
+        .instantiate(&mut store)
+        .map_err(|e| format!("{e:?}"))?;
+    let second = template
+        .instantiate(&mut store)
+        .map_err(|e| format!("{e:?}"))?;
+    first
+        .exports
+        .get_function("set")
+        .map_err(|e| format!("{e:?}"))?
+        .call(&mut store, &[Value::I32(0), Value::I32(b'j' as i32)])
+        .map_err(|e| format!("{e:?}"))?;
+
+    assert_eq!(read(&store, &first, 0, 6), b"jelp!?");
+    assert_eq!(read(&store, &second, 0, 6), b"help!?");
+    assert_eq!(read(&store, &second, 100, 5), b"world");
+    assert_eq!(
+        second
+            .exports
+            .get_global("started")
+            .map_err(|e| format!("{e:?}"))?
+            .get(&mut store),
+        Value::I32(1),
+        "the start function is called"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn templates_apply_segments_based_on_globals() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (import "host" "offset" (global $offset i32))
+  (memory (export "memory") 1)
+  (data (global.get $offset) "hello"))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let offset = Global::new(&mut store, Value::I32(8));
+    let template = InstanceTemplate::new(&module, &imports! { "host" => { "offset" => offset } })
+        .map_err(|e| format!("{e:?}"))?;
+
+    let instance = template
+        .instantiate(&mut store)
+        .map_err(|e| format!("{e:?}"))?;
+    let mut hello = [0; 5];
+    instance
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?
+        .view(&store)
+        .read(8, &mut hello)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(&hello, b"hello");
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn hot_swap_keeps_the_memories_and_globals() -> Result<(), String> {
+    let mut store = Store::default();
+    let counter = |step: i32| {
+        format!(
+            r#"
+(module
+  (memory (export "memory") 1)
+  (global $count (export "count") (mut i32) (i32.const 0))
+  (data (i32.const 0) "v{step}")
+  (func (export "tick")
+    global.get $count
+    i32.const {step}
+    i32.add
+    global.set $count))
+"#
+        )
+    };
+    let module = Module::new(&store, counter(1)).map_err(|e| format!("{e:?}"))?;
+    let mut instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let tick = |store: &mut Store, instance: &Instance| {
+        instance
+            .exports
+            .get_function("tick")
+            .unwrap()
+            .call(store, &[])
+            .unwrap();
+    };
+    let count = |store: &mut Store, instance: &Instance| {
+        instance.exports.get_global("count").unwrap().get(store)
+    };
+
+    tick(&mut store, &instance);
+    tick(&mut store, &instance);
+    let memory = instance
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?;
+    memory.grow(&mut store, 1).map_err(|e| format!("{e:?}"))?;
+    memory
+        .view(&store)
+        .write(70_000, b"kept")
+        .map_err(|e| format!("{e:?}"))?;
+
+    let module = Module::new(&store, counter(10)).map_err(|e| format!("{e:?}"))?;
+    assert!(instance.hot_swap_incompatibilities(&module).is_empty());
+    instance
+        .hot_swap(&mut store, &module, &imports! {})
+        .map_err(|e| format!("{e:?}"))?;
+
+    assert_eq!(count(&mut store, &instance), Value::I32(2));
+    tick(&mut store, &instance);
+    assert_eq!(
+        count(&mut store, &instance),
+        Value::I32(12),
+        "the new code runs"
+    );
+    let mut bytes = [0; 4];
+    let memory = instance
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?;
+    memory
+        .view(&store)
+        .read(0, &mut bytes[..3])
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        &bytes[..3],
+        b"v1\0",
+        "the memory is copied over the data segments"
+    );
+    memory
+        .view(&store)
+        .read(70_000, &mut bytes)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(&bytes, b"kept");
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn hot_swap_matches_the_state_whatever_the_imports() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (import "env" "step" (global $step i32))
+  (memory (export "memory") 1)
+  (global $count (export "count") (mut i32) (i32.const 0))
+  (func (export "tick")
+    global.get $count
+    global.get $step
+    i32.add
+    global.set $count
+    i32.const 0
+    global.get $count
+    i32.store))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let step = Global::new(&mut store, Value::I32(3));
+    let mut instance = Instance::new(
+        &mut store,
+        &module,
+        &imports! {
+            "env" => {
+                "step" => step,
+            },
+        },
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let tick = instance
+        .exports
+        .get_function("tick")
+        .map_err(|e| format!("{e:?}"))?;
+    tick.call(&mut store, &[]).map_err(|e| format!("{e:?}"))?;
+
+    // The new module imports no global, so its own globals and
+    // memories come first.
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (memory (export "memory") 1)
+  (global $count (export "count") (mut i32) (i32.const 0))
+  (func (export "tick")
+    global.get $count
+    i32.const 10
+    i32.add
+    global.set $count))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    assert!(instance.hot_swap_incompatibilities(&module).is_empty());
+    instance
+        .hot_swap(&mut store, &module, &imports! {})
+        .map_err(|e| format!("{e:?}"))?;
+
+    let count = instance
+        .exports
+        .get_global("count")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(count.get(&mut store), Value::I32(3));
+    let mut bytes = [0; 4];
+    instance
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?
+        .view(&store)
+        .read(0, &mut bytes)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(i32::from_le_bytes(bytes), 3);
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn hot_swap_rejects_incompatible_modules() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (global (mut i32) (i32.const 0))
+  (func (export "run") (param i32)))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let mut instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (global (mut i64) (i64.const 0))
+  (func (export "run")))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let incompatibilities = instance.hot_swap_incompatibilities(&module);
+    assert_eq!(incompatibilities.len(), 2);
+    assert!(matches!(
+        &incompatibilities[0],
+        HotSwapIncompatibility::Export { name, .. } if name == "run"
+    ));
+    assert!(matches!(
+        &incompatibilities[1],
+        HotSwapIncompatibility::Globals { .. }
+    ));
+    assert!(matches!(
+        instance.hot_swap(&mut store, &module, &imports! {}),
+        Err(HotSwapError::Incompatible(_))
+    ));
+
+    Ok(())
+}
@@ -275,3 +275,50 @@ fn calling_host_functions_with_negative_values_works() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(feature = "sys")]
+#[test]
+fn modules_share_the_artifact_of_the_same_binary() -> Result<(), String> {
+    let store = Store::default();
+    let wat = r#"(module $name (memory 1) (func (export "f")))"#;
+    let mut first = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let second = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(store.engine().artifact_count(), 1);
+
+    // The names of the modules stay their own.
+    assert!(first.set_name("first"));
+    assert_eq!(first.name(), Some("first"));
+    assert_eq!(second.name(), Some("name"));
+
+    let other = Module::new(&store, r#"(module)"#).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(store.engine().artifact_count(), 2);
+
+    drop((first, second, other));
+    assert_eq!(store.engine().artifact_count(), 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn function_debug_names() -> Result<(), String> {
+    let store = Store::default();
+    let wat = r#"(module
+(import "host" "func" (func $host_func (param i32)))
+(func $internal (export "run") (result i32) (i32.const 0))
+(func (export "anonymous"))
+)"#;
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+
+    let import = module.imports().functions().next().unwrap();
+    assert_eq!(import.debug_name(), Some("host_func"));
+    assert_eq!(import.ty().params(), &[Type::I32]);
+
+    let exports = module.exports().functions().collect::<Vec<_>>();
+    assert_eq!(exports[0].name(), "run");
+    assert_eq!(exports[0].debug_name(), Some("internal"));
+    assert_eq!(exports[0].ty().results(), &[Type::I32]);
+    assert_eq!(exports[1].debug_name(), None);
+
+    Ok(())
+}
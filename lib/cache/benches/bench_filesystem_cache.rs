@@ -86,9 +86,41 @@ pub fn load_cache_native(c: &mut Criterion) {
     });
 }
 
+pub fn load_cache_universal_by_path(c: &mut Criterion) {
+    let tmp_dir = TempDir::new().unwrap();
+    let mut fs_cache = FileSystemCache::new(tmp_dir.path()).unwrap();
+    let compiler = Singlepass::default();
+    let store = Store::new(compiler);
+    let path = std::path::Path::new("../../lib/c-api/examples/assets/qjs.wasm");
+    let module = Module::new(&store, std::fs::read(path).unwrap()).unwrap();
+    let key = fs_cache.key(&std::fs::read(path).unwrap());
+    fs_cache.store(key, &module).unwrap();
+    fs_cache.index(path, key).unwrap();
+
+    // What `wasmer run` does by default: read and hash the module to
+    // find it in the cache.
+    c.bench_function(
+        "read, hash and load universal module in filesystem cache",
+        |b| {
+            b.iter(|| unsafe {
+                let key = fs_cache.key(&std::fs::read(path).unwrap());
+                fs_cache.load(&store, key).unwrap()
+            })
+        },
+    );
+
+    // What `wasmer run --cache-strategy mmap` does.
+    c.bench_function("load universal module in filesystem cache by path", |b| {
+        b.iter(|| unsafe {
+            let key = fs_cache.indexed_key(path).unwrap();
+            fs_cache.load(&store, key).unwrap()
+        })
+    });
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(300);
-    targets = store_cache_universal, load_cache_universal, store_cache_native, load_cache_native
+    targets = store_cache_universal, load_cache_universal, load_cache_universal_by_path, store_cache_native, load_cache_native
 }
 criterion_main!(benches);
@@ -1,9 +1,13 @@
 #![cfg_attr(not(feature = "filesystem"), allow(unused))]
 use crate::cache::Cache;
 use crate::hash::Hash;
-use std::fs::{create_dir_all, File};
+use crate::key::{Blake3, CacheKeyStrategy};
+use filetime::FileTime;
+use std::fs::{self, create_dir_all, File};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 use wasmer::{DeserializeError, Module, SerializeError, Store};
 
 /// Representation of a directory that contains compiled wasm artifacts.
@@ -15,7 +19,7 @@ use wasmer::{DeserializeError, Module, SerializeError, Store};
 ///
 /// ```
 /// use wasmer::{DeserializeError, SerializeError};
-/// use wasmer_cache::{Cache, FileSystemCache, Hash};
+/// use wasmer_cache::{Cache, FileSystemCache};
 ///
 /// # use wasmer::{Module};
 /// fn store_module(module: &Module, bytes: &[u8]) -> Result<(), SerializeError> {
@@ -23,7 +27,7 @@ use wasmer::{DeserializeError, Module, SerializeError, Store};
 ///     let mut fs_cache = FileSystemCache::new("some/directory/goes/here")?;
 ///
 ///     // Compute a key for a given WebAssembly binary
-///     let key = Hash::generate(bytes);
+///     let key = fs_cache.key(bytes);
 ///
 ///     // Store a module into the cache given a key
 ///     fs_cache.store(key, module)?;
@@ -34,6 +38,21 @@ use wasmer::{DeserializeError, Module, SerializeError, Store};
 pub struct FileSystemCache {
     path: PathBuf,
     ext: Option<String>,
+    key_strategy: Box<dyn CacheKeyStrategy>,
+    max_size: Option<u64>,
+}
+
+/// A module stored in a [`FileSystemCache`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// The key of the module
+    pub key: Hash,
+    /// The file of the module
+    pub path: PathBuf,
+    /// The size of the file, in bytes
+    pub size: u64,
+    /// When the module was last stored or loaded
+    pub last_used: SystemTime,
 }
 
 #[cfg(feature = "filesystem")]
@@ -45,7 +64,12 @@ impl FileSystemCache {
             let metadata = path.metadata()?;
             if metadata.is_dir() {
                 if !metadata.permissions().readonly() {
-                    Ok(Self { path, ext: None })
+                    Ok(Self {
+                        path,
+                        ext: None,
+                        key_strategy: Box::new(Blake3),
+                        max_size: None,
+                    })
                 } else {
                     // This directory is readonly.
                     Err(io::Error::new(
@@ -72,7 +96,12 @@ impl FileSystemCache {
                     format!("failed to create cache directory: {}", path.display()),
                 ))
             } else {
-                Ok(Self { path, ext: None })
+                Ok(Self {
+                    path,
+                    ext: None,
+                    key_strategy: Box::new(Blake3),
+                    max_size: None,
+                })
             }
         }
     }
@@ -84,6 +113,138 @@ impl FileSystemCache {
     pub fn set_cache_extension(&mut self, ext: Option<impl ToString>) {
         self.ext = ext.map(|ext| ext.to_string());
     }
+
+    /// Set the strategy deriving the keys of the modules, used by
+    /// [`Self::key`]. Defaults to [`Blake3`].
+    ///
+    /// The modules stored with another strategy are not found
+    /// anymore, so this is best used with a dedicated directory.
+    pub fn set_key_strategy(&mut self, key_strategy: impl CacheKeyStrategy + 'static) {
+        self.key_strategy = Box::new(key_strategy);
+    }
+
+    /// Compute the key of the module made of `wasm_bytes`, to load
+    /// or store it in this cache.
+    pub fn key(&self, wasm_bytes: &[u8]) -> Hash {
+        self.key_strategy.key(wasm_bytes)
+    }
+
+    /// Set the maximum size of the modules of this cache, in bytes.
+    ///
+    /// When a module is stored beyond it, the least recently used
+    /// ones are removed, see [`Self::prune`]. Unlimited by default.
+    pub fn set_max_size(&mut self, max_size: Option<u64>) {
+        self.max_size = max_size;
+    }
+
+    /// The modules of this cache, the least recently used first.
+    pub fn entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let mut entries = vec![];
+        for dir_entry in fs::read_dir(&self.path)? {
+            let dir_entry = dir_entry?;
+            let metadata = dir_entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let file_name = dir_entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            let key = match &self.ext {
+                Some(ext) => file_name
+                    .strip_suffix(ext.as_str())
+                    .and_then(|name| name.strip_suffix('.')),
+                None => Some(file_name.as_ref()),
+            };
+            // Other files aren't modules of this cache.
+            if let Some(Ok(key)) = key.map(Hash::from_str) {
+                entries.push(CacheEntry {
+                    key,
+                    path: dir_entry.path(),
+                    size: metadata.len(),
+                    last_used: metadata.modified()?,
+                });
+            }
+        }
+        entries.sort_by_key(|entry| entry.last_used);
+        Ok(entries)
+    }
+
+    /// Remove the module of `key` from this cache.
+    pub fn remove(&self, key: Hash) -> io::Result<()> {
+        fs::remove_file(self.path.join(self.filename(key)))
+    }
+
+    /// Remove the least recently used modules until the others fit in
+    /// `max_size` bytes, and return the removed ones.
+    pub fn prune(&self, max_size: u64) -> io::Result<Vec<CacheEntry>> {
+        let entries = self.entries()?;
+        let mut size = entries.iter().map(|entry| entry.size).sum::<u64>();
+        let mut removed = vec![];
+        for entry in entries {
+            if size <= max_size {
+                break;
+            }
+            fs::remove_file(&entry.path)?;
+            size -= entry.size;
+            removed.push(entry);
+        }
+        Ok(removed)
+    }
+
+    /// The key of the module recorded for the file `path` by
+    /// [`Self::index`], if the file didn't change since.
+    ///
+    /// This avoids reading and hashing large modules to load them.
+    /// The index is shared by all the processes using this cache
+    /// directory.
+    pub fn indexed_key(&self, path: &Path) -> Option<Hash> {
+        let record = fs::read_to_string(self.index_path(path).ok()?).ok()?;
+        let (stamp, key) = record.rsplit_once(' ')?;
+        if stamp != file_stamp(path).ok()? {
+            return None;
+        }
+        Hash::from_str(key).ok()
+    }
+
+    /// Record that the module of the file `path` is stored with `key`,
+    /// see [`Self::indexed_key`].
+    pub fn index(&self, path: &Path, key: Hash) -> io::Result<()> {
+        let index_path = self.index_path(path)?;
+        create_dir_all(self.path.join("index"))?;
+        // Other processes only ever see complete records, as renaming
+        // is atomic.
+        let temporary_path = index_path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(
+            &temporary_path,
+            format!("{} {}", file_stamp(path)?, key.to_string()),
+        )?;
+        fs::rename(temporary_path, index_path)
+    }
+
+    /// The file recording the key of the module of `path`.
+    fn index_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = path.canonicalize()?;
+        let name = Hash::generate(path.to_string_lossy().as_bytes());
+        Ok(self.path.join("index").join(name.to_string()))
+    }
+
+    fn filename(&self, key: Hash) -> String {
+        if let Some(ref ext) = self.ext {
+            format!("{}.{}", key.to_string(), ext)
+        } else {
+            key.to_string()
+        }
+    }
+}
+
+/// What identifies the contents of the file `path`: its size and its
+/// modification time.
+fn file_stamp(path: &Path) -> io::Result<String> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!("{} {}", metadata.len(), modified.as_nanos()))
 }
 
 #[cfg(feature = "filesystem")]
@@ -92,27 +253,75 @@ impl Cache for FileSystemCache {
     type SerializeError = SerializeError;
 
     unsafe fn load(&self, store: &Store, key: Hash) -> Result<Module, Self::DeserializeError> {
-        let filename = if let Some(ref ext) = self.ext {
-            format!("{}.{}", key.to_string(), ext)
-        } else {
-            key.to_string()
-        };
-        let path = self.path.join(filename);
-        Module::deserialize_from_file(store, path)
+        let path = self.path.join(self.filename(key));
+        let module = Module::deserialize_from_file(store, &path)?;
+        // The modification time tells which modules were least
+        // recently used, it doesn't matter if it can't be updated.
+        let _ = filetime::set_file_mtime(&path, FileTime::now());
+        Ok(module)
     }
 
     fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
-        let filename = if let Some(ref ext) = self.ext {
-            format!("{}.{}", key.to_string(), ext)
-        } else {
-            key.to_string()
-        };
-        let path = self.path.join(filename);
+        let path = self.path.join(self.filename(key));
         let mut file = File::create(path)?;
 
         let buffer = module.serialize()?;
         file.write_all(&buffer)?;
 
+        if let Some(max_size) = self.max_size {
+            self.prune(max_size)?;
+        }
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_removes_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = FileSystemCache::new(dir.path()).unwrap();
+        cache.set_cache_extension(Some("wasmu"));
+        let keys = [Hash::new([1; 32]), Hash::new([2; 32]), Hash::new([3; 32])];
+        for (i, key) in keys.iter().enumerate() {
+            let path = dir.path().join(cache.filename(*key));
+            fs::write(&path, [0; 10]).unwrap();
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(i as i64 * 60, 0)).unwrap();
+        }
+        fs::write(dir.path().join("unrelated"), [0; 100]).unwrap();
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(
+            entries.iter().map(|entry| entry.key).collect::<Vec<_>>(),
+            keys
+        );
+
+        let removed = cache.prune(25).unwrap();
+        assert_eq!(
+            removed.iter().map(|entry| entry.key).collect::<Vec<_>>(),
+            [keys[0]]
+        );
+        cache.remove(keys[2]).unwrap();
+        assert_eq!(cache.entries().unwrap().len(), 1);
+        assert!(dir.path().join("unrelated").exists());
+    }
+
+    #[test]
+    fn index_records_unchanged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = FileSystemCache::new(dir.path().join("cache")).unwrap();
+        let module = dir.path().join("module.wasm");
+        fs::write(&module, b"\0asm").unwrap();
+        assert_eq!(cache.indexed_key(&module), None);
+
+        let key = Hash::new([1; 32]);
+        cache.index(&module, key).unwrap();
+        assert_eq!(cache.indexed_key(&module), Some(key));
+        assert!(cache.entries().unwrap().is_empty());
+
+        fs::write(&module, b"\0asm\x01").unwrap();
+        assert_eq!(cache.indexed_key(&module), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! The strategies deriving the [`Hash`] under which a module is
+//! cached.
+
+use crate::hash::Hash;
+
+/// Derives the key under which a WebAssembly module is stored in a
+/// cache, from the bytes of the module.
+///
+/// A custom strategy can mix other inputs in the key, such as
+/// feature flags or a tenant ID, so that the modules compiled for
+/// different configurations don't share a cache entry. Closures of
+/// type `Fn(&[u8]) -> Hash` implement this trait.
+///
+/// # Usage
+///
+/// ```
+/// use wasmer_cache::{CacheKeyStrategy, Hash};
+///
+/// struct PerTenant(String);
+///
+/// impl CacheKeyStrategy for PerTenant {
+///     fn key(&self, wasm_bytes: &[u8]) -> Hash {
+///         let mut bytes = self.0.as_bytes().to_vec();
+///         bytes.extend_from_slice(wasm_bytes);
+///         Hash::generate(&bytes)
+///     }
+/// }
+///
+/// let wasm = b"\0asm\x01\0\0\0";
+/// assert_ne!(
+///     PerTenant("a".into()).key(wasm),
+///     PerTenant("b".into()).key(wasm),
+/// );
+/// ```
+pub trait CacheKeyStrategy: Send + Sync {
+    /// Computes the key of the module made of `wasm_bytes`.
+    fn key(&self, wasm_bytes: &[u8]) -> Hash;
+}
+
+impl<F> CacheKeyStrategy for F
+where
+    F: Fn(&[u8]) -> Hash + Send + Sync,
+{
+    fn key(&self, wasm_bytes: &[u8]) -> Hash {
+        self(wasm_bytes)
+    }
+}
+
+/// Hashes the module with BLAKE3, like [`Hash::generate`]. This is
+/// the default strategy.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Blake3;
+
+impl CacheKeyStrategy for Blake3 {
+    fn key(&self, wasm_bytes: &[u8]) -> Hash {
+        Hash::generate(wasm_bytes)
+    }
+}
+
+/// Hashes the module with XXH3, which is noticeably faster than
+/// BLAKE3 on very large modules.
+///
+/// XXH3 is not a cryptographic hash: modules can be crafted to
+/// collide, so this strategy must not be used with a cache that is
+/// shared with untrusted parties.
+#[cfg(feature = "xxhash")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Xxh3;
+
+#[cfg(feature = "xxhash")]
+impl CacheKeyStrategy for Xxh3 {
+    fn key(&self, wasm_bytes: &[u8]) -> Hash {
+        use xxhash_rust::xxh3::{xxh3_128, xxh3_128_with_seed};
+
+        // Two differently seeded 128-bit hashes fill the 32 bytes of
+        // the key.
+        let mut bytes = [0; 32];
+        bytes[..16].copy_from_slice(&xxh3_128(wasm_bytes).to_le_bytes());
+        bytes[16..].copy_from_slice(&xxh3_128_with_seed(wasm_bytes, 1).to_le_bytes());
+
+        Hash::new(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blake3_is_hash_generate() {
+        let wasm = b"\0asm\x01\0\0\0";
+        assert_eq!(Blake3.key(wasm), Hash::generate(wasm));
+    }
+
+    #[test]
+    fn closures_are_strategies() {
+        let strategy = |_: &[u8]| Hash::new([7; 32]);
+        assert_eq!(strategy.key(b""), Hash::new([7; 32]));
+    }
+
+    #[cfg(feature = "xxhash")]
+    #[test]
+    fn xxh3_is_deterministic() {
+        let wasm = b"\0asm\x01\0\0\0";
+        assert_eq!(Xxh3.key(wasm), Xxh3.key(wasm));
+        assert_ne!(Xxh3.key(wasm), Xxh3.key(b"\0asm\x01\0\0\0\0"));
+        assert_ne!(Xxh3.key(wasm), Blake3.key(wasm));
+    }
+}
@@ -21,11 +21,15 @@
 mod cache;
 mod filesystem;
 mod hash;
+mod key;
 
 pub use crate::cache::Cache;
 #[cfg(feature = "filesystem")]
-pub use crate::filesystem::FileSystemCache;
+pub use crate::filesystem::{CacheEntry, FileSystemCache};
 pub use crate::hash::Hash;
+#[cfg(feature = "xxhash")]
+pub use crate::key::Xxh3;
+pub use crate::key::{Blake3, CacheKeyStrategy};
 
 // We re-export those for convinience of users
 pub use wasmer::{DeserializeError, SerializeError};
@@ -2,15 +2,23 @@
 
 #[cfg(target_os = "linux")]
 use crate::commands::Binfmt;
+#[cfg(all(feature = "compiler", feature = "wasi"))]
+use crate::commands::Bundle;
 #[cfg(feature = "compiler")]
 use crate::commands::Compile;
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
 use crate::commands::CreateExe;
 #[cfg(feature = "static-artifact-create")]
 use crate::commands::CreateObj;
+#[cfg(feature = "sign")]
+use crate::commands::Sign;
+#[cfg(feature = "fuse")]
+use crate::commands::Vfs;
 #[cfg(feature = "wast")]
 use crate::commands::Wast;
-use crate::commands::{Cache, Config, Inspect, Run, SelfUpdate, Validate};
+use crate::commands::{
+    Cache, Completions, Config, Explain, Inspect, Man, Repl, Run, SelfUpdate, Transform, Validate,
+};
 use crate::error::PrettyError;
 use anyhow::Result;
 
@@ -36,7 +44,7 @@ use clap::{ErrorKind, Parser};
     )
 )]
 /// The options for the wasmer Command Line Interface
-enum WasmerCLIOptions {
+pub(crate) enum WasmerCLIOptions {
     /// Run a WebAssembly file. Formats accepted: wasm, wat
     #[clap(name = "run")]
     Run(Run),
@@ -54,6 +62,23 @@ enum WasmerCLIOptions {
     #[clap(name = "compile")]
     Compile(Compile),
 
+    /// Make a standalone executable running a WebAssembly module, with
+    /// its WASI options and default arguments
+    ///
+    /// The executable is a copy of wasmer, or of the `--runtime`, with
+    /// the compiled module appended to it. It doesn't need a C
+    /// toolchain, unlike `create-exe`.
+    ///
+    /// Example usage:
+    ///
+    /// ```text
+    /// $ wasmer bundle qjs.wasm -o qjs --dir . --env HOME=/ -- --std
+    /// $ ./qjs script.js # runs `qjs --std script.js`
+    /// ```
+    #[cfg(all(feature = "compiler", feature = "wasi"))]
+    #[clap(name = "bundle", verbatim_doc_comment)]
+    Bundle(Bundle),
+
     /// Compile a WebAssembly binary into a native executable
     ///
     /// To use, you need to set the `WASMER_DIR` environment variable
@@ -130,10 +155,38 @@ enum WasmerCLIOptions {
     #[clap(name = "self-update")]
     SelfUpdate(SelfUpdate),
 
+    /// Explain a diagnostic code of an error, e.g. `WSM1201`, or list
+    /// all of them
+    #[clap(name = "explain")]
+    Explain(Explain),
+
     /// Inspect a WebAssembly file
     #[clap(name = "inspect")]
     Inspect(Inspect),
 
+    /// Explore a WebAssembly module interactively: call its functions,
+    /// read its globals and memories
+    #[clap(name = "repl")]
+    Repl(Repl),
+
+    /// Sign a WebAssembly file or artifact, or generate a key pair to
+    /// sign with, so `--verify-key` accepts it
+    ///
+    /// Example usage:
+    ///
+    /// ```text
+    /// $ wasmer sign --generate-key plugins.key
+    /// $ wasmer sign plugin.wasm --key plugins.key
+    /// $ wasmer run plugin.wasm --verify-key plugins.key.pub
+    /// ```
+    #[cfg(feature = "sign")]
+    #[clap(name = "sign", verbatim_doc_comment)]
+    Sign(Sign),
+
+    /// Rename the imports and exports of a WebAssembly binary
+    #[clap(name = "transform")]
+    Transform(Transform),
+
     /// Run spec testsuite
     #[cfg(feature = "wast")]
     #[clap(name = "wast")]
@@ -143,6 +196,27 @@ enum WasmerCLIOptions {
     #[cfg(target_os = "linux")]
     #[clap(name = "binfmt")]
     Binfmt(Binfmt),
+
+    /// Mount the file system of a guest on the host
+    #[cfg(feature = "fuse")]
+    #[clap(subcommand, name = "vfs")]
+    Vfs(Vfs),
+
+    /// Print the completions of the wasmer commands for a shell
+    ///
+    /// Example usage:
+    ///
+    /// ```text
+    /// $ wasmer completions bash > /usr/share/bash-completion/completions/wasmer
+    /// $ wasmer completions zsh > "${fpath[1]}/_wasmer"
+    /// ```
+    #[clap(name = "completions", verbatim_doc_comment)]
+    Completions(Completions),
+
+    /// Print the manual page of wasmer, or write the pages of all its
+    /// subcommands to a directory
+    #[clap(name = "man")]
+    Man(Man),
 }
 
 impl WasmerCLIOptions {
@@ -152,6 +226,8 @@ impl WasmerCLIOptions {
             Self::SelfUpdate(options) => options.execute(),
             Self::Cache(cache) => cache.execute(),
             Self::Validate(validate) => validate.execute(),
+            #[cfg(all(feature = "compiler", feature = "wasi"))]
+            Self::Bundle(bundle) => bundle.execute(),
             #[cfg(feature = "compiler")]
             Self::Compile(compile) => compile.execute(),
             #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
@@ -159,11 +235,20 @@ impl WasmerCLIOptions {
             #[cfg(feature = "static-artifact-create")]
             Self::CreateObj(create_obj) => create_obj.execute(),
             Self::Config(config) => config.execute(),
+            Self::Explain(explain) => explain.execute(),
             Self::Inspect(inspect) => inspect.execute(),
+            Self::Repl(repl) => repl.execute(),
+            #[cfg(feature = "sign")]
+            Self::Sign(sign) => sign.execute(),
+            Self::Transform(transform) => transform.execute(),
             #[cfg(feature = "wast")]
             Self::Wast(wast) => wast.execute(),
             #[cfg(target_os = "linux")]
             Self::Binfmt(binfmt) => binfmt.execute(),
+            #[cfg(feature = "fuse")]
+            Self::Vfs(vfs) => vfs.execute(),
+            Self::Completions(completions) => completions.execute(),
+            Self::Man(man) => man.execute(),
         }
     }
 }
@@ -182,14 +267,23 @@ pub fn wasmer_main() {
     // In case we've been run as wasmer-binfmt-interpreter myfile.wasm args,
     // we assume that we're registered via binfmt_misc
     let args = std::env::args().collect::<Vec<_>>();
+
+    // Executables made by `wasmer bundle` run their module with all
+    // the arguments.
+    #[cfg(feature = "wasi")]
+    if let Some(result) = crate::commands::run_embedded(args.get(1..).unwrap_or_default()) {
+        PrettyError::report(result);
+    }
+
     let binpath = args.get(0).map(|s| s.as_ref()).unwrap_or("");
     let command = args.get(1);
     let options = if cfg!(target_os = "linux") && binpath.ends_with("wasmer-binfmt-interpreter") {
         WasmerCLIOptions::Run(Run::from_binfmt_args())
     } else {
         match command.unwrap_or(&"".to_string()).as_ref() {
-            "cache" | "compile" | "config" | "create-exe" | "help" | "inspect" | "run"
-            | "self-update" | "validate" | "wast" | "binfmt" => WasmerCLIOptions::parse(),
+            "bundle" | "cache" | "compile" | "completions" | "config" | "create-exe"
+            | "explain" | "help" | "inspect" | "man" | "repl" | "run" | "self-update" | "sign"
+            | "transform" | "validate" | "vfs" | "wast" | "binfmt" => WasmerCLIOptions::parse(),
             _ => {
                 WasmerCLIOptions::try_parse_from(args.iter()).unwrap_or_else(|e| {
                     match e.kind() {
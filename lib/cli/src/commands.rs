@@ -1,32 +1,54 @@
 //! The commands available in the Wasmer binary.
 #[cfg(target_os = "linux")]
 mod binfmt;
+#[cfg(feature = "wasi")]
+mod bundle;
 mod cache;
 #[cfg(feature = "compiler")]
 mod compile;
+mod completions;
 mod config;
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
 mod create_exe;
 #[cfg(feature = "static-artifact-create")]
 mod create_obj;
+mod explain;
 mod inspect;
+mod man;
+mod repl;
 mod run;
 mod self_update;
+#[cfg(feature = "sign")]
+mod sign;
+mod transform;
 mod validate;
+#[cfg(feature = "fuse")]
+mod vfs;
 #[cfg(feature = "wast")]
 mod wast;
 
 #[cfg(target_os = "linux")]
 pub use binfmt::*;
+#[cfg(feature = "wasi")]
+pub use bundle::run_embedded;
+#[cfg(all(feature = "compiler", feature = "wasi"))]
+pub use bundle::Bundle;
 #[cfg(feature = "compiler")]
 pub use compile::*;
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
 pub use create_exe::*;
 #[cfg(feature = "static-artifact-create")]
 pub use create_obj::*;
+#[cfg(feature = "sign")]
+pub use sign::*;
+#[cfg(feature = "fuse")]
+pub use vfs::*;
 #[cfg(feature = "wast")]
 pub use wast::*;
-pub use {cache::*, config::*, inspect::*, run::*, self_update::*, validate::*};
+pub use {
+    cache::*, completions::*, config::*, explain::*, inspect::*, man::*, repl::*, run::*,
+    self_update::*, transform::*, validate::*,
+};
 
 /// The kind of object format to emit.
 #[derive(Debug, Copy, Clone, clap::Parser)]
@@ -1,5 +1,5 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueHint};
 use std::env;
 use std::fs;
 use std::io::Write;
@@ -26,7 +26,7 @@ enum Action {
 pub struct Binfmt {
     // Might be better to traverse the mount list
     /// Mount point of binfmt_misc fs
-    #[clap(long, default_value = "/proc/sys/fs/binfmt_misc/")]
+    #[clap(long, default_value = "/proc/sys/fs/binfmt_misc/", value_hint = ValueHint::DirPath)]
     binfmt_misc: PathBuf,
 
     #[clap(subcommand)]
@@ -0,0 +1,341 @@
+//! Standalone executables made by `wasmer bundle`: a copy of the
+//! wasmer runtime followed by a manifest, with the WASI options and the
+//! default arguments, and the serialized artifact of a module.
+//!
+//! ```text
+//! [runtime][manifest][artifact][manifest length][artifact length][MAGIC]
+//! ```
+//!
+//! The lengths are little-endian `u64`s. When the runtime finds this
+//! trailer at the end of its own executable, it runs the module
+//! instead of parsing its arguments as wasmer's.
+
+use crate::commands::Run;
+#[cfg(feature = "compiler")]
+use crate::store::StoreOptions;
+#[cfg(feature = "compiler")]
+use crate::utils::{parse_envvar, parse_mapdir};
+use anyhow::{Context, Result};
+use clap::Parser;
+#[cfg(feature = "compiler")]
+use clap::ValueHint;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+#[cfg(feature = "compiler")]
+use std::path::PathBuf;
+use toml::value::{Table, Value};
+#[cfg(feature = "compiler")]
+use wasmer::*;
+
+/// The last bytes of a bundle.
+const MAGIC: &[u8; 8] = b"WASMBNDL";
+
+/// The length of the trailer: the lengths of the manifest and the
+/// artifact, and `MAGIC`.
+const TRAILER_LEN: u64 = 24;
+
+#[cfg(feature = "compiler")]
+#[derive(Debug, Parser)]
+/// The options for the `wasmer bundle` subcommand
+pub struct Bundle {
+    /// The module to bundle, a WebAssembly file or an artifact made by
+    /// `wasmer compile`
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
+    path: PathBuf,
+
+    /// Output file
+    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str), value_hint = ValueHint::FilePath)]
+    output: PathBuf,
+
+    /// The wasmer executable the bundle runs the module with, e.g. a
+    /// `wasmer-headless` or a wasmer built for the `--target`. The
+    /// current one by default
+    #[clap(long = "runtime", parse(from_os_str), value_hint = ValueHint::FilePath)]
+    runtime: Option<PathBuf>,
+
+    /// Compilation Target triple
+    #[clap(long = "target")]
+    target_triple: Option<Triple>,
+
+    /// The CPU features of the `--target`, comma separated, e.g.
+    /// `avx2,bmi2`
+    #[clap(short = 'm', long = "cpu-features", use_value_delimiter = true)]
+    cpu_features: Vec<CpuFeature>,
+
+    #[clap(flatten)]
+    store: StoreOptions,
+
+    /// A directory the module can access
+    #[clap(long = "dir", name = "DIR", value_hint = ValueHint::DirPath)]
+    pre_opened_directories: Vec<PathBuf>,
+
+    /// A directory the module can access under another name
+    #[clap(
+        long = "mapdir",
+        name = "GUEST_DIR:HOST_DIR",
+        parse(try_from_str = parse_mapdir),
+    )]
+    mapped_dirs: Vec<(String, PathBuf)>,
+
+    /// An environment variable of the module
+    #[clap(
+        long = "env",
+        name = "KEY=VALUE",
+        parse(try_from_str = parse_envvar),
+    )]
+    env_vars: Vec<(String, String)>,
+
+    /// The arguments the module receives before the ones given to the
+    /// bundle
+    #[clap(value_name = "ARGS", last = true)]
+    args: Vec<String>,
+}
+
+#[cfg(feature = "compiler")]
+impl Bundle {
+    /// Runs logic for the `bundle` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to bundle `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let contents = std::fs::read(&self.path)?;
+        let artifact = if wasmer_compiler::Artifact::is_deserializable(&contents) {
+            contents
+        } else {
+            let target = self
+                .target_triple
+                .as_ref()
+                .map(|target_triple| {
+                    let mut features = self
+                        .cpu_features
+                        .clone()
+                        .into_iter()
+                        .fold(CpuFeature::set(), |a, b| a | b);
+                    // Cranelift requires SSE2, as in `wasmer compile`
+                    if target_triple.architecture == Architecture::X86_64 {
+                        features |= CpuFeature::SSE2;
+                    }
+                    Target::new(target_triple.clone(), features)
+                })
+                .unwrap_or_default();
+            let (store, compiler_type) = self.store.get_store_for_target(target.clone())?;
+            println!("Compiler: {}", compiler_type.to_string());
+            println!("Target: {}", target.triple());
+            Module::new(&store, contents)?.serialize()?.to_vec()
+        };
+
+        let runtime_path = match &self.runtime {
+            Some(runtime) => runtime.clone(),
+            None => std::env::current_exe()?,
+        };
+        let runtime = std::fs::read(&runtime_path)
+            .with_context(|| format!("failed to read `{}`", runtime_path.display()))?;
+        if read_trailer(&mut std::io::Cursor::new(&runtime))?.is_some() {
+            bail!(
+                "`{}` is already a bundle, use a wasmer executable as `--runtime`",
+                runtime_path.display()
+            );
+        }
+
+        let name = self
+            .path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let mut options = vec![];
+        for dir in &self.pre_opened_directories {
+            options.push("--dir".to_string());
+            options.push(dir.to_string_lossy().to_string());
+        }
+        for (guest, host) in &self.mapped_dirs {
+            options.push("--mapdir".to_string());
+            options.push(format!("{}::{}", guest, host.display()));
+        }
+        for (key, value) in &self.env_vars {
+            options.push("--env".to_string());
+            options.push(format!("{}={}", key, value));
+        }
+        let manifest = Manifest {
+            name,
+            options,
+            args: self.args.clone(),
+        }
+        .to_toml()?;
+
+        let mut bundle = runtime;
+        bundle.extend_from_slice(manifest.as_bytes());
+        bundle.extend_from_slice(&artifact);
+        bundle.extend_from_slice(&(manifest.len() as u64).to_le_bytes());
+        bundle.extend_from_slice(&(artifact.len() as u64).to_le_bytes());
+        bundle.extend_from_slice(MAGIC);
+        std::fs::write(&self.output, bundle)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.output, std::fs::Permissions::from_mode(0o755))?;
+        }
+
+        eprintln!(
+            "✔ Executable bundled successfully to `{}`.",
+            self.output.display(),
+        );
+        Ok(())
+    }
+}
+
+/// What a bundle runs its module with, written as TOML.
+#[derive(Debug, PartialEq)]
+struct Manifest {
+    /// The name of the module, its first argument
+    name: String,
+    /// The options of `wasmer run`: `--dir`, `--mapdir` and `--env`
+    options: Vec<String>,
+    /// The default arguments of the module
+    args: Vec<String>,
+}
+
+impl Manifest {
+    #[cfg(any(test, feature = "compiler"))]
+    fn to_toml(&self) -> Result<String> {
+        let strings =
+            |strings: &[String]| Value::Array(strings.iter().cloned().map(Value::String).collect());
+        let mut table = Table::new();
+        table.insert("name".to_string(), Value::String(self.name.clone()));
+        table.insert("options".to_string(), strings(&self.options));
+        table.insert("args".to_string(), strings(&self.args));
+        Ok(toml::to_string(&table)?)
+    }
+
+    fn from_toml(contents: &str) -> Result<Self> {
+        let table = contents.parse::<Table>()?;
+        let strings = |key: &str| -> Result<Vec<String>> {
+            table
+                .get(key)
+                .and_then(Value::as_array)
+                .ok_or_else(|| anyhow!("the manifest has no `{}`", key))?
+                .iter()
+                .map(|value| {
+                    value
+                        .as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| anyhow!("`{}` must only contain strings", key))
+                })
+                .collect()
+        };
+        Ok(Self {
+            name: table
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("the manifest has no `name`"))?
+                .to_string(),
+            options: strings("options")?,
+            args: strings("args")?,
+        })
+    }
+}
+
+/// Reads the lengths of the manifest and the artifact at the end of
+/// `file`, if it is a bundle.
+fn read_trailer(file: &mut (impl Read + Seek)) -> Result<Option<(u64, u64)>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    if len < TRAILER_LEN {
+        return Ok(None);
+    }
+    let mut trailer = [0; TRAILER_LEN as usize];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(&mut trailer)?;
+    if &trailer[16..] != MAGIC {
+        return Ok(None);
+    }
+    let mut length = [0; 8];
+    length.copy_from_slice(&trailer[..8]);
+    let manifest_len = u64::from_le_bytes(length);
+    length.copy_from_slice(&trailer[8..16]);
+    let artifact_len = u64::from_le_bytes(length);
+    if manifest_len + artifact_len > len - TRAILER_LEN {
+        bail!("the bundle is truncated");
+    }
+    Ok(Some((manifest_len, artifact_len)))
+}
+
+/// The module embedded in the executable `path`, if it is a bundle.
+fn read_embedded(path: &Path) -> Result<Option<(Manifest, Vec<u8>)>> {
+    let mut file = File::open(path)?;
+    let (manifest_len, artifact_len) = match read_trailer(&mut file)? {
+        Some(lengths) => lengths,
+        None => return Ok(None),
+    };
+    file.seek(SeekFrom::End(
+        -((TRAILER_LEN + manifest_len + artifact_len) as i64),
+    ))?;
+    let mut manifest = vec![0; manifest_len as usize];
+    file.read_exact(&mut manifest)?;
+    let mut artifact = vec![0; artifact_len as usize];
+    file.read_exact(&mut artifact)?;
+    let manifest = Manifest::from_toml(std::str::from_utf8(&manifest)?)?;
+    Ok(Some((manifest, artifact)))
+}
+
+/// Runs the module embedded in the current executable, with `args`, if
+/// it is a bundle. Returns `None` otherwise, e.g. for wasmer itself.
+pub fn run_embedded(args: &[String]) -> Option<Result<()>> {
+    // An executable which can't be read is not a bundle.
+    let path = std::env::current_exe().ok()?;
+    let (manifest, artifact) =
+        match read_embedded(&path).context("failed to read the bundled module") {
+            Ok(embedded) => embedded?,
+            Err(err) => return Some(Err(err)),
+        };
+    let run = Run::try_parse_from(
+        vec![
+            "wasmer".to_string(),
+            path.to_string_lossy().to_string(),
+            "--command-name".to_string(),
+            manifest.name,
+        ]
+        .into_iter()
+        .chain(manifest.options)
+        .chain(Some("--".to_string()))
+        .chain(manifest.args)
+        .chain(args.iter().cloned()),
+    );
+    Some(match run {
+        Ok(run) => run.with_module_contents(artifact).execute(),
+        Err(err) => Err(err.into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_manifest_and_trailer() {
+        let manifest = Manifest {
+            name: "tool".to_string(),
+            options: vec!["--env".to_string(), "KEY=a \"quoted\" value".to_string()],
+            args: vec!["--verbose".to_string()],
+        };
+        let toml = manifest.to_toml().unwrap();
+        assert_eq!(Manifest::from_toml(&toml).unwrap(), manifest);
+
+        let mut bundle = b"runtime".to_vec();
+        bundle.extend_from_slice(toml.as_bytes());
+        bundle.extend_from_slice(b"artifact");
+        bundle.extend_from_slice(&(toml.len() as u64).to_le_bytes());
+        bundle.extend_from_slice(&8u64.to_le_bytes());
+        bundle.extend_from_slice(MAGIC);
+        assert_eq!(
+            read_trailer(&mut Cursor::new(&bundle)).unwrap(),
+            Some((toml.len() as u64, 8))
+        );
+        assert_eq!(read_trailer(&mut Cursor::new(b"runtime")).unwrap(), None);
+        assert!(read_trailer(&mut Cursor::new(&bundle[7..])).is_ok());
+        assert!(read_trailer(&mut Cursor::new(&bundle[8 + toml.len()..])).is_err());
+    }
+}
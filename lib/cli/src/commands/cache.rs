@@ -1,7 +1,19 @@
 use crate::common::get_cache_dir;
+#[cfg(feature = "cache")]
+use crate::common::{get_cache, get_downloads_dir};
+#[cfg(feature = "cache")]
+use crate::config::WasmerConfig;
 use anyhow::{Context, Result};
+#[cfg(feature = "cache")]
+use bytesize::ByteSize;
 use clap::Parser;
 use std::fs;
+#[cfg(feature = "cache")]
+use std::str::FromStr;
+#[cfg(feature = "cache")]
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "cache")]
+use wasmer_cache::{FileSystemCache, Hash};
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer cache` subcommand
@@ -13,6 +25,37 @@ pub enum Cache {
     /// Display the location of the cache
     #[clap(name = "dir")]
     Dir,
+
+    /// List the cached modules, the least recently used first
+    #[cfg(feature = "cache")]
+    #[clap(name = "ls")]
+    Ls,
+
+    /// Remove modules from the cache, given their keys or their
+    /// WebAssembly files
+    #[cfg(feature = "cache")]
+    #[clap(name = "rm")]
+    Rm {
+        /// The keys, as listed by `wasmer cache ls`, or the files
+        #[clap(name = "KEY or FILE", required = true)]
+        modules: Vec<String>,
+    },
+
+    /// Remove the least recently used modules of each compiler beyond
+    /// a size
+    #[cfg(feature = "cache")]
+    #[clap(name = "prune")]
+    Prune {
+        /// The size to keep, e.g. `512 MiB`, the `cache-max-size`
+        /// setting by default
+        #[clap(long = "max-size")]
+        max_size: Option<ByteSize>,
+    },
+
+    /// Display the number and the size of the cached modules
+    #[cfg(feature = "cache")]
+    #[clap(name = "stats")]
+    Stats,
 }
 
 impl Cache {
@@ -25,11 +68,23 @@ impl Cache {
             Cache::Dir => {
                 self.dir()?;
             }
+            #[cfg(feature = "cache")]
+            Cache::Ls => self.ls().context("failed to list the cached modules")?,
+            #[cfg(feature = "cache")]
+            Cache::Rm { modules } => self
+                .rm(modules)
+                .context("failed to remove modules from the cache")?,
+            #[cfg(feature = "cache")]
+            Cache::Prune { max_size } => self
+                .prune(*max_size)
+                .context("failed to prune wasmer cache")?,
+            #[cfg(feature = "cache")]
+            Cache::Stats => self.stats().context("failed to read wasmer cache")?,
         }
         Ok(())
     }
     fn clean(&self) -> Result<()> {
-        let cache_dir = get_cache_dir();
+        let cache_dir = get_cache_dir()?;
         if cache_dir.exists() {
             fs::remove_dir_all(cache_dir.clone())?;
         }
@@ -38,7 +93,145 @@ impl Cache {
         Ok(())
     }
     fn dir(&self) -> Result<()> {
-        println!("{}", get_cache_dir().to_string_lossy());
+        println!("{}", get_cache_dir()?.to_string_lossy());
+        Ok(())
+    }
+    #[cfg(feature = "cache")]
+    fn ls(&self) -> Result<()> {
+        let now = SystemTime::now();
+        for (compiler, cache) in caches()? {
+            for entry in cache.entries()? {
+                println!(
+                    "{}  {:<10}  {:>10}  {}",
+                    entry.key.to_string(),
+                    compiler,
+                    ByteSize(entry.size).to_string(),
+                    format_age(now.duration_since(entry.last_used).unwrap_or_default())
+                );
+            }
+        }
+        Ok(())
+    }
+    #[cfg(feature = "cache")]
+    fn rm(&self, modules: &[String]) -> Result<()> {
+        let caches = caches()?;
+        for module in modules {
+            let mut removed = false;
+            for (compiler, cache) in &caches {
+                let key = if std::path::Path::new(module).is_file() {
+                    cache.key(&fs::read(module)?)
+                } else {
+                    Hash::from_str(module).with_context(|| {
+                        format!("`{}` is neither a key nor a WebAssembly file", module)
+                    })?
+                };
+                if cache.remove(key).is_ok() {
+                    eprintln!("Removed `{}` compiled by {}.", module, compiler);
+                    removed = true;
+                }
+            }
+            if !removed {
+                bail!("`{}` is not in the cache", module);
+            }
+        }
+        Ok(())
+    }
+    #[cfg(feature = "cache")]
+    fn prune(&self, max_size: Option<ByteSize>) -> Result<()> {
+        let max_size = match max_size {
+            Some(max_size) => max_size.as_u64(),
+            None => WasmerConfig::load()?.cache_max_size().ok_or_else(|| {
+                anyhow!("no size to keep, pass `--max-size` or set `cache-max-size`")
+            })?,
+        };
+        let (mut count, mut size) = (0, 0);
+        for (_compiler, cache) in caches()? {
+            for entry in cache.prune(max_size)? {
+                count += 1;
+                size += entry.size;
+            }
+        }
+        eprintln!(
+            "Removed {} modules, freeing {}.",
+            count,
+            ByteSize(size).to_string()
+        );
+        Ok(())
+    }
+    #[cfg(feature = "cache")]
+    fn stats(&self) -> Result<()> {
+        println!("Directory: {}", get_cache_dir()?.display());
+        match WasmerConfig::load()?.cache_max_size() {
+            Some(max_size) => println!("Maximum size: {} per compiler", ByteSize(max_size)),
+            None => println!("Maximum size: unlimited"),
+        }
+        let (mut count, mut size) = (0, 0);
+        for (compiler, cache) in caches()? {
+            let entries = cache.entries()?;
+            let compiler_size = entries.iter().map(|entry| entry.size).sum::<u64>();
+            println!(
+                "{}: {} modules, {}",
+                compiler,
+                entries.len(),
+                ByteSize(compiler_size).to_string()
+            );
+            count += entries.len();
+            size += compiler_size;
+        }
+        println!("Total: {} modules, {}", count, ByteSize(size).to_string());
         Ok(())
     }
 }
+
+/// The caches of the compilers which have modules in the cache
+/// directory.
+#[cfg(feature = "cache")]
+fn caches() -> Result<Vec<(String, FileSystemCache)>> {
+    let cache_dir = get_cache_dir()?;
+    if !cache_dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut caches = vec![];
+    let downloads_dir = get_downloads_dir()?;
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        // The downloaded modules aren't compiled.
+        if entry.file_type()?.is_dir() && entry.path() != downloads_dir {
+            let compiler = entry.file_name().to_string_lossy().to_string();
+            caches.push((compiler.clone(), get_cache(&compiler)?));
+        }
+    }
+    caches.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(caches)
+}
+
+/// Formats how long ago a module was used, e.g. `3 hours ago`.
+#[cfg(feature = "cache")]
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    let (count, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+#[cfg(all(test, feature = "cache"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(5)), "just now");
+        assert_eq!(format_age(Duration::from_secs(60)), "1 minute ago");
+        assert_eq!(format_age(Duration::from_secs(3 * 3600 + 5)), "3 hours ago");
+        assert_eq!(format_age(Duration::from_secs(40 * 86400)), "40 days ago");
+    }
+}
@@ -1,7 +1,8 @@
+use crate::signature::VerifyOptions;
 use crate::store::StoreOptions;
 use crate::warning;
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueHint};
 use std::path::PathBuf;
 use wasmer::*;
 
@@ -9,11 +10,11 @@ use wasmer::*;
 /// The options for the `wasmer compile` subcommand
 pub struct Compile {
     /// Input file
-    #[clap(name = "FILE", parse(from_os_str))]
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
     path: PathBuf,
 
     /// Output file
-    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str))]
+    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str), value_hint = ValueHint::FilePath)]
     output: PathBuf,
 
     /// Compilation Target triple
@@ -23,8 +24,13 @@ pub struct Compile {
     #[clap(flatten)]
     store: StoreOptions,
 
-    #[clap(short = 'm')]
+    /// The CPU features of the `--target`, comma separated, e.g.
+    /// `avx2,bmi2`
+    #[clap(short = 'm', long = "cpu-features", use_value_delimiter = true)]
     cpu_features: Vec<CpuFeature>,
+
+    #[clap(flatten)]
+    verify: VerifyOptions,
 }
 
 impl Compile {
@@ -72,8 +78,21 @@ impl Compile {
         }
         println!("Compiler: {}", compiler_type.to_string());
         println!("Target: {}", target.triple());
+        println!(
+            "CPU features: {}",
+            target
+                .cpu_features()
+                .iter()
+                .map(|feature| feature.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
 
-        let module = Module::from_file(&store, &self.path)?;
+        let contents = std::fs::read(&self.path)?;
+        self.verify.verify(&self.path, &contents)?;
+        let mut module = Module::new(&store, contents)?;
+        // Named like `Module::from_file` does, for the stack traces.
+        module.set_name(&self.path.canonicalize()?.to_string_lossy());
         module.serialize_to_file(&self.output)?;
         eprintln!(
             "✔ File compiled successfully to `{}`.",
@@ -0,0 +1,23 @@
+use crate::cli::WasmerCLIOptions;
+use anyhow::Result;
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use std::io;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer completions` subcommand
+pub struct Completions {
+    /// The shell to generate the completions for
+    #[clap(name = "SHELL", arg_enum)]
+    shell: Shell,
+}
+
+impl Completions {
+    /// Runs logic for the `completions` subcommand
+    pub fn execute(&self) -> Result<()> {
+        let mut command = WasmerCLIOptions::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(self.shell, &mut command, name, &mut io::stdout());
+        Ok(())
+    }
+}
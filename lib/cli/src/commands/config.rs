@@ -1,3 +1,4 @@
+use crate::config::{format_value, ConfigLayer, WasmerConfig, KEYS};
 use crate::VERSION;
 use anyhow::{Context, Result};
 use clap::Parser;
@@ -35,6 +36,46 @@ pub struct Config {
     /// and linking a program to Wasmer, using the `pkg-config` format.
     #[clap(long)]
     pkg_config: bool,
+
+    #[clap(subcommand)]
+    settings: Option<Settings>,
+}
+
+#[derive(Debug, Parser)]
+/// Read and write the layered Wasmer settings
+enum Settings {
+    /// Print the value of a setting
+    #[clap(name = "get")]
+    Get {
+        /// The setting to read
+        key: String,
+
+        /// Also print where the value comes from
+        #[clap(long)]
+        show_origin: bool,
+    },
+
+    /// Change the value of a setting, in the user configuration file by default
+    #[clap(name = "set")]
+    Set {
+        /// The setting to change
+        key: String,
+
+        /// The new value
+        value: String,
+
+        /// Write to the `.wasmer.toml` file of the current project
+        #[clap(long, conflicts_with = "system")]
+        project: bool,
+
+        /// Write to the system-wide configuration file
+        #[clap(long)]
+        system: bool,
+    },
+
+    /// List all the settings and where they come from
+    #[clap(name = "list")]
+    List,
 }
 
 impl Config {
@@ -44,6 +85,10 @@ impl Config {
             .context("failed to retrieve the wasmer config".to_string())
     }
     fn inner_execute(&self) -> Result<()> {
+        if let Some(settings) = &self.settings {
+            return settings.execute();
+        }
+
         let key = "WASMER_DIR";
         let wasmer_dir = env::var(key)
             .or_else(|e| {
@@ -100,3 +145,52 @@ impl Config {
         Ok(())
     }
 }
+
+impl Settings {
+    fn execute(&self) -> Result<()> {
+        match self {
+            Self::Get { key, show_origin } => {
+                if !KEYS.iter().any(|(k, _, _)| k == key) {
+                    bail!("unknown setting `{}`", key);
+                }
+                let config = WasmerConfig::load()?;
+                if let Some((value, layer)) = config.get(key) {
+                    if *show_origin {
+                        println!("{}\t{}", layer, format_value(value));
+                    } else {
+                        println!("{}", format_value(value));
+                    }
+                }
+            }
+            Self::Set {
+                key,
+                value,
+                project,
+                system,
+            } => {
+                let layer = if *project {
+                    ConfigLayer::Project
+                } else if *system {
+                    ConfigLayer::System
+                } else {
+                    ConfigLayer::User
+                };
+                let path = WasmerConfig::set(layer, key, value)?;
+                eprintln!("Updated `{}` in `{}`.", key, path.display());
+            }
+            Self::List => {
+                let config = WasmerConfig::load()?;
+                for (key, var, description) in KEYS {
+                    match config.get(key) {
+                        Some((value, layer)) => {
+                            println!("{} = {} ({})", key, format_value(value), layer)
+                        }
+                        None => println!("# {} is not set", key),
+                    }
+                    println!("#   {} (env: {})", description, var);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,12 @@
 //! Create a standalone native executable for a given Wasm file.
 
 use super::ObjectFormat;
+use crate::diagnostic;
+use crate::diagnostic::Code;
+use crate::fetch::FetchOptions;
 use crate::store::CompilerOptions;
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueHint};
 use std::env;
 use std::fs;
 use std::fs::File;
@@ -46,11 +49,11 @@ struct CrossCompileSetup {
 /// The options for the `wasmer create-exe` subcommand
 pub struct CreateExe {
     /// Input file
-    #[clap(name = "FILE", parse(from_os_str))]
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
     path: PathBuf,
 
     /// Output file
-    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str))]
+    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str), value_hint = ValueHint::FilePath)]
     output: PathBuf,
 
     /// Compilation Target triple
@@ -69,15 +72,15 @@ pub struct CreateExe {
 
     // Cross-compile with `zig`
     /// Cross-compilation library path.
-    #[clap(long = "library-path")]
+    #[clap(long = "library-path", value_hint = ValueHint::FilePath)]
     library_path: Option<PathBuf>,
 
     /// Cross-compilation tarball library path.
-    #[clap(long = "tarball")]
+    #[clap(long = "tarball", value_hint = ValueHint::FilePath)]
     tarball: Option<PathBuf>,
 
     /// Specify `zig` binary path
-    #[clap(long = "zig-binary-path")]
+    #[clap(long = "zig-binary-path", value_hint = ValueHint::ExecutablePath)]
     zig_binary_path: Option<PathBuf>,
 
     /// Object format options
@@ -93,7 +96,12 @@ pub struct CreateExe {
     ///
     /// If given, the input `PATH` is assumed to be an object created with `wasmer create-obj` and
     /// this is its accompanying header file.
-    #[clap(name = "HEADER", long = "header", verbatim_doc_comment)]
+    #[clap(
+        name = "HEADER",
+        long = "header",
+        value_hint = ValueHint::FilePath,
+        verbatim_doc_comment
+    )]
     header: Option<PathBuf>,
 
     #[clap(short = 'm')]
@@ -106,6 +114,9 @@ pub struct CreateExe {
 
     #[clap(flatten)]
     compiler: CompilerOptions,
+
+    #[clap(flatten)]
+    fetch: FetchOptions,
 }
 
 impl CreateExe {
@@ -240,15 +251,22 @@ impl CreateExe {
                     } else {
                         #[cfg(feature = "http")]
                         {
-                            let release = http_fetch::get_latest_release()?;
-                            let tarball = http_fetch::download_release(release, target.clone())?;
+                            let policy = self.fetch.policy()?;
+                            // Only the cached tarballs are used in offline mode.
+                            let release = if policy.offline {
+                                serde_json::Value::Null
+                            } else {
+                                http_fetch::get_latest_release(&policy)?
+                            };
+                            let tarball =
+                                http_fetch::download_release(release, target.clone(), &policy)?;
                             let files = untar(tarball)?;
                             files.clone().into_iter().find(|f| f.contains(libwasmer_headless_path)).or_else(||
                             files.into_iter().find(|f| f.contains(libwasmer_path))).ok_or_else(|| {
                                 anyhow!("Could not find libwasmer for {} target in the fetched release from Github: you can download it manually and specify its path with the --cross-compilation-library-path LIBRARY_PATH flag.", target)})?
                         }
                         #[cfg(not(feature = "http"))]
-                        return Err(anyhow!("This wasmer binary isn't compiled with an HTTP request library (feature flag `http`). To cross-compile, specify the path of the non-native libwasmer or release tarball with the --library-path LIBRARY_PATH or --tarball TARBALL_PATH flag."));
+                        return Err(diagnostic!(Code::FeatureDisabled, "This wasmer binary isn't compiled with an HTTP request library (feature flag `http`). To cross-compile, specify the path of the non-native libwasmer or release tarball with the --library-path LIBRARY_PATH or --tarball TARBALL_PATH flag."));
                     };
                     filename.into()
                 }
@@ -357,7 +375,7 @@ impl CreateExe {
                 }
                 #[cfg(not(feature = "static-artifact-create"))]
                 ObjectFormat::Symbols => {
-                    return Err(anyhow!("This version of wasmer-cli hasn't been compiled with static artifact support. You need to enable the `static-artifact-create` feature during compilation."));
+                    return Err(diagnostic!(Code::FeatureDisabled, "This version of wasmer-cli hasn't been compiled with static artifact support. You need to enable the `static-artifact-create` feature during compilation."));
                 }
                 #[cfg(feature = "static-artifact-create")]
                 ObjectFormat::Symbols => {
@@ -812,32 +830,40 @@ impl LinkCode {
 
 #[cfg(feature = "http")]
 mod http_fetch {
-    use anyhow::{anyhow, Context, Result};
+    use crate::fetch::{FetchError, FetchPolicy};
+    use anyhow::{anyhow, Result};
     use http_req::{
-        request::Request,
         response::{Response, StatusCode},
         uri::Uri,
     };
     use std::convert::TryFrom;
 
-    pub fn get_latest_release() -> Result<serde_json::Value> {
-        let mut writer = Vec::new();
+    pub fn get_latest_release(policy: &FetchPolicy) -> Result<serde_json::Value> {
         let uri = Uri::try_from("https://api.github.com/repos/wasmerio/wasmer/releases").unwrap();
 
-        let response = Request::new(&uri)
-            .header("User-Agent", "wasmer")
-            .header("Accept", "application/vnd.github.v3+json")
-            .timeout(Some(std::time::Duration::new(30, 0)))
-            .send(&mut writer)
-            .map_err(anyhow::Error::new)
-            .context("Could not lookup wasmer repository on Github.")?;
+        let writer = policy.fetch("the latest release", || {
+            let mut writer = Vec::new();
+            let response = policy
+                .request(&uri)
+                .header("User-Agent", "wasmer")
+                .header("Accept", "application/vnd.github.v3+json")
+                .send(&mut writer)
+                .map_err(|error| {
+                    FetchError::send(error).context("Could not lookup wasmer repository on Github.")
+                })?;
+
+            if response.status_code() != StatusCode::new(200) {
+                return Err(FetchError::status(
+                    response.status_code(),
+                    anyhow!(
+                        "Github API replied with non-200 status code: {}",
+                        response.status_code()
+                    ),
+                ));
+            }
 
-        if response.status_code() != StatusCode::new(200) {
-            return Err(anyhow!(
-                "Github API replied with non-200 status code: {}",
-                response.status_code()
-            ));
-        }
+            Ok(writer)
+        })?;
 
         let v: std::result::Result<serde_json::Value, _> = serde_json::from_reader(&*writer);
         let mut response = v.map_err(anyhow::Error::new)?;
@@ -860,6 +886,7 @@ mod http_fetch {
     pub fn download_release(
         mut release: serde_json::Value,
         target_triple: wasmer::Triple,
+        policy: &FetchPolicy,
     ) -> Result<std::path::PathBuf> {
         let check_arch = |name: &str| -> bool {
             match target_triple.architecture {
@@ -923,6 +950,9 @@ mod http_fetch {
                 Err(_ioerr) => {}
             }
         }
+        if policy.offline {
+            return Err(diagnostic!(Code::Offline, "No release tarball is cached, and none can be downloaded in offline mode: specify the path of the tarball with the --tarball TARBALL_PATH flag."));
+        }
         if let Some(assets) = release["assets"].as_array_mut() {
             assets.retain(|a| {
                 if let Some(name) = a["name"].as_str() {
@@ -967,31 +997,43 @@ mod http_fetch {
                     .last()
                     .unwrap_or("output")
                     .to_string();
-                let mut file = std::fs::File::create(&filename)?;
                 println!("Downloading {} to {}", browser_download_url, &filename);
+                let policy = policy.clone();
+                let path = filename.clone();
                 let download_thread: std::thread::JoinHandle<Result<Response, anyhow::Error>> =
                     std::thread::spawn(move || {
                         let uri = Uri::try_from(browser_download_url.as_str())?;
-                        let mut response = Request::new(&uri)
-                            .header("User-Agent", "wasmer")
-                            .send(&mut file)
-                            .map_err(anyhow::Error::new)
-                            .context("Could not lookup wasmer artifact on Github.")?;
-                        if response.status_code() == StatusCode::new(302) {
-                            let redirect_uri =
-                                Uri::try_from(response.headers().get("Location").unwrap().as_str())
-                                    .unwrap();
-                            response = Request::new(&redirect_uri)
+                        policy.fetch("the release tarball", || {
+                            let mut file = std::fs::File::create(&path)
+                                .map_err(|error| FetchError::Permanent(error.into()))?;
+                            let mut response = policy
+                                .request(&uri)
                                 .header("User-Agent", "wasmer")
                                 .send(&mut file)
-                                .map_err(anyhow::Error::new)
-                                .context("Could not lookup wasmer artifact on Github.")?;
-                        }
-                        Ok(response)
+                                .map_err(|error| {
+                                    FetchError::send(error)
+                                        .context("Could not lookup wasmer artifact on Github.")
+                                })?;
+                            if response.status_code() == StatusCode::new(302) {
+                                let redirect_uri = Uri::try_from(
+                                    response.headers().get("Location").unwrap().as_str(),
+                                )
+                                .unwrap();
+                                response = policy
+                                    .request(&redirect_uri)
+                                    .header("User-Agent", "wasmer")
+                                    .send(&mut file)
+                                    .map_err(|error| {
+                                        FetchError::send(error)
+                                            .context("Could not lookup wasmer artifact on Github.")
+                                    })?;
+                            }
+                            Ok(response)
+                        })
                     });
                 let _response = download_thread
                     .join()
-                    .expect("Could not join downloading thread");
+                    .expect("Could not join downloading thread")?;
                 match super::get_libwasmer_cache_path() {
                     Ok(mut cache_path) => {
                         let _ = std::fs::create_dir_all(&cache_path);
@@ -3,7 +3,7 @@
 use super::ObjectFormat;
 use crate::{commands::PrefixerFn, store::CompilerOptions};
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueHint};
 use std::env;
 use std::fs;
 use std::fs::File;
@@ -19,18 +19,19 @@ const WASMER_SERIALIZED_HEADER: &[u8] = include_bytes!("wasmer_deserialize_modul
 /// The options for the `wasmer create-exe` subcommand
 pub struct CreateObj {
     /// Input file
-    #[clap(name = "FILE", parse(from_os_str))]
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
     path: PathBuf,
 
     /// Output file
-    #[clap(name = "OUTPUT_PATH", short = 'o', parse(from_os_str))]
+    #[clap(name = "OUTPUT_PATH", short = 'o', parse(from_os_str), value_hint = ValueHint::FilePath)]
     output: PathBuf,
 
     /// Header output file
     #[clap(
         name = "OUTPUT_HEADER_PATH",
         long = "output-header-path",
-        parse(from_os_str)
+        parse(from_os_str),
+        value_hint = ValueHint::FilePath
     )]
     header_output: Option<PathBuf>,
 
@@ -0,0 +1,66 @@
+use crate::diagnostic::{json_string, Code};
+use anyhow::Result;
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer explain` subcommand
+pub struct Explain {
+    /// The diagnostic code to explain, e.g. `WSM1201`; all of them are
+    /// listed when omitted
+    #[clap(name = "CODE")]
+    code: Option<Code>,
+
+    /// Print the catalog as JSON, one object per code
+    #[clap(long)]
+    json: bool,
+}
+
+impl Explain {
+    /// Runs logic for the `explain` subcommand
+    pub fn execute(&self) -> Result<()> {
+        match self.code {
+            Some(code) if self.json => println!("{}", Self::json(code)),
+            Some(code) => {
+                println!("{}: {}\n", code, code.title());
+                println!("{}", textwrap(code.explanation(), 72));
+            }
+            None if self.json => {
+                for code in Code::ALL {
+                    println!("{}", Self::json(*code));
+                }
+            }
+            None => {
+                for code in Code::ALL {
+                    println!("{}  {}", code, code.title());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn json(code: Code) -> String {
+        format!(
+            "{{\"code\":{},\"title\":{},\"explanation\":{}}}",
+            json_string(&code.to_string()),
+            json_string(code.title()),
+            json_string(code.explanation())
+        )
+    }
+}
+
+/// Wraps `text` at `width` columns, between words.
+fn textwrap(text: &str, width: usize) -> String {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines.join("\n")
+}
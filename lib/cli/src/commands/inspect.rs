@@ -1,21 +1,53 @@
+use crate::diagnostic::json_string;
 use crate::store::StoreOptions;
 use anyhow::{Context, Result};
 use bytesize::ByteSize;
-use clap::Parser;
+use clap::{Parser, ValueHint};
 use std::path::PathBuf;
+use std::str::FromStr;
 use wasmer::*;
+use wasmer_types::FunctionIndex;
+
+mod sections;
+
+use sections::{CustomContents, CustomSection, Offset, Sections, Segment, SegmentKind};
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer validate` subcommand
 pub struct Inspect {
     /// File to validate as WebAssembly
-    #[clap(name = "FILE", parse(from_os_str))]
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
     path: PathBuf,
 
+    /// The format of the output: `human`, or `json` for tools
+    #[clap(long = "format", default_value = "human")]
+    format: InspectFormat,
+
     #[clap(flatten)]
     store: StoreOptions,
 }
 
+/// The format of the output of `wasmer inspect`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InspectFormat {
+    /// A summary for humans.
+    Human,
+    /// A JSON object with all the metadata of the module.
+    Json,
+}
+
+impl FromStr for InspectFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err("must be one of two options: `human` or `json`."),
+        }
+    }
+}
+
 impl Inspect {
     /// Runs logic for the `validate` subcommand
     pub fn execute(&self) -> Result<()> {
@@ -27,43 +59,373 @@ impl Inspect {
         let module_contents = std::fs::read(&self.path)?;
         let iswasm = is_wasm(&module_contents);
         let module_len = module_contents.len();
+        #[cfg(feature = "wat")]
+        let module_contents = wat2wasm(&module_contents)?.into_owned();
+        let sections = Sections::parse(&module_contents)?;
         let module = Module::new(&store, module_contents)?;
-        println!("Type: {}", if !iswasm { "wat" } else { "wasm" });
-        println!("Size: {}", ByteSize(module_len as _));
-        println!("Imports:");
-        println!("  Functions:");
-        for f in module.imports().functions() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        match self.format {
+            InspectFormat::Human => {
+                println!("Type: {}", if !iswasm { "wat" } else { "wasm" });
+                println!("Size: {}", ByteSize(module_len as _));
+                print_human(&module, &sections);
+            }
+            InspectFormat::Json => println!(
+                "{}",
+                json(
+                    &module,
+                    &sections,
+                    if !iswasm { "wat" } else { "wasm" },
+                    module_len
+                )
+            ),
         }
-        println!("  Memories:");
-        for f in module.imports().memories() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
-        }
-        println!("  Tables:");
-        for f in module.imports().tables() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        Ok(())
+    }
+}
+
+fn print_human(module: &Module, sections: &Sections) {
+    println!("Imports:");
+    println!("  Functions:");
+    for f in module.imports().functions() {
+        match f.debug_name() {
+            Some(debug_name) => println!(
+                "    \"{}\".\"{}\" ({}): {}",
+                f.module(),
+                f.name(),
+                debug_name,
+                f.ty()
+            ),
+            None => println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty()),
         }
-        println!("  Globals:");
-        for f in module.imports().globals() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+    }
+    println!("  Memories:");
+    for f in module.imports().memories() {
+        println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+    }
+    println!("  Tables:");
+    for f in module.imports().tables() {
+        println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+    }
+    println!("  Globals:");
+    for f in module.imports().globals() {
+        println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+    }
+    println!("Exports:");
+    println!("  Functions:");
+    for f in module.exports().functions() {
+        match f.debug_name() {
+            Some(debug_name) if debug_name != f.name() => {
+                println!("    \"{}\" ({}): {}", f.name(), debug_name, f.ty())
+            }
+            _ => println!("    \"{}\": {}", f.name(), f.ty()),
         }
-        println!("Exports:");
-        println!("  Functions:");
-        for f in module.exports().functions() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+    println!("  Memories:");
+    for f in module.exports().memories() {
+        println!("    \"{}\": {}", f.name(), f.ty());
+    }
+    println!("  Tables:");
+    for f in module.exports().tables() {
+        println!("    \"{}\": {}", f.name(), f.ty());
+    }
+    println!("  Globals:");
+    for f in module.exports().globals() {
+        println!("    \"{}\": {}", f.name(), f.ty());
+    }
+
+    let info = module.info();
+    println!("Definitions:");
+    println!("  Memories:");
+    for (index, ty) in info.memories.iter().skip(info.num_imported_memories) {
+        println!("    {}: {}", index.as_u32(), ty);
+    }
+    println!("  Tables:");
+    for (index, ty) in info.tables.iter().skip(info.num_imported_tables) {
+        println!("    {}: {}", index.as_u32(), ty);
+    }
+    println!("  Globals:");
+    for (index, ty) in info.globals.iter().skip(info.num_imported_globals) {
+        println!("    {}: {}", index.as_u32(), ty);
+    }
+    if let Some(start) = sections.start {
+        println!("Start function: {}", function_name(module, start));
+    }
+    println!("Data segments:");
+    for segment in &sections.data {
+        println!(
+            "  {} bytes, {}",
+            segment.size,
+            human_segment_kind(segment, "memory")
+        );
+    }
+    println!("Element segments:");
+    for segment in &sections.elements {
+        println!(
+            "  {} items, {}",
+            segment.size,
+            human_segment_kind(segment, "table")
+        );
+    }
+    println!("Custom sections:");
+    for section in &sections.custom {
+        println!("  \"{}\": {}", section.name, ByteSize(section.size as _));
+        match &section.contents {
+            Some(CustomContents::Names { module, functions }) => {
+                if let Some(module) = module {
+                    println!("    module: {}", module);
+                }
+                println!("    functions named: {}", functions);
+            }
+            Some(CustomContents::Producers(fields)) => {
+                for (field, tools) in fields {
+                    let tools = tools
+                        .iter()
+                        .map(|(tool, version)| format!("{} {}", tool, version).trim().to_string())
+                        .collect::<Vec<_>>();
+                    println!("    {}: {}", field, tools.join(", "));
+                }
+            }
+            Some(CustomContents::TargetFeatures(features)) => {
+                let features = features
+                    .iter()
+                    .map(|(prefix, feature)| format!("{}{}", prefix, feature))
+                    .collect::<Vec<_>>();
+                println!("    {}", features.join(" "));
+            }
+            None => {}
         }
-        println!("  Memories:");
-        for f in module.exports().memories() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+}
+
+/// The function `index`, with its name if it has one.
+fn function_name(module: &Module, index: u32) -> String {
+    match module
+        .info()
+        .function_names
+        .get(&FunctionIndex::from_u32(index))
+    {
+        Some(name) => format!("{} ({})", index, name),
+        None => index.to_string(),
+    }
+}
+
+fn human_segment_kind(segment: &Segment, target: &str) -> String {
+    match &segment.kind {
+        SegmentKind::Active { index, offset } => {
+            let offset = match offset {
+                Offset::Const(offset) => format!("{:#x}", offset),
+                Offset::Global(global) => format!("global {}", global),
+                Offset::Unsupported => "an unsupported expression".to_string(),
+            };
+            format!("active in {} {} at {}", target, index, offset)
         }
-        println!("  Tables:");
-        for f in module.exports().tables() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        SegmentKind::Passive => "passive".to_string(),
+        SegmentKind::Declared => "declared".to_string(),
+    }
+}
+
+/// The metadata of `module` as a JSON object.
+fn json(module: &Module, sections: &Sections, kind: &str, size: usize) -> String {
+    let imports = module
+        .imports()
+        .map(|import| {
+            format!(
+                "{{\"module\":{},\"name\":{},{}}}",
+                json_string(import.module()),
+                json_string(import.name()),
+                json_extern_type(import.ty())
+            )
+        })
+        .collect::<Vec<_>>();
+    let exports = module
+        .exports()
+        .map(|export| {
+            format!(
+                "{{\"name\":{},{}}}",
+                json_string(export.name()),
+                json_extern_type(export.ty())
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let info = module.info();
+    let memories = info
+        .memories
+        .values()
+        .skip(info.num_imported_memories)
+        .map(json_memory_type)
+        .collect::<Vec<_>>();
+    let tables = info
+        .tables
+        .values()
+        .skip(info.num_imported_tables)
+        .map(json_table_type)
+        .collect::<Vec<_>>();
+    let globals = info
+        .globals
+        .values()
+        .skip(info.num_imported_globals)
+        .map(json_global_type)
+        .collect::<Vec<_>>();
+
+    let start = match sections.start {
+        Some(start) => start.to_string(),
+        None => "null".to_string(),
+    };
+    let data = sections
+        .data
+        .iter()
+        .map(|segment| json_segment(segment, "memory"))
+        .collect::<Vec<_>>();
+    let elements = sections
+        .elements
+        .iter()
+        .map(|segment| json_segment(segment, "table"))
+        .collect::<Vec<_>>();
+    let custom_sections = sections
+        .custom
+        .iter()
+        .map(json_custom_section)
+        .collect::<Vec<_>>();
+
+    format!(
+        "{{\"type\":{},\"size\":{},\"name\":{},\"imports\":[{}],\"exports\":[{}],\
+         \"memories\":[{}],\"tables\":[{}],\"globals\":[{}],\"start\":{},\
+         \"data\":[{}],\"elements\":[{}],\"custom_sections\":[{}]}}",
+        json_string(kind),
+        size,
+        module.name().map_or("null".to_string(), json_string),
+        imports.join(","),
+        exports.join(","),
+        memories.join(","),
+        tables.join(","),
+        globals.join(","),
+        start,
+        data.join(","),
+        elements.join(","),
+        custom_sections.join(",")
+    )
+}
+
+/// The kind and the type of an import or an export, as JSON members.
+fn json_extern_type(ty: &ExternType) -> String {
+    match ty {
+        ExternType::Function(ty) => {
+            format!("\"kind\":\"function\",\"type\":{}", json_function_type(ty))
         }
-        println!("  Globals:");
-        for f in module.exports().globals() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        ExternType::Memory(ty) => format!("\"kind\":\"memory\",\"type\":{}", json_memory_type(ty)),
+        ExternType::Table(ty) => format!("\"kind\":\"table\",\"type\":{}", json_table_type(ty)),
+        ExternType::Global(ty) => format!("\"kind\":\"global\",\"type\":{}", json_global_type(ty)),
+    }
+}
+
+/// A value type the way the text format writes it, e.g. `i32`.
+fn json_value_type(ty: &Type) -> String {
+    json_string(&ty.to_string().to_lowercase())
+}
+
+fn json_function_type(ty: &FunctionType) -> String {
+    let params = ty.params().iter().map(json_value_type).collect::<Vec<_>>();
+    let results = ty.results().iter().map(json_value_type).collect::<Vec<_>>();
+    format!(
+        "{{\"params\":[{}],\"results\":[{}]}}",
+        params.join(","),
+        results.join(",")
+    )
+}
+
+fn json_memory_type(ty: &MemoryType) -> String {
+    format!(
+        "{{\"minimum\":{},\"maximum\":{},\"shared\":{}}}",
+        ty.minimum.0,
+        ty.maximum
+            .map_or("null".to_string(), |maximum| maximum.0.to_string()),
+        ty.shared
+    )
+}
+
+fn json_table_type(ty: &TableType) -> String {
+    format!(
+        "{{\"element\":{},\"minimum\":{},\"maximum\":{}}}",
+        json_value_type(&ty.ty),
+        ty.minimum,
+        ty.maximum
+            .map_or("null".to_string(), |maximum| maximum.to_string())
+    )
+}
+
+fn json_global_type(ty: &GlobalType) -> String {
+    format!(
+        "{{\"type\":{},\"mutable\":{}}}",
+        json_value_type(&ty.ty),
+        ty.mutability.is_mutable()
+    )
+}
+
+/// A segment, whose target is a `memory` or a `table`.
+fn json_segment(segment: &Segment, target: &str) -> String {
+    match &segment.kind {
+        SegmentKind::Active { index, offset } => {
+            let offset = match offset {
+                Offset::Const(offset) => offset.to_string(),
+                Offset::Global(global) => format!("{{\"global\":{}}}", global),
+                Offset::Unsupported => "null".to_string(),
+            };
+            format!(
+                "{{\"mode\":\"active\",\"{}\":{},\"offset\":{},\"size\":{}}}",
+                target, index, offset, segment.size
+            )
         }
-        Ok(())
+        SegmentKind::Passive => format!("{{\"mode\":\"passive\",\"size\":{}}}", segment.size),
+        SegmentKind::Declared => format!("{{\"mode\":\"declared\",\"size\":{}}}", segment.size),
     }
 }
+
+fn json_custom_section(section: &CustomSection) -> String {
+    let contents = match &section.contents {
+        Some(CustomContents::Names { module, functions }) => format!(
+            ",\"module\":{},\"functions\":{}",
+            module.as_deref().map_or("null".to_string(), json_string),
+            functions
+        ),
+        Some(CustomContents::Producers(fields)) => {
+            let fields = fields
+                .iter()
+                .map(|(field, tools)| {
+                    let tools = tools
+                        .iter()
+                        .map(|(tool, version)| {
+                            format!(
+                                "{{\"name\":{},\"version\":{}}}",
+                                json_string(tool),
+                                json_string(version)
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    format!("{}:[{}]", json_string(field), tools.join(","))
+                })
+                .collect::<Vec<_>>();
+            format!(",\"fields\":{{{}}}", fields.join(","))
+        }
+        Some(CustomContents::TargetFeatures(features)) => {
+            let features = features
+                .iter()
+                .map(|(prefix, feature)| {
+                    format!(
+                        "{{\"prefix\":{},\"name\":{}}}",
+                        json_string(&prefix.to_string()),
+                        json_string(feature)
+                    )
+                })
+                .collect::<Vec<_>>();
+            format!(",\"features\":[{}]", features.join(","))
+        }
+        None => String::new(),
+    };
+    format!(
+        "{{\"name\":{},\"size\":{}{}}}",
+        json_string(&section.name),
+        section.size,
+        contents
+    )
+}
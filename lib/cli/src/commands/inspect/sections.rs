@@ -0,0 +1,288 @@
+//! The parts of a module `wasmer inspect` reads from its binary
+//! directly, as `Module` doesn't keep them: its start function, its
+//! data and element segments, and its custom sections.
+
+use anyhow::Result;
+use wasmer_compiler::wasmparser::{
+    BinaryReader, DataKind, ElementKind, InitExpr, Name, NameSectionReader, Operator, Parser,
+    Payload,
+};
+
+/// The sections of a module which aren't described by `Module`.
+#[derive(Debug, Default)]
+pub struct Sections {
+    /// The index of the start function
+    pub start: Option<u32>,
+    /// The data segments, whose sizes are in bytes
+    pub data: Vec<Segment>,
+    /// The element segments, whose sizes are in items
+    pub elements: Vec<Segment>,
+    /// The custom sections, in the order of the module
+    pub custom: Vec<CustomSection>,
+}
+
+/// A data or element segment.
+#[derive(Debug, PartialEq)]
+pub struct Segment {
+    /// How the segment is initialized
+    pub kind: SegmentKind,
+    /// The number of bytes or items of the segment
+    pub size: u32,
+}
+
+/// How a segment is initialized.
+#[derive(Debug, PartialEq)]
+pub enum SegmentKind {
+    /// Copied at instantiation in the memory or table `index`
+    Active {
+        /// The index of the memory or table
+        index: u32,
+        /// Where the segment is copied
+        offset: Offset,
+    },
+    /// Copied by `memory.init` or `table.init`
+    Passive,
+    /// Only declaring the functions referenced by the module
+    Declared,
+}
+
+/// The offset of an active segment.
+#[derive(Debug, PartialEq)]
+pub enum Offset {
+    /// A constant offset
+    Const(u32),
+    /// The value of a global
+    Global(u32),
+    /// An expression wasmer can't instantiate
+    Unsupported,
+}
+
+/// A custom section, with its contents when they are known.
+#[derive(Debug, PartialEq)]
+pub struct CustomSection {
+    /// The name of the section
+    pub name: String,
+    /// The size of the section, in bytes
+    pub size: usize,
+    /// The contents of a `name`, `producers` or `target_features`
+    /// section
+    pub contents: Option<CustomContents>,
+}
+
+/// The contents of the custom sections of the tool conventions.
+#[derive(Debug, PartialEq)]
+pub enum CustomContents {
+    /// The `name` section: the name of the module, and the number of
+    /// named functions
+    Names {
+        /// The name of the module
+        module: Option<String>,
+        /// The number of functions named
+        functions: u32,
+    },
+    /// The `producers` section: the fields, e.g. `language` or
+    /// `processed-by`, with their tools and versions
+    Producers(Vec<(String, Vec<(String, String)>)>),
+    /// The `target_features` section: the features with their prefix,
+    /// `+` if used, `-` if disallowed and `=` if required
+    TargetFeatures(Vec<(char, String)>),
+}
+
+impl Sections {
+    /// Reads the sections of the module binary `wasm`.
+    pub fn parse(wasm: &[u8]) -> Result<Self> {
+        let mut sections = Self::default();
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::StartSection { func, .. } => sections.start = Some(func),
+                Payload::DataSection(reader) => {
+                    for data in reader {
+                        let data = data?;
+                        let kind = match data.kind {
+                            DataKind::Active {
+                                memory_index,
+                                init_expr,
+                            } => SegmentKind::Active {
+                                index: memory_index,
+                                offset: offset(&init_expr),
+                            },
+                            DataKind::Passive => SegmentKind::Passive,
+                        };
+                        sections.data.push(Segment {
+                            kind,
+                            size: data.data.len() as u32,
+                        });
+                    }
+                }
+                Payload::ElementSection(reader) => {
+                    for element in reader {
+                        let element = element?;
+                        let kind = match element.kind {
+                            ElementKind::Active {
+                                table_index,
+                                init_expr,
+                            } => SegmentKind::Active {
+                                index: table_index,
+                                offset: offset(&init_expr),
+                            },
+                            ElementKind::Passive => SegmentKind::Passive,
+                            ElementKind::Declared => SegmentKind::Declared,
+                        };
+                        sections.elements.push(Segment {
+                            kind,
+                            size: element.items.get_items_reader()?.get_count(),
+                        });
+                    }
+                }
+                Payload::CustomSection {
+                    name,
+                    data,
+                    data_offset,
+                    ..
+                } => sections.custom.push(CustomSection {
+                    name: name.to_string(),
+                    size: data.len(),
+                    // A malformed section is still listed, without its
+                    // contents, as wasmer ignores it.
+                    contents: match name {
+                        "name" => names(data, data_offset).ok(),
+                        "producers" => producers(data).ok(),
+                        "target_features" => target_features(data).ok(),
+                        _ => None,
+                    },
+                }),
+                _ => {}
+            }
+        }
+        Ok(sections)
+    }
+}
+
+/// The offset computed by `init_expr`.
+fn offset(init_expr: &InitExpr) -> Offset {
+    match init_expr.get_binary_reader().read_operator() {
+        Ok(Operator::I32Const { value }) => Offset::Const(value as u32),
+        Ok(Operator::GlobalGet { global_index }) => Offset::Global(global_index),
+        _ => Offset::Unsupported,
+    }
+}
+
+fn names(data: &[u8], offset: usize) -> Result<CustomContents> {
+    let mut reader = NameSectionReader::new(data, offset)?;
+    let mut module = None;
+    let mut functions = 0;
+    while let Ok(subsection) = reader.read() {
+        match subsection {
+            Name::Module(name) => module = Some(name.get_name()?.to_string()),
+            Name::Function(names) => functions = names.get_map()?.get_count(),
+            _ => {}
+        }
+    }
+    Ok(CustomContents::Names { module, functions })
+}
+
+fn producers(data: &[u8]) -> Result<CustomContents> {
+    let mut reader = BinaryReader::new(data);
+    let mut fields = vec![];
+    for _ in 0..reader.read_var_u32()? {
+        let field = reader.read_string()?.to_string();
+        let mut tools = vec![];
+        for _ in 0..reader.read_var_u32()? {
+            let tool = reader.read_string()?.to_string();
+            tools.push((tool, reader.read_string()?.to_string()));
+        }
+        fields.push((field, tools));
+    }
+    Ok(CustomContents::Producers(fields))
+}
+
+fn target_features(data: &[u8]) -> Result<CustomContents> {
+    let mut reader = BinaryReader::new(data);
+    let mut features = vec![];
+    for _ in 0..reader.read_var_u32()? {
+        let prefix = reader.read_u8()? as u8 as char;
+        features.push((prefix, reader.read_string()?.to_string()));
+    }
+    Ok(CustomContents::TargetFeatures(features))
+}
+
+#[cfg(all(test, feature = "wat"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sections() {
+        let wasm = wasmer::wat2wasm(
+            r#"(module $example
+                (memory 1)
+                (table 2 funcref)
+                (func $f)
+                (start $f)
+                (data (i32.const 16) "hello")
+                (data "passive")
+                (elem (i32.const 0) $f $f)
+                (@custom "producers" "\01\08language\01\04Rust\06" "1.61.0")
+                (@custom "target_features" "\02\2b\0bmutable-globals\2d\04simd"))"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let sections = Sections::parse(&wasm).unwrap();
+
+        assert_eq!(sections.start, Some(0));
+        assert_eq!(
+            sections.data,
+            [
+                Segment {
+                    kind: SegmentKind::Active {
+                        index: 0,
+                        offset: Offset::Const(16)
+                    },
+                    size: 5
+                },
+                Segment {
+                    kind: SegmentKind::Passive,
+                    size: 7
+                }
+            ]
+        );
+        assert_eq!(
+            sections.elements,
+            [Segment {
+                kind: SegmentKind::Active {
+                    index: 0,
+                    offset: Offset::Const(0)
+                },
+                size: 2
+            }]
+        );
+
+        let contents = |name| {
+            sections
+                .custom
+                .iter()
+                .find(|section| section.name == name)
+                .and_then(|section| section.contents.as_ref())
+        };
+        assert_eq!(
+            contents("producers"),
+            Some(&CustomContents::Producers(vec![(
+                "language".to_string(),
+                vec![("Rust".to_string(), "1.61.0".to_string())]
+            )]))
+        );
+        assert_eq!(
+            contents("target_features"),
+            Some(&CustomContents::TargetFeatures(vec![
+                ('+', "mutable-globals".to_string()),
+                ('-', "simd".to_string())
+            ]))
+        );
+        assert_eq!(
+            contents("name"),
+            Some(&CustomContents::Names {
+                module: Some("example".to_string()),
+                functions: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::cli::WasmerCLIOptions;
+use anyhow::{Context, Result};
+use clap::{Command, CommandFactory, Parser, ValueHint};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer man` subcommand
+pub struct Man {
+    /// Write the pages of `wasmer` and of all its subcommands, like
+    /// `wasmer-run.1`, to this directory instead of printing the page
+    /// of `wasmer`
+    #[clap(long = "out-dir", name = "DIR", parse(from_os_str), value_hint = ValueHint::DirPath)]
+    out_dir: Option<PathBuf>,
+}
+
+impl Man {
+    /// Runs logic for the `man` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context("failed to generate the manual pages")
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let command = WasmerCLIOptions::command();
+        match &self.out_dir {
+            Some(dir) => {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create `{}`", dir.display()))?;
+                write_pages(command, dir)
+            }
+            None => Ok(clap_mangen::Man::new(command).render(&mut io::stdout())?),
+        }
+    }
+}
+
+/// Writes the page of `command` to `dir`, then the pages of its
+/// visible subcommands, named after their parent, e.g.
+/// `wasmer-cache-clean.1`.
+fn write_pages(command: Command, dir: &Path) -> Result<()> {
+    let name = command.get_name().to_string();
+    let subcommands = command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+        .map(|subcommand| {
+            let full_name = format!("{}-{}", name, subcommand.get_name());
+            subcommand.clone().name(full_name)
+        })
+        .collect::<Vec<_>>();
+
+    let path = dir.join(format!("{}.1", name));
+    let mut file = fs::File::create(&path)
+        .with_context(|| format!("failed to create `{}`", path.display()))?;
+    clap_mangen::Man::new(command).render(&mut file)?;
+    eprintln!("Wrote `{}`.", path.display());
+
+    for subcommand in subcommands {
+        write_pages(subcommand, dir)?;
+    }
+    Ok(())
+}
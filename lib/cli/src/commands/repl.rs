@@ -0,0 +1,297 @@
+//! An interactive prompt to explore a module, for `wasmer repl`: list
+//! its exports, call its functions, read its globals and memories.
+
+use crate::store::StoreOptions;
+use crate::utils::parse_value;
+use anyhow::{Context, Result};
+use clap::{Parser, ValueHint};
+use colored::*;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use wasmer::*;
+
+/// The commands of the prompt.
+const HELP: &str = "\
+exports                         list the exports of the module
+call FUNCTION [ARGS...]         call an exported function, e.g. `call add 1 2`
+FUNCTION [ARGS...]              the same, if FUNCTION is not a command
+global NAME [VALUE]             print a global, or set it if it is mutable
+memory OFFSET [LENGTH] [NAME]   dump a range of a memory, `memory` by default
+reload                          compile and instantiate the module again
+help                            print this help
+quit                            exit";
+
+/// The number of bytes dumped by `memory` by default.
+const DUMP_LENGTH: u64 = 64;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer repl` subcommand
+pub struct Repl {
+    /// The module to explore
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
+    path: PathBuf,
+
+    #[clap(flatten)]
+    store: StoreOptions,
+}
+
+impl Repl {
+    /// Runs logic for the `repl` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to explore `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let mut session = self.instantiate()?;
+        println!(
+            "{} instantiated, type `help` for the commands",
+            self.path.display()
+        );
+
+        let stdin = io::stdin();
+        let mut line = String::new();
+        loop {
+            print!("> ");
+            io::stdout().flush()?;
+            line.clear();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                println!();
+                return Ok(());
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let result = match words.as_slice() {
+                [] => Ok(()),
+                ["quit"] | ["exit"] => return Ok(()),
+                ["reload"] => self.instantiate().map(|new| {
+                    session = new;
+                    println!("{} instantiated again", self.path.display());
+                }),
+                [command, args @ ..] => session.eval(command, args),
+            };
+            if let Err(err) = result {
+                eprintln!("{}: {:#}", "error".red().bold(), err);
+            }
+        }
+    }
+
+    /// Compiles and instantiates the module, with WASI if it imports it.
+    fn instantiate(&self) -> Result<Session> {
+        let (mut store, _compiler_type) = self.store.get_store()?;
+        let contents = std::fs::read(&self.path)?;
+        let module = Module::new(&store, contents)?;
+
+        #[cfg(feature = "wasi")]
+        if wasmer_wasi::is_wasi_module(&module) {
+            let name = self
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            let wasi_env = wasmer_wasi::WasiState::new(name).finalize(&mut store)?;
+            let import_object =
+                wasmer_wasi::import_object_for_all_wasi_versions(&mut store, &wasi_env.env);
+            let instance = Instance::new(&mut store, &module, &import_object)?;
+            let memory = instance.exports.get_memory("memory")?;
+            wasi_env.data_mut(&mut store).set_memory(memory.clone());
+            return Ok(Session {
+                store,
+                module,
+                instance,
+            });
+        }
+
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        Ok(Session {
+            store,
+            module,
+            instance,
+        })
+    }
+}
+
+/// An instance of the module explored.
+struct Session {
+    store: Store,
+    module: Module,
+    instance: Instance,
+}
+
+impl Session {
+    /// Runs `command` with its `args`.
+    fn eval(&mut self, command: &str, args: &[&str]) -> Result<()> {
+        match (command, args) {
+            ("help", []) => println!("{}", HELP),
+            ("exports", []) => self.exports(),
+            ("call", [function, args @ ..]) => self.call(function, args)?,
+            ("call", _) => bail!("usage: call FUNCTION [ARGS...]"),
+            ("global", [name]) => {
+                let global = self.instance.exports.get_global(name)?;
+                println!("{}", global.get(&mut self.store).to_string());
+            }
+            ("global", [name, value]) => {
+                let global = self.instance.exports.get_global(name)?;
+                let value = parse_value(value, global.ty(&self.store).ty)?;
+                global.set(&mut self.store, value)?;
+            }
+            ("global", _) => bail!("usage: global NAME [VALUE]"),
+            ("memory", [offset, rest @ ..]) if rest.len() <= 2 => {
+                let offset = parse_offset(offset)?;
+                let length = match rest.first() {
+                    Some(length) => parse_offset(length)?,
+                    None => DUMP_LENGTH,
+                };
+                self.dump(rest.get(1).copied().unwrap_or("memory"), offset, length)?;
+            }
+            ("memory", _) => bail!("usage: memory OFFSET [LENGTH] [NAME]"),
+            (function, args) => {
+                if self.instance.exports.get_function(function).is_err() {
+                    bail!(
+                        "`{}` is neither a command nor an exported function, see `help`",
+                        function
+                    );
+                }
+                self.call(function, args)?
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the exports of the module, with their types.
+    fn exports(&self) {
+        let exports = self.module.exports();
+        for (kind, exports) in [
+            (
+                "Functions",
+                exports
+                    .clone()
+                    .functions()
+                    .map(|f| format!("{}: {}", f.name(), f.ty()))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "Memories",
+                exports
+                    .clone()
+                    .memories()
+                    .map(|f| format!("{}: {}", f.name(), f.ty()))
+                    .collect(),
+            ),
+            (
+                "Tables",
+                exports
+                    .clone()
+                    .tables()
+                    .map(|f| format!("{}: {}", f.name(), f.ty()))
+                    .collect(),
+            ),
+            (
+                "Globals",
+                exports
+                    .clone()
+                    .globals()
+                    .map(|f| format!("{}: {}", f.name(), f.ty()))
+                    .collect(),
+            ),
+        ] {
+            if !exports.is_empty() {
+                println!("{}:", kind);
+                for export in exports {
+                    println!("  {}", export);
+                }
+            }
+        }
+    }
+
+    /// Calls `function` with `args`, parsed with the types of its
+    /// parameters, and prints its results.
+    fn call(&mut self, function: &str, args: &[&str]) -> Result<()> {
+        let function = self.instance.exports.get_function(function)?.clone();
+        let ty = function.ty(&self.store);
+        if ty.params().len() != args.len() {
+            bail!(
+                "the function expects {} arguments, but received {}",
+                ty.params().len(),
+                args.len()
+            );
+        }
+        let args = args
+            .iter()
+            .zip(ty.params())
+            .map(|(arg, ty)| parse_value(arg, *ty))
+            .collect::<Result<Vec<_>>>()?;
+
+        let results = function.call(&mut self.store, &args)?;
+        if !results.is_empty() {
+            println!(
+                "{}",
+                results
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Prints the `length` bytes of the memory `name` at `offset`, in
+    /// hexadecimal and as ASCII.
+    fn dump(&self, name: &str, offset: u64, length: u64) -> Result<()> {
+        let memory = self.instance.exports.get_memory(name)?;
+        let view = memory.view(&self.store);
+        let mut bytes = vec![0; length as usize];
+        view.read(offset, &mut bytes).with_context(|| {
+            format!(
+                "the range is outside of `{}`, which has {} bytes",
+                name,
+                view.data_size()
+            )
+        })?;
+
+        for (line, chunk) in bytes.chunks(16).enumerate() {
+            let hex = chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            println!("{:08x}  {:<47}  {}", offset + line as u64 * 16, hex, ascii);
+        }
+        Ok(())
+    }
+}
+
+/// Parses an offset or a length, in decimal or in hexadecimal with
+/// `0x`.
+fn parse_offset(arg: &str) -> Result<u64> {
+    match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => arg.parse(),
+    }
+    .map_err(|_| anyhow!("`{}` is not an offset", arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_offset;
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("1024").unwrap(), 1024);
+        assert_eq!(parse_offset("0x10").unwrap(), 16);
+        assert!(parse_offset("-1").is_err());
+        assert!(parse_offset("0xg").is_err());
+    }
+}
@@ -1,8 +1,16 @@
-use crate::common::get_cache_dir;
+#[cfg(feature = "cache")]
+use crate::common::get_cache;
+use crate::common::get_downloads_dir;
+use crate::diagnostic;
+use crate::diagnostic::Code;
+use crate::error::{ErrorFormat, PrettyError};
+use crate::fetch::FetchOptions;
 #[cfg(feature = "debug")]
 use crate::logging;
+use crate::signature::VerifyOptions;
 use crate::store::{CompilerType, StoreOptions};
-use crate::suggestions::suggest_function_exports;
+use crate::suggestions::{describe_function_export, suggest_function_exports};
+use crate::utils::parse_value;
 use crate::warning;
 use anyhow::{anyhow, Context, Result};
 use std::path::PathBuf;
@@ -11,13 +19,30 @@ use wasmer::FunctionEnv;
 use wasmer::*;
 #[cfg(feature = "cache")]
 use wasmer_cache::{Cache, FileSystemCache, Hash};
-use wasmer_types::Type as ValueType;
+#[cfg(feature = "compiler")]
+use wasmer_middlewares::metering::{MeteringPoints, MeteringStore};
 
-use clap::Parser;
+use clap::{Parser, ValueHint};
 
+mod entry;
+#[cfg(feature = "wasi")]
+mod explain;
+#[cfg(feature = "import-lib")]
+mod import_lib;
+mod limits;
+#[cfg(feature = "wasi")]
+mod package;
+mod source;
 #[cfg(feature = "wasi")]
 mod wasi;
+#[cfg(feature = "watch")]
+mod watch;
 
+use entry::Entry;
+use limits::Limits;
+#[cfg(feature = "wasi")]
+use package::Package;
+use source::{parse_checksum, ModuleSource};
 #[cfg(feature = "wasi")]
 use wasi::Wasi;
 
@@ -29,10 +54,22 @@ pub struct Run {
     #[clap(long = "disable-cache")]
     disable_cache: bool,
 
-    /// File to run
-    #[clap(name = "FILE", parse(from_os_str))]
+    /// File to run, or URL to download it from, or package of the
+    /// registry, e.g. `registry:namespace/name@version`
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
     path: PathBuf,
 
+    /// The SHA-256 digest the downloaded module must have, e.g.
+    /// `sha256:9f86d0...`
+    #[clap(long = "checksum", parse(try_from_str = parse_checksum))]
+    checksum: Option<String>,
+
+    #[clap(flatten)]
+    fetch: FetchOptions,
+
+    #[clap(flatten)]
+    verify: VerifyOptions,
+
     /// Invoke a specified function
     #[clap(long = "invoke", short = 'i')]
     invoke: Option<String>,
@@ -43,6 +80,12 @@ pub struct Run {
     #[clap(long = "command-name", hide = true)]
     command_name: Option<String>,
 
+    /// The command to run, when running a package, i.e. a directory or
+    /// a `.tar` archive holding a `wasmer.toml` manifest
+    #[cfg(feature = "wasi")]
+    #[clap(long = "command")]
+    command: Option<String>,
+
     /// A prehashed string, used to speed up start times by avoiding hashing the
     /// wasm module. If the specified hash is not found, Wasmer will hash the module
     /// as if no `cache-key` argument was passed.
@@ -50,19 +93,53 @@ pub struct Run {
     #[clap(long = "cache-key", hide = true)]
     cache_key: Option<String>,
 
+    /// How the module is found in the cache: `read` reads and hashes
+    /// it, `mmap` maps the artifact cached for its path, without
+    /// reading it as long as it doesn't change
+    #[cfg(feature = "cache")]
+    #[clap(long = "cache-strategy", default_value = "read")]
+    cache_strategy: CacheStrategy,
+
     #[clap(flatten)]
     store: StoreOptions,
 
+    #[clap(flatten)]
+    limits: Limits,
+
     // TODO: refactor WASI structure to allow shared options with Emscripten
     #[cfg(feature = "wasi")]
     #[clap(flatten)]
     wasi: Wasi,
 
+    /// Load host functions from a native library exporting the C
+    /// function `wasmer_register_imports`, and give them to the module
+    /// as imports
+    #[cfg(feature = "import-lib")]
+    #[clap(long = "import-lib", name = "LIBRARY", parse(from_os_str), value_hint = ValueHint::FilePath)]
+    import_libs: Vec<PathBuf>,
+
+    /// Run the module again, recompiling it, whenever its file changes
+    #[cfg(feature = "watch")]
+    #[clap(long = "watch")]
+    watch: bool,
+
+    /// With `--watch`, also run the module again when the directories
+    /// given with `--dir` or `--mapdir` change
+    #[cfg(all(feature = "watch", feature = "wasi"))]
+    #[clap(long = "watch-dirs", requires = "watch")]
+    watch_dirs: bool,
+
     /// Enable non-standard experimental IO devices
     #[cfg(feature = "io-devices")]
     #[clap(long = "enable-io-devices")]
     enable_experimental_io_devices: bool,
 
+    /// How errors are printed: `human`, or `json` to report traps,
+    /// with their kind and wasm backtrace, and exit codes as JSON
+    /// objects on stderr. The `error-format` setting by default
+    #[clap(long = "error-format")]
+    error_format: Option<ErrorFormat>,
+
     /// Enable debug output
     #[cfg(feature = "debug")]
     #[clap(long = "debug", short = 'd')]
@@ -75,16 +152,38 @@ pub struct Run {
     /// Application arguments
     #[clap(value_name = "ARGS")]
     args: Vec<String>,
+
+    /// The module to run instead of the one at `path`, e.g. the one
+    /// embedded in a bundle
+    #[clap(skip)]
+    module_contents: Option<Vec<u8>>,
 }
 
 impl Run {
     /// Execute the run command
     pub fn execute(&self) -> Result<()> {
+        if let Some(error_format) = self.error_format {
+            error_format.set();
+        }
         #[cfg(feature = "debug")]
         if self.debug {
             logging::set_up_logging(self.verbose).unwrap();
         }
-        self.inner_execute().with_context(|| {
+        #[cfg(feature = "watch")]
+        if self.watch && !watch::is_child() {
+            #[cfg(feature = "wasi")]
+            let dirs = if self.watch_dirs {
+                self.wasi.host_dirs()
+            } else {
+                vec![]
+            };
+            #[cfg(not(feature = "wasi"))]
+            let dirs = vec![];
+            return watch::run(&self.path, &dirs)
+                .with_context(|| format!("failed to watch `{}`", self.path.display()));
+        }
+        let result = self.limits.check_memory(self.inner_execute());
+        result.with_context(|| {
             format!(
                 "failed to run `{}`{}",
                 self.path.display(),
@@ -98,16 +197,54 @@ impl Run {
     }
 
     fn inner_module_run(&self, mut store: Store, instance: Instance) -> Result<()> {
+        #[cfg(feature = "compiler")]
+        self.refuel(&mut store, &instance)?;
+        self.limits.start_timer();
+        let result = self.run_instance(&mut store, &instance);
+        self.check_fuel(&mut store, &instance, result)
+    }
+
+    /// Gives the fuel of `--fuel` to `instance`, which must have been
+    /// compiled with metering.
+    #[cfg(feature = "compiler")]
+    fn refuel(&self, store: &mut Store, instance: &Instance) -> Result<()> {
+        if let Some(fuel) = self.store.fuel() {
+            if !store.is_metered(instance) {
+                bail!("`--fuel` can't limit a module compiled without metering, like this precompiled one; compile it with `--enable-metering`");
+            }
+            store.set_remaining_points(instance, fuel);
+        }
+        Ok(())
+    }
+
+    /// Gives the error of `instance` failing because it ran out of
+    /// fuel the code of this limit.
+    #[allow(unused_variables)]
+    fn check_fuel(&self, store: &mut Store, instance: &Instance, result: Result<()>) -> Result<()> {
+        #[cfg(feature = "compiler")]
+        if result.is_err()
+            && store.is_metered(instance)
+            && store.remaining_points(instance) == MeteringPoints::Exhausted
+        {
+            return result.context(crate::diagnostic::Diagnostic::new(
+                Code::FuelExhausted,
+                "the module ran out of fuel",
+            ));
+        }
+        result
+    }
+
+    fn run_instance(&self, store: &mut Store, instance: &Instance) -> Result<()> {
         // If this module exports an _initialize function, run that first.
         if let Ok(initialize) = instance.exports.get_function("_initialize") {
             initialize
-                .call(&mut store, &[])
+                .call(store, &[])
                 .with_context(|| "failed to run _initialize function")?;
         }
 
         // Do we want to invoke a function?
         if let Some(ref invoke) = self.invoke {
-            let result = self.invoke_function(&mut store, &instance, invoke, &self.args)?;
+            let result = self.invoke_function(store, instance, invoke, &self.args)?;
             println!(
                 "{}",
                 result
@@ -117,8 +254,7 @@ impl Run {
                     .join(" ")
             );
         } else {
-            let start: Function = self.try_find_function(&instance, "_start", &[])?;
-            let result = start.call(&mut store, &[]);
+            let result = self.call_entry(store, instance)?;
             #[cfg(feature = "wasi")]
             self.wasi.handle_result(result)?;
             #[cfg(not(feature = "wasi"))]
@@ -128,7 +264,60 @@ impl Run {
         Ok(())
     }
 
+    /// Calls `_start`, or the entry point found by [`Entry::find`] if
+    /// the module has none. The status returned by the latter, if
+    /// any, is the exit code of the process.
+    fn call_entry(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+    ) -> Result<std::result::Result<Box<[Value]>, RuntimeError>> {
+        if instance.exports.get_function("_start").is_err() {
+            if let Some(entry) = Entry::find(&*store, instance) {
+                warning!(
+                    "the module has no `_start` function, running `{}` instead",
+                    entry.name()
+                );
+                let result = entry.call(store, instance, &self.program_name(), &self.args)?;
+                if let Ok(values) = &result {
+                    if let [Value::I32(status)] = **values {
+                        if status != 0 {
+                            PrettyError::report_exit(status);
+                        }
+                    }
+                }
+
+                return Ok(result);
+            }
+        }
+
+        let start: Function = self.try_find_function(instance, "_start", &[])?;
+        Ok(start.call(store, &[]))
+    }
+
+    /// The name of the program, i.e. the first argument given to the
+    /// module.
+    fn program_name(&self) -> String {
+        self.command_name
+            .clone()
+            .or_else(|| {
+                self.path
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+            })
+            .unwrap_or_default()
+    }
+
     fn inner_execute(&self) -> Result<()> {
+        let source = ModuleSource::parse(&self.path);
+        if source.is_remote() {
+            return self.with_source(&source)?.inner_execute();
+        }
+        #[cfg(feature = "wasi")]
+        if self.wasi.package().is_none() && Package::is_package(&self.path) {
+            return self.with_package()?.inner_execute();
+        }
+
         let (mut store, module) = self.get_store_module()?;
         #[cfg(feature = "emscripten")]
         {
@@ -160,6 +349,7 @@ impl Run {
                     }
                 };
 
+                self.limits.start_timer();
                 run_emscripten_instance(
                     &mut instance,
                     env.into_mut(&mut store),
@@ -176,6 +366,11 @@ impl Run {
             }
         }
 
+        #[allow(unused_mut)]
+        let mut imports = Imports::new();
+        #[cfg(feature = "import-lib")]
+        let import_env = import_lib::define_imports(&mut store, &self.import_libs, &mut imports)?;
+
         // If WASI is enabled, try to execute it with it
         #[cfg(feature = "wasi")]
         let ret = {
@@ -195,31 +390,32 @@ impl Run {
                         };
                         if self.wasi.deny_multiple_wasi_versions {
                             let version_list = get_version_list(&wasi_versions);
-                            bail!("Found more than 1 WASI version in this module ({}) and `--deny-multiple-wasi-versions` is enabled.", version_list);
+                            return Err(diagnostic!(Code::MultipleWasiVersions, "Found more than 1 WASI version in this module ({}) and `--deny-multiple-wasi-versions` is enabled.", version_list));
                         } else if !self.wasi.allow_multiple_wasi_versions {
                             let version_list = get_version_list(&wasi_versions);
                             warning!("Found more than 1 WASI version in this module ({}). If this is intentional, pass `--allow-multiple-wasi-versions` to suppress this warning.", version_list);
                         }
                     }
 
-                    let program_name = self
-                        .command_name
-                        .clone()
-                        .or_else(|| {
-                            self.path
-                                .file_name()
-                                .map(|f| f.to_string_lossy().to_string())
-                        })
-                        .unwrap_or_default();
                     let (_ctx, instance) = self
                         .wasi
-                        .instantiate(&mut store, &module, program_name, self.args.clone())
+                        .instantiate(
+                            &mut store,
+                            &module,
+                            self.program_name(),
+                            self.args.clone(),
+                            &imports,
+                        )
                         .with_context(|| "failed to instantiate WASI module")?;
+                    #[cfg(feature = "import-lib")]
+                    import_lib::set_instance(&mut store, &import_env, &instance);
                     self.inner_module_run(store, instance)
                 }
                 // not WASI
                 _ => {
-                    let instance = Instance::new(&mut store, &module, &imports! {})?;
+                    let instance = Instance::new(&mut store, &module, &imports)?;
+                    #[cfg(feature = "import-lib")]
+                    import_lib::set_instance(&mut store, &import_env, &instance);
                     self.inner_module_run(store, instance)
                 }
             }
@@ -259,21 +455,115 @@ impl Run {
         ret
     }
 
+    /// Returns the options running the module downloaded from
+    /// `source`.
+    fn with_source(&self, source: &ModuleSource) -> Result<Self> {
+        // `--command` picks the module of a registry package, and is
+        // only available with WASI.
+        #[cfg(feature = "wasi")]
+        let module = self.command.as_deref();
+        #[cfg(not(feature = "wasi"))]
+        let module = None;
+        self.verify.check_remote(source)?;
+        let path = source.fetch(
+            &get_downloads_dir()?,
+            module,
+            self.checksum.as_deref(),
+            &self.fetch.policy()?,
+        )?;
+
+        let mut run = self.clone();
+        run.command_name = Some(self.command_name.clone().unwrap_or_else(|| source.name()));
+        run.path = path;
+        Ok(run)
+    }
+
+    /// Returns the options to run the command of the package at
+    /// `self.path`: its module, arguments, directories and environment
+    /// variables.
+    #[cfg(feature = "wasi")]
+    fn with_package(&self) -> Result<Self> {
+        let package = Package::open(&self.path, self.command.as_deref())?;
+        let command = package.command();
+
+        let mut run = self.clone();
+        run.command_name = Some(
+            self.command_name
+                .clone()
+                .unwrap_or_else(|| command.name.clone()),
+        );
+        run.args = command.args.clone();
+        run.args.extend(self.args.iter().cloned());
+        run.wasi.set_package(package);
+        Ok(run)
+    }
+
+    /// Runs the module `contents` instead of the one at `path`.
+    #[cfg(feature = "wasi")]
+    pub fn with_module_contents(mut self, contents: Vec<u8>) -> Self {
+        self.module_contents = Some(contents);
+        self
+    }
+
+    /// Returns the contents of the module to run, and its name.
+    fn module_contents(&self) -> Result<(Vec<u8>, String)> {
+        if let Some(contents) = &self.module_contents {
+            return Ok((contents.clone(), self.program_name()));
+        }
+        #[cfg(feature = "wasi")]
+        if let Some(package) = self.wasi.package() {
+            let name = package
+                .module()
+                .source
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            return Ok((package.module_contents()?, name));
+        }
+
+        let name = self
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        Ok((std::fs::read(&self.path)?, name))
+    }
+
     fn get_store_module(&self) -> Result<(Store, Module)> {
-        let contents = std::fs::read(self.path.clone())?;
+        #[cfg(feature = "cache")]
+        if let Some(found) = self.get_module_from_index()? {
+            return Ok(found);
+        }
+        let (contents, name) = self.module_contents()?;
+        // The modules embedded in bundles are trusted like wasmer
+        // itself.
+        if self.module_contents.is_none() {
+            self.verify.verify(&self.path, &contents)?;
+        }
         if wasmer_compiler::Artifact::is_deserializable(&contents) {
             let engine = wasmer_compiler::EngineBuilder::headless();
-            let store = Store::new(engine);
-            let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
+            let store = self.limits.store(engine.engine());
+            let module =
+                unsafe { Module::deserialize(&store, contents) }.map_err(|err| match err {
+                    DeserializeError::Incompatible(_) => {
+                        diagnostic!(Code::IncompatibleArtifact, "{}", err)
+                    }
+                    err => err.into(),
+                })?;
             return Ok((store, module));
         }
-        let (store, compiler_type) = self.store.get_store()?;
+        let (engine, compiler_type) = self.store.get_engine()?;
+        let store = self.limits.store(engine);
+        // The cached modules are not metered.
         #[cfg(feature = "cache")]
-        let module_result: Result<Module> = if !self.disable_cache && contents.len() > 0x1000 {
-            self.get_module_from_cache(&store, &contents, &compiler_type)
-        } else {
-            Module::new(&store, contents).map_err(|e| e.into())
-        };
+        let module_result: Result<Module> =
+            if !self.disable_cache && !self.store.metered() && contents.len() > 0x1000 {
+                self.get_module_from_cache(&store, &contents, &compiler_type)
+            } else {
+                Module::new(&store, contents).map_err(|e| e.into())
+            };
         #[cfg(not(feature = "cache"))]
         let module_result = Module::new(&store, &contents);
 
@@ -284,7 +574,7 @@ impl Run {
             )
         })?;
         // We set the name outside the cache, to make sure we dont cache the name
-        module.set_name(&self.path.file_name().unwrap_or_default().to_string_lossy());
+        module.set_name(&name);
 
         Ok((store, module))
     }
@@ -307,7 +597,7 @@ impl Run {
             .cache_key
             .as_ref()
             .and_then(|key| Hash::from_str(key).ok())
-            .unwrap_or_else(|| Hash::generate(contents));
+            .unwrap_or_else(|| cache.key(contents));
         match unsafe { cache.load(store, hash) } {
             Ok(module) => Ok(module),
             Err(e) => {
@@ -322,21 +612,62 @@ impl Run {
                 let module = Module::new(store, contents)?;
                 // Store the compiled Module in cache
                 cache.store(hash, &module)?;
+                if self.indexes_by_path() {
+                    // The next runs only miss the module if the index
+                    // can't be written.
+                    let _ = cache.index(&self.path, hash);
+                }
                 Ok(module)
             }
         }
     }
 
+    /// Whether the module is found in the cache by its path, with
+    /// `--cache-strategy mmap`: only modules read from `path` are.
+    #[cfg(feature = "cache")]
+    fn indexes_by_path(&self) -> bool {
+        #[cfg(feature = "wasi")]
+        if self.wasi.package().is_some() {
+            return false;
+        }
+        self.cache_strategy == CacheStrategy::Mmap
+            && !self.disable_cache
+            && !self.store.metered()
+            && self.cache_key.is_none()
+            && self.module_contents.is_none()
+            // The modules to verify must be read.
+            && !self.verify.is_enabled()
+    }
+
+    /// Loads the module cached for `path`, without reading it, if it
+    /// didn't change since it was cached, see `indexes_by_path`.
+    #[cfg(feature = "cache")]
+    fn get_module_from_index(&self) -> Result<Option<(Store, Module)>> {
+        if !self.indexes_by_path() {
+            return Ok(None);
+        }
+        let (engine, compiler_type) = self.store.get_engine()?;
+        let cache = self.get_cache(&compiler_type)?;
+        let key = match cache.indexed_key(&self.path) {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let store = self.limits.store(engine);
+        // An evicted or corrupted module is found again from the
+        // contents of `path`.
+        match unsafe { cache.load(&store, key) } {
+            Ok(mut module) => {
+                module.set_name(&self.path.file_name().unwrap_or_default().to_string_lossy());
+                Ok(Some((store, module)))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
     #[cfg(feature = "cache")]
     /// Get the Compiler Filesystem cache
     fn get_cache(&self, compiler_type: &CompilerType) -> Result<FileSystemCache> {
-        let mut cache_dir_root = get_cache_dir();
-        cache_dir_root.push(compiler_type.to_string());
-        let mut cache = FileSystemCache::new(cache_dir_root)?;
-
-        let extension = "wasmu";
-        cache.set_cache_extension(Some(extension));
-        Ok(cache)
+        get_cache(&compiler_type.to_string())
     }
 
     fn try_find_function(
@@ -352,17 +683,20 @@ impl Run {
                 if instance.module().info().functions.is_empty() {
                     anyhow!("The module has no exported functions to call.")
                 } else {
-                    let suggested_functions = suggest_function_exports(instance.module(), "");
+                    let suggested_functions =
+                        suggest_function_exports(instance.module(), name, Some(args.len()));
                     let names = suggested_functions
                         .iter()
                         .take(3)
-                        .map(|arg| format!("`{}`", arg))
+                        .map(describe_function_export)
                         .collect::<Vec<_>>()
                         .join(", ");
                     let suggested_command = format!(
                         "wasmer {} -i {} {}",
                         self.path.display(),
-                        suggested_functions.get(0).unwrap_or(&String::new()),
+                        suggested_functions
+                            .get(0)
+                            .map_or("", |function| function.name()),
                         args.join(" ")
                     );
                     let suggestion = if suggested_functions.is_empty() {
@@ -374,10 +708,26 @@ impl Run {
                         )
                     };
                     match e {
+                        ExportError::Missing(_) if name == "_start" => diagnostic!(
+                            Code::MissingExport,
+                            "No export `_start` found in the module, nor any entry point among {}.\n{}",
+                            entry::ENTRY_NAMES
+                                .iter()
+                                .map(|name| format!("`{}`", name))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            suggestion
+                        ),
                         ExportError::Missing(_) => {
-                            anyhow!("No export `{}` found in the module.\n{}", name, suggestion)
+                            diagnostic!(
+                                Code::MissingExport,
+                                "No export `{}` found in the module.\n{}",
+                                name,
+                                suggestion
+                            )
                         }
-                        ExportError::IncompatibleType => anyhow!(
+                        ExportError::IncompatibleType => diagnostic!(
+                            Code::MissingExport,
                             "Export `{}` found, but is not a function.\n{}",
                             name,
                             suggestion
@@ -410,33 +760,7 @@ impl Run {
         let invoke_args = args
             .iter()
             .zip(func_ty.params().iter())
-            .map(|(arg, param_type)| match param_type {
-                ValueType::I32 => {
-                    Ok(Value::I32(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a i32", arg)
-                    })?))
-                }
-                ValueType::I64 => {
-                    Ok(Value::I64(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a i64", arg)
-                    })?))
-                }
-                ValueType::F32 => {
-                    Ok(Value::F32(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a f32", arg)
-                    })?))
-                }
-                ValueType::F64 => {
-                    Ok(Value::F64(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a f64", arg)
-                    })?))
-                }
-                _ => Err(anyhow!(
-                    "Don't know how to convert {} into {:?}",
-                    arg,
-                    param_type
-                )),
-            })
+            .map(|(arg, param_type)| parse_value(arg, *param_type))
             .collect::<Result<Vec<_>>>()?;
         Ok(func.call(ctx, &invoke_args)?)
     }
@@ -493,6 +817,40 @@ impl Run {
     }
     #[cfg(not(target_os = "linux"))]
     fn from_binfmt_args_fallible() -> Result<Run> {
-        bail!("binfmt_misc is only available on linux.")
+        Err(diagnostic!(
+            Code::UnsupportedPlatform,
+            "binfmt_misc is only available on linux."
+        ))
+    }
+}
+
+/// How `wasmer run` finds a module in the cache.
+#[cfg(feature = "cache")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CacheStrategy {
+    /// Reads and hashes the module each time.
+    Read,
+    /// Maps the artifact cached for the path of the module, as long as
+    /// it doesn't change, without reading it.
+    Mmap,
+}
+
+#[cfg(feature = "cache")]
+impl Default for CacheStrategy {
+    fn default() -> Self {
+        Self::Read
+    }
+}
+
+#[cfg(feature = "cache")]
+impl FromStr for CacheStrategy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Self::Read),
+            "mmap" => Ok(Self::Mmap),
+            _ => Err("must be one of two options: `read` or `mmap`."),
+        }
     }
 }
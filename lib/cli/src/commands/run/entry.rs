@@ -0,0 +1,137 @@
+//! Finds the entry point of modules without a `_start` function, as
+//! produced by toolchains which don't target WASI commands.
+
+use anyhow::{anyhow, Result};
+use std::convert::TryInto;
+use wasmer::*;
+
+/// The names of the entry points tried when a module has no `_start`
+/// function, in order.
+pub const ENTRY_NAMES: &[&str] = &["main", "__main_argc_argv", "_main", "run"];
+
+/// The calling convention of an entry point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Convention {
+    /// `fn()`, ignoring the arguments.
+    NoArguments,
+    /// `fn(argc: i32, argv: i32)`, `argv` being a null-terminated
+    /// array of pointers to null-terminated strings.
+    ArgcArgv,
+}
+
+/// An entry point of a module.
+pub struct Entry {
+    name: String,
+    function: Function,
+    convention: Convention,
+}
+
+impl Entry {
+    /// Finds the first entry point exported by `instance` with a
+    /// supported signature, trying the names in [`ENTRY_NAMES`], then
+    /// the `run` function of the `wasi:cli/run` interface of
+    /// components.
+    pub fn find(store: &impl AsStoreRef, instance: &Instance) -> Option<Self> {
+        let component_run = instance
+            .exports
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| name.starts_with("wasi:cli/run") && name.ends_with("#run"));
+
+        ENTRY_NAMES
+            .iter()
+            .copied()
+            .chain(component_run)
+            .find_map(|name| {
+                let function = instance.exports.get_function(name).ok()?;
+                let ty = function.ty(store);
+                if !matches!(ty.results(), [] | [Type::I32]) {
+                    return None;
+                }
+                let convention = match ty.params() {
+                    [] => Convention::NoArguments,
+                    // The arguments are written in the exported memory.
+                    [Type::I32, Type::I32] if instance.exports.get_memory("memory").is_ok() => {
+                        Convention::ArgcArgv
+                    }
+                    _ => return None,
+                };
+
+                Some(Self {
+                    name: name.to_string(),
+                    function: function.clone(),
+                    convention,
+                })
+            })
+    }
+
+    /// The name of the exported function.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Calls the entry point with the program name and the arguments,
+    /// if it takes them.
+    pub fn call(
+        &self,
+        store: &mut impl AsStoreMut,
+        instance: &Instance,
+        program_name: &str,
+        args: &[String],
+    ) -> Result<std::result::Result<Box<[Value]>, RuntimeError>> {
+        let params = match self.convention {
+            Convention::NoArguments => vec![],
+            Convention::ArgcArgv => {
+                let memory = instance.exports.get_memory("memory")?;
+                let argv = std::iter::once(program_name)
+                    .chain(args.iter().map(String::as_str))
+                    .collect::<Vec<_>>();
+                let (argc, argv) = write_argv(store, memory, &argv)?;
+
+                vec![Value::I32(argc), Value::I32(argv)]
+            }
+        };
+
+        Ok(self.function.call(store, &params))
+    }
+}
+
+/// Writes the arguments in new pages of `memory`, returning `argc`
+/// and the address of `argv`.
+fn write_argv(store: &mut impl AsStoreMut, memory: &Memory, args: &[&str]) -> Result<(i32, i32)> {
+    // The strings, followed by the array of pointers, aligned on 4
+    // bytes.
+    let strings_len = args.iter().map(|arg| arg.len() + 1).sum::<usize>();
+    let pointers_offset = (strings_len + 3) & !3;
+    let len = pointers_offset + 4 * (args.len() + 1);
+
+    let pages = (len + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+    let base = memory
+        .grow(store, pages as u32)
+        .map_err(|err| {
+            anyhow!(
+                "failed to allocate the arguments of the entry point: {}",
+                err
+            )
+        })?
+        .bytes()
+        .0;
+
+    let mut bytes = vec![0; len];
+    let mut pointers = Vec::with_capacity(args.len() + 1);
+    let mut offset = 0;
+    for arg in args {
+        pointers.push((base + offset) as u32);
+        bytes[offset..offset + arg.len()].copy_from_slice(arg.as_bytes());
+        offset += arg.len() + 1;
+    }
+    pointers.push(0);
+    for (nth, pointer) in pointers.iter().enumerate() {
+        let offset = pointers_offset + 4 * nth;
+        bytes[offset..offset + 4].copy_from_slice(&pointer.to_le_bytes());
+    }
+
+    memory.view(store).write(base as u64, &bytes)?;
+
+    Ok((args.len().try_into()?, (base + pointers_offset).try_into()?))
+}
@@ -0,0 +1,226 @@
+//! Explains why the WASI file system rejected a path, for
+//! `wasmer run --explain-errors`.
+
+use crate::diagnostic::Code;
+use colored::*;
+use std::path::{Component, Path, PathBuf};
+use wasmer_wasi::types::wasi::Errno;
+use wasmer_wasi::PathError;
+
+/// The directories made visible to the guest, and the flags that did so.
+#[derive(Debug, Clone)]
+pub struct Explainer {
+    /// The guest directory, the host directory, and the flag mapping them.
+    mappings: Vec<(String, PathBuf, String)>,
+}
+
+impl Explainer {
+    pub fn new(pre_opened_directories: &[PathBuf], mapped_dirs: &[(String, PathBuf)]) -> Self {
+        let mappings = pre_opened_directories
+            .iter()
+            .map(|dir| {
+                let dir_str = dir.to_string_lossy().to_string();
+                (dir_str.clone(), dir.clone(), format!("--dir {}", dir_str))
+            })
+            .chain(mapped_dirs.iter().map(|(guest, host)| {
+                (
+                    guest.clone(),
+                    host.clone(),
+                    format!("--mapdir {}:{}", guest, host.display()),
+                )
+            }))
+            .collect();
+
+        Self { mappings }
+    }
+
+    /// Prints the explanation of `error` on stderr, if there is
+    /// anything to explain.
+    pub fn report(&self, error: &PathError) {
+        if let Some(explanation) = self.explain(error) {
+            eprintln!("{}", explanation);
+        }
+    }
+
+    /// Explains `error`, if it is a failure the user can act upon.
+    pub fn explain(&self, error: &PathError) -> Option<String> {
+        if !matches!(
+            error.errno,
+            Errno::Noent | Errno::Access | Errno::Perm | Errno::Notcapable | Errno::Notdir
+        ) {
+            return None;
+        }
+
+        let header = |code: Code| {
+            format!(
+                "{}: `{}` failed with E{} ({}) for `{}`",
+                format!("wasi error[{}]", code).red().bold(),
+                error.syscall,
+                error.errno.name().to_uppercase(),
+                error.errno.message().trim_end_matches('.'),
+                error.path,
+            )
+        };
+        let mut lines = vec![];
+        let mut note = |message: String| lines.push(format!("  {} {}", "-->".blue(), message));
+
+        let base_host_path = match &error.base_host_path {
+            Some(base_host_path) => base_host_path,
+            // The path is relative to the virtual root, so it must start
+            // with a pre-opened directory.
+            None => {
+                note(format!(
+                    "`{}` is not inside any pre-opened directory",
+                    error.path
+                ));
+                note(self.available_mappings());
+                lines.insert(0, header(Code::MissingPreopen));
+                return Some(lines.join("\n"));
+            }
+        };
+
+        match self
+            .mappings
+            .iter()
+            .find(|(_, host, _)| host == base_host_path)
+        {
+            Some((guest, host, flag)) => note(format!(
+                "relative to `{}`, mapped to the host directory `{}` by `{}`",
+                guest,
+                host.display(),
+                flag
+            )),
+            None => note(format!(
+                "relative to `{}`, backed by the host directory `{}`",
+                error.base_name,
+                base_host_path.display()
+            )),
+        }
+
+        let host_path = match join_within(base_host_path, Path::new(&error.path)) {
+            Some(host_path) => host_path,
+            None => {
+                note(format!(
+                    "`{}` escapes the pre-opened directory, the sandbox denies access outside of it",
+                    error.path
+                ));
+                lines.insert(0, header(Code::PathEscapesPreopen));
+                return Some(lines.join("\n"));
+            }
+        };
+
+        let code = match (error.errno, host_path.symlink_metadata()) {
+            (Errno::Notcapable, _) => {
+                note(format!(
+                    "the file descriptor `{}` lacks the rights for this operation",
+                    error.base_fd
+                ));
+                Code::MissingRights
+            }
+            (_, Err(_)) => {
+                note(format!(
+                    "resolves to the host path `{}`, which does not exist",
+                    host_path.display()
+                ));
+                let existing = host_path.ancestors().skip(1).find(|dir| dir.exists());
+                if let Some(existing) = existing.filter(|dir| dir != base_host_path) {
+                    note(format!(
+                        "the closest existing directory is `{}`",
+                        existing.display()
+                    ));
+                }
+                Code::HostPathNotFound
+            }
+            (Errno::Access | Errno::Perm, Ok(metadata)) => {
+                note(format!(
+                    "resolves to the host path `{}`, which the host denied access to{}",
+                    host_path.display(),
+                    if metadata.permissions().readonly() {
+                        " (it is read-only)"
+                    } else {
+                        ""
+                    }
+                ));
+                Code::HostAccessDenied
+            }
+            // E.g. ENOTDIR, a file used as a directory.
+            (_, Ok(_)) => {
+                note(format!(
+                    "resolves to the host path `{}`",
+                    host_path.display()
+                ));
+                Code::HostPathNotFound
+            }
+        };
+
+        lines.insert(0, header(code));
+        Some(lines.join("\n"))
+    }
+
+    fn available_mappings(&self) -> String {
+        if self.mappings.is_empty() {
+            return "no directory is pre-opened, use `--dir` or `--mapdir` to give access to one"
+                .to_string();
+        }
+        format!(
+            "the pre-opened directories are {}",
+            self.mappings
+                .iter()
+                .map(|(guest, _, flag)| format!("`{}` (`{}`)", guest, flag))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+/// Joins `path` to `base` lexically, or returns `None` if `path`
+/// goes above `base`.
+fn join_within(base: &Path, path: &Path) -> Option<PathBuf> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(name),
+            Component::ParentDir => {
+                components.pop()?;
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+
+    let mut joined = base.to_path_buf();
+    joined.extend(components);
+    Some(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_within() {
+        assert_eq!(
+            join_within(Path::new("/data"), Path::new("a/./b/../c")),
+            Some(PathBuf::from("/data/a/c"))
+        );
+        assert_eq!(join_within(Path::new("/data"), Path::new("a/../..")), None);
+    }
+
+    #[test]
+    fn test_explain_missing_preopen() {
+        colored::control::set_override(false);
+        let explainer = Explainer::new(&[], &[("/data".to_string(), PathBuf::from("/host"))]);
+        let explanation = explainer
+            .explain(&PathError {
+                syscall: "path_open",
+                base_fd: 3,
+                base_name: "/".to_string(),
+                base_host_path: None,
+                path: "/etc/passwd".to_string(),
+                errno: Errno::Noent,
+            })
+            .unwrap();
+        assert!(explanation.starts_with("wasi error[WSM1201]: `path_open` failed"));
+        assert!(explanation.contains("`/etc/passwd` is not inside any pre-opened directory"));
+        assert!(explanation.contains("`/data` (`--mapdir /data:/host`)"));
+    }
+}
@@ -0,0 +1,361 @@
+//! Host functions loaded from native libraries, for `wasmer run
+//! --import-lib`, so modules needing custom imports can be run
+//! without writing an embedder.
+//!
+//! A library exports a C function registering its host functions:
+//!
+//! ```c
+//! typedef union {
+//!     int32_t i32;
+//!     int64_t i64;
+//!     float f32;
+//!     double f64;
+//! } wasmer_import_value_t;
+//!
+//! // Returns 0 on success; any other status traps the module.
+//! typedef int32_t (*wasmer_import_func_t)(
+//!     void *data, const wasmer_import_value_t *params,
+//!     wasmer_import_value_t *results, uint8_t *memory, uint64_t memory_size);
+//!
+//! // The types are 0 for i32, 1 for i64, 2 for f32 and 3 for f64.
+//! // Returns 0 on success.
+//! typedef int32_t (*wasmer_register_t)(
+//!     void *registry, const char *module, const char *name,
+//!     const uint8_t *params, size_t num_params,
+//!     const uint8_t *results, size_t num_results,
+//!     wasmer_import_func_t func, void *data);
+//!
+//! void wasmer_register_imports(void *registry, wasmer_register_t register_import);
+//! ```
+//!
+//! `memory` is the exported memory of the module, or null if it has
+//! none; it is only valid during the call.
+
+use anyhow::{Context, Result};
+use libloading::{Library, Symbol};
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+use wasmer::{
+    AsStoreMut, Function, FunctionEnv, FunctionEnvMut, FunctionType, Imports, Instance, Memory,
+    RuntimeError, Type, Value,
+};
+
+/// The function registering the host functions of a library.
+const REGISTER_SYMBOL: &[u8] = b"wasmer_register_imports\0";
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union RawValue {
+    i32: i32,
+    i64: i64,
+    f32: f32,
+    f64: f64,
+}
+
+impl RawValue {
+    fn new(value: &Value) -> Self {
+        match *value {
+            Value::I32(i32) => Self { i32 },
+            Value::I64(i64) => Self { i64 },
+            Value::F32(f32) => Self { f32 },
+            Value::F64(f64) => Self { f64 },
+            _ => Self { i64: 0 },
+        }
+    }
+
+    fn value(&self, ty: Type) -> Value {
+        unsafe {
+            match ty {
+                Type::I64 => Value::I64(self.i64),
+                Type::F32 => Value::F32(self.f32),
+                Type::F64 => Value::F64(self.f64),
+                _ => Value::I32(self.i32),
+            }
+        }
+    }
+}
+
+type ImportFunc =
+    unsafe extern "C" fn(*mut c_void, *const RawValue, *mut RawValue, *mut u8, u64) -> i32;
+
+type RegisterFunc = unsafe extern "C" fn(
+    *mut c_void,
+    *const c_char,
+    *const c_char,
+    *const u8,
+    usize,
+    *const u8,
+    usize,
+    ImportFunc,
+    *mut c_void,
+) -> i32;
+
+type RegisterImports = unsafe extern "C" fn(*mut c_void, RegisterFunc);
+
+/// The data given with a host function, owned by its library.
+#[derive(Clone, Copy)]
+struct Data(*mut c_void);
+
+// The library is responsible for the thread safety of its data.
+unsafe impl Send for Data {}
+unsafe impl Sync for Data {}
+
+/// A host function registered by a library.
+struct HostFunction {
+    module: String,
+    name: String,
+    ty: FunctionType,
+    func: ImportFunc,
+    data: Data,
+}
+
+/// The host functions registered by a library, and the errors of the
+/// invalid registrations.
+#[derive(Default)]
+struct Registry {
+    functions: Vec<HostFunction>,
+    errors: Vec<String>,
+}
+
+/// The `wasmer_register_t` given to the libraries.
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn register(
+    registry: *mut c_void,
+    module: *const c_char,
+    name: *const c_char,
+    params: *const u8,
+    num_params: usize,
+    results: *const u8,
+    num_results: usize,
+    func: ImportFunc,
+    data: *mut c_void,
+) -> i32 {
+    let registry = &mut *(registry as *mut Registry);
+    let function = (|| -> Result<HostFunction, String> {
+        if module.is_null() || name.is_null() {
+            return Err("a host function has no module or name".to_string());
+        }
+        let module = CStr::from_ptr(module).to_string_lossy().into_owned();
+        let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+        let params = types(params, num_params)
+            .ok_or_else(|| format!("`{}.{}` has an invalid parameter type", module, name))?;
+        let results = types(results, num_results)
+            .ok_or_else(|| format!("`{}.{}` has an invalid result type", module, name))?;
+        Ok(HostFunction {
+            module,
+            name,
+            ty: FunctionType::new(params, results),
+            func,
+            data: Data(data),
+        })
+    })();
+
+    match function {
+        Ok(function) => {
+            registry.functions.push(function);
+            0
+        }
+        Err(error) => {
+            registry.errors.push(error);
+            1
+        }
+    }
+}
+
+/// The value types given by their codes, or `None` if one is invalid.
+unsafe fn types(codes: *const u8, len: usize) -> Option<Vec<Type>> {
+    if len == 0 {
+        return Some(vec![]);
+    }
+    if codes.is_null() {
+        return None;
+    }
+    slice::from_raw_parts(codes, len)
+        .iter()
+        .map(|code| match code {
+            0 => Some(Type::I32),
+            1 => Some(Type::I64),
+            2 => Some(Type::F32),
+            3 => Some(Type::F64),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The environment of the host functions: the memory of the module,
+/// once instantiated.
+#[derive(Debug, Default)]
+pub struct ImportEnv {
+    memory: Option<Memory>,
+}
+
+/// Loads the libraries at `paths`, and defines their host functions
+/// in `imports`.
+pub fn define_imports(
+    store: &mut impl AsStoreMut,
+    paths: &[PathBuf],
+    imports: &mut Imports,
+) -> Result<FunctionEnv<ImportEnv>> {
+    let env = FunctionEnv::new(store, ImportEnv::default());
+    for path in paths {
+        let (library, registry) =
+            load(path).with_context(|| format!("failed to load `{}`", path.display()))?;
+        for function in registry.functions {
+            let (module, name) = (function.module.clone(), function.name.clone());
+            imports.define(
+                &module,
+                &name,
+                host_function(store, &env, library.clone(), function),
+            );
+        }
+    }
+    Ok(env)
+}
+
+/// Gives the memory of `instance` to the host functions of `env`.
+pub fn set_instance(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<ImportEnv>,
+    instance: &Instance,
+) {
+    env.as_mut(store).memory = instance.exports.get_memory("memory").ok().cloned();
+}
+
+/// Loads the library at `path` and calls its registration function.
+fn load(path: &Path) -> Result<(Arc<Library>, Registry)> {
+    let library = unsafe { Library::new(path) }?;
+    let mut registry = Registry::default();
+    unsafe {
+        let register_imports: Symbol<RegisterImports> = library
+            .get(REGISTER_SYMBOL)
+            .context("the library exports no `wasmer_register_imports` function")?;
+        register_imports(&mut registry as *mut Registry as *mut c_void, register);
+    }
+    if let Some(error) = registry.errors.first() {
+        bail!("{}", error);
+    }
+    Ok((Arc::new(library), registry))
+}
+
+/// A function calling `function`, which is kept loaded by `library`.
+fn host_function(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<ImportEnv>,
+    library: Arc<Library>,
+    function: HostFunction,
+) -> Function {
+    let ty = function.ty.clone();
+    let call = move |env: FunctionEnvMut<ImportEnv>, args: &[Value]| {
+        let _library = &library;
+        let params = args.iter().map(RawValue::new).collect::<Vec<_>>();
+        let mut results = vec![RawValue { i64: 0 }; function.ty.results().len()];
+        let (memory, memory_size) = match env.data().memory.as_ref() {
+            Some(memory) => {
+                let view = memory.view(&env);
+                (view.data_ptr(), view.data_size())
+            }
+            None => (ptr::null_mut(), 0),
+        };
+        let status = unsafe {
+            (function.func)(
+                function.data.0,
+                params.as_ptr(),
+                results.as_mut_ptr(),
+                memory,
+                memory_size,
+            )
+        };
+        if status != 0 {
+            return Err(RuntimeError::new(format!(
+                "the host function `{}.{}` failed with status {}",
+                function.module, function.name, status
+            )));
+        }
+        Ok(results
+            .iter()
+            .zip(function.ty.results())
+            .map(|(result, ty)| result.value(*ty))
+            .collect())
+    };
+    Function::new_with_env(store, env, ty, call)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn add(
+        _data: *mut c_void,
+        params: *const RawValue,
+        results: *mut RawValue,
+        _memory: *mut u8,
+        _memory_size: u64,
+    ) -> i32 {
+        let params = slice::from_raw_parts(params, 2);
+        (*results).i64 = params[0].i64 + params[1].i64;
+        0
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = Registry::default();
+        let registry_ptr = &mut registry as *mut Registry as *mut c_void;
+        let status = unsafe {
+            register(
+                registry_ptr,
+                b"env\0".as_ptr() as *const c_char,
+                b"add\0".as_ptr() as *const c_char,
+                [1, 1].as_ptr(),
+                2,
+                [1].as_ptr(),
+                1,
+                add,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, 0);
+        let status = unsafe {
+            register(
+                registry_ptr,
+                b"env\0".as_ptr() as *const c_char,
+                b"bad\0".as_ptr() as *const c_char,
+                [7].as_ptr(),
+                1,
+                ptr::null(),
+                0,
+                add,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, 1);
+
+        assert_eq!(registry.errors, ["`env.bad` has an invalid parameter type"]);
+        let function = &registry.functions[0];
+        assert_eq!(
+            (function.module.as_str(), function.name.as_str()),
+            ("env", "add")
+        );
+        assert_eq!(
+            function.ty,
+            FunctionType::new([Type::I64, Type::I64], [Type::I64])
+        );
+
+        let params = [RawValue::new(&Value::I64(2)), RawValue::new(&Value::I64(3))];
+        let mut results = [RawValue { i64: 0 }];
+        let status = unsafe {
+            (function.func)(
+                function.data.0,
+                params.as_ptr(),
+                results.as_mut_ptr(),
+                ptr::null_mut(),
+                0,
+            )
+        };
+        assert_eq!(status, 0);
+        assert_eq!(results[0].value(Type::I64), Value::I64(5));
+    }
+}
@@ -0,0 +1,230 @@
+//! The limits of `wasmer run` on the resources used by the module:
+//! its running time and its memory, so untrusted modules can be run
+//! safely. The number of instructions it executes is limited by the
+//! fuel of `--fuel`, see `crate::store::StoreOptions`.
+//!
+//! A module exceeding a limit makes wasmer fail with a diagnostic
+//! code of its own, hence with a distinct exit status, see
+//! `Code::exit_code`.
+
+use crate::diagnostic::{Code, Diagnostic};
+use crate::error::PrettyError;
+use crate::utils::{parse_bytes, parse_duration};
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use wasmer::vm::{MemoryError, MemoryStyle, TableStyle, VMMemory, VMMemoryDefinition};
+use wasmer::vm::{VMTable, VMTableDefinition};
+use wasmer::{BaseTunables, Engine, MemoryType, Pages, Store, TableType, Tunables, WASM_PAGE_SIZE};
+use wasmer_vm::LinearMemory;
+
+#[derive(Debug, Clone, Parser, Default)]
+/// The limits of the resources used by the module
+pub struct Limits {
+    /// Terminate the module when it runs for longer than this, in
+    /// seconds or with a unit, e.g. `500ms`, `30s` or `2m`
+    #[clap(
+        long = "timeout",
+        name = "DURATION",
+        parse(try_from_str = parse_duration)
+    )]
+    timeout: Option<Duration>,
+
+    /// The most memory the module can use, in bytes or with a unit,
+    /// e.g. `64M`; growing its memory beyond fails
+    #[clap(
+        long = "max-memory",
+        name = "BYTES",
+        parse(try_from_str = parse_bytes)
+    )]
+    max_memory: Option<u64>,
+
+    /// Whether the module tried to grow its memory beyond the limit.
+    #[clap(skip)]
+    memory_exceeded: Arc<AtomicBool>,
+}
+
+impl Limits {
+    /// A store of `engine` whose memories are limited by `--max-memory`.
+    pub fn store(&self, engine: Engine) -> Store {
+        match self.max_memory {
+            Some(bytes) => {
+                let pages = (bytes / WASM_PAGE_SIZE as u64).min(u32::MAX as u64) as u32;
+                let tunables = LimitingTunables {
+                    base: BaseTunables::for_target(engine.target()),
+                    limit: Pages(pages),
+                    exceeded: self.memory_exceeded.clone(),
+                };
+                Store::new_with_tunables(engine, tunables)
+            }
+            None => Store::new(engine),
+        }
+    }
+
+    /// Terminates wasmer when `--timeout` elapses, if given; the
+    /// module should start running right after.
+    pub fn start_timer(&self) {
+        if let Some(timeout) = self.timeout {
+            thread::spawn(move || {
+                thread::sleep(timeout);
+                PrettyError::report::<()>(Err(crate::diagnostic!(
+                    Code::Timeout,
+                    "the module did not finish within {:?}",
+                    timeout
+                )));
+            });
+        }
+    }
+
+    /// Gives the error of a module failing after it tried to grow its
+    /// memory beyond `--max-memory` the code of this limit.
+    pub fn check_memory(&self, result: Result<()>) -> Result<()> {
+        if let Some(bytes) = self.max_memory {
+            if result.is_err() && self.memory_exceeded.load(Ordering::SeqCst) {
+                return result.context(Diagnostic::new(
+                    Code::MemoryLimit,
+                    format!("the module exceeded the memory limit of {} bytes", bytes),
+                ));
+            }
+        }
+        result
+    }
+}
+
+/// Tunables limiting the memories to `limit` pages, and recording
+/// when a module tries to grow one beyond.
+struct LimitingTunables {
+    base: BaseTunables,
+    limit: Pages,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl LimitingTunables {
+    /// Lowers the maximum of `ty` to the limit.
+    fn adjust_memory(&self, ty: &MemoryType) -> MemoryType {
+        let limit = self.limit.max(ty.minimum);
+        let mut adjusted = *ty;
+        adjusted.maximum = Some(ty.maximum.map_or(limit, |max| max.min(limit)));
+        adjusted
+    }
+
+    /// Ensures the minimum of `ty` is within the limit, before the
+    /// memory is created.
+    fn validate_memory(&self, ty: &MemoryType) -> Result<(), MemoryError> {
+        if ty.minimum > self.limit {
+            self.exceeded.store(true, Ordering::SeqCst);
+            return Err(MemoryError::Generic(format!(
+                "the module needs {} pages of memory, but the limit is {} pages",
+                ty.minimum.0, self.limit.0
+            )));
+        }
+        Ok(())
+    }
+
+    fn limit_memory(&self, memory: VMMemory) -> VMMemory {
+        VMMemory(Box::new(LimitedMemory {
+            memory,
+            limit: self.limit,
+            exceeded: self.exceeded.clone(),
+        }))
+    }
+}
+
+impl Tunables for LimitingTunables {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style(&self.adjust_memory(memory))
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        self.validate_memory(ty)?;
+        let adjusted = self.adjust_memory(ty);
+        let memory = self.base.create_host_memory(&adjusted, style)?;
+        Ok(self.limit_memory(memory))
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        self.validate_memory(ty)?;
+        let adjusted = self.adjust_memory(ty);
+        let memory = self
+            .base
+            .create_vm_memory(&adjusted, style, vm_definition_location)?;
+        Ok(self.limit_memory(memory))
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
+/// A memory which records the attempts to grow it beyond the limit.
+#[derive(Debug)]
+struct LimitedMemory {
+    memory: VMMemory,
+    limit: Pages,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl LinearMemory for LimitedMemory {
+    fn ty(&self) -> MemoryType {
+        self.memory.ty()
+    }
+
+    fn size(&self) -> Pages {
+        self.memory.size()
+    }
+
+    fn style(&self) -> MemoryStyle {
+        self.memory.style()
+    }
+
+    fn grow(&mut self, delta: Pages) -> Result<Pages, MemoryError> {
+        let current = self.size();
+        if current.0.saturating_add(delta.0) > self.limit.0 {
+            self.exceeded.store(true, Ordering::SeqCst);
+            return Err(MemoryError::CouldNotGrow {
+                current,
+                attempted_delta: delta,
+            });
+        }
+        self.memory.grow(delta)
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.memory.vmmemory()
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>> {
+        let memory = self.memory.try_clone()?;
+        Some(Box::new(Self {
+            memory: VMMemory(memory),
+            limit: self.limit,
+            exceeded: self.exceeded.clone(),
+        }))
+    }
+}
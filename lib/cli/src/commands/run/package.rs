@@ -0,0 +1,421 @@
+//! Packages, i.e. directories or tar archives holding a `wasmer.toml`
+//! manifest, run with `wasmer run ./package --command NAME`.
+//!
+//! The manifest declares the modules of the package, the commands
+//! running them, the directories of the package mapped in the guest
+//! and the environment variables of the guest:
+//!
+//! ```toml
+//! [[module]]
+//! name = "server"
+//! source = "server.wasm"
+//!
+//! [[command]]
+//! name = "serve"
+//! module = "server"
+//! args = ["--port", "8080"]
+//!
+//! [fs]
+//! "/public" = "public"
+//!
+//! [env]
+//! LOG_LEVEL = "info"
+//! ```
+//!
+//! Paths of the manifest are relative to the package, and can't go
+//! outside of it.
+
+use anyhow::{Context, Result};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use toml::value::{Table, Value};
+use wasmer_vfs::{mem_fs, FileSystem};
+
+/// The name of the package manifest.
+pub const MANIFEST_FILE: &str = "wasmer.toml";
+
+/// A module of a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestModule {
+    pub name: String,
+    /// The path of the module, relative to the package.
+    pub source: PathBuf,
+}
+
+/// A command of a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestCommand {
+    pub name: String,
+    /// The name of the module the command runs.
+    pub module: String,
+    /// The arguments passed before the ones of the command line.
+    pub args: Vec<String>,
+}
+
+/// The contents of a `wasmer.toml` manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub modules: Vec<ManifestModule>,
+    pub commands: Vec<ManifestCommand>,
+    /// The guest directories, and the directories of the package
+    /// they map.
+    pub fs: Vec<(String, PathBuf)>,
+    pub env: Vec<(String, String)>,
+}
+
+impl Manifest {
+    /// Parses a manifest.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let table = match contents.parse::<Value>()? {
+            Value::Table(table) => table,
+            _ => bail!("the manifest is not a TOML table"),
+        };
+
+        let mut manifest = Self::default();
+        for (key, value) in table {
+            match key.as_str() {
+                // The metadata of the package don't matter to run it.
+                "package" => {}
+                "module" => {
+                    for module in tables(&key, value)? {
+                        manifest.modules.push(ManifestModule {
+                            name: string(&module, "module", "name")?,
+                            source: relative_path(&string(&module, "module", "source")?)?,
+                        });
+                    }
+                }
+                "command" => {
+                    for command in tables(&key, value)? {
+                        let args = match command.get("args") {
+                            Some(Value::Array(args)) => args
+                                .iter()
+                                .map(|arg| {
+                                    arg.as_str()
+                                        .map(str::to_string)
+                                        .ok_or_else(|| anyhow!("`command.args` must be strings"))
+                                })
+                                .collect::<Result<_>>()?,
+                            Some(_) => bail!("`command.args` must be an array"),
+                            None => vec![],
+                        };
+                        manifest.commands.push(ManifestCommand {
+                            name: string(&command, "command", "name")?,
+                            module: string(&command, "command", "module")?,
+                            args,
+                        });
+                    }
+                }
+                "fs" => {
+                    for (guest, host) in table_of_strings(&key, value)? {
+                        manifest.fs.push((guest, relative_path(&host)?));
+                    }
+                }
+                "env" => manifest.env = table_of_strings(&key, value)?,
+                _ => bail!("unknown section `{}`", key),
+            }
+        }
+
+        for command in &manifest.commands {
+            if !manifest
+                .modules
+                .iter()
+                .any(|module| module.name == command.module)
+            {
+                bail!(
+                    "the command `{}` runs the unknown module `{}`",
+                    command.name,
+                    command.module
+                );
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// Returns the tables of an array of tables, like `[[module]]`.
+fn tables(key: &str, value: Value) -> Result<Vec<Table>> {
+    match value {
+        Value::Array(values) => values
+            .into_iter()
+            .map(|value| match value {
+                Value::Table(table) => Ok(table),
+                _ => bail!("`{}` must be an array of tables", key),
+            })
+            .collect(),
+        _ => bail!("`{}` must be an array of tables", key),
+    }
+}
+
+/// Returns the entries of a table of strings, like `[env]`.
+fn table_of_strings(key: &str, value: Value) -> Result<Vec<(String, String)>> {
+    match value {
+        Value::Table(table) => table
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::String(value) => Ok((name, value)),
+                _ => bail!("`{}.{}` must be a string", key, name),
+            })
+            .collect(),
+        _ => bail!("`{}` must be a table", key),
+    }
+}
+
+fn string(table: &Table, section: &str, key: &str) -> Result<String> {
+    match table.get(key) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(_) => bail!("`{}.{}` must be a string", section, key),
+        None => bail!("`{}.{}` is missing", section, key),
+    }
+}
+
+/// Checks a path of the manifest stays inside of the package.
+fn relative_path(path: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(path);
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("`{}` is outside of the package", path.display());
+    }
+
+    Ok(path)
+}
+
+/// A package, along with the command to run.
+#[derive(Clone)]
+pub struct Package {
+    /// The directory of the package, or the root of `archive`.
+    root: PathBuf,
+    /// The contents of the package, when it's an archive.
+    archive: Option<mem_fs::FileSystem>,
+    manifest: Manifest,
+    /// The index of the command to run.
+    command: usize,
+}
+
+impl Package {
+    /// Whether `path` designates a package rather than a module.
+    pub fn is_package(path: &Path) -> bool {
+        path.is_dir() || path.extension() == Some(OsStr::new("tar"))
+    }
+
+    /// Opens the package at `path`, to run the command `command`. It
+    /// can be omitted if the package has a single command.
+    pub fn open(path: &Path, command: Option<&str>) -> Result<Self> {
+        let (root, archive) = if path.is_dir() {
+            (path.to_path_buf(), None)
+        } else {
+            let file =
+                File::open(path).with_context(|| format!("failed to open `{}`", path.display()))?;
+            let archive = mem_fs::FileSystem::from_tar(file)
+                .with_context(|| format!("failed to read the archive `{}`", path.display()))?;
+            (PathBuf::from("/"), Some(archive))
+        };
+
+        let mut package = Self {
+            root,
+            archive,
+            manifest: Manifest::default(),
+            command: 0,
+        };
+        let contents = String::from_utf8(package.read(Path::new(MANIFEST_FILE))?)
+            .with_context(|| format!("`{}` is not valid UTF-8", MANIFEST_FILE))?;
+        package.manifest = Manifest::parse(&contents)
+            .with_context(|| format!("invalid `{}` in `{}`", MANIFEST_FILE, path.display()))?;
+
+        let commands = &package.manifest.commands;
+        package.command = match command {
+            Some(name) => commands
+                .iter()
+                .position(|command| command.name == name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "the package has no command `{}`, expected one of: {}",
+                        name,
+                        command_names(commands)
+                    )
+                })?,
+            None if commands.len() == 1 => 0,
+            None if commands.is_empty() => bail!("the package has no commands"),
+            None => bail!(
+                "the package has several commands, pass one of them with `--command`: {}",
+                command_names(commands)
+            ),
+        };
+
+        Ok(package)
+    }
+
+    /// Reads a file of the package.
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let path = self.root.join(path);
+        let mut contents = Vec::new();
+        match &self.archive {
+            Some(archive) => {
+                archive
+                    .new_open_options()
+                    .read(true)
+                    .open(&path)
+                    .map_err(|err| anyhow!("{}", err))
+                    .and_then(|mut file| Ok(file.read_to_end(&mut contents)?))
+                    .with_context(|| format!("failed to read `{}`", path.display()))?;
+            }
+            None => {
+                contents = std::fs::read(&path)
+                    .with_context(|| format!("failed to read `{}`", path.display()))?;
+            }
+        }
+
+        Ok(contents)
+    }
+
+    /// The command to run.
+    pub fn command(&self) -> &ManifestCommand {
+        &self.manifest.commands[self.command]
+    }
+
+    /// The module the command runs.
+    pub fn module(&self) -> &ManifestModule {
+        let name = &self.command().module;
+        self.manifest
+            .modules
+            .iter()
+            .find(|module| &module.name == name)
+            .expect("the modules of the commands are checked by `Manifest::parse`")
+    }
+
+    /// Reads the module the command runs.
+    pub fn module_contents(&self) -> Result<Vec<u8>> {
+        self.read(&self.module().source)
+    }
+
+    /// The guest directories, and the directories they map in the
+    /// file system of the package.
+    pub fn mapped_dirs(&self) -> Vec<(String, PathBuf)> {
+        self.manifest
+            .fs
+            .iter()
+            .map(|(guest, path)| (guest.clone(), self.root.join(path)))
+            .collect()
+    }
+
+    /// The environment variables of the guest.
+    pub fn env(&self) -> &[(String, String)] {
+        &self.manifest.env
+    }
+
+    /// The file system of the guest, when it isn't the host one.
+    pub fn file_system(&self) -> Option<Box<dyn FileSystem>> {
+        self.archive
+            .clone()
+            .map(|archive| Box::new(archive) as Box<dyn FileSystem>)
+    }
+}
+
+impl fmt::Debug for Package {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Package")
+            .field("root", &self.root)
+            .field("archive", &self.archive.is_some())
+            .field("manifest", &self.manifest)
+            .field("command", &self.command)
+            .finish()
+    }
+}
+
+fn command_names(commands: &[ManifestCommand]) -> String {
+    commands
+        .iter()
+        .map(|command| format!("`{}`", command.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+        [package]
+        name = "hello"
+        version = "0.1.0"
+
+        [[module]]
+        name = "hello"
+        source = "bin/hello.wasm"
+
+        [[command]]
+        name = "hello"
+        module = "hello"
+
+        [[command]]
+        name = "greet"
+        module = "hello"
+        args = ["--name", "world"]
+
+        [fs]
+        "/data" = "data"
+
+        [env]
+        GREETING = "hi"
+    "#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        assert_eq!(
+            manifest.modules,
+            vec![ManifestModule {
+                name: "hello".to_string(),
+                source: PathBuf::from("bin/hello.wasm"),
+            }]
+        );
+        assert_eq!(manifest.commands.len(), 2);
+        assert_eq!(manifest.commands[1].args, vec!["--name", "world"]);
+        assert_eq!(
+            manifest.fs,
+            vec![("/data".to_string(), PathBuf::from("data"))]
+        );
+        assert_eq!(
+            manifest.env,
+            vec![("GREETING".to_string(), "hi".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_manifest() {
+        assert!(Manifest::parse("[[command]]\nname = \"a\"\nmodule = \"b\"\n").is_err());
+        assert!(Manifest::parse("[fs]\n\"/data\" = \"../data\"\n").is_err());
+        assert!(Manifest::parse("[fs]\n\"/data\" = \"/etc\"\n").is_err());
+        assert!(Manifest::parse("[env]\nLEVEL = 1\n").is_err());
+        assert!(Manifest::parse("[unknown]\n").is_err());
+    }
+
+    #[test]
+    fn test_open_package_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(MANIFEST_FILE), MANIFEST).unwrap();
+        std::fs::create_dir(dir.path().join("bin")).unwrap();
+        std::fs::write(dir.path().join("bin/hello.wasm"), b"\0asm").unwrap();
+
+        assert!(Package::is_package(dir.path()));
+        assert!(
+            Package::open(dir.path(), None).is_err(),
+            "the command is ambiguous"
+        );
+        assert!(Package::open(dir.path(), Some("unknown")).is_err());
+
+        let package = Package::open(dir.path(), Some("greet")).unwrap();
+        assert_eq!(package.command().name, "greet");
+        assert_eq!(package.module_contents().unwrap(), b"\0asm");
+        assert_eq!(
+            package.mapped_dirs(),
+            vec![("/data".to_string(), dir.path().join("data"))]
+        );
+        assert!(package.file_system().is_none());
+    }
+}
@@ -0,0 +1,551 @@
+//! Where `wasmer run` finds its module: a file, a URL, e.g.
+//! `https://example.com/app.wasm`, or a package of the registry, e.g.
+//! `registry:namespace/name@1.0.0`.
+//!
+//! Remote modules are downloaded into the cache directory, and checked
+//! against `--checksum` when given. A download is reused without the
+//! network when its contents can't have changed: its checksum is
+//! pinned and matches, or it is a module of a pinned version of a
+//! package. In offline mode, any download already made is reused.
+
+use crate::diagnostic;
+use crate::diagnostic::Code;
+#[cfg(feature = "http")]
+use crate::fetch::FetchPolicy;
+use anyhow::Result;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The default GraphQL endpoint of the registry, see the `registry`
+/// setting.
+#[cfg(feature = "http")]
+const DEFAULT_REGISTRY: &str = "https://registry.wapm.io/graphql";
+
+/// The number of redirections followed when downloading a module.
+#[cfg(feature = "http")]
+const MAX_REDIRECTS: u32 = 5;
+
+/// Where a module comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleSource {
+    /// A local file, or a package directory or archive
+    File(PathBuf),
+    /// A module downloaded over HTTP
+    Url(String),
+    /// A module of a package of the registry
+    Registry {
+        /// The name of the package, `namespace/name`
+        package: String,
+        /// The version of the package, the latest one if `None`
+        version: Option<String>,
+    },
+}
+
+impl ModuleSource {
+    /// The source designated by the `FILE` argument of `wasmer run`.
+    pub fn parse(path: &Path) -> Self {
+        let arg = path.to_string_lossy();
+        if let Some(package) = arg.strip_prefix("registry:") {
+            match package.split_once('@') {
+                Some((package, version)) => Self::Registry {
+                    package: package.to_string(),
+                    version: Some(version.to_string()),
+                },
+                None => Self::Registry {
+                    package: package.to_string(),
+                    version: None,
+                },
+            }
+        } else if arg.starts_with("https://") || arg.starts_with("http://") {
+            Self::Url(arg.to_string())
+        } else {
+            Self::File(path.to_path_buf())
+        }
+    }
+
+    /// Whether the module has to be downloaded.
+    pub fn is_remote(&self) -> bool {
+        !matches!(self, Self::File(_))
+    }
+
+    /// The name of the module, the first argument it receives: the
+    /// last segment of its URL or of the name of its package.
+    pub fn name(&self) -> String {
+        match self {
+            Self::File(path) => path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            Self::Url(url) => {
+                let path = url.split(&['?', '#'][..]).next().unwrap_or_default();
+                path.trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            }
+            Self::Registry { package, .. } => {
+                package.rsplit('/').next().unwrap_or_default().to_string()
+            }
+        }
+    }
+
+    /// Downloads the module into `downloads` if needed, and returns its
+    /// path. `module` selects the module of a package which has
+    /// several, and `checksum` is the SHA-256 digest the module must
+    /// have, see `parse_checksum`.
+    #[cfg(feature = "http")]
+    pub fn fetch(
+        &self,
+        downloads: &Path,
+        module: Option<&str>,
+        checksum: Option<&str>,
+        policy: &FetchPolicy,
+    ) -> Result<PathBuf> {
+        let (key, immutable) = match self {
+            Self::File(path) => return Ok(path.clone()),
+            Self::Url(url) => (url.clone(), checksum.is_some()),
+            Self::Registry { version, .. } => (
+                format!("{}#{}", self, module.unwrap_or_default()),
+                version.is_some() || checksum.is_some(),
+            ),
+        };
+        let path = downloads.join(format!("{}.wasm", sha256(key.as_bytes())));
+        if path.is_file() && (immutable || policy.offline) {
+            // The download may have been made without the checksum, or
+            // with another one: it is then made again, unless offline.
+            match verify(&std::fs::read(&path)?, checksum) {
+                Ok(()) => return Ok(path),
+                Err(error) if policy.offline => return Err(error),
+                Err(_) => {}
+            }
+        }
+
+        let url = match self {
+            Self::Registry { package, version } => {
+                registry_module_url(package, version.as_deref(), module, policy)?
+            }
+            _ => key,
+        };
+        let contents = policy.fetch(&format!("`{}`", self), || http::get(&url, policy))?;
+        verify(&contents, checksum)?;
+
+        // Other processes only ever see complete modules, as renaming
+        // is atomic.
+        std::fs::create_dir_all(downloads)?;
+        let temporary_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&temporary_path, contents)?;
+        std::fs::rename(temporary_path, &path)?;
+        Ok(path)
+    }
+
+    /// Fails, as downloading modules needs the `http` feature.
+    #[cfg(not(feature = "http"))]
+    pub fn fetch(
+        &self,
+        _downloads: &Path,
+        _module: Option<&str>,
+        _checksum: Option<&str>,
+        _policy: &crate::fetch::FetchPolicy,
+    ) -> Result<PathBuf> {
+        match self {
+            Self::File(path) => Ok(path.clone()),
+            _ => Err(diagnostic!(
+                Code::FeatureDisabled,
+                "can't download `{}`, this wasmer is built without the `http` feature",
+                self
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ModuleSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Url(url) => write!(f, "{}", url),
+            Self::Registry {
+                package,
+                version: Some(version),
+            } => write!(f, "registry:{}@{}", package, version),
+            Self::Registry {
+                package,
+                version: None,
+            } => write!(f, "registry:{}", package),
+        }
+    }
+}
+
+/// Parses the `--checksum` of a module: its SHA-256 digest in
+/// hexadecimal, optionally prefixed by `sha256:`.
+pub fn parse_checksum(checksum: &str) -> Result<String> {
+    let digest = checksum.strip_prefix("sha256:").unwrap_or(checksum);
+    if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!(
+            "`{}` is not a SHA-256 digest, expected 64 hexadecimal digits",
+            checksum
+        );
+    }
+    Ok(digest.to_ascii_lowercase())
+}
+
+/// Checks that `contents` have the SHA-256 digest `checksum`, if any.
+#[cfg(feature = "http")]
+fn verify(contents: &[u8], checksum: Option<&str>) -> Result<()> {
+    if let Some(checksum) = checksum {
+        let digest = sha256(contents);
+        if digest != checksum {
+            return Err(diagnostic!(
+                Code::ChecksumMismatch,
+                "the module has the checksum sha256:{}, but sha256:{} is expected",
+                digest,
+                checksum
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The SHA-256 digest of `bytes`, in hexadecimal.
+#[cfg(feature = "http")]
+fn sha256(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Queries the registry for the URL of `module` of `package`, which
+/// may be omitted if the package has a single module.
+#[cfg(feature = "http")]
+fn registry_module_url(
+    package: &str,
+    version: Option<&str>,
+    module: Option<&str>,
+    policy: &FetchPolicy,
+) -> Result<String> {
+    let config = crate::config::WasmerConfig::load()?;
+    let registry = config.registry().unwrap_or(DEFAULT_REGISTRY);
+    let query = serde_json::json!({
+        "query": "query($name: String!, $version: String) { \
+                  getPackageVersion(name: $name, version: $version) { \
+                  version modules { name publicUrl } } }",
+        "variables": { "name": package, "version": version },
+    });
+    let response = policy.fetch(&format!("`{}` from the registry", package), || {
+        http::post_json(registry, &query.to_string(), policy)
+    })?;
+    let response: serde_json::Value = serde_json::from_slice(&response)?;
+
+    let package_version = &response["data"]["getPackageVersion"];
+    if package_version.is_null() {
+        bail!(
+            "the package `{}` is not in the registry `{}`{}",
+            package,
+            registry,
+            version.map_or_else(String::new, |version| format!(
+                " with the version {}",
+                version
+            ))
+        );
+    }
+    let modules = package_version["modules"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let names = modules
+        .iter()
+        .filter_map(|module| module["name"].as_str())
+        .collect::<Vec<_>>();
+    let found = match module {
+        Some(module) => modules
+            .iter()
+            .find(|candidate| candidate["name"].as_str() == Some(module)),
+        None if modules.len() == 1 => modules.first(),
+        None => bail!(
+            "the package `{}` has the modules {}, choose one with `--command`",
+            package,
+            names.join(", ")
+        ),
+    };
+    found
+        .and_then(|module| module["publicUrl"].as_str())
+        .map(str::to_string)
+        .ok_or_else(|| {
+            anyhow!(
+                "the package `{}` has no module `{}`, it has: {}",
+                package,
+                module.unwrap_or_default(),
+                names.join(", ")
+            )
+        })
+}
+
+#[cfg(feature = "http")]
+mod http {
+    use super::MAX_REDIRECTS;
+    use crate::fetch::{FetchError, FetchPolicy};
+    use http_req::request::Method;
+    use http_req::uri::Uri;
+    use std::convert::TryFrom;
+
+    /// The body of `url`, following its redirections. A redirection
+    /// from HTTPS to plain HTTP is refused.
+    pub fn get(url: &str, policy: &FetchPolicy) -> Result<Vec<u8>, FetchError> {
+        let mut url = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let uri = parse(&url)?;
+            let mut body = vec![];
+            let response = policy
+                .request(&uri)
+                .header("User-Agent", "wasmer")
+                .send(&mut body)
+                .map_err(FetchError::send)?;
+            let status = response.status_code();
+            if status.is_redirect() {
+                let location = response
+                    .headers()
+                    .get("Location")
+                    .ok_or_else(|| FetchError::Permanent(anyhow!("`{}` redirects nowhere", url)))?;
+                url = redirection(&url, location)?;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(FetchError::status(
+                    status,
+                    anyhow!("`{}` replied with the status {}", url, status),
+                ));
+            }
+            return Ok(body);
+        }
+        Err(FetchError::Permanent(anyhow!(
+            "`{}` redirects more than {} times",
+            url,
+            MAX_REDIRECTS
+        )))
+    }
+
+    /// The body of the response to the JSON `request` posted to `url`.
+    pub fn post_json(
+        url: &str,
+        request: &str,
+        policy: &FetchPolicy,
+    ) -> Result<Vec<u8>, FetchError> {
+        let uri = parse(url)?;
+        let length = request.len().to_string();
+        let mut body = vec![];
+        let response = policy
+            .request(&uri)
+            .method(Method::POST)
+            .header("User-Agent", "wasmer")
+            .header("Content-Type", "application/json")
+            .header("Content-Length", &length)
+            .body(request.as_bytes())
+            .send(&mut body)
+            .map_err(FetchError::send)?;
+        if !response.status_code().is_success() {
+            return Err(FetchError::status(
+                response.status_code(),
+                anyhow!(
+                    "`{}` replied with the status {}",
+                    url,
+                    response.status_code()
+                ),
+            ));
+        }
+        Ok(body)
+    }
+
+    /// The URL `url` redirects to with the `Location` header
+    /// `location`, unless it downgrades HTTPS to plain HTTP.
+    pub fn redirection(url: &str, location: &str) -> Result<String, FetchError> {
+        let target = resolve(url, location);
+        let is_https =
+            |url: &str| matches!(scheme(url), Some(scheme) if scheme.eq_ignore_ascii_case("https"));
+        if is_https(url) && !is_https(&target) {
+            return Err(FetchError::Permanent(anyhow!(
+                "`{}` redirects to `{}`, which isn't secure",
+                url,
+                target
+            )));
+        }
+        Ok(target)
+    }
+
+    /// Resolves `reference`, e.g. `../app.wasm`, against the absolute
+    /// URL `base`, as RFC 3986 does.
+    pub fn resolve(base: &str, reference: &str) -> String {
+        let scheme = match (scheme(reference), scheme(base)) {
+            (Some(_), _) | (None, None) => return reference.to_string(),
+            (None, Some(scheme)) => scheme,
+        };
+        if reference.starts_with("//") {
+            return format!("{}:{}", scheme, reference);
+        }
+
+        // The base is `scheme://authority/path?query#fragment`.
+        let rest = base[scheme.len() + 1..].trim_start_matches('/');
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (rest, base_query) = rest.split_at(rest.find('?').unwrap_or(rest.len()));
+        let (authority, base_path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+
+        let end_of_path = reference.find(&['?', '#'][..]).unwrap_or(reference.len());
+        let (path, suffix) = reference.split_at(end_of_path);
+        let (path, suffix) = if path.is_empty() {
+            // The query of the base is kept unless a new one is given.
+            let query = if suffix.starts_with('?') {
+                ""
+            } else {
+                base_query
+            };
+            (base_path.to_string(), format!("{}{}", query, suffix))
+        } else if path.starts_with('/') {
+            (path.to_string(), suffix.to_string())
+        } else {
+            let directory = &base_path[..base_path.rfind('/').map_or(0, |i| i + 1)];
+            let directory = if directory.is_empty() { "/" } else { directory };
+            (format!("{}{}", directory, path), suffix.to_string())
+        };
+
+        format!(
+            "{}://{}{}{}",
+            scheme,
+            authority,
+            remove_dot_segments(&path),
+            suffix
+        )
+    }
+
+    /// The scheme of `url`, e.g. `https`, if it is absolute.
+    fn scheme(url: &str) -> Option<&str> {
+        let (scheme, _) = url.split_once(':')?;
+        let mut chars = scheme.chars();
+        let valid = chars.next()?.is_ascii_alphabetic()
+            && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        if valid {
+            Some(scheme)
+        } else {
+            None
+        }
+    }
+
+    /// Removes the `.` and `..` segments of the absolute `path`.
+    fn remove_dot_segments(path: &str) -> String {
+        let mut segments = Vec::new();
+        for segment in path.split('/').skip(1) {
+            match segment {
+                "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+        // `/a/b/..` is the directory `/a/`.
+        if path.ends_with("/.") || path.ends_with("/..") {
+            segments.push("");
+        }
+        format!("/{}", segments.join("/"))
+    }
+
+    /// Parses `url`, which is never worth fetching again if invalid.
+    fn parse(url: &str) -> Result<Uri<'_>, FetchError> {
+        Uri::try_from(url).map_err(|error| {
+            FetchError::Permanent(
+                anyhow::Error::new(error).context(format!("invalid URL `{}`", url)),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sources() {
+        let source = ModuleSource::parse(Path::new("https://example.com/app.wasm?v=2"));
+        assert_eq!(
+            source,
+            ModuleSource::Url("https://example.com/app.wasm?v=2".to_string())
+        );
+        assert_eq!(source.name(), "app.wasm");
+
+        let source = ModuleSource::parse(Path::new("registry:syrusakbary/cowsay@0.3.0"));
+        assert_eq!(
+            source,
+            ModuleSource::Registry {
+                package: "syrusakbary/cowsay".to_string(),
+                version: Some("0.3.0".to_string())
+            }
+        );
+        assert_eq!(source.name(), "cowsay");
+        assert_eq!(source.to_string(), "registry:syrusakbary/cowsay@0.3.0");
+
+        let source = ModuleSource::parse(Path::new("./app.wasm"));
+        assert!(!source.is_remote());
+        assert_eq!(source.name(), "app.wasm");
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let digest = "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08";
+        assert_eq!(
+            parse_checksum(&format!("sha256:{}", digest)).unwrap(),
+            digest.to_ascii_lowercase()
+        );
+        assert!(parse_checksum(digest).is_ok());
+        assert!(parse_checksum("sha256:9f86").is_err());
+        assert!(parse_checksum(&digest.replace('F', "g")).is_err());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_redirections() {
+        use super::http::{redirection, resolve};
+
+        let base = "https://example.com/a/b/app.wasm?v=2";
+        assert_eq!(
+            resolve(base, "https://cdn.example.com/app.wasm"),
+            "https://cdn.example.com/app.wasm"
+        );
+        assert_eq!(
+            resolve(base, "//cdn.example.com/app.wasm"),
+            "https://cdn.example.com/app.wasm"
+        );
+        assert_eq!(
+            resolve(base, "/c/app.wasm"),
+            "https://example.com/c/app.wasm"
+        );
+        assert_eq!(
+            resolve(base, "app-2.wasm"),
+            "https://example.com/a/b/app-2.wasm"
+        );
+        assert_eq!(
+            resolve(base, "../c/./app.wasm?v=3"),
+            "https://example.com/a/c/app.wasm?v=3"
+        );
+        assert_eq!(
+            resolve(base, "?v=3"),
+            "https://example.com/a/b/app.wasm?v=3"
+        );
+        assert_eq!(resolve(base, ".."), "https://example.com/a/");
+        assert_eq!(
+            resolve("http://localhost:8080", "app.wasm"),
+            "http://localhost:8080/app.wasm"
+        );
+
+        assert_eq!(
+            redirection(
+                "http://example.com/app.wasm",
+                "https://example.com/app.wasm"
+            )
+            .unwrap(),
+            "https://example.com/app.wasm"
+        );
+        assert!(redirection(base, "http://example.com/app.wasm").is_err());
+        assert!(redirection(base, "HTTPS://example.com/app.wasm").is_ok());
+    }
+}
@@ -0,0 +1,150 @@
+//! Reruns the module when its file, or the directories given to it,
+//! change, for `wasmer run --watch`.
+//!
+//! The module runs in a child `wasmer` process, since it may exit the
+//! process; the child reuses the cache, so only a changed module is
+//! compiled again.
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::Duration;
+use wasmer_vfs::{host_fs, FileSystem, WatchHandle};
+
+/// Set in the environment of the child process, which runs the module
+/// rather than watching it.
+const CHILD_VAR: &str = "WASMER_WATCH_CHILD";
+
+/// How often the changes are polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait for more changes before rerunning, as a compiler
+/// usually writes its output in several steps.
+const SETTLE_DELAY: Duration = Duration::from_millis(200);
+
+/// Whether this process is the child running the module.
+pub fn is_child() -> bool {
+    env::var_os(CHILD_VAR).is_some()
+}
+
+/// Runs the module of `file` with the arguments of this process, and
+/// reruns it whenever `file` or one of `dirs` change, until wasmer is
+/// interrupted.
+pub fn run(file: &Path, dirs: &[PathBuf]) -> Result<()> {
+    let watcher = Watcher::new(file, dirs)?;
+    let program = env::current_exe().context("failed to find the wasmer executable")?;
+
+    loop {
+        let mut child = Command::new(&program)
+            .args(env::args_os().skip(1))
+            .env(CHILD_VAR, "1")
+            .spawn()
+            .with_context(|| format!("failed to run `{}`", program.display()))?;
+
+        if !wait_child(&mut child, &watcher)? {
+            watcher.wait_change();
+        }
+        eprintln!(
+            "{} changes detected, running `{}` again",
+            "watch:".blue().bold(),
+            file.display()
+        );
+    }
+}
+
+/// Waits for `child` to exit, or kills it when a change comes first.
+/// Returns whether a change came.
+fn wait_child(child: &mut Child, watcher: &Watcher) -> Result<bool> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            let status = match status.code() {
+                Some(code) => format!("exited with status {}", code),
+                None => "was killed".to_string(),
+            };
+            eprintln!(
+                "{} the module {}, waiting for changes",
+                "watch:".blue().bold(),
+                status
+            );
+            return Ok(false);
+        }
+        if watcher.changed() {
+            // The child may have exited in the meantime.
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(true);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Watches the file of the module, and the directories given to it.
+struct Watcher {
+    file: PathBuf,
+    /// The directory of the file, which may be replaced rather than
+    /// written to.
+    file_dir: WatchHandle,
+    dirs: Vec<WatchHandle>,
+}
+
+impl Watcher {
+    fn new(file: &Path, dirs: &[PathBuf]) -> Result<Self> {
+        let fs = host_fs::FileSystem;
+        let file = file
+            .canonicalize()
+            .with_context(|| format!("failed to watch `{}`", file.display()))?;
+        let file_dir = fs
+            .watch(file.parent().unwrap_or_else(|| Path::new("/")), false)
+            .with_context(|| format!("failed to watch `{}`", file.display()))?;
+        let dirs = dirs
+            .iter()
+            .map(|dir| {
+                fs.watch(dir, true)
+                    .with_context(|| format!("failed to watch `{}`", dir.display()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            file,
+            file_dir,
+            dirs,
+        })
+    }
+
+    /// Whether something changed since the last call; the changes
+    /// coming shortly after are included.
+    fn changed(&self) -> bool {
+        if !self.drain() {
+            return false;
+        }
+        loop {
+            thread::sleep(SETTLE_DELAY);
+            if !self.drain() {
+                return true;
+            }
+        }
+    }
+
+    /// Waits until something changes.
+    fn wait_change(&self) {
+        while !self.changed() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Consumes the pending events, and returns whether one of them
+    /// is a change of the watched paths.
+    fn drain(&self) -> bool {
+        let mut changed = false;
+        for event in self.file_dir.pending() {
+            changed |= event.paths().contains(&self.file.as_path());
+        }
+        for dir in self.dirs.iter() {
+            changed |= dir.pending().count() > 0;
+        }
+        changed
+    }
+}
@@ -34,6 +34,6 @@ impl SelfUpdate {
 
     #[cfg(target_os = "windows")]
     fn inner_execute(&self) -> Result<()> {
-        bail!("Self update is not supported on Windows. Use install instructions on the Wasmer homepage: https://wasmer.io");
+        Err(crate::diagnostic!(crate::diagnostic::Code::UnsupportedPlatform, "Self update is not supported on Windows. Use install instructions on the Wasmer homepage: https://wasmer.io"))
     }
 }
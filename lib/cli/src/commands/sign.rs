@@ -0,0 +1,105 @@
+use crate::signature::{signature_path, SecretKey};
+use anyhow::{Context, Result};
+use clap::{Parser, ValueHint};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer sign` subcommand
+pub struct Sign {
+    /// The module to sign, a WebAssembly file or an artifact made by
+    /// `wasmer compile`
+    #[clap(
+        name = "FILE",
+        parse(from_os_str),
+        value_hint = ValueHint::FilePath,
+        required_unless_present = "KEY_PATH"
+    )]
+    path: Option<PathBuf>,
+
+    /// The secret key to sign with, made by `--generate-key`
+    #[clap(
+        long = "key",
+        name = "SECRET_KEY",
+        parse(from_os_str),
+        value_hint = ValueHint::FilePath,
+        required_unless_present = "KEY_PATH"
+    )]
+    key: Option<PathBuf>,
+
+    /// The signature file, `FILE.minisig` by default
+    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str), value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Generate a key pair instead: the secret key in this file, and
+    /// the public key in the same file with `.pub` appended, to give
+    /// to `--verify-key`
+    #[clap(
+        long = "generate-key",
+        name = "KEY_PATH",
+        parse(from_os_str),
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = &["FILE", "SECRET_KEY"]
+    )]
+    generate_key: Option<PathBuf>,
+}
+
+impl Sign {
+    /// Runs logic for the `sign` subcommand
+    pub fn execute(&self) -> Result<()> {
+        match (&self.generate_key, &self.path, &self.key) {
+            (Some(key_path), _, _) => self
+                .generate(key_path)
+                .context(format!("failed to generate `{}`", key_path.display())),
+            (None, Some(path), Some(key)) => self
+                .sign(path, key)
+                .context(format!("failed to sign `{}`", path.display())),
+            _ => bail!("give a FILE and its `--key`, or `--generate-key`"),
+        }
+    }
+
+    fn generate(&self, key_path: &Path) -> Result<()> {
+        let public_key_path = PathBuf::from(format!("{}.pub", key_path.display()));
+        for path in [key_path, &public_key_path] {
+            if path.exists() {
+                bail!("`{}` already exists", path.display());
+            }
+        }
+        let (secret_key, public_key) = SecretKey::generate()?;
+
+        // The secret key is only ever readable by its owner, even while
+        // it is written.
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(key_path)?
+            .write_all(secret_key.to_file().as_bytes())?;
+        fs::write(&public_key_path, public_key.to_file())?;
+        eprintln!(
+            "✔ Secret key written to `{}`, keep it private.\n✔ Public key written to `{}`.",
+            key_path.display(),
+            public_key_path.display()
+        );
+        Ok(())
+    }
+
+    fn sign(&self, path: &Path, key: &Path) -> Result<()> {
+        let secret_key = SecretKey::parse(
+            &fs::read_to_string(key)
+                .with_context(|| format!("failed to read `{}`", key.display()))?,
+        )?;
+        let contents = fs::read(path)?;
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let signature = secret_key.sign(&contents, &file_name);
+        let output = self.output.clone().unwrap_or_else(|| signature_path(path));
+        fs::write(&output, signature.to_file())?;
+        eprintln!("✔ Signature written to `{}`.", output.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueHint};
+use std::path::PathBuf;
+use wasmer::is_wasm;
+use wasmer_compiler::ModuleRewriter;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer transform` subcommand
+pub struct Transform {
+    /// Input file
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
+    path: PathBuf,
+
+    /// Output file
+    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str), value_hint = ValueHint::FilePath)]
+    output: PathBuf,
+
+    /// Move all the imports of a namespace to another one, e.g.
+    /// `wasi_unstable=wasi_snapshot_preview1`
+    #[clap(long = "alias-namespace", value_name = "FROM=TO", parse(try_from_str = parse_pair))]
+    namespace_aliases: Vec<(String, String)>,
+
+    /// Rename a single import, e.g. `env.write=wasi_snapshot_preview1.fd_write`
+    #[clap(long = "rename-import", value_name = "MODULE.NAME=MODULE.NAME", parse(try_from_str = parse_import_rename))]
+    import_renames: Vec<((String, String), (String, String))>,
+
+    /// Rename an export, e.g. `main=_start`
+    #[clap(long = "rename-export", value_name = "OLD=NEW", parse(try_from_str = parse_pair))]
+    export_renames: Vec<(String, String)>,
+
+    /// Export a new function forwarding to an exported function, e.g. `_start=run`
+    #[clap(long = "trampoline", value_name = "EXPORT=ALIAS", parse(try_from_str = parse_pair))]
+    trampolines: Vec<(String, String)>,
+}
+
+fn parse_pair(entry: &str) -> Result<(String, String)> {
+    match entry.split_once('=') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() => {
+            Ok((from.to_string(), to.to_string()))
+        }
+        _ => bail!("expected `FROM=TO`, got `{}`", entry),
+    }
+}
+
+fn parse_import(import: &str) -> Result<(String, String)> {
+    match import.split_once('.') {
+        Some((module, name)) if !module.is_empty() && !name.is_empty() => {
+            Ok((module.to_string(), name.to_string()))
+        }
+        _ => bail!("expected `MODULE.NAME`, got `{}`", import),
+    }
+}
+
+fn parse_import_rename(entry: &str) -> Result<((String, String), (String, String))> {
+    let (from, to) = parse_pair(entry)?;
+    Ok((parse_import(&from)?, parse_import(&to)?))
+}
+
+impl Transform {
+    /// Runs logic for the `transform` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to transform `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let mut rewriter = ModuleRewriter::new();
+        for (from, to) in self.namespace_aliases.iter() {
+            rewriter.alias_namespace(from, to);
+        }
+        for ((module, name), (new_module, new_name)) in self.import_renames.iter() {
+            rewriter.rename_import(module, name, new_module, new_name);
+        }
+        for (from, to) in self.export_renames.iter() {
+            rewriter.rename_export(from, to);
+        }
+        for (export, alias) in self.trampolines.iter() {
+            rewriter.add_trampoline(export, alias);
+        }
+
+        let module_contents = std::fs::read(&self.path)?;
+        #[cfg(feature = "wat")]
+        let module_contents = wasmer::wat2wasm(&module_contents)?.into_owned();
+        if !is_wasm(&module_contents) {
+            bail!("`wasmer transform` only transforms WebAssembly files");
+        }
+        let output = rewriter.rewrite(&module_contents)?;
+        std::fs::write(&self.output, output)
+            .context(format!("failed to write `{}`", self.output.display()))?;
+        eprintln!(
+            "✔ Module transformed successfully to `{}`.",
+            self.output.display()
+        );
+        Ok(())
+    }
+}
@@ -1,20 +1,49 @@
+use crate::diagnostic;
+use crate::diagnostic::Code;
 use crate::store::StoreOptions;
-use anyhow::{bail, Context, Result};
-use clap::Parser;
+use crate::warning;
+use anyhow::{Context, Result};
+use clap::{Parser, ValueHint};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use wasmer::*;
+use wasmer_compiler::wasmparser::{
+    self, BinaryReaderError, ImportSectionEntryType, Name, NameSectionReader, Payload, Validator,
+};
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer validate` subcommand
 pub struct Validate {
     /// File to validate as WebAssembly
-    #[clap(name = "FILE", parse(from_os_str))]
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
     path: PathBuf,
 
+    /// The target triple the module is meant for: wasmer warns about
+    /// the proposals it uses which the compiler doesn't enable by
+    /// default for it
+    #[clap(long = "target")]
+    target_triple: Option<Triple>,
+
     #[clap(flatten)]
     store: StoreOptions,
 }
 
+/// The proposals which can be enabled from the command line, with
+/// their flags, see `crate::common::WasmFeatures`.
+const PROPOSALS: [(&str, &str, fn(&mut Features) -> &mut bool); 5] = [
+    ("threads", "--enable-threads", |f| &mut f.threads),
+    ("SIMD", "--enable-simd", |f| &mut f.simd),
+    ("reference types", "--enable-reference-types", |f| {
+        &mut f.reference_types
+    }),
+    ("multi-value", "--enable-multi-value", |f| {
+        &mut f.multi_value
+    }),
+    ("bulk memory", "--enable-bulk-memory", |f| {
+        &mut f.bulk_memory
+    }),
+];
+
 impl Validate {
     /// Runs logic for the `validate` subcommand
     pub fn execute(&self) -> Result<()> {
@@ -22,13 +51,218 @@ impl Validate {
             .context(format!("failed to validate `{}`", self.path.display()))
     }
     fn inner_execute(&self) -> Result<()> {
-        let (store, _compiler_type) = self.store.get_store()?;
+        let target = match &self.target_triple {
+            Some(triple) => Target::new(triple.clone(), CpuFeature::set()),
+            None => Target::default(),
+        };
+        let (defaults, compiler_type) = self.store.get_default_features(&target)?;
+        let features = self.store.get_features(defaults.clone())?;
         let module_contents = std::fs::read(&self.path)?;
         if !is_wasm(&module_contents) {
-            bail!("`wasmer validate` only validates WebAssembly files");
+            return Err(diagnostic!(
+                Code::InvalidModule,
+                "`wasmer validate` only validates WebAssembly files"
+            ));
+        }
+
+        if let Err(err) = validate(&module_contents, &features) {
+            let mut message = format!("{}", Location::find(&module_contents, &err));
+            // A proposal the module needs may just not be enabled.
+            for (name, flag, feature) in PROPOSALS {
+                let mut enabled = features.clone();
+                if !*feature(&mut enabled) {
+                    *feature(&mut enabled) = true;
+                    if validate(&module_contents, &enabled).is_ok() {
+                        message.push_str(&format!(
+                            "\nthe module uses the {} proposal, enable it with `{}`",
+                            name, flag
+                        ));
+                    }
+                }
+            }
+            return Err(diagnostic!(Code::InvalidModule, "{}", message));
+        }
+
+        for (name, _flag, feature) in PROPOSALS {
+            let mut defaults = defaults.clone();
+            let mut disabled = features.clone();
+            if *feature(&mut disabled) && !*feature(&mut defaults) {
+                *feature(&mut disabled) = false;
+                if validate(&module_contents, &disabled).is_err() {
+                    warning!(
+                        "the module uses the {} proposal, which {} doesn't enable by default for `{}`",
+                        name,
+                        compiler_type.to_string(),
+                        target.triple()
+                    );
+                }
+            }
         }
-        Module::validate(&store, &module_contents)?;
         eprintln!("Validation passed for `{}`.", self.path.display());
         Ok(())
     }
 }
+
+/// Validates `wasm` with `features`, like the compilers do.
+fn validate(wasm: &[u8], features: &Features) -> Result<(), BinaryReaderError> {
+    let mut validator = Validator::new();
+    validator.wasm_features(wasmparser::WasmFeatures {
+        bulk_memory: features.bulk_memory,
+        threads: features.threads,
+        reference_types: features.reference_types,
+        multi_value: features.multi_value,
+        simd: features.simd,
+        tail_call: features.tail_call,
+        module_linking: features.module_linking,
+        multi_memory: features.multi_memory,
+        memory64: features.memory64,
+        exceptions: features.exceptions,
+        deterministic_only: false,
+        extended_const: features.extended_const,
+        relaxed_simd: features.relaxed_simd,
+        mutable_global: true,
+        saturating_float_to_int: true,
+        sign_extension: true,
+    });
+    validator.validate_all(wasm)?;
+    Ok(())
+}
+
+/// Where a validation error is: its offset, and the function and the
+/// instruction there, if it's in a function body.
+#[derive(Debug, PartialEq)]
+struct Location {
+    message: String,
+    offset: usize,
+    function: Option<u32>,
+    function_name: Option<String>,
+    instruction: Option<String>,
+}
+
+impl Location {
+    /// Finds `err` in `wasm`, reading as much as can be parsed.
+    fn find(wasm: &[u8], err: &BinaryReaderError) -> Self {
+        let offset = err.offset();
+        let mut location = Self {
+            message: err.message().to_string(),
+            offset,
+            function: None,
+            function_name: None,
+            instruction: None,
+        };
+        let mut functions = 0;
+        let mut names = HashMap::new();
+        for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+            match payload {
+                Ok(Payload::ImportSection(imports)) => {
+                    for import in imports.into_iter().flatten() {
+                        if let ImportSectionEntryType::Function(_) = import.ty {
+                            functions += 1;
+                        }
+                    }
+                }
+                Ok(Payload::CodeSectionEntry(body)) => {
+                    let range = body.range();
+                    if range.start <= offset && offset < range.end {
+                        location.function = Some(functions);
+                        location.instruction = instruction_at(&body, offset);
+                    }
+                    functions += 1;
+                }
+                Ok(Payload::CustomSection {
+                    name: "name",
+                    data,
+                    data_offset,
+                    ..
+                }) => names = function_names(data, data_offset),
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        location.function_name = location
+            .function
+            .and_then(|function| names.remove(&function));
+        location
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at offset {:#x}", self.message, self.offset)?;
+        if let Some(function) = self.function {
+            write!(f, ", in function {}", function)?;
+            if let Some(name) = &self.function_name {
+                write!(f, " (`{}`)", name)?;
+            }
+        }
+        if let Some(instruction) = &self.instruction {
+            write!(f, ", at instruction `{}`", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// The instruction of `body` at `offset`, or the last one before if
+/// `offset` is in the middle of one.
+fn instruction_at(body: &wasmparser::FunctionBody, offset: usize) -> Option<String> {
+    let mut reader = body.get_operators_reader().ok()?;
+    let mut instruction = None;
+    while !reader.eof() {
+        match reader.read_with_offset() {
+            Ok((operator, operator_offset)) if operator_offset <= offset => {
+                instruction = Some(format!("{:?}", operator));
+            }
+            _ => break,
+        }
+    }
+    instruction
+}
+
+/// The names of the functions in the `name` section.
+fn function_names(data: &[u8], offset: usize) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    if let Ok(mut reader) = NameSectionReader::new(data, offset) {
+        while let Ok(subsection) = reader.read() {
+            if let Name::Function(function_names) = subsection {
+                if let Ok(mut map) = function_names.get_map() {
+                    for _ in 0..map.get_count() {
+                        match map.read() {
+                            Ok(naming) => names.insert(naming.index, naming.name.to_string()),
+                            Err(_) => break,
+                        };
+                    }
+                }
+            }
+        }
+    }
+    names
+}
+
+#[cfg(all(test, feature = "wat"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_errors() {
+        let wasm = wat2wasm(
+            br#"(module
+                (import "env" "f" (func))
+                (func $add (param i32) (result i32)
+                    local.get 0
+                    i64.const 1
+                    i32.add))"#,
+        )
+        .unwrap();
+        let err = validate(&wasm, &Features::default()).unwrap_err();
+        let location = Location::find(&wasm, &err);
+        assert_eq!(location.function, Some(1));
+        assert_eq!(location.function_name.as_deref(), Some("add"));
+        assert_eq!(location.instruction.as_deref(), Some("I32Add"));
+
+        let wasm = wat2wasm(br#"(module (memory 1 1 shared))"#).unwrap();
+        let mut features = Features::default();
+        assert!(validate(&wasm, &features).is_err());
+        features.threads(true);
+        assert!(validate(&wasm, &features).is_ok());
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueHint};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use wasmer_vfs::fuse::FuseMount;
+use wasmer_vfs::mem_fs;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer vfs` subcommand
+pub enum Vfs {
+    /// Mount a file system image, or a tar archive, on the host until
+    /// it is unmounted, e.g. with `fusermount -u`
+    #[clap(name = "mount")]
+    Mount(Mount),
+}
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer vfs mount` subcommand
+pub struct Mount {
+    /// The image saved from a memory file system, or a tar archive
+    #[clap(name = "IMAGE", parse(from_os_str), value_hint = ValueHint::FilePath)]
+    image: PathBuf,
+
+    /// The directory to mount it on
+    #[clap(name = "MOUNTPOINT", parse(from_os_str), value_hint = ValueHint::DirPath)]
+    mountpoint: PathBuf,
+
+    /// Allow changes, which are written back to the image, in the same
+    /// format, when it is unmounted
+    #[clap(long = "writable")]
+    writable: bool,
+}
+
+/// The formats a file system can be read from.
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Image,
+    Tar,
+}
+
+impl Vfs {
+    /// Runs logic for the `vfs` subcommand
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            Self::Mount(mount) => mount.execute(),
+        }
+    }
+}
+
+impl Mount {
+    /// Runs logic for the `vfs mount` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute().context(format!(
+            "failed to mount `{}` on `{}`",
+            self.image.display(),
+            self.mountpoint.display()
+        ))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let bytes = fs::read(&self.image)?;
+        let (fs, format) = match mem_fs::FileSystem::load_from_bytes(&bytes) {
+            Ok(fs) => (fs, Format::Image),
+            Err(_) => match mem_fs::FileSystem::from_tar(bytes.as_slice()) {
+                Ok(fs) => (fs, Format::Tar),
+                Err(_) => bail!("the file is neither a file system image nor a tar archive"),
+            },
+        };
+
+        eprintln!(
+            "Mounted `{}` on `{}`, unmount it to exit.",
+            self.image.display(),
+            self.mountpoint.display()
+        );
+        FuseMount::new(Arc::new(fs.clone()))
+            .name(self.image.to_string_lossy())
+            .read_only(!self.writable)
+            .mount(&self.mountpoint)?;
+
+        if self.writable {
+            let bytes = match format {
+                Format::Image => fs.save_to_bytes()?,
+                Format::Tar => {
+                    let mut bytes = Vec::new();
+                    fs.to_tar(&mut bytes)?;
+                    bytes
+                }
+            };
+            fs::write(&self.image, bytes)?;
+            eprintln!("Saved the changes to `{}`.", self.image.display());
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 //! Runs a .wast WebAssembly test suites
 use crate::store::StoreOptions;
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueHint};
 use std::path::PathBuf;
 use wasmer_wast::Wast as WastSpectest;
 
@@ -9,7 +9,7 @@ use wasmer_wast::Wast as WastSpectest;
 /// The options for the `wasmer wast` subcommand
 pub struct Wast {
     /// Wast file to run
-    #[clap(name = "FILE", parse(from_os_str))]
+    #[clap(name = "FILE", parse(from_os_str), value_hint = ValueHint::FilePath)]
     path: PathBuf,
 
     #[clap(flatten)]
@@ -27,6 +27,9 @@ impl Wast {
             .context(format!("failed to test the wast `{}`", self.path.display()))
     }
     fn inner_execute(&self) -> Result<()> {
+        if self.store.metered() {
+            bail!("`--enable-metering` can't be used with `wast`, which compiles several modules with the same engine");
+        }
         let (store, _compiler_name) = self.store.get_store()?;
         let mut wast = WastSpectest::new_with_spectest(store);
         wast.fail_fast = self.fail_fast;
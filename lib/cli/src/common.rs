@@ -1,9 +1,13 @@
 //! Common module with common used structures across different
 //! commands.
+use crate::config::WasmerConfig;
 use crate::VERSION;
+use anyhow::Result;
 use clap::Parser;
 use std::env;
 use std::path::PathBuf;
+#[cfg(feature = "cache")]
+use wasmer_cache::FileSystemCache;
 
 #[derive(Debug, Parser, Clone, Default)]
 /// The WebAssembly features that can be passed through the
@@ -35,19 +39,58 @@ pub struct WasmFeatures {
 }
 
 /// Get the cache dir
-pub fn get_cache_dir() -> PathBuf {
-    match env::var("WASMER_CACHE_DIR") {
-        Ok(dir) => {
-            let mut path = PathBuf::from(dir);
+///
+/// It's the `cache-dir` setting (`WASMER_CACHE_DIR`) if any, see
+/// [`crate::config`], which fails if the configuration is invalid.
+pub fn get_cache_dir() -> Result<PathBuf> {
+    let config = WasmerConfig::load()?;
+    Ok(match config.cache_dir() {
+        Some(dir) => {
+            let mut path = dir;
             path.push(VERSION);
             path
         }
-        Err(_) => {
+        None => {
             // We use a temporal directory for saving cache files
             let mut temp_dir = env::temp_dir();
             temp_dir.push("wasmer");
             temp_dir.push(VERSION);
             temp_dir
         }
-    }
+    })
+}
+
+/// Get the directory where `wasmer run` downloads modules, in
+/// [`get_cache_dir`].
+pub fn get_downloads_dir() -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join("downloads"))
+}
+
+/// Get the cache of the modules compiled by `compiler`, in
+/// [`get_cache_dir`].
+///
+/// Its size is limited by the `cache-max-size` setting
+/// (`WASMER_CACHE_MAX_SIZE`), if any.
+#[cfg(feature = "cache")]
+pub fn get_cache(compiler: &str) -> Result<FileSystemCache> {
+    let mut cache = FileSystemCache::new(get_cache_dir()?.join(compiler))?;
+
+    let extension = "wasmu";
+    cache.set_cache_extension(Some(extension));
+    cache.set_max_size(WasmerConfig::load()?.cache_max_size());
+
+    // Hashing contributes noticeably to the start up of the very
+    // large modules, use a faster hash for them.
+    #[cfg(feature = "cache-xxhash")]
+    cache.set_key_strategy(|contents: &[u8]| {
+        use wasmer_cache::{Blake3, CacheKeyStrategy, Xxh3};
+
+        if contents.len() > 64 * 1024 * 1024 {
+            Xxh3.key(contents)
+        } else {
+            Blake3.key(contents)
+        }
+    });
+
+    Ok(cache)
 }
@@ -0,0 +1,461 @@
+//! Layered configuration of the Wasmer CLI.
+//!
+//! Settings are read from the following layers, each one overriding
+//! the previous ones:
+//!
+//! 1. the system file, `/etc/wasmer/config.toml`,
+//! 2. the user file, `$WASMER_DIR/config.toml` (`~/.wasmer/config.toml`
+//!    by default),
+//! 3. the project file, the closest `.wasmer.toml` found from the
+//!    current directory upwards,
+//! 4. the `WASMER_*` environment variables.
+//!
+//! Command line flags take precedence over all of them.
+
+use crate::diagnostic::{Code, Diagnostic};
+use anyhow::{Context, Result};
+use bytesize::ByteSize;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::value::{Table, Value};
+
+/// The name of the project configuration file.
+pub const PROJECT_CONFIG_FILE: &str = ".wasmer.toml";
+
+/// The known configuration keys, with their environment variable and
+/// description.
+pub const KEYS: &[(&str, &str, &str)] = &[
+    (
+        "compiler",
+        "WASMER_COMPILER",
+        "The default compiler: `singlepass`, `cranelift` or `llvm`",
+    ),
+    (
+        "cache-dir",
+        "WASMER_CACHE_DIR",
+        "The directory where compiled modules are cached",
+    ),
+    (
+        "cache-max-size",
+        "WASMER_CACHE_MAX_SIZE",
+        "The maximum size of the cached modules of each compiler, e.g. `1 GiB`",
+    ),
+    (
+        "features",
+        "WASMER_FEATURES",
+        "The WebAssembly proposals enabled by default, e.g. `simd,threads`",
+    ),
+    (
+        "connect-timeout",
+        "WASMER_CONNECT_TIMEOUT",
+        "The time to wait for a network connection, in seconds",
+    ),
+    (
+        "read-timeout",
+        "WASMER_READ_TIMEOUT",
+        "The time to wait for each network read, in seconds",
+    ),
+    (
+        "fetch-retries",
+        "WASMER_FETCH_RETRIES",
+        "The number of times a failed network fetch is retried",
+    ),
+    (
+        "registry",
+        "WASMER_REGISTRY",
+        "The GraphQL endpoint of the registry of `wasmer run registry:NAMESPACE/NAME`",
+    ),
+    (
+        "verify-key",
+        "WASMER_VERIFY_KEY",
+        "The public key the modules must be signed by to run, see `wasmer sign`",
+    ),
+    (
+        "offline",
+        "WASMER_OFFLINE",
+        "Whether to only use the cached resources, never the network: `true` or `false`",
+    ),
+    (
+        "error-format",
+        "WASMER_ERROR_FORMAT",
+        "How errors are printed: `human` or `json`, one object per error with the trap of the module",
+    ),
+];
+
+/// The names accepted in the `features` setting.
+const FEATURES: &[&str] = &[
+    "simd",
+    "threads",
+    "reference-types",
+    "multi-value",
+    "bulk-memory",
+    "all",
+];
+
+/// A configuration layer, from lowest to highest precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigLayer {
+    /// The system-wide configuration file.
+    System,
+    /// The configuration file of the current user.
+    User,
+    /// The configuration file of the current project.
+    Project,
+    /// The environment variables.
+    Env,
+}
+
+impl ConfigLayer {
+    /// The configuration file backing this layer, if any.
+    pub fn path(&self) -> Option<PathBuf> {
+        match self {
+            Self::System => Some(PathBuf::from("/etc/wasmer/config.toml")),
+            Self::User => {
+                let wasmer_dir = match env::var("WASMER_DIR") {
+                    Ok(dir) => PathBuf::from(dir),
+                    Err(_) => PathBuf::from(env::var("HOME").ok()?).join(".wasmer"),
+                };
+                Some(wasmer_dir.join("config.toml"))
+            }
+            Self::Project => {
+                let current_dir = env::current_dir().ok()?;
+                Some(
+                    current_dir
+                        .ancestors()
+                        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+                        .find(|path| path.is_file())
+                        .unwrap_or_else(|| current_dir.join(PROJECT_CONFIG_FILE)),
+                )
+            }
+            Self::Env => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::System => write!(f, "system"),
+            Self::User => write!(f, "user"),
+            Self::Project => write!(f, "project"),
+            Self::Env => write!(f, "env"),
+        }
+    }
+}
+
+/// The merged configuration of all the layers.
+#[derive(Debug, Clone, Default)]
+pub struct WasmerConfig {
+    values: Vec<(String, Value, ConfigLayer)>,
+}
+
+impl WasmerConfig {
+    /// Loads and merges all the configuration layers.
+    pub fn load() -> Result<Self> {
+        let mut config = Self::default();
+        for layer in [ConfigLayer::System, ConfigLayer::User, ConfigLayer::Project] {
+            let path = match layer.path() {
+                Some(path) if path.is_file() => path,
+                _ => continue,
+            };
+            for (key, value) in read_table(&path)? {
+                config.insert(&key, value, layer);
+            }
+        }
+        for (key, var, _) in KEYS {
+            if let Ok(value) = env::var(var) {
+                let value = parse_value(key, &value).with_context(|| {
+                    Diagnostic::new(Code::InvalidSetting, format!("invalid value for `{}`", var))
+                })?;
+                config.insert(key, value, ConfigLayer::Env);
+            }
+        }
+        Ok(config)
+    }
+
+    fn insert(&mut self, key: &str, value: Value, layer: ConfigLayer) {
+        self.values.retain(|(k, _, _)| k != key);
+        self.values.push((key.to_string(), value, layer));
+    }
+
+    /// Gets a setting along with the layer it comes from.
+    pub fn get(&self, key: &str) -> Option<(&Value, ConfigLayer)> {
+        self.values
+            .iter()
+            .find(|(k, _, _)| k == key)
+            .map(|(_, value, layer)| (value, *layer))
+    }
+
+    /// The default compiler.
+    pub fn compiler(&self) -> Option<&str> {
+        self.get("compiler").and_then(|(value, _)| value.as_str())
+    }
+
+    /// The directory where compiled modules are cached.
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        self.get("cache-dir")
+            .and_then(|(value, _)| value.as_str())
+            .map(PathBuf::from)
+    }
+
+    /// The maximum size of the cached modules of each compiler, in
+    /// bytes.
+    pub fn cache_max_size(&self) -> Option<u64> {
+        self.get("cache-max-size")
+            .and_then(|(value, _)| value.as_str())
+            .and_then(|size| size.parse::<ByteSize>().ok())
+            .map(|size| size.as_u64())
+    }
+
+    /// The WebAssembly proposals enabled by default.
+    pub fn features(&self) -> Vec<&str> {
+        match self.get("features") {
+            Some((Value::Array(features), _)) => {
+                features.iter().filter_map(Value::as_str).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The time to wait for a network connection, in seconds.
+    pub fn connect_timeout(&self) -> Option<u64> {
+        self.integer("connect-timeout")
+    }
+
+    /// The time to wait for each network read, in seconds.
+    pub fn read_timeout(&self) -> Option<u64> {
+        self.integer("read-timeout")
+    }
+
+    /// The number of times a failed network fetch is retried.
+    pub fn fetch_retries(&self) -> Option<u32> {
+        self.integer("fetch-retries")
+            .map(|retries| retries.min(u32::MAX as u64) as u32)
+    }
+
+    /// The GraphQL endpoint of the package registry.
+    pub fn registry(&self) -> Option<&str> {
+        self.get("registry").and_then(|(value, _)| value.as_str())
+    }
+
+    /// The public key the modules must be signed by.
+    pub fn verify_key(&self) -> Option<&str> {
+        self.get("verify-key").and_then(|(value, _)| value.as_str())
+    }
+
+    /// Whether to only use the cached resources, never the network.
+    pub fn offline(&self) -> bool {
+        self.get("offline")
+            .and_then(|(value, _)| value.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// How errors are printed: `human` or `json`.
+    pub fn error_format(&self) -> Option<&str> {
+        self.get("error-format")
+            .and_then(|(value, _)| value.as_str())
+    }
+
+    /// A setting which is a non-negative integer, see `check_value`.
+    fn integer(&self, key: &str) -> Option<u64> {
+        self.get(key)
+            .and_then(|(value, _)| value.as_integer())
+            .map(|value| value as u64)
+    }
+
+    /// Sets `key` to `value` in the configuration file of `layer` and
+    /// returns the path of that file.
+    pub fn set(layer: ConfigLayer, key: &str, value: &str) -> Result<PathBuf> {
+        let value = parse_value(key, value)?;
+        let path = layer
+            .path()
+            .ok_or_else(|| anyhow!("the `{}` layer can't be written to", layer))?;
+        let mut table = if path.is_file() {
+            read_table(&path)?
+        } else {
+            Table::new()
+        };
+        table.insert(key.to_string(), value);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, toml::to_string(&table)?)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Formats a setting the way it is given on the command line.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(format_value)
+            .collect::<Vec<_>>()
+            .join(","),
+        value => value.to_string(),
+    }
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    let table = contents
+        .parse::<Value>()
+        .with_context(|| format!("failed to parse `{}`", path.display()))?;
+    match table {
+        Value::Table(table) => {
+            for (key, value) in table.iter() {
+                check_value(key, value).with_context(|| {
+                    Diagnostic::new(
+                        Code::InvalidSetting,
+                        format!("invalid setting in `{}`", path.display()),
+                    )
+                })?;
+            }
+            Ok(table)
+        }
+        _ => bail!("`{}` is not a TOML table", path.display()),
+    }
+}
+
+/// Parses the string representation of a setting, as given on the
+/// command line or in an environment variable.
+fn parse_value(key: &str, value: &str) -> Result<Value> {
+    let value = match key {
+        "features" => Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|feature| !feature.is_empty())
+                .map(|feature| Value::String(feature.to_string()))
+                .collect(),
+        ),
+        "connect-timeout" | "read-timeout" | "fetch-retries" => Value::Integer(
+            value
+                .trim()
+                .parse()
+                .with_context(|| format!("`{}` is not an integer", value))?,
+        ),
+        "offline" => Value::Boolean(
+            value
+                .trim()
+                .parse()
+                .with_context(|| format!("`{}` is not `true` or `false`", value))?,
+        ),
+        _ => Value::String(value.to_string()),
+    };
+    check_value(key, &value)?;
+    Ok(value)
+}
+
+fn check_value(key: &str, value: &Value) -> Result<()> {
+    match (key, value) {
+        ("compiler", Value::String(compiler)) => {
+            if !["singlepass", "cranelift", "llvm"].contains(&compiler.as_str()) {
+                bail!(
+                    "unknown compiler `{}`, expected `singlepass`, `cranelift` or `llvm`",
+                    compiler
+                );
+            }
+        }
+        ("error-format", Value::String(format)) => {
+            if !["human", "json"].contains(&format.as_str()) {
+                bail!(
+                    "unknown error format `{}`, expected `human` or `json`",
+                    format
+                );
+            }
+        }
+        ("cache-max-size", Value::String(size)) => {
+            if size.parse::<ByteSize>().is_err() {
+                bail!("`{}` is not a size, e.g. `512 MiB`", size);
+            }
+        }
+        ("cache-dir" | "registry" | "verify-key", Value::String(_))
+        | ("offline", Value::Boolean(_)) => {}
+        ("connect-timeout" | "read-timeout" | "fetch-retries", Value::Integer(value)) => {
+            if *value < 0 {
+                bail!("`{}` must not be negative", key);
+            }
+        }
+        ("features", Value::Array(features)) => {
+            for feature in features {
+                match feature.as_str() {
+                    Some(feature) if FEATURES.contains(&feature) => {}
+                    _ => bail!(
+                        "unknown feature {}, expected one of: {}",
+                        feature,
+                        FEATURES.join(", ")
+                    ),
+                }
+            }
+        }
+        (key, _) if KEYS.iter().any(|(k, _, _)| *k == key) => {
+            bail!("unexpected type for `{}`", key)
+        }
+        (key, _) => bail!("unknown setting `{}`", key),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(
+            parse_value("features", "simd, threads").unwrap(),
+            Value::Array(vec![
+                Value::String("simd".to_string()),
+                Value::String("threads".to_string())
+            ])
+        );
+        assert!(parse_value("features", "simd,gc").is_err());
+        assert!(parse_value("compiler", "llvm").is_ok());
+        assert!(parse_value("compiler", "v8").is_err());
+        assert!(parse_value("unknown", "value").is_err());
+        assert_eq!(
+            parse_value("fetch-retries", "5").unwrap(),
+            Value::Integer(5)
+        );
+        assert!(parse_value("connect-timeout", "-1").is_err());
+        assert!(parse_value("read-timeout", "soon").is_err());
+        assert_eq!(
+            parse_value("offline", "true").unwrap(),
+            Value::Boolean(true)
+        );
+        assert!(parse_value("error-format", "json").is_ok());
+        assert!(parse_value("error-format", "xml").is_err());
+        assert!(parse_value("cache-max-size", "512 MiB").is_ok());
+        assert!(parse_value("cache-max-size", "a lot").is_err());
+    }
+
+    #[test]
+    fn test_layers_override() {
+        let mut config = WasmerConfig::default();
+        config.insert(
+            "compiler",
+            Value::String("llvm".to_string()),
+            ConfigLayer::User,
+        );
+        config.insert(
+            "cache-dir",
+            Value::String("/tmp/cache".to_string()),
+            ConfigLayer::User,
+        );
+        config.insert(
+            "compiler",
+            Value::String("singlepass".to_string()),
+            ConfigLayer::Project,
+        );
+        assert_eq!(config.compiler(), Some("singlepass"));
+        assert_eq!(config.get("compiler").unwrap().1, ConfigLayer::Project);
+        assert_eq!(config.cache_dir(), Some(PathBuf::from("/tmp/cache")));
+    }
+}
@@ -0,0 +1,396 @@
+//! Stable codes identifying the errors of the CLI, e.g. `WSM1201`
+//! for a path outside of the pre-opened directories, so tools can
+//! match errors without parsing their messages.
+//!
+//! The codes are grouped by area: `WSM1xxx` for the file system of
+//! the module, `WSM2xxx` for the module itself, `WSM3xxx` for the
+//! configuration and the features of the binary, and `WSM4xxx` for
+//! the network. A code is never reused once published; `wasmer
+//! explain` prints the catalog.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Creates an `anyhow::Error` carrying a diagnostic code, with a
+/// message formatted like `anyhow!`.
+#[macro_export]
+macro_rules! diagnostic {
+    ($code:expr, $($arg:tt)*) => {
+        anyhow::Error::new($crate::diagnostic::Diagnostic::new($code, format!($($arg)*)))
+    };
+}
+
+/// A diagnostic code of the catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Code {
+    /// A directory given with `--dir` or `--mapdir` does not exist.
+    PreopenNotFound,
+    /// The module accessed a path outside of the pre-opened directories.
+    MissingPreopen,
+    /// The module accessed a path escaping its pre-opened directory.
+    PathEscapesPreopen,
+    /// The host path of a file accessed by the module does not exist.
+    HostPathNotFound,
+    /// The host denied access to a file accessed by the module.
+    HostAccessDenied,
+    /// A file descriptor lacks the rights for an operation.
+    MissingRights,
+    /// The module is not valid WebAssembly.
+    InvalidModule,
+    /// The precompiled artifact was compiled for another target.
+    IncompatibleArtifact,
+    /// The module has no valid signature by the `--verify-key`.
+    InvalidSignature,
+    /// The module imports several versions of WASI.
+    MultipleWasiVersions,
+    /// The function to call is not exported by the module.
+    MissingExport,
+    /// The module ran longer than `--timeout`.
+    Timeout,
+    /// The module tried to use more memory than `--max-memory`.
+    MemoryLimit,
+    /// The module executed more instructions than its `--fuel`.
+    FuelExhausted,
+    /// A configuration setting is unknown or has an invalid value.
+    InvalidSetting,
+    /// No compiler is available for the host.
+    NoCompiler,
+    /// The compiler is not included in this binary.
+    CompilerDisabled,
+    /// A feature is not included in this binary.
+    FeatureDisabled,
+    /// The command is not supported on the host platform.
+    UnsupportedPlatform,
+    /// A resource could not be fetched from the network.
+    FetchFailed,
+    /// A resource had to be fetched in offline mode.
+    Offline,
+    /// A downloaded module doesn't match its `--checksum`.
+    ChecksumMismatch,
+}
+
+impl Code {
+    /// All the codes, in the order of the catalog.
+    pub const ALL: &'static [Self] = &[
+        Self::PreopenNotFound,
+        Self::MissingPreopen,
+        Self::PathEscapesPreopen,
+        Self::HostPathNotFound,
+        Self::HostAccessDenied,
+        Self::MissingRights,
+        Self::InvalidModule,
+        Self::IncompatibleArtifact,
+        Self::InvalidSignature,
+        Self::MultipleWasiVersions,
+        Self::MissingExport,
+        Self::Timeout,
+        Self::MemoryLimit,
+        Self::FuelExhausted,
+        Self::InvalidSetting,
+        Self::NoCompiler,
+        Self::CompilerDisabled,
+        Self::FeatureDisabled,
+        Self::UnsupportedPlatform,
+        Self::FetchFailed,
+        Self::Offline,
+        Self::ChecksumMismatch,
+    ];
+
+    /// The number of the code, e.g. `1201` for `WSM1201`.
+    pub fn number(&self) -> u16 {
+        match self {
+            Self::PreopenNotFound => 1101,
+            Self::MissingPreopen => 1201,
+            Self::PathEscapesPreopen => 1202,
+            Self::HostPathNotFound => 1203,
+            Self::HostAccessDenied => 1204,
+            Self::MissingRights => 1205,
+            Self::InvalidModule => 2101,
+            Self::IncompatibleArtifact => 2102,
+            Self::InvalidSignature => 2103,
+            Self::MultipleWasiVersions => 2201,
+            Self::MissingExport => 2202,
+            Self::Timeout => 2301,
+            Self::MemoryLimit => 2302,
+            Self::FuelExhausted => 2303,
+            Self::InvalidSetting => 3001,
+            Self::NoCompiler => 3101,
+            Self::CompilerDisabled => 3102,
+            Self::FeatureDisabled => 3104,
+            Self::UnsupportedPlatform => 3201,
+            Self::FetchFailed => 4101,
+            Self::Offline => 4102,
+            Self::ChecksumMismatch => 4103,
+        }
+    }
+
+    /// The exit status of wasmer failing with this code: the limits
+    /// of `wasmer run` have their own, so scripts can tell them from
+    /// the failures of the module.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Timeout => 124,
+            Self::MemoryLimit => 122,
+            Self::FuelExhausted => 123,
+            _ => 1,
+        }
+    }
+
+    /// A one-line summary of the error.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::PreopenNotFound => "pre-opened directory not found",
+            Self::MissingPreopen => "path outside of the pre-opened directories",
+            Self::PathEscapesPreopen => "path escaping its pre-opened directory",
+            Self::HostPathNotFound => "host path not found",
+            Self::HostAccessDenied => "host access denied",
+            Self::MissingRights => "missing file descriptor rights",
+            Self::InvalidModule => "invalid WebAssembly module",
+            Self::IncompatibleArtifact => "incompatible artifact",
+            Self::InvalidSignature => "missing or invalid signature",
+            Self::MultipleWasiVersions => "multiple WASI versions",
+            Self::MissingExport => "missing export",
+            Self::Timeout => "timeout",
+            Self::MemoryLimit => "memory limit exceeded",
+            Self::FuelExhausted => "fuel exhausted",
+            Self::InvalidSetting => "invalid setting",
+            Self::NoCompiler => "no compiler available",
+            Self::CompilerDisabled => "compiler not included",
+            Self::FeatureDisabled => "feature disabled",
+            Self::UnsupportedPlatform => "unsupported platform",
+            Self::FetchFailed => "fetch failed",
+            Self::Offline => "offline",
+            Self::ChecksumMismatch => "checksum mismatch",
+        }
+    }
+
+    /// What causes the error and how to fix it.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Self::PreopenNotFound => {
+                "A directory given to `--dir` or `--mapdir` does not exist on the host, or is \
+                 not a directory. Create it, or fix its path."
+            }
+            Self::MissingPreopen => {
+                "The module accessed a path which is not inside any pre-opened directory, so \
+                 the WASI sandbox hid it. Give the module access to the directory holding the \
+                 path with `--dir DIR` or `--mapdir GUEST_DIR:HOST_DIR`; `--explain-errors` \
+                 lists the directories the module can see."
+            }
+            Self::PathEscapesPreopen => {
+                "The module accessed a path going above its pre-opened directory with `..`. \
+                 The sandbox denies access outside of the pre-opened directories: pre-open the \
+                 parent directory instead."
+            }
+            Self::HostPathNotFound => {
+                "The path accessed by the module is inside a pre-opened directory, but the \
+                 host file it resolves to does not exist."
+            }
+            Self::HostAccessDenied => {
+                "The path accessed by the module resolves to a host file which the host denied \
+                 access to, e.g. because of its permissions or because it is read-only."
+            }
+            Self::MissingRights => {
+                "The file descriptor used by the module lacks the WASI rights for the \
+                 operation, e.g. writing to a file opened for reading."
+            }
+            Self::InvalidModule => {
+                "The file is not a valid WebAssembly module: it is malformed, or it uses a \
+                 proposal which is not enabled. Enable the proposals with `--enable-all` or \
+                 the `features` setting."
+            }
+            Self::IncompatibleArtifact => {
+                "The precompiled artifact, e.g. a `.wasmu` file made by `wasmer compile`, was \
+                 compiled for another architecture or operating system, with CPU features \
+                 the host lacks, or by an incompatible version of wasmer. Compile the module \
+                 again, with `--target` and `--cpu-features` matching the host."
+            }
+            Self::InvalidSignature => {
+                "A public key is given with `--verify-key` or the `verify-key` setting, but the \
+                 module has no signature, `FILE.minisig` by default, or its signature is not \
+                 made by that key or doesn't match the module. wasmer refuses to run or \
+                 compile it. Sign the module with `wasmer sign`."
+            }
+            Self::MultipleWasiVersions => {
+                "The module imports several versions of WASI, e.g. `wasi_unstable` and \
+                 `wasi_snapshot_preview1`, and `--deny-multiple-wasi-versions` is given."
+            }
+            Self::MissingExport => {
+                "The function to call, given by `--invoke` or the entry point, is not \
+                 exported by the module, or the export is not a function. `wasmer inspect` \
+                 lists the exports of a module."
+            }
+            Self::Timeout => {
+                "The module was still running when the time given by `--timeout` elapsed, so \
+                 it was terminated. wasmer exits with status 124 in this case, like `timeout`."
+            }
+            Self::MemoryLimit => {
+                "The module declares, or tried to grow its memory beyond, the size given by \
+                 `--max-memory`, so the growth failed. wasmer exits with status 122 when the \
+                 module fails after that."
+            }
+            Self::FuelExhausted => {
+                "The module executed more instructions than allowed by its fuel, given with \
+                 `--fuel`, so it was terminated. wasmer exits with status 123 in this case."
+            }
+            Self::InvalidSetting => {
+                "A configuration file or a `WASMER_*` environment variable holds an unknown \
+                 setting, or a value of the wrong type. `wasmer config list` shows the \
+                 known settings."
+            }
+            Self::NoCompiler => {
+                "This binary includes no compiler supporting the host architecture, so \
+                 modules can only be run once precompiled with `wasmer compile`."
+            }
+            Self::CompilerDisabled => {
+                "The compiler chosen with a flag or the `compiler` setting is not included in \
+                 this binary. Choose another one, or build wasmer with its feature."
+            }
+            Self::FeatureDisabled => {
+                "The command needs a feature which was disabled when this binary was built, \
+                 e.g. `http` to download release tarballs. Rebuild wasmer with the feature, \
+                 or use the flags avoiding it."
+            }
+            Self::UnsupportedPlatform => {
+                "The command is not supported on the host operating system, e.g. `binfmt` \
+                 outside of Linux."
+            }
+            Self::FetchFailed => {
+                "A resource could not be fetched from the network after all the retries. \
+                 Check the connection, or raise the `connect-timeout`, `read-timeout` and \
+                 `fetch-retries` settings."
+            }
+            Self::Offline => {
+                "A resource is not cached and offline mode, `--offline` or the `offline` \
+                 setting, forbids fetching it."
+            }
+            Self::ChecksumMismatch => {
+                "The SHA-256 digest of the module downloaded by `wasmer run` differs from the \
+                 one given with `--checksum`: the module changed since it was pinned, or it \
+                 was tampered with. It is not run, nor cached."
+            }
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WSM{:04}", self.number())
+    }
+}
+
+impl FromStr for Code {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = s
+            .trim()
+            .to_uppercase()
+            .trim_start_matches("WSM")
+            .parse::<u16>()
+            .map_err(|_| format!("`{}` is not a diagnostic code, e.g. `WSM1201`", s))?;
+        Self::ALL
+            .iter()
+            .find(|code| code.number() == number)
+            .copied()
+            .ok_or_else(|| format!("unknown diagnostic code `{}`", s))
+    }
+}
+
+/// An error carrying a diagnostic code.
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// The code of the error.
+    pub code: Code,
+    message: String,
+}
+
+impl Diagnostic {
+    /// An error with `code`, described by `message`.
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// The code attached to `error`, at any level of its context or
+    /// of its sources.
+    pub fn code_of(error: &anyhow::Error) -> Option<Code> {
+        error
+            .downcast_ref::<Self>()
+            .or_else(|| error.chain().find_map(|error| error.downcast_ref::<Self>()))
+            .map(|diagnostic| diagnostic.code)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Diagnostic {}
+
+/// Quotes `s` as a JSON string.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes() {
+        let numbers = Code::ALL.iter().map(Code::number).collect::<HashSet<_>>();
+        assert_eq!(numbers.len(), Code::ALL.len());
+
+        assert_eq!(Code::MissingPreopen.to_string(), "WSM1201");
+        assert_eq!("WSM3104".parse(), Ok(Code::FeatureDisabled));
+        assert_eq!("wsm1201".parse(), Ok(Code::MissingPreopen));
+        assert!("WSM9999".parse::<Code>().is_err());
+        assert!("E0425".parse::<Code>().is_err());
+
+        assert_eq!(Code::Timeout.exit_code(), 124);
+        assert_eq!(Code::InvalidModule.exit_code(), 1);
+    }
+
+    #[test]
+    fn test_code_of() {
+        let error = diagnostic!(Code::Offline, "can't fetch {} in offline mode", "it");
+        assert_eq!(error.to_string(), "can't fetch it in offline mode");
+        let error = Err::<(), _>(error).context("failed to build").unwrap_err();
+        assert_eq!(Diagnostic::code_of(&error), Some(Code::Offline));
+
+        let error = Err::<(), _>(anyhow!("connection reset"))
+            .context(Diagnostic::new(Code::FetchFailed, "failed to fetch it"))
+            .context("failed to build")
+            .unwrap_err();
+        assert_eq!(Diagnostic::code_of(&error), Some(Code::FetchFailed));
+
+        assert_eq!(Diagnostic::code_of(&anyhow!("uncoded")), None);
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a \"b\"\n\\"), r#""a \"b\"\n\\""#);
+    }
+}
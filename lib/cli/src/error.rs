@@ -1,8 +1,14 @@
 //! Implements `PretyError` to print pretty errors in the CLI (when they happen)
 
+use crate::config::WasmerConfig;
+use crate::diagnostic::{json_string, Diagnostic};
 use anyhow::{Chain, Error};
 use colored::*;
 use std::fmt::{self, Debug, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use wasmer::{InstantiationError, RuntimeError};
+use wasmer_types::TrapCode;
 
 /// A `PrettyError` for printing `anyhow::Error` nicely.
 pub struct PrettyError {
@@ -18,6 +24,52 @@ macro_rules! warning {
     })
 }
 
+/// How errors are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Colored prose, with the causes indented.
+    Human,
+    /// One JSON object per error, see [`PrettyError::json`].
+    Json,
+}
+
+/// The format set with `--error-format`: 0 if none, otherwise the
+/// format plus one.
+static ERROR_FORMAT: AtomicU8 = AtomicU8::new(0);
+
+impl ErrorFormat {
+    /// The format set with [`ErrorFormat::set`], otherwise the one of
+    /// the `error-format` setting, `human` by default.
+    pub fn current() -> Self {
+        match ERROR_FORMAT.load(Ordering::Relaxed) {
+            1 => Self::Human,
+            2 => Self::Json,
+            _ => WasmerConfig::load()
+                .ok()
+                .and_then(|config| config.error_format().and_then(|format| format.parse().ok()))
+                .unwrap_or(Self::Human),
+        }
+    }
+
+    /// Prints the errors in this format, whatever the `error-format`
+    /// setting.
+    pub fn set(self) {
+        ERROR_FORMAT.store(self as u8 + 1, Ordering::Relaxed);
+    }
+}
+
+impl FromStr for ErrorFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err("must be one of two options: `human` or `json`."),
+        }
+    }
+}
+
 impl PrettyError {
     /// Process a `Result` printing any errors and exiting
     /// the process after
@@ -25,11 +77,126 @@ impl PrettyError {
         std::process::exit(match result {
             Ok(_t) => 0,
             Err(error) => {
-                eprintln!("{:?}", PrettyError { error });
-                1
+                let status = Diagnostic::code_of(&error).map_or(1, |code| code.exit_code());
+                match ErrorFormat::current() {
+                    ErrorFormat::Json => eprintln!("{}", Self::json(&error, status)),
+                    ErrorFormat::Human => eprintln!("{:?}", PrettyError { error }),
+                }
+                status
             }
         });
     }
+
+    /// Exits with the status a module exited with. A non-zero status
+    /// is reported on stderr with the JSON error format, so it can be
+    /// told from a failure of wasmer.
+    pub fn report_exit(status: i32) -> ! {
+        if status != 0 && ErrorFormat::current() == ErrorFormat::Json {
+            eprintln!("{}", Self::exit_json(status));
+        }
+        std::process::exit(status);
+    }
+
+    /// Formats `error` as a JSON object with its diagnostic code, or
+    /// `null`, its message, the messages of its causes, the exit code
+    /// of wasmer and the trap of the module, if any, see
+    /// [`PrettyError::trap_json`].
+    pub fn json(error: &Error, exit_code: i32) -> String {
+        let code = match Diagnostic::code_of(error) {
+            Some(code) => json_string(&code.to_string()),
+            None => "null".to_string(),
+        };
+        let causes = error
+            .chain()
+            .skip(1)
+            .map(|cause| json_string(&cause.to_string()))
+            .collect::<Vec<_>>();
+        let trap = match runtime_error(error) {
+            Some(runtime_error) => Self::trap_json(runtime_error),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"code\":{},\"message\":{},\"causes\":[{}],\"exit_code\":{},\"trap\":{}}}",
+            code,
+            json_string(&error.to_string()),
+            causes.join(","),
+            exit_code,
+            trap
+        )
+    }
+
+    /// Formats the exit of a module with `status` like an error.
+    fn exit_json(status: i32) -> String {
+        format!(
+            "{{\"code\":null,\"message\":{},\"causes\":[],\"exit_code\":{},\"trap\":null}}",
+            json_string(&format!("the module exited with code {}", status)),
+            status
+        )
+    }
+
+    /// Formats the error of a module as a JSON object with its kind,
+    /// e.g. `unreachable`, or `host` for the errors of the host
+    /// functions, its message, and the wasm frames of its backtrace,
+    /// innermost first, with their source file and line when the module
+    /// has DWARF sections.
+    pub fn trap_json(error: &RuntimeError) -> String {
+        let kind = error.clone().to_trap().map_or("host", trap_kind);
+        let frames = error
+            .trace()
+            .iter()
+            .map(|frame| {
+                let number = |number: Option<u32>| number.map_or("null".to_string(), |n| n.to_string());
+                format!(
+                    "{{\"module\":{},\"function_index\":{},\"function\":{},\"function_offset\":{},\"module_offset\":{},\"file\":{},\"line\":{},\"column\":{}}}",
+                    json_string(frame.module_name()),
+                    frame.func_index(),
+                    frame.function_name().map_or("null".to_string(), json_string),
+                    frame.func_offset(),
+                    frame.module_offset(),
+                    frame.file().map_or("null".to_string(), json_string),
+                    number(frame.line()),
+                    number(frame.column())
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"kind\":{},\"message\":{},\"frames\":[{}]}}",
+            json_string(kind),
+            json_string(&error.message()),
+            frames.join(",")
+        )
+    }
+}
+
+/// The error of the module in `error`, if it trapped or one of its
+/// host functions failed, when running or instantiating it.
+fn runtime_error(error: &Error) -> Option<&RuntimeError> {
+    error.chain().find_map(|error| {
+        error.downcast_ref::<RuntimeError>().or_else(|| {
+            match error.downcast_ref::<InstantiationError>() {
+                Some(InstantiationError::Start(runtime_error)) => Some(runtime_error),
+                _ => None,
+            }
+        })
+    })
+}
+
+/// The name of a trap in the JSON error format.
+fn trap_kind(code: TrapCode) -> &'static str {
+    match code {
+        TrapCode::StackOverflow => "stack_overflow",
+        TrapCode::HeapAccessOutOfBounds => "heap_access_out_of_bounds",
+        TrapCode::HeapMisaligned => "heap_misaligned",
+        TrapCode::TableAccessOutOfBounds => "table_access_out_of_bounds",
+        TrapCode::OutOfBounds => "out_of_bounds",
+        TrapCode::IndirectCallToNull => "indirect_call_to_null",
+        TrapCode::BadSignature => "bad_signature",
+        TrapCode::IntegerOverflow => "integer_overflow",
+        TrapCode::IntegerDivisionByZero => "integer_division_by_zero",
+        TrapCode::BadConversionToInteger => "bad_conversion_to_integer",
+        TrapCode::UnreachableCodeReached => "unreachable",
+        TrapCode::UnalignedAtomic => "unaligned_atomic",
+    }
 }
 
 impl Debug for PrettyError {
@@ -40,7 +207,12 @@ impl Debug for PrettyError {
             return Debug::fmt(&error, f);
         }
 
-        write!(f, "{}", format!("{}: {}", "error".red(), error).bold())?;
+        let code = Diagnostic::code_of(error);
+        let label = match code {
+            Some(code) => format!("error[{}]", code),
+            None => "error".to_string(),
+        };
+        write!(f, "{}", format!("{}: {}", label.red(), error).bold())?;
         // write!(f, "{}", error)?;
 
         if let Some(cause) = error.source() {
@@ -58,6 +230,17 @@ impl Debug for PrettyError {
                 write!(indented, "{}", error)?;
             }
         }
+        if let Some(code) = code {
+            write!(
+                f,
+                "\n{}",
+                format!(
+                    "For more information about this error, try `wasmer explain {}`.",
+                    code
+                )
+                .dimmed()
+            )?;
+        }
         Ok(())
     }
 }
@@ -112,3 +295,40 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_vm::Trap;
+
+    #[test]
+    fn test_error_format() {
+        assert_eq!("json".parse(), Ok(ErrorFormat::Json));
+        assert_eq!("human".parse(), Ok(ErrorFormat::Human));
+        assert!("xml".parse::<ErrorFormat>().is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let error = Error::new(RuntimeError::from_trap(Trap::lib(
+            TrapCode::IntegerDivisionByZero,
+        )))
+        .context("failed to run `main.wasm`");
+        let json = PrettyError::json(&error, 1);
+        assert!(json.starts_with(
+            r#"{"code":null,"message":"failed to run `main.wasm`","causes":["RuntimeError: integer divide by zero"],"exit_code":1,"trap":{"kind":"integer_division_by_zero","message":"integer divide by zero","frames":["#
+        ));
+
+        let error = Error::new(InstantiationError::Start(RuntimeError::new("no file")));
+        assert!(
+            PrettyError::json(&error, 1).contains(r#""trap":{"kind":"host","message":"no file""#)
+        );
+
+        let error = anyhow!("no module");
+        assert!(PrettyError::json(&error, 1).ends_with(r#""exit_code":1,"trap":null}"#));
+        assert_eq!(
+            PrettyError::exit_json(3),
+            r#"{"code":null,"message":"the module exited with code 3","causes":[],"exit_code":3,"trap":null}"#
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! How the CLI fetches resources from the network, like the release
+//! tarballs of `wasmer create-exe`: the timeouts, the retries with
+//! their backoff, and whether to only use what is already cached.
+//!
+//! The policy is read from the configuration, see `crate::config`,
+//! the command line flags taking precedence.
+
+use crate::config::WasmerConfig;
+use crate::diagnostic;
+use crate::diagnostic::{Code, Diagnostic};
+use anyhow::Result;
+use clap::Parser;
+#[cfg(feature = "http")]
+use http_req::response::StatusCode;
+use std::thread;
+use std::time::Duration;
+
+/// The longest delay between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Parser, Default)]
+/// The options fetching resources from the network
+pub struct FetchOptions {
+    /// The time to wait for a connection, in seconds
+    #[clap(long = "connect-timeout", name = "CONNECT_SECONDS")]
+    connect_timeout: Option<u64>,
+
+    /// The time to wait for each read once connected, in seconds
+    #[clap(long = "read-timeout", name = "READ_SECONDS")]
+    read_timeout: Option<u64>,
+
+    /// The number of times a failed fetch is retried, waiting twice
+    /// longer each time
+    #[clap(long = "fetch-retries", name = "RETRIES")]
+    retries: Option<u32>,
+
+    /// Never use the network, only the resources already cached
+    #[clap(long = "offline")]
+    offline: bool,
+}
+
+impl FetchOptions {
+    /// The policy given by the configuration and these options.
+    pub fn policy(&self) -> Result<FetchPolicy> {
+        let config = WasmerConfig::load()?;
+        let default = FetchPolicy::default();
+
+        Ok(FetchPolicy {
+            connect_timeout: self
+                .connect_timeout
+                .or_else(|| config.connect_timeout())
+                .map_or(default.connect_timeout, Duration::from_secs),
+            read_timeout: self
+                .read_timeout
+                .or_else(|| config.read_timeout())
+                .map_or(default.read_timeout, Duration::from_secs),
+            retries: self
+                .retries
+                .or_else(|| config.fetch_retries())
+                .unwrap_or(default.retries),
+            offline: self.offline || config.offline(),
+            ..default
+        })
+    }
+}
+
+/// A failed attempt to fetch a resource, telling
+/// [`FetchPolicy::fetch`] whether to try again.
+#[derive(Debug)]
+pub enum FetchError {
+    /// Another attempt may succeed: the connection failed or timed
+    /// out, or the server failed.
+    Transient(anyhow::Error),
+    /// Another attempt would fail the same way, e.g. the URL is invalid
+    /// or the resource doesn't exist.
+    Permanent(anyhow::Error),
+}
+
+impl FetchError {
+    /// The error of a request which couldn't be sent or whose response
+    /// couldn't be read: only the I/O errors are transient.
+    #[cfg(feature = "http")]
+    pub fn send(error: http_req::error::Error) -> Self {
+        match error {
+            http_req::error::Error::IO(_) => Self::Transient(error.into()),
+            _ => Self::Permanent(error.into()),
+        }
+    }
+
+    /// The error of a request which got an unexpected `status`: the
+    /// server errors, the timeouts and the rate limits are transient.
+    #[cfg(feature = "http")]
+    pub fn status(status: StatusCode, error: anyhow::Error) -> Self {
+        if status.is_server_err()
+            || status == StatusCode::new(408)
+            || status == StatusCode::new(429)
+        {
+            Self::Transient(error)
+        } else {
+            Self::Permanent(error)
+        }
+    }
+
+    /// Adds `context` to the error.
+    pub fn context(self, context: &'static str) -> Self {
+        match self {
+            Self::Transient(error) => Self::Transient(error.context(context)),
+            Self::Permanent(error) => Self::Permanent(error.context(context)),
+        }
+    }
+}
+
+/// How resources are fetched from the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchPolicy {
+    /// The time to wait for a connection.
+    pub connect_timeout: Duration,
+    /// The time to wait for each read once connected.
+    pub read_timeout: Duration,
+    /// The number of times a failed fetch is retried.
+    pub retries: u32,
+    /// The delay before the first retry, doubled for each following
+    /// one, up to 30 seconds.
+    pub backoff: Duration,
+    /// Whether the network is never used, only the cached resources.
+    pub offline: bool,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            retries: 3,
+            backoff: Duration::from_secs(1),
+            offline: false,
+        }
+    }
+}
+
+impl FetchPolicy {
+    /// The delay before the retry following the failed `attempt`,
+    /// counted from 0.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .map_or(MAX_BACKOFF, |delay| delay.min(MAX_BACKOFF))
+    }
+
+    /// Runs `fetch` until it succeeds, fails with a
+    /// [`FetchError::Permanent`] error, or the retries are exhausted,
+    /// waiting between the attempts, and returns its last result.
+    /// `what` is fetched, e.g. `the latest release`; nothing is in
+    /// offline mode, which fails at once.
+    pub fn fetch<T>(
+        &self,
+        what: &str,
+        mut fetch: impl FnMut() -> Result<T, FetchError>,
+    ) -> Result<T> {
+        if self.offline {
+            return Err(diagnostic!(
+                Code::Offline,
+                "can't fetch {} in offline mode",
+                what
+            ));
+        }
+
+        let mut attempt = 0;
+        loop {
+            match fetch() {
+                Ok(value) => return Ok(value),
+                Err(FetchError::Transient(err)) if attempt < self.retries => {
+                    let delay = self.delay(attempt);
+                    eprintln!(
+                        "Failed to fetch {}, retrying in {}s: {:#}",
+                        what,
+                        delay.as_secs_f32(),
+                        err
+                    );
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(FetchError::Transient(err)) => {
+                    return Err(err.context(Diagnostic::new(
+                        Code::FetchFailed,
+                        format!("failed to fetch {} after {} attempts", what, attempt + 1),
+                    )))
+                }
+                Err(FetchError::Permanent(err)) => {
+                    return Err(err.context(Diagnostic::new(
+                        Code::FetchFailed,
+                        format!("failed to fetch {}", what),
+                    )))
+                }
+            }
+        }
+    }
+
+    /// A GET request of `uri`, with the timeouts of the policy.
+    #[cfg(feature = "http")]
+    pub fn request<'a>(&self, uri: &'a http_req::uri::Uri<'a>) -> http_req::request::Request<'a> {
+        let mut request = http_req::request::Request::new(uri);
+        request
+            .connect_timeout(Some(self.connect_timeout))
+            .read_timeout(Some(self.read_timeout));
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn policy(retries: u32) -> FetchPolicy {
+        FetchPolicy {
+            retries,
+            backoff: Duration::from_millis(1),
+            ..FetchPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = FetchPolicy::default();
+        assert_eq!(policy.delay(0), Duration::from_secs(1));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert_eq!(policy.delay(10), MAX_BACKOFF);
+        assert_eq!(policy.delay(40), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_retries() {
+        let attempts = Cell::new(0);
+        let flaky = || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                return Err(FetchError::Transient(anyhow!("connection reset")));
+            }
+            Ok(attempts.get())
+        };
+        assert_eq!(policy(2).fetch("a", flaky).unwrap(), 3);
+
+        attempts.set(0);
+        let err = policy(1).fetch("a", flaky).unwrap_err();
+        assert_eq!(attempts.get(), 2);
+        assert_eq!(err.to_string(), "failed to fetch a after 2 attempts");
+
+        let offline = FetchPolicy {
+            offline: true,
+            ..policy(3)
+        };
+        assert!(offline.fetch("a", || Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_permanent_errors_are_not_retried() {
+        let attempts = Cell::new(0);
+        let missing = || -> Result<(), FetchError> {
+            attempts.set(attempts.get() + 1);
+            Err(FetchError::Permanent(anyhow!("not found")))
+        };
+        let err = policy(3).fetch("a", missing).unwrap_err();
+        assert_eq!(attempts.get(), 1);
+        assert_eq!(err.to_string(), "failed to fetch a");
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_status_errors() {
+        let transient = |status| {
+            matches!(
+                FetchError::status(StatusCode::new(status), anyhow!("status")),
+                FetchError::Transient(_)
+            )
+        };
+        assert!(transient(500));
+        assert!(transient(503));
+        assert!(transient(429));
+        assert!(!transient(403));
+        assert!(!transient(404));
+    }
+}
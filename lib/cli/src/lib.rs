@@ -18,12 +18,17 @@ extern crate anyhow;
 
 pub mod commands;
 pub mod common;
+pub mod config;
+#[macro_use]
+pub mod diagnostic;
 #[macro_use]
 pub mod error;
 pub mod c_gen;
 pub mod cli;
+pub mod fetch;
 #[cfg(feature = "debug")]
 pub mod logging;
+pub mod signature;
 pub mod store;
 pub mod suggestions;
 pub mod utils;
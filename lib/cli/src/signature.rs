@@ -0,0 +1,417 @@
+//! Detached signatures of modules, so wasmer can refuse to run or
+//! compile the modules which are not signed by a trusted key.
+//!
+//! The keys and the signatures are Ed25519 ones, in the formats of
+//! minisign: its public keys are accepted, and so are the signatures
+//! of `minisign -S -l`, but not the prehashed ones it makes by
+//! default. The secret keys made by `wasmer sign --generate-key` are
+//! specific to wasmer.
+//!
+//! The key to verify the modules with is given by `--verify-key`, or
+//! the `verify-key` setting, see `crate::config`.
+
+use crate::config::WasmerConfig;
+use crate::diagnostic;
+use crate::diagnostic::Code;
+use anyhow::Result;
+use clap::Parser;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The extension of the signature of a module, added to its path.
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+
+#[derive(Debug, Clone, Parser, Default)]
+/// The options verifying the signature of a module
+pub struct VerifyOptions {
+    /// Only accept the module if it is signed by this public key, given
+    /// as a minisign key or the file holding it, e.g. made by `wasmer
+    /// sign --generate-key`. The `verify-key` setting by default
+    #[clap(long = "verify-key", name = "PUBLIC_KEY")]
+    verify_key: Option<String>,
+
+    /// The signature of the module, `FILE.minisig` by default. It is
+    /// required to verify a module downloaded from a URL or a registry
+    #[clap(long = "signature", name = "SIGNATURE", parse(from_os_str))]
+    signature: Option<PathBuf>,
+}
+
+impl VerifyOptions {
+    /// Whether the modules are verified: when a key is given, or can't
+    /// be known because the configuration is invalid.
+    pub fn is_enabled(&self) -> bool {
+        self.key().map_or(true, |key| key.is_some())
+    }
+
+    /// Checks the module at `path`, made of `contents`, is signed by
+    /// the key given, if any.
+    pub fn verify(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        match self.key()? {
+            Some(key) => self.verify_with(&key, path, contents),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the module downloaded from `source` can be verified,
+    /// before it is: its signature isn't downloaded with it, so it
+    /// must be given by `--signature`.
+    pub fn check_remote(&self, source: &impl fmt::Display) -> Result<()> {
+        if cfg!(feature = "sign") && self.signature.is_none() && self.key()?.is_some() {
+            return Err(diagnostic!(
+                Code::InvalidSignature,
+                "can't verify the signature of `{}`, which isn't downloaded with it: \
+                 give it with `--signature`",
+                source
+            ));
+        }
+        Ok(())
+    }
+
+    /// The public key given by `--verify-key` or the configuration.
+    fn key(&self) -> Result<Option<String>> {
+        match &self.verify_key {
+            Some(key) => Ok(Some(key.clone())),
+            None => Ok(WasmerConfig::load()?.verify_key().map(str::to_string)),
+        }
+    }
+
+    #[cfg(feature = "sign")]
+    fn verify_with(&self, key: &str, path: &Path, contents: &[u8]) -> Result<()> {
+        let public_key = PublicKey::parse(&read_key(key)?)?;
+        let signature_path = self
+            .signature
+            .clone()
+            .unwrap_or_else(|| signature_path(path));
+        let signature = std::fs::read_to_string(&signature_path).map_err(|err| {
+            diagnostic!(
+                Code::InvalidSignature,
+                "`{}` has no signature, can't read `{}`: {}",
+                path.display(),
+                signature_path.display(),
+                err
+            )
+        })?;
+        Signature::parse(&signature)
+            .and_then(|signature| public_key.verify(contents, &signature))
+            .map_err(|err| {
+                diagnostic!(
+                    Code::InvalidSignature,
+                    "the signature `{}` of `{}` is invalid: {}",
+                    signature_path.display(),
+                    path.display(),
+                    err
+                )
+            })
+    }
+
+    #[cfg(not(feature = "sign"))]
+    fn verify_with(&self, _key: &str, path: &Path, _contents: &[u8]) -> Result<()> {
+        Err(diagnostic!(
+            Code::FeatureDisabled,
+            "can't verify the signature of `{}`, this wasmer is built without the `sign` feature",
+            path.display()
+        ))
+    }
+}
+
+/// The default path of the signature of the file `path`.
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".");
+    signature_path.push(SIGNATURE_EXTENSION);
+    PathBuf::from(signature_path)
+}
+
+/// The key `key`, or the contents of the file `key` without its
+/// comment.
+#[cfg(feature = "sign")]
+fn read_key(key: &str) -> Result<String> {
+    let contents = if Path::new(key).is_file() {
+        std::fs::read_to_string(key)?
+    } else {
+        key.to_string()
+    };
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .last()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("`{}` is not a key", key))
+}
+
+#[cfg(feature = "sign")]
+pub use keys::*;
+
+#[cfg(feature = "sign")]
+mod keys {
+    use anyhow::{Context, Result};
+    use ring::rand::{SecureRandom, SystemRandom};
+    use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// The algorithm of the signatures of the contents themselves.
+    const ALGORITHM: &[u8; 2] = b"Ed";
+
+    /// The algorithm of the signatures of the BLAKE2b digests of the
+    /// contents, the default of minisign.
+    const PREHASHED_ALGORITHM: &[u8; 2] = b"ED";
+
+    /// A public key, with the random identifier of its key pair.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct PublicKey {
+        id: [u8; 8],
+        key: [u8; 32],
+    }
+
+    /// A secret key, with the random identifier of its key pair.
+    pub struct SecretKey {
+        id: [u8; 8],
+        pkcs8: Vec<u8>,
+        key_pair: Ed25519KeyPair,
+    }
+
+    /// A signature of some contents, with a trusted comment signed
+    /// along with them.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Signature {
+        algorithm: [u8; 2],
+        key_id: [u8; 8],
+        signature: Vec<u8>,
+        trusted_comment: String,
+        global_signature: Vec<u8>,
+    }
+
+    /// Formats the identifier of a key pair like minisign.
+    fn format_id(id: &[u8; 8]) -> String {
+        format!("{:016X}", u64::from_le_bytes(*id))
+    }
+
+    impl PublicKey {
+        /// Parses the base64 encoding of a minisign public key.
+        pub fn parse(key: &str) -> Result<Self> {
+            let bytes = base64::decode(key).context("the public key is not base64")?;
+            if bytes.len() != 42 || &bytes[..2] != ALGORITHM {
+                bail!("`{}` is not an Ed25519 public key", key);
+            }
+            let mut public_key = Self {
+                id: [0; 8],
+                key: [0; 32],
+            };
+            public_key.id.copy_from_slice(&bytes[2..10]);
+            public_key.key.copy_from_slice(&bytes[10..]);
+            Ok(public_key)
+        }
+
+        /// The contents of the file of this key.
+        pub fn to_file(&self) -> String {
+            let mut bytes = ALGORITHM.to_vec();
+            bytes.extend_from_slice(&self.id);
+            bytes.extend_from_slice(&self.key);
+            format!(
+                "untrusted comment: minisign public key {}\n{}\n",
+                format_id(&self.id),
+                base64::encode(bytes)
+            )
+        }
+
+        /// Checks `signature` is a signature of `contents` by this
+        /// key.
+        pub fn verify(&self, contents: &[u8], signature: &Signature) -> Result<()> {
+            if &signature.algorithm == PREHASHED_ALGORITHM {
+                bail!("prehashed signatures aren't supported, sign with `wasmer sign` or `minisign -S -l`");
+            }
+            if signature.key_id != self.id {
+                bail!(
+                    "it is made by the key {}, not {}",
+                    format_id(&signature.key_id),
+                    format_id(&self.id)
+                );
+            }
+            let key = UnparsedPublicKey::new(&ED25519, &self.key);
+            key.verify(contents, &signature.signature)
+                .map_err(|_| anyhow!("it doesn't match the contents"))?;
+            let mut global = signature.signature.clone();
+            global.extend_from_slice(signature.trusted_comment.as_bytes());
+            key.verify(&global, &signature.global_signature)
+                .map_err(|_| anyhow!("its trusted comment was tampered with"))?;
+            Ok(())
+        }
+    }
+
+    impl SecretKey {
+        /// Generates a new key pair.
+        pub fn generate() -> Result<(Self, PublicKey)> {
+            let random = SystemRandom::new();
+            let mut id = [0; 8];
+            random
+                .fill(&mut id)
+                .map_err(|_| anyhow!("failed to generate a key"))?;
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&random)
+                .map_err(|_| anyhow!("failed to generate a key"))?;
+            let secret_key = Self::from_parts(id, pkcs8.as_ref().to_vec())?;
+            let public_key = secret_key.public_key();
+            Ok((secret_key, public_key))
+        }
+
+        fn from_parts(id: [u8; 8], pkcs8: Vec<u8>) -> Result<Self> {
+            let key_pair = Ed25519KeyPair::from_pkcs8(&pkcs8)
+                .map_err(|_| anyhow!("the secret key is corrupted"))?;
+            Ok(Self {
+                id,
+                pkcs8,
+                key_pair,
+            })
+        }
+
+        /// Parses the contents of the file of a secret key.
+        pub fn parse(file: &str) -> Result<Self> {
+            let key = super::read_key(file)?;
+            let bytes = base64::decode(key).context("the secret key is not base64")?;
+            if bytes.len() <= 8 {
+                bail!("the secret key is truncated");
+            }
+            let mut id = [0; 8];
+            id.copy_from_slice(&bytes[..8]);
+            Self::from_parts(id, bytes[8..].to_vec())
+        }
+
+        /// The contents of the file of this key.
+        pub fn to_file(&self) -> String {
+            let mut bytes = self.id.to_vec();
+            bytes.extend_from_slice(&self.pkcs8);
+            format!(
+                "untrusted comment: wasmer secret key {}\n{}\n",
+                format_id(&self.id),
+                base64::encode(bytes)
+            )
+        }
+
+        /// The public key of this key pair.
+        pub fn public_key(&self) -> PublicKey {
+            let mut key = [0; 32];
+            key.copy_from_slice(self.key_pair.public_key().as_ref());
+            PublicKey { id: self.id, key }
+        }
+
+        /// Signs `contents`, the file `file_name`.
+        pub fn sign(&self, contents: &[u8], file_name: &str) -> Signature {
+            let signature = self.key_pair.sign(contents).as_ref().to_vec();
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let trusted_comment = format!("timestamp:{}\tfile:{}", timestamp, file_name);
+            let mut global = signature.clone();
+            global.extend_from_slice(trusted_comment.as_bytes());
+            Signature {
+                algorithm: *ALGORITHM,
+                key_id: self.id,
+                signature,
+                trusted_comment,
+                global_signature: self.key_pair.sign(&global).as_ref().to_vec(),
+            }
+        }
+    }
+
+    impl Signature {
+        /// Parses the contents of a signature file.
+        pub fn parse(file: &str) -> Result<Self> {
+            let lines = file.lines().map(str::trim_end).collect::<Vec<_>>();
+            let (signature, trusted_comment, global_signature) = match lines[..] {
+                [untrusted, signature, trusted, global, ..]
+                    if untrusted.starts_with("untrusted comment:") =>
+                {
+                    let trusted_comment = trusted
+                        .strip_prefix("trusted comment: ")
+                        .ok_or_else(|| anyhow!("it has no trusted comment"))?;
+                    (signature, trusted_comment, global)
+                }
+                _ => bail!("it is not a minisign signature"),
+            };
+            let bytes = base64::decode(signature).context("the signature is not base64")?;
+            let global_signature =
+                base64::decode(global_signature).context("the signature is not base64")?;
+            if bytes.len() != 74 || global_signature.len() != 64 {
+                bail!("the signature is truncated");
+            }
+            let mut algorithm = [0; 2];
+            algorithm.copy_from_slice(&bytes[..2]);
+            let mut key_id = [0; 8];
+            key_id.copy_from_slice(&bytes[2..10]);
+            Ok(Self {
+                algorithm,
+                key_id,
+                signature: bytes[10..].to_vec(),
+                trusted_comment: trusted_comment.to_string(),
+                global_signature,
+            })
+        }
+
+        /// The contents of the file of this signature.
+        pub fn to_file(&self) -> String {
+            let mut bytes = self.algorithm.to_vec();
+            bytes.extend_from_slice(&self.key_id);
+            bytes.extend_from_slice(&self.signature);
+            format!(
+                "untrusted comment: signature from wasmer secret key\n{}\ntrusted comment: {}\n{}\n",
+                base64::encode(bytes),
+                self.trusted_comment,
+                base64::encode(&self.global_signature)
+            )
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sign"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let (secret_key, public_key) = SecretKey::generate().unwrap();
+        let secret_key = SecretKey::parse(&secret_key.to_file()).unwrap();
+        let public_key = PublicKey::parse(&read_key(&public_key.to_file()).unwrap()).unwrap();
+
+        let signature = secret_key.sign(b"\0asm", "module.wasm");
+        let signature = Signature::parse(&signature.to_file()).unwrap();
+        assert!(public_key.verify(b"\0asm", &signature).is_ok());
+        assert!(public_key.verify(b"\0asm\x01", &signature).is_err());
+
+        let mut tampered = signature.clone();
+        tampered.trusted_comment.push_str("\tfile:other.wasm");
+        assert!(public_key.verify(b"\0asm", &tampered).is_err());
+
+        let (_, other_key) = SecretKey::generate().unwrap();
+        assert!(other_key.verify(b"\0asm", &signature).is_err());
+    }
+
+    #[test]
+    fn test_remote_modules_need_a_signature() {
+        let (_, public_key) = SecretKey::generate().unwrap();
+        let options = VerifyOptions {
+            verify_key: Some(public_key.to_file()),
+            signature: None,
+        };
+        let err = options
+            .check_remote(&"https://example.com/app.wasm")
+            .unwrap_err();
+        assert!(err.to_string().contains("`--signature`"));
+
+        let options = VerifyOptions {
+            signature: Some(PathBuf::from("app.wasm.minisig")),
+            ..options
+        };
+        assert!(options
+            .check_remote(&"https://example.com/app.wasm")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_signature_path() {
+        assert_eq!(
+            signature_path(Path::new("plugins/a.wasm")),
+            PathBuf::from("plugins/a.wasm.minisig")
+        );
+    }
+}
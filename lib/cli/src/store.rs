@@ -5,16 +5,22 @@ use anyhow::Result;
 
 #[allow(unused_imports)]
 use crate::common::WasmFeatures;
+#[cfg(feature = "compiler")]
+use crate::config::WasmerConfig;
 use clap::Parser;
 #[allow(unused_imports)]
 use std::path::PathBuf;
 use std::string::ToString;
 #[allow(unused_imports)]
 use std::sync::Arc;
+#[cfg(feature = "compiler")]
+use wasmer::wasmparser::Operator;
 use wasmer::*;
 #[cfg(feature = "compiler")]
 use wasmer_compiler::CompilerConfig;
 use wasmer_compiler::Engine;
+#[cfg(feature = "compiler")]
+use wasmer_middlewares::Metering;
 
 #[derive(Debug, Clone, Parser, Default)]
 /// The compiler options
@@ -40,16 +46,55 @@ pub struct CompilerOptions {
     #[clap(long, conflicts_with_all = &["singlepass", "cranelift"])]
     llvm: bool,
 
+    /// Use the compiler given by name, like `--singlepass`, `--cranelift`
+    /// or `--llvm`.
+    #[clap(
+        long,
+        value_name = "NAME",
+        possible_values = &["singlepass", "cranelift", "llvm"],
+        conflicts_with_all = &["singlepass", "cranelift", "llvm"]
+    )]
+    compiler: Option<String>,
+
     /// Enable compiler internal verification.
     #[clap(long)]
     #[cfg(any(feature = "singlepass", feature = "cranelift", feature = "llvm"))]
     enable_verifier: bool,
 
+    /// The optimization level of the Cranelift and LLVM compilers,
+    /// trading the compilation time against the speed of the code.
+    #[clap(
+        long,
+        value_name = "LEVEL",
+        possible_values = &["none", "speed", "speed_and_size"]
+    )]
+    #[cfg(any(feature = "cranelift", feature = "llvm"))]
+    opt_level: Option<String>,
+
+    /// Register the compiled code with the GDB JIT interface, so that
+    /// gdb and lldb attached to wasmer show the frames of the module
+    /// with their function names and source lines, read from its DWARF
+    /// sections, if wasmer is built with the `jit-debug` feature. Also
+    /// collects the debug info of Cranelift.
+    #[clap(long)]
+    #[cfg(any(feature = "cranelift", feature = "jit-debug"))]
+    debug_info: bool,
+
     /// LLVM debug directory, where IR and object files will be written to.
     #[cfg(feature = "llvm")]
-    #[clap(long, parse(from_os_str))]
+    #[clap(long, parse(from_os_str), value_hint = clap::ValueHint::DirPath)]
     llvm_debug_dir: Option<PathBuf>,
 
+    /// Compile the module with metering, counting one unit of fuel
+    /// for each instruction, so its execution can be limited.
+    #[clap(long)]
+    enable_metering: bool,
+
+    /// The fuel of the module, which is terminated once it is
+    /// exhausted; implies `--enable-metering`.
+    #[clap(long, value_name = "FUEL", alias = "max-fuel")]
+    fuel: Option<u64>,
+
     #[clap(flatten)]
     features: WasmFeatures,
 }
@@ -63,6 +108,11 @@ impl CompilerOptions {
             Ok(CompilerType::LLVM)
         } else if self.singlepass {
             Ok(CompilerType::Singlepass)
+        } else if let Some(compiler) = &self.compiler {
+            Ok(Self::compiler_by_name(compiler))
+        } else if let Some(compiler) = WasmerConfig::load()?.compiler() {
+            // The `compiler` setting, see `crate::config`
+            Ok(Self::compiler_by_name(compiler))
         } else {
             // Auto mode, we choose the best compiler for that platform
             cfg_if::cfg_if! {
@@ -75,27 +125,58 @@ impl CompilerOptions {
                 else if #[cfg(feature = "llvm")] {
                     Ok(CompilerType::LLVM)
                 } else {
-                    bail!("There are no available compilers for your architecture");
+                    return Err(crate::diagnostic!(crate::diagnostic::Code::NoCompiler, "There are no available compilers for your architecture"));
                 }
             }
         }
     }
 
+    /// The compiler named `singlepass`, `cranelift` or `llvm`.
+    fn compiler_by_name(name: &str) -> CompilerType {
+        match name {
+            "cranelift" => CompilerType::Cranelift,
+            "llvm" => CompilerType::LLVM,
+            _ => CompilerType::Singlepass,
+        }
+    }
+
+    /// The initial fuel of the modules if they are compiled with
+    /// metering: the one of `--fuel`, or an unlimited one.
+    fn metering(&self) -> Option<u64> {
+        match self.fuel {
+            Some(fuel) => Some(fuel),
+            None if self.enable_metering => Some(u64::MAX),
+            None => None,
+        }
+    }
+
+    /// Adds the metering middleware to `config`, if enabled.
+    fn meter(&self, mut config: Box<dyn CompilerConfig>) -> Box<dyn CompilerConfig> {
+        if let Some(fuel) = self.metering() {
+            config.push_middleware(Arc::new(Metering::new(fuel, |_: &Operator| -> u64 { 1 })));
+        }
+        config
+    }
+
     /// Get the enaled Wasm features.
     pub fn get_features(&self, mut features: Features) -> Result<Features> {
-        if self.features.threads || self.features.all {
+        // Features enabled by the `features` setting, see `crate::config`
+        let config = WasmerConfig::load()?;
+        let enabled = config.features();
+        let all = self.features.all || enabled.contains(&"all");
+        if self.features.threads || all || enabled.contains(&"threads") {
             features.threads(true);
         }
-        if self.features.multi_value || self.features.all {
+        if self.features.multi_value || all || enabled.contains(&"multi-value") {
             features.multi_value(true);
         }
-        if self.features.simd || self.features.all {
+        if self.features.simd || all || enabled.contains(&"simd") {
             features.simd(true);
         }
-        if self.features.bulk_memory || self.features.all {
+        if self.features.bulk_memory || all || enabled.contains(&"bulk-memory") {
             features.bulk_memory(true);
         }
-        if self.features.reference_types || self.features.all {
+        if self.features.reference_types || all || enabled.contains(&"reference-types") {
             features.reference_types(true);
         }
         Ok(features)
@@ -116,10 +197,12 @@ impl CompilerOptions {
         compiler_config: Box<dyn CompilerConfig>,
     ) -> Result<Engine> {
         let features = self.get_features(compiler_config.default_features_for_target(&target))?;
-        let engine: Engine = wasmer_compiler::EngineBuilder::new(compiler_config)
+        let builder = wasmer_compiler::EngineBuilder::new(compiler_config)
             .set_features(Some(features))
-            .set_target(Some(target))
-            .engine();
+            .set_target(Some(target));
+        #[cfg(feature = "jit-debug")]
+        let builder = builder.set_debug_info(self.debug_info);
+        let engine: Engine = builder.engine();
 
         Ok(engine)
     }
@@ -140,10 +223,21 @@ impl CompilerOptions {
             }
             #[cfg(feature = "cranelift")]
             CompilerType::Cranelift => {
+                use wasmer_compiler_cranelift::CraneliftOptLevel;
                 let mut config = wasmer_compiler_cranelift::Cranelift::new();
                 if self.enable_verifier {
                     config.enable_verifier();
                 }
+                match self.opt_level.as_deref() {
+                    Some("none") => {
+                        config.opt_level(CraneliftOptLevel::None);
+                    }
+                    Some("speed_and_size") => {
+                        config.opt_level(CraneliftOptLevel::SpeedAndSize);
+                    }
+                    _ => {}
+                }
+                config.debug_info(self.debug_info);
                 Box::new(config)
             }
             #[cfg(feature = "llvm")]
@@ -152,7 +246,8 @@ impl CompilerOptions {
                 use std::fs::File;
                 use std::io::Write;
                 use wasmer_compiler_llvm::{
-                    CompiledKind, InkwellMemoryBuffer, InkwellModule, LLVMCallbacks, LLVM,
+                    CompiledKind, InkwellMemoryBuffer, InkwellModule, LLVMCallbacks, LLVMOptLevel,
+                    LLVM,
                 };
                 use wasmer_types::entity::EntityRef;
                 let mut config = LLVM::new();
@@ -247,19 +342,29 @@ impl CompilerOptions {
                 if self.enable_verifier {
                     config.enable_verifier();
                 }
+                match self.opt_level.as_deref() {
+                    Some("none") => {
+                        config.opt_level(LLVMOptLevel::None);
+                    }
+                    Some("speed_and_size") => {
+                        config.opt_level(LLVMOptLevel::Default);
+                    }
+                    _ => {}
+                }
                 Box::new(config)
             }
             #[cfg(not(all(feature = "singlepass", feature = "cranelift", feature = "llvm",)))]
             compiler => {
-                bail!(
+                return Err(crate::diagnostic!(
+                    crate::diagnostic::Code::CompilerDisabled,
                     "The `{}` compiler is not included in this binary.",
                     compiler.to_string()
-                )
+                ))
             }
         };
 
         #[allow(unreachable_code)]
-        Ok((compiler_config, compiler))
+        Ok((self.meter(compiler_config), compiler))
     }
 }
 
@@ -317,6 +422,41 @@ impl StoreOptions {
         Ok((store, compiler_type))
     }
 
+    /// Gets the engine for the host target, with the compiler name
+    /// selected.
+    pub fn get_engine(&self) -> Result<(Engine, CompilerType)> {
+        let (compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
+        let engine = self.get_engine_with_compiler(Target::default(), compiler_config)?;
+        Ok((engine, compiler_type))
+    }
+
+    /// Gets the Wasm features the compiler selected enables by default
+    /// for a given target, with the compiler name selected.
+    pub fn get_default_features(&self, target: &Target) -> Result<(Features, CompilerType)> {
+        let (compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
+        Ok((
+            compiler_config.default_features_for_target(target),
+            compiler_type,
+        ))
+    }
+
+    /// Gets the Wasm features enabled on top of `defaults`, see
+    /// `--enable-all` and the `features` setting.
+    pub fn get_features(&self, defaults: Features) -> Result<Features> {
+        self.compiler.get_features(defaults)
+    }
+
+    /// Whether the modules are compiled with metering, see
+    /// `--enable-metering`.
+    pub fn metered(&self) -> bool {
+        self.compiler.metering().is_some()
+    }
+
+    /// The fuel given with `--fuel`, if any.
+    pub fn fuel(&self) -> Option<u64> {
+        self.compiler.fuel
+    }
+
     #[cfg(feature = "compiler")]
     fn get_engine_with_compiler(
         &self,
@@ -342,4 +482,27 @@ impl StoreOptions {
         let store = Store::new(engine);
         Ok((store, CompilerType::Headless))
     }
+
+    /// Get the headless engine
+    pub fn get_engine(&self) -> Result<(Engine, CompilerType)> {
+        let engine = self.get_engine_headless()?;
+        Ok((engine, CompilerType::Headless))
+    }
+
+    /// Gets the default Wasm features (headless engine)
+    pub fn get_default_features(&self, _target: &Target) -> Result<(Features, CompilerType)> {
+        Ok((Features::default(), CompilerType::Headless))
+    }
+
+    /// Gets the Wasm features: the headless engine has no options to
+    /// enable more.
+    pub fn get_features(&self, defaults: Features) -> Result<Features> {
+        Ok(defaults)
+    }
+
+    /// Whether the modules are compiled with metering: the headless
+    /// engine compiles none.
+    pub fn metered(&self) -> bool {
+        false
+    }
 }
@@ -1,18 +1,32 @@
 //! This file provides suggestions for the user, to help them on the
 //! usage of WebAssembly
 use distance::damerau_levenshtein;
-use wasmer::Module;
+use wasmer::{ExportType, FunctionType, Module};
 
-/// Suggest function exports for the module
-pub fn suggest_function_exports(module: &Module, query: &str) -> Vec<String> {
-    let mut function_names = module
-        .exports()
-        .functions()
-        .map(|extern_fn| {
-            let name = extern_fn.name();
-            name.to_string()
-        })
-        .collect::<Vec<_>>();
-    function_names.sort_by_key(|name| damerau_levenshtein(name, query));
-    function_names
+/// Suggest function exports for the module, the ones taking `arity`
+/// arguments first, if given, then the ones closest to `query`
+pub fn suggest_function_exports(
+    module: &Module,
+    query: &str,
+    arity: Option<usize>,
+) -> Vec<ExportType<FunctionType>> {
+    let mut functions = module.exports().functions().collect::<Vec<_>>();
+    functions.sort_by_key(|function| {
+        (
+            arity.map_or(false, |arity| function.ty().params().len() != arity),
+            damerau_levenshtein(function.name(), query),
+        )
+    });
+    functions
+}
+
+/// Describes a function export, with its signature and the name given
+/// by the name section of the module, if it differs
+pub fn describe_function_export(function: &ExportType<FunctionType>) -> String {
+    match function.debug_name() {
+        Some(debug_name) if debug_name != function.name() => {
+            format!("`{}` ({}: {})", function.name(), debug_name, function.ty())
+        }
+        _ => format!("`{}` ({})", function.name(), function.ty()),
+    }
 }
@@ -1,7 +1,11 @@
 //! Utility functions for the WebAssembly module
+use crate::diagnostic;
+use crate::diagnostic::Code;
 use anyhow::{bail, Result};
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use wasmer::{Type, Value};
 
 /// Whether or not Wasmer should print with color
 pub fn wasmer_should_print_color() -> bool {
@@ -11,14 +15,43 @@ pub fn wasmer_should_print_color() -> bool {
         .unwrap_or_else(|| atty::is(atty::Stream::Stdout))
 }
 
+/// The prefix of a mapped directory which is an archive loaded in
+/// memory rather than a host directory, e.g. `/assets::mem:assets.tar`.
+pub const MEM_ARCHIVE_PREFIX: &str = "mem:";
+
+/// The archive of a mapped directory loaded in memory, if it is one.
+pub fn mem_archive(dir: &Path) -> Option<&Path> {
+    dir.to_str()?
+        .strip_prefix(MEM_ARCHIVE_PREFIX)
+        .map(Path::new)
+}
+
 fn retrieve_alias_pathbuf(alias: &str, real_dir: &str) -> Result<(String, PathBuf)> {
+    if let Some(archive) = real_dir.strip_prefix(MEM_ARCHIVE_PREFIX) {
+        if !Path::new(archive).is_file() {
+            return Err(diagnostic!(
+                Code::PreopenNotFound,
+                "Archive \"{}\" does not exist",
+                archive
+            ));
+        }
+        return Ok((alias.to_string(), PathBuf::from(real_dir)));
+    }
     let pb = PathBuf::from(&real_dir);
     if let Ok(pb_metadata) = pb.metadata() {
         if !pb_metadata.is_dir() {
-            bail!("\"{}\" exists, but it is not a directory", &real_dir);
+            return Err(diagnostic!(
+                Code::PreopenNotFound,
+                "\"{}\" exists, but it is not a directory",
+                &real_dir
+            ));
         }
     } else {
-        bail!("Directory \"{}\" does not exist", &real_dir);
+        return Err(diagnostic!(
+            Code::PreopenNotFound,
+            "Directory \"{}\" does not exist",
+            &real_dir
+        ));
     }
     Ok((alias.to_string(), pb))
 }
@@ -64,9 +97,95 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     }
 }
 
+/// Parses a value of the type `ty`, e.g. an argument of a function.
+pub fn parse_value(arg: &str, ty: Type) -> Result<Value> {
+    match ty {
+        Type::I32 => {
+            Ok(Value::I32(arg.parse().map_err(|_| {
+                anyhow!("Can't convert `{}` into a i32", arg)
+            })?))
+        }
+        Type::I64 => {
+            Ok(Value::I64(arg.parse().map_err(|_| {
+                anyhow!("Can't convert `{}` into a i64", arg)
+            })?))
+        }
+        Type::F32 => {
+            Ok(Value::F32(arg.parse().map_err(|_| {
+                anyhow!("Can't convert `{}` into a f32", arg)
+            })?))
+        }
+        Type::F64 => {
+            Ok(Value::F64(arg.parse().map_err(|_| {
+                anyhow!("Can't convert `{}` into a f64", arg)
+            })?))
+        }
+        _ => bail!("Don't know how to convert {} into {:?}", arg, ty),
+    }
+}
+
+/// Splits `entry` into its number and its unit suffix, if any.
+fn split_unit(entry: &str) -> (&str, &str) {
+    let entry = entry.trim();
+    let position = entry
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or_else(|| entry.len());
+    (&entry[..position], entry[position..].trim())
+}
+
+/// Parses a duration, in seconds or with a unit: `500ms`, `30s`, `5m`
+/// or `1h`; fractions like `1.5s` are allowed.
+pub fn parse_duration(entry: &str) -> Result<Duration> {
+    let (number, unit) = split_unit(entry);
+    let scale = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => bail!(
+            "Durations must be a number of seconds, or use one of the units `ms`, `s`, `m` or `h`; found `{}`",
+            entry
+        ),
+    };
+    match number.parse::<f64>().map(|number| number * scale) {
+        Ok(seconds) if seconds < u64::MAX as f64 => Ok(Duration::from_secs_f64(seconds)),
+        _ => bail!("Invalid duration `{}`", entry),
+    }
+}
+
+/// Parses a size in bytes, with an optional unit: `K`, `M` or `G`,
+/// all multiples of 1024, optionally followed by `B` or `iB`.
+pub fn parse_bytes(entry: &str) -> Result<u64> {
+    let (number, unit) = split_unit(entry);
+    let unit = unit.to_uppercase();
+    let scale: u64 = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => bail!(
+            "Sizes must be a number of bytes, or use one of the units `K`, `M` or `G`; found `{}`",
+            entry
+        ),
+    };
+    match number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(scale))
+    {
+        Some(bytes) => Ok(bytes),
+        None => bail!("Invalid size `{}`", entry),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_envvar;
+    use super::{
+        mem_archive, parse_bytes, parse_duration, parse_envvar, parse_mapdir, parse_value,
+    };
+    use std::path::Path;
+    use std::time::Duration;
+    use wasmer::{Type, Value};
 
     #[test]
     fn test_parse_envvar() {
@@ -89,4 +208,42 @@ mod tests {
             ("A".into(), "B=C=D".into())
         );
     }
+
+    #[test]
+    fn test_parse_mapdir_mem_archive() {
+        let archive = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let (alias, dir) = parse_mapdir(&format!("/assets::mem:{}", archive.display())).unwrap();
+        assert_eq!(alias, "/assets");
+        assert_eq!(mem_archive(&dir), Some(archive.as_path()));
+        assert!(parse_mapdir("/assets::mem:missing.tar").is_err());
+        assert_eq!(mem_archive(Path::new("/host/assets")), None);
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_duration("2").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert!(parse_duration("2d").is_err());
+        assert!(parse_duration("s").is_err());
+
+        assert_eq!(parse_bytes("4096").unwrap(), 4096);
+        assert_eq!(parse_bytes("64K").unwrap(), 64 * 1024);
+        assert_eq!(parse_bytes("16MiB").unwrap(), 16 << 20);
+        assert_eq!(parse_bytes("1gb").unwrap(), 1 << 30);
+        assert!(parse_bytes("1.5M").is_err());
+        assert!(parse_bytes("1T").is_err());
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("-7", Type::I32).unwrap(), Value::I32(-7));
+        assert_eq!(parse_value("1.5", Type::F64).unwrap(), Value::F64(1.5));
+        assert_eq!(
+            parse_value("x", Type::I64).unwrap_err().to_string(),
+            "Can't convert `x` into a i64"
+        );
+        assert!(parse_value("0", Type::ExternRef).is_err());
+    }
 }
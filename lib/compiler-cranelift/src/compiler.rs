@@ -125,9 +125,9 @@ impl Compiler for CraneliftCompiler {
                 );
                 context.func.name = get_function_name(func_index);
                 context.func.signature = signatures[module.functions[func_index]].clone();
-                // if generate_debug_info {
-                //     context.func.collect_debug_info();
-                // }
+                if self.config.generate_debug_info {
+                    context.func.collect_debug_info();
+                }
                 let mut reader =
                     MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
                 reader.set_middleware_chain(
@@ -226,9 +226,9 @@ impl Compiler for CraneliftCompiler {
                 );
                 context.func.name = get_function_name(func_index);
                 context.func.signature = signatures[module.functions[func_index]].clone();
-                // if generate_debug_info {
-                //     context.func.collect_debug_info();
-                // }
+                if self.config.generate_debug_info {
+                    context.func.collect_debug_info();
+                }
                 let mut reader =
                     MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
                 reader.set_middleware_chain(
@@ -33,6 +33,7 @@ pub struct Cranelift {
     enable_verifier: bool,
     enable_pic: bool,
     opt_level: CraneliftOptLevel,
+    pub(crate) generate_debug_info: bool,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
 }
@@ -46,6 +47,7 @@ impl Cranelift {
             enable_verifier: false,
             opt_level: CraneliftOptLevel::Speed,
             enable_pic: false,
+            generate_debug_info: false,
             middlewares: vec![],
         }
     }
@@ -65,6 +67,13 @@ impl Cranelift {
         self
     }
 
+    /// Collect the debug info of the functions, i.e. the locations of
+    /// the Wasm locals, when compiling them.
+    pub fn debug_info(&mut self, enable: bool) -> &mut Self {
+        self.generate_debug_info = enable;
+        self
+    }
+
     /// Generates the ISA for the provided target
     pub fn isa(&self, target: &Target) -> CodegenResult<Box<dyn TargetIsa>> {
         let mut builder =
@@ -112,6 +112,7 @@ impl ArtifactBuild {
             compile_info,
             data_initializers,
             cpu_features: target.cpu_features().as_u64(),
+            target_triple: target.triple().to_string(),
         };
         Ok(Self { serializable })
     }
@@ -200,6 +201,10 @@ impl ArtifactCreate for ArtifactBuild {
         EnumSet::from_u64(self.serializable.cpu_features)
     }
 
+    fn target_triple(&self) -> &str {
+        &self.serializable.target_triple
+    }
+
     fn data_initializers(&self) -> &[OwnedDataInitializer] {
         &self.serializable.data_initializers
     }
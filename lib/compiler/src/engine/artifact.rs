@@ -29,7 +29,7 @@ use wasmer_types::SerializableCompilation;
 use wasmer_types::{
     CompileError, CpuFeature, DataInitializer, DeserializeError, FunctionIndex, LocalFunctionIndex,
     MemoryIndex, ModuleInfo, OwnedDataInitializer, SerializableModule, SerializeError,
-    SignatureIndex, TableIndex,
+    SignatureIndex, TableIndex, Triple,
 };
 #[cfg(feature = "static-artifact-create")]
 use wasmer_types::{CompileModuleInfo, Target};
@@ -46,6 +46,9 @@ pub struct Artifact {
     /// Some(_) only if this is not a deserialized static artifact
     frame_info_registration: Option<Mutex<Option<GlobalFrameInfoRegistration>>>,
     finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    /// Whether the code is registered with the GDB JIT interface
+    #[cfg_attr(not(feature = "jit-debug"), allow(dead_code))]
+    debug_info: bool,
 }
 
 #[cfg(feature = "static-artifact-create")]
@@ -125,10 +128,43 @@ impl Artifact {
 
         let serializable = SerializableModule::deserialize(metadata_slice)?;
         let artifact = ArtifactBuild::from_serializable(serializable);
+        Self::check_target(engine, &artifact)?;
         let mut inner_engine = engine.inner_mut();
         Self::from_parts(&mut inner_engine, artifact).map_err(DeserializeError::Compiler)
     }
 
+    /// Checks a deserialized artifact was compiled for the target of
+    /// `engine`: for its architecture and operating system, and with
+    /// CPU features it has.
+    fn check_target(engine: &Engine, artifact: &ArtifactBuild) -> Result<(), DeserializeError> {
+        let target = engine.target();
+        let triple: Triple = artifact.target_triple().parse().map_err(|_| {
+            DeserializeError::CorruptedBinary(format!(
+                "invalid target triple `{}`",
+                artifact.target_triple()
+            ))
+        })?;
+        if triple.architecture != target.triple().architecture
+            || triple.operating_system != target.triple().operating_system
+        {
+            return Err(DeserializeError::Incompatible(format!(
+                "the artifact was compiled for `{}`, but the target is `{}`",
+                triple,
+                target.triple()
+            )));
+        }
+        let missing = artifact.cpu_features().difference(*target.cpu_features());
+        if !missing.is_empty() {
+            return Err(DeserializeError::Incompatible(format!(
+                "the artifact was compiled for `{}` with the CPU features {}, but the target lacks {}",
+                triple,
+                cpu_features_list(artifact.cpu_features()),
+                cpu_features_list(missing)
+            )));
+        }
+        Ok(())
+    }
+
     /// Construct a `ArtifactBuild` from component parts.
     pub fn from_parts(
         engine_inner: &mut EngineInner,
@@ -210,6 +246,7 @@ impl Artifact {
             signatures,
             frame_info_registration: Some(Mutex::new(None)),
             finished_function_lengths,
+            debug_info: engine_inner.debug_info(),
         })
     }
 
@@ -232,6 +269,10 @@ impl ArtifactCreate for Artifact {
         self.artifact.cpu_features()
     }
 
+    fn target_triple(&self) -> &str {
+        self.artifact.target_triple()
+    }
+
     fn data_initializers(&self) -> &[OwnedDataInitializer] {
         self.artifact.data_initializers()
     }
@@ -276,6 +317,13 @@ impl Artifact {
                 &finished_function_extents,
                 frame_infos.clone(),
             );
+
+            #[cfg(feature = "jit-debug")]
+            if self.debug_info {
+                if let Some(registration) = info.as_mut() {
+                    registration.register_debug_info();
+                }
+            }
         }
     }
 
@@ -407,8 +455,26 @@ impl Artifact {
                 data: &init.data,
             })
             .collect::<Vec<_>>();
+        self.finish_instantiation_with_data(trap_handler, handle, &data_initializers)
+    }
+
+    /// Like [`Self::finish_instantiation`], but initializes the
+    /// memories with `data_initializers` rather than with the data
+    /// segments of the module.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::finish_instantiation`]. The data initializers
+    /// must leave the memories as the data segments of the module
+    /// would.
+    pub unsafe fn finish_instantiation_with_data(
+        &self,
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        handle: &mut InstanceHandle,
+        data_initializers: &[DataInitializer<'_>],
+    ) -> Result<(), InstantiationError> {
         handle
-            .finish_instantiation(trap_handler, &data_initializers)
+            .finish_instantiation(trap_handler, data_initializers)
             .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))
     }
 
@@ -684,6 +750,8 @@ impl Artifact {
             compile_info: metadata.compile_info,
             data_initializers: metadata.data_initializers,
             cpu_features: metadata.cpu_features,
+            // The object is linked in the running executable.
+            target_triple: engine.target().triple().to_string(),
         });
 
         let finished_function_lengths = finished_functions
@@ -702,6 +770,16 @@ impl Artifact {
             signatures: signatures.into_boxed_slice(),
             finished_function_lengths,
             frame_info_registration: None,
+            debug_info: false,
         })
     }
 }
+
+/// The names of `features`, e.g. `sse2, avx2`.
+fn cpu_features_list(features: EnumSet<CpuFeature>) -> String {
+    features
+        .iter()
+        .map(|feature| feature.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
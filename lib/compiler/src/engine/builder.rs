@@ -10,6 +10,8 @@ pub struct EngineBuilder {
     target: Option<Target>,
     /// The features to compile the Wasm module with
     features: Option<Features>,
+    /// Whether to register the compiled code with the GDB JIT interface
+    debug_info: bool,
 }
 
 impl EngineBuilder {
@@ -22,6 +24,7 @@ impl EngineBuilder {
             compiler_config: Some(compiler_config.into()),
             target: None,
             features: None,
+            debug_info: false,
         }
     }
 
@@ -31,6 +34,7 @@ impl EngineBuilder {
             compiler_config: None,
             target: None,
             features: None,
+            debug_info: false,
         }
     }
 
@@ -46,24 +50,36 @@ impl EngineBuilder {
         self
     }
 
+    /// Register the compiled code with the GDB JIT interface, so that
+    /// gdb and lldb show the frames of the modules with their function
+    /// names and source lines (requires the `jit-debug` feature)
+    pub fn set_debug_info(mut self, enable: bool) -> Self {
+        self.debug_info = enable;
+        self
+    }
+
     /// Build the `Engine` for this configuration
     #[cfg(feature = "compiler")]
     pub fn engine(self) -> Engine {
         let target = self.target.unwrap_or_default();
-        if let Some(compiler_config) = self.compiler_config {
+        let engine = if let Some(compiler_config) = self.compiler_config {
             let features = self
                 .features
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
             Engine::new(compiler_config, target, features)
         } else {
             Engine::headless()
-        }
+        };
+        engine.inner_mut().set_debug_info(self.debug_info);
+        engine
     }
 
     /// Build the `Engine` for this configuration
     #[cfg(not(feature = "compiler"))]
     pub fn engine(self) -> Engine {
-        Engine::headless()
+        let engine = Engine::headless();
+        engine.inner_mut().set_debug_info(self.debug_info);
+        engine
     }
 
     /// The Wasm features
@@ -2,6 +2,8 @@
 
 use crate::engine::builder::EngineBuilder;
 #[cfg(not(target_arch = "wasm32"))]
+use crate::engine::registry::{ArtifactRegistry, ArtifactSource};
+#[cfg(not(target_arch = "wasm32"))]
 use crate::Artifact;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::CodeMemory;
@@ -54,6 +56,9 @@ impl Engine {
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                artifacts: ArtifactRegistry::new(),
+                debug_info: false,
             })),
             target: Arc::new(target),
             engine_id: EngineId::default(),
@@ -84,6 +89,9 @@ impl Engine {
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                artifacts: ArtifactRegistry::new(),
+                debug_info: false,
             })),
             target: Arc::new(Target::default()),
             engine_id: EngineId::default(),
@@ -126,6 +134,10 @@ impl Engine {
     }
 
     /// Compile a WebAssembly binary
+    ///
+    /// Compiling the same binary again, with tunables giving the same
+    /// memory and table styles, returns the same artifact while it's
+    /// still in use.
     #[cfg(feature = "compiler")]
     #[cfg(not(target_arch = "wasm32"))]
     pub fn compile(
@@ -133,7 +145,23 @@ impl Engine {
         binary: &[u8],
         tunables: &dyn Tunables,
     ) -> Result<Arc<Artifact>, CompileError> {
-        Ok(Arc::new(Artifact::new(self, binary, tunables)?))
+        let hasher = self.inner().artifacts.hasher();
+        let hash = hasher.hash(binary);
+        if let Some(artifact) =
+            self.inner()
+                .artifacts
+                .get(ArtifactSource::Compiled, hash, Some(tunables))
+        {
+            return Ok(artifact);
+        }
+
+        let artifact = Arc::new(Artifact::new(self, binary, tunables)?);
+        Ok(self.inner_mut().artifacts.insert(
+            ArtifactSource::Compiled,
+            hash,
+            Some(tunables),
+            artifact,
+        ))
     }
 
     /// Compile a WebAssembly binary
@@ -156,7 +184,21 @@ impl Engine {
     ///
     /// The serialized content must represent a serialized WebAssembly module.
     pub unsafe fn deserialize(&self, bytes: &[u8]) -> Result<Arc<Artifact>, DeserializeError> {
-        Ok(Arc::new(Artifact::deserialize(self, bytes)?))
+        let hasher = self.inner().artifacts.hasher();
+        let hash = hasher.hash(bytes);
+        if let Some(artifact) = self
+            .inner()
+            .artifacts
+            .get(ArtifactSource::Deserialized, hash, None)
+        {
+            return Ok(artifact);
+        }
+
+        let artifact = Arc::new(Artifact::deserialize(self, bytes)?);
+        Ok(self
+            .inner_mut()
+            .artifacts
+            .insert(ArtifactSource::Deserialized, hash, None, artifact))
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -174,6 +216,13 @@ impl Engine {
         self.deserialize(&mmap)
     }
 
+    /// The number of artifacts in use, each of them shared by the
+    /// modules compiled or deserialized from the same bytes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn artifact_count(&self) -> usize {
+        self.inner().artifacts.len()
+    }
+
     /// A unique identifier for this object.
     ///
     /// This exists to allow us to compare two Engines for equality. Otherwise,
@@ -205,6 +254,13 @@ pub struct EngineInner {
     /// performantly.
     #[cfg(not(target_arch = "wasm32"))]
     signatures: SignatureRegistry,
+    /// The artifacts in use, to share them between the modules
+    /// created from the same bytes.
+    #[cfg(not(target_arch = "wasm32"))]
+    artifacts: ArtifactRegistry,
+    /// Whether the compiled code is registered with the GDB JIT
+    /// interface.
+    debug_info: bool,
 }
 
 impl EngineInner {
@@ -232,6 +288,19 @@ impl EngineInner {
         &self.features
     }
 
+    /// Whether the compiled code of the modules is registered with the
+    /// GDB JIT interface, so debuggers show their frames.
+    pub fn debug_info(&self) -> bool {
+        self.debug_info
+    }
+
+    /// Registers the compiled code of the modules instantiated from now
+    /// on with the GDB JIT interface. Only supported with the
+    /// `jit-debug` feature.
+    pub fn set_debug_info(&mut self, enable: bool) {
+        self.debug_info = enable;
+    }
+
     /// Allocate compiled functions into memory
     #[cfg(not(target_arch = "wasm32"))]
     #[allow(clippy::type_complexity)]
@@ -0,0 +1,138 @@
+//! The registry of the artifacts of an `Engine`, sharing the artifact
+//! of a module between all the `Module`s created from it.
+
+use crate::{Artifact, ArtifactCreate, Tunables};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::{Arc, Weak};
+use wasmer_types::{MemoryType, TableType};
+
+/// The hash of the bytes an artifact is created from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ModuleHash([u64; 2]);
+
+/// Hashes the bytes of modules, combining two SipHash digests keyed
+/// with random keys, which are different in each process, so that a
+/// module can't be crafted to collide with another one.
+#[derive(Clone)]
+pub(crate) struct ModuleHasher([RandomState; 2]);
+
+impl ModuleHasher {
+    /// Hashes the bytes of a module or of a serialized artifact.
+    pub(crate) fn hash(&self, bytes: &[u8]) -> ModuleHash {
+        let digest = |state: &RandomState| {
+            let mut hasher = state.build_hasher();
+            bytes.hash(&mut hasher);
+            hasher.finish()
+        };
+        ModuleHash([digest(&self.0[0]), digest(&self.0[1])])
+    }
+}
+
+/// How the artifact was created from the bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ArtifactSource {
+    /// Compiled from a WebAssembly module.
+    Compiled,
+    /// Deserialized from a serialized artifact.
+    Deserialized,
+}
+
+struct Entry {
+    artifact: Weak<Artifact>,
+    /// The types of the memories and tables of the module, to check
+    /// the tunables of a compilation result in the styles of the
+    /// artifact.
+    memories: Vec<MemoryType>,
+    tables: Vec<TableType>,
+}
+
+/// The artifacts in use, keyed by the hash of the bytes they were
+/// created from.
+///
+/// The registry only holds weak references: an artifact is dropped
+/// along with the last `Module` using it, and its entry is then
+/// evicted.
+pub(crate) struct ArtifactRegistry {
+    hasher: ModuleHasher,
+    entries: HashMap<(ArtifactSource, ModuleHash), Entry>,
+}
+
+impl ArtifactRegistry {
+    pub(crate) fn new() -> Self {
+        Self {
+            hasher: ModuleHasher([RandomState::new(), RandomState::new()]),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The hasher of the keys of the registry.
+    pub(crate) fn hasher(&self) -> ModuleHasher {
+        self.hasher.clone()
+    }
+
+    /// Returns the artifact created from the bytes hashed in `hash`,
+    /// if it's still alive. Compiled artifacts are only returned if
+    /// `tunables` would have compiled them the same way.
+    pub(crate) fn get(
+        &self,
+        source: ArtifactSource,
+        hash: ModuleHash,
+        tunables: Option<&dyn Tunables>,
+    ) -> Option<Arc<Artifact>> {
+        let entry = self.entries.get(&(source, hash))?;
+        let artifact = entry.artifact.upgrade()?;
+        if let Some(tunables) = tunables {
+            let memory_styles = entry.memories.iter().map(|ty| tunables.memory_style(ty));
+            let table_styles = entry.tables.iter().map(|ty| tunables.table_style(ty));
+            if !memory_styles.eq(artifact.memory_styles().values().cloned())
+                || !table_styles.eq(artifact.table_styles().values().cloned())
+            {
+                return None;
+            }
+        }
+
+        Some(artifact)
+    }
+
+    /// Registers `artifact`, unless an equivalent artifact was
+    /// registered in the meantime, which is then returned instead.
+    pub(crate) fn insert(
+        &mut self,
+        source: ArtifactSource,
+        hash: ModuleHash,
+        tunables: Option<&dyn Tunables>,
+        artifact: Arc<Artifact>,
+    ) -> Arc<Artifact> {
+        if let Some(registered) = self.get(source, hash, tunables) {
+            return registered;
+        }
+
+        self.evict();
+        let module_info = artifact.create_module_info();
+        self.entries.insert(
+            (source, hash),
+            Entry {
+                artifact: Arc::downgrade(&artifact),
+                memories: module_info.memories.values().cloned().collect(),
+                tables: module_info.tables.values().cloned().collect(),
+            },
+        );
+        artifact
+    }
+
+    /// Removes the entries of the dropped artifacts.
+    fn evict(&mut self) {
+        self.entries
+            .retain(|_, entry| entry.artifact.strong_count() > 0);
+    }
+
+    /// The number of artifacts alive.
+    pub(crate) fn len(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| entry.artifact.strong_count() > 0)
+            .count()
+    }
+}
@@ -0,0 +1,204 @@
+//! The lines of the source code a module was compiled from, read from
+//! the DWARF sections toolchains like LLVM put in the custom sections
+//! of the module, to tell where a frame of a backtrace is.
+//!
+//! The addresses of the DWARF sections of a wasm module are offsets
+//! from the contents of its code section.
+use super::frame_info::SourceLocation;
+use gimli::{ColumnType, Dwarf, EndianSlice, LittleEndian, SectionId, Unit};
+use std::collections::HashMap;
+use std::sync::Arc;
+use wasmer_types::ModuleInfo;
+
+type DwarfReader<'a> = EndianSlice<'a, LittleEndian>;
+
+/// A row of the line table: the location of the instructions from
+/// `address` to the next row, or `None` at the end of a sequence.
+#[derive(Debug)]
+struct LineRow {
+    address: u64,
+    location: Option<SourceLocation>,
+}
+
+/// The line tables of all the compilation units of a module, merged
+/// and sorted by address.
+#[derive(Debug, Default)]
+pub struct LineTable {
+    rows: Vec<LineRow>,
+}
+
+impl LineTable {
+    /// Reads the `.debug_line` section of `module`. The table is empty
+    /// if the module has no DWARF sections or they can't be read.
+    pub fn parse(module: &ModuleInfo) -> Self {
+        Self::try_parse(module).unwrap_or_default()
+    }
+
+    fn try_parse(module: &ModuleInfo) -> gimli::Result<Self> {
+        let sections = Dwarf::load(|id: SectionId| -> gimli::Result<Vec<u8>> {
+            Ok(module
+                .custom_sections(id.name())
+                .next()
+                .map(Vec::from)
+                .unwrap_or_default())
+        })?;
+        let dwarf = sections.borrow(|section| EndianSlice::new(&section[..], LittleEndian));
+
+        let mut table = Self::default();
+        let mut files = HashMap::<String, Arc<str>>::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let program = match unit.line_program.clone() {
+                Some(program) => program,
+                None => continue,
+            };
+            let mut unit_files = HashMap::<u64, Arc<str>>::new();
+            let mut rows = program.rows();
+            let mut sequence_start = true;
+            let mut skip_sequence = false;
+            while let Some((header, row)) = rows.next_row()? {
+                // Linkers leave the sequences of the functions they
+                // removed at address 0, which is the start of the code
+                // section, never an instruction.
+                if sequence_start {
+                    skip_sequence = row.address() == 0;
+                    sequence_start = false;
+                }
+                if row.end_sequence() {
+                    if !skip_sequence {
+                        table.rows.push(LineRow {
+                            address: row.address(),
+                            location: None,
+                        });
+                    }
+                    sequence_start = true;
+                    continue;
+                }
+                if skip_sequence {
+                    continue;
+                }
+                let file = match unit_files.get(&row.file_index()) {
+                    Some(file) => file.clone(),
+                    None => {
+                        let path = match row.file(header) {
+                            Some(file) => file_path(&dwarf, &unit, header, file)?,
+                            None => continue,
+                        };
+                        let file = files
+                            .entry(path)
+                            .or_insert_with_key(|path| Arc::from(path.as_str()))
+                            .clone();
+                        unit_files.insert(row.file_index(), file.clone());
+                        file
+                    }
+                };
+                table.rows.push(LineRow {
+                    address: row.address(),
+                    location: row.line().map(|line| SourceLocation {
+                        file,
+                        line: line.get() as u32,
+                        column: match row.column() {
+                            ColumnType::LeftEdge => None,
+                            ColumnType::Column(column) => Some(column.get() as u32),
+                        },
+                    }),
+                });
+            }
+        }
+        // The end of a sequence goes before the start of the next one
+        // at the same address.
+        table
+            .rows
+            .sort_by_key(|row| (row.address, row.location.is_some()));
+        Ok(table)
+    }
+
+    /// The location of the instruction at `address`, relative to the
+    /// contents of the code section.
+    pub fn lookup(&self, address: u64) -> Option<&SourceLocation> {
+        let index = self.rows.partition_point(|row| row.address <= address);
+        self.rows.get(index.checked_sub(1)?)?.location.as_ref()
+    }
+}
+
+/// The path of `file`, joined to its directory and to the compilation
+/// directory of `unit` when they are relative.
+fn file_path<'a>(
+    dwarf: &Dwarf<DwarfReader<'a>>,
+    unit: &Unit<DwarfReader<'a>>,
+    header: &gimli::LineProgramHeader<DwarfReader<'a>>,
+    file: &gimli::FileEntry<DwarfReader<'a>>,
+) -> gimli::Result<String> {
+    let mut path = match &unit.comp_dir {
+        Some(comp_dir) => comp_dir.to_string_lossy().into_owned(),
+        None => String::new(),
+    };
+    if let Some(directory) = file.directory(header) {
+        let directory = dwarf.attr_string(unit, directory)?;
+        push_path(&mut path, &directory.to_string_lossy());
+    }
+    let name = dwarf.attr_string(unit, file.path_name())?;
+    push_path(&mut path, &name.to_string_lossy());
+    Ok(path)
+}
+
+/// Joins `component` to `path`, replacing it if it is absolute.
+fn push_path(path: &mut String, component: &str) {
+    let is_absolute = component.starts_with('/')
+        || component.starts_with('\\')
+        || component.get(1..3) == Some(":\\");
+    if is_absolute || path.is_empty() {
+        *path = component.to_string();
+    } else if !component.is_empty() {
+        if !path.ends_with('/') && !path.ends_with('\\') {
+            path.push('/');
+        }
+        path.push_str(component);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_path() {
+        let mut path = "/home/user/project".to_string();
+        push_path(&mut path, "src");
+        push_path(&mut path, "main.rs");
+        assert_eq!(path, "/home/user/project/src/main.rs");
+        push_path(&mut path, "/rustc/library/core/src/panicking.rs");
+        assert_eq!(path, "/rustc/library/core/src/panicking.rs");
+        let mut path = String::new();
+        push_path(&mut path, "main.c");
+        assert_eq!(path, "main.c");
+    }
+
+    #[test]
+    fn test_lookup() {
+        let location = |line| {
+            Some(SourceLocation {
+                file: Arc::from("main.c"),
+                line,
+                column: None,
+            })
+        };
+        let mut table = LineTable::default();
+        for (address, location) in vec![
+            (10, location(1)),
+            (14, location(2)),
+            (20, None),
+            (20, location(7)),
+            (30, None),
+        ] {
+            table.rows.push(LineRow { address, location });
+        }
+        assert_eq!(table.lookup(5), None);
+        assert_eq!(table.lookup(10).map(|l| l.line), Some(1));
+        assert_eq!(table.lookup(17).map(|l| l.line), Some(2));
+        assert_eq!(table.lookup(20).map(|l| l.line), Some(7));
+        assert_eq!(table.lookup(30), None);
+        assert!(LineTable::parse(&ModuleInfo::new()).rows.is_empty());
+    }
+}
@@ -276,6 +276,12 @@ impl fmt::Display for RuntimeError {
                 func_index,
                 frame.module_offset()
             )?;
+            if let (Some(file), Some(line)) = (frame.file(), frame.line()) {
+                write!(f, "\n        at {}:{}", file, line)?;
+                if let Some(column) = frame.column() {
+                    write!(f, ":{}", column)?;
+                }
+            }
         }
         Ok(())
     }
@@ -11,8 +11,15 @@
 //! let module: ModuleInfo = ...;
 //! FRAME_INFO.register(module, compiled_functions);
 //! ```
+#[cfg(feature = "dwarf")]
+use super::dwarf::LineTable;
+#[cfg(feature = "jit-debug")]
+use super::gdb_jit::{self, GdbJitImageRegistration, JitFunction};
 use std::cmp;
 use std::collections::BTreeMap;
+use std::sync::Arc;
+#[cfg(feature = "dwarf")]
+use std::sync::Mutex;
 use std::sync::RwLock;
 use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{CompiledFunctionFrameInfo, SourceLoc, TrapInformation};
@@ -48,6 +55,30 @@ pub struct GlobalFrameInfoRegistration {
     /// The key that will be removed from the global `ranges` map when this is
     /// dropped.
     key: usize,
+    /// The registration of the module with the GDB JIT interface, see
+    /// [`GlobalFrameInfoRegistration::register_debug_info`].
+    #[cfg(feature = "jit-debug")]
+    gdb_jit: Option<GdbJitImageRegistration>,
+}
+
+impl GlobalFrameInfoRegistration {
+    /// Registers the compiled code of the module with the GDB JIT
+    /// interface, so that debuggers attached to the process show its
+    /// frames with their function names and source lines.
+    #[cfg(feature = "jit-debug")]
+    pub fn register_debug_info(&mut self) {
+        if self.gdb_jit.is_some() {
+            return;
+        }
+        let info = FRAME_INFO.read().unwrap();
+        if let Some(image) = info
+            .ranges
+            .get(&self.key)
+            .and_then(|module| module.gdb_jit_image())
+        {
+            self.gdb_jit = Some(GdbJitImageRegistration::register(image));
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -56,6 +87,10 @@ struct ModuleInfoFrameInfo {
     functions: BTreeMap<usize, FunctionInfo>,
     module: ModuleInfo,
     frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+    /// The line table of the DWARF sections of the module, read on the
+    /// first lookup.
+    #[cfg(feature = "dwarf")]
+    line_table: Mutex<Option<LineTable>>,
 }
 
 impl ModuleInfoFrameInfo {
@@ -63,6 +98,37 @@ impl ModuleInfoFrameInfo {
         self.frame_infos.get(local_index).unwrap()
     }
 
+    /// The location in the source code of the instruction at `srcloc`,
+    /// if the module has DWARF sections describing it.
+    #[cfg(feature = "dwarf")]
+    fn source_location(&self, srcloc: SourceLoc) -> Option<SourceLocation> {
+        let address = srcloc.bits().checked_sub(self.module.code_section_offset)?;
+        let mut line_table = self.line_table.lock().ok()?;
+        line_table
+            .get_or_insert_with(|| LineTable::parse(&self.module))
+            .lookup(u64::from(address))
+            .cloned()
+    }
+
+    /// The ELF image describing the module to the GDB JIT interface.
+    #[cfg(feature = "jit-debug")]
+    fn gdb_jit_image(&self) -> Option<Vec<u8>> {
+        let mut line_table = self.line_table.lock().ok()?;
+        let line_table = line_table.get_or_insert_with(|| LineTable::parse(&self.module));
+        let functions = self.functions.iter().map(|(end, func)| JitFunction {
+            index: self.module.func_index(func.local_index),
+            start: func.start,
+            len: end - func.start + 1,
+            address_map: &self.function_debug_info(func.local_index).address_map,
+        });
+        gdb_jit::create_image(&self.module, functions, line_table)
+    }
+
+    #[cfg(not(feature = "dwarf"))]
+    fn source_location(&self, _srcloc: SourceLoc) -> Option<SourceLocation> {
+        None
+    }
+
     /// Gets a function given a pc
     fn function_info(&self, pc: usize) -> Option<&FunctionInfo> {
         let (end, func) = self.functions.range(pc..).next()?;
@@ -133,8 +199,9 @@ impl GlobalFrameInfo {
             module_name: module.module.name(),
             func_index: func_index.index() as u32,
             function_name: module.module.function_names.get(&func_index).cloned(),
-            instr,
             func_start: instr_map.start_srcloc,
+            instr,
+            source_location: module.source_location(instr),
         })
     }
 
@@ -235,10 +302,16 @@ pub fn register(
             functions,
             module,
             frame_infos,
+            #[cfg(feature = "dwarf")]
+            line_table: Mutex::new(None),
         },
     );
     assert!(prev.is_none());
-    Some(GlobalFrameInfoRegistration { key: max })
+    Some(GlobalFrameInfoRegistration {
+        key: max,
+        #[cfg(feature = "jit-debug")]
+        gdb_jit: None,
+    })
 }
 
 /// Description of a frame in a backtrace for a [`RuntimeError::trace`](crate::RuntimeError::trace).
@@ -256,6 +329,19 @@ pub struct FrameInfo {
     function_name: Option<String>,
     func_start: SourceLoc,
     instr: SourceLoc,
+    source_location: Option<SourceLocation>,
+}
+
+/// A position in the source code a module was compiled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(not(feature = "dwarf"), allow(dead_code))]
+pub(crate) struct SourceLocation {
+    /// The path of the file, as the compiler saw it.
+    pub file: Arc<str>,
+    /// The line, starting at 1.
+    pub line: u32,
+    /// The column, starting at 1, if known.
+    pub column: Option<u32>,
 }
 
 impl FrameInfo {
@@ -316,4 +402,30 @@ impl FrameInfo {
     pub fn func_offset(&self) -> usize {
         (self.instr.bits() - self.func_start.bits()) as usize
     }
+
+    /// Returns the path of the source file this frame's instruction was
+    /// compiled from, as the compiler saw it.
+    ///
+    /// The location comes from the DWARF sections of the module, so this
+    /// function returns `None` when the module was built without debug
+    /// information or when the `dwarf` feature is disabled.
+    pub fn file(&self) -> Option<&str> {
+        self.source_location
+            .as_ref()
+            .map(|location| &*location.file)
+    }
+
+    /// Returns the line, starting at 1, of this frame's instruction in
+    /// [`FrameInfo::file`].
+    pub fn line(&self) -> Option<u32> {
+        self.source_location.as_ref().map(|location| location.line)
+    }
+
+    /// Returns the column, starting at 1, of this frame's instruction in
+    /// [`FrameInfo::file`], if the DWARF sections tell it.
+    pub fn column(&self) -> Option<u32> {
+        self.source_location
+            .as_ref()
+            .and_then(|location| location.column)
+    }
 }
@@ -0,0 +1,553 @@
+//! Registration of the compiled code of the modules with the [GDB JIT
+//! interface], so debuggers attached to the process, gdb and lldb,
+//! show the frames of the modules with their names and source lines.
+//!
+//! Each module is described by an in-memory ELF image holding a DWARF
+//! compilation unit: a subprogram per function, at the address of its
+//! native code, and a line program mapping the native code to the
+//! lines of the DWARF sections of the module, or otherwise to the
+//! offsets of the instructions in a `<module>.wasm` file.
+//!
+//! [GDB JIT interface]: https://sourceware.org/gdb/onlinedocs/gdb/JIT-Interface.html
+use super::dwarf::LineTable;
+use gimli::write::{
+    Address, AttributeValue, DwarfUnit, EndianVec, FileId, LineProgram, LineString, Sections,
+};
+use gimli::{constants, Encoding, Format, LineEncoding, LittleEndian};
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::Mutex;
+use wasmer_types::{FunctionAddressMap, FunctionIndex, ModuleInfo, SourceLoc};
+
+/// The compiled code of a function of a module.
+pub struct JitFunction<'a> {
+    /// The index of the function in the module.
+    pub index: FunctionIndex,
+    /// The address of its native code.
+    pub start: usize,
+    /// The length of its native code.
+    pub len: usize,
+    /// The wasm offsets of its native instructions.
+    pub address_map: &'a FunctionAddressMap,
+}
+
+/// Creates the ELF image describing `functions`, sorted by address,
+/// or `None` if the host is not supported.
+pub fn create_image<'a>(
+    module: &ModuleInfo,
+    functions: impl Iterator<Item = JitFunction<'a>>,
+    line_table: &LineTable,
+) -> Option<Vec<u8>> {
+    let machine = elf_machine()?;
+    let functions = functions.collect::<Vec<_>>();
+    let low = functions.first()?.start as u64;
+    let high = functions.last().map(|f| (f.start + f.len) as u64)?;
+    let sections = create_dwarf(module, &functions, line_table, low, high).ok()?;
+    let symbols = functions
+        .iter()
+        .map(|function| (function_name(module, function.index), function))
+        .map(|(name, function)| (name, function.start as u64, function.len as u64))
+        .collect::<Vec<_>>();
+    Some(write_elf(machine, (low, high - low), &sections, &symbols))
+}
+
+/// The name of a function in the name section, or `wasm-function[N]`.
+fn function_name(module: &ModuleInfo, index: FunctionIndex) -> String {
+    match module.function_names.get(&index) {
+        Some(name) => name.clone(),
+        None => format!("wasm-function[{}]", index.as_u32()),
+    }
+}
+
+/// The DWARF sections describing `functions`, from `low` to `high`.
+fn create_dwarf(
+    module: &ModuleInfo,
+    functions: &[JitFunction],
+    line_table: &LineTable,
+    low: u64,
+    high: u64,
+) -> gimli::write::Result<Vec<(&'static str, Vec<u8>)>> {
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+    let wasm_file = format!("{}.wasm", module.name());
+    let mut dwarf = DwarfUnit::new(encoding);
+    dwarf.unit.line_program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(b".".to_vec()),
+        LineString::String(wasm_file.clone().into_bytes()),
+        None,
+    );
+    let wasm_file_id = add_file(&mut dwarf.unit.line_program, &wasm_file);
+
+    let root = dwarf.unit.root();
+    let entry = dwarf.unit.get_mut(root);
+    entry.set(
+        constants::DW_AT_producer,
+        AttributeValue::String(format!("wasmer {}", crate::VERSION).into_bytes()),
+    );
+    entry.set(
+        constants::DW_AT_name,
+        AttributeValue::String(wasm_file.into_bytes()),
+    );
+    entry.set(
+        constants::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(low)),
+    );
+    entry.set(constants::DW_AT_high_pc, AttributeValue::Udata(high - low));
+    entry.set(constants::DW_AT_stmt_list, AttributeValue::LineProgramRef);
+
+    let mut files = HashMap::new();
+    for function in functions {
+        let id = dwarf.unit.add(root, constants::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(id);
+        entry.set(
+            constants::DW_AT_name,
+            AttributeValue::String(function_name(module, function.index).into_bytes()),
+        );
+        entry.set(
+            constants::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(function.start as u64)),
+        );
+        entry.set(
+            constants::DW_AT_high_pc,
+            AttributeValue::Udata(function.len as u64),
+        );
+        entry.set(constants::DW_AT_external, AttributeValue::Flag(true));
+
+        let program = &mut dwarf.unit.line_program;
+        program.begin_sequence(Some(Address::Constant(function.start as u64)));
+        let map = function.address_map;
+        let instructions = map
+            .instructions
+            .iter()
+            .map(|instruction| (instruction.code_offset, instruction.srcloc));
+        // Some compilers, like LLVM, don't map the instructions.
+        let start = Some((0, map.start_srcloc)).filter(|_| map.instructions.is_empty());
+        for (code_offset, srcloc) in start.into_iter().chain(instructions) {
+            let (file, line, column) = match source_location(module, line_table, srcloc) {
+                Some((file, line, column)) => {
+                    let file = *files
+                        .entry(file.to_string())
+                        .or_insert_with(|| add_file(program, file));
+                    (file, line, column)
+                }
+                None => (wasm_file_id, srcloc.bits() as u64, 0),
+            };
+            let row = program.row();
+            row.address_offset = code_offset as u64;
+            row.file = file;
+            row.line = line;
+            row.column = column;
+            program.generate_row();
+        }
+        program.end_sequence(function.len as u64);
+    }
+
+    let mut sections = Sections::new(EndianVec::new(LittleEndian));
+    dwarf.write(&mut sections)?;
+    let mut debug_sections = vec![];
+    sections.for_each(|id, data| -> gimli::write::Result<()> {
+        if !data.slice().is_empty() {
+            debug_sections.push((id.name(), data.slice().to_vec()));
+        }
+        Ok(())
+    })?;
+    Ok(debug_sections)
+}
+
+/// The file, line and column of the instruction at `srcloc` in the
+/// DWARF sections of `module`.
+fn source_location<'a>(
+    module: &ModuleInfo,
+    line_table: &'a LineTable,
+    srcloc: SourceLoc,
+) -> Option<(&'a str, u64, u64)> {
+    let address = srcloc.bits().checked_sub(module.code_section_offset)?;
+    let location = line_table.lookup(u64::from(address))?;
+    Some((
+        &*location.file,
+        u64::from(location.line),
+        u64::from(location.column.unwrap_or(0)),
+    ))
+}
+
+/// Adds the file at `path` to the line program.
+fn add_file(program: &mut LineProgram, path: &str) -> FileId {
+    let (directory, name) = match path.rfind('/') {
+        Some(0) => (
+            program.add_directory(LineString::String(b"/".to_vec())),
+            &path[1..],
+        ),
+        Some(i) => (
+            program.add_directory(LineString::String(path[..i].as_bytes().to_vec())),
+            &path[i + 1..],
+        ),
+        None => (program.default_directory(), path),
+    };
+    let name = if name.is_empty() { "<unknown>" } else { name };
+    program.add_file(
+        LineString::String(name.as_bytes().to_vec()),
+        directory,
+        None,
+    )
+}
+
+/// The ELF machine of the host, if its code can be described.
+fn elf_machine() -> Option<u16> {
+    if cfg!(not(all(
+        target_pointer_width = "64",
+        target_endian = "little"
+    ))) {
+        return None;
+    }
+    if cfg!(target_arch = "x86_64") {
+        Some(62)
+    } else if cfg!(target_arch = "aarch64") {
+        Some(183)
+    } else if cfg!(target_arch = "riscv64") {
+        Some(243)
+    } else {
+        None
+    }
+}
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 2;
+const SHF_EXECINSTR: u64 = 4;
+
+/// A section of the ELF image.
+struct ElfSection<'a> {
+    name: &'a str,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    data: &'a [u8],
+    size: u64,
+    link: u32,
+    info: u32,
+    entsize: u64,
+}
+
+impl<'a> ElfSection<'a> {
+    fn new(name: &'a str, kind: u32, data: &'a [u8]) -> Self {
+        Self {
+            name,
+            kind,
+            flags: 0,
+            addr: 0,
+            data,
+            size: data.len() as u64,
+            link: 0,
+            info: 0,
+            entsize: 0,
+        }
+    }
+}
+
+/// Writes a 64-bit little-endian ELF shared object with a `.text`
+/// section without contents at the address of the code, the DWARF
+/// `sections`, and the `symbols` of the functions, with their
+/// addresses and sizes.
+fn write_elf(
+    machine: u16,
+    (text_addr, text_size): (u64, u64),
+    sections: &[(&str, Vec<u8>)],
+    symbols: &[(String, u64, u64)],
+) -> Vec<u8> {
+    const TEXT_INDEX: u16 = 1;
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; 24];
+    for (name, addr, size) in symbols {
+        symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+        // A global function.
+        symtab.push(0x12);
+        symtab.push(0);
+        symtab.extend_from_slice(&TEXT_INDEX.to_le_bytes());
+        symtab.extend_from_slice(&addr.to_le_bytes());
+        symtab.extend_from_slice(&size.to_le_bytes());
+    }
+
+    let mut elf_sections = vec![
+        ElfSection::new("", 0, &[]),
+        ElfSection {
+            flags: SHF_ALLOC | SHF_EXECINSTR,
+            addr: text_addr,
+            size: text_size,
+            ..ElfSection::new(".text", SHT_NOBITS, &[])
+        },
+    ];
+    for (name, data) in sections {
+        elf_sections.push(ElfSection::new(name, SHT_PROGBITS, data));
+    }
+    let strtab_index = elf_sections.len() as u32 + 1;
+    elf_sections.push(ElfSection {
+        link: strtab_index,
+        info: 1,
+        entsize: 24,
+        ..ElfSection::new(".symtab", SHT_SYMTAB, &symtab)
+    });
+    elf_sections.push(ElfSection::new(".strtab", SHT_STRTAB, &strtab));
+    let mut shstrtab = vec![0];
+    let mut names = vec![];
+    for section in &elf_sections {
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(section.name.as_bytes());
+        shstrtab.push(0);
+    }
+    names.push(shstrtab.len() as u32);
+    shstrtab.extend_from_slice(b".shstrtab\0");
+    elf_sections.push(ElfSection::new(".shstrtab", SHT_STRTAB, &shstrtab));
+
+    const EHDR_LEN: usize = 64;
+    const PHDR_LEN: usize = 56;
+    let mut elf = vec![0; EHDR_LEN + PHDR_LEN];
+    let mut offsets = vec![];
+    for section in &elf_sections {
+        while elf.len() % 8 != 0 {
+            elf.push(0);
+        }
+        offsets.push(elf.len() as u64);
+        elf.extend_from_slice(section.data);
+    }
+    while elf.len() % 8 != 0 {
+        elf.push(0);
+    }
+    let shoff = elf.len() as u64;
+    for ((section, offset), name) in elf_sections.iter().zip(offsets).zip(names) {
+        elf.extend_from_slice(&name.to_le_bytes());
+        elf.extend_from_slice(&section.kind.to_le_bytes());
+        elf.extend_from_slice(&section.flags.to_le_bytes());
+        elf.extend_from_slice(&section.addr.to_le_bytes());
+        elf.extend_from_slice(&offset.to_le_bytes());
+        elf.extend_from_slice(&section.size.to_le_bytes());
+        elf.extend_from_slice(&section.link.to_le_bytes());
+        elf.extend_from_slice(&section.info.to_le_bytes());
+        let align: u64 = if section.kind == SHT_NOBITS { 16 } else { 1 };
+        elf.extend_from_slice(&align.to_le_bytes());
+        elf.extend_from_slice(&section.entsize.to_le_bytes());
+    }
+
+    let mut header = Vec::with_capacity(EHDR_LEN + PHDR_LEN);
+    // 64-bit, little-endian, version 1.
+    header.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    header.extend_from_slice(&[0; 8]);
+    // A shared object, so that the addresses are used as they are.
+    header.extend_from_slice(&3u16.to_le_bytes());
+    header.extend_from_slice(&machine.to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&(EHDR_LEN as u64).to_le_bytes());
+    header.extend_from_slice(&shoff.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&(EHDR_LEN as u16).to_le_bytes());
+    header.extend_from_slice(&(PHDR_LEN as u16).to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&64u16.to_le_bytes());
+    header.extend_from_slice(&(elf_sections.len() as u16).to_le_bytes());
+    header.extend_from_slice(&(elf_sections.len() as u16 - 1).to_le_bytes());
+    // A loadable, readable and executable segment covering the code.
+    header.extend_from_slice(&1u32.to_le_bytes());
+    header.extend_from_slice(&5u32.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&text_addr.to_le_bytes());
+    header.extend_from_slice(&text_addr.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&text_size.to_le_bytes());
+    header.extend_from_slice(&1u64.to_le_bytes());
+    elf[..EHDR_LEN + PHDR_LEN].copy_from_slice(&header);
+    elf
+}
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+/// The list of the images registered with the GDB JIT interface,
+/// which debuggers read when `__jit_debug_register_code` is called.
+#[repr(C)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+// The debuggers find the descriptor and the function by their names,
+// so both are exported unmangled, but neither is public: the
+// descriptor is only accessed through `with_descriptor`. Another JIT
+// linked in the same binary and defining them, like the execution
+// engine of LLVM, makes the link fail with duplicate symbols.
+
+/// The descriptor of the GDB JIT interface, found by its name.
+#[no_mangle]
+static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The function debuggers put a breakpoint on to be notified of the
+/// changes of `__jit_debug_descriptor`.
+#[no_mangle]
+#[inline(never)]
+extern "C" fn __jit_debug_register_code() {
+    // Keeps the function from being optimized out or merged.
+    unsafe { ptr::read_volatile(&0u8) };
+}
+
+lazy_static::lazy_static! {
+    /// Serializes the changes of `__jit_debug_descriptor`.
+    static ref GDB_JIT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Links or unlinks `entry` with `link`, then tells the debuggers
+/// about it with `action`, holding `GDB_JIT_LOCK` all along.
+///
+/// # Safety
+///
+/// `link` must only access the entries of the descriptor, and `entry`
+/// must be a valid entry, linked in the list when unregistered.
+unsafe fn with_descriptor(
+    entry: *mut JitCodeEntry,
+    action: u32,
+    link: impl FnOnce(&mut JitDescriptor),
+) {
+    let _lock = GDB_JIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let descriptor = &mut *ptr::addr_of_mut!(__jit_debug_descriptor);
+    link(descriptor);
+    descriptor.relevant_entry = entry;
+    descriptor.action_flag = action;
+    __jit_debug_register_code();
+    descriptor.action_flag = JIT_NOACTION;
+    descriptor.relevant_entry = ptr::null_mut();
+}
+
+/// An image registered with the GDB JIT interface, unregistered when
+/// dropped.
+pub struct GdbJitImageRegistration {
+    entry: *mut JitCodeEntry,
+    _image: Box<[u8]>,
+}
+
+// The entry is only accessed with `GDB_JIT_LOCK` held.
+unsafe impl Send for GdbJitImageRegistration {}
+unsafe impl Sync for GdbJitImageRegistration {}
+
+impl GdbJitImageRegistration {
+    /// Registers `image` with the GDB JIT interface.
+    pub fn register(image: Vec<u8>) -> Self {
+        let image = image.into_boxed_slice();
+        let entry = Box::into_raw(Box::new(JitCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: image.as_ptr(),
+            symfile_size: image.len() as u64,
+        }));
+        unsafe {
+            with_descriptor(entry, JIT_REGISTER_FN, |descriptor| {
+                (*entry).next_entry = descriptor.first_entry;
+                if !descriptor.first_entry.is_null() {
+                    (*descriptor.first_entry).prev_entry = entry;
+                }
+                descriptor.first_entry = entry;
+            });
+        }
+        Self {
+            entry,
+            _image: image,
+        }
+    }
+}
+
+impl Drop for GdbJitImageRegistration {
+    fn drop(&mut self) {
+        let entry = self.entry;
+        unsafe {
+            with_descriptor(entry, JIT_UNREGISTER_FN, |descriptor| {
+                let entry = &mut *entry;
+                if entry.prev_entry.is_null() {
+                    descriptor.first_entry = entry.next_entry;
+                } else {
+                    (*entry.prev_entry).next_entry = entry.next_entry;
+                }
+                if !entry.next_entry.is_null() {
+                    (*entry.next_entry).prev_entry = entry.prev_entry;
+                }
+            });
+            drop(Box::from_raw(entry));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_types::InstructionAddressMap;
+
+    #[test]
+    fn test_create_image() {
+        let mut module = ModuleInfo::new();
+        module.name = Some("hello".to_string());
+        module
+            .function_names
+            .insert(FunctionIndex::from_u32(0), "main".to_string());
+        let address_map = FunctionAddressMap {
+            instructions: vec![
+                InstructionAddressMap {
+                    srcloc: SourceLoc::new(0x20),
+                    code_offset: 0,
+                    code_len: 4,
+                },
+                InstructionAddressMap {
+                    srcloc: SourceLoc::new(0x22),
+                    code_offset: 4,
+                    code_len: 8,
+                },
+            ],
+            ..Default::default()
+        };
+        let functions = vec![
+            JitFunction {
+                index: FunctionIndex::from_u32(0),
+                start: 0x1000,
+                len: 12,
+                address_map: &address_map,
+            },
+            JitFunction {
+                index: FunctionIndex::from_u32(1),
+                start: 0x1010,
+                len: 12,
+                address_map: &address_map,
+            },
+        ];
+        let image = match create_image(&module, functions.into_iter(), &LineTable::default()) {
+            Some(image) => image,
+            None => return assert!(elf_machine().is_none()),
+        };
+        assert_eq!(&image[..4], b"\x7fELF");
+        let contains = |bytes: &[u8]| image.windows(bytes.len()).any(|w| w == bytes);
+        assert!(contains(b".debug_line\0"));
+        assert!(contains(b".debug_info\0"));
+        assert!(contains(b"main\0"));
+        assert!(contains(b"wasm-function[1]\0"));
+        assert!(contains(b"hello.wasm\0"));
+    }
+}
@@ -1,5 +1,9 @@
+#[cfg(feature = "dwarf")]
+mod dwarf;
 mod error;
 mod frame_info;
+#[cfg(feature = "jit-debug")]
+mod gdb_jit;
 pub use error::RuntimeError;
 pub use frame_info::{
     register as register_frame_info, FrameInfo, FunctionExtent, GlobalFrameInfoRegistration,
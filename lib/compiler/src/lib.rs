@@ -75,7 +75,8 @@ pub use crate::compiler::{Compiler, CompilerConfig};
 pub use crate::translator::{
     from_binaryreadererror_wasmerror, translate_module, wptype_to_type, FunctionBinaryReader,
     FunctionBodyData, FunctionMiddleware, MiddlewareBinaryReader, MiddlewareReaderState,
-    ModuleEnvironment, ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
+    ModuleEnvironment, ModuleMiddleware, ModuleMiddlewareChain, ModuleRewriter,
+    ModuleTranslationState,
 };
 
 pub use wasmer_types::{Addend, CodeOffset, Features};
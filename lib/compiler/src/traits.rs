@@ -27,6 +27,9 @@ pub trait ArtifactCreate: Send + Sync + Upcastable {
     /// Returns the CPU features for this Artifact
     fn cpu_features(&self) -> EnumSet<CpuFeature>;
 
+    /// Returns the target triple for this Artifact
+    fn target_triple(&self) -> &str;
+
     /// Returns the memory styles associated with this `Artifact`.
     fn memory_styles(&self) -> &PrimaryMap<MemoryIndex, MemoryStyle>;
 
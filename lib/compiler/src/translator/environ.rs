@@ -416,6 +416,11 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    pub(crate) fn declare_code_section_start(&mut self, offset: usize) -> WasmResult<()> {
+        self.module.code_section_offset = offset.try_into().unwrap();
+        Ok(())
+    }
+
     pub(crate) fn declare_module_name(&mut self, name: &'data str) -> WasmResult<()> {
         self.module.name = Some(name.to_string());
         Ok(())
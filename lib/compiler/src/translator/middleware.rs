@@ -1,5 +1,32 @@
 //! The middleware parses the function binary bytecodes and transform them
 //! with the chosen functions.
+//!
+//! # Writing a middleware
+//!
+//! Middlewares are the extension point for instrumenting modules from
+//! outside of the compilers. They are registered on a compiler with
+//! `CompilerConfig::push_middleware` and are applied by every compiler in
+//! the same way:
+//!
+//! 1. [`ModuleMiddleware::transform_module_info`] is called once per
+//!    module, before any function is compiled. This is where globals
+//!    needed by the instrumentation are added (push a `GlobalType` to
+//!    `ModuleInfo::globals` and its initializer to
+//!    `ModuleInfo::global_initializers`) and optionally exported through
+//!    `ModuleInfo::exports` so the host can read them after execution.
+//! 2. [`ModuleMiddleware::generate_function_middleware`] is called for
+//!    every local function, possibly from several compilation threads at
+//!    once. The returned [`FunctionMiddleware`] holds the state local to
+//!    that function (e.g. the cost of the current basic block).
+//! 3. [`FunctionMiddleware::feed`] receives the operators of the function
+//!    body in order. Whatever is pushed to the [`MiddlewareReaderState`]
+//!    replaces the operator: forward it unchanged with
+//!    [`MiddlewareReaderState::push_operator`], drop it, or surround it with
+//!    additional operators.
+//!
+//! Middlewares are chained: the output of one middleware is the input of
+//! the next one, in the order they were pushed. The `wasmer-middlewares`
+//! crate and the `middleware_*` examples are templates to start from.
 
 use smallvec::SmallVec;
 use std::collections::VecDeque;
@@ -96,6 +123,12 @@ impl<'a> MiddlewareReaderState<'a> {
     pub fn push_operator(&mut self, operator: Operator<'a>) {
         self.pending_operations.push_back(operator);
     }
+
+    /// Returns the offset, in the module binary, right after the operator
+    /// being fed. Useful to map instrumentation back to the original code.
+    pub fn original_position(&self) -> usize {
+        self.inner.original_position()
+    }
 }
 
 impl<'a> Extend<Operator<'a>> for MiddlewareReaderState<'a> {
@@ -8,6 +8,7 @@
 mod environ;
 mod middleware;
 mod module;
+mod rewriter;
 mod state;
 #[macro_use]
 mod error;
@@ -19,6 +20,7 @@ pub use self::middleware::{
     ModuleMiddlewareChain,
 };
 pub use self::module::translate_module;
+pub use self::rewriter::ModuleRewriter;
 pub use self::sections::wptype_to_type;
 pub use self::state::ModuleTranslationState;
 pub use error::from_binaryreadererror_wasmerror;
@@ -0,0 +1,549 @@
+//! Binary-level rewriting of a WebAssembly module's imports and exports.
+//!
+//! The [`ModuleRewriter`] works section by section: only the sections
+//! it needs to change (types are only read) are decoded and
+//! re-encoded, every other section is copied verbatim. This keeps
+//! function bodies, custom sections and unknown proposals intact.
+use super::error::from_binaryreadererror_wasmerror;
+use std::collections::HashMap;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use wasmer_types::{WasmError, WasmResult};
+use wasmparser::{
+    BinaryReader, ExportSectionReader, ExternalKind, FunctionSectionReader, ImportSectionEntryType,
+    ImportSectionReader, TypeDef, TypeSectionReader,
+};
+
+const SECTION_TYPE: u8 = 1;
+const SECTION_IMPORT: u8 = 2;
+const SECTION_FUNCTION: u8 = 3;
+const SECTION_EXPORT: u8 = 7;
+const SECTION_CODE: u8 = 10;
+
+/// Rewrites the imports and exports of a WebAssembly module.
+///
+/// Usage:
+///
+/// ```ignore
+/// let wasm = ModuleRewriter::new()
+///     .alias_namespace("wasi_unstable", "wasi_snapshot_preview1")
+///     .rename_export("main", "_start")
+///     .add_trampoline("_start", "run")
+///     .rewrite(&wasm)?;
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ModuleRewriter {
+    namespace_aliases: HashMap<String, String>,
+    import_renames: HashMap<(String, String), (String, String)>,
+    export_renames: HashMap<String, String>,
+    trampolines: Vec<(String, String)>,
+}
+
+impl ModuleRewriter {
+    /// Creates a rewriter that leaves the module untouched.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves every import of the `from` namespace to the `to` namespace,
+    /// e.g. `wasi_unstable` to `wasi_snapshot_preview1`.
+    pub fn alias_namespace(&mut self, from: &str, to: &str) -> &mut Self {
+        self.namespace_aliases
+            .insert(from.to_string(), to.to_string());
+        self
+    }
+
+    /// Renames the single import `module.name` to `new_module.new_name`.
+    ///
+    /// Import renames take precedence over namespace aliases.
+    pub fn rename_import(
+        &mut self,
+        module: &str,
+        name: &str,
+        new_module: &str,
+        new_name: &str,
+    ) -> &mut Self {
+        self.import_renames.insert(
+            (module.to_string(), name.to_string()),
+            (new_module.to_string(), new_name.to_string()),
+        );
+        self
+    }
+
+    /// Renames the export `from` to `to`.
+    pub fn rename_export(&mut self, from: &str, to: &str) -> &mut Self {
+        self.export_renames.insert(from.to_string(), to.to_string());
+        self
+    }
+
+    /// Injects a new function, exported as `alias`, that forwards all its
+    /// arguments to the function currently exported as `export` and
+    /// returns its results.
+    ///
+    /// `export` refers to the name in the original module, before any
+    /// [`ModuleRewriter::rename_export`] is applied.
+    pub fn add_trampoline(&mut self, export: &str, alias: &str) -> &mut Self {
+        self.trampolines
+            .push((export.to_string(), alias.to_string()));
+        self
+    }
+
+    /// Returns `true` if the rewriter has nothing to do.
+    pub fn is_empty(&self) -> bool {
+        self.namespace_aliases.is_empty()
+            && self.import_renames.is_empty()
+            && self.export_renames.is_empty()
+            && self.trampolines.is_empty()
+    }
+
+    /// Applies the configured transformations to the binary `wasm` module
+    /// and returns the rewritten module.
+    pub fn rewrite(&self, wasm: &[u8]) -> WasmResult<Vec<u8>> {
+        let sections = split_sections(wasm)?;
+
+        // Gather what the trampolines need: the signature of each
+        // function, and which function each export points to.
+        let mut param_counts = Vec::new();
+        let mut func_types = Vec::new();
+        let mut exported_funcs = HashMap::new();
+        for section in sections.iter() {
+            match section.id {
+                SECTION_TYPE => {
+                    let mut reader = TypeSectionReader::new(section.data, section.offset)
+                        .map_err(from_binaryreadererror_wasmerror)?;
+                    for _ in 0..reader.get_count() {
+                        match reader.read().map_err(from_binaryreadererror_wasmerror)? {
+                            TypeDef::Func(ty) => param_counts.push(Some(ty.params.len() as u32)),
+                            _ => param_counts.push(None),
+                        }
+                    }
+                }
+                SECTION_IMPORT => {
+                    let mut reader = ImportSectionReader::new(section.data, section.offset)
+                        .map_err(from_binaryreadererror_wasmerror)?;
+                    for _ in 0..reader.get_count() {
+                        let import = reader.read().map_err(from_binaryreadererror_wasmerror)?;
+                        if let ImportSectionEntryType::Function(ty) = import.ty {
+                            func_types.push(ty);
+                        }
+                    }
+                }
+                SECTION_FUNCTION => {
+                    let mut reader = FunctionSectionReader::new(section.data, section.offset)
+                        .map_err(from_binaryreadererror_wasmerror)?;
+                    for _ in 0..reader.get_count() {
+                        func_types.push(reader.read().map_err(from_binaryreadererror_wasmerror)?);
+                    }
+                }
+                SECTION_EXPORT => {
+                    let mut reader = ExportSectionReader::new(section.data, section.offset)
+                        .map_err(from_binaryreadererror_wasmerror)?;
+                    for _ in 0..reader.get_count() {
+                        let export = reader.read().map_err(from_binaryreadererror_wasmerror)?;
+                        if let ExternalKind::Function = export.kind {
+                            exported_funcs.insert(export.field.to_string(), export.index);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut new_types = Vec::new();
+        let mut new_bodies = Vec::new();
+        let mut new_exports = Vec::new();
+        for (i, (export, alias)) in self.trampolines.iter().enumerate() {
+            let target = *exported_funcs.get(export).ok_or_else(|| {
+                WasmError::Generic(format!(
+                    "cannot add trampoline `{}`: there is no exported function `{}`",
+                    alias, export
+                ))
+            })?;
+            let (ty, params) = func_types
+                .get(target as usize)
+                .and_then(|&ty| Some((ty, param_counts.get(ty as usize).copied().flatten()?)))
+                .ok_or_else(|| {
+                    WasmError::Generic(format!("function `{}` has an invalid type", export))
+                })?;
+
+            let mut body = Vec::new();
+            // No locals.
+            write_u32(&mut body, 0);
+            for param in 0..params {
+                // local.get
+                body.push(0x20);
+                write_u32(&mut body, param);
+            }
+            // call
+            body.push(0x10);
+            write_u32(&mut body, target);
+            // end
+            body.push(0x0b);
+
+            new_types.push(ty);
+            new_bodies.push(body);
+            new_exports.push((alias.as_str(), func_types.len() as u32 + i as u32));
+        }
+
+        let mut out = wasm[..8].to_vec();
+        let mut wrote_functions = false;
+        let mut wrote_code = false;
+        for section in sections.iter() {
+            if !new_types.is_empty() {
+                // Modules that only import functions have neither a function
+                // nor a code section, they must be inserted at the right place.
+                if !wrote_functions && section_order(section.id) > section_order(SECTION_FUNCTION) {
+                    write_section(
+                        &mut out,
+                        SECTION_FUNCTION,
+                        &self.extend_vec(None, &new_types)?,
+                    );
+                    wrote_functions = true;
+                }
+                if !wrote_code && section_order(section.id) > section_order(SECTION_CODE) {
+                    write_section(
+                        &mut out,
+                        SECTION_CODE,
+                        &self.extend_code(None, &new_bodies)?,
+                    );
+                    wrote_code = true;
+                }
+            }
+            match section.id {
+                SECTION_IMPORT => {
+                    write_section(&mut out, SECTION_IMPORT, &self.rewrite_imports(section)?)
+                }
+                SECTION_FUNCTION if !new_types.is_empty() => {
+                    write_section(
+                        &mut out,
+                        SECTION_FUNCTION,
+                        &self.extend_vec(Some(section), &new_types)?,
+                    );
+                    wrote_functions = true;
+                }
+                SECTION_EXPORT => write_section(
+                    &mut out,
+                    SECTION_EXPORT,
+                    &self.rewrite_exports(section, &new_exports)?,
+                ),
+                SECTION_CODE if !new_bodies.is_empty() => {
+                    write_section(
+                        &mut out,
+                        SECTION_CODE,
+                        &self.extend_code(Some(section), &new_bodies)?,
+                    );
+                    wrote_code = true;
+                }
+                _ => out.extend_from_slice(section.raw),
+            }
+        }
+        if !new_types.is_empty() {
+            if !wrote_functions {
+                write_section(
+                    &mut out,
+                    SECTION_FUNCTION,
+                    &self.extend_vec(None, &new_types)?,
+                );
+            }
+            if !wrote_code {
+                write_section(
+                    &mut out,
+                    SECTION_CODE,
+                    &self.extend_code(None, &new_bodies)?,
+                );
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn rewrite_imports(&self, section: &Section) -> WasmResult<Vec<u8>> {
+        let mut reader = ImportSectionReader::new(section.data, section.offset)
+            .map_err(from_binaryreadererror_wasmerror)?;
+        let count = reader.get_count();
+        let mut out = Vec::new();
+        write_u32(&mut out, count);
+        for _ in 0..count {
+            let start = reader.original_position() - section.offset;
+            let import = reader.read().map_err(from_binaryreadererror_wasmerror)?;
+            let end = reader.original_position() - section.offset;
+            let field = import.field.ok_or_else(|| {
+                WasmError::Unsupported("module linking imports cannot be rewritten".to_string())
+            })?;
+
+            // Keep the import descriptor as is, only the names change.
+            let mut raw = BinaryReader::new(&section.data[start..end]);
+            raw.skip_string()
+                .map_err(from_binaryreadererror_wasmerror)?;
+            raw.skip_string()
+                .map_err(from_binaryreadererror_wasmerror)?;
+            let descriptor = &section.data[start + raw.current_position()..end];
+
+            let (module, field) = match self
+                .import_renames
+                .get(&(import.module.to_string(), field.to_string()))
+            {
+                Some((module, field)) => (module.as_str(), field.as_str()),
+                None => (
+                    self.namespace_aliases
+                        .get(import.module)
+                        .map(String::as_str)
+                        .unwrap_or(import.module),
+                    field,
+                ),
+            };
+            write_name(&mut out, module);
+            write_name(&mut out, field);
+            out.extend_from_slice(descriptor);
+        }
+        Ok(out)
+    }
+
+    fn rewrite_exports(
+        &self,
+        section: &Section,
+        new_exports: &[(&str, u32)],
+    ) -> WasmResult<Vec<u8>> {
+        let mut reader = ExportSectionReader::new(section.data, section.offset)
+            .map_err(from_binaryreadererror_wasmerror)?;
+        let count = reader.get_count();
+        let mut out = Vec::new();
+        write_u32(&mut out, count + new_exports.len() as u32);
+        for _ in 0..count {
+            let start = reader.original_position() - section.offset;
+            let export = reader.read().map_err(from_binaryreadererror_wasmerror)?;
+            let end = reader.original_position() - section.offset;
+
+            let mut raw = BinaryReader::new(&section.data[start..end]);
+            raw.skip_string()
+                .map_err(from_binaryreadererror_wasmerror)?;
+            let descriptor = &section.data[start + raw.current_position()..end];
+
+            let name = self
+                .export_renames
+                .get(export.field)
+                .map(String::as_str)
+                .unwrap_or(export.field);
+            write_name(&mut out, name);
+            out.extend_from_slice(descriptor);
+        }
+        for (name, index) in new_exports {
+            write_name(&mut out, name);
+            // Function export.
+            out.push(0x00);
+            write_u32(&mut out, *index);
+        }
+        Ok(out)
+    }
+
+    /// Appends `items` to a section made of a vector of `u32`s.
+    fn extend_vec(&self, section: Option<&Section>, items: &[u32]) -> WasmResult<Vec<u8>> {
+        let (count, rest) = match section {
+            Some(section) => split_count(section)?,
+            None => (0, &[][..]),
+        };
+        let mut out = Vec::new();
+        write_u32(&mut out, count + items.len() as u32);
+        out.extend_from_slice(rest);
+        for item in items {
+            write_u32(&mut out, *item);
+        }
+        Ok(out)
+    }
+
+    /// Appends the function `bodies` to the code section.
+    fn extend_code(&self, section: Option<&Section>, bodies: &[Vec<u8>]) -> WasmResult<Vec<u8>> {
+        let (count, rest) = match section {
+            Some(section) => split_count(section)?,
+            None => (0, &[][..]),
+        };
+        let mut out = Vec::new();
+        write_u32(&mut out, count + bodies.len() as u32);
+        out.extend_from_slice(rest);
+        for body in bodies {
+            write_u32(&mut out, body.len() as u32);
+            out.extend_from_slice(body);
+        }
+        Ok(out)
+    }
+}
+
+/// A raw section of a WebAssembly module.
+struct Section<'data> {
+    id: u8,
+    /// The whole section, header included.
+    raw: &'data [u8],
+    /// The section content.
+    data: &'data [u8],
+    /// Offset of `data` in the module.
+    offset: usize,
+}
+
+fn split_sections(wasm: &[u8]) -> WasmResult<Vec<Section<'_>>> {
+    if wasm.len() < 8 || &wasm[..4] != b"\0asm" {
+        return Err(WasmError::InvalidWebAssembly {
+            message: "expected a WebAssembly binary module".to_string(),
+            offset: 0,
+        });
+    }
+    let mut reader = BinaryReader::new(wasm);
+    reader
+        .skip_bytes(8)
+        .map_err(from_binaryreadererror_wasmerror)?;
+    let mut sections = Vec::new();
+    while !reader.eof() {
+        let start = reader.current_position();
+        let id = reader.read_u8().map_err(from_binaryreadererror_wasmerror)? as u8;
+        let size = reader
+            .read_var_u32()
+            .map_err(from_binaryreadererror_wasmerror)? as usize;
+        let offset = reader.current_position();
+        let data = reader
+            .read_bytes(size)
+            .map_err(from_binaryreadererror_wasmerror)?;
+        sections.push(Section {
+            id,
+            raw: &wasm[start..offset + size],
+            data,
+            offset,
+        });
+    }
+    Ok(sections)
+}
+
+/// Splits a vector section into its element count and the raw elements.
+fn split_count<'data>(section: &Section<'data>) -> WasmResult<(u32, &'data [u8])> {
+    let mut reader = BinaryReader::new_with_offset(section.data, section.offset);
+    let count = reader
+        .read_var_u32()
+        .map_err(from_binaryreadererror_wasmerror)?;
+    Ok((count, &section.data[reader.current_position()..]))
+}
+
+/// The position a known section must appear at in a module.
+fn section_order(id: u8) -> u8 {
+    match id {
+        // Custom sections may appear anywhere.
+        0 => 0,
+        // The data count section goes between the element and code sections.
+        12 => 95,
+        // The tag section goes between the memory and global sections.
+        13 => 55,
+        id => id * 10,
+    }
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, data: &[u8]) {
+    out.push(id);
+    write_u32(out, data.len() as u32);
+    out.extend_from_slice(data);
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_u32(out, name.len() as u32);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    leb128::write::unsigned(out, value.into()).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmparser::{Parser, Payload};
+
+    // (module
+    //   (type (func (param i32 i32) (result i32)))
+    //   (import "wasi_unstable" "fd_write" (func (type 0)))
+    //   (func (type 0) local.get 0)
+    //   (export "add" (func 1)))
+    const MODULE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type
+        0x02, 0x1a, 0x01, 0x0d, b'w', b'a', b's', b'i', b'_', b'u', b'n', b's', b't', b'a', b'b',
+        b'l', b'e', 0x08, b'f', b'd', b'_', b'w', b'r', b'i', b't', b'e', 0x00,
+        0x00, // import
+        0x03, 0x02, 0x01, 0x00, // function
+        0x07, 0x07, 0x01, 0x03, b'a', b'd', b'd', 0x00, 0x01, // export
+        0x0a, 0x06, 0x01, 0x04, 0x00, 0x20, 0x00, 0x0b, // code
+    ];
+
+    fn imports(wasm: &[u8]) -> Vec<(String, String)> {
+        let mut imports = Vec::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::ImportSection(mut reader) = payload.unwrap() {
+                for _ in 0..reader.get_count() {
+                    let import = reader.read().unwrap();
+                    imports.push((import.module.to_string(), import.field.unwrap().to_string()));
+                }
+            }
+        }
+        imports
+    }
+
+    fn exports(wasm: &[u8]) -> Vec<(String, u32)> {
+        let mut exports = Vec::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::ExportSection(mut reader) = payload.unwrap() {
+                for _ in 0..reader.get_count() {
+                    let export = reader.read().unwrap();
+                    exports.push((export.field.to_string(), export.index));
+                }
+            }
+        }
+        exports
+    }
+
+    #[test]
+    fn test_noop() {
+        assert_eq!(ModuleRewriter::new().rewrite(MODULE).unwrap(), MODULE);
+    }
+
+    #[test]
+    fn test_imports() {
+        let wasm = ModuleRewriter::new()
+            .alias_namespace("wasi_unstable", "wasi_snapshot_preview1")
+            .rewrite(MODULE)
+            .unwrap();
+        assert_eq!(
+            imports(&wasm),
+            vec![("wasi_snapshot_preview1".to_string(), "fd_write".to_string())]
+        );
+
+        let wasm = ModuleRewriter::new()
+            .alias_namespace("wasi_unstable", "wasi_snapshot_preview1")
+            .rename_import("wasi_unstable", "fd_write", "env", "write")
+            .rewrite(MODULE)
+            .unwrap();
+        assert_eq!(
+            imports(&wasm),
+            vec![("env".to_string(), "write".to_string())]
+        );
+        wasmparser::validate(&wasm).unwrap();
+    }
+
+    #[test]
+    fn test_exports_and_trampolines() {
+        let wasm = ModuleRewriter::new()
+            .rename_export("add", "sum")
+            .add_trampoline("add", "plus")
+            .rewrite(MODULE)
+            .unwrap();
+        assert_eq!(
+            exports(&wasm),
+            vec![("sum".to_string(), 1), ("plus".to_string(), 2)]
+        );
+        wasmparser::validate(&wasm).unwrap();
+
+        assert!(
+            matches!(
+                ModuleRewriter::new()
+                    .add_trampoline("missing", "plus")
+                    .rewrite(MODULE),
+                Err(WasmError::Generic(_))
+            ),
+            "trampolines need an existing export",
+        );
+    }
+}
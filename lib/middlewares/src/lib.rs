@@ -3,4 +3,4 @@ pub mod metering;
 // The most commonly used symbol are exported at top level of the
 // module. Others are available via modules,
 // e.g. `wasmer_middlewares::metering::get_remaining_points`
-pub use metering::Metering;
+pub use metering::{Metering, MeteringStore};
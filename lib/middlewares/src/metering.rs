@@ -9,12 +9,14 @@
 //! example](https://github.com/wasmerio/wasmer/blob/master/examples/metering.rs).
 
 use std::convert::TryInto;
+use std::error::Error;
 use std::fmt;
 use std::sync::{Arc, Mutex};
 use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
 use wasmer::{
     AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
-    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability,
+    RuntimeError, Type,
 };
 use wasmer_types::{GlobalIndex, ModuleInfo};
 
@@ -348,6 +350,90 @@ pub fn set_remaining_points(ctx: &mut impl AsStoreMut, instance: &Instance, poin
         .expect("Can't set `wasmer_metering_points_exhausted` in Instance");
 }
 
+/// The error of an execution stopped because the metering points of
+/// its instance were exhausted.
+///
+/// The middleware stops the execution with an `unreachable` trap;
+/// [`MeteringStore::metering_error`] tells it from the other traps by
+/// turning it into this error, which can be checked with
+/// `RuntimeError::is::<PointsExhausted>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointsExhausted;
+
+impl fmt::Display for PointsExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("metering points exhausted")
+    }
+}
+
+impl Error for PointsExhausted {}
+
+/// The metering points of the instances of a store, for the modules
+/// compiled with the [`Metering`] middleware.
+///
+/// It is implemented for every store, so the points can be handled
+/// through it, e.g. `store.set_remaining_points(&instance, 10)`.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance, RuntimeError, TypedFunction};
+/// use wasmer_middlewares::metering::{MeteringStore, PointsExhausted};
+///
+/// fn call_with_points(
+///     store: &mut impl AsStoreMut,
+///     instance: &Instance,
+///     function: &TypedFunction<(), ()>,
+///     points: u64,
+/// ) -> Result<(), RuntimeError> {
+///     store.set_remaining_points(instance, points);
+///     function
+///         .call(store)
+///         .map_err(|error| store.metering_error(instance, error))
+/// }
+/// ```
+pub trait MeteringStore: AsStoreMut + Sized {
+    /// Whether `instance` was processed with the [`Metering`]
+    /// middleware at compile time.
+    fn is_metered(&self, instance: &Instance) -> bool {
+        instance
+            .exports
+            .get_global("wasmer_metering_remaining_points")
+            .is_ok()
+            && instance
+                .exports
+                .get_global("wasmer_metering_points_exhausted")
+                .is_ok()
+    }
+
+    /// The remaining points of `instance`, see [`get_remaining_points`].
+    fn remaining_points(&mut self, instance: &Instance) -> MeteringPoints {
+        get_remaining_points(self, instance)
+    }
+
+    /// Sets the remaining points of `instance`, see
+    /// [`set_remaining_points`].
+    fn set_remaining_points(&mut self, instance: &Instance, points: u64) {
+        set_remaining_points(self, instance, points)
+    }
+
+    /// Turns `error`, returned by a function of `instance`, into
+    /// [`PointsExhausted`] if the execution stopped because the points
+    /// were exhausted; the other errors are returned unchanged.
+    fn metering_error(&mut self, instance: &Instance, error: RuntimeError) -> RuntimeError {
+        if self.is_metered(instance)
+            && self.remaining_points(instance) == MeteringPoints::Exhausted
+            && !error.is::<PointsExhausted>()
+        {
+            RuntimeError::user(Box::new(PointsExhausted))
+        } else {
+            error
+        }
+    }
+}
+
+impl<T: AsStoreMut> MeteringStore for T {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -485,4 +571,41 @@ mod tests {
             MeteringPoints::Remaining(4)
         );
     }
+
+    #[test]
+    fn metering_store_works() {
+        let metering = Arc::new(Metering::new(4, cost_function));
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(metering);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        assert!(store.is_metered(&instance));
+        let add_one: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("add_one")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        add_one.call(&mut store, 1).unwrap();
+        assert_eq!(
+            store.remaining_points(&instance),
+            MeteringPoints::Remaining(0)
+        );
+
+        let error = add_one.call(&mut store, 1).unwrap_err();
+        let error = store.metering_error(&instance, error);
+        assert!(error.is::<PointsExhausted>());
+
+        store.set_remaining_points(&instance, 4);
+        assert_eq!(add_one.call(&mut store, 1).unwrap(), 2);
+
+        // Not metered
+        let mut store = Store::default();
+        let module = Module::new(&store, bytecode()).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        assert!(!store.is_metered(&instance));
+    }
 }
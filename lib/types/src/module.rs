@@ -133,6 +133,10 @@ pub struct ModuleInfo {
     /// The data for each CustomSection in the module.
     pub custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
 
+    /// The offset of the contents of the code section in the wasm
+    /// file, which the addresses of the DWARF sections are relative to.
+    pub code_section_offset: u32,
+
     /// Number of imported functions in the module.
     pub num_imported_functions: usize,
 
@@ -165,6 +169,7 @@ pub struct ArchivableModuleInfo {
     globals: PrimaryMap<GlobalIndex, GlobalType>,
     custom_sections: IndexMap<String, CustomSectionIndex>,
     custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
+    code_section_offset: u32,
     num_imported_functions: usize,
     num_imported_tables: usize,
     num_imported_memories: usize,
@@ -190,6 +195,7 @@ impl From<ModuleInfo> for ArchivableModuleInfo {
             globals: it.globals,
             custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
+            code_section_offset: it.code_section_offset,
             num_imported_functions: it.num_imported_functions,
             num_imported_tables: it.num_imported_tables,
             num_imported_memories: it.num_imported_memories,
@@ -218,6 +224,7 @@ impl From<ArchivableModuleInfo> for ModuleInfo {
             globals: it.globals,
             custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
+            code_section_offset: it.code_section_offset,
             num_imported_functions: it.num_imported_functions,
             num_imported_tables: it.num_imported_tables,
             num_imported_memories: it.num_imported_memories,
@@ -278,6 +285,7 @@ impl PartialEq for ModuleInfo {
             && self.globals == other.globals
             && self.custom_sections == other.custom_sections
             && self.custom_sections_data == other.custom_sections_data
+            && self.code_section_offset == other.code_section_offset
             && self.num_imported_functions == other.num_imported_functions
             && self.num_imported_tables == other.num_imported_tables
             && self.num_imported_memories == other.num_imported_memories
@@ -316,6 +324,10 @@ impl ModuleInfo {
     /// Get the export types of the module
     pub fn exports(&'_ self) -> ExportsIterator<impl Iterator<Item = ExportType> + '_> {
         let iter = self.exports.iter().map(move |(name, export_index)| {
+            let debug_name = match export_index {
+                ExportIndex::Function(i) => self.function_name(*i),
+                _ => None,
+            };
             let extern_type = match export_index {
                 ExportIndex::Function(i) => {
                     let signature = self.functions.get(*i).unwrap();
@@ -335,7 +347,7 @@ impl ModuleInfo {
                     ExternType::Global(*global_type)
                 }
             };
-            ExportType::new(name, extern_type)
+            ExportType::new(name, extern_type).with_debug_name(debug_name)
         });
         ExportsIterator::new(iter, self.exports.len())
     }
@@ -346,6 +358,10 @@ impl ModuleInfo {
             self.imports
                 .iter()
                 .map(move |(ImportKey { module, field, .. }, import_index)| {
+                    let debug_name = match import_index {
+                        ImportIndex::Function(i) => self.function_name(*i),
+                        _ => None,
+                    };
                     let extern_type = match import_index {
                         ImportIndex::Function(i) => {
                             let signature = self.functions.get(*i).unwrap();
@@ -365,11 +381,17 @@ impl ModuleInfo {
                             ExternType::Global(*global_type)
                         }
                     };
-                    ImportType::new(module, field, extern_type)
+                    ImportType::new(module, field, extern_type).with_debug_name(debug_name)
                 });
         ImportsIterator::new(iter, self.imports.len())
     }
 
+    /// Get the name given to a function by the name section of the
+    /// module, if any.
+    pub fn function_name(&self, index: FunctionIndex) -> Option<&str> {
+        self.function_names.get(&index).map(String::as_str)
+    }
+
     /// Get the custom sections of the module given a `name`.
     pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Box<[u8]>> + 'a {
         self.custom_sections
@@ -508,7 +530,9 @@ impl<I: Iterator<Item = ExportType> + Sized> ExportsIterator<I> {
     /// Get only the functions
     pub fn functions(self) -> impl Iterator<Item = ExportType<FunctionType>> + Sized {
         self.iter.filter_map(|extern_| match extern_.ty() {
-            ExternType::Function(ty) => Some(ExportType::new(extern_.name(), ty.clone())),
+            ExternType::Function(ty) => Some(
+                ExportType::new(extern_.name(), ty.clone()).with_debug_name(extern_.debug_name()),
+            ),
             _ => None,
         })
     }
@@ -567,11 +591,10 @@ impl<I: Iterator<Item = ImportType> + Sized> ImportsIterator<I> {
     /// Get only the functions
     pub fn functions(self) -> impl Iterator<Item = ImportType<FunctionType>> + Sized {
         self.iter.filter_map(|extern_| match extern_.ty() {
-            ExternType::Function(ty) => Some(ImportType::new(
-                extern_.module(),
-                extern_.name(),
-                ty.clone(),
-            )),
+            ExternType::Function(ty) => Some(
+                ImportType::new(extern_.module(), extern_.name(), ty.clone())
+                    .with_debug_name(extern_.debug_name()),
+            ),
             _ => None,
         })
     }
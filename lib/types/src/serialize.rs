@@ -61,6 +61,8 @@ pub struct SerializableModule {
     pub data_initializers: Box<[OwnedDataInitializer]>,
     /// CPU Feature flags for this compilation
     pub cpu_features: u64,
+    /// The target triple of this compilation
+    pub target_triple: String,
 }
 
 fn to_serialize_error(err: impl std::error::Error) -> SerializeError {
@@ -142,6 +144,11 @@ impl SerializableModule {
         EnumSet::from_u64(self.cpu_features)
     }
 
+    /// Returns the target triple for this Artifact
+    pub fn target_triple(&self) -> &str {
+        &self.target_triple
+    }
+
     /// Returns data initializers to pass to `InstanceHandle::initialize`
     pub fn data_initializers(&self) -> &[OwnedDataInitializer] {
         &self.data_initializers
@@ -178,7 +185,7 @@ pub struct MetadataHeader {
 impl MetadataHeader {
     /// Current ABI version. Increment this any time breaking changes are made
     /// to the format of the serialized data.
-    const CURRENT_VERSION: u32 = 1;
+    const CURRENT_VERSION: u32 = 3;
 
     /// Magic number to identify wasmer metadata.
     const MAGIC: [u8; 8] = *b"WASMER\0\0";
@@ -523,6 +523,7 @@ pub struct ImportType<T = ExternType> {
     module: String,
     name: String,
     ty: T,
+    debug_name: Option<String>,
 }
 
 impl<T> ImportType<T> {
@@ -533,9 +534,23 @@ impl<T> ImportType<T> {
             module: module.to_owned(),
             name: name.to_owned(),
             ty,
+            debug_name: None,
         }
     }
 
+    /// Sets the name given to the imported item by the name section of
+    /// the module.
+    pub fn with_debug_name(mut self, debug_name: Option<&str>) -> Self {
+        self.debug_name = debug_name.map(str::to_owned);
+        self
+    }
+
+    /// Returns the name given to the imported item by the name section
+    /// of the module, if any. Only functions can have one.
+    pub fn debug_name(&self) -> Option<&str> {
+        self.debug_name.as_deref()
+    }
+
     /// Returns the module name that this import is expected to come from.
     pub fn module(&self) -> &str {
         &self.module
@@ -569,6 +584,7 @@ impl<T> ImportType<T> {
 pub struct ExportType<T = ExternType> {
     name: String,
     ty: T,
+    debug_name: Option<String>,
 }
 
 impl<T> ExportType<T> {
@@ -578,9 +594,24 @@ impl<T> ExportType<T> {
         Self {
             name: name.to_string(),
             ty,
+            debug_name: None,
         }
     }
 
+    /// Sets the name given to the exported item by the name section of
+    /// the module.
+    pub fn with_debug_name(mut self, debug_name: Option<&str>) -> Self {
+        self.debug_name = debug_name.map(str::to_owned);
+        self
+    }
+
+    /// Returns the name given to the exported item by the name section
+    /// of the module, if any. Only functions can have one, which may
+    /// differ from the name of the export.
+    pub fn debug_name(&self) -> Option<&str> {
+        self.debug_name.as_deref()
+    }
+
     /// Returns the name by which this export is known by.
     pub fn name(&self) -> &str {
         &self.name
@@ -0,0 +1,132 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rand::{thread_rng, Rng};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use wasmer_vfs::{mem_fs, FileSystem};
+
+const FILES: usize = 10_000;
+const DEPTH: usize = 256;
+const PAGE: usize = 4 * 1024;
+const LARGE_FILE: usize = 64 * 1024 * 1024;
+
+fn create_files(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mem_fs");
+    group.throughput(Throughput::Elements(FILES as u64));
+
+    group.bench_function("create 10k files", |b| {
+        b.iter_batched(
+            mem_fs::FileSystem::default,
+            |fs| {
+                for i in 0..FILES {
+                    fs.new_open_options()
+                        .write(true)
+                        .create_new(true)
+                        .open(format!("/file{}", i))
+                        .unwrap();
+                }
+                fs
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn deep_tree(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mem_fs");
+
+    let deepest = (0..DEPTH).fold(PathBuf::from("/"), |path, i| path.join(format!("d{}", i)));
+
+    group.bench_function("create a deep directory tree", |b| {
+        b.iter_batched(
+            mem_fs::FileSystem::default,
+            |fs| {
+                let mut path = PathBuf::from("/");
+                for i in 0..DEPTH {
+                    path.push(format!("d{}", i));
+                    fs.create_dir(&path).unwrap();
+                }
+                fs
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    let fs = mem_fs::FileSystem::default();
+    let mut path = PathBuf::from("/");
+    for i in 0..DEPTH {
+        path.push(format!("d{}", i));
+        fs.create_dir(&path).unwrap();
+    }
+
+    group.bench_function("stat the bottom of a deep directory tree", |b| {
+        b.iter(|| fs.metadata(black_box(&deepest)).unwrap())
+    });
+
+    group.finish();
+}
+
+fn random_writes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mem_fs");
+    group.throughput(Throughput::Bytes(PAGE as u64));
+
+    let fs = mem_fs::FileSystem::default();
+    let mut file = fs
+        .new_open_options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(Path::new("/random"))
+        .unwrap();
+    file.set_len((PAGE * 1024) as u64).unwrap();
+    let page = [0xa5; PAGE];
+    let mut rng = thread_rng();
+
+    group.bench_function("random 4KB writes", |b| {
+        b.iter(|| {
+            let offset = rng.gen_range(0..1024) * PAGE;
+            file.seek(SeekFrom::Start(offset as u64)).unwrap();
+            file.write_all(black_box(&page)).unwrap();
+        })
+    });
+
+    group.finish();
+}
+
+fn sequential_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mem_fs");
+    group.throughput(Throughput::Bytes(LARGE_FILE as u64));
+    group.sample_size(10);
+
+    let fs = mem_fs::FileSystem::default();
+    let mut file = fs
+        .new_open_options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(Path::new("/large"))
+        .unwrap();
+    file.write_all(&vec![0x5a; LARGE_FILE]).unwrap();
+    let mut buffer = vec![0; 64 * 1024];
+
+    group.bench_function("large sequential reads", |b| {
+        b.iter(|| {
+            file.seek(SeekFrom::Start(0)).unwrap();
+            while file.read(&mut buffer).unwrap() > 0 {
+                black_box(&buffer);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    create_files,
+    deep_tree,
+    random_writes,
+    sequential_reads
+);
+criterion_main!(benches);
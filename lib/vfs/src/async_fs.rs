@@ -0,0 +1,713 @@
+//! An asynchronous variant of the file system traits, for storages
+//! which can only be accessed asynchronously, like IndexedDB, OPFS
+//! or `fetch()` in a browser, along with a synchronous file system
+//! running on top of it.
+//!
+//! Files are accessed by path, at a given offset, rather than through
+//! handles, so that a storage doesn't have to keep files open.
+//!
+//! The synchronous file system blocks the calling thread until the
+//! futures of the storage complete. They must thus make progress
+//! without this thread, e.g. by being serviced by another worker:
+//! blocking the thread running the event loop they depend on would
+//! never return.
+
+use crate::{
+    DirEntry, FileDescriptor, FileOpener, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, Result, VirtualFile, WatchHandle,
+};
+use std::fmt;
+use std::future::{self, Future};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// A future returned by an `AsyncFileSystem`.
+///
+/// It isn't required to be `Send`, since the futures of JavaScript
+/// APIs are bound to their thread.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// A file system whose operations are asynchronous.
+pub trait AsyncFileSystem: fmt::Debug + Send + Sync + 'static {
+    fn read_dir<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Vec<DirEntry>>>;
+    fn create_dir<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>>;
+    fn remove_dir<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>>;
+    fn rename<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>>;
+    fn metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Metadata>>;
+    fn remove_file<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>>;
+
+    /// Creates an empty file at `path`, failing with `AlreadyExists`
+    /// if there's already one.
+    fn create_file<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>>;
+
+    /// Reads at most `len` bytes of the file at `path`, from
+    /// `offset`. Fewer bytes are only returned at the end of the
+    /// file.
+    fn read<'a>(
+        &'a self,
+        path: &'a Path,
+        offset: u64,
+        len: usize,
+    ) -> BoxFuture<'a, Result<Vec<u8>>>;
+
+    /// Writes `data` in the file at `path`, from `offset`, extending
+    /// the file if needed.
+    fn write<'a>(
+        &'a self,
+        path: &'a Path,
+        offset: u64,
+        data: &'a [u8],
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Truncates or extends the file at `path` to `len` bytes.
+    fn set_len<'a>(&'a self, path: &'a Path, len: u64) -> BoxFuture<'a, Result<()>>;
+
+    /// See [`crate::FileSystem::symlink_metadata`].
+    fn symlink_metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Metadata>> {
+        self.metadata(path)
+    }
+
+    /// See [`crate::FileSystem::symlink`].
+    fn symlink<'a>(&'a self, _original: &'a Path, _link: &'a Path) -> BoxFuture<'a, Result<()>> {
+        ready(Err(FsError::Unsupported))
+    }
+
+    /// See [`crate::FileSystem::read_link`].
+    fn read_link<'a>(&'a self, _path: &'a Path) -> BoxFuture<'a, Result<PathBuf>> {
+        ready(Err(FsError::InvalidInput))
+    }
+
+    /// See [`crate::FileSystem::link`].
+    fn link<'a>(&'a self, _original: &'a Path, _link: &'a Path) -> BoxFuture<'a, Result<()>> {
+        ready(Err(FsError::Unsupported))
+    }
+
+    /// See [`crate::FileSystem::chmod`].
+    fn chmod<'a>(&'a self, _path: &'a Path, _mode: u32) -> BoxFuture<'a, Result<()>> {
+        ready(Err(FsError::Unsupported))
+    }
+
+    /// See [`crate::FileSystem::chown`].
+    fn chown<'a>(
+        &'a self,
+        _path: &'a Path,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+    ) -> BoxFuture<'a, Result<()>> {
+        ready(Err(FsError::Unsupported))
+    }
+
+    /// See [`crate::FileSystem::set_times`].
+    fn set_times<'a>(
+        &'a self,
+        _path: &'a Path,
+        _accessed: Option<u64>,
+        _modified: Option<u64>,
+    ) -> BoxFuture<'a, Result<()>> {
+        ready(Err(FsError::Unsupported))
+    }
+
+    /// See [`crate::FileSystem::clone_file`]. The default
+    /// implementation reads the whole file and writes it to `to`.
+    fn clone_file<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let len = self.metadata(from).await?.len;
+            let contents = self.read(from, 0, len as usize).await?;
+            match self.create_file(to).await {
+                Ok(()) | Err(FsError::AlreadyExists) => {}
+                Err(error) => return Err(error),
+            }
+            self.set_len(to, 0).await?;
+            self.write(to, 0, &contents).await
+        })
+    }
+
+    /// See [`crate::FileSystem::watch`].
+    fn watch<'a>(
+        &'a self,
+        _path: &'a Path,
+        _recursive: bool,
+    ) -> BoxFuture<'a, Result<WatchHandle>> {
+        ready(Err(FsError::Unsupported))
+    }
+}
+
+/// A future which is already complete.
+fn ready<'a, T: 'a>(output: T) -> BoxFuture<'a, T> {
+    Box::pin(future::ready(output))
+}
+
+/// Wakes a thread blocked in `block_on`.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs `future` to completion, blocking the current thread.
+fn block_on<T>(future: BoxFuture<'_, T>) -> T {
+    let mut future = future;
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// The synchronous file system, running on top of an asynchronous
+/// one. It is cheap to clone, clones share the same storage.
+#[derive(Debug)]
+pub struct FileSystem<F> {
+    inner: Arc<F>,
+}
+
+impl<F> FileSystem<F> {
+    /// Runs on top of `inner`.
+    pub fn new(inner: F) -> Self {
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// The asynchronous file system.
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+}
+
+impl<F> Clone for FileSystem<F> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<F: AsyncFileSystem> crate::FileSystem for FileSystem<F> {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        block_on(self.inner.read_dir(path)).map(ReadDir::new)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        block_on(self.inner.create_dir(path))
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        block_on(self.inner.remove_dir(path))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        block_on(self.inner.rename(from, to))
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        block_on(self.inner.metadata(path))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        block_on(self.inner.symlink_metadata(path))
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        block_on(self.inner.symlink(original, link))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        block_on(self.inner.read_link(path))
+    }
+
+    fn link(&self, original: &Path, link: &Path) -> Result<()> {
+        block_on(self.inner.link(original, link))
+    }
+
+    fn chmod(&self, path: &Path, mode: u32) -> Result<()> {
+        block_on(self.inner.chmod(path, mode))
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        block_on(self.inner.chown(path, uid, gid))
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<u64>, modified: Option<u64>) -> Result<()> {
+        block_on(self.inner.set_times(path, accessed, modified))
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        block_on(self.inner.remove_file(path))
+    }
+
+    fn clone_file(&self, from: &Path, to: &Path) -> Result<()> {
+        block_on(self.inner.clone_file(from, to))
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<WatchHandle> {
+        block_on(self.inner.watch(path, recursive))
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl<F: AsyncFileSystem> FileOpener for FileSystem<F> {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        match block_on(self.inner.metadata(path)) {
+            Ok(_) if conf.create_new() => return Err(FsError::AlreadyExists),
+            Ok(metadata) if metadata.is_dir() => return Err(FsError::NotAFile),
+            Ok(_) => {}
+            Err(FsError::EntityNotFound) if conf.create() || conf.create_new() => {
+                block_on(self.inner.create_file(path))?
+            }
+            Err(err) => return Err(err),
+        }
+
+        let writable = conf.write() || conf.append();
+        if conf.truncate() && writable {
+            block_on(self.inner.set_len(path, 0))?;
+        }
+
+        Ok(Box::new(File {
+            fs: self.inner.clone(),
+            path: path.to_path_buf(),
+            position: 0,
+            readable: conf.read(),
+            writable,
+            append: conf.append(),
+        }))
+    }
+}
+
+/// Converts an error of the storage, for the `std::io` traits.
+fn io_error(error: FsError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error)
+}
+
+/// A file of the file system.
+#[derive(Debug)]
+struct File<F> {
+    fs: Arc<F>,
+    path: PathBuf,
+    position: u64,
+    readable: bool,
+    writable: bool,
+    append: bool,
+}
+
+impl<F: AsyncFileSystem> File<F> {
+    fn metadata(&self) -> Result<Metadata> {
+        block_on(self.fs.metadata(&self.path))
+    }
+}
+
+impl<F: AsyncFileSystem> VirtualFile for File<F> {
+    fn last_accessed(&self) -> u64 {
+        self.metadata().map_or(0, |metadata| metadata.accessed)
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.metadata().map_or(0, |metadata| metadata.modified)
+    }
+
+    fn created_time(&self) -> u64 {
+        self.metadata().map_or(0, |metadata| metadata.created)
+    }
+
+    fn size(&self) -> u64 {
+        self.metadata().map_or(0, |metadata| metadata.len)
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<()> {
+        if !self.writable {
+            return Err(FsError::PermissionDenied);
+        }
+
+        block_on(self.fs.set_len(&self.path, new_size))
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        block_on(self.fs.remove_file(&self.path))
+    }
+
+    fn bytes_available(&self) -> Result<usize> {
+        Ok(self.metadata()?.len.saturating_sub(self.position) as usize)
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>> {
+        self.bytes_available().map(Some)
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>> {
+        Ok(None)
+    }
+
+    fn is_readable(&self) -> bool {
+        self.readable
+    }
+
+    fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        None
+    }
+}
+
+impl<F: AsyncFileSystem> Read for File<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.readable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the file isn't open for reading",
+            ));
+        }
+
+        let data =
+            block_on(self.fs.read(&self.path, self.position, buf.len())).map_err(io_error)?;
+        let read = data.len().min(buf.len());
+        buf[..read].copy_from_slice(&data[..read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<F: AsyncFileSystem> Seek for File<F> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match position {
+            SeekFrom::Start(offset) => {
+                self.position = offset;
+                return Ok(offset);
+            }
+            SeekFrom::End(offset) => (self.metadata().map_err(io_error)?.len, offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        match (base as i64).checked_add(offset) {
+            Some(position) if position >= 0 => {
+                self.position = position as u64;
+                Ok(self.position)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seeking before the start of the file",
+            )),
+        }
+    }
+}
+
+impl<F: AsyncFileSystem> Write for File<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.writable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the file isn't open for writing",
+            ));
+        }
+
+        if self.append {
+            self.position = self.metadata().map_err(io_error)?.len;
+        }
+        block_on(self.fs.write(&self.path, self.position, buf)).map_err(io_error)?;
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_filesystem {
+    use super::{AsyncFileSystem, BoxFuture, FileSystem};
+    use crate::{mem_fs, DirEntry, FileSystem as FS, FsError, Metadata, Result};
+    use std::future::Future;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::path::{Path, PathBuf};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::thread;
+    use std::time::Duration;
+
+    macro_rules! path {
+        ($path:expr) => {
+            Path::new($path)
+        };
+    }
+
+    /// Completes once woken up by another thread.
+    #[derive(Default)]
+    struct Delay {
+        started: bool,
+    }
+
+    impl Future for Delay {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+            if self.started {
+                return Poll::Ready(());
+            }
+
+            self.started = true;
+            let waker = context.waker().clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(1));
+                waker.wake();
+            });
+            Poll::Pending
+        }
+    }
+
+    /// A memory file system, answering asynchronously.
+    #[derive(Debug, Default)]
+    struct Storage {
+        fs: mem_fs::FileSystem,
+    }
+
+    impl Storage {
+        fn delay<'a, T: 'a>(&'a self, f: impl FnOnce() -> T + 'a) -> BoxFuture<'a, T> {
+            Box::pin(async move {
+                Delay::default().await;
+                f()
+            })
+        }
+    }
+
+    impl AsyncFileSystem for Storage {
+        fn read_dir<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Vec<DirEntry>>> {
+            self.delay(move || self.fs.read_dir(path)?.collect())
+        }
+
+        fn create_dir<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+            self.delay(move || self.fs.create_dir(path))
+        }
+
+        fn remove_dir<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+            self.delay(move || self.fs.remove_dir(path))
+        }
+
+        fn rename<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>> {
+            self.delay(move || self.fs.rename(from, to))
+        }
+
+        fn metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Metadata>> {
+            // `mem_fs` reports the missing files as not being files.
+            self.delay(move || match self.fs.metadata(path) {
+                Err(FsError::NotAFile) => Err(FsError::EntityNotFound),
+                result => result,
+            })
+        }
+
+        fn remove_file<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+            self.delay(move || self.fs.remove_file(path))
+        }
+
+        fn create_file<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+            self.delay(move || {
+                self.fs
+                    .new_open_options()
+                    .write(true)
+                    .create_new(true)
+                    .open(path)
+                    .map(|_| ())
+            })
+        }
+
+        fn read<'a>(
+            &'a self,
+            path: &'a Path,
+            offset: u64,
+            len: usize,
+        ) -> BoxFuture<'a, Result<Vec<u8>>> {
+            self.delay(move || {
+                let mut file = self.fs.new_open_options().read(true).open(path)?;
+                let mut data = Vec::new();
+                file.seek(SeekFrom::Start(offset))?;
+                file.take(len as u64).read_to_end(&mut data)?;
+                Ok(data)
+            })
+        }
+
+        fn write<'a>(
+            &'a self,
+            path: &'a Path,
+            offset: u64,
+            data: &'a [u8],
+        ) -> BoxFuture<'a, Result<()>> {
+            // `mem_fs` inserts the data written in the middle of a file,
+            // rather than overwriting it.
+            self.delay(move || {
+                let mut contents = Vec::new();
+                let mut file = self
+                    .fs
+                    .new_open_options()
+                    .read(true)
+                    .write(true)
+                    .open(path)?;
+                file.read_to_end(&mut contents)?;
+                let (start, end) = (offset as usize, offset as usize + data.len());
+                contents.resize(contents.len().max(end), 0);
+                contents[start..end].copy_from_slice(data);
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+                file.write_all(&contents)?;
+                Ok(())
+            })
+        }
+
+        fn set_len<'a>(&'a self, path: &'a Path, len: u64) -> BoxFuture<'a, Result<()>> {
+            self.delay(move || {
+                self.fs
+                    .new_open_options()
+                    .write(true)
+                    .open(path)?
+                    .set_len(len)
+            })
+        }
+
+        fn symlink_metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Metadata>> {
+            self.delay(move || self.fs.symlink_metadata(path))
+        }
+
+        fn symlink<'a>(&'a self, original: &'a Path, link: &'a Path) -> BoxFuture<'a, Result<()>> {
+            self.delay(move || self.fs.symlink(original, link))
+        }
+
+        fn read_link<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<PathBuf>> {
+            self.delay(move || self.fs.read_link(path))
+        }
+
+        fn chmod<'a>(&'a self, path: &'a Path, mode: u32) -> BoxFuture<'a, Result<()>> {
+            self.delay(move || self.fs.chmod(path, mode))
+        }
+    }
+
+    #[test]
+    fn test_files() {
+        let fs = FileSystem::new(Storage::default());
+        fs.create_dir(path!("/etc")).unwrap();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path!("/etc/motd"))
+            .unwrap();
+        file.write_all(b"hello").unwrap();
+        assert_eq!(file.size(), 5);
+        assert_eq!(file.seek(SeekFrom::Current(-3)).unwrap(), 2);
+        file.write_all(b"y!").unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "hey!o");
+
+        let mut file = fs
+            .new_open_options()
+            .append(true)
+            .open(path!("/etc/motd"))
+            .unwrap();
+        file.write_all(b", world").unwrap();
+        assert_eq!(fs.metadata(path!("/etc/motd")).unwrap().len(), 12);
+
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .truncate(true)
+                .open(path!("/etc/motd"))
+                .map(|file| file.size()),
+            Ok(0)
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .create_new(true)
+                .open(path!("/etc/motd"))
+                .map(|_| ()),
+            Err(FsError::AlreadyExists)
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .read(true)
+                .open(path!("/etc/issue"))
+                .map(|_| ()),
+            Err(FsError::EntityNotFound)
+        );
+    }
+
+    #[test]
+    fn test_directories() {
+        let fs = FileSystem::new(Storage::default());
+        fs.create_dir(path!("/etc")).unwrap();
+        for name in ["/etc/hosts", "/etc/motd"] {
+            fs.new_open_options()
+                .write(true)
+                .create_new(true)
+                .open(name)
+                .unwrap();
+        }
+
+        assert_eq!(
+            fs.read_dir(path!("/etc"))
+                .unwrap()
+                .map(|entry| entry.unwrap().path)
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("/etc/hosts"), PathBuf::from("/etc/motd")]
+        );
+        assert!(fs.metadata(path!("/etc")).unwrap().is_dir());
+
+        assert_eq!(fs.rename(path!("/etc/motd"), path!("/etc/issue")), Ok(()));
+        assert_eq!(fs.remove_file(path!("/etc/hosts")), Ok(()));
+        assert_eq!(fs.remove_file(path!("/etc/issue")), Ok(()));
+        assert_eq!(fs.remove_dir(path!("/etc")), Ok(()));
+        assert!(fs.inner().fs.metadata(path!("/etc")).is_err());
+    }
+
+    #[test]
+    fn test_links_and_metadata() {
+        let fs = FileSystem::new(Storage::default());
+        fs.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/motd"))
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+
+        assert_eq!(fs.symlink(path!("/motd"), path!("/link")), Ok(()));
+        assert_eq!(fs.read_link(path!("/link")), Ok(PathBuf::from("/motd")));
+        assert!(fs.symlink_metadata(path!("/link")).unwrap().ft.is_symlink());
+        assert_eq!(fs.chmod(path!("/motd"), 0o600), Ok(()));
+        assert_eq!(fs.metadata(path!("/motd")).unwrap().mode, 0o600);
+        assert_eq!(
+            fs.link(path!("/motd"), path!("/hard")),
+            Err(FsError::Unsupported),
+            "the storage doesn't support hard links"
+        );
+
+        assert_eq!(fs.clone_file(path!("/motd"), path!("/copy")), Ok(()));
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/copy"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello");
+    }
+}
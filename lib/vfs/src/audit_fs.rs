@@ -0,0 +1,635 @@
+//! A file system wrapping another one, recording every operation.
+//!
+//! Each operation, including the reads and writes of the opened
+//! files, is handed to a function once done, as a [`Record`] of the
+//! paths, the number of bytes, the result and the time, so that the
+//! embedder can keep a complete log of what a guest touched. A
+//! `Record` is displayed as a JSON object, to write the log one
+//! record per line.
+
+use crate::{
+    time, FileDescriptor, FileMapping, FileOpener, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, Result, VirtualFile, WatchHandle,
+};
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The function called with each [`Record`].
+pub type AuditFn = Arc<dyn Fn(&Record) + Send + Sync>;
+
+/// An operation of the file system, or of one of its files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    ReadDir,
+    CreateDir,
+    RemoveDir,
+    Rename,
+    Metadata,
+    SymlinkMetadata,
+    Symlink,
+    ReadLink,
+    Link,
+    Chmod,
+    Chown,
+    SetTimes,
+    RemoveFile,
+    CloneFile,
+    Watch,
+    /// Opening a file, for reading only.
+    Open,
+    /// Opening a file for writing, appending, truncating or creating
+    /// it.
+    OpenWrite,
+    Read,
+    Write,
+    SetLen,
+    Unlink,
+}
+
+impl Operation {
+    /// The name of the operation, in snake case.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ReadDir => "read_dir",
+            Self::CreateDir => "create_dir",
+            Self::RemoveDir => "remove_dir",
+            Self::Rename => "rename",
+            Self::Metadata => "metadata",
+            Self::SymlinkMetadata => "symlink_metadata",
+            Self::Symlink => "symlink",
+            Self::ReadLink => "read_link",
+            Self::Link => "link",
+            Self::Chmod => "chmod",
+            Self::Chown => "chown",
+            Self::SetTimes => "set_times",
+            Self::RemoveFile => "remove_file",
+            Self::CloneFile => "clone_file",
+            Self::Watch => "watch",
+            Self::Open => "open",
+            Self::OpenWrite => "open_write",
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::SetLen => "set_len",
+            Self::Unlink => "unlink",
+        }
+    }
+}
+
+/// An operation done through the file system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// When the operation ended, in nanoseconds since `UNIX_EPOCH`.
+    pub time: u64,
+    pub operation: Operation,
+    /// The path given to the file system, or the one the file was
+    /// opened at.
+    pub path: PathBuf,
+    /// The second path of the operations taking two, like the
+    /// destination of a rename or the link of a symlink.
+    pub target: Option<PathBuf>,
+    /// The number of bytes read or written, or the new size of a
+    /// file.
+    pub bytes: Option<u64>,
+    pub result: Result<()>,
+}
+
+impl fmt::Display for Record {
+    /// Displays the record as a JSON object, like
+    /// `{"time":1,"operation":"write","path":"/tmp/a","target":null,"bytes":5,"error":null}`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"time\":{},\"operation\":\"{}\",\"path\":",
+            self.time,
+            self.operation.name()
+        )?;
+        write_json_string(f, &self.path.to_string_lossy())?;
+        f.write_str(",\"target\":")?;
+        match &self.target {
+            Some(target) => write_json_string(f, &target.to_string_lossy())?,
+            None => f.write_str("null")?,
+        }
+        f.write_str(",\"bytes\":")?;
+        match self.bytes {
+            Some(bytes) => write!(f, "{}", bytes)?,
+            None => f.write_str("null")?,
+        }
+        f.write_str(",\"error\":")?;
+        match &self.result {
+            Ok(()) => f.write_str("null")?,
+            Err(err) => write_json_string(f, &err.to_string())?,
+        }
+        f.write_str("}")
+    }
+}
+
+/// Writes `string` as a JSON string, quoted and escaped.
+fn write_json_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// The file system. It is cheap to clone, clones share the same
+/// wrapped file system and function.
+#[derive(Clone)]
+pub struct FileSystem {
+    inner: Arc<dyn crate::FileSystem>,
+    audit: AuditFn,
+}
+
+impl FileSystem {
+    /// Wraps `inner`, calling `audit` after each operation.
+    pub fn new(inner: Arc<dyn crate::FileSystem>, audit: AuditFn) -> Self {
+        Self { inner, audit }
+    }
+
+    /// The wrapped file system.
+    pub fn inner(&self) -> &dyn crate::FileSystem {
+        &*self.inner
+    }
+
+    /// Records the `operation` on `path` and `target`, which ended
+    /// with `result`, and returns `result`.
+    fn record<T>(
+        &self,
+        operation: Operation,
+        path: &Path,
+        target: Option<&Path>,
+        result: Result<T>,
+    ) -> Result<T> {
+        (self.audit)(&Record {
+            time: time(),
+            operation,
+            path: path.to_path_buf(),
+            target: target.map(Path::to_path_buf),
+            bytes: None,
+            result: result.as_ref().map(|_| ()).map_err(|err| *err),
+        });
+
+        result
+    }
+}
+
+impl fmt::Debug for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileSystem")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl crate::FileSystem for FileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.record(Operation::ReadDir, path, None, self.inner.read_dir(path))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.record(
+            Operation::CreateDir,
+            path,
+            None,
+            self.inner.create_dir(path),
+        )
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.record(
+            Operation::RemoveDir,
+            path,
+            None,
+            self.inner.remove_dir(path),
+        )
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(
+            Operation::Rename,
+            from,
+            Some(to),
+            self.inner.rename(from, to),
+        )
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.record(Operation::Metadata, path, None, self.inner.metadata(path))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.record(
+            Operation::SymlinkMetadata,
+            path,
+            None,
+            self.inner.symlink_metadata(path),
+        )
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        self.record(
+            Operation::Symlink,
+            original,
+            Some(link),
+            self.inner.symlink(original, link),
+        )
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        self.record(Operation::ReadLink, path, None, self.inner.read_link(path))
+    }
+
+    fn link(&self, original: &Path, link: &Path) -> Result<()> {
+        self.record(
+            Operation::Link,
+            original,
+            Some(link),
+            self.inner.link(original, link),
+        )
+    }
+
+    fn chmod(&self, path: &Path, mode: u32) -> Result<()> {
+        self.record(Operation::Chmod, path, None, self.inner.chmod(path, mode))
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.record(
+            Operation::Chown,
+            path,
+            None,
+            self.inner.chown(path, uid, gid),
+        )
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<u64>, modified: Option<u64>) -> Result<()> {
+        self.record(
+            Operation::SetTimes,
+            path,
+            None,
+            self.inner.set_times(path, accessed, modified),
+        )
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.record(
+            Operation::RemoveFile,
+            path,
+            None,
+            self.inner.remove_file(path),
+        )
+    }
+
+    fn clone_file(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(
+            Operation::CloneFile,
+            from,
+            Some(to),
+            self.inner.clone_file(from, to),
+        )
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<WatchHandle> {
+        self.record(
+            Operation::Watch,
+            path,
+            None,
+            self.inner.watch(path, recursive),
+        )
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl FileOpener for FileSystem {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let operation = if conf.write()
+            || conf.append()
+            || conf.truncate()
+            || conf.create()
+            || conf.create_new()
+        {
+            Operation::OpenWrite
+        } else {
+            Operation::Open
+        };
+        let inner = self.record(
+            operation,
+            path,
+            None,
+            self.inner
+                .new_open_options()
+                .options(conf.clone())
+                .open(path),
+        )?;
+
+        Ok(Box::new(File {
+            inner,
+            path: path.to_path_buf(),
+            audit: self.audit.clone(),
+        }))
+    }
+}
+
+/// A file of the file system, recording its reads and writes.
+struct File {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+    path: PathBuf,
+    audit: AuditFn,
+}
+
+impl File {
+    /// Records the `operation` on the file, which ended with `result`
+    /// after reading or writing `bytes`.
+    fn record(&self, operation: Operation, bytes: Option<u64>, result: Result<()>) {
+        (self.audit)(&Record {
+            time: time(),
+            operation,
+            path: self.path.clone(),
+            target: None,
+            bytes,
+            result,
+        });
+    }
+
+    /// Records the read or write `operation` of the file, which ended
+    /// with `result`, and returns `result`.
+    fn record_io(&self, operation: Operation, result: io::Result<usize>) -> io::Result<usize> {
+        match &result {
+            Ok(bytes) => self.record(operation, Some(*bytes as u64), Ok(())),
+            Err(err) => self.record(operation, None, Err(io::Error::from(err.kind()).into())),
+        }
+
+        result
+    }
+}
+
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("File")
+            .field("inner", &self.inner)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl VirtualFile for File {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<()> {
+        let result = self.inner.set_len(new_size);
+        self.record(Operation::SetLen, Some(new_size), result);
+
+        result
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        let result = self.inner.unlink();
+        self.record(Operation::Unlink, None, result);
+
+        result
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.inner.read_at(offset, buf);
+        self.record_io(Operation::Read, result)
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write_at(offset, buf);
+        self.record_io(Operation::Write, result)
+    }
+
+    fn map(&self) -> Option<FileMapping> {
+        self.inner.map()
+    }
+
+    fn sync_to_disk(&self) -> Result<()> {
+        self.inner.sync_to_disk()
+    }
+
+    fn bytes_available(&self) -> Result<usize> {
+        self.inner.bytes_available()
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>> {
+        self.inner.bytes_available_read()
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>> {
+        self.inner.bytes_available_write()
+    }
+
+    fn ioctl(&mut self, request: u32, data: &[u8]) -> Result<Vec<u8>> {
+        self.inner.ioctl(request, data)
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn is_readable(&self) -> bool {
+        self.inner.is_readable()
+    }
+
+    fn is_writable(&self) -> bool {
+        self.inner.is_writable()
+    }
+
+    fn is_seekable(&self) -> bool {
+        self.inner.is_seekable()
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.inner.read(buf);
+        self.record_io(Operation::Read, result)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        let result = self.inner.read_vectored(bufs);
+        self.record_io(Operation::Read, result)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.record_io(Operation::Write, result)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let result = self.inner.write_vectored(bufs);
+        self.record_io(Operation::Write, result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_filesystem {
+    use super::{FileSystem, Operation, Record};
+    use crate::{mem_fs, FileSystem as FS, FsError};
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    /// An audited memory file system, and its records.
+    fn audited() -> (FileSystem, Arc<Mutex<Vec<Record>>>) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let fs = FileSystem::new(Arc::new(mem_fs::FileSystem::default()), {
+            let records = records.clone();
+            Arc::new(move |record: &Record| records.lock().unwrap().push(record.clone()))
+        });
+
+        (fs, records)
+    }
+
+    #[test]
+    fn test_operations_are_recorded() {
+        let (fs, records) = audited();
+
+        fs.create_dir(Path::new("/tmp")).unwrap();
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(Path::new("/tmp/a"))
+            .unwrap();
+        file.write_all(b"hello").unwrap();
+        drop(file);
+        let mut contents = [0; 8];
+        assert_eq!(
+            fs.new_open_options()
+                .read(true)
+                .open(Path::new("/tmp/a"))
+                .unwrap()
+                .read(&mut contents)
+                .unwrap(),
+            5
+        );
+        fs.rename(Path::new("/tmp/a"), Path::new("/tmp/b")).unwrap();
+        assert_eq!(
+            fs.remove_dir(Path::new("/tmp")),
+            Err(FsError::DirectoryNotEmpty)
+        );
+
+        let records = records.lock().unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (
+                    record.operation,
+                    record.path.clone(),
+                    record.target.clone(),
+                    record.bytes,
+                    record.result,
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Operation::CreateDir,
+                    PathBuf::from("/tmp"),
+                    None,
+                    None,
+                    Ok(())
+                ),
+                (
+                    Operation::OpenWrite,
+                    PathBuf::from("/tmp/a"),
+                    None,
+                    None,
+                    Ok(())
+                ),
+                (
+                    Operation::Write,
+                    PathBuf::from("/tmp/a"),
+                    None,
+                    Some(5),
+                    Ok(())
+                ),
+                (Operation::Open, PathBuf::from("/tmp/a"), None, None, Ok(())),
+                (
+                    Operation::Read,
+                    PathBuf::from("/tmp/a"),
+                    None,
+                    Some(5),
+                    Ok(())
+                ),
+                (
+                    Operation::Rename,
+                    PathBuf::from("/tmp/a"),
+                    Some(PathBuf::from("/tmp/b")),
+                    None,
+                    Ok(())
+                ),
+                (
+                    Operation::RemoveDir,
+                    PathBuf::from("/tmp"),
+                    None,
+                    None,
+                    Err(FsError::DirectoryNotEmpty)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_records_are_displayed_as_json() {
+        let record = Record {
+            time: 42,
+            operation: Operation::Rename,
+            path: PathBuf::from("/a \"b\""),
+            target: Some(PathBuf::from("/c\n")),
+            bytes: None,
+            result: Err(FsError::AlreadyExists),
+        };
+
+        assert_eq!(
+            record.to_string(),
+            r#"{"time":42,"operation":"rename","path":"/a \"b\"","target":"/c\n","bytes":null,"error":"file exists"}"#
+        );
+    }
+}
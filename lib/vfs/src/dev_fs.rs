@@ -0,0 +1,406 @@
+//! A file system holding the usual character devices, intended to be
+//! mounted at `/dev`:
+//!
+//! - `null` reads as empty and discards what's written,
+//! - `zero` reads as zeros and discards what's written,
+//! - `random` and `urandom` read as random bytes from the host, and
+//!   discard what's written,
+//! - `tty` is the terminal given by the embedder, see
+//!   [`FileSystem::set_tty`]; opening it fails with
+//!   [`FsError::NoDevice`] otherwise, like without a controlling
+//!   terminal.
+
+use crate::{
+    time, DirEntry, FileOpener, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    PathResolver, ReadDir, Result, VirtualFile,
+};
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Opens the terminal, see [`FileSystem::set_tty`].
+pub type TtyOpener =
+    Arc<dyn Fn(&OpenOptionsConfig) -> Result<Box<dyn VirtualFile + Send + Sync>> + Send + Sync>;
+
+/// A device of the file system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    /// `null`.
+    Null,
+    /// `zero`.
+    Zero,
+    /// `random`.
+    Random,
+    /// `urandom`.
+    Urandom,
+    /// `tty`.
+    Tty,
+}
+
+impl Device {
+    /// The devices, in the order they are listed.
+    pub const ALL: [Device; 5] = [
+        Device::Null,
+        Device::Random,
+        Device::Tty,
+        Device::Urandom,
+        Device::Zero,
+    ];
+
+    /// The name of the device file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Zero => "zero",
+            Self::Random => "random",
+            Self::Urandom => "urandom",
+            Self::Tty => "tty",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|device| device.name() == name)
+    }
+}
+
+fn metadata(ft: FileType, mode: u32, created: u64) -> Metadata {
+    Metadata {
+        ft,
+        accessed: created,
+        created,
+        modified: created,
+        len: 0,
+        nlink: 1,
+        mode,
+        ..Default::default()
+    }
+}
+
+/// The file system. It is cheap to clone, clones share the same
+/// terminal.
+#[derive(Clone)]
+pub struct FileSystem {
+    tty: Arc<RwLock<Option<TtyOpener>>>,
+    created: u64,
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        Self {
+            tty: Default::default(),
+            created: time(),
+        }
+    }
+}
+
+impl fmt::Debug for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tty = self.tty.try_read().map(|tty| tty.is_some());
+        f.debug_struct("dev_fs::FileSystem")
+            .field("tty", &tty.ok())
+            .finish()
+    }
+}
+
+impl FileSystem {
+    /// Makes `opener` open `tty`, e.g. returning a file reading the
+    /// keyboard and writing to the screen of a terminal emulator.
+    pub fn set_tty<F>(&self, opener: F) -> Result<()>
+    where
+        F: Fn(&OpenOptionsConfig) -> Result<Box<dyn VirtualFile + Send + Sync>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        *self.tty.write().map_err(|_| FsError::Lock)? = Some(Arc::new(opener));
+
+        Ok(())
+    }
+
+    /// Gets the device at `path`, or `None` for the root directory.
+    fn device(&self, path: &Path) -> Result<Option<Device>> {
+        let names = PathResolver::new().names(path)?;
+        match names.as_slice() {
+            [] => Ok(None),
+            [name] => name
+                .to_str()
+                .and_then(Device::from_name)
+                .map(Some)
+                .ok_or(FsError::EntityNotFound),
+            [name, ..] if name.to_str().and_then(Device::from_name).is_some() => {
+                Err(FsError::BaseNotDirectory)
+            }
+            _ => Err(FsError::EntityNotFound),
+        }
+    }
+
+    fn metadata_of(&self, device: Option<Device>) -> Metadata {
+        match device {
+            None => metadata(
+                FileType {
+                    dir: true,
+                    ..Default::default()
+                },
+                0o755,
+                self.created,
+            ),
+            Some(_) => metadata(
+                FileType {
+                    char_device: true,
+                    ..Default::default()
+                },
+                0o666,
+                self.created,
+            ),
+        }
+    }
+}
+
+impl crate::FileSystem for FileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        match self.device(path)? {
+            None => Ok(ReadDir::new(
+                Device::ALL
+                    .iter()
+                    .map(|device| DirEntry {
+                        path: path.join(device.name()),
+                        metadata: Ok(self.metadata_of(Some(*device))),
+                    })
+                    .collect(),
+            )),
+            Some(_) => Err(FsError::BaseNotDirectory),
+        }
+    }
+
+    fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        Ok(self.metadata_of(self.device(path)?))
+    }
+
+    fn remove_file(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl FileOpener for FileSystem {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let device = match self.device(path) {
+            Ok(Some(device)) => device,
+            Ok(None) => return Err(FsError::NotAFile),
+            Err(FsError::EntityNotFound) if conf.create() || conf.create_new() => {
+                return Err(FsError::PermissionDenied)
+            }
+            Err(err) => return Err(err),
+        };
+        if conf.create_new() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        if device == Device::Tty {
+            let tty = self.tty.read().map_err(|_| FsError::Lock)?;
+            return match tty.as_ref() {
+                Some(opener) => opener(conf),
+                None => Err(FsError::NoDevice),
+            };
+        }
+
+        Ok(Box::new(DeviceFile {
+            device,
+            created: self.created,
+        }))
+    }
+}
+
+/// An opened `null`, `zero`, `random` or `urandom` device.
+#[derive(Debug)]
+pub struct DeviceFile {
+    device: Device,
+    created: u64,
+}
+
+impl DeviceFile {
+    /// The device opened.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+}
+
+impl VirtualFile for DeviceFile {
+    fn last_accessed(&self) -> u64 {
+        self.created
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.created
+    }
+
+    fn created_time(&self) -> u64 {
+        self.created
+    }
+
+    fn size(&self) -> u64 {
+        0
+    }
+
+    /// Truncating a device is ignored.
+    fn set_len(&mut self, _new_size: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>> {
+        match self.device {
+            Device::Null => Ok(Some(0)),
+            _ => Ok(None),
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        false
+    }
+}
+
+impl Read for DeviceFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.device {
+            Device::Null => Ok(0),
+            Device::Zero => {
+                buf.fill(0);
+                Ok(buf.len())
+            }
+            Device::Random | Device::Urandom => {
+                getrandom::getrandom(buf)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+                Ok(buf.len())
+            }
+            Device::Tty => unreachable!("the tty is opened by the embedder"),
+        }
+    }
+}
+
+impl Write for DeviceFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Seeking a device does nothing, and returns 0, like on Linux.
+impl Seek for DeviceFile {
+    fn seek(&mut self, _position: SeekFrom) -> io::Result<u64> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test_filesystem {
+    use super::{Device, FileSystem};
+    use crate::{FileSystem as FS, FsError, Metadata, VirtualFile};
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+
+    fn open(fs: &FileSystem, path: &str) -> Result<Box<dyn VirtualFile + Send + Sync>, FsError> {
+        fs.new_open_options()
+            .read(true)
+            .write(true)
+            .open(Path::new(path))
+    }
+
+    #[test]
+    fn test_devices() {
+        let fs = FileSystem::default();
+        let mut buffer = [1; 64];
+
+        let mut null = open(&fs, "/null").unwrap();
+        assert_eq!(null.read(&mut buffer).unwrap(), 0);
+        assert_eq!(null.write(b"discarded").unwrap(), 9);
+
+        let mut zero = open(&fs, "/zero").unwrap();
+        assert_eq!(zero.read(&mut buffer).unwrap(), 64);
+        assert_eq!(buffer, [0; 64]);
+
+        let mut urandom = open(&fs, "/urandom").unwrap();
+        urandom.read_exact(&mut buffer).unwrap();
+        let mut other = [0; 64];
+        open(&fs, "/random")
+            .unwrap()
+            .read_exact(&mut other)
+            .unwrap();
+        assert_ne!(buffer, other, "random bytes");
+
+        assert!(fs.metadata(Path::new("/null")).unwrap().ft.char_device);
+        assert!(fs.metadata(Path::new("/")).unwrap().is_dir());
+        assert_eq!(
+            fs.read_dir(Path::new("/"))
+                .unwrap()
+                .map(|entry| entry.unwrap().path)
+                .collect::<Vec<_>>(),
+            Device::ALL
+                .iter()
+                .map(|device| PathBuf::from("/").join(device.name()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_missing_devices() {
+        let fs = FileSystem::default();
+
+        assert_eq!(
+            fs.metadata(Path::new("/sda")).map(|m: Metadata| m.len),
+            Err(FsError::EntityNotFound)
+        );
+        assert!(matches!(open(&fs, "/tty"), Err(FsError::NoDevice)));
+        assert!(matches!(
+            fs.new_open_options()
+                .write(true)
+                .create(true)
+                .open(Path::new("/file")),
+            Err(FsError::PermissionDenied)
+        ));
+    }
+
+    #[test]
+    fn test_tty() {
+        let fs = FileSystem::default();
+        fs.set_tty(|_conf| {
+            FileSystem::default()
+                .new_open_options()
+                .write(true)
+                .open(Path::new("/null"))
+        })
+        .unwrap();
+
+        let mut tty = open(&fs, "/tty").unwrap();
+        assert_eq!(tty.write(b"prompt> ").unwrap(), 8);
+    }
+}
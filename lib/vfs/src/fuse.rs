@@ -0,0 +1,746 @@
+//! Mounts a [`FileSystem`] on the host with FUSE, so that its files,
+//! e.g. the ones of a guest, can be inspected with the usual tools.
+//!
+//! The inode numbers are given out as the paths are looked up, and
+//! follow the entries when they are renamed. The files opened through
+//! the mount are kept open until the kernel releases them.
+//!
+//! This module is behind the `fuse` feature, which links against the
+//! system libfuse (or macFUSE on macOS): its development files and
+//! `pkg-config` must be installed to build it.
+
+use crate::{FileSystem, FsError, Metadata, VirtualFile};
+use fuser::{
+    FileAttr, FileType, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow, FUSE_ROOT_ID,
+};
+use libc::c_int;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long the kernel may cache the entries and attributes. It's
+/// short since the file system may change behind the mount, e.g. when
+/// a guest is running.
+const TTL: Duration = Duration::from_secs(1);
+
+/// Mounts a [`FileSystem`] on the host.
+///
+/// ```no_run
+/// use std::path::Path;
+/// use std::sync::Arc;
+/// use wasmer_vfs::{fuse::FuseMount, mem_fs};
+///
+/// let fs = mem_fs::FileSystem::default();
+/// FuseMount::new(Arc::new(fs.clone()))
+///     .read_only(true)
+///     .mount(Path::new("/mnt/guest"))
+///     .unwrap();
+/// ```
+pub struct FuseMount {
+    fs: Arc<dyn FileSystem>,
+    read_only: bool,
+    name: String,
+}
+
+impl FuseMount {
+    /// Mounts `fs`, writable, named `wasmer-vfs` in the mount table.
+    pub fn new(fs: Arc<dyn FileSystem>) -> Self {
+        Self {
+            fs,
+            read_only: false,
+            name: "wasmer-vfs".to_string(),
+        }
+    }
+
+    /// Forbids changing the file system through the mount.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sets the name of the file system in the mount table.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    fn options(&self) -> Vec<MountOption> {
+        let mut options = vec![MountOption::FSName(self.name.clone())];
+        if self.read_only {
+            options.push(MountOption::RO);
+        }
+        options
+    }
+
+    /// Mounts the file system at `mountpoint`, returning once it is
+    /// unmounted, e.g. with `fusermount -u`.
+    pub fn mount(self, mountpoint: &Path) -> io::Result<()> {
+        let options = self.options();
+        fuser::mount2(Adapter::new(self), mountpoint, &options)
+    }
+
+    /// Mounts the file system at `mountpoint`, serving it from a
+    /// background thread until the returned session is dropped.
+    pub fn spawn(self, mountpoint: &Path) -> io::Result<fuser::BackgroundSession> {
+        let options = self.options();
+        fuser::spawn_mount2(Adapter::new(self), mountpoint, &options)
+    }
+}
+
+/// The inode numbers given out, and their paths.
+#[derive(Debug)]
+struct Inodes {
+    paths: HashMap<u64, PathBuf>,
+    inos: HashMap<PathBuf, u64>,
+    next_ino: u64,
+}
+
+impl Inodes {
+    fn new() -> Self {
+        let root = PathBuf::from("/");
+        Self {
+            paths: HashMap::from([(FUSE_ROOT_ID, root.clone())]),
+            inos: HashMap::from([(root, FUSE_ROOT_ID)]),
+            next_ino: FUSE_ROOT_ID + 1,
+        }
+    }
+
+    /// Returns the inode number of `path`, giving out a new one if
+    /// it has none yet.
+    fn ino(&mut self, path: &Path) -> u64 {
+        if let Some(ino) = self.inos.get(path) {
+            return *ino;
+        }
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        self.paths.insert(ino, path.to_path_buf());
+        self.inos.insert(path.to_path_buf(), ino);
+        ino
+    }
+
+    fn path(&self, ino: u64) -> Result<PathBuf, c_int> {
+        self.paths.get(&ino).cloned().ok_or(libc::ENOENT)
+    }
+
+    /// Moves the inode numbers of `from`, and of all the paths below
+    /// it, to `to`.
+    fn rename(&mut self, from: &Path, to: &Path) {
+        self.remove(to);
+        let moved = self
+            .inos
+            .iter()
+            .filter_map(|(path, ino)| {
+                let rest = path.strip_prefix(from).ok()?;
+                Some((path.clone(), to.join(rest), *ino))
+            })
+            .collect::<Vec<_>>();
+        for (old, new, ino) in moved {
+            self.inos.remove(&old);
+            self.inos.insert(new.clone(), ino);
+            self.paths.insert(ino, new);
+        }
+    }
+
+    /// Forgets the inode numbers of `path`, and of all the paths
+    /// below it.
+    fn remove(&mut self, path: &Path) {
+        let paths = &mut self.paths;
+        self.inos.retain(|other, ino| {
+            let removed = other.starts_with(path);
+            if removed {
+                paths.remove(ino);
+            }
+            !removed
+        });
+    }
+}
+
+/// Converts an error of the file system to an `errno`.
+fn errno(error: FsError) -> c_int {
+    match error {
+        FsError::BaseNotDirectory => libc::ENOTDIR,
+        FsError::NotAFile => libc::EISDIR,
+        FsError::InvalidFd => libc::EBADF,
+        FsError::AlreadyExists => libc::EEXIST,
+        FsError::AddressInUse => libc::EADDRINUSE,
+        FsError::AddressNotAvailable => libc::EADDRNOTAVAIL,
+        FsError::BrokenPipe => libc::EPIPE,
+        FsError::ConnectionAborted => libc::ECONNABORTED,
+        FsError::ConnectionRefused => libc::ECONNREFUSED,
+        FsError::ConnectionReset => libc::ECONNRESET,
+        FsError::Interrupted => libc::EINTR,
+        FsError::InvalidInput => libc::EINVAL,
+        FsError::NotConnected => libc::ENOTCONN,
+        FsError::EntityNotFound => libc::ENOENT,
+        FsError::NoDevice => libc::ENODEV,
+        FsError::PermissionDenied => libc::EACCES,
+        FsError::TimedOut => libc::ETIMEDOUT,
+        FsError::WouldBlock => libc::EAGAIN,
+        FsError::DirectoryNotEmpty => libc::ENOTEMPTY,
+        FsError::CrossDevice => libc::EXDEV,
+        FsError::Busy => libc::EBUSY,
+        FsError::SymlinkLoop => libc::ELOOP,
+        FsError::Unsupported => libc::EOPNOTSUPP,
+        FsError::QuotaExceeded => libc::EDQUOT,
+        FsError::UnsupportedControl => libc::ENOTTY,
+        FsError::Lock
+        | FsError::IOError
+        | FsError::InvalidData
+        | FsError::UnexpectedEof
+        | FsError::WriteZero
+        | FsError::UnknownError => libc::EIO,
+    }
+}
+
+fn io_errno(error: io::Error) -> c_int {
+    errno(error.into())
+}
+
+fn system_time(nanos: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(nanos)
+}
+
+fn nanos(time: TimeOrNow) -> u64 {
+    let time = match time {
+        TimeOrNow::SpecificTime(time) => time,
+        TimeOrNow::Now => SystemTime::now(),
+    };
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn file_type(metadata: &Metadata) -> FileType {
+    let ft = &metadata.ft;
+    if ft.dir {
+        FileType::Directory
+    } else if ft.symlink {
+        FileType::Symlink
+    } else if ft.char_device {
+        FileType::CharDevice
+    } else if ft.block_device {
+        FileType::BlockDevice
+    } else if ft.socket {
+        FileType::Socket
+    } else if ft.fifo {
+        FileType::NamedPipe
+    } else {
+        FileType::RegularFile
+    }
+}
+
+/// Serves the FUSE requests from the file system.
+struct Adapter {
+    fs: Arc<dyn FileSystem>,
+    read_only: bool,
+    inodes: Inodes,
+    handles: HashMap<u64, Box<dyn VirtualFile + Send + Sync>>,
+    next_fh: u64,
+}
+
+impl Adapter {
+    fn new(mount: FuseMount) -> Self {
+        Self {
+            fs: mount.fs,
+            read_only: mount.read_only,
+            inodes: Inodes::new(),
+            handles: HashMap::new(),
+            next_fh: 1,
+        }
+    }
+
+    fn child(&self, parent: u64, name: &OsStr) -> Result<PathBuf, c_int> {
+        Ok(self.inodes.path(parent)?.join(name))
+    }
+
+    fn writable(&self) -> Result<(), c_int> {
+        if self.read_only {
+            Err(libc::EROFS)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn attr(&mut self, path: &Path) -> Result<FileAttr, c_int> {
+        let metadata = self.fs.symlink_metadata(path).map_err(errno)?;
+        let kind = file_type(&metadata);
+        // Not every file system keeps permission bits.
+        let mut perm = match metadata.mode {
+            0 if matches!(kind, FileType::Directory) => 0o755,
+            0 => 0o644,
+            mode => mode & 0o7777,
+        };
+        if self.read_only {
+            perm &= !0o222;
+        }
+
+        Ok(FileAttr {
+            ino: self.inodes.ino(path),
+            size: metadata.len,
+            blocks: (metadata.len + 511) / 512,
+            atime: system_time(metadata.accessed),
+            mtime: system_time(metadata.modified),
+            ctime: system_time(metadata.modified),
+            crtime: system_time(metadata.created),
+            kind,
+            perm: perm as u16,
+            nlink: metadata.nlink.max(1) as u32,
+            uid: metadata.uid,
+            gid: metadata.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+
+    fn handle(&mut self, fh: u64) -> Result<&mut Box<dyn VirtualFile + Send + Sync>, c_int> {
+        self.handles.get_mut(&fh).ok_or(libc::EBADF)
+    }
+
+    fn open_handle(&mut self, path: &Path, flags: i32, create: bool) -> Result<u64, c_int> {
+        let access = flags & libc::O_ACCMODE;
+        let write = access != libc::O_RDONLY;
+        if write || create {
+            self.writable()?;
+        }
+        let handle = self
+            .fs
+            .new_open_options()
+            .read(access != libc::O_WRONLY)
+            .write(write)
+            .append(flags & libc::O_APPEND != 0)
+            .truncate(flags & libc::O_TRUNC != 0)
+            .create(create)
+            .create_new(create && flags & libc::O_EXCL != 0)
+            .open(path)
+            .map_err(errno)?;
+
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        self.handles.insert(fh, handle);
+        Ok(fh)
+    }
+
+    fn setattr(
+        &mut self,
+        ino: u64,
+        mode: Option<u32>,
+        (uid, gid): (Option<u32>, Option<u32>),
+        size: Option<u64>,
+        (accessed, modified): (Option<TimeOrNow>, Option<TimeOrNow>),
+        fh: Option<u64>,
+    ) -> Result<FileAttr, c_int> {
+        let path = self.inodes.path(ino)?;
+        if mode.is_some() || uid.is_some() || gid.is_some() || size.is_some() {
+            self.writable()?;
+        }
+        if let Some(mode) = mode {
+            self.fs.chmod(&path, mode & 0o7777).map_err(errno)?;
+        }
+        if uid.is_some() || gid.is_some() {
+            self.fs.chown(&path, uid, gid).map_err(errno)?;
+        }
+        if let Some(size) = size {
+            match fh.and_then(|fh| self.handles.get_mut(&fh)) {
+                Some(handle) => handle.set_len(size),
+                None => self
+                    .fs
+                    .new_open_options()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|mut file| file.set_len(size)),
+            }
+            .map_err(errno)?;
+        }
+        if accessed.is_some() || modified.is_some() {
+            self.writable()?;
+            self.fs
+                .set_times(&path, accessed.map(nanos), modified.map(nanos))
+                .map_err(errno)?;
+        }
+        self.attr(&path)
+    }
+
+    fn read(&mut self, fh: u64, offset: i64, size: u32) -> Result<Vec<u8>, c_int> {
+        let handle = self.handle(fh)?;
+        handle
+            .seek(SeekFrom::Start(offset as u64))
+            .map_err(io_errno)?;
+        let mut data = Vec::with_capacity(size as usize);
+        handle
+            .take(size as u64)
+            .read_to_end(&mut data)
+            .map_err(io_errno)?;
+        Ok(data)
+    }
+
+    fn write(&mut self, fh: u64, offset: i64, data: &[u8]) -> Result<u32, c_int> {
+        let handle = self.handle(fh)?;
+        handle
+            .seek(SeekFrom::Start(offset as u64))
+            .map_err(io_errno)?;
+        handle.write_all(data).map_err(io_errno)?;
+        Ok(data.len() as u32)
+    }
+
+    fn readdir(&mut self, ino: u64, offset: i64, reply: &mut ReplyDirectory) -> Result<(), c_int> {
+        let path = self.inodes.path(ino)?;
+        let parent = path.parent().unwrap_or(&path).to_path_buf();
+        let mut entries = vec![
+            (ino, FileType::Directory, OsStr::new(".").to_os_string()),
+            (
+                self.inodes.ino(&parent),
+                FileType::Directory,
+                OsStr::new("..").to_os_string(),
+            ),
+        ];
+        for entry in self.fs.read_dir(&path).map_err(errno)? {
+            let entry = entry.map_err(errno)?;
+            let kind = entry
+                .metadata
+                .as_ref()
+                .map(file_type)
+                .unwrap_or(FileType::RegularFile);
+            let name = entry.file_name();
+            entries.push((self.inodes.ino(&path.join(&name)), kind, name));
+        }
+
+        for (index, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            // The offset is the one of the next entry.
+            if reply.add(ino, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fuser::Filesystem for Adapter {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.child(parent, name).and_then(|path| self.attr(&path)) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.inodes.path(ino).and_then(|path| self.attr(&path)) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        match Adapter::setattr(self, ino, mode, (uid, gid), size, (atime, mtime), fh) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        match self
+            .inodes
+            .path(ino)
+            .and_then(|path| self.fs.read_link(&path).map_err(errno))
+        {
+            Ok(target) => reply.data(target.as_os_str().as_bytes()),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let result = self.writable().and_then(|()| {
+            let path = self.child(parent, name)?;
+            self.fs.create_dir(&path).map_err(errno)?;
+            // The permissions are kept if the file system supports them.
+            let _ = self.fs.chmod(&path, mode & !umask & 0o7777);
+            self.attr(&path)
+        });
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let result = self.writable().and_then(|()| {
+            let path = self.child(parent, name)?;
+            self.fs.remove_file(&path).map_err(errno)?;
+            self.inodes.remove(&path);
+            Ok(())
+        });
+        match result {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let result = self.writable().and_then(|()| {
+            let path = self.child(parent, name)?;
+            self.fs.remove_dir(&path).map_err(errno)?;
+            self.inodes.remove(&path);
+            Ok(())
+        });
+        match result {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn symlink(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        link: &Path,
+        reply: ReplyEntry,
+    ) {
+        let result = self.writable().and_then(|()| {
+            let path = self.child(parent, name)?;
+            self.fs.symlink(link, &path).map_err(errno)?;
+            self.attr(&path)
+        });
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        let result = self.writable().and_then(|()| {
+            let from = self.child(parent, name)?;
+            let to = self.child(newparent, newname)?;
+            self.fs.rename(&from, &to).map_err(errno)?;
+            self.inodes.rename(&from, &to);
+            Ok(())
+        });
+        match result {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn link(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        newparent: u64,
+        newname: &OsStr,
+        reply: ReplyEntry,
+    ) {
+        let result = self.writable().and_then(|()| {
+            let original = self.inodes.path(ino)?;
+            let link = self.child(newparent, newname)?;
+            self.fs.link(&original, &link).map_err(errno)?;
+            self.attr(&link)
+        });
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        match self
+            .inodes
+            .path(ino)
+            .and_then(|path| self.open_handle(&path, flags, false))
+        {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match Adapter::read(self, fh, offset, size) {
+            Ok(data) => reply.data(&data),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        match Adapter::write(self, fh, offset, data) {
+            Ok(written) => reply.written(written),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _lock_owner: u64,
+        reply: ReplyEmpty,
+    ) {
+        match self
+            .handle(fh)
+            .and_then(|handle| handle.flush().map_err(io_errno))
+        {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn fsync(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _datasync: bool,
+        reply: ReplyEmpty,
+    ) {
+        match self
+            .handle(fh)
+            .and_then(|handle| handle.flush().map_err(io_errno))
+        {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.handles.remove(&fh);
+        reply.ok();
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        match Adapter::readdir(self, ino, offset, &mut reply) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let result = self.child(parent, name).and_then(|path| {
+            let fh = self.open_handle(&path, flags, true)?;
+            // The permissions are kept if the file system supports them.
+            let _ = self.fs.chmod(&path, mode & !umask & 0o7777);
+            Ok((self.attr(&path)?, fh))
+        });
+        match result {
+            Ok((attr, fh)) => reply.created(&TTL, &attr, 0, fh, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_inodes {
+    use super::Inodes;
+    use fuser::FUSE_ROOT_ID;
+    use std::path::Path;
+
+    #[test]
+    fn test_rename_and_remove() {
+        let mut inodes = Inodes::new();
+        assert_eq!(inodes.ino(Path::new("/")), FUSE_ROOT_ID);
+
+        let dir = inodes.ino(Path::new("/dir"));
+        let file = inodes.ino(Path::new("/dir/file"));
+        let other = inodes.ino(Path::new("/directory"));
+        assert_eq!(inodes.ino(Path::new("/dir/file")), file, "kept");
+
+        inodes.rename(Path::new("/dir"), Path::new("/moved"));
+        assert_eq!(inodes.path(dir), Ok(Path::new("/moved").to_path_buf()));
+        assert_eq!(
+            inodes.path(file),
+            Ok(Path::new("/moved/file").to_path_buf())
+        );
+        assert_eq!(
+            inodes.path(other),
+            Ok(Path::new("/directory").to_path_buf()),
+            "not below the renamed directory"
+        );
+
+        inodes.remove(Path::new("/moved"));
+        assert_eq!(inodes.path(file), Err(libc::ENOENT));
+        assert_ne!(inodes.ino(Path::new("/moved/file")), file);
+    }
+}
@@ -2,6 +2,8 @@ use crate::{
     DirEntry, FileDescriptor, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir,
     Result, VirtualFile,
 };
+#[cfg(feature = "host-fs-watch")]
+use crate::{WatchEvent, WatchHandle};
 #[cfg(feature = "enable-serde")]
 use serde::{de, Deserialize, Serialize};
 use std::convert::TryInto;
@@ -104,6 +106,100 @@ impl crate::FileSystem for FileSystem {
         fs::remove_file(path).map_err(Into::into)
     }
 
+    fn clone_file(&self, from: &Path, to: &Path) -> Result<()> {
+        fs::copy(from, to).map(|_| ()).map_err(Into::into)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        fs::read_link(path).map_err(Into::into)
+    }
+
+    fn link(&self, original: &Path, link: &Path) -> Result<()> {
+        fs::hard_link(original, link).map_err(Into::into)
+    }
+
+    fn chmod(&self, path: &Path, mode: u32) -> Result<()> {
+        #[cfg(unix)]
+        let permissions = {
+            use std::os::unix::fs::PermissionsExt;
+            fs::Permissions::from_mode(mode & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let permissions = {
+            let mut permissions = fs::metadata(path)?.permissions();
+            permissions.set_readonly(mode & 0o222 == 0);
+            permissions
+        };
+
+        fs::set_permissions(path, permissions).map_err(Into::into)
+    }
+
+    #[cfg(unix)]
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| FsError::InvalidInput)?;
+        // `-1` leaves the ID unchanged.
+        let uid = uid.unwrap_or(u32::MAX) as libc::uid_t;
+        let gid = gid.unwrap_or(u32::MAX) as libc::gid_t;
+
+        if unsafe { libc::chown(path.as_ptr(), uid, gid) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error().into())
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_times(&self, path: &Path, accessed: Option<u64>, modified: Option<u64>) -> Result<()> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| FsError::InvalidInput)?;
+        let timespec = |time: Option<u64>| match time {
+            Some(time) => libc::timespec {
+                tv_sec: (time / 1_000_000_000) as libc::time_t,
+                tv_nsec: (time % 1_000_000_000) as _,
+            },
+            None => libc::timespec {
+                tv_sec: 0,
+                tv_nsec: libc::UTIME_OMIT,
+            },
+        };
+        let times = [timespec(accessed), timespec(modified)];
+
+        if unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), 0) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error().into())
+        }
+    }
+
+    #[cfg(feature = "host-fs-watch")]
+    fn watch(&self, path: &Path, recursive: bool) -> Result<WatchHandle> {
+        use notify::{RecursiveMode, Watcher};
+
+        let (sender, handle) = WatchHandle::new();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            if let Ok(event) = event {
+                for event in watch_events(event) {
+                    // The handle may have been dropped already.
+                    let _ = sender.send(event);
+                }
+            }
+        })
+        .map_err(notify_error)?;
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(path, mode).map_err(notify_error)?;
+
+        Ok(handle.keep_alive(watcher))
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         OpenOptions::new(Box::new(FileOpener))
     }
@@ -113,6 +209,63 @@ impl crate::FileSystem for FileSystem {
             .and_then(TryInto::try_into)
             .map_err(Into::into)
     }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        fs::symlink_metadata(path)
+            .and_then(TryInto::try_into)
+            .map_err(Into::into)
+    }
+}
+
+/// Translates an event of the host. Renames are reported as moves
+/// out of and into the watched paths, followed by a `Renamed` event
+/// when the host knows both ends.
+#[cfg(feature = "host-fs-watch")]
+fn watch_events(event: notify::Event) -> Vec<WatchEvent> {
+    use notify::event::{EventKind, ModifyKind, RenameMode};
+
+    let paths = event.paths.into_iter();
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            paths.map(WatchEvent::Created).collect()
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            paths.map(WatchEvent::Removed).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            match paths.collect::<Vec<_>>()[..] {
+                [ref from, ref to] => vec![WatchEvent::Renamed {
+                    from: from.clone(),
+                    to: to.clone(),
+                }],
+                _ => vec![],
+            }
+        }
+        // Only one end of the rename is known.
+        EventKind::Modify(ModifyKind::Name(_)) => paths
+            .map(|path| {
+                if path.exists() {
+                    WatchEvent::Created(path)
+                } else {
+                    WatchEvent::Removed(path)
+                }
+            })
+            .collect(),
+        EventKind::Modify(_) => paths.map(WatchEvent::Modified).collect(),
+        EventKind::Access(_) | EventKind::Any | EventKind::Other => vec![],
+    }
+}
+
+#[cfg(feature = "host-fs-watch")]
+fn notify_error(error: notify::Error) -> FsError {
+    match error.kind {
+        notify::ErrorKind::Io(error) => error.into(),
+        notify::ErrorKind::PathNotFound | notify::ErrorKind::WatchNotFound => {
+            FsError::EntityNotFound
+        }
+        notify::ErrorKind::InvalidConfig(_) => FsError::InvalidInput,
+        _ => FsError::UnknownError,
+    }
 }
 
 impl TryInto<Metadata> for fs::Metadata {
@@ -136,6 +289,23 @@ impl TryInto<Metadata> for fs::Metadata {
                 (false, false, false, false)
             }
         };
+        let (nlink, uid, gid, mode) = {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                (self.nlink(), self.uid(), self.gid(), self.mode() & 0o7777)
+            }
+            #[cfg(not(unix))]
+            {
+                let mode = match (self.is_dir(), self.permissions().readonly()) {
+                    (true, true) => 0o555,
+                    (true, false) => 0o755,
+                    (false, true) => 0o444,
+                    (false, false) => 0o644,
+                };
+                (1, 0, 0, mode)
+            }
+        };
 
         Ok(Metadata {
             ft: FileType {
@@ -169,6 +339,10 @@ impl TryInto<Metadata> for fs::Metadata {
                 })
                 .map_or(0, |time| time.as_nanos() as u64),
             len: self.len(),
+            nlink,
+            uid,
+            gid,
+            mode,
         })
     }
 }
@@ -209,7 +383,8 @@ pub struct File {
     #[cfg_attr(feature = "enable-serde", serde(skip_serializing))]
     pub inner: fs::File,
     pub host_path: PathBuf,
-    #[cfg(feature = "enable-serde")]
+    /// The mode the file is opened in, see `File::READ`, `File::WRITE`
+    /// and `File::APPEND`.
     flags: u16,
 }
 
@@ -308,25 +483,24 @@ impl File {
 
     /// creates a new host file from a `std::fs::File` and a path
     pub fn new(file: fs::File, host_path: PathBuf, read: bool, write: bool, append: bool) -> Self {
-        let mut _flags = 0;
+        let mut flags = 0;
 
         if read {
-            _flags |= Self::READ;
+            flags |= Self::READ;
         }
 
         if write {
-            _flags |= Self::WRITE;
+            flags |= Self::WRITE;
         }
 
         if append {
-            _flags |= Self::APPEND;
+            flags |= Self::APPEND;
         }
 
         Self {
             inner: file,
             host_path,
-            #[cfg(feature = "enable-serde")]
-            flags: _flags,
+            flags,
         }
     }
 
@@ -340,6 +514,10 @@ impl Read for File {
         self.inner.read(buf)
     }
 
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        self.inner.read_vectored(bufs)
+    }
+
     fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
         self.inner.read_to_end(buf)
     }
@@ -364,6 +542,10 @@ impl Write for File {
         self.inner.write(buf)
     }
 
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.inner.write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.inner.flush()
     }
@@ -417,6 +599,17 @@ impl VirtualFile for File {
     fn unlink(&mut self) -> Result<()> {
         fs::remove_file(&self.host_path).map_err(Into::into)
     }
+
+    #[cfg(unix)]
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(&self.inner, buf, offset)
+    }
+
+    #[cfg(unix)]
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::write_at(&self.inner, buf, offset)
+    }
+
     fn sync_to_disk(&self) -> Result<()> {
         self.inner.sync_all().map_err(Into::into)
     }
@@ -424,6 +617,14 @@ impl VirtualFile for File {
     fn bytes_available(&self) -> Result<usize> {
         host_file_bytes_available(self.inner.try_into_filedescriptor()?)
     }
+
+    fn is_readable(&self) -> bool {
+        self.flags & Self::READ != 0
+    }
+
+    fn is_writable(&self) -> bool {
+        self.flags & (Self::WRITE | Self::APPEND) != 0
+    }
 }
 
 #[cfg(unix)]
@@ -540,6 +741,14 @@ impl VirtualFile for Stdout {
     fn get_fd(&self) -> Option<FileDescriptor> {
         io::stdout().try_into_filedescriptor().ok()
     }
+
+    fn is_readable(&self) -> bool {
+        false
+    }
+
+    fn is_seekable(&self) -> bool {
+        false
+    }
 }
 
 /// A wrapper type around Stderr that implements `VirtualFile` and
@@ -636,6 +845,14 @@ impl VirtualFile for Stderr {
     fn get_fd(&self) -> Option<FileDescriptor> {
         io::stderr().try_into_filedescriptor().ok()
     }
+
+    fn is_readable(&self) -> bool {
+        false
+    }
+
+    fn is_seekable(&self) -> bool {
+        false
+    }
 }
 
 /// A wrapper type around Stdin that implements `VirtualFile` and
@@ -731,4 +948,38 @@ impl VirtualFile for Stdin {
     fn get_fd(&self) -> Option<FileDescriptor> {
         io::stdin().try_into_filedescriptor().ok()
     }
+
+    fn is_writable(&self) -> bool {
+        false
+    }
+
+    fn is_seekable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test_file {
+    use crate::{host_fs::FileSystem, FileSystem as FS};
+
+    #[test]
+    fn test_open_mode() {
+        let path =
+            std::env::temp_dir().join(format!("wasmer-vfs-open-mode-{}", std::process::id()));
+        let fs = FileSystem::default();
+        let open = |read, write, append| {
+            fs.new_open_options()
+                .read(read)
+                .write(write)
+                .append(append)
+                .create(write || append)
+                .open(&path)
+                .map(|file| (file.is_readable(), file.is_writable()))
+        };
+
+        assert_eq!(open(false, true, false), Ok((false, true)));
+        assert_eq!(open(true, false, false), Ok((true, false)));
+        assert_eq!(open(true, false, true), Ok((true, true)));
+        std::fs::remove_file(&path).unwrap();
+    }
 }
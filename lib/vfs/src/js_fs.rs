@@ -0,0 +1,832 @@
+//! A file system delegating to a storage of the embedder, intended
+//! for the browser, where it is given as JavaScript callbacks, see
+//! `JsBackend`.
+//!
+//! The storage, a [`Backend`], only lists directories and reads and
+//! writes whole files: the paths are normalized, and the opened files
+//! are kept in memory, by the file system. A file is read from the
+//! backend when it's opened, and written back when it's flushed or
+//! dropped after being modified. The contents of the files and the
+//! directory listings are cached, see [`CacheOptions`], so that a
+//! slow storage like HTTP is only asked once.
+
+use crate::{
+    time, DirEntry, FileOpener, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    PathResolver, ReadDir, Result, VirtualFile,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[cfg(target_arch = "wasm32")]
+pub use self::js::JsBackend;
+
+/// An entry of a directory, as listed by a [`Backend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+    /// The size of a file, if the backend knows it without reading
+    /// the file.
+    pub len: Option<u64>,
+}
+
+/// The storage behind the file system. The paths are absolute and
+/// normalized.
+pub trait Backend: fmt::Debug + Send + Sync + 'static {
+    /// Lists the entries of the directory `path`.
+    fn read_dir(&self, path: &Path) -> Result<Vec<Entry>>;
+    /// Reads the contents of the file `path`.
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+    /// Replaces the contents of the file `path`, creating it if it
+    /// doesn't exist.
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()>;
+    /// Creates the directory `path`.
+    fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+    /// Removes the file, or the empty directory, `path`.
+    fn remove(&self, _path: &Path) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+}
+
+/// What the file system caches.
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    /// The total size of the contents of the files kept, the least
+    /// recently used being evicted first. `0` disables the cache of
+    /// the contents.
+    pub max_bytes: u64,
+    /// Whether the directory listings are kept. They are dropped when
+    /// the directory is modified through the file system.
+    pub directories: bool,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024 * 1024,
+            directories: true,
+        }
+    }
+}
+
+/// The cached contents and directory listings.
+#[derive(Debug, Default)]
+struct Cache {
+    options: CacheOptions,
+    /// The contents of the files, with the tick of their last use.
+    files: HashMap<PathBuf, (Arc<Vec<u8>>, u64)>,
+    directories: HashMap<PathBuf, Vec<Entry>>,
+    /// The total size of `files`.
+    bytes: u64,
+    tick: u64,
+}
+
+impl Cache {
+    fn file(&mut self, path: &Path) -> Option<Arc<Vec<u8>>> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.files.get_mut(path).map(|(contents, used)| {
+            *used = tick;
+            contents.clone()
+        })
+    }
+
+    /// Keeps the `contents` of the file `path`, evicting the least
+    /// recently used files until they fit.
+    fn insert_file(&mut self, path: &Path, contents: Arc<Vec<u8>>) {
+        self.remove_file(path);
+
+        let len = contents.len() as u64;
+        if len > self.options.max_bytes {
+            return;
+        }
+        while self.bytes + len > self.options.max_bytes {
+            let oldest = self
+                .files
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(path, _)| path.clone());
+            match oldest {
+                Some(oldest) => self.remove_file(&oldest),
+                None => break,
+            }
+        }
+
+        self.tick += 1;
+        self.bytes += len;
+        self.files.insert(path.to_path_buf(), (contents, self.tick));
+    }
+
+    fn remove_file(&mut self, path: &Path) {
+        if let Some((contents, _)) = self.files.remove(path) {
+            self.bytes -= contents.len() as u64;
+        }
+    }
+
+    fn directory(&self, path: &Path) -> Option<Vec<Entry>> {
+        self.directories.get(path).cloned()
+    }
+
+    fn insert_directory(&mut self, path: &Path, entries: Vec<Entry>) {
+        if self.options.directories {
+            self.directories.insert(path.to_path_buf(), entries);
+        }
+    }
+
+    fn remove_directory(&mut self, path: &Path) {
+        self.directories.remove(path);
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    backend: Box<dyn Backend>,
+    cache: Mutex<Cache>,
+}
+
+impl Inner {
+    fn cache(&self) -> Result<std::sync::MutexGuard<'_, Cache>> {
+        self.cache.lock().map_err(|_| FsError::Lock)
+    }
+
+    /// Lists the directory `path`, from the cache if it's there.
+    fn entries(&self, path: &Path) -> Result<Vec<Entry>> {
+        if let Some(entries) = self.cache()?.directory(path) {
+            return Ok(entries);
+        }
+
+        let entries = self.backend.read_dir(path)?;
+        self.cache()?.insert_directory(path, entries.clone());
+        Ok(entries)
+    }
+
+    /// Returns the entry of the normalized `path`, `None` for the
+    /// root.
+    fn entry(&self, path: &Path) -> Result<Option<Entry>> {
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => return Ok(None),
+        };
+        if let Some(Entry { is_dir: false, .. }) = self.entry(parent)? {
+            return Err(FsError::BaseNotDirectory);
+        }
+
+        self.entries(parent)?
+            .into_iter()
+            .find(|entry| name == entry.name.as_str())
+            .map(Some)
+            .ok_or(FsError::EntityNotFound)
+    }
+
+    /// Reads the file `path`, from the cache if it's there.
+    fn contents(&self, path: &Path) -> Result<Arc<Vec<u8>>> {
+        if let Some(contents) = self.cache()?.file(path) {
+            return Ok(contents);
+        }
+
+        let contents = Arc::new(self.backend.read(path)?);
+        self.cache()?.insert_file(path, contents.clone());
+        Ok(contents)
+    }
+
+    /// Writes the file `path` to the backend, and updates the cache.
+    fn write(&self, path: &Path, contents: Vec<u8>) -> Result<()> {
+        self.backend.write(path, &contents)?;
+
+        let mut cache = self.cache()?;
+        cache.insert_file(path, Arc::new(contents));
+        if let Some(parent) = path.parent() {
+            cache.remove_directory(parent);
+        }
+        Ok(())
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.metadata_of(path, self.entry(path)?.as_ref())
+    }
+
+    /// The metadata of the normalized `path`, whose `entry` is
+    /// listed by its parent. A file whose size isn't listed is read.
+    fn metadata_of(&self, path: &Path, entry: Option<&Entry>) -> Result<Metadata> {
+        let (dir, len) = match entry {
+            None | Some(Entry { is_dir: true, .. }) => (true, 0),
+            Some(Entry { len: Some(len), .. }) => (false, *len),
+            Some(Entry { len: None, .. }) => (false, self.contents(path)?.len() as u64),
+        };
+        let now = time();
+
+        Ok(Metadata {
+            ft: FileType {
+                dir,
+                file: !dir,
+                ..Default::default()
+            },
+            accessed: now,
+            created: now,
+            modified: now,
+            len,
+            nlink: 1,
+            mode: if dir { 0o755 } else { 0o644 },
+            ..Default::default()
+        })
+    }
+}
+
+/// The file system. It is cheap to clone, clones share the same
+/// backend and cache.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    inner: Arc<Inner>,
+}
+
+impl FileSystem {
+    /// Delegates to `backend`, caching as `cache` says.
+    pub fn new<B: Backend>(backend: B, cache: CacheOptions) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                backend: Box::new(backend),
+                cache: Mutex::new(Cache {
+                    options: cache,
+                    ..Default::default()
+                }),
+            }),
+        }
+    }
+
+    /// Forgets everything cached, e.g. after the storage was modified
+    /// behind the file system.
+    pub fn clear_cache(&self) -> Result<()> {
+        let mut cache = self.inner.cache()?;
+        cache.files.clear();
+        cache.directories.clear();
+        cache.bytes = 0;
+        Ok(())
+    }
+}
+
+fn normalize(path: &Path) -> Result<PathBuf> {
+    PathResolver::new().normalize(path)
+}
+
+impl crate::FileSystem for FileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let path = normalize(path)?;
+        if let Some(Entry { is_dir: false, .. }) = self.inner.entry(&path)? {
+            return Err(FsError::BaseNotDirectory);
+        }
+
+        let entries = self
+            .inner
+            .entries(&path)?
+            .into_iter()
+            .map(|entry| {
+                let path = path.join(&entry.name);
+                DirEntry {
+                    metadata: self.inner.metadata_of(&path, Some(&entry)),
+                    path,
+                }
+            })
+            .collect();
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        let parent = path.parent().ok_or(FsError::AlreadyExists)?;
+        match self.inner.entry(&path) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::EntityNotFound) => {}
+            Err(err) => return Err(err),
+        }
+
+        self.inner.backend.create_dir(&path)?;
+        self.inner.cache()?.remove_directory(parent);
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        let parent = path.parent().ok_or(FsError::PermissionDenied)?;
+        match self.inner.entry(&path)? {
+            Some(Entry { is_dir: true, .. }) => {}
+            _ => return Err(FsError::BaseNotDirectory),
+        }
+        if !self.inner.entries(&path)?.is_empty() {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+
+        self.inner.backend.remove(&path)?;
+        let mut cache = self.inner.cache()?;
+        cache.remove_directory(&path);
+        cache.remove_directory(parent);
+        Ok(())
+    }
+
+    /// Renames a file by writing its contents to `to`, and removing
+    /// `from`. Renaming a directory is unsupported.
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let from = normalize(from)?;
+        let to = normalize(to)?;
+        match self.inner.entry(&from)? {
+            Some(Entry { is_dir: false, .. }) => {}
+            _ => return Err(FsError::Unsupported),
+        }
+        match self.inner.entry(&to) {
+            Ok(Some(Entry { is_dir: false, .. })) | Err(FsError::EntityNotFound) => {}
+            Ok(_) => return Err(FsError::NotAFile),
+            Err(err) => return Err(err),
+        }
+        if from == to {
+            return Ok(());
+        }
+
+        let contents = self.inner.contents(&from)?;
+        self.inner.write(&to, contents.to_vec())?;
+        self.remove_file(&from)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(&normalize(path)?)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        match self.inner.entry(&path)? {
+            Some(Entry { is_dir: false, .. }) => {}
+            _ => return Err(FsError::NotAFile),
+        }
+
+        self.inner.backend.remove(&path)?;
+        let mut cache = self.inner.cache()?;
+        cache.remove_file(&path);
+        if let Some(parent) = path.parent() {
+            cache.remove_directory(parent);
+        }
+        Ok(())
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl FileOpener for FileSystem {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let path = normalize(path)?;
+        let exists = match self.inner.entry(&path) {
+            Ok(Some(Entry { is_dir: false, .. })) => true,
+            Ok(_) => return Err(FsError::NotAFile),
+            Err(FsError::EntityNotFound) => false,
+            Err(err) => return Err(err),
+        };
+        let writable = conf.write() || conf.append();
+
+        let (contents, dirty) = match (exists, conf.create_new()) {
+            (true, true) => return Err(FsError::AlreadyExists),
+            (false, _) if !conf.create() && !conf.create_new() => {
+                return Err(FsError::EntityNotFound)
+            }
+            // The new file is written when the file is closed.
+            (false, _) => (Vec::new(), true),
+            (true, _) if conf.truncate() && writable => (Vec::new(), true),
+            (true, _) => (self.inner.contents(&path)?.to_vec(), false),
+        };
+
+        let mut contents = Cursor::new(contents);
+        if conf.append() {
+            contents.seek(SeekFrom::End(0))?;
+        }
+
+        Ok(Box::new(File {
+            inner: self.inner.clone(),
+            path,
+            contents,
+            readable: conf.read(),
+            writable,
+            append: conf.append(),
+            dirty,
+            opened: time(),
+        }))
+    }
+}
+
+/// An opened file, whose contents are kept in memory until it's
+/// flushed or dropped.
+#[derive(Debug)]
+struct File {
+    inner: Arc<Inner>,
+    path: PathBuf,
+    contents: Cursor<Vec<u8>>,
+    readable: bool,
+    writable: bool,
+    append: bool,
+    /// Whether the contents must be written back to the backend.
+    dirty: bool,
+    opened: u64,
+}
+
+impl File {
+    fn check_writable(&self) -> io::Result<()> {
+        if self.writable {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the file isn't opened for writing",
+            ))
+        }
+    }
+
+    /// Writes the contents back to the backend if they were modified.
+    fn write_back(&mut self) -> Result<()> {
+        if self.dirty {
+            self.inner
+                .write(&self.path, self.contents.get_ref().clone())?;
+            self.dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        if let Err(err) = self.write_back() {
+            tracing::warn!("failed to write back `{}`: {}", self.path.display(), err);
+        }
+    }
+}
+
+impl VirtualFile for File {
+    fn last_accessed(&self) -> u64 {
+        self.opened
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.opened
+    }
+
+    fn created_time(&self) -> u64 {
+        self.opened
+    }
+
+    fn size(&self) -> u64 {
+        self.contents.get_ref().len() as u64
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<()> {
+        if !self.writable {
+            return Err(FsError::PermissionDenied);
+        }
+
+        self.contents.get_mut().resize(new_size as usize, 0);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        crate::FileSystem::remove_file(
+            &FileSystem {
+                inner: self.inner.clone(),
+            },
+            &self.path,
+        )?;
+        // Nothing is written back once the file is removed.
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn sync_to_disk(&self) -> Result<()> {
+        if self.dirty {
+            self.inner
+                .write(&self.path, self.contents.get_ref().clone())?;
+        }
+
+        Ok(())
+    }
+
+    fn bytes_available(&self) -> Result<usize> {
+        Ok(self
+            .contents
+            .get_ref()
+            .len()
+            .saturating_sub(self.contents.position() as usize))
+    }
+
+    fn is_readable(&self) -> bool {
+        self.readable
+    }
+
+    fn is_writable(&self) -> bool {
+        self.writable
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.contents.read(buf)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.contents.seek(position)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check_writable()?;
+        if self.append {
+            self.contents.seek(SeekFrom::End(0))?;
+        }
+
+        self.dirty = true;
+        self.contents.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_back()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod js {
+    use super::{Backend, Entry};
+    use crate::{FsError, Result};
+    use js_sys::{Array, Function, Object, Reflect, Uint8Array};
+    use std::path::Path;
+    use wasm_bindgen::{JsCast, JsValue};
+
+    /// A [`Backend`] calling the methods of a JavaScript object:
+    ///
+    /// - `readdir(path)` returns the entries of a directory, as an
+    ///   array of `{ name, dir, size }`, `dir` and `size` being
+    ///   optional,
+    /// - `read(path)` returns the contents of a file, as a
+    ///   `Uint8Array`,
+    /// - `write(path, contents)` replaces the contents of a file,
+    /// - `mkdir(path)` and `remove(path)`, optional, create a
+    ///   directory, and remove a file or an empty directory.
+    ///
+    /// The methods are synchronous. They fail by throwing, an object
+    /// whose `code` is `ENOENT`, `EEXIST`, `EACCES`, `ENOTDIR` or
+    /// `ENOTEMPTY` being reported as such.
+    #[derive(Debug)]
+    pub struct JsBackend {
+        callbacks: Object,
+    }
+
+    // The JavaScript values stay on the thread of the page, the
+    // browser runtime being single-threaded.
+    unsafe impl Send for JsBackend {}
+    unsafe impl Sync for JsBackend {}
+
+    impl JsBackend {
+        /// Calls the methods of `callbacks`.
+        pub fn new(callbacks: Object) -> Self {
+            Self { callbacks }
+        }
+
+        /// Calls the method `name` with `path`, and `contents` if
+        /// any. A missing method is unsupported.
+        fn call(&self, name: &str, path: &Path, contents: Option<&[u8]>) -> Result<JsValue> {
+            let method = Reflect::get(&self.callbacks, &JsValue::from_str(name))
+                .ok()
+                .and_then(|method| method.dyn_into::<Function>().ok())
+                .ok_or(FsError::Unsupported)?;
+
+            let args = Array::new();
+            args.push(&JsValue::from_str(&path.to_string_lossy()));
+            if let Some(contents) = contents {
+                args.push(&Uint8Array::from(contents));
+            }
+
+            method.apply(&self.callbacks, &args).map_err(to_fs_error)
+        }
+    }
+
+    /// Converts an exception thrown by a method.
+    fn to_fs_error(error: JsValue) -> FsError {
+        let code = Reflect::get(&error, &JsValue::from_str("code"))
+            .ok()
+            .and_then(|code| code.as_string());
+
+        match code.as_deref() {
+            Some("ENOENT") => FsError::EntityNotFound,
+            Some("EEXIST") => FsError::AlreadyExists,
+            Some("EACCES") => FsError::PermissionDenied,
+            Some("ENOTDIR") => FsError::BaseNotDirectory,
+            Some("ENOTEMPTY") => FsError::DirectoryNotEmpty,
+            _ => FsError::IOError,
+        }
+    }
+
+    /// Gets the property `name` of `object`.
+    fn get(object: &JsValue, name: &str) -> JsValue {
+        Reflect::get(object, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+    }
+
+    impl Backend for JsBackend {
+        fn read_dir(&self, path: &Path) -> Result<Vec<Entry>> {
+            self.call("readdir", path, None)?
+                .dyn_into::<Array>()
+                .map_err(|_| FsError::InvalidData)?
+                .iter()
+                .map(|entry| {
+                    Ok(Entry {
+                        name: get(&entry, "name")
+                            .as_string()
+                            .ok_or(FsError::InvalidData)?,
+                        is_dir: get(&entry, "dir").as_bool().unwrap_or(false),
+                        len: get(&entry, "size").as_f64().map(|size| size as u64),
+                    })
+                })
+                .collect()
+        }
+
+        fn read(&self, path: &Path) -> Result<Vec<u8>> {
+            let contents = self.call("read", path, None)?;
+            if contents.is_null() || contents.is_undefined() {
+                return Err(FsError::EntityNotFound);
+            }
+
+            contents
+                .dyn_into::<Uint8Array>()
+                .map(|contents| contents.to_vec())
+                .map_err(|_| FsError::InvalidData)
+        }
+
+        fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+            self.call("write", path, Some(contents)).map(|_| ())
+        }
+
+        fn create_dir(&self, path: &Path) -> Result<()> {
+            self.call("mkdir", path, None).map(|_| ())
+        }
+
+        fn remove(&self, path: &Path) -> Result<()> {
+            self.call("remove", path, None).map(|_| ())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_filesystem {
+    use super::{Backend, CacheOptions, Entry, FileSystem};
+    use crate::{FileSystem as FS, FsError, Result};
+    use std::collections::BTreeMap;
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    /// A storage in memory, counting the files it reads. A `None`
+    /// content is a directory.
+    #[derive(Debug, Default, Clone)]
+    struct Storage {
+        nodes: Arc<Mutex<BTreeMap<PathBuf, Option<Vec<u8>>>>>,
+        reads: Arc<Mutex<usize>>,
+    }
+
+    impl Backend for Storage {
+        fn read_dir(&self, path: &Path) -> Result<Vec<Entry>> {
+            Ok(self
+                .nodes
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(child, _)| child.parent() == Some(path))
+                .map(|(child, contents)| Entry {
+                    name: child.file_name().unwrap().to_string_lossy().into_owned(),
+                    is_dir: contents.is_none(),
+                    len: None,
+                })
+                .collect())
+        }
+
+        fn read(&self, path: &Path) -> Result<Vec<u8>> {
+            *self.reads.lock().unwrap() += 1;
+            match self.nodes.lock().unwrap().get(path) {
+                Some(Some(contents)) => Ok(contents.clone()),
+                _ => Err(FsError::EntityNotFound),
+            }
+        }
+
+        fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+            self.nodes
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), Some(contents.to_vec()));
+            Ok(())
+        }
+
+        fn create_dir(&self, path: &Path) -> Result<()> {
+            self.nodes.lock().unwrap().insert(path.to_path_buf(), None);
+            Ok(())
+        }
+
+        fn remove(&self, path: &Path) -> Result<()> {
+            self.nodes.lock().unwrap().remove(path);
+            Ok(())
+        }
+    }
+
+    fn read(fs: &FileSystem, path: &str) -> Result<String> {
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(Path::new(path))?
+            .read_to_string(&mut contents)
+            .unwrap();
+        Ok(contents)
+    }
+
+    #[test]
+    fn test_reading_and_writing() {
+        let storage = Storage::default();
+        let fs = FileSystem::new(storage.clone(), CacheOptions::default());
+
+        assert_eq!(fs.create_dir(Path::new("/etc")), Ok(()));
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(Path::new("/etc/../etc/motd"))
+            .unwrap();
+        file.write_all(b"hello").unwrap();
+        assert_eq!(
+            storage.read(Path::new("/etc/motd")),
+            Err(FsError::EntityNotFound),
+            "written back once closed",
+        );
+        drop(file);
+        assert_eq!(storage.read(Path::new("/etc/motd")), Ok(b"hello".to_vec()));
+
+        assert_eq!(read(&fs, "/etc/motd"), Ok("hello".to_string()));
+        assert_eq!(fs.metadata(Path::new("/etc/motd")).unwrap().len(), 5);
+        assert_eq!(
+            fs.read_dir(Path::new("/etc"))
+                .unwrap()
+                .map(|entry| entry.unwrap().path)
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("/etc/motd")]
+        );
+
+        assert_eq!(
+            fs.rename(Path::new("/etc/motd"), Path::new("/etc/issue")),
+            Ok(())
+        );
+        assert_eq!(read(&fs, "/etc/motd"), Err(FsError::EntityNotFound));
+        assert_eq!(read(&fs, "/etc/issue"), Ok("hello".to_string()));
+        assert_eq!(
+            fs.remove_dir(Path::new("/etc")),
+            Err(FsError::DirectoryNotEmpty)
+        );
+        assert_eq!(fs.remove_file(Path::new("/etc/issue")), Ok(()));
+        assert_eq!(fs.remove_dir(Path::new("/etc")), Ok(()));
+        assert!(storage.nodes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_caching() {
+        let storage = Storage::default();
+        for (path, contents) in [("/a", "aaaa"), ("/b", "bbbb")] {
+            storage.write(Path::new(path), contents.as_bytes()).unwrap();
+        }
+        let reads = || *storage.reads.lock().unwrap();
+
+        let fs = FileSystem::new(
+            storage.clone(),
+            CacheOptions {
+                max_bytes: 6,
+                directories: true,
+            },
+        );
+        assert_eq!(read(&fs, "/a"), Ok("aaaa".to_string()));
+        assert_eq!(read(&fs, "/a"), Ok("aaaa".to_string()));
+        assert_eq!(reads(), 1, "the contents are cached");
+
+        assert_eq!(read(&fs, "/b"), Ok("bbbb".to_string()));
+        assert_eq!(read(&fs, "/a"), Ok("aaaa".to_string()));
+        assert_eq!(reads(), 3, "the least recently used file is evicted");
+
+        storage.write(Path::new("/c"), b"c").unwrap();
+        assert_eq!(
+            read(&fs, "/c"),
+            Err(FsError::EntityNotFound),
+            "the listing is cached"
+        );
+        fs.clear_cache().unwrap();
+        assert_eq!(read(&fs, "/c"), Ok("c".to_string()));
+    }
+}
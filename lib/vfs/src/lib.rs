@@ -11,13 +11,72 @@ compile_error!("At least the `host-fs` or the `mem-fs` feature must be enabled.
 //#[cfg(all(feature = "mem-fs", feature = "enable-serde"))]
 //compile_warn!("`mem-fs` does not support `enable-serde` for the moment.");
 
+#[cfg(feature = "async-fs")]
+pub mod async_fs;
+#[cfg(feature = "audit-fs")]
+pub mod audit_fs;
+#[cfg(feature = "dev-fs")]
+pub mod dev_fs;
+#[cfg(all(unix, feature = "fuse"))]
+pub mod fuse;
 #[cfg(feature = "host-fs")]
 pub mod host_fs;
+#[cfg(feature = "js-fs")]
+pub mod js_fs;
+mod mapping;
 #[cfg(feature = "mem-fs")]
 pub mod mem_fs;
+#[cfg(feature = "mount-fs")]
+pub mod mount_fs;
+#[cfg(feature = "overlay-fs")]
+pub mod overlay_fs;
+mod path_resolver;
+pub mod pipe;
+#[cfg(feature = "read-only-fs")]
+pub mod read_only_fs;
+#[cfg(feature = "sandbox-fs")]
+pub mod sandbox_fs;
+#[cfg(feature = "synthetic-fs")]
+pub mod synthetic_fs;
+pub mod watch;
+
+pub use mapping::FileMapping;
+pub use path_resolver::{is_directory_path, ParentOfRoot, PathResolver, MAX_SYMLINKS};
+pub use pipe::{Pipe, PipeReader, PipeSocket, PipeWriter};
+pub use watch::{WatchEvent, WatchHandle};
 
 pub type Result<T> = std::result::Result<T, FsError>;
 
+/// Makes `path` canonical, see [`PathResolver::normalize`].
+#[cfg(any(feature = "mount-fs", feature = "overlay-fs"))]
+fn normalize(path: &Path) -> Result<PathBuf> {
+    PathResolver::new().normalize(path)
+}
+
+/// Returns the current time, in nanoseconds since `UNIX_EPOCH`.
+#[cfg(any(
+    feature = "mem-fs",
+    feature = "synthetic-fs",
+    feature = "audit-fs",
+    feature = "js-fs"
+))]
+fn time() -> u64 {
+    #[cfg(not(feature = "no-time"))]
+    {
+        // SAFETY: It's very unlikely that the system returns a time that
+        // is before `UNIX_EPOCH` :-).
+        std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+    }
+
+    #[cfg(feature = "no-time")]
+    {
+        0
+    }
+}
+
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct FileDescriptor(usize);
@@ -38,15 +97,82 @@ pub trait FileSystem: fmt::Debug + Send + Sync + 'static + Upcastable {
     fn read_dir(&self, path: &Path) -> Result<ReadDir>;
     fn create_dir(&self, path: &Path) -> Result<()>;
     fn remove_dir(&self, path: &Path) -> Result<()>;
+    /// Renames `from` to `to`, replacing `to` if it exists, unless
+    /// it's a directory and `from` isn't, or the other way around, or
+    /// a directory which isn't empty.
+    ///
+    /// The files opened at `from`, or at a replaced `to`, stay valid
+    /// and keep referring to the same file, like on POSIX.
     fn rename(&self, from: &Path, to: &Path) -> Result<()>;
     fn metadata(&self, path: &Path) -> Result<Metadata>;
-    /// This method gets metadata without following symlinks in the path.
-    /// Currently identical to `metadata` because symlinks aren't implemented
-    /// yet.
+    /// This method gets metadata without following a symlink at the end
+    /// of the path, i.e. it returns the metadata of the symlink itself.
+    /// The default implementation is identical to `metadata`, for file
+    /// systems that don't support symlinks.
     fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
         self.metadata(path)
     }
+    /// Creates a symlink at `link` pointing to `original`, which is
+    /// stored as is and resolved relatively to the parent of `link`.
+    fn symlink(&self, _original: &Path, _link: &Path) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+    /// Returns the path the symlink at `path` points to.
+    fn read_link(&self, _path: &Path) -> Result<PathBuf> {
+        Err(FsError::InvalidInput)
+    }
+    /// Creates a hard link at `link` to the file `original`, both
+    /// names then referring to the same file.
+    fn link(&self, _original: &Path, _link: &Path) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+    /// Changes the permission bits of `path`, following symlinks.
+    fn chmod(&self, _path: &Path, _mode: u32) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+    /// Changes the owner and the group of `path`, following symlinks.
+    /// `None` leaves the corresponding ID unchanged.
+    fn chown(&self, _path: &Path, _uid: Option<u32>, _gid: Option<u32>) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+    /// Sets the access and modification times of `path`, in
+    /// nanoseconds since `UNIX_EPOCH`, following symlinks. `None`
+    /// leaves the corresponding time unchanged.
+    fn set_times(
+        &self,
+        _path: &Path,
+        _accessed: Option<u64>,
+        _modified: Option<u64>,
+    ) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+    /// Removes the name `path` of a file. The files opened on it stay
+    /// valid, like on POSIX, so that a temporary file can be unlinked
+    /// as soon as it's created; its space is reclaimed once it has no
+    /// name left and the last of them is dropped.
     fn remove_file(&self, path: &Path) -> Result<()>;
+    /// Copies the contents of the file `from` to the file `to`, which
+    /// is created or truncated. The default implementation reads and
+    /// writes the whole file; file systems able to share the contents
+    /// between both files should override it.
+    fn clone_file(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut source = self.new_open_options().read(true).open(from)?;
+        let mut destination = self
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(to)?;
+        io::copy(&mut source, &mut destination)?;
+
+        Ok(())
+    }
+    /// Starts watching the changes of `path` and of its immediate
+    /// children, or of all of its descendants if `recursive`. The
+    /// events are received from the returned handle.
+    fn watch(&self, _path: &Path, _recursive: bool) -> Result<WatchHandle> {
+        Err(FsError::Unsupported)
+    }
 
     fn new_open_options(&self) -> OpenOptions;
 }
@@ -171,6 +297,10 @@ impl OpenOptions {
 }
 
 /// This trait relies on your file closing when it goes out of scope via `Drop`
+///
+/// The WASI `fd_read` and `fd_write` hand all their buffers to
+/// [`Read::read_vectored`] and [`Write::write_vectored`]. Implement them
+/// if the file can transfer several buffers at once.
 //#[cfg_attr(feature = "enable-serde", typetag::serde)]
 pub trait VirtualFile: fmt::Debug + Write + Read + Seek + Upcastable {
     /// the last time the file was accessed in nanoseconds as a UNIX timestamp
@@ -192,6 +322,38 @@ pub trait VirtualFile: fmt::Debug + Write + Read + Seek + Upcastable {
     /// Request deletion of the file
     fn unlink(&mut self) -> Result<()>;
 
+    /// Reads from the file at `offset`, without moving the cursor.
+    /// Default implementation seeks to `offset`, reads, and seeks
+    /// back. Implement it if the file can read at a position directly.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let cursor = self.stream_position()?;
+        self.seek(io::SeekFrom::Start(offset))?;
+        let result = self.read(buf);
+        self.seek(io::SeekFrom::Start(cursor))?;
+
+        result
+    }
+
+    /// Writes to the file at `offset`, without moving the cursor.
+    /// Default implementation seeks to `offset`, writes, and seeks
+    /// back. Implement it if the file can write at a position
+    /// directly.
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<usize> {
+        let cursor = self.stream_position()?;
+        self.seek(io::SeekFrom::Start(offset))?;
+        let result = self.write(buf);
+        self.seek(io::SeekFrom::Start(cursor))?;
+
+        result
+    }
+
+    /// Maps the contents of the file, to read them without copying
+    /// them out of the file first. See [`FileMapping`].
+    /// Defaults to `None`, for files whose contents aren't in memory.
+    fn map(&self) -> Option<FileMapping> {
+        None
+    }
+
     /// Store file contents and metadata to disk
     /// Default implementation returns `Ok(())`.  You should implement this method if you care
     /// about flushing your cache to permanent storage
@@ -223,6 +385,35 @@ pub trait VirtualFile: fmt::Debug + Write + Read + Seek + Upcastable {
         true
     }
 
+    /// Indicates if the file can be read from.
+    /// Defaults to `true`
+    fn is_readable(&self) -> bool {
+        true
+    }
+
+    /// Indicates if the file can be written to, or resized.
+    /// Defaults to `true`
+    fn is_writable(&self) -> bool {
+        true
+    }
+
+    /// Indicates if the file supports seeking, which is not the case
+    /// of streams like pipes or the standard I/O.
+    /// Defaults to `true`
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    /// Handles the control `request`, in the manner of `ioctl`, with
+    /// its `data`, returning the data of the response. Each file
+    /// defines the requests it understands, e.g. a terminal its size
+    /// or a framebuffer its mode; they reach it through the
+    /// `fd_ioctl` syscall.
+    /// Defaults to failing with [`FsError::UnsupportedControl`].
+    fn ioctl(&mut self, _request: u32, _data: &[u8]) -> Result<Vec<u8>> {
+        Err(FsError::UnsupportedControl)
+    }
+
     /// Used for polling.  Default returns `None` because this method cannot be implemented for most types
     /// Returns the underlying host fd
     fn get_fd(&self) -> Option<FileDescriptor> {
@@ -342,6 +533,25 @@ pub enum FsError {
     /// Directory not Empty
     #[error("directory not empty")]
     DirectoryNotEmpty,
+    /// The operation would move or link a file across file systems
+    #[error("cross-device link")]
+    CrossDevice,
+    /// The resource is in use, like a mount point
+    #[error("resource busy")]
+    Busy,
+    /// Too many symlinks were followed while resolving a path
+    #[error("too many levels of symbolic links")]
+    SymlinkLoop,
+    /// The operation is not supported by the file system
+    #[error("operation not supported")]
+    Unsupported,
+    /// A limit of the file system, like its size, would be exceeded
+    #[error("quota exceeded")]
+    QuotaExceeded,
+    /// The file doesn't understand a control request, see
+    /// [`VirtualFile::ioctl`]
+    #[error("inappropriate I/O control operation")]
+    UnsupportedControl,
     /// Some other unhandled error. If you see this, it's probably a bug.
     #[error("unknown error found")]
     UnknownError,
@@ -421,10 +631,21 @@ impl DirEntry {
 // TODO: review this, proper solution would probably use a trait object internally
 pub struct Metadata {
     pub ft: FileType,
+    /// The last access time, in nanoseconds since `UNIX_EPOCH`.
     pub accessed: u64,
+    /// The creation time, in nanoseconds since `UNIX_EPOCH`.
     pub created: u64,
+    /// The last modification time, in nanoseconds since `UNIX_EPOCH`.
     pub modified: u64,
     pub len: u64,
+    /// The number of directory entries referring to this node.
+    pub nlink: u64,
+    /// The user owning this node.
+    pub uid: u32,
+    /// The group owning this node.
+    pub gid: u32,
+    /// The permission bits of this node, as in `st_mode & 0o7777`.
+    pub mode: u32,
 }
 
 impl Metadata {
@@ -455,6 +676,22 @@ impl Metadata {
     pub fn len(&self) -> u64 {
         self.len
     }
+
+    pub fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -0,0 +1,69 @@
+//! Mappings of the contents of the files, read without copying them
+//! out of the file first, see [`VirtualFile::map`](crate::VirtualFile::map).
+
+use std::borrow::Cow;
+use std::cmp;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// The contents of a file, shared with the file rather than copied.
+///
+/// The mapping is a snapshot: the file isn't changed by the mapping,
+/// and the writes to the file after the mapping was taken don't
+/// change it. Keeping a mapping alive makes the next write to the
+/// file copy its contents, so it's better dropped soon.
+#[derive(Debug, Clone)]
+pub struct FileMapping {
+    bytes: Arc<Vec<u8>>,
+    len: usize,
+}
+
+impl FileMapping {
+    /// Maps `bytes`, the contents of a file of `len` bytes. When
+    /// `len` is larger than `bytes`, the rest of the file is a hole
+    /// read as zeros.
+    pub fn new(bytes: Arc<Vec<u8>>, len: usize) -> Self {
+        Self { bytes, len }
+    }
+
+    /// The size of the file.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the bytes in `range`, clamped to the size of the file.
+    /// They are borrowed from the file unless they cross its hole.
+    pub fn get(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        let end = cmp::min(range.end, self.len);
+        let start = cmp::min(range.start, end);
+
+        if end <= self.bytes.len() {
+            Cow::Borrowed(&self.bytes[start..end])
+        } else {
+            let mut bytes = self.bytes.get(start..).unwrap_or_default().to_vec();
+            bytes.resize(end - start, 0);
+
+            Cow::Owned(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let mapping = FileMapping::new(Arc::new(b"foobar".to_vec()), 8);
+
+        assert!(matches!(mapping.get(1..4), Cow::Borrowed(b"oob")));
+        assert_eq!(mapping.get(4..10), &b"ar\0\0"[..], "reading the hole");
+        assert_eq!(mapping.get(7..10), &b"\0"[..]);
+        assert_eq!(mapping.get(9..12), &b""[..], "reading after the end");
+    }
+}
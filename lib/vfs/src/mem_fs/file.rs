@@ -2,13 +2,16 @@
 //! implementations. They aren't exposed to the public API. Only
 //! `FileHandle` can be used through the `VirtualFile` trait object.
 
+use super::journal::Record;
 use super::*;
-use crate::{FileDescriptor, FsError, Result, VirtualFile};
+use crate::{FileDescriptor, FileMapping, FsError, Result, VirtualFile};
+use std::borrow::Cow;
 use std::cmp;
 use std::convert::TryInto;
 use std::fmt;
 use std::io::{self, Read, Seek, Write};
 use std::str;
+use std::sync::Arc;
 
 /// A file handle. The file system doesn't return the [`File`] type
 /// directly, but rather this `FileHandle` type, which contains the
@@ -17,7 +20,9 @@ use std::str;
 /// operations to be executed, and then it is checked that the file
 /// still exists in the file system. After that, the operation is
 /// delegated to the file itself.
-#[derive(Clone)]
+///
+/// The file is kept in the file system as long as a handle is opened
+/// on it, even if it has been unlinked meanwhile.
 pub(super) struct FileHandle {
     inode: Inode,
     filesystem: FileSystem,
@@ -42,6 +47,84 @@ impl FileHandle {
             append_mode,
         }
     }
+
+    /// Writes `bufs`, one after the other, at `offset`, or at the
+    /// cursor, which then advances, if `None`.
+    fn write_bytes(&mut self, offset: Option<usize>, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        if !self.writable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "the file (inode `{}) doesn't have the `write` permission",
+                    self.inode
+                ),
+            ));
+        }
+
+        let mut fs =
+            self.filesystem.inner.try_write().map_err(|_| {
+                io::Error::new(io::ErrorKind::Other, "failed to acquire a write lock")
+            })?;
+
+        if let Some(Node::File { file, metadata, .. }) = fs.storage.get(self.inode) {
+            let position = match (self.append_mode, offset) {
+                (true, _) => file.len(),
+                (false, Some(offset)) => offset,
+                (false, None) => file.cursor(),
+            };
+            let to_write = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+            let new_len = cmp::max(file.len(), position + to_write);
+
+            fs.check_resize(metadata.len, new_len as u64)
+                .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        }
+
+        let inode = fs.storage.get_mut(self.inode);
+        let (file, metadata) = match inode {
+            Some(Node::File { file, metadata, .. }) => (file, metadata),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("inode `{}` doesn't match a file", self.inode),
+                ))
+            }
+        };
+
+        // In `append` mode, the bytes are always written at the end.
+        let bytes_written = match offset {
+            _ if self.append_mode => {
+                file.seek(io::SeekFrom::End(0))?;
+                file.write_vectored(bufs)?
+            }
+            Some(offset) => {
+                let mut position = offset;
+                for buf in bufs {
+                    file.write_at(position, buf);
+                    position += buf.len();
+                }
+                position - offset
+            }
+            None => file.write_vectored(bufs)?,
+        };
+
+        let old_len = metadata.len;
+        metadata.len = file.len().try_into().unwrap();
+        metadata.modified = time();
+
+        let new_len = metadata.len;
+        fs.resized(old_len, new_len);
+        fs.mark_dirty(self.inode);
+
+        Ok(bytes_written)
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        if let Ok(mut fs) = self.filesystem.inner.write() {
+            fs.close_handle(self.inode);
+        }
+    }
 }
 
 impl VirtualFile for FileHandle {
@@ -106,21 +189,30 @@ impl VirtualFile for FileHandle {
             .try_write()
             .map_err(|_| FsError::Lock)?;
 
+        if let Some(Node::File { metadata, .. }) = fs.storage.get(self.inode) {
+            fs.check_resize(metadata.len, new_size)?;
+        }
+
         let inode = fs.storage.get_mut(self.inode);
         match inode {
             Some(Node::File { file, metadata, .. }) => {
-                file.buffer
-                    .resize(new_size.try_into().map_err(|_| FsError::UnknownError)?, 0);
+                let old_len = metadata.len;
+                file.set_len(new_size.try_into().map_err(|_| FsError::UnknownError)?);
                 metadata.len = new_size;
+                metadata.modified = time();
+
+                fs.resized(old_len, new_size);
             }
             _ => return Err(FsError::NotAFile),
         }
 
+        fs.mark_dirty(self.inode);
+
         Ok(())
     }
 
     fn unlink(&mut self) -> Result<()> {
-        let (inode_of_parent, position, inode_of_file) = {
+        let (inode_of_parent, position) = {
             // Read lock.
             let fs = self
                 .filesystem
@@ -128,30 +220,9 @@ impl VirtualFile for FileHandle {
                 .try_read()
                 .map_err(|_| FsError::Lock)?;
 
-            // The inode of the file.
-            let inode_of_file = self.inode;
-
             // Find the position of the file in the parent, and the
             // inode of the parent.
-            let (position, inode_of_parent) = fs
-                .storage
-                .iter()
-                .find_map(|(inode_of_parent, node)| match node {
-                    Node::Directory { children, .. } => {
-                        children.iter().enumerate().find_map(|(nth, inode)| {
-                            if inode == &inode_of_file {
-                                Some((nth, inode_of_parent))
-                            } else {
-                                None
-                            }
-                        })
-                    }
-
-                    _ => None,
-                })
-                .ok_or(FsError::BaseNotDirectory)?;
-
-            (inode_of_parent, position, inode_of_file)
+            fs.as_child_get_parent_and_position(self.inode)?
         };
 
         {
@@ -162,16 +233,74 @@ impl VirtualFile for FileHandle {
                 .try_write()
                 .map_err(|_| FsError::Lock)?;
 
-            // Remove the file from the storage.
-            fs.storage.remove(inode_of_file);
+            // The removal is recorded at the path the file has now.
+            let path = fs.path_of(self.inode);
+
+            // Remove the file from the parent directory. The file
+            // itself remains until this handle is dropped.
+            fs.remove_entry(inode_of_parent, position)?;
 
-            // Remove the child from the parent directory.
-            fs.remove_child_from_node(inode_of_parent, position)?;
+            if let Some(path) = path {
+                fs.record(|| Record::RemoveFile(path));
+            }
         }
 
         Ok(())
     }
 
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.readable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "the file (inode `{}) doesn't have the `read` permission",
+                    self.inode
+                ),
+            ));
+        }
+        let offset = offset
+            .try_into()
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+
+        let mut fs =
+            self.filesystem.inner.try_write().map_err(|_| {
+                io::Error::new(io::ErrorKind::Other, "failed to acquire a write lock")
+            })?;
+
+        let inode = fs.storage.get_mut(self.inode);
+        match inode {
+            Some(Node::File { file, metadata, .. }) => {
+                metadata.accessed = time();
+                Ok(file.read_at(offset, buf))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("inode `{}` doesn't match a file", self.inode),
+            )),
+        }
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<usize> {
+        let offset = offset
+            .try_into()
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+
+        self.write_bytes(Some(offset), &[io::IoSlice::new(buf)])
+    }
+
+    fn map(&self) -> Option<FileMapping> {
+        if !self.readable {
+            return None;
+        }
+
+        let fs = self.filesystem.inner.try_read().ok()?;
+
+        match fs.storage.get(self.inode) {
+            Some(Node::File { file, .. }) => Some(file.map()),
+            _ => None,
+        }
+    }
+
     fn bytes_available(&self) -> Result<usize> {
         let fs = self
             .filesystem
@@ -181,7 +310,7 @@ impl VirtualFile for FileHandle {
 
         let inode = fs.storage.get(self.inode);
         match inode {
-            Some(Node::File { file, .. }) => Ok(file.buffer.len() - file.cursor),
+            Some(Node::File { file, .. }) => Ok(file.len.saturating_sub(file.cursor)),
             _ => Err(FsError::NotAFile),
         }
     }
@@ -189,6 +318,14 @@ impl VirtualFile for FileHandle {
     fn get_fd(&self) -> Option<FileDescriptor> {
         Some(FileDescriptor(self.inode))
     }
+
+    fn is_readable(&self) -> bool {
+        self.readable
+    }
+
+    fn is_writable(&self) -> bool {
+        self.writable
+    }
 }
 
 #[cfg(test)]
@@ -273,6 +410,48 @@ mod test_virtual_file {
         );
     }
 
+    #[test]
+    fn test_times_are_updated_on_read_and_write() {
+        use std::io::{self, Read, Seek, Write};
+
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+        let (accessed, modified) = (file.last_accessed(), file.last_modified());
+
+        sleep(Duration::from_millis(10));
+        file.write_all(b"foo").unwrap();
+        assert!(
+            file.last_modified() > modified,
+            "the last modified time is updated on write"
+        );
+        assert_eq!(
+            file.last_accessed(),
+            accessed,
+            "the last accessed time is kept on write"
+        );
+
+        let modified = file.last_modified();
+        sleep(Duration::from_millis(10));
+        file.seek(io::SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut Vec::new()).unwrap();
+        assert!(
+            file.last_accessed() > accessed,
+            "the last accessed time is updated on read"
+        );
+        assert_eq!(
+            file.last_modified(),
+            modified,
+            "the last modified time is kept on read"
+        );
+    }
+
     #[test]
     fn test_size() {
         let fs = FileSystem::default();
@@ -349,8 +528,8 @@ mod test_virtual_file {
 
             assert_eq!(
                 fs_inner.storage.len(),
-                1,
-                "storage no longer has the new file"
+                2,
+                "storage still has the file, which is opened"
             );
             assert!(
                 matches!(
@@ -365,6 +544,14 @@ mod test_virtual_file {
                 "`/` is empty",
             );
         }
+
+        drop(file);
+
+        assert_eq!(
+            fs.inner.read().unwrap().storage.len(),
+            1,
+            "storage no longer has the new file"
+        );
     }
 
     #[test]
@@ -399,9 +586,42 @@ mod test_virtual_file {
             "reading the file descriptor",
         );
     }
+
+    #[test]
+    fn test_capabilities() {
+        let fs = FileSystem::default();
+
+        let file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+
+        assert!(!file.is_readable(), "the file is write-only");
+        assert!(file.is_writable(), "the file is writable");
+        assert!(file.is_seekable(), "the file is seekable");
+
+        let file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to open the file");
+
+        assert!(file.is_readable(), "the file is readable");
+        assert!(!file.is_writable(), "the file is read-only");
+    }
 }
 
 impl Read for FileHandle {
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(
+            level = "trace",
+            skip(self, buf),
+            fields(inode = self.inode, len = buf.len())
+        )
+    )]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if !self.readable {
             return Err(io::Error::new(
@@ -420,7 +640,10 @@ impl Read for FileHandle {
 
         let inode = fs.storage.get_mut(self.inode);
         let file = match inode {
-            Some(Node::File { file, .. }) => file,
+            Some(Node::File { file, metadata, .. }) => {
+                metadata.accessed = time();
+                file
+            }
             _ => {
                 return Err(io::Error::new(
                     io::ErrorKind::NotFound,
@@ -432,6 +655,47 @@ impl Read for FileHandle {
         file.read(buf)
     }
 
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(
+            level = "trace",
+            skip(self, bufs),
+            fields(inode = self.inode, count = bufs.len())
+        )
+    )]
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        if !self.readable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "the file (inode `{}) doesn't have the `read` permission",
+                    self.inode
+                ),
+            ));
+        }
+
+        let mut fs =
+            self.filesystem.inner.try_write().map_err(|_| {
+                io::Error::new(io::ErrorKind::Other, "failed to acquire a write lock")
+            })?;
+
+        let inode = fs.storage.get_mut(self.inode);
+        let file = match inode {
+            Some(Node::File { file, metadata, .. }) => {
+                metadata.accessed = time();
+                file
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("inode `{}` doesn't match a file", self.inode),
+                ))
+            }
+        };
+
+        file.read_vectored(bufs)
+    }
+
     fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
         if !self.readable {
             return Err(io::Error::new(
@@ -450,7 +714,10 @@ impl Read for FileHandle {
 
         let inode = fs.storage.get_mut(self.inode);
         let file = match inode {
-            Some(Node::File { file, .. }) => file,
+            Some(Node::File { file, metadata, .. }) => {
+                metadata.accessed = time();
+                file
+            }
             _ => {
                 return Err(io::Error::new(
                     io::ErrorKind::NotFound,
@@ -499,7 +766,10 @@ impl Read for FileHandle {
 
         let inode = fs.storage.get_mut(self.inode);
         let file = match inode {
-            Some(Node::File { file, .. }) => file,
+            Some(Node::File { file, metadata, .. }) => {
+                metadata.accessed = time();
+                file
+            }
             _ => {
                 return Err(io::Error::new(
                     io::ErrorKind::NotFound,
@@ -553,38 +823,28 @@ impl Seek for FileHandle {
 }
 
 impl Write for FileHandle {
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(
+            level = "trace",
+            skip(self, buf),
+            fields(inode = self.inode, len = buf.len())
+        )
+    )]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        if !self.writable {
-            return Err(io::Error::new(
-                io::ErrorKind::PermissionDenied,
-                format!(
-                    "the file (inode `{}) doesn't have the `write` permission",
-                    self.inode
-                ),
-            ));
-        }
-
-        let mut fs =
-            self.filesystem.inner.try_write().map_err(|_| {
-                io::Error::new(io::ErrorKind::Other, "failed to acquire a write lock")
-            })?;
-
-        let inode = fs.storage.get_mut(self.inode);
-        let (file, metadata) = match inode {
-            Some(Node::File { file, metadata, .. }) => (file, metadata),
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("inode `{}` doesn't match a file", self.inode),
-                ))
-            }
-        };
-
-        let bytes_written = file.write(buf)?;
-
-        metadata.len = file.len().try_into().unwrap();
+        self.write_bytes(None, &[io::IoSlice::new(buf)])
+    }
 
-        Ok(bytes_written)
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(
+            level = "trace",
+            skip(self, bufs),
+            fields(inode = self.inode, count = bufs.len())
+        )
+    )]
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.write_bytes(None, bufs)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -641,15 +901,15 @@ mod test_read_write_seek {
 
         assert!(
             matches!(file.write(b"baz"), Ok(3)),
-            "writing `baz` at the beginning of the file",
+            "overwriting `foo` at the beginning of the file",
         );
-        assert_eq!(file.size(), 9, "checking the size of the file");
+        assert_eq!(file.size(), 6, "checking the size of the file");
 
         assert!(
-            matches!(file.write(b"qux"), Ok(3)),
-            "writing `qux` in the middle of the file",
+            matches!(file.write(b"quxquux"), Ok(7)),
+            "overwriting `bar` and extending the file",
         );
-        assert_eq!(file.size(), 12, "checking the size of the file");
+        assert_eq!(file.size(), 10, "checking the size of the file");
 
         assert!(
             matches!(file.seek(io::SeekFrom::Start(0)), Ok(0)),
@@ -658,26 +918,26 @@ mod test_read_write_seek {
 
         let mut string = String::new();
         assert!(
-            matches!(file.read_to_string(&mut string), Ok(12)),
-            "reading `bazquxfoobar`",
+            matches!(file.read_to_string(&mut string), Ok(10)),
+            "reading `bazquxquux`",
         );
-        assert_eq!(string, "bazquxfoobar");
+        assert_eq!(string, "bazquxquux");
 
         assert!(
-            matches!(file.seek(io::SeekFrom::Current(-6)), Ok(6)),
-            "seeking to 6",
+            matches!(file.seek(io::SeekFrom::Current(-7)), Ok(3)),
+            "seeking to 3",
         );
 
         let mut string = String::new();
         assert!(
-            matches!(file.read_to_string(&mut string), Ok(6)),
-            "reading `foobar`",
+            matches!(file.read_to_string(&mut string), Ok(7)),
+            "reading `quxquux`",
         );
-        assert_eq!(string, "foobar");
+        assert_eq!(string, "quxquux");
 
         assert!(
-            matches!(file.seek(io::SeekFrom::End(0)), Ok(12)),
-            "seeking to 12",
+            matches!(file.seek(io::SeekFrom::End(0)), Ok(10)),
+            "seeking to 10",
         );
 
         let mut string = String::new();
@@ -688,6 +948,270 @@ mod test_read_write_seek {
         assert_eq!(string, "");
     }
 
+    #[test]
+    fn test_positioned_io() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+        let mut other_file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to open the file again");
+
+        assert!(matches!(file.write(b"foobar"), Ok(6)), "writing `foobar`");
+        assert!(
+            matches!(file.seek(io::SeekFrom::Start(1)), Ok(1)),
+            "seeking to 1",
+        );
+
+        assert!(
+            matches!(file.write_at(3, b"BAR!"), Ok(4)),
+            "writing `BAR!` at 3",
+        );
+        assert_eq!(file.size(), 7, "checking the size of the file");
+
+        let mut buffer = [0; 4];
+        assert!(
+            matches!(other_file.read_at(2, &mut buffer), Ok(4)),
+            "reading at 2 with another handle",
+        );
+        assert_eq!(&buffer, b"oBAR");
+        assert!(
+            matches!(other_file.read_at(6, &mut buffer), Ok(1)),
+            "reading at 6 with another handle",
+        );
+        assert!(
+            matches!(other_file.read_at(10, &mut buffer), Ok(0)),
+            "reading after the end",
+        );
+
+        assert!(
+            matches!(file.stream_position(), Ok(1)),
+            "the cursor didn't move",
+        );
+    }
+
+    #[test]
+    fn test_vectored_io() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+
+        assert!(
+            matches!(
+                file.write_vectored(&[
+                    io::IoSlice::new(b"foo"),
+                    io::IoSlice::new(b""),
+                    io::IoSlice::new(b"bar"),
+                ]),
+                Ok(6)
+            ),
+            "writing `foo` and `bar`",
+        );
+        assert_eq!(file.size(), 6, "checking the size of the file");
+        assert!(
+            matches!(file.seek(io::SeekFrom::Start(1)), Ok(1)),
+            "seeking to 1",
+        );
+
+        let (mut first, mut second) = ([0; 2], [0; 4]);
+        assert!(
+            matches!(
+                file.read_vectored(&mut [
+                    io::IoSliceMut::new(&mut first),
+                    io::IoSliceMut::new(&mut second),
+                ]),
+                Ok(5)
+            ),
+            "reading until the end of the file",
+        );
+        assert_eq!(&first, b"oo");
+        assert_eq!(&second[..3], b"bar");
+        assert!(
+            matches!(file.stream_position(), Ok(6)),
+            "the cursor is at the end",
+        );
+    }
+
+    #[test]
+    fn test_mapping() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+        let write_only = fs
+            .new_open_options()
+            .write(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to open the file again");
+
+        assert!(matches!(file.write(b"foobar"), Ok(6)), "writing `foobar`");
+
+        let mapping = file.map().expect("the file can be mapped");
+        assert_eq!(mapping.len(), 6);
+        assert_eq!(mapping.get(0..6), &b"foobar"[..]);
+
+        assert!(matches!(file.write_at(0, b"F"), Ok(1)), "writing `F`");
+        assert_eq!(
+            mapping.get(0..6),
+            &b"foobar"[..],
+            "the mapping is a snapshot"
+        );
+        assert_eq!(file.map().unwrap().get(0..6), &b"Foobar"[..]);
+
+        assert!(
+            write_only.map().is_none(),
+            "a write-only file can't be mapped"
+        );
+    }
+
+    #[test]
+    fn test_sparse_file() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+
+        assert!(matches!(file.write(b"foo"), Ok(3)), "writing `foo`");
+        assert!(
+            matches!(file.set_len(1 << 30), Ok(())),
+            "extending the file to 1GiB",
+        );
+        assert_eq!(file.size(), 1 << 30, "checking the size of the file");
+        {
+            let fs = fs.inner.read().unwrap();
+            match fs.storage.get(ROOT_INODE + 1) {
+                Some(Node::File { file, .. }) => {
+                    assert_eq!(file.allocated_len(), 3, "the extension is a hole")
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        assert!(
+            matches!(file.seek(io::SeekFrom::Start(8)), Ok(8)),
+            "seeking to 8",
+        );
+        assert!(
+            matches!(file.write(b"bar"), Ok(3)),
+            "writing `bar` in the hole"
+        );
+
+        assert!(
+            matches!(file.seek(io::SeekFrom::Start(0)), Ok(0)),
+            "seeking to 0",
+        );
+        let mut buffer = [0xff; 12];
+        assert!(
+            matches!(file.read_exact(&mut buffer), Ok(())),
+            "reading the beginning of the file",
+        );
+        assert_eq!(&buffer, b"foo\0\0\0\0\0bar\0", "the hole reads as zeros");
+
+        assert!(matches!(file.set_len(4), Ok(())), "truncating the file");
+        assert!(
+            matches!(file.seek(io::SeekFrom::Start(6)), Ok(6)),
+            "seeking beyond the end",
+        );
+        assert!(matches!(file.write(b"!"), Ok(1)), "writing after a hole");
+        assert_eq!(file.size(), 7, "checking the size of the file");
+
+        assert!(
+            matches!(file.seek(io::SeekFrom::Start(0)), Ok(0)),
+            "seeking to 0",
+        );
+        let mut contents = Vec::new();
+        assert!(
+            matches!(file.read_to_end(&mut contents), Ok(7)),
+            "reading the file",
+        );
+        assert_eq!(contents, b"foo\0\0\0!");
+    }
+
+    #[test]
+    fn test_reading_and_writing_an_unlinked_file() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+        let mut other_file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to open the file");
+
+        assert!(matches!(file.write(b"foo"), Ok(3)), "writing `foo`");
+        assert_eq!(
+            fs.remove_file(path!("/foo.txt")),
+            Ok(()),
+            "unlinking the file"
+        );
+        assert!(
+            fs.metadata(path!("/foo.txt")).is_err(),
+            "the file no longer has a name",
+        );
+
+        assert!(
+            matches!(file.write(b"bar"), Ok(3)),
+            "writing `bar` after the unlink",
+        );
+        assert_eq!(file.size(), 6, "checking the size of the file");
+
+        drop(file);
+
+        assert!(
+            matches!(other_file.seek(io::SeekFrom::Start(0)), Ok(0)),
+            "seeking to 0 with the remaining handle",
+        );
+        let mut string = String::new();
+        assert!(
+            matches!(other_file.read_to_string(&mut string), Ok(6)),
+            "reading `foobar` with the remaining handle",
+        );
+        assert_eq!(string, "foobar");
+        assert_eq!(
+            fs.inner.read().unwrap().storage.len(),
+            2,
+            "the file is kept while a handle remains",
+        );
+
+        drop(other_file);
+
+        assert_eq!(
+            fs.inner.read().unwrap().storage.len(),
+            1,
+            "the file is removed with its last handle",
+        );
+    }
+
     #[test]
     fn test_reading() {
         let fs = FileSystem::default();
@@ -850,84 +1374,209 @@ impl fmt::Debug for FileHandle {
 
 /// The real file! It is simply a buffer of bytes with a cursor that
 /// represents a read/write position in the buffer.
+///
+/// The buffer can be shared between several files, see
+/// [`File::clone_contents`]. It is copied on the first write.
+///
+/// The file can be longer than its buffer: the bytes after the end of
+/// the buffer form a hole, which reads as zeros but is only allocated
+/// once written to. Extending a file, with [`File::set_len`] or by
+/// writing after its end, doesn't materialize the zeros in between.
 #[derive(Debug)]
 pub(super) struct File {
-    buffer: Vec<u8>,
+    buffer: Arc<Vec<u8>>,
+    len: usize,
     cursor: usize,
 }
 
 impl File {
     pub(super) fn new() -> Self {
         Self {
-            buffer: Vec::new(),
+            buffer: Arc::new(Vec::new()),
+            len: 0,
+            cursor: 0,
+        }
+    }
+
+    /// Creates a new file holding `contents`.
+    pub(super) fn with_contents(contents: Vec<u8>) -> Self {
+        Self {
+            len: contents.len(),
+            buffer: Arc::new(contents),
+            cursor: 0,
+        }
+    }
+
+    /// The contents of the file, with the trailing hole filled with
+    /// zeros.
+    pub(super) fn contents(&self) -> Cow<'_, [u8]> {
+        if self.buffer.len() == self.len {
+            Cow::Borrowed(&self.buffer)
+        } else {
+            let mut contents = self.buffer.as_ref().clone();
+            contents.resize(self.len, 0);
+
+            Cow::Owned(contents)
+        }
+    }
+
+    /// Maps the contents of the file, sharing them until the file is
+    /// written to.
+    pub(super) fn map(&self) -> FileMapping {
+        FileMapping::new(self.buffer.clone(), self.len)
+    }
+
+    /// Creates a new file sharing the contents of this one, until one
+    /// of them is modified.
+    pub(super) fn clone_contents(&self) -> Self {
+        Self {
+            buffer: Arc::clone(&self.buffer),
+            len: self.len,
             cursor: 0,
         }
     }
 
+    /// Whether the contents are shared with another file.
+    #[cfg(test)]
+    pub(super) fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.buffer) > 1
+    }
+
+    /// The number of bytes actually allocated for the contents.
+    #[cfg(test)]
+    pub(super) fn allocated_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Gets the buffer for writing, copying it first if it is shared.
+    fn buffer_mut(&mut self) -> &mut Vec<u8> {
+        Arc::make_mut(&mut self.buffer)
+    }
+
+    /// Releases the memory allocated beyond the contents, returning
+    /// its size. A shared buffer is left as is.
+    pub(super) fn shrink_to_fit(&mut self) -> usize {
+        match Arc::get_mut(&mut self.buffer) {
+            Some(buffer) => {
+                let capacity = buffer.capacity();
+                buffer.shrink_to_fit();
+                capacity - buffer.capacity()
+            }
+            None => 0,
+        }
+    }
+
     pub(super) fn truncate(&mut self) {
-        self.buffer.clear();
+        // Don't copy a shared buffer only to clear it.
+        match Arc::get_mut(&mut self.buffer) {
+            Some(buffer) => buffer.clear(),
+            None => self.buffer = Arc::new(Vec::new()),
+        }
+        self.len = 0;
         self.cursor = 0;
     }
 
+    /// Truncates or extends the file to `new_size` bytes. The
+    /// extension is a hole.
+    pub(super) fn set_len(&mut self, new_size: usize) {
+        if new_size < self.buffer.len() {
+            self.buffer_mut().truncate(new_size);
+        }
+        self.len = new_size;
+    }
+
     pub(super) fn len(&self) -> usize {
-        self.buffer.len()
+        self.len
+    }
+
+    /// The cursor, where the next read or write happens.
+    pub(super) fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Copies the bytes at `offset` to `buf`, reading the hole as
+    /// zeros.
+    pub(super) fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let max_to_read = cmp::min(self.len.saturating_sub(offset), buf.len());
+        let buf = &mut buf[..max_to_read];
+
+        let data_to_copy = self
+            .buffer
+            .get(offset..)
+            .map(|data| &data[..cmp::min(data.len(), max_to_read)])
+            .unwrap_or_default();
+        let (data, hole) = buf.split_at_mut(data_to_copy.len());
+        data.copy_from_slice(data_to_copy);
+        hole.fill(0);
+
+        max_to_read
+    }
+
+    /// Copies `buf` at `offset`, overwriting the bytes there and
+    /// extending the file if needed.
+    pub(super) fn write_at(&mut self, offset: usize, buf: &[u8]) {
+        let end = offset + buf.len();
+        let buffer = self.buffer_mut();
+
+        // The bytes of the hole before `offset` are materialized
+        // first, then the bytes at `offset` are overwritten.
+        if buffer.len() < offset {
+            buffer.resize(offset, 0);
+        }
+        let overwritten = cmp::min(buffer.len(), end) - offset;
+        buffer[offset..][..overwritten].copy_from_slice(&buf[..overwritten]);
+        buffer.extend_from_slice(&buf[overwritten..]);
+
+        self.len = cmp::max(self.len, end);
+    }
+
+    /// Reads at the cursor, and advances it.
+    fn read_at_cursor(&mut self, buf: &mut [u8]) -> usize {
+        let read = self.read_at(self.cursor, buf);
+        self.cursor += read;
+
+        read
     }
 }
 
 impl Read for File {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let max_to_read = cmp::min(self.buffer.len() - self.cursor, buf.len());
-        let data_to_copy = &self.buffer[self.cursor..][..max_to_read];
-
-        // SAFETY: `buf[..max_to_read]` and `data_to_copy` have the same size, due to
-        // how `max_to_read` is computed.
-        buf[..max_to_read].copy_from_slice(data_to_copy);
+        Ok(self.read_at_cursor(buf))
+    }
 
-        self.cursor += max_to_read;
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        let mut read = 0;
 
-        Ok(max_to_read)
-    }
+        // Stop at the first buffer that isn't filled, i.e. at the
+        // end of the file.
+        for buf in bufs {
+            let read_in_buf = self.read_at_cursor(buf);
+            read += read_in_buf;
 
-    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
-        let data_to_copy = &self.buffer[self.cursor..];
-        let max_to_read = data_to_copy.len();
-
-        // `buf` is too small to contain the data. Let's resize it.
-        if max_to_read > buf.len() {
-            // Let's resize the capacity if needed.
-            if max_to_read > buf.capacity() {
-                buf.reserve_exact(max_to_read - buf.capacity());
+            if read_in_buf < buf.len() {
+                break;
             }
-
-            // SAFETY: The space is reserved, and it's going to be
-            // filled with `copy_from_slice` below.
-            unsafe { buf.set_len(max_to_read) }
         }
 
-        // SAFETY: `buf` and `data_to_copy` have the same size, see
-        // above.
-        buf.copy_from_slice(data_to_copy);
+        Ok(read)
+    }
 
-        self.cursor += max_to_read;
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let start = buf.len();
+        buf.resize(start + self.len.saturating_sub(self.cursor), 0);
 
-        Ok(max_to_read)
+        Ok(self.read_at_cursor(&mut buf[start..]))
     }
 
     fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
-        if buf.len() > (self.buffer.len() - self.cursor) {
+        if buf.len() > self.len.saturating_sub(self.cursor) {
             return Err(io::Error::new(
                 io::ErrorKind::UnexpectedEof,
                 "not enough data available in file",
             ));
         }
 
-        let max_to_read = cmp::min(buf.len(), self.buffer.len() - self.cursor);
-        let data_to_copy = &self.buffer[self.cursor..][..max_to_read];
-
-        // SAFETY: `buf` and `data_to_copy` have the same size.
-        buf.copy_from_slice(data_to_copy);
-
-        self.cursor += data_to_copy.len();
+        self.read_at_cursor(buf);
 
         Ok(())
     }
@@ -942,9 +1591,9 @@ impl Seek for File {
             // Calculate from the beginning, so `0 + offset`.
             io::SeekFrom::Start(offset) => offset.try_into().map_err(to_err)?,
 
-            // Calculate from the end, so `buffer.len() + offset`.
+            // Calculate from the end, so `len + offset`.
             io::SeekFrom::End(offset) => {
-                TryInto::<i64>::try_into(self.buffer.len()).map_err(to_err)? + offset
+                TryInto::<i64>::try_into(self.len).map_err(to_err)? + offset
             }
 
             // Calculate from the current cursor, so `cursor + offset`.
@@ -961,9 +1610,9 @@ impl Seek for File {
             ));
         }
 
-        // In this implementation, it's an error to seek beyond the
-        // end of the buffer.
-        self.cursor = cmp::min(self.buffer.len(), next_cursor.try_into().map_err(to_err)?);
+        // Seeking beyond the end is allowed, writing there leaves a
+        // hole before the written bytes.
+        self.cursor = next_cursor.try_into().map_err(to_err)?;
 
         Ok(self.cursor.try_into().map_err(to_err)?)
     }
@@ -971,36 +1620,20 @@ impl Seek for File {
 
 impl Write for File {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self.cursor {
-            // The cursor is at the end of the buffer: happy path!
-            position if position == self.buffer.len() => {
-                self.buffer.extend_from_slice(buf);
-            }
-
-            // The cursor is at the beginning of the buffer (and the
-            // buffer is not empty, otherwise it would have been
-            // caught by the previous arm): almost a happy path!
-            0 => {
-                let mut new_buffer = Vec::with_capacity(self.buffer.len() + buf.len());
-                new_buffer.extend_from_slice(buf);
-                new_buffer.append(&mut self.buffer);
-
-                self.buffer = new_buffer;
-            }
+        self.write_at(self.cursor, buf);
+        self.cursor += buf.len();
 
-            // The cursor is somewhere in the buffer: not the happy path.
-            position => {
-                self.buffer.reserve_exact(buf.len());
+        Ok(buf.len())
+    }
 
-                let mut remainder = self.buffer.split_off(position);
-                self.buffer.extend_from_slice(buf);
-                self.buffer.append(&mut remainder);
-            }
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let start = self.cursor;
+        for buf in bufs {
+            self.write_at(self.cursor, buf);
+            self.cursor += buf.len();
         }
 
-        self.cursor += buf.len();
-
-        Ok(buf.len())
+        Ok(self.cursor - start)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -1,3 +1,5 @@
+use super::filesystem::FollowSymlink;
+use super::journal::Record;
 use super::*;
 use crate::{FileType, FsError, Metadata, OpenOptionsConfig, Result, VirtualFile};
 use std::io::{self, Seek};
@@ -10,6 +12,10 @@ pub struct FileOpener {
 }
 
 impl crate::FileOpener for FileOpener {
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     fn open(
         &mut self,
         path: &Path,
@@ -39,7 +45,13 @@ impl crate::FileOpener for FileOpener {
             write = false;
         }
 
-        let (inode_of_parent, maybe_inode_of_file, name_of_file) = {
+        // A path ending with a slash designates a directory, which
+        // can't be opened as a file.
+        if crate::is_directory_path(path) {
+            return Err(FsError::BaseNotDirectory);
+        }
+
+        let (path, inode_of_parent, maybe_inode_of_file, name_of_file) = {
             // Read lock.
             let fs = self
                 .filesystem
@@ -47,6 +59,9 @@ impl crate::FileOpener for FileOpener {
                 .try_read()
                 .map_err(|_| FsError::Lock)?;
 
+            // Follow the symlinks, the file is the one they point to.
+            let (path, _) = fs.resolve(path, FollowSymlink::Yes)?;
+
             // Check the path has a parent.
             let parent_of_path = path.parent().ok_or(FsError::BaseNotDirectory)?;
 
@@ -59,12 +74,13 @@ impl crate::FileOpener for FileOpener {
             // Find the parent inode.
             let inode_of_parent = fs.inode_of_parent(parent_of_path)?;
 
-            // Find the inode of the file if it exists.
+            // Find the inode of the file if it exists, following
+            // the hard links.
             let maybe_inode_of_file = fs
                 .as_parent_get_position_and_inode_of_file(inode_of_parent, &name_of_file)?
-                .map(|(_nth, inode)| inode);
+                .map(|(_nth, inode)| fs.follow_hard_link(inode));
 
-            (inode_of_parent, maybe_inode_of_file, name_of_file)
+            (path, inode_of_parent, maybe_inode_of_file, name_of_file)
         };
 
         let inode_of_file = match maybe_inode_of_file {
@@ -82,8 +98,10 @@ impl crate::FileOpener for FileOpener {
                     .map_err(|_| FsError::Lock)?;
 
                 let inode = fs.storage.get_mut(inode_of_file);
-                match inode {
+                let old_len = match inode {
                     Some(Node::File { metadata, file, .. }) => {
+                        let old_len = metadata.len;
+
                         // Update the accessed time.
                         metadata.accessed = time();
 
@@ -101,10 +119,18 @@ impl crate::FileOpener for FileOpener {
                         else {
                             file.seek(io::SeekFrom::Start(0))?;
                         }
+
+                        old_len
                     }
 
                     _ => return Err(FsError::NotAFile),
+                };
+
+                if truncate {
+                    fs.resized(old_len, 0);
+                    fs.mark_dirty(inode_of_file);
                 }
+                fs.open_handle(inode_of_file);
 
                 inode_of_file
             }
@@ -120,10 +146,13 @@ impl crate::FileOpener for FileOpener {
                     .try_write()
                     .map_err(|_| FsError::Lock)?;
 
+                fs.check_new_node(&path)?;
+
                 let file = File::new();
 
                 // Creating the file in the storage.
                 let inode_of_file = fs.storage.vacant_entry().key();
+                trace_node!("insert", inode_of_file);
                 let real_inode_of_file = fs.storage.insert(Node::File {
                     inode: inode_of_file,
                     name: name_of_file,
@@ -140,8 +169,12 @@ impl crate::FileOpener for FileOpener {
                             created: time,
                             modified: time,
                             len: 0,
+                            nlink: 1,
+                            mode: FILE_MODE,
+                            ..Default::default()
                         }
                     },
+                    open_handles: 1,
                 });
 
                 assert_eq!(
@@ -152,6 +185,8 @@ impl crate::FileOpener for FileOpener {
                 // Adding the new directory to its parent.
                 fs.add_child_to_node(inode_of_parent, inode_of_file)?;
 
+                fs.record(|| Record::CreateFile(path));
+
                 inode_of_file
             }
 
@@ -245,6 +280,14 @@ mod test_file_opener {
             "creating a file in a directory that doesn't exist",
         );
 
+        assert!(
+            matches!(
+                fs.new_open_options().read(true).open(path!("/foo.txt/")),
+                Err(FsError::BaseNotDirectory),
+            ),
+            "opening a file with a trailing slash",
+        );
+
         assert_eq!(fs.remove_file(path!("/foo.txt")), Ok(()), "removing a file");
 
         assert!(
@@ -1,7 +1,13 @@
 //! This module contains the [`FileSystem`] type itself.
 
+use super::journal::{Journal, Record};
+use super::limits::Limits;
 use super::*;
-use crate::{DirEntry, FileType, FsError, Metadata, OpenOptions, ReadDir, Result};
+use crate::watch::Watchers;
+use crate::{
+    DirEntry, FileType, FsError, Metadata, OpenOptions, ParentOfRoot, PathResolver, ReadDir,
+    Result, WatchEvent, WatchHandle, MAX_SYMLINKS,
+};
 use slab::Slab;
 use std::convert::identity;
 use std::ffi::OsString;
@@ -20,6 +26,10 @@ pub struct FileSystem {
 }
 
 impl crate::FileSystem for FileSystem {
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "trace", skip(self))
+    )]
     fn read_dir(&self, path: &Path) -> Result<ReadDir> {
         // Read lock.
         let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
@@ -32,11 +42,18 @@ impl crate::FileSystem for FileSystem {
         let children = match inode {
             Some(Node::Directory { children, .. }) => children
                 .iter()
-                .filter_map(|inode| fs.storage.get(*inode))
-                .map(|node| DirEntry {
+                .filter_map(|inode| {
+                    // The metadata of a hard link are the ones of its
+                    // target.
+                    let entry = fs.storage.get(*inode)?;
+                    let node = fs.storage.get(fs.follow_hard_link(*inode))?;
+
+                    Some((entry, node))
+                })
+                .map(|(entry, node)| DirEntry {
                     path: {
                         let mut entry_path = path.to_path_buf();
-                        entry_path.push(node.name());
+                        entry_path.push(entry.name());
 
                         entry_path
                     },
@@ -50,6 +67,10 @@ impl crate::FileSystem for FileSystem {
         Ok(ReadDir::new(children))
     }
 
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     fn create_dir(&self, path: &Path) -> Result<()> {
         let (inode_of_parent, name_of_directory) = {
             // Read lock.
@@ -71,6 +92,8 @@ impl crate::FileSystem for FileSystem {
             // Find the parent inode.
             let inode_of_parent = fs.inode_of_parent(parent_of_path)?;
 
+            fs.check_new_node(&path)?;
+
             (inode_of_parent, name_of_directory)
         };
 
@@ -80,6 +103,7 @@ impl crate::FileSystem for FileSystem {
 
             // Creating the directory in the storage.
             let inode_of_directory = fs.storage.vacant_entry().key();
+            trace_node!("insert", inode_of_directory);
             let real_inode_of_directory = fs.storage.insert(Node::Directory {
                 inode: inode_of_directory,
                 name: name_of_directory,
@@ -96,6 +120,9 @@ impl crate::FileSystem for FileSystem {
                         created: time,
                         modified: time,
                         len: 0,
+                        nlink: 1,
+                        mode: DIRECTORY_MODE,
+                        ..Default::default()
                     }
                 },
             });
@@ -107,11 +134,17 @@ impl crate::FileSystem for FileSystem {
 
             // Adding the new directory to its parent.
             fs.add_child_to_node(inode_of_parent, inode_of_directory)?;
+
+            fs.record(|| Record::CreateDir(path.to_path_buf()));
         }
 
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     fn remove_dir(&self, path: &Path) -> Result<()> {
         let (inode_of_parent, position, inode_of_directory) = {
             // Read lock.
@@ -148,17 +181,27 @@ impl crate::FileSystem for FileSystem {
             let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
 
             // Remove the directory from the storage.
+            trace_node!("remove", inode_of_directory);
             fs.storage.remove(inode_of_directory);
 
             // Remove the child from the parent directory.
             fs.remove_child_from_node(inode_of_parent, position)?;
+
+            fs.record(|| Record::RemoveDir(path.to_path_buf()));
         }
 
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     fn rename(&self, from: &Path, to: &Path) -> Result<()> {
-        let ((position_of_from, inode, inode_of_from_parent), (inode_of_to_parent, name_of_to)) = {
+        let (
+            (position_of_from, inode, inode_of_from_parent),
+            (inode_of_to_parent, name_of_to, replaced),
+        ) = {
             // Read lock.
             let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
 
@@ -186,9 +229,32 @@ impl crate::FileSystem for FileSystem {
                 .as_parent_get_position_and_inode(inode_of_from_parent, &name_of_from)?
                 .ok_or(FsError::NotAFile)?;
 
+            fs.check_move(&to, inode)?;
+
+            // The entry at `to`, which is replaced.
+            let replaced = fs.as_parent_get_position_and_inode(inode_of_to_parent, &name_of_to)?;
+            if let Some((_, inode_of_to)) = replaced {
+                if fs.follow_hard_link(inode_of_to) == fs.follow_hard_link(inode) {
+                    // Both names refer to the same node, nothing to do.
+                    return Ok(());
+                }
+                match (fs.storage.get(inode), fs.storage.get(inode_of_to)) {
+                    (Some(Node::Directory { .. }), Some(Node::Directory { children, .. })) => {
+                        if !children.is_empty() {
+                            return Err(FsError::DirectoryNotEmpty);
+                        }
+                    }
+                    (Some(Node::Directory { .. }), Some(_)) => {
+                        return Err(FsError::BaseNotDirectory)
+                    }
+                    (Some(_), Some(Node::Directory { .. })) => return Err(FsError::NotAFile),
+                    _ => {}
+                }
+            }
+
             (
                 (position_of_from, inode, inode_of_from_parent),
-                (inode_of_to_parent, name_of_to),
+                (inode_of_to_parent, name_of_to, replaced),
             )
         };
 
@@ -196,6 +262,16 @@ impl crate::FileSystem for FileSystem {
             // Write lock.
             let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
 
+            // Remove the replaced entry. A file stays valid for the
+            // handles opened on it, until the last one is dropped.
+            let mut position_of_from = position_of_from;
+            if let Some((position_of_to, _)) = replaced {
+                fs.remove_entry(inode_of_to_parent, position_of_to)?;
+                if inode_of_to_parent == inode_of_from_parent && position_of_to < position_of_from {
+                    position_of_from -= 1;
+                }
+            }
+
             // Update the file name, and update the modified time.
             fs.update_node_name(inode, name_of_to)?;
 
@@ -220,11 +296,20 @@ impl crate::FileSystem for FileSystem {
                     _ => return Err(FsError::UnknownError),
                 }
             }
+
+            fs.record(|| Record::Rename {
+                from: from.to_path_buf(),
+                to: to.to_path_buf(),
+            });
         }
 
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "trace", skip(self))
+    )]
     fn metadata(&self, path: &Path) -> Result<Metadata> {
         // Read lock.
         let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
@@ -237,8 +322,209 @@ impl crate::FileSystem for FileSystem {
             .clone())
     }
 
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "trace", skip(self))
+    )]
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        // Read lock.
+        let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+        Ok(fs
+            .storage
+            .get(fs.inode_of_symlink(path)?)
+            .ok_or(FsError::UnknownError)?
+            .metadata()
+            .clone())
+    }
+
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        let (inode_of_parent, name_of_link) = {
+            // Read lock.
+            let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+            // Canonicalize the path without checking the path exists,
+            // because it's about to be created.
+            let link = fs.canonicalize_without_inode(link)?;
+
+            // Check the path has a parent.
+            let parent_of_link = link.parent().ok_or(FsError::BaseNotDirectory)?;
+
+            // Check the link name.
+            let name_of_link = link
+                .file_name()
+                .ok_or(FsError::InvalidInput)?
+                .to_os_string();
+
+            // Find the parent inode.
+            let inode_of_parent = fs.inode_of_parent(parent_of_link)?;
+
+            // Check the link doesn't already exist.
+            if fs
+                .as_parent_get_position_and_inode(inode_of_parent, &name_of_link)?
+                .is_some()
+            {
+                return Err(FsError::AlreadyExists);
+            }
+
+            fs.check_new_node(&link)?;
+
+            (inode_of_parent, name_of_link)
+        };
+
+        {
+            // Write lock.
+            let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+            // Creating the symlink in the storage.
+            let inode_of_link = fs.storage.vacant_entry().key();
+            trace_node!("insert", inode_of_link);
+            let real_inode_of_link = fs.storage.insert(Node::Symlink {
+                inode: inode_of_link,
+                name: name_of_link,
+                target: original.to_path_buf(),
+                metadata: {
+                    let time = time();
+
+                    Metadata {
+                        ft: FileType {
+                            symlink: true,
+                            ..Default::default()
+                        },
+                        accessed: time,
+                        created: time,
+                        modified: time,
+                        len: original.as_os_str().len() as u64,
+                        nlink: 1,
+                        mode: SYMLINK_MODE,
+                        ..Default::default()
+                    }
+                },
+            });
+
+            assert_eq!(
+                inode_of_link, real_inode_of_link,
+                "new symlink inode should have been correctly calculated",
+            );
+
+            // Adding the new symlink to its parent.
+            fs.add_child_to_node(inode_of_parent, inode_of_link)?;
+
+            fs.record(|| Record::Symlink {
+                original: original.to_path_buf(),
+                link: link.to_path_buf(),
+            });
+        }
+
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "trace", skip(self))
+    )]
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        // Read lock.
+        let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+        match fs.storage.get(fs.inode_of_symlink(path)?) {
+            Some(Node::Symlink { target, .. }) => Ok(target.clone()),
+            _ => Err(FsError::InvalidInput),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    fn chmod(&self, path: &Path, mode: u32) -> Result<()> {
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        let inode = fs.inode_of(path)?;
+        let node = fs.storage.get_mut(inode).ok_or(FsError::UnknownError)?;
+        node.metadata_mut().mode = mode & 0o7777;
+
+        fs.record(|| Record::Chmod {
+            path: path.to_path_buf(),
+            mode,
+        });
+
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    fn set_times(&self, path: &Path, accessed: Option<u64>, modified: Option<u64>) -> Result<()> {
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        let inode = fs.inode_of(path)?;
+        let metadata = fs
+            .storage
+            .get_mut(inode)
+            .ok_or(FsError::UnknownError)?
+            .metadata_mut();
+
+        if let Some(accessed) = accessed {
+            metadata.accessed = accessed;
+        }
+        if let Some(modified) = modified {
+            metadata.modified = modified;
+        }
+
+        fs.record(|| Record::SetTimes {
+            path: path.to_path_buf(),
+            accessed,
+            modified,
+        });
+
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        let inode = fs.inode_of(path)?;
+        let metadata = fs
+            .storage
+            .get_mut(inode)
+            .ok_or(FsError::UnknownError)?
+            .metadata_mut();
+
+        if let Some(uid) = uid {
+            metadata.uid = uid;
+        }
+        if let Some(gid) = gid {
+            metadata.gid = gid;
+        }
+
+        fs.record(|| Record::Chown {
+            path: path.to_path_buf(),
+            uid,
+            gid,
+        });
+
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
     fn remove_file(&self, path: &Path) -> Result<()> {
-        let (inode_of_parent, position, inode_of_file) = {
+        let (inode_of_parent, position) = {
             // Read lock.
             let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
 
@@ -262,7 +548,7 @@ impl crate::FileSystem for FileSystem {
                 fs.as_parent_get_position_and_inode_of_file(inode_of_parent, &name_of_file)?;
 
             match maybe_position_and_inode_of_file {
-                Some((position, inode_of_file)) => (inode_of_parent, position, inode_of_file),
+                Some((position, _inode_of_file)) => (inode_of_parent, position),
                 None => return Err(FsError::NotAFile),
             }
         };
@@ -271,16 +557,252 @@ impl crate::FileSystem for FileSystem {
             // Write lock.
             let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
 
-            // Remove the file from the storage.
-            fs.storage.remove(inode_of_file);
+            // Remove the entry from the parent directory, and the
+            // file from the storage if it was its last name.
+            fs.remove_entry(inode_of_parent, position)?;
 
-            // Remove the child from the parent directory.
-            fs.remove_child_from_node(inode_of_parent, position)?;
+            fs.record(|| Record::RemoveFile(path.to_path_buf()));
+        }
+
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    fn link(&self, original: &Path, link: &Path) -> Result<()> {
+        let (inode_of_original, inode_of_parent, name_of_link) = {
+            // Read lock.
+            let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+            // Canonicalize the path of the original, which must be
+            // a file.
+            let (_, inode_of_original) = fs.canonicalize(original)?;
+
+            match fs.storage.get(inode_of_original) {
+                Some(Node::File { .. }) => {}
+                _ => return Err(FsError::NotAFile),
+            }
+
+            // Canonicalize the path without checking the path exists,
+            // because it's about to be created.
+            let link = fs.canonicalize_without_inode(link)?;
+
+            // Check the path has a parent.
+            let parent_of_link = link.parent().ok_or(FsError::BaseNotDirectory)?;
+
+            // Check the link name.
+            let name_of_link = link
+                .file_name()
+                .ok_or(FsError::InvalidInput)?
+                .to_os_string();
+
+            // Find the parent inode.
+            let inode_of_parent = fs.inode_of_parent(parent_of_link)?;
+
+            // Check the link doesn't already exist.
+            if fs
+                .as_parent_get_position_and_inode(inode_of_parent, &name_of_link)?
+                .is_some()
+            {
+                return Err(FsError::AlreadyExists);
+            }
+
+            fs.check_new_node(&link)?;
+
+            (inode_of_original, inode_of_parent, name_of_link)
+        };
+
+        {
+            // Write lock.
+            let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+            // Creating the hard link in the storage.
+            let inode_of_link = fs.storage.vacant_entry().key();
+            trace_node!("insert", inode_of_link);
+            let real_inode_of_link = fs.storage.insert(Node::HardLink {
+                inode: inode_of_link,
+                name: name_of_link,
+                target: inode_of_original,
+            });
+
+            assert_eq!(
+                inode_of_link, real_inode_of_link,
+                "new hard link inode should have been correctly calculated",
+            );
+
+            // Adding the new hard link to its parent.
+            fs.add_child_to_node(inode_of_parent, inode_of_link)?;
+
+            // The file has one more name.
+            fs.storage
+                .get_mut(inode_of_original)
+                .ok_or(FsError::UnknownError)?
+                .metadata_mut()
+                .nlink += 1;
+
+            fs.record(|| Record::Link {
+                original: original.to_path_buf(),
+                link: link.to_path_buf(),
+            });
+        }
+
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    fn clone_file(&self, from: &Path, to: &Path) -> Result<()> {
+        let (inode_of_from, to, inode_of_to_parent, maybe_inode_of_to, name_of_to) = {
+            // Read lock.
+            let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+            // Canonicalize the paths.
+            let (_, inode_of_from) = fs.canonicalize(from)?;
+            let to = fs.canonicalize_without_inode(to)?;
+
+            // Check the source is a file.
+            match fs.storage.get(inode_of_from) {
+                Some(Node::File { .. }) => {}
+                _ => return Err(FsError::NotAFile),
+            }
+
+            // Check the destination path has a parent.
+            let parent_of_to = to.parent().ok_or(FsError::BaseNotDirectory)?;
+
+            // Check the destination name.
+            let name_of_to = to.file_name().ok_or(FsError::InvalidInput)?.to_os_string();
+
+            // Find the parent inode.
+            let inode_of_to_parent = fs.inode_of_parent(parent_of_to)?;
+
+            // Find the inode of the destination if it exists, it must
+            // be a file.
+            let maybe_inode_of_to = fs
+                .as_parent_get_position_and_inode(inode_of_to_parent, &name_of_to)?
+                .map(|(_nth, inode)| fs.follow_hard_link(inode));
+
+            if let Some(inode_of_to) = maybe_inode_of_to {
+                match fs.storage.get(inode_of_to) {
+                    Some(Node::File { .. }) => {}
+                    _ => return Err(FsError::NotAFile),
+                }
+            }
+
+            (
+                inode_of_from,
+                to,
+                inode_of_to_parent,
+                maybe_inode_of_to,
+                name_of_to,
+            )
+        };
+
+        {
+            // Write lock.
+            let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+            // Share the contents of the source.
+            let (file, len) = match fs.storage.get(inode_of_from) {
+                Some(Node::File { file, metadata, .. }) => (file.clone_contents(), metadata.len),
+                _ => return Err(FsError::NotAFile),
+            };
+
+            // Check the file system has room for the contents.
+            let old_len =
+                match maybe_inode_of_to.and_then(|inode_of_to| fs.storage.get(inode_of_to)) {
+                    Some(Node::File { metadata, .. }) => metadata.len,
+                    _ => {
+                        fs.check_new_node(&to)?;
+
+                        0
+                    }
+                };
+            fs.check_resize(old_len, len)?;
+
+            let inode_of_to = match maybe_inode_of_to {
+                // The destination already exists: replace its contents.
+                Some(inode_of_to) => match fs.storage.get_mut(inode_of_to) {
+                    Some(Node::File {
+                        file: file_of_to,
+                        metadata,
+                        ..
+                    }) => {
+                        *file_of_to = file;
+                        metadata.len = len;
+                        metadata.modified = time();
+
+                        inode_of_to
+                    }
+                    _ => return Err(FsError::NotAFile),
+                },
+
+                // Otherwise, create it.
+                None => {
+                    let inode_of_to = fs.storage.vacant_entry().key();
+                    trace_node!("insert", inode_of_to);
+                    let real_inode_of_to = fs.storage.insert(Node::File {
+                        inode: inode_of_to,
+                        name: name_of_to,
+                        file,
+                        metadata: {
+                            let time = time();
+
+                            Metadata {
+                                ft: FileType {
+                                    file: true,
+                                    ..Default::default()
+                                },
+                                accessed: time,
+                                created: time,
+                                modified: time,
+                                len,
+                                nlink: 1,
+                                mode: FILE_MODE,
+                                ..Default::default()
+                            }
+                        },
+                        open_handles: 0,
+                    });
+
+                    assert_eq!(
+                        inode_of_to, real_inode_of_to,
+                        "new file inode should have been correctly calculated",
+                    );
+
+                    // Adding the new file to its parent.
+                    fs.add_child_to_node(inode_of_to_parent, inode_of_to)?;
+                    fs.notify(|| WatchEvent::Created(to.clone()));
+
+                    inode_of_to
+                }
+            };
+            fs.resized(old_len, len);
+
+            // The contents of the destination are recorded with the
+            // modified files.
+            fs.record(|| Record::CloneFile {
+                from: from.to_path_buf(),
+                to,
+            });
+            fs.mark_dirty(inode_of_to);
         }
 
         Ok(())
     }
 
+    fn watch(&self, path: &Path, recursive: bool) -> Result<WatchHandle> {
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        let (path, _) = fs.canonicalize(path)?;
+
+        Ok(fs.watchers.watch(path, recursive))
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         OpenOptions::new(Box::new(FileOpener {
             filesystem: self.clone(),
@@ -300,13 +822,44 @@ impl fmt::Debug for FileSystem {
 /// indexed by their respective `Inode` in a slab.
 pub(super) struct FileSystemInner {
     pub(super) storage: Slab<Node>,
+    /// The mutations recorded since the journal was last taken, if
+    /// it is started.
+    pub(super) journal: Option<Journal>,
+    pub(super) watchers: Watchers,
+    pub(super) limits: Limits,
+    /// The size of the contents of all the files, including the
+    /// unlinked ones which are still opened, kept up to date by
+    /// [`Self::resized`] and [`Self::remove_node`].
+    pub(super) used_bytes: u64,
 }
 
 impl FileSystemInner {
-    /// Get the inode associated to a path if it exists.
+    /// Get the inode associated to a path if it exists. Symlinks are
+    /// followed, including the last component of the path.
     pub(super) fn inode_of(&self, path: &Path) -> Result<Inode> {
-        // SAFETY: The root node always exists, so it's safe to unwrap here.
-        let mut node = self.storage.get(ROOT_INODE).unwrap();
+        self.resolve(path, FollowSymlink::Yes)?
+            .1
+            .ok_or(FsError::NotAFile)
+    }
+
+    /// Like `Self::inode_of`, but if the last component of the path
+    /// is a symlink, the inode of the symlink itself is returned.
+    pub(super) fn inode_of_symlink(&self, path: &Path) -> Result<Inode> {
+        self.resolve(path, FollowSymlink::No)?
+            .1
+            .ok_or(FsError::NotAFile)
+    }
+
+    /// Resolve a path to the path it designates once the symlinks it
+    /// goes through are followed, along with its inode if it exists.
+    ///
+    /// All the components of the path but the last one must exist.
+    /// The last one is followed only if `follow_symlink` says so.
+    pub(super) fn resolve(
+        &self,
+        path: &Path,
+        follow_symlink: FollowSymlink,
+    ) -> Result<(PathBuf, Option<Inode>)> {
         let mut components = path.components();
 
         match components.next() {
@@ -314,18 +867,90 @@ impl FileSystemInner {
             _ => return Err(FsError::BaseNotDirectory),
         }
 
-        for component in components {
-            node = match node {
-                Node::Directory { children, .. } => children
+        // The components left to resolve, in reverse order, and the
+        // inodes of the path resolved so far, to go back on `..`.
+        let mut pending: Vec<OsString> = components
+            .rev()
+            .map(|component| component.as_os_str().to_os_string())
+            .collect();
+        let mut resolved_path = PathBuf::from("/");
+        let mut resolved_inodes = vec![ROOT_INODE];
+        let mut followed_symlinks = 0;
+
+        while let Some(name) = pending.pop() {
+            if name == "." {
+                continue;
+            }
+
+            if name == ".." {
+                if resolved_inodes.len() > 1 {
+                    resolved_inodes.pop();
+                    resolved_path.pop();
+                }
+
+                continue;
+            }
+
+            // SAFETY: `resolved_inodes` always contains the root.
+            let inode_of_parent = *resolved_inodes.last().unwrap();
+            let node = match self.storage.get(inode_of_parent) {
+                Some(Node::Directory { children, .. }) => children
                     .iter()
                     .filter_map(|inode| self.storage.get(*inode))
-                    .find(|node| node.name() == component.as_os_str())
-                    .ok_or(FsError::NotAFile)?,
+                    .find(|node| node.name() == name),
                 _ => return Err(FsError::BaseNotDirectory),
             };
+
+            match node {
+                Some(Node::Symlink { target, .. })
+                    if follow_symlink.yes() || !pending.is_empty() =>
+                {
+                    followed_symlinks += 1;
+
+                    if followed_symlinks > MAX_SYMLINKS {
+                        return Err(FsError::SymlinkLoop);
+                    }
+
+                    // An absolute target is resolved from the root,
+                    // a relative one from the directory of the symlink.
+                    if target.has_root() {
+                        resolved_path = PathBuf::from("/");
+                        resolved_inodes.truncate(1);
+                    }
+
+                    pending.extend(
+                        target
+                            .components()
+                            .filter(|component| !matches!(component, Component::RootDir))
+                            .rev()
+                            .map(|component| component.as_os_str().to_os_string()),
+                    );
+                }
+
+                // A hard link designates the file it points to.
+                Some(Node::HardLink { target, .. }) => {
+                    resolved_path.push(&name);
+                    resolved_inodes.push(*target);
+                }
+
+                Some(node) => {
+                    resolved_path.push(&name);
+                    resolved_inodes.push(node.inode());
+                }
+
+                // The last component doesn't exist, it may be about
+                // to be created.
+                None if pending.is_empty() => {
+                    resolved_path.push(&name);
+
+                    return Ok((resolved_path, None));
+                }
+
+                None => return Err(FsError::NotAFile),
+            }
         }
 
-        Ok(node.inode())
+        Ok((resolved_path, resolved_inodes.last().copied()))
     }
 
     /// Get the inode associated to a “parent path”. The returned
@@ -376,7 +1001,9 @@ impl FileSystemInner {
     }
 
     /// From the inode of a parent node (so, a directory), returns the
-    /// child index of `name_of_file` along with its inode.
+    /// child index of `name_of_file` along with its inode. Symlinks
+    /// and hard links are considered as files, the inode of the
+    /// latter being the one of the link itself.
     pub(super) fn as_parent_get_position_and_inode_of_file(
         &self,
         inode_of_parent: Inode,
@@ -388,7 +1015,11 @@ impl FileSystemInner {
                 .enumerate()
                 .filter_map(|(nth, inode)| self.storage.get(*inode).map(|node| (nth, node)))
                 .find_map(|(nth, node)| match node {
-                    Node::File { inode, name, .. } if name.as_os_str() == name_of_file => {
+                    Node::File { inode, name, .. }
+                    | Node::Symlink { inode, name, .. }
+                    | Node::HardLink { inode, name, .. }
+                        if name.as_os_str() == name_of_file =>
+                    {
                         Some(Some((nth, *inode)))
                     }
 
@@ -403,7 +1034,7 @@ impl FileSystemInner {
 
     /// From the inode of a parent node (so, a directory), returns the
     /// child index of `name_of` along with its inode, whatever the
-    /// type of inode is (directory or file).
+    /// type of inode is (directory, file, symlink or hard link).
     fn as_parent_get_position_and_inode(
         &self,
         inode_of_parent: Inode,
@@ -415,7 +1046,10 @@ impl FileSystemInner {
                 .enumerate()
                 .filter_map(|(nth, inode)| self.storage.get(*inode).map(|node| (nth, node)))
                 .find_map(|(nth, node)| match node {
-                    Node::File { inode, name, .. } | Node::Directory { inode, name, .. }
+                    Node::File { inode, name, .. }
+                    | Node::Directory { inode, name, .. }
+                    | Node::Symlink { inode, name, .. }
+                    | Node::HardLink { inode, name, .. }
                         if name.as_os_str() == name_of =>
                     {
                         Some(Some((nth, *inode)))
@@ -430,32 +1064,167 @@ impl FileSystemInner {
         }
     }
 
-    /// Set a new name for the node represented by `inode`.
+    /// Set a new name for the node represented by `inode`. The
+    /// modified time of a hard link is the one of its target.
     pub(super) fn update_node_name(&mut self, inode: Inode, new_name: OsString) -> Result<()> {
-        let node = self.storage.get_mut(inode).ok_or(FsError::UnknownError)?;
+        let inode_of_target = self.follow_hard_link(inode);
 
+        let node = self.storage.get_mut(inode).ok_or(FsError::UnknownError)?;
         node.set_name(new_name);
-        node.metadata_mut().modified = time();
+
+        let target = self
+            .storage
+            .get_mut(inode_of_target)
+            .ok_or(FsError::UnknownError)?;
+        target.metadata_mut().modified = time();
 
         Ok(())
     }
 
-    /// Add a child to a directory node represented by `inode`.
-    ///
-    /// This function also updates the modified time of the directory.
-    ///
-    /// # Safety
-    ///
-    /// `inode` must represents an existing directory.
-    pub(super) fn add_child_to_node(&mut self, inode: Inode, new_child: Inode) -> Result<()> {
-        match self.storage.get_mut(inode) {
-            Some(Node::Directory {
-                children,
-                metadata: Metadata { modified, .. },
-                ..
-            }) => {
-                children.push(new_child);
-                *modified = time();
+    /// Get the inode of the file a hard link points to, or `inode`
+    /// itself if it doesn't represent a hard link.
+    pub(super) fn follow_hard_link(&self, inode: Inode) -> Inode {
+        match self.storage.get(inode) {
+            Some(Node::HardLink { target, .. }) => *target,
+            _ => inode,
+        }
+    }
+
+    /// Find the directory containing the node represented by
+    /// `inode`, and the position of the node in it.
+    pub(super) fn as_child_get_parent_and_position(&self, inode: Inode) -> Result<(Inode, usize)> {
+        self.storage
+            .iter()
+            .find_map(|(inode_of_parent, node)| match node {
+                Node::Directory { children, .. } => children
+                    .iter()
+                    .position(|child| *child == inode)
+                    .map(|position| (inode_of_parent, position)),
+
+                _ => None,
+            })
+            .ok_or(FsError::BaseNotDirectory)
+    }
+
+    /// Remove the child at position `position` of a directory node
+    /// represented by `inode_of_parent`, and the node it represents.
+    ///
+    /// A file is only removed from the storage once it has no name
+    /// left and no handle opened on it. When the removed name is the
+    /// one of the file node itself, one of its hard links takes its
+    /// place.
+    pub(super) fn remove_entry(&mut self, inode_of_parent: Inode, position: usize) -> Result<()> {
+        let inode = match self.storage.get(inode_of_parent) {
+            Some(Node::Directory { children, .. }) => {
+                *children.get(position).ok_or(FsError::UnknownError)?
+            }
+            _ => return Err(FsError::UnknownError),
+        };
+
+        match self.storage.get(inode) {
+            Some(Node::HardLink { target, .. }) => {
+                let inode_of_target = *target;
+
+                trace_node!("remove", inode);
+                self.storage.remove(inode);
+                self.storage
+                    .get_mut(inode_of_target)
+                    .ok_or(FsError::UnknownError)?
+                    .metadata_mut()
+                    .nlink -= 1;
+            }
+
+            Some(Node::File { metadata, .. }) if metadata.nlink > 1 => {
+                // Find a hard link to the file.
+                let (inode_of_link, name_of_link) = self
+                    .storage
+                    .iter()
+                    .find_map(|(inode_of_link, node)| match node {
+                        Node::HardLink { name, target, .. } if *target == inode => {
+                            Some((inode_of_link, name.clone()))
+                        }
+
+                        _ => None,
+                    })
+                    .ok_or(FsError::UnknownError)?;
+                let (inode_of_link_parent, position_of_link) =
+                    self.as_child_get_parent_and_position(inode_of_link)?;
+
+                // Replace the hard link by the file itself.
+                trace_node!("remove", inode_of_link);
+                self.storage.remove(inode_of_link);
+
+                match self.storage.get_mut(inode_of_link_parent) {
+                    Some(Node::Directory { children, .. }) => children[position_of_link] = inode,
+                    _ => return Err(FsError::UnknownError),
+                }
+
+                let node = self.storage.get_mut(inode).ok_or(FsError::UnknownError)?;
+                node.set_name(name_of_link);
+                node.metadata_mut().nlink -= 1;
+            }
+
+            Some(Node::File { open_handles, .. }) if *open_handles > 0 => {
+                // The file is kept, unnamed, until its handles are
+                // closed.
+                let node = self.storage.get_mut(inode).ok_or(FsError::UnknownError)?;
+                node.metadata_mut().nlink = 0;
+            }
+
+            Some(_) => {
+                trace_node!("remove", inode);
+                self.remove_node(inode);
+            }
+
+            None => return Err(FsError::UnknownError),
+        }
+
+        self.remove_child_from_node(inode_of_parent, position)
+    }
+
+    /// Register a new handle opened on the file represented by
+    /// `inode`.
+    pub(super) fn open_handle(&mut self, inode: Inode) {
+        if let Some(Node::File { open_handles, .. }) = self.storage.get_mut(inode) {
+            *open_handles += 1;
+        }
+    }
+
+    /// Release a handle opened on the file represented by `inode`.
+    /// The file is removed from the storage if it was the last
+    /// handle of an unlinked file.
+    pub(super) fn close_handle(&mut self, inode: Inode) {
+        if let Some(Node::File {
+            open_handles,
+            metadata,
+            ..
+        }) = self.storage.get_mut(inode)
+        {
+            *open_handles = open_handles.saturating_sub(1);
+
+            if *open_handles == 0 && metadata.nlink == 0 {
+                trace_node!("remove", inode);
+                self.remove_node(inode);
+            }
+        }
+    }
+
+    /// Add a child to a directory node represented by `inode`.
+    ///
+    /// This function also updates the modified time of the directory.
+    ///
+    /// # Safety
+    ///
+    /// `inode` must represents an existing directory.
+    pub(super) fn add_child_to_node(&mut self, inode: Inode, new_child: Inode) -> Result<()> {
+        match self.storage.get_mut(inode) {
+            Some(Node::Directory {
+                children,
+                metadata: Metadata { modified, .. },
+                ..
+            }) => {
+                children.push(new_child);
+                *modified = time();
 
                 Ok(())
             }
@@ -506,43 +1275,9 @@ impl FileSystemInner {
     /// the path, which means that there is no guarantee that the path
     /// exists in the file system.
     pub(super) fn canonicalize_without_inode(&self, path: &Path) -> Result<PathBuf> {
-        let mut components = path.components();
-
-        match components.next() {
-            Some(Component::RootDir) => {}
-            _ => return Err(FsError::InvalidInput),
-        }
-
-        let mut new_path = PathBuf::with_capacity(path.as_os_str().len());
-        new_path.push("/");
-
-        for component in components {
-            match component {
-                // That's an error to get a `RootDir` a second time.
-                Component::RootDir => return Err(FsError::UnknownError),
-
-                // Nothing to do on `new_path`.
-                Component::CurDir => (),
-
-                // Pop the lastly inserted component on `new_path` if
-                // any, otherwise it's an error.
-                Component::ParentDir => {
-                    if !new_path.pop() {
-                        return Err(FsError::InvalidInput);
-                    }
-                }
-
-                // A normal
-                Component::Normal(name) => {
-                    new_path.push(name);
-                }
-
-                // We don't support Windows path prefix.
-                Component::Prefix(_) => return Err(FsError::InvalidInput),
-            }
-        }
-
-        Ok(new_path)
+        PathResolver::new()
+            .parent_of_root(ParentOfRoot::Reject)
+            .normalize(path)
     }
 }
 
@@ -569,6 +1304,8 @@ impl fmt::Debug for FileSystemInner {
                     ty = match node {
                         Node::File { .. } => "file",
                         Node::Directory { .. } => "dir",
+                        Node::Symlink { .. } => "link",
+                        Node::HardLink { .. } => "hard",
                     },
                     name = node.name().to_string_lossy(),
                     indentation_symbol = " ",
@@ -618,10 +1355,19 @@ impl Default for FileSystemInner {
                 created: time,
                 modified: time,
                 len: 0,
+                nlink: 1,
+                mode: DIRECTORY_MODE,
+                ..Default::default()
             },
         });
 
-        Self { storage: slab }
+        Self {
+            storage: slab,
+            journal: None,
+            watchers: Watchers::default(),
+            limits: Limits::default(),
+            used_bytes: 0,
+        }
     }
 }
 
@@ -1052,9 +1798,11 @@ mod test_filesystem {
                 accessed,
                 created,
                 modified,
-                len: 0
+                len: 0,
+                ..
             }) if accessed == created && created == modified && modified > 0
         ));
+        let root_metadata = root_metadata.unwrap();
 
         assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
 
@@ -1069,7 +1817,8 @@ mod test_filesystem {
                 accessed,
                 created,
                 modified,
-                len: 0
+                len: 0,
+                ..
             } if accessed == created && created == modified && modified > 0
         ));
 
@@ -1085,7 +1834,8 @@ mod test_filesystem {
                     accessed,
                     created,
                     modified,
-                    len: 0
+                    len: 0,
+                    ..
                 }) if
                     accessed == foo_metadata.accessed &&
                     created == foo_metadata.created &&
@@ -1101,10 +1851,11 @@ mod test_filesystem {
                     accessed,
                     created,
                     modified,
-                    len: 0
+                    len: 0,
+                    ..
                 }) if
-                    accessed == foo_metadata.accessed &&
-                    created == foo_metadata.created &&
+                    accessed == root_metadata.accessed &&
+                    created == root_metadata.created &&
                     modified > foo_metadata.modified
             ),
             "the modified time of the parent is updated when file is renamed",
@@ -1186,6 +1937,610 @@ mod test_filesystem {
         );
     }
 
+    #[test]
+    fn test_clone_file() {
+        use std::io::{Read, Write};
+
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("creating a new file");
+        file.write_all(b"foobar").unwrap();
+        assert_eq!(fs.create_dir(path!("/bar")), Ok(()));
+
+        assert_eq!(
+            fs.clone_file(path!("/foo.txt"), path!("/bar/baz.txt")),
+            Ok(()),
+            "cloning a file",
+        );
+        assert_eq!(
+            fs.metadata(path!("/bar/baz.txt"))
+                .map(|metadata| metadata.len),
+            Ok(6),
+            "the clone has the same length",
+        );
+
+        {
+            let fs_inner = fs.inner.read().unwrap();
+
+            assert!(
+                matches!(
+                    fs_inner.storage.get(3),
+                    Some(Node::File { name, file, .. }) if name == "baz.txt" && file.is_shared()
+                ),
+                "the clone shares the contents of `foo.txt`",
+            );
+        }
+
+        // Writing to the source copies its contents first.
+        file.write_all(b"baz").unwrap();
+
+        {
+            let fs_inner = fs.inner.read().unwrap();
+
+            assert!(
+                matches!(
+                    fs_inner.storage.get(3),
+                    Some(Node::File { file, .. }) if !file.is_shared()
+                ),
+                "the clone no longer shares its contents",
+            );
+        }
+
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/bar/baz.txt"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "foobar", "the clone is unchanged");
+
+        // Cloning over an existing file replaces its contents.
+        assert_eq!(
+            fs.clone_file(path!("/foo.txt"), path!("/bar/baz.txt")),
+            Ok(()),
+            "cloning over an existing file",
+        );
+        assert_eq!(
+            fs.metadata(path!("/bar/baz.txt"))
+                .map(|metadata| metadata.len),
+            Ok(9),
+        );
+
+        assert_eq!(
+            fs.clone_file(path!("/bar"), path!("/qux.txt")),
+            Err(FsError::NotAFile),
+            "cloning a directory",
+        );
+        assert_eq!(
+            fs.clone_file(path!("/foo.txt"), path!("/bar")),
+            Err(FsError::NotAFile),
+            "cloning over a directory",
+        );
+    }
+
+    #[test]
+    fn test_symlink() {
+        use std::io::{Read, Write};
+
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        fs.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+
+        assert_eq!(
+            fs.symlink(path!("bar.txt"), path!("/foo/link")),
+            Ok(()),
+            "creating a relative symlink",
+        );
+        assert_eq!(
+            fs.symlink(path!("bar.txt"), path!("/foo/link")),
+            Err(FsError::AlreadyExists),
+            "creating a symlink that already exists",
+        );
+        assert_eq!(
+            fs.read_link(path!("/foo/link")),
+            Ok(path!(buf "bar.txt")),
+            "reading the symlink",
+        );
+        assert_eq!(
+            fs.read_link(path!("/foo/bar.txt")),
+            Err(FsError::InvalidInput),
+            "reading a file as a symlink",
+        );
+
+        assert!(
+            fs.metadata(path!("/foo/link")).unwrap().is_file(),
+            "`metadata` follows the symlink",
+        );
+        assert!(
+            fs.symlink_metadata(path!("/foo/link"))
+                .unwrap()
+                .file_type()
+                .is_symlink(),
+            "`symlink_metadata` doesn't follow the symlink",
+        );
+
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/foo/link"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello", "opening the file through the symlink");
+
+        assert_eq!(
+            fs.symlink(path!("/foo"), path!("/baz")),
+            Ok(()),
+            "creating an absolute symlink to a directory",
+        );
+        assert!(
+            fs.metadata(path!("/baz/link")).unwrap().is_file(),
+            "following symlinks in the middle of a path",
+        );
+        assert_eq!(
+            fs.read_dir(path!("/baz")).map(|entries| entries.count()),
+            Ok(2),
+            "reading a directory through a symlink",
+        );
+
+        assert_eq!(
+            fs.symlink(path!("new.txt"), path!("/foo/dangling")),
+            Ok(()),
+            "creating a dangling symlink",
+        );
+        assert!(
+            fs.new_open_options()
+                .write(true)
+                .create(true)
+                .open(path!("/foo/dangling"))
+                .is_ok(),
+            "creating a file through a dangling symlink",
+        );
+        assert!(
+            fs.metadata(path!("/foo/new.txt")).unwrap().is_file(),
+            "the file is created where the symlink points to",
+        );
+
+        assert_eq!(fs.symlink(path!("/loop"), path!("/loop")), Ok(()));
+        assert!(
+            matches!(fs.metadata(path!("/loop")), Err(FsError::SymlinkLoop)),
+            "following a symlink loop",
+        );
+
+        assert_eq!(
+            fs.remove_file(path!("/foo/link")),
+            Ok(()),
+            "removing a symlink",
+        );
+        assert!(
+            fs.metadata(path!("/foo/bar.txt")).is_ok(),
+            "removing a symlink keeps the file it points to",
+        );
+    }
+
+    #[test]
+    fn test_link() {
+        use std::io::{Read, Write};
+
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        fs.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+
+        assert_eq!(
+            fs.link(path!("/foo/bar.txt"), path!("/baz.txt")),
+            Ok(()),
+            "creating a hard link",
+        );
+        assert_eq!(
+            fs.link(path!("/foo/bar.txt"), path!("/baz.txt")),
+            Err(FsError::AlreadyExists),
+            "creating a hard link that already exists",
+        );
+        assert_eq!(
+            fs.link(path!("/foo"), path!("/qux")),
+            Err(FsError::NotAFile),
+            "creating a hard link to a directory",
+        );
+        assert_eq!(
+            fs.metadata(path!("/foo/bar.txt"))
+                .map(|metadata| metadata.nlink),
+            Ok(2),
+            "the file has two names",
+        );
+        assert_eq!(
+            fs.read_dir(path!("/"))
+                .unwrap()
+                .map(|entry| entry.unwrap())
+                .find(|entry| entry.path == path!("/baz.txt"))
+                .map(|entry| entry.metadata.unwrap().nlink),
+            Some(2),
+            "reading a directory gives the metadata of the file",
+        );
+
+        fs.new_open_options()
+            .append(true)
+            .open(path!("/baz.txt"))
+            .unwrap()
+            .write_all(b", world")
+            .unwrap();
+
+        let read = |path| {
+            let mut contents = String::new();
+            fs.new_open_options()
+                .read(true)
+                .open(path)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            contents
+        };
+        assert_eq!(
+            read(path!("/foo/bar.txt")),
+            "hello, world",
+            "writing through a hard link",
+        );
+
+        assert_eq!(
+            fs.remove_file(path!("/foo/bar.txt")),
+            Ok(()),
+            "removing the original name",
+        );
+        assert_eq!(
+            read(path!("/baz.txt")),
+            "hello, world",
+            "the file remains under its other name",
+        );
+        assert_eq!(
+            fs.metadata(path!("/baz.txt"))
+                .map(|metadata| metadata.nlink),
+            Ok(1),
+            "the file has one name left",
+        );
+
+        {
+            let fs_inner = fs.inner.read().unwrap();
+
+            assert_eq!(
+                fs_inner.storage.len(),
+                3,
+                "the hard link has been replaced by the file",
+            );
+            assert!(
+                matches!(
+                    fs_inner.storage.get(2),
+                    Some(Node::File { name, .. }) if name == "baz.txt"
+                ),
+                "the file takes the name of the hard link",
+            );
+        }
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/baz.txt"))
+            .unwrap();
+        assert_eq!(
+            fs.remove_file(path!("/baz.txt")),
+            Ok(()),
+            "removing the last name",
+        );
+        assert_eq!(
+            fs.inner.read().unwrap().storage.len(),
+            3,
+            "the file is kept while it's opened",
+        );
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "hello, world", "reading an unlinked file");
+
+        drop(file);
+        assert_eq!(
+            fs.inner.read().unwrap().storage.len(),
+            2,
+            "the file is removed once closed",
+        );
+    }
+
+    #[test]
+    fn test_rename_over_an_existing_entry() {
+        use std::io::{Read, Write};
+
+        let fs = FileSystem::default();
+        let write = |path, contents: &str| {
+            fs.new_open_options()
+                .write(true)
+                .create(true)
+                .open(path)
+                .unwrap()
+                .write_all(contents.as_bytes())
+                .unwrap();
+        };
+
+        write(path!("/new.txt"), "new");
+        write(path!("/old.txt"), "old");
+        let mut old = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/old.txt"))
+            .unwrap();
+
+        assert_eq!(
+            fs.rename(path!("/new.txt"), path!("/old.txt")),
+            Ok(()),
+            "replacing a file",
+        );
+        assert_eq!(
+            fs.read_dir(path!("/"))
+                .unwrap()
+                .map(|entry| entry.unwrap().path)
+                .collect::<Vec<_>>(),
+            vec![path!(buf "/old.txt")],
+            "the replaced file has no name left",
+        );
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/old.txt"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "new");
+
+        contents.clear();
+        old.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "old", "the replaced file is still opened");
+        assert_eq!(fs.used_bytes(), Ok(6));
+        drop(old);
+        assert_eq!(
+            fs.used_bytes(),
+            Ok(3),
+            "the replaced file is reclaimed once closed"
+        );
+
+        assert_eq!(fs.create_dir(path!("/dir")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/dir/sub")), Ok(()));
+        assert_eq!(
+            fs.rename(path!("/old.txt"), path!("/dir")),
+            Err(FsError::NotAFile),
+            "replacing a directory by a file",
+        );
+        assert_eq!(
+            fs.rename(path!("/dir/sub"), path!("/old.txt")),
+            Err(FsError::BaseNotDirectory),
+            "replacing a file by a directory",
+        );
+        assert_eq!(fs.create_dir(path!("/empty")), Ok(()));
+        assert_eq!(
+            fs.rename(path!("/empty"), path!("/dir")),
+            Err(FsError::DirectoryNotEmpty),
+            "replacing a directory which isn't empty",
+        );
+        assert_eq!(
+            fs.rename(path!("/dir"), path!("/empty")),
+            Ok(()),
+            "replacing an empty directory",
+        );
+        assert!(fs.metadata(path!("/empty/sub")).unwrap().is_dir());
+        assert_eq!(
+            fs.metadata(path!("/dir")).map(|metadata| metadata.len),
+            Err(FsError::NotAFile)
+        );
+    }
+
+    #[test]
+    fn test_unlinking_an_opened_temporary_file() {
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/tmp.txt"))
+            .unwrap();
+        assert_eq!(fs.remove_file(path!("/tmp.txt")), Ok(()));
+        assert_eq!(
+            fs.metadata(path!("/tmp.txt")).map(|metadata| metadata.len),
+            Err(FsError::NotAFile),
+            "the file has no name",
+        );
+
+        file.write_all(b"scratch").unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "scratch", "writing and reading the unlinked file");
+        assert_eq!(fs.used_bytes(), Ok(7), "the space is still used");
+
+        drop(file);
+        assert_eq!(fs.used_bytes(), Ok(0), "the space is reclaimed once closed");
+    }
+
+    #[test]
+    fn test_chmod_and_chown() {
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        let _ = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap();
+        assert_eq!(fs.symlink(path!("bar.txt"), path!("/foo/baz")), Ok(()));
+
+        let metadata = fs.metadata(path!("/foo")).unwrap();
+        assert_eq!(
+            (metadata.uid, metadata.gid, metadata.mode),
+            (0, 0, 0o755),
+            "a new directory",
+        );
+        assert_eq!(
+            fs.metadata(path!("/foo/bar.txt")).unwrap().mode,
+            0o644,
+            "a new file",
+        );
+
+        assert_eq!(
+            fs.chmod(path!("/foo/baz"), 0o100600),
+            Ok(()),
+            "changing the mode through a symlink",
+        );
+        assert_eq!(
+            fs.metadata(path!("/foo/bar.txt")).unwrap().mode,
+            0o600,
+            "only the permission bits are kept",
+        );
+        assert_eq!(
+            fs.symlink_metadata(path!("/foo/baz")).unwrap().mode,
+            0o777,
+            "the symlink itself is left unchanged",
+        );
+
+        assert_eq!(
+            fs.chown(path!("/foo/bar.txt"), Some(1000), None),
+            Ok(()),
+            "changing the owner",
+        );
+        assert_eq!(
+            fs.chown(path!("/foo/bar.txt"), None, Some(100)),
+            Ok(()),
+            "changing the group",
+        );
+        let metadata = fs.metadata(path!("/foo/bar.txt")).unwrap();
+        assert_eq!((metadata.uid, metadata.gid), (1000, 100));
+
+        assert_eq!(
+            fs.chmod(path!("/qux"), 0o600),
+            Err(FsError::NotAFile),
+            "changing the mode of a file that doesn't exist",
+        );
+    }
+
+    #[test]
+    fn test_set_times() {
+        let fs = FileSystem::default();
+
+        let _ = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .unwrap();
+        let created = fs.metadata(path!("/foo.txt")).unwrap().created;
+
+        assert_eq!(
+            fs.set_times(path!("/foo.txt"), Some(1_500_000_001), Some(2_500_000_002)),
+            Ok(()),
+            "setting both times",
+        );
+        let times = |fs: &FileSystem| {
+            fs.metadata(path!("/foo.txt"))
+                .map(|metadata| (metadata.accessed, metadata.created, metadata.modified))
+        };
+        assert_eq!(
+            times(&fs),
+            Ok((1_500_000_001, created, 2_500_000_002)),
+            "the times are kept to the nanosecond",
+        );
+
+        assert_eq!(
+            fs.set_times(path!("/foo.txt"), None, Some(42)),
+            Ok(()),
+            "setting the modification time only",
+        );
+        assert_eq!(times(&fs), Ok((1_500_000_001, created, 42)));
+
+        assert_eq!(
+            fs.set_times(path!("/bar.txt"), Some(0), None),
+            Err(FsError::NotAFile),
+            "setting the times of a file that doesn't exist",
+        );
+    }
+
+    #[test]
+    fn test_watch() {
+        use crate::WatchEvent::*;
+        use std::io::Write;
+
+        let fs = FileSystem::default();
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/foo/bar")), Ok(()));
+
+        let foo = fs.watch(path!("/foo"), false).unwrap();
+        let recursive = fs.watch(path!("/foo/./bar/.."), true).unwrap();
+        assert!(
+            matches!(fs.watch(path!("/baz"), false), Err(FsError::NotAFile)),
+            "watching a path that doesn't exist"
+        );
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo/bar/baz.txt"))
+            .unwrap();
+        file.write_all(b"hello").unwrap();
+        assert_eq!(
+            fs.rename(path!("/foo/bar/baz.txt"), path!("/foo/qux.txt")),
+            Ok(())
+        );
+        assert_eq!(fs.remove_file(path!("/foo/qux.txt")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/other")), Ok(()));
+
+        assert_eq!(
+            recursive.pending().collect::<Vec<_>>(),
+            vec![
+                Created(path!(buf "/foo/bar/baz.txt")),
+                Modified(path!(buf "/foo/bar/baz.txt")),
+                Renamed {
+                    from: path!(buf "/foo/bar/baz.txt"),
+                    to: path!(buf "/foo/qux.txt"),
+                },
+                Removed(path!(buf "/foo/qux.txt")),
+            ]
+        );
+        assert_eq!(
+            foo.pending().collect::<Vec<_>>(),
+            vec![
+                Renamed {
+                    from: path!(buf "/foo/bar/baz.txt"),
+                    to: path!(buf "/foo/qux.txt"),
+                },
+                Removed(path!(buf "/foo/qux.txt")),
+            ],
+            "only the immediate children are watched"
+        );
+
+        // The watch stops once its handle is dropped.
+        drop(foo);
+        assert_eq!(fs.create_dir(path!("/foo/quux")), Ok(()));
+        assert_eq!(fs.inner.read().unwrap().watchers.len(), 1);
+    }
+
     #[test]
     fn test_readdir() {
         let fs = FileSystem::default();
@@ -1373,3 +2728,14 @@ impl DirectoryMustBeEmpty {
         !self.yes()
     }
 }
+
+pub(super) enum FollowSymlink {
+    Yes,
+    No,
+}
+
+impl FollowSymlink {
+    pub(super) fn yes(&self) -> bool {
+        matches!(self, Self::Yes)
+    }
+}
@@ -0,0 +1,224 @@
+//! Reclaims the nodes which can't be reached anymore, see
+//! [`FileSystem::gc`].
+
+use super::*;
+use crate::{FsError, Result};
+
+/// What a [`FileSystem::gc`] pass reclaimed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// The number of nodes removed from the storage.
+    pub nodes: usize,
+    /// The size of the contents of the removed files, in bytes.
+    pub bytes: u64,
+}
+
+impl FileSystem {
+    /// Removes the nodes which can't be reached from the root
+    /// directory, nor from an opened handle.
+    ///
+    /// Such nodes are left behind when an operation made of several
+    /// steps fails half-way, e.g. a node inserted in the storage but
+    /// never added to its parent. Nothing reachable is modified, so
+    /// the pass can run at any time.
+    #[cfg_attr(
+        feature = "mem-fs-tracing",
+        tracing::instrument(level = "debug", skip(self))
+    )]
+    pub fn gc(&self) -> Result<GcReport> {
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        // Mark the nodes reachable from the root, and from the files
+        // kept alive by their handles.
+        let mut reachable = vec![false; fs.storage.capacity()];
+        let mut pending = fs
+            .storage
+            .iter()
+            .filter_map(|(inode, node)| match node {
+                Node::File { open_handles, .. } if *open_handles > 0 => Some(inode),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        pending.push(ROOT_INODE);
+
+        while let Some(inode) = pending.pop() {
+            if std::mem::replace(&mut reachable[inode], true) {
+                continue;
+            }
+
+            match fs.storage.get(inode) {
+                Some(Node::Directory { children, .. }) => pending.extend(children),
+                Some(Node::HardLink { target, .. }) => pending.push(*target),
+                _ => {}
+            }
+        }
+
+        // Sweep the others.
+        let unreachable = fs
+            .storage
+            .iter()
+            .map(|(inode, _)| inode)
+            .filter(|inode| !reachable[*inode])
+            .collect::<Vec<_>>();
+
+        let mut report = GcReport::default();
+        for inode in unreachable {
+            trace_node!("remove", inode);
+            if let Node::File { file, .. } = fs.remove_node(inode) {
+                report.bytes += file.len() as u64;
+            }
+            report.nodes += 1;
+        }
+
+        Ok(report)
+    }
+
+    /// Runs a [`gc`](Self::gc) pass, then releases the memory
+    /// allocated beyond the contents of the files and the nodes,
+    /// returning the number of bytes released, e.g. when the host is
+    /// short of memory.
+    ///
+    /// The file contents shared with a clone or a mapping are left as
+    /// they are.
+    pub fn compact(&self) -> Result<u64> {
+        let report = self.gc()?;
+
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        let mut released = report.bytes;
+        for (_, node) in fs.storage.iter_mut() {
+            if let Node::File { file, .. } = node {
+                released += file.shrink_to_fit() as u64;
+            }
+        }
+
+        let capacity = fs.storage.capacity();
+        fs.storage.shrink_to_fit();
+        released += ((capacity - fs.storage.capacity()) * std::mem::size_of::<Node>()) as u64;
+
+        Ok(released)
+    }
+}
+
+#[cfg(test)]
+mod test_gc {
+    use super::*;
+    use crate::FileSystem as FS;
+    use std::io::{self, Read, Seek, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    /// Inserts a node in the storage, without adding it to a parent.
+    fn insert_orphan(fs: &FileSystem, node: impl FnOnce(Inode) -> Node) -> Inode {
+        let mut fs = fs.inner.write().unwrap();
+        let entry = fs.storage.vacant_entry();
+        let inode = entry.key();
+        let node = entry.insert(node(inode));
+
+        if let Node::File { metadata, .. } = node {
+            let len = metadata.len;
+            fs.resized(0, len);
+        }
+
+        inode
+    }
+
+    #[test]
+    fn test_gc() {
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/foo/bar"))
+            .unwrap();
+        file.write_all(b"hello").unwrap();
+        assert_eq!(fs.link(path!("/foo/bar"), path!("/baz")), Ok(()));
+
+        // An unlinked file is kept as long as it is opened.
+        let mut unlinked = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/qux"))
+            .unwrap();
+        unlinked.write_all(b"kept").unwrap();
+        assert_eq!(fs.remove_file(path!("/qux")), Ok(()));
+
+        assert_eq!(fs.gc(), Ok(GcReport::default()));
+
+        // An orphan directory, with a file.
+        let orphan_file = insert_orphan(&fs, |inode| Node::File {
+            inode,
+            name: "orphan".into(),
+            file: File::with_contents(b"lost".to_vec()),
+            metadata: Metadata {
+                len: 4,
+                ..Default::default()
+            },
+            open_handles: 0,
+        });
+        let orphan_directory = insert_orphan(&fs, |inode| Node::Directory {
+            inode,
+            name: "orphans".into(),
+            children: vec![orphan_file],
+            metadata: Metadata::default(),
+        });
+
+        assert_eq!(fs.gc(), Ok(GcReport { nodes: 2, bytes: 4 }));
+        {
+            let fs = fs.inner.read().unwrap();
+            assert!(!fs.storage.contains(orphan_file));
+            assert!(!fs.storage.contains(orphan_directory));
+        }
+
+        // The reachable nodes are intact.
+        assert_eq!(fs.metadata(path!("/foo/bar")).map(|m| m.len), Ok(5));
+        assert_eq!(fs.metadata(path!("/baz")).map(|m| m.len), Ok(5));
+        assert_eq!(unlinked.size(), 4);
+    }
+
+    #[test]
+    fn test_compact() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path!("/foo"))
+            .unwrap();
+        file.write_all(&[1; 1024]).unwrap();
+        file.set_len(4).unwrap();
+        insert_orphan(&fs, |inode| Node::File {
+            inode,
+            name: "orphan".into(),
+            file: File::with_contents(b"lost".to_vec()),
+            metadata: Metadata {
+                len: 4,
+                ..Default::default()
+            },
+            open_handles: 0,
+        });
+        assert_eq!(fs.used_bytes(), Ok(8));
+
+        let released = fs.compact().unwrap();
+        assert!(released >= 4 + 1020, "released {} bytes", released);
+        assert_eq!(fs.used_bytes(), Ok(4));
+        assert_eq!(fs.compact(), Ok(0), "nothing left to release");
+
+        let mut contents = Vec::new();
+        file.seek(io::SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, [1; 4]);
+    }
+}
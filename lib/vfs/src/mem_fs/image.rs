@@ -0,0 +1,553 @@
+//! This module contains the snapshot and restore of a [`FileSystem`]
+//! to and from a binary image.
+//!
+//! The image is made of a header followed by the root node, nodes
+//! being written depth-first. All integers are little-endian:
+//!
+//! ```text
+//! image    := MAGIC VERSION:u8 node
+//! node     := kind:u8 name (metadata (directory | file | symlink) | hardlink)
+//! name     := length:u64 bytes
+//! metadata := accessed:u64 created:u64 modified:u64 uid:u32 gid:u32 mode:u32
+//! directory:= count:u64 node*
+//! file     := length:u64 bytes
+//! symlink  := length:u64 bytes
+//! hardlink := target:u64
+//! ```
+//!
+//! The target of a hard link is the index of the file it points to,
+//! in the depth-first order of the nodes, the root being 0. Times are
+//! in nanoseconds since `UNIX_EPOCH`.
+//!
+//! Older images are still readable: images of the version 1 have no
+//! hard links, images older than the version 3 have no ownership nor
+//! permissions, their nodes get the defaults of new nodes, and images
+//! older than the version 4 have times in seconds.
+
+use super::filesystem::FileSystemInner;
+use super::*;
+use crate::{FileType, FsError, Metadata, Result};
+use slab::Slab;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+const MAGIC: &[u8; 4] = b"\0wfs";
+const VERSION: u8 = 4;
+
+const DIRECTORY: u8 = 0;
+const FILE: u8 = 1;
+const SYMLINK: u8 = 2;
+const HARDLINK: u8 = 3;
+
+impl FileSystem {
+    /// Serializes the whole file system, i.e. the directory tree, the
+    /// file contents and the metadata, into a binary image.
+    ///
+    /// Inodes are not preserved, and open files are not part of the
+    /// image.
+    pub fn save_to_bytes(&self) -> Result<Vec<u8>> {
+        // Read lock.
+        let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+        let mut image = Vec::new();
+        image.extend_from_slice(MAGIC);
+        image.push(VERSION);
+
+        let nodes = nodes_depth_first(&fs);
+        let indexes = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, inode)| (*inode, index as u64))
+            .collect::<HashMap<_, _>>();
+        for inode in nodes {
+            write_node(&fs, inode, &indexes, &mut image)?;
+        }
+
+        Ok(image)
+    }
+
+    /// Creates a file system from an image produced by
+    /// [`FileSystem::save_to_bytes`].
+    pub fn load_from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, version: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(FsError::InvalidData);
+        }
+        reader.version = reader.u8()?;
+        if !(1..=VERSION).contains(&reader.version) {
+            return Err(FsError::InvalidData);
+        }
+
+        let mut storage = Slab::new();
+        let root = read_nodes(&mut reader, &mut storage)?;
+        if root != ROOT_INODE
+            || !matches!(storage.get(ROOT_INODE), Some(Node::Directory { .. }))
+            || !reader.bytes.is_empty()
+        {
+            return Err(FsError::InvalidData);
+        }
+
+        // Inodes follow the image order, so the targets of the hard
+        // links are inodes. Each of them adds a name to its file.
+        let targets = storage
+            .iter()
+            .filter_map(|(_, node)| match node {
+                Node::HardLink { target, .. } => Some(*target),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for target in targets {
+            match storage.get_mut(target) {
+                Some(Node::File { metadata, .. }) => metadata.nlink += 1,
+                _ => return Err(FsError::InvalidData),
+            }
+        }
+
+        let used_bytes = storage
+            .iter()
+            .map(|(_, node)| match node {
+                Node::File { metadata, .. } => metadata.len,
+                _ => 0,
+            })
+            .sum();
+
+        Ok(Self {
+            inner: Arc::new(RwLock::new(FileSystemInner {
+                storage,
+                journal: None,
+                watchers: Default::default(),
+                limits: Default::default(),
+                used_bytes,
+            })),
+        })
+    }
+}
+
+pub(super) fn write_u64(value: u64, image: &mut Vec<u8>) {
+    image.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(super) fn write_u32(value: u32, image: &mut Vec<u8>) {
+    image.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(super) fn write_bytes(bytes: &[u8], image: &mut Vec<u8>) {
+    write_u64(bytes.len() as u64, image);
+    image.extend_from_slice(bytes);
+}
+
+/// Lists the nodes in the depth-first order they are written in, the
+/// root first. The tree is walked with an explicit stack, so that its
+/// depth is only bounded by the memory.
+fn nodes_depth_first(fs: &FileSystemInner) -> Vec<Inode> {
+    let mut nodes = Vec::new();
+    let mut stack = vec![ROOT_INODE];
+
+    while let Some(inode) = stack.pop() {
+        nodes.push(inode);
+
+        if let Some(Node::Directory { children, .. }) = fs.storage.get(inode) {
+            stack.extend(children.iter().rev());
+        }
+    }
+
+    nodes
+}
+
+/// Writes the node represented by `inode`. The children of a
+/// directory are not written, only their count.
+fn write_node(
+    fs: &FileSystemInner,
+    inode: Inode,
+    indexes: &HashMap<Inode, u64>,
+    image: &mut Vec<u8>,
+) -> Result<()> {
+    let node = fs.storage.get(inode).ok_or(FsError::UnknownError)?;
+
+    image.push(match node {
+        Node::Directory { .. } => DIRECTORY,
+        Node::File { .. } => FILE,
+        Node::Symlink { .. } => SYMLINK,
+        Node::HardLink { .. } => HARDLINK,
+    });
+
+    // Names are part of paths, and are written as UTF-8.
+    let name = node.name().to_str().ok_or(FsError::InvalidInput)?;
+    write_bytes(name.as_bytes(), image);
+
+    // A hard link has no metadata, they are the ones of its target.
+    if let Node::HardLink { target, .. } = node {
+        write_u64(*indexes.get(target).ok_or(FsError::UnknownError)?, image);
+
+        return Ok(());
+    }
+
+    let metadata = node.metadata();
+    write_u64(metadata.accessed, image);
+    write_u64(metadata.created, image);
+    write_u64(metadata.modified, image);
+    write_u32(metadata.uid, image);
+    write_u32(metadata.gid, image);
+    write_u32(metadata.mode, image);
+
+    match node {
+        Node::Directory { children, .. } => {
+            write_u64(children.len() as u64, image);
+        }
+        Node::File { file, .. } => write_bytes(&file.contents(), image),
+        Node::Symlink { target, .. } => {
+            let target = target.to_str().ok_or(FsError::InvalidInput)?;
+            write_bytes(target.as_bytes(), image);
+        }
+        Node::HardLink { .. } => {}
+    }
+
+    Ok(())
+}
+
+/// A cursor over the image, or the journal, being read.
+pub(super) struct Reader<'a> {
+    pub(super) bytes: &'a [u8],
+    /// The version of the image, or of the journal.
+    pub(super) version: u8,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        if length > self.bytes.len() {
+            return Err(FsError::InvalidData);
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32> {
+        // `take` returns exactly 4 bytes.
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(super) fn u64(&mut self) -> Result<u64> {
+        // `take` returns exactly 8 bytes.
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub(super) fn bytes(&mut self) -> Result<&'a [u8]> {
+        let length = self.u64()?.try_into().map_err(|_| FsError::InvalidData)?;
+        self.take(length)
+    }
+}
+
+/// Reads the nodes of the image, and returns the inode of the root.
+///
+/// The directories being read are kept on an explicit stack rather
+/// than read recursively, so that a deep image can't overflow the
+/// stack.
+fn read_nodes(reader: &mut Reader, storage: &mut Slab<Node>) -> Result<Inode> {
+    // The directories being read, with the number of children left to
+    // read in each of them.
+    let mut parents: Vec<(Inode, u64)> = Vec::new();
+    let mut root = None;
+
+    loop {
+        let (inode, count) = read_node(reader, storage)?;
+
+        match parents.last_mut() {
+            Some((parent, left)) => {
+                *left -= 1;
+                match storage.get_mut(*parent) {
+                    Some(Node::Directory { children, .. }) => children.push(inode),
+                    _ => return Err(FsError::UnknownError),
+                }
+            }
+            None => root = Some(inode),
+        }
+
+        if let Some(count) = count {
+            parents.push((inode, count));
+        }
+
+        // Leave the directories whose children are all read.
+        while let Some((_, 0)) = parents.last() {
+            parents.pop();
+        }
+
+        if parents.is_empty() {
+            return root.ok_or(FsError::UnknownError);
+        }
+    }
+}
+
+/// Reads a node, without its children. The number of children of a
+/// directory is returned along with its inode, they follow it in the
+/// image.
+fn read_node(reader: &mut Reader, storage: &mut Slab<Node>) -> Result<(Inode, Option<u64>)> {
+    let kind = reader.u8()?;
+    let name = std::str::from_utf8(reader.bytes()?).map_err(|_| FsError::InvalidData)?;
+    let name = OsString::from(name);
+    let inode = storage.vacant_entry().key();
+
+    if kind == HARDLINK {
+        let target = reader.u64()?.try_into().map_err(|_| FsError::InvalidData)?;
+        storage.insert(Node::HardLink {
+            inode,
+            name,
+            target,
+        });
+
+        return Ok((inode, None));
+    }
+
+    let time = |reader: &mut Reader| -> Result<u64> {
+        let time = reader.u64()?;
+        if reader.version >= 4 {
+            Ok(time)
+        } else {
+            Ok(time.saturating_mul(1_000_000_000))
+        }
+    };
+    let mut metadata = Metadata {
+        accessed: time(reader)?,
+        created: time(reader)?,
+        modified: time(reader)?,
+        nlink: 1,
+        ..Default::default()
+    };
+    if reader.version >= 3 {
+        metadata.uid = reader.u32()?;
+        metadata.gid = reader.u32()?;
+        metadata.mode = reader.u32()?;
+    } else {
+        metadata.mode = match kind {
+            DIRECTORY => DIRECTORY_MODE,
+            SYMLINK => SYMLINK_MODE,
+            _ => FILE_MODE,
+        };
+    }
+
+    let mut count = None;
+
+    match kind {
+        DIRECTORY => {
+            metadata.ft = FileType {
+                dir: true,
+                ..Default::default()
+            };
+
+            // The directory is inserted before its children, so that
+            // inodes follow the image order.
+            storage.insert(Node::Directory {
+                inode,
+                name,
+                children: Vec::new(),
+                metadata,
+            });
+
+            count = Some(reader.u64()?);
+        }
+
+        FILE => {
+            let contents = reader.bytes()?.to_vec();
+            metadata.ft = FileType {
+                file: true,
+                ..Default::default()
+            };
+            metadata.len = contents.len() as u64;
+
+            storage.insert(Node::File {
+                inode,
+                name,
+                file: File::with_contents(contents),
+                metadata,
+                open_handles: 0,
+            });
+        }
+
+        SYMLINK => {
+            let target = std::str::from_utf8(reader.bytes()?).map_err(|_| FsError::InvalidData)?;
+            metadata.ft = FileType {
+                symlink: true,
+                ..Default::default()
+            };
+            metadata.len = target.len() as u64;
+
+            storage.insert(Node::Symlink {
+                inode,
+                name,
+                target: PathBuf::from(target),
+                metadata,
+            });
+        }
+
+        _ => return Err(FsError::InvalidData),
+    }
+
+    Ok((inode, count))
+}
+
+#[cfg(test)]
+mod test_image {
+    use crate::{mem_fs::*, FileSystem as FS, FsError};
+    use std::io::{Read, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let fs = FileSystem::default();
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/foo/bar")), Ok(()));
+        fs.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo/bar/baz.txt"))
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        assert_eq!(fs.chmod(path!("/foo/bar/baz.txt"), 0o600), Ok(()));
+        assert_eq!(
+            fs.chown(path!("/foo/bar/baz.txt"), Some(1000), Some(100)),
+            Ok(())
+        );
+
+        let image = fs.save_to_bytes().unwrap();
+        let restored = FileSystem::load_from_bytes(&image).unwrap();
+
+        // Saving the restored file system gives the same image, as
+        // long as nothing is accessed in between.
+        assert_eq!(restored.save_to_bytes().unwrap(), image);
+
+        let mut contents = String::new();
+        restored
+            .new_open_options()
+            .read(true)
+            .open(path!("/foo/bar/baz.txt"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello", "the file contents are restored");
+
+        let times = |fs: &FileSystem| {
+            fs.metadata(path!("/foo/bar/baz.txt"))
+                .map(|metadata| (metadata.len, metadata.created, metadata.modified))
+        };
+        assert_eq!(times(&restored), times(&fs), "the metadata are restored");
+        let permissions = |fs: &FileSystem| {
+            fs.metadata(path!("/foo/bar/baz.txt"))
+                .map(|metadata| (metadata.uid, metadata.gid, metadata.mode))
+        };
+        assert_eq!(
+            permissions(&restored),
+            Ok((1000, 100, 0o600)),
+            "the ownership and the permissions are restored"
+        );
+        assert!(restored.metadata(path!("/foo/bar")).unwrap().is_dir());
+    }
+
+    #[test]
+    fn test_save_and_load_hard_links() {
+        let fs = FileSystem::default();
+        // The hard link precedes the file it points to in the image.
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/qux")), Ok(()));
+        fs.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/qux/bar.txt"))
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        assert_eq!(
+            fs.link(path!("/qux/bar.txt"), path!("/foo/baz.txt")),
+            Ok(())
+        );
+
+        let image = fs.save_to_bytes().unwrap();
+        let restored = FileSystem::load_from_bytes(&image).unwrap();
+
+        assert_eq!(
+            restored.remove_file(path!("/qux/bar.txt")),
+            Ok(()),
+            "removing the original name"
+        );
+        let mut contents = String::new();
+        restored
+            .new_open_options()
+            .read(true)
+            .open(path!("/foo/baz.txt"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello", "the hard link is restored");
+    }
+
+    #[test]
+    fn test_load_version_2_image() {
+        let mut image = b"\0wfs\x02".to_vec();
+        // The root directory, without children.
+        image.push(0);
+        image.extend_from_slice(&1u64.to_le_bytes());
+        image.extend_from_slice(b"/");
+        for time in [1, 2, 3] {
+            image.extend_from_slice(&(time as u64).to_le_bytes());
+        }
+        image.extend_from_slice(&0u64.to_le_bytes());
+
+        let restored = FileSystem::load_from_bytes(&image).unwrap();
+        let metadata = restored.metadata(path!("/")).unwrap();
+        assert_eq!(
+            metadata.mode, 0o755,
+            "the directory gets the default permissions"
+        );
+        assert_eq!(
+            (metadata.accessed, metadata.created, metadata.modified),
+            (1_000_000_000, 2_000_000_000, 3_000_000_000),
+            "the times are converted to nanoseconds"
+        );
+    }
+
+    #[test]
+    fn test_load_invalid_image() {
+        let image = FileSystem::default().save_to_bytes().unwrap();
+
+        assert!(matches!(
+            FileSystem::load_from_bytes(b"not an image"),
+            Err(FsError::InvalidData)
+        ));
+        assert!(matches!(
+            FileSystem::load_from_bytes(&image[..image.len() - 1]),
+            Err(FsError::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load_deep_image() {
+        let mut image = b"\0wfs\x04".to_vec();
+        // Directories nested deeper than the stack would allow to
+        // recurse, the innermost one being empty.
+        let depth = 100_000;
+        for level in 0..depth {
+            image.push(0);
+            image.extend_from_slice(&1u64.to_le_bytes());
+            image.extend_from_slice(b"a");
+            image.extend_from_slice(&[0; 36]);
+            let count: u64 = if level + 1 < depth { 1 } else { 0 };
+            image.extend_from_slice(&count.to_le_bytes());
+        }
+
+        let restored = FileSystem::load_from_bytes(&image).unwrap();
+        assert_eq!(restored.save_to_bytes().unwrap(), image);
+    }
+}
@@ -0,0 +1,620 @@
+//! This module contains the journal of a [`FileSystem`], i.e. the log
+//! of its mutations.
+//!
+//! Once started with [`FileSystem::start_journal`], the journal
+//! records every mutation of the file system. The records are taken
+//! as a self-contained batch with [`FileSystem::take_journal`], which
+//! an embedder appends to a persistent storage (e.g. an IndexedDB
+//! object store in a browser), and the concatenation of the batches
+//! is replayed on startup with [`FileSystem::replay_journal`].
+//!
+//! Structural mutations are recorded as they happen. Writes to the
+//! files only mark them as modified: their contents are written once
+//! per batch, after the structural records, at their path at the time
+//! the batch is taken. All integers are little-endian:
+//!
+//! ```text
+//! journal  := batch*
+//! batch    := MAGIC VERSION:u8 count:u64 record*
+//! record   := kind:u8 path (path | mode:u32 | ids | times | contents)?
+//! path     := length:u64 bytes
+//! ids      := option(u32) option(u32)
+//! times    := option(u64) option(u64)
+//! contents := modified:u64 length:u64 bytes
+//! option(t):= 0:u8 | 1:u8 t
+//! ```
+//!
+//! Paths are written as UTF-8, and times are in nanoseconds since
+//! `UNIX_EPOCH`.
+
+use super::filesystem::FileSystemInner;
+use super::image::{write_bytes, write_u32, write_u64, Reader};
+use super::*;
+use crate::{FileSystem as _, FsError, Result, WatchEvent};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"\0wfj";
+const VERSION: u8 = 1;
+
+const CREATE_DIR: u8 = 0;
+const REMOVE_DIR: u8 = 1;
+const RENAME: u8 = 2;
+const REMOVE_FILE: u8 = 3;
+const SYMLINK: u8 = 4;
+const LINK: u8 = 5;
+const CLONE_FILE: u8 = 6;
+const CHMOD: u8 = 7;
+const CHOWN: u8 = 8;
+const SET_TIMES: u8 = 9;
+const CREATE_FILE: u8 = 10;
+const CONTENTS: u8 = 11;
+
+/// A mutation of the file system, replayed with the corresponding
+/// method of [`FileSystem`].
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Record {
+    CreateDir(PathBuf),
+    RemoveDir(PathBuf),
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    RemoveFile(PathBuf),
+    Symlink {
+        original: PathBuf,
+        link: PathBuf,
+    },
+    Link {
+        original: PathBuf,
+        link: PathBuf,
+    },
+    CloneFile {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Chmod {
+        path: PathBuf,
+        mode: u32,
+    },
+    Chown {
+        path: PathBuf,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    },
+    SetTimes {
+        path: PathBuf,
+        accessed: Option<u64>,
+        modified: Option<u64>,
+    },
+    CreateFile(PathBuf),
+    /// The whole contents of a file, and its modified time.
+    Contents {
+        path: PathBuf,
+        modified: u64,
+        data: Vec<u8>,
+    },
+}
+
+/// The mutations recorded since the journal was last taken.
+#[derive(Debug, Default)]
+pub(super) struct Journal {
+    records: Vec<Record>,
+    /// The files whose contents were modified.
+    dirty: HashSet<Inode>,
+}
+
+impl Record {
+    /// The change reported to the watchers, at the paths given by
+    /// `normalize`.
+    fn event(&self, normalize: impl Fn(&Path) -> PathBuf) -> Option<WatchEvent> {
+        Some(match self {
+            Self::CreateDir(path)
+            | Self::Symlink { link: path, .. }
+            | Self::Link { link: path, .. }
+            | Self::CreateFile(path) => WatchEvent::Created(normalize(path)),
+            Self::RemoveDir(path) | Self::RemoveFile(path) => WatchEvent::Removed(normalize(path)),
+            Self::Rename { from, to } => WatchEvent::Renamed {
+                from: normalize(from),
+                to: normalize(to),
+            },
+            Self::Chmod { path, .. } | Self::Chown { path, .. } | Self::SetTimes { path, .. } => {
+                WatchEvent::Modified(normalize(path))
+            }
+            // The destination of a clone is reported when created, and
+            // when its contents are modified.
+            Self::CloneFile { .. } | Self::Contents { .. } => return None,
+        })
+    }
+}
+
+impl FileSystemInner {
+    /// Record a mutation, if the journal is started, and report it
+    /// to the watchers.
+    pub(super) fn record(&mut self, record: impl FnOnce() -> Record) {
+        if self.journal.is_none() && self.watchers.is_empty() {
+            return;
+        }
+
+        let record = record();
+        if !self.watchers.is_empty() {
+            let event = record.event(|path| {
+                self.canonicalize_without_inode(path)
+                    .unwrap_or_else(|_| path.to_path_buf())
+            });
+            if let Some(event) = event {
+                self.watchers.notify(event);
+            }
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.records.push(record);
+        }
+    }
+
+    /// Mark the contents of the file represented by `inode` as
+    /// modified, if the journal is started, and report it to the
+    /// watchers.
+    pub(super) fn mark_dirty(&mut self, inode: Inode) {
+        if let Some(journal) = &mut self.journal {
+            journal.dirty.insert(inode);
+        }
+        if !self.watchers.is_empty() {
+            if let Some(path) = self.path_of(inode) {
+                self.watchers.notify(WatchEvent::Modified(path));
+            }
+        }
+    }
+
+    /// Report a change to the watchers, if any.
+    pub(super) fn notify(&mut self, event: impl FnOnce() -> WatchEvent) {
+        if !self.watchers.is_empty() {
+            self.watchers.notify(event());
+        }
+    }
+
+    /// Get the path of the node represented by `inode`, if it is
+    /// still in the tree.
+    pub(super) fn path_of(&self, mut inode: Inode) -> Option<PathBuf> {
+        let mut names = Vec::new();
+        while inode != ROOT_INODE {
+            names.push(self.storage.get(inode)?.name().to_os_string());
+            inode = self.as_child_get_parent_and_position(inode).ok()?.0;
+        }
+
+        let mut path = PathBuf::from("/");
+        path.extend(names.iter().rev());
+
+        Some(path)
+    }
+
+    /// Collect the path of the modified files reachable from the
+    /// directory represented by `inode`.
+    fn dirty_paths(
+        &self,
+        inode: Inode,
+        path: &Path,
+        dirty: &HashSet<Inode>,
+        paths: &mut Vec<(Inode, PathBuf)>,
+    ) {
+        if let Some(Node::Directory { children, .. }) = self.storage.get(inode) {
+            for child in children {
+                match self.storage.get(*child) {
+                    Some(Node::File { name, .. }) if dirty.contains(child) => {
+                        paths.push((*child, path.join(name)))
+                    }
+                    Some(Node::Directory { name, .. }) => {
+                        self.dirty_paths(*child, &path.join(name), dirty, paths)
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl FileSystem {
+    /// Starts recording the mutations of the file system in its
+    /// journal. It does nothing if the journal is already started.
+    pub fn start_journal(&self) -> Result<()> {
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        if fs.journal.is_none() {
+            fs.journal = Some(Journal::default());
+        }
+
+        Ok(())
+    }
+
+    /// Takes the mutations recorded since the last call, as a batch
+    /// to append to the previous ones.
+    ///
+    /// The journal must have been started with
+    /// [`FileSystem::start_journal`].
+    pub fn take_journal(&self) -> Result<Vec<u8>> {
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        let journal = fs.journal.as_mut().ok_or(FsError::InvalidInput)?;
+        let mut records = std::mem::take(&mut journal.records);
+        let dirty = std::mem::take(&mut journal.dirty);
+
+        // The modified files that were removed since are skipped.
+        let mut paths = Vec::new();
+        fs.dirty_paths(ROOT_INODE, Path::new("/"), &dirty, &mut paths);
+        for (inode, path) in paths {
+            if let Some(Node::File { file, metadata, .. }) = fs.storage.get(inode) {
+                records.push(Record::Contents {
+                    path,
+                    modified: metadata.modified,
+                    data: file.contents().into_owned(),
+                });
+            }
+        }
+
+        let mut batch = Vec::new();
+        batch.extend_from_slice(MAGIC);
+        batch.push(VERSION);
+        write_u64(records.len() as u64, &mut batch);
+        for record in &records {
+            write_record(record, &mut batch)?;
+        }
+
+        Ok(batch)
+    }
+
+    /// Replays the batches taken with [`FileSystem::take_journal`],
+    /// concatenated in the order they were taken.
+    ///
+    /// The replayed mutations aren't recorded in the journal, if
+    /// it is started.
+    pub fn replay_journal(&self, bytes: &[u8]) -> Result<()> {
+        let mut records = Vec::new();
+        let mut reader = Reader { bytes, version: 0 };
+        while !reader.bytes.is_empty() {
+            if reader.take(MAGIC.len())? != MAGIC {
+                return Err(FsError::InvalidData);
+            }
+            reader.version = reader.u8()?;
+            if reader.version != VERSION {
+                return Err(FsError::InvalidData);
+            }
+
+            for _ in 0..reader.u64()? {
+                records.push(read_record(&mut reader)?);
+            }
+        }
+
+        // Suspend the journal while replaying.
+        let journal = {
+            // Write lock.
+            let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+            fs.journal.take()
+        };
+
+        let result = records
+            .into_iter()
+            .try_for_each(|record| self.apply(record));
+
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+        fs.journal = journal;
+
+        result
+    }
+
+    fn apply(&self, record: Record) -> Result<()> {
+        match record {
+            Record::CreateDir(path) => self.create_dir(&path),
+            Record::RemoveDir(path) => self.remove_dir(&path),
+            Record::Rename { from, to } => self.rename(&from, &to),
+            Record::RemoveFile(path) => self.remove_file(&path),
+            Record::Symlink { original, link } => self.symlink(&original, &link),
+            Record::Link { original, link } => self.link(&original, &link),
+            Record::CloneFile { from, to } => self.clone_file(&from, &to),
+            Record::Chmod { path, mode } => self.chmod(&path, mode),
+            Record::Chown { path, uid, gid } => self.chown(&path, uid, gid),
+            Record::SetTimes {
+                path,
+                accessed,
+                modified,
+            } => self.set_times(&path, accessed, modified),
+            Record::CreateFile(path) => self
+                .new_open_options()
+                .write(true)
+                .create(true)
+                .open(&path)
+                .map(drop),
+            Record::Contents {
+                path,
+                modified,
+                data,
+            } => {
+                self.new_open_options()
+                    .write(true)
+                    .truncate(true)
+                    .open(&path)?
+                    .write_all(&data)
+                    .map_err(|_| FsError::UnknownError)?;
+
+                self.set_times(&path, None, Some(modified))
+            }
+        }
+    }
+}
+
+fn write_path(path: &Path, batch: &mut Vec<u8>) -> Result<()> {
+    let path = path.to_str().ok_or(FsError::InvalidInput)?;
+    write_bytes(path.as_bytes(), batch);
+
+    Ok(())
+}
+
+fn write_option<T>(value: Option<T>, write: fn(T, &mut Vec<u8>), batch: &mut Vec<u8>) {
+    match value {
+        Some(value) => {
+            batch.push(1);
+            write(value, batch);
+        }
+        None => batch.push(0),
+    }
+}
+
+fn write_record(record: &Record, batch: &mut Vec<u8>) -> Result<()> {
+    match record {
+        Record::CreateDir(path) => {
+            batch.push(CREATE_DIR);
+            write_path(path, batch)?;
+        }
+        Record::RemoveDir(path) => {
+            batch.push(REMOVE_DIR);
+            write_path(path, batch)?;
+        }
+        Record::Rename { from, to } => {
+            batch.push(RENAME);
+            write_path(from, batch)?;
+            write_path(to, batch)?;
+        }
+        Record::RemoveFile(path) => {
+            batch.push(REMOVE_FILE);
+            write_path(path, batch)?;
+        }
+        Record::Symlink { original, link } => {
+            batch.push(SYMLINK);
+            write_path(link, batch)?;
+            write_path(original, batch)?;
+        }
+        Record::Link { original, link } => {
+            batch.push(LINK);
+            write_path(link, batch)?;
+            write_path(original, batch)?;
+        }
+        Record::CloneFile { from, to } => {
+            batch.push(CLONE_FILE);
+            write_path(to, batch)?;
+            write_path(from, batch)?;
+        }
+        Record::Chmod { path, mode } => {
+            batch.push(CHMOD);
+            write_path(path, batch)?;
+            write_u32(*mode, batch);
+        }
+        Record::Chown { path, uid, gid } => {
+            batch.push(CHOWN);
+            write_path(path, batch)?;
+            write_option(*uid, write_u32, batch);
+            write_option(*gid, write_u32, batch);
+        }
+        Record::SetTimes {
+            path,
+            accessed,
+            modified,
+        } => {
+            batch.push(SET_TIMES);
+            write_path(path, batch)?;
+            write_option(*accessed, write_u64, batch);
+            write_option(*modified, write_u64, batch);
+        }
+        Record::CreateFile(path) => {
+            batch.push(CREATE_FILE);
+            write_path(path, batch)?;
+        }
+        Record::Contents {
+            path,
+            modified,
+            data,
+        } => {
+            batch.push(CONTENTS);
+            write_path(path, batch)?;
+            write_u64(*modified, batch);
+            write_bytes(data, batch);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_path(reader: &mut Reader) -> Result<PathBuf> {
+    let path = std::str::from_utf8(reader.bytes()?).map_err(|_| FsError::InvalidData)?;
+
+    Ok(PathBuf::from(path))
+}
+
+fn read_option<'a, T>(
+    reader: &mut Reader<'a>,
+    read: fn(&mut Reader<'a>) -> Result<T>,
+) -> Result<Option<T>> {
+    match reader.u8()? {
+        0 => Ok(None),
+        1 => Ok(Some(read(reader)?)),
+        _ => Err(FsError::InvalidData),
+    }
+}
+
+fn read_record(reader: &mut Reader) -> Result<Record> {
+    let kind = reader.u8()?;
+    let path = read_path(reader)?;
+
+    Ok(match kind {
+        CREATE_DIR => Record::CreateDir(path),
+        REMOVE_DIR => Record::RemoveDir(path),
+        RENAME => Record::Rename {
+            from: path,
+            to: read_path(reader)?,
+        },
+        REMOVE_FILE => Record::RemoveFile(path),
+        SYMLINK => Record::Symlink {
+            link: path,
+            original: read_path(reader)?,
+        },
+        LINK => Record::Link {
+            link: path,
+            original: read_path(reader)?,
+        },
+        CLONE_FILE => Record::CloneFile {
+            to: path,
+            from: read_path(reader)?,
+        },
+        CHMOD => Record::Chmod {
+            path,
+            mode: reader.u32()?,
+        },
+        CHOWN => Record::Chown {
+            path,
+            uid: read_option(reader, Reader::u32)?,
+            gid: read_option(reader, Reader::u32)?,
+        },
+        SET_TIMES => Record::SetTimes {
+            path,
+            accessed: read_option(reader, Reader::u64)?,
+            modified: read_option(reader, Reader::u64)?,
+        },
+        CREATE_FILE => Record::CreateFile(path),
+        CONTENTS => Record::Contents {
+            path,
+            modified: reader.u64()?,
+            data: reader.bytes()?.to_vec(),
+        },
+        _ => return Err(FsError::InvalidData),
+    })
+}
+
+#[cfg(test)]
+mod test_journal {
+    use crate::{mem_fs::*, FileSystem as FS, FsError};
+    use std::io::{Read, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    fn read(fs: &FileSystem, path: &str) -> String {
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!(path))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+
+        contents
+    }
+
+    #[test]
+    fn test_replay_journal() {
+        let fs = FileSystem::default();
+        assert_eq!(fs.start_journal(), Ok(()));
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap();
+        file.write_all(b"hello").unwrap();
+        assert_eq!(fs.chmod(path!("/foo/bar.txt"), 0o600), Ok(()));
+        let first = fs.take_journal().unwrap();
+
+        // The file is written again, then moved.
+        file.write_all(b", world").unwrap();
+        assert_eq!(fs.create_dir(path!("/qux")), Ok(()));
+        assert_eq!(
+            fs.rename(path!("/foo/bar.txt"), path!("/qux/baz.txt")),
+            Ok(())
+        );
+        assert_eq!(fs.remove_dir(path!("/foo")), Ok(()));
+        assert_eq!(fs.symlink(path!("baz.txt"), path!("/qux/link")), Ok(()));
+        let second = fs.take_journal().unwrap();
+
+        let restored = FileSystem::default();
+        assert_eq!(restored.replay_journal(&[first, second].concat()), Ok(()));
+
+        assert_eq!(read(&restored, "/qux/baz.txt"), "hello, world");
+        assert_eq!(read(&restored, "/qux/link"), "hello, world");
+        assert!(restored.metadata(path!("/foo")).is_err());
+        let metadata = |fs: &FileSystem| {
+            fs.metadata(path!("/qux/baz.txt"))
+                .map(|metadata| (metadata.len, metadata.modified, metadata.mode))
+        };
+        assert_eq!(metadata(&restored), metadata(&fs));
+    }
+
+    #[test]
+    fn test_removed_files_are_skipped() {
+        let fs = FileSystem::default();
+        assert_eq!(fs.start_journal(), Ok(()));
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .unwrap();
+        file.write_all(b"hello").unwrap();
+        file.unlink().unwrap();
+        fs.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .unwrap()
+            .write_all(b"bye")
+            .unwrap();
+
+        let restored = FileSystem::default();
+        assert_eq!(restored.replay_journal(&fs.take_journal().unwrap()), Ok(()));
+        assert_eq!(read(&restored, "/foo.txt"), "bye");
+    }
+
+    #[test]
+    fn test_replay_is_not_recorded() {
+        let fs = FileSystem::default();
+        assert_eq!(
+            fs.take_journal(),
+            Err(FsError::InvalidInput),
+            "the journal isn't started"
+        );
+        assert_eq!(fs.start_journal(), Ok(()));
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        let batch = fs.take_journal().unwrap();
+
+        let restored = FileSystem::default();
+        assert_eq!(restored.start_journal(), Ok(()));
+        assert_eq!(restored.replay_journal(&batch), Ok(()));
+        assert!(restored.metadata(path!("/foo")).unwrap().is_dir());
+        assert_eq!(
+            restored.take_journal().unwrap(),
+            [&b"\0wfj\x01"[..], &0u64.to_le_bytes()].concat(),
+            "the replayed mutations aren't recorded"
+        );
+
+        assert_eq!(
+            restored.replay_journal(&batch[..batch.len() - 1]),
+            Err(FsError::InvalidData)
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Quotas of the file system, see [`Limits`].
+
+use super::filesystem::FileSystemInner;
+use super::*;
+use crate::{FsError, Result};
+use std::path::{Component, Path};
+
+/// The limits enforced by a [`FileSystem`]. `None` means unlimited,
+/// which is the default.
+///
+/// An operation which would exceed a limit fails with
+/// [`FsError::QuotaExceeded`]. Lowering a limit below the current
+/// usage removes nothing, but the usage can't grow anymore.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum size of the contents of all the files, in bytes.
+    pub max_total_bytes: Option<u64>,
+    /// The maximum size of a file, in bytes.
+    pub max_file_size: Option<u64>,
+    /// The maximum number of nodes: files, directories, symlinks and
+    /// hard links, the root directory included.
+    pub max_inodes: Option<usize>,
+    /// The maximum number of components of a path below the root,
+    /// e.g. `/a/b` is 2 deep.
+    pub max_depth: Option<usize>,
+}
+
+impl FileSystem {
+    /// Creates an empty file system enforcing `limits`.
+    pub fn with_limits(limits: Limits) -> Self {
+        let fs = Self::default();
+        fs.inner.write().unwrap().limits = limits;
+
+        fs
+    }
+
+    /// Gets the limits enforced by the file system.
+    pub fn limits(&self) -> Result<Limits> {
+        let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+        Ok(fs.limits)
+    }
+
+    /// Changes the limits enforced by the file system.
+    pub fn set_limits(&self, limits: Limits) -> Result<()> {
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+        fs.limits = limits;
+
+        Ok(())
+    }
+
+    /// Gets the size of the contents of all the files, in bytes, as
+    /// counted against [`Limits::max_total_bytes`].
+    pub fn used_bytes(&self) -> Result<u64> {
+        let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+        Ok(fs.used_bytes)
+    }
+}
+
+impl FileSystemInner {
+    /// Check a new node can be added at `path`, which must be
+    /// canonical.
+    pub(super) fn check_new_node(&self, path: &Path) -> Result<()> {
+        if let Some(max_inodes) = self.limits.max_inodes {
+            if self.storage.len() >= max_inodes {
+                return Err(FsError::QuotaExceeded);
+            }
+        }
+
+        self.check_depth(path, 0)
+    }
+
+    /// Check the node represented by `inode`, along with its
+    /// descendants, can be moved to `path`, which must be canonical.
+    pub(super) fn check_move(&self, path: &Path, inode: Inode) -> Result<()> {
+        if self.limits.max_depth.is_none() {
+            return Ok(());
+        }
+
+        self.check_depth(path, self.height(inode))
+    }
+
+    /// Check a file can be resized from `old_len` to `new_len` bytes.
+    /// Shrinking a file is always possible.
+    pub(super) fn check_resize(&self, old_len: u64, new_len: u64) -> Result<()> {
+        if new_len <= old_len {
+            return Ok(());
+        }
+
+        if let Some(max_file_size) = self.limits.max_file_size {
+            if new_len > max_file_size {
+                return Err(FsError::QuotaExceeded);
+            }
+        }
+
+        if let Some(max_total_bytes) = self.limits.max_total_bytes {
+            if self.used_bytes - old_len + new_len > max_total_bytes {
+                return Err(FsError::QuotaExceeded);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Account for a file resized from `old_len` to `new_len` bytes.
+    pub(super) fn resized(&mut self, old_len: u64, new_len: u64) {
+        self.used_bytes = self.used_bytes - old_len + new_len;
+    }
+
+    /// Remove the node represented by `inode` from the storage,
+    /// releasing the bytes of its contents.
+    pub(super) fn remove_node(&mut self, inode: Inode) -> Node {
+        let node = self.storage.remove(inode);
+
+        if let Node::File { metadata, .. } = &node {
+            self.used_bytes -= metadata.len;
+        }
+
+        node
+    }
+
+    /// Check a node with descendants `height` levels below it can be
+    /// at `path`.
+    fn check_depth(&self, path: &Path, height: usize) -> Result<()> {
+        if let Some(max_depth) = self.limits.max_depth {
+            let depth = path
+                .components()
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .count();
+
+            if depth + height > max_depth {
+                return Err(FsError::QuotaExceeded);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The number of levels of descendants of the node represented by
+    /// `inode`.
+    fn height(&self, inode: Inode) -> usize {
+        match self.storage.get(inode) {
+            Some(Node::Directory { children, .. }) => children
+                .iter()
+                .map(|child| self.height(*child) + 1)
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_limits {
+    use crate::{mem_fs::*, FileSystem as FS, FsError};
+    use std::io::{ErrorKind, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let fs = FileSystem::with_limits(Limits {
+            max_depth: Some(2),
+            ..Default::default()
+        });
+
+        assert_eq!(fs.create_dir(path!("/a")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/a/b")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/a/b/c")), Err(FsError::QuotaExceeded));
+        assert_eq!(
+            fs.symlink(path!("/a"), path!("/a/b/c")),
+            Err(FsError::QuotaExceeded)
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .create(true)
+                .open(path!("/a/b/c"))
+                .map(|_| ()),
+            Err(FsError::QuotaExceeded)
+        );
+
+        // Moving a directory moves its descendants too.
+        assert_eq!(fs.create_dir(path!("/d")), Ok(()));
+        assert_eq!(
+            fs.rename(path!("/a"), path!("/d/a")),
+            Err(FsError::QuotaExceeded)
+        );
+        assert_eq!(fs.rename(path!("/a/b"), path!("/d/b")), Ok(()));
+    }
+
+    #[test]
+    fn test_max_inodes() {
+        // The root directory is a node.
+        let fs = FileSystem::with_limits(Limits {
+            max_inodes: Some(3),
+            ..Default::default()
+        });
+
+        assert_eq!(fs.create_dir(path!("/a")), Ok(()));
+        assert!(fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/a/f"))
+            .is_ok());
+        assert_eq!(
+            fs.link(path!("/a/f"), path!("/a/g")),
+            Err(FsError::QuotaExceeded)
+        );
+        assert_eq!(
+            fs.clone_file(path!("/a/f"), path!("/a/g")),
+            Err(FsError::QuotaExceeded)
+        );
+        assert_eq!(fs.create_dir(path!("/b")), Err(FsError::QuotaExceeded));
+
+        // Removing a node frees its room.
+        assert_eq!(fs.remove_file(path!("/a/f")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/b")), Ok(()));
+    }
+
+    #[test]
+    fn test_max_file_size() {
+        let fs = FileSystem::with_limits(Limits {
+            max_file_size: Some(4),
+            ..Default::default()
+        });
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/f"))
+            .unwrap();
+
+        assert_eq!(file.write(b"abc").unwrap(), 3);
+        let error = file.write(b"de").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Other);
+        assert_eq!(
+            error.into_inner().unwrap().downcast_ref::<FsError>(),
+            Some(&FsError::QuotaExceeded)
+        );
+        assert_eq!(file.set_len(5), Err(FsError::QuotaExceeded));
+        assert_eq!(file.set_len(4), Ok(()));
+        assert_eq!(file.size(), 4);
+    }
+
+    #[test]
+    fn test_max_total_bytes() {
+        let fs = FileSystem::with_limits(Limits {
+            max_total_bytes: Some(8),
+            ..Default::default()
+        });
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/f"))
+            .unwrap();
+        assert_eq!(file.write(b"abcdef").unwrap(), 6);
+
+        // The clones of a file count as much as the file.
+        assert_eq!(
+            fs.clone_file(path!("/f"), path!("/g")),
+            Err(FsError::QuotaExceeded)
+        );
+
+        let mut other = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/g"))
+            .unwrap();
+        assert_eq!(other.write(b"gh").unwrap(), 2);
+        assert!(other.write(b"i").is_err());
+
+        // Lowering the limit keeps the files as they are.
+        assert_eq!(
+            fs.set_limits(Limits {
+                max_total_bytes: Some(4),
+                ..Default::default()
+            }),
+            Ok(())
+        );
+        assert_eq!(file.set_len(3), Ok(()));
+        assert_eq!(file.set_len(4), Err(FsError::QuotaExceeded));
+
+        // Unlimited again.
+        assert_eq!(fs.set_limits(Limits::default()), Ok(()));
+        assert_eq!(file.set_len(100), Ok(()));
+    }
+
+    #[test]
+    fn test_used_bytes() {
+        let fs = FileSystem::default();
+        let open = |path: &str| {
+            fs.new_open_options()
+                .write(true)
+                .create(true)
+                .open(path!(path))
+                .unwrap()
+        };
+
+        let mut file = open("/f");
+        assert_eq!(file.write(b"abcdef").unwrap(), 6);
+        assert_eq!(file.set_len(4), Ok(()));
+        assert_eq!(fs.used_bytes(), Ok(4));
+
+        assert_eq!(fs.clone_file(path!("/f"), path!("/g")), Ok(()));
+        assert_eq!(fs.used_bytes(), Ok(8));
+
+        // Replacing a file releases its contents.
+        assert_eq!(open("/h").write(b"ab").unwrap(), 2);
+        assert_eq!(fs.clone_file(path!("/h"), path!("/g")), Ok(()));
+        assert_eq!(fs.used_bytes(), Ok(8));
+        assert_eq!(fs.rename(path!("/h"), path!("/g")), Ok(()));
+        assert_eq!(fs.used_bytes(), Ok(6));
+
+        // Truncating on open.
+        fs.new_open_options()
+            .write(true)
+            .truncate(true)
+            .open(path!("/g"))
+            .unwrap();
+        assert_eq!(fs.used_bytes(), Ok(4));
+
+        // An unlinked file counts until its last handle is closed.
+        assert_eq!(fs.remove_file(path!("/f")), Ok(()));
+        assert_eq!(fs.used_bytes(), Ok(4));
+        drop(file);
+        assert_eq!(fs.used_bytes(), Ok(0));
+    }
+}
@@ -1,19 +1,45 @@
+/// Emits a `tracing` event when a node is inserted in, or removed
+/// from, the storage, if the `mem-fs-tracing` feature is enabled.
+macro_rules! trace_node {
+    ($action:literal, $inode:expr) => {
+        #[cfg(feature = "mem-fs-tracing")]
+        tracing::trace!(inode = $inode, $action);
+    };
+}
+
 mod file;
 mod file_opener;
 mod filesystem;
+mod gc;
+mod image;
+mod journal;
+mod limits;
 mod stdio;
+#[cfg(feature = "mem-fs-tar")]
+mod tarball;
 
 use file::{File, FileHandle};
 pub use file_opener::FileOpener;
 pub use filesystem::FileSystem;
-pub use stdio::{Stderr, Stdin, Stdout};
+pub use gc::GcReport;
+pub use limits::Limits;
+pub use stdio::{
+    CallbackSink, ChannelSink, ChannelSource, LineDiscipline, RingBuffer, Stderr, Stdin, StdioSink,
+    StdioSource, Stdout,
+};
 
-use crate::Metadata;
+use crate::{time, Metadata};
 use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
 
 type Inode = usize;
 const ROOT_INODE: Inode = 0;
 
+/// The permission bits of new files, directories and symlinks.
+const FILE_MODE: u32 = 0o644;
+const DIRECTORY_MODE: u32 = 0o755;
+const SYMLINK_MODE: u32 = 0o777;
+
 #[derive(Debug)]
 enum Node {
     File {
@@ -21,6 +47,9 @@ enum Node {
         name: OsString,
         file: File,
         metadata: Metadata,
+        /// The number of handles opened on the file. The node is
+        /// kept, even unlinked, as long as some remain.
+        open_handles: usize,
     },
     Directory {
         inode: Inode,
@@ -28,6 +57,19 @@ enum Node {
         children: Vec<Inode>,
         metadata: Metadata,
     },
+    Symlink {
+        inode: Inode,
+        name: OsString,
+        target: PathBuf,
+        metadata: Metadata,
+    },
+    /// An additional name of the file `target`, which holds the
+    /// contents and the metadata.
+    HardLink {
+        inode: Inode,
+        name: OsString,
+        target: Inode,
+    },
 }
 
 impl Node {
@@ -35,6 +77,8 @@ impl Node {
         *match self {
             Self::File { inode, .. } => inode,
             Self::Directory { inode, .. } => inode,
+            Self::Symlink { inode, .. } => inode,
+            Self::HardLink { inode, .. } => inode,
         }
     }
 
@@ -42,13 +86,19 @@ impl Node {
         match self {
             Self::File { name, .. } => name.as_os_str(),
             Self::Directory { name, .. } => name.as_os_str(),
+            Self::Symlink { name, .. } => name.as_os_str(),
+            Self::HardLink { name, .. } => name.as_os_str(),
         }
     }
 
+    /// The metadata of the node. Hard links have none, they must be
+    /// followed to their target first.
     fn metadata(&self) -> &Metadata {
         match self {
             Self::File { metadata, .. } => metadata,
             Self::Directory { metadata, .. } => metadata,
+            Self::Symlink { metadata, .. } => metadata,
+            Self::HardLink { .. } => unreachable!("hard links must be followed"),
         }
     }
 
@@ -56,6 +106,8 @@ impl Node {
         match self {
             Self::File { metadata, .. } => metadata,
             Self::Directory { metadata, .. } => metadata,
+            Self::Symlink { metadata, .. } => metadata,
+            Self::HardLink { .. } => unreachable!("hard links must be followed"),
         }
     }
 
@@ -63,27 +115,12 @@ impl Node {
         match self {
             Self::File { name, .. } => *name = new_name,
             Self::Directory { name, .. } => *name = new_name,
+            Self::Symlink { name, .. } => *name = new_name,
+            Self::HardLink { name, .. } => *name = new_name,
         }
     }
 }
 
-fn time() -> u64 {
-    #[cfg(not(feature = "no-time"))]
-    {
-        // SAFETY: It's very unlikely that the system returns a time that
-        // is before `UNIX_EPOCH` :-).
-        std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-    }
-
-    #[cfg(feature = "no-time")]
-    {
-        0
-    }
-}
-
 // If the `host-fs` feature is not enabled, let's write a
 // `TryInto<i32>` implementation for `FileDescriptor`, otherwise on
 // Unix, it conflicts with `TryInto<RawFd>` (where `RawFd` is an alias
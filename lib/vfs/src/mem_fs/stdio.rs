@@ -1,8 +1,315 @@
 //! This module contains the standard I/O streams, i.e. “emulated”
 //! `stdin`, `stdout` and `stderr`.
+//!
+//! By default, a stream is a buffer: the bytes written to `Stdout` and
+//! `Stderr` are kept in `buf`, and the bytes read from `Stdin` are taken
+//! from it. A stream can instead be plugged to a [`StdioSink`] or a
+//! [`StdioSource`], like a callback, a channel or a [`RingBuffer`], and
+//! given a [`LineDiscipline`], so that a terminal can be wired to it.
 
 use crate::{FileDescriptor, FsError, Result, VirtualFile};
+use std::collections::VecDeque;
+use std::fmt;
 use std::io::{self, Read, Seek, Write};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+
+/// The number of bytes pulled at once from a source.
+const CHUNK_SIZE: usize = 4096;
+
+/// Where the bytes written to `Stdout` or `Stderr`, or echoed by
+/// `Stdin`, go.
+pub trait StdioSink: fmt::Debug + Send + Sync {
+    /// Writes all of `data`.
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Flushes the bytes written so far.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Where the bytes read from `Stdin` come from.
+pub trait StdioSource: fmt::Debug + Send + Sync {
+    /// Reads the bytes available into `buf`, without blocking;
+    /// returns 0 if there are none.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+/// How the bytes going through a stream are transformed, like the
+/// line discipline of a terminal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineDiscipline {
+    /// Whether the bytes read from `Stdin` are written to its echo
+    /// sink, see [`Stdin::set_echo`].
+    pub echo: bool,
+    /// Whether the bytes are passed on line by line: `Stdin` delivers
+    /// the input once a line is complete, and `Stdout` and `Stderr`
+    /// give whole lines to their sink, flushing it after each.
+    pub line_buffered: bool,
+    /// Whether the line endings are translated: `\n` is written as
+    /// `\r\n`, and `\r` or `\r\n` is read as `\n`.
+    pub crlf: bool,
+}
+
+type Callback = Box<dyn FnMut(&[u8]) + Send + Sync>;
+
+/// A sink calling a function with the bytes written.
+pub struct CallbackSink(Callback);
+
+impl CallbackSink {
+    /// Creates a sink calling `callback`.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: FnMut(&[u8]) + Send + Sync + 'static,
+    {
+        Self(Box::new(callback))
+    }
+}
+
+impl fmt::Debug for CallbackSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CallbackSink").finish()
+    }
+}
+
+impl StdioSink for CallbackSink {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        (self.0)(data);
+        Ok(())
+    }
+}
+
+/// A sink sending the bytes written to a channel, one message per
+/// write.
+#[derive(Debug)]
+pub struct ChannelSink(Mutex<Sender<Vec<u8>>>);
+
+impl ChannelSink {
+    /// Creates a sink sending to `sender`.
+    pub fn new(sender: Sender<Vec<u8>>) -> Self {
+        Self(Mutex::new(sender))
+    }
+}
+
+impl StdioSink for ChannelSink {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.0
+            .get_mut()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "poisoned channel"))?
+            .send(data.to_vec())
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+/// A source receiving the bytes to read from a channel.
+#[derive(Debug)]
+pub struct ChannelSource {
+    receiver: Mutex<Receiver<Vec<u8>>>,
+    /// The part of the last message that wasn't read yet.
+    pending: Vec<u8>,
+}
+
+impl ChannelSource {
+    /// Creates a source receiving from `receiver`.
+    pub fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver: Mutex::new(receiver),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl StdioSource for ChannelSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            let receiver = self
+                .receiver
+                .get_mut()
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "poisoned channel"))?;
+            match receiver.try_recv() {
+                Ok(message) => self.pending = message,
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return Ok(0),
+            }
+        }
+
+        let length = buf.len().min(self.pending.len());
+        buf[..length].copy_from_slice(&self.pending[..length]);
+        self.pending.drain(..length);
+        Ok(length)
+    }
+}
+
+/// A bounded queue of bytes shared by its clones, both a sink and a
+/// source: the host can write the input of `Stdin` to a clone, or read
+/// the output of `Stdout` from one. Once full, the oldest bytes are
+/// dropped to make room for the new ones.
+#[derive(Debug, Clone)]
+pub struct RingBuffer {
+    bytes: Arc<Mutex<VecDeque<u8>>>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    /// Creates an empty buffer holding at most `capacity` bytes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            bytes: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// The number of bytes in the buffer.
+    pub fn len(&self) -> usize {
+        self.bytes.lock().map(|bytes| bytes.len()).unwrap_or(0)
+    }
+
+    /// Whether the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, VecDeque<u8>>> {
+        self.bytes
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "poisoned ring buffer"))
+    }
+}
+
+impl StdioSink for RingBuffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut bytes = self.lock()?;
+        let data = &data[data.len().saturating_sub(self.capacity)..];
+        let overflow = (bytes.len() + data.len()).saturating_sub(self.capacity);
+        bytes.drain(..overflow);
+        bytes.extend(data);
+        Ok(())
+    }
+}
+
+impl StdioSource for RingBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut bytes = self.lock()?;
+        let length = buf.len().min(bytes.len());
+        for (byte, read) in buf.iter_mut().zip(bytes.drain(..length)) {
+            *byte = read;
+        }
+        Ok(length)
+    }
+}
+
+/// Where the bytes of a stream come from or go, and how they are
+/// transformed on the way.
+#[derive(Debug, Default)]
+struct Plumbing {
+    source: Option<Box<dyn StdioSource>>,
+    sink: Option<Box<dyn StdioSink>>,
+    echo: Option<Box<dyn StdioSink>>,
+    discipline: LineDiscipline,
+    /// The incomplete line held back by the line discipline.
+    line: Vec<u8>,
+    /// Whether the last byte read was a `\r` turned into a `\n`.
+    after_cr: bool,
+}
+
+impl Plumbing {
+    /// Pulls the bytes available from the source into `buf`.
+    fn fill(&mut self, buf: &mut Vec<u8>) -> io::Result<()> {
+        let mut chunk = [0; CHUNK_SIZE];
+        loop {
+            let length = match self.source.as_mut() {
+                Some(source) => source.read(&mut chunk)?,
+                None => return Ok(()),
+            };
+            if length == 0 {
+                return Ok(());
+            }
+
+            let mut input = Vec::with_capacity(length);
+            for &byte in &chunk[..length] {
+                match byte {
+                    b'\r' if self.discipline.crlf => input.push(b'\n'),
+                    b'\n' if self.discipline.crlf && self.after_cr => {}
+                    byte => input.push(byte),
+                }
+                self.after_cr = byte == b'\r';
+            }
+
+            if self.discipline.echo {
+                if let Some(echo) = self.echo.as_mut() {
+                    echo.write(&translate_newlines(&input, self.discipline.crlf))?;
+                    echo.flush()?;
+                }
+            }
+
+            if self.discipline.line_buffered {
+                self.line.extend_from_slice(&input);
+                if let Some(end) = self.line.iter().rposition(|&byte| byte == b'\n') {
+                    buf.extend(self.line.drain(..=end));
+                }
+            } else {
+                buf.extend_from_slice(&input);
+            }
+        }
+    }
+
+    /// Writes `data` to the sink, or to `buf` if there's none.
+    fn write(&mut self, buf: &mut Vec<u8>, data: &[u8]) -> io::Result<()> {
+        let output = translate_newlines(data, self.discipline.crlf);
+
+        if self.discipline.line_buffered {
+            self.line.extend_from_slice(&output);
+            if let Some(end) = self.line.iter().rposition(|&byte| byte == b'\n') {
+                let lines = self.line.drain(..=end).collect::<Vec<_>>();
+                self.emit(buf, &lines)?;
+                if let Some(sink) = self.sink.as_mut() {
+                    sink.flush()?;
+                }
+            }
+            Ok(())
+        } else {
+            self.emit(buf, &output)
+        }
+    }
+
+    /// Writes the incomplete line, and flushes the sink.
+    fn flush(&mut self, buf: &mut Vec<u8>) -> io::Result<()> {
+        let line = std::mem::take(&mut self.line);
+        if !line.is_empty() {
+            self.emit(buf, &line)?;
+        }
+        match self.sink.as_mut() {
+            Some(sink) => sink.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn emit(&mut self, buf: &mut Vec<u8>, data: &[u8]) -> io::Result<()> {
+        match self.sink.as_mut() {
+            Some(sink) => sink.write(data),
+            None => {
+                buf.extend_from_slice(data);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Writes each `\n` of `data` as `\r\n` if `crlf`.
+fn translate_newlines(data: &[u8], crlf: bool) -> Vec<u8> {
+    if !crlf {
+        return data.to_vec();
+    }
+
+    let mut translated = Vec::with_capacity(data.len());
+    for &byte in data {
+        if byte == b'\n' {
+            translated.push(b'\r');
+        }
+        translated.push(byte);
+    }
+    translated
+}
 
 macro_rules! impl_virtualfile_on_std_streams {
     ($name:ident { readable: $readable:expr, writable: $writable:expr $(,)* }) => {
@@ -11,15 +318,19 @@ macro_rules! impl_virtualfile_on_std_streams {
         #[derive(Debug, Default)]
         pub struct $name {
             pub buf: Vec<u8>,
+            plumbing: Plumbing,
         }
 
         impl $name {
-            const fn is_readable(&self) -> bool {
-                $readable
+            /// Sets how the bytes going through the stream are
+            /// transformed.
+            pub fn set_line_discipline(&mut self, discipline: LineDiscipline) {
+                self.plumbing.discipline = discipline;
             }
 
-            const fn is_writable(&self) -> bool {
-                $writable
+            /// The line discipline of the stream.
+            pub fn line_discipline(&self) -> LineDiscipline {
+                self.plumbing.discipline
             }
         }
 
@@ -55,6 +366,18 @@ macro_rules! impl_virtualfile_on_std_streams {
             fn get_fd(&self) -> Option<FileDescriptor> {
                 None
             }
+
+            fn is_readable(&self) -> bool {
+                $readable
+            }
+
+            fn is_writable(&self) -> bool {
+                $writable
+            }
+
+            fn is_seekable(&self) -> bool {
+                false
+            }
         }
 
         impl_virtualfile_on_std_streams!(impl Seek for $name);
@@ -77,6 +400,7 @@ macro_rules! impl_virtualfile_on_std_streams {
         impl Read for $name {
             fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
                 if self.is_readable() {
+                    self.plumbing.fill(&mut self.buf)?;
                     let length = self.buf.as_slice().read(buf)?;
 
                     // Remove what has been consumed.
@@ -93,6 +417,7 @@ macro_rules! impl_virtualfile_on_std_streams {
 
             fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
                 if self.is_readable() {
+                    self.plumbing.fill(&mut self.buf)?;
                     let length = self.buf.as_slice().read_to_end(buf)?;
 
                     // Remove what has been consumed.
@@ -109,6 +434,7 @@ macro_rules! impl_virtualfile_on_std_streams {
 
             fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
                 if self.is_readable() {
+                    self.plumbing.fill(&mut self.buf)?;
                     let length = self.buf.as_slice().read_to_string(buf)?;
 
                     // Remove what has been consumed.
@@ -125,6 +451,7 @@ macro_rules! impl_virtualfile_on_std_streams {
 
             fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
                 if self.is_readable() {
+                    self.plumbing.fill(&mut self.buf)?;
                     self.buf.as_slice().read_exact(buf)?;
 
                     self.buf.drain(..buf.len());
@@ -144,7 +471,8 @@ macro_rules! impl_virtualfile_on_std_streams {
         impl Write for $name {
             fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
                 if self.is_writable() {
-                    self.buf.write(buf)
+                    self.plumbing.write(&mut self.buf, buf)?;
+                    Ok(buf.len())
                 } else {
                     Err(io::Error::new(
                         io::ErrorKind::PermissionDenied,
@@ -155,7 +483,7 @@ macro_rules! impl_virtualfile_on_std_streams {
 
             fn flush(&mut self) -> io::Result<()> {
                 if self.is_writable() {
-                    self.buf.flush()
+                    self.plumbing.flush(&mut self.buf)
                 } else {
                     Err(io::Error::new(
                         io::ErrorKind::PermissionDenied,
@@ -166,7 +494,7 @@ macro_rules! impl_virtualfile_on_std_streams {
 
             fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
                 if self.is_writable() {
-                    self.buf.write_all(buf)
+                    self.plumbing.write(&mut self.buf, buf)
                 } else {
                     Err(io::Error::new(
                         io::ErrorKind::PermissionDenied,
@@ -176,6 +504,15 @@ macro_rules! impl_virtualfile_on_std_streams {
             }
         }
     };
+
+    (impl sink for $name:ident) => {
+        impl $name {
+            /// Sends the bytes written to `sink` rather than to `buf`.
+            pub fn set_sink(&mut self, sink: Box<dyn StdioSink>) {
+                self.plumbing.sink = Some(sink);
+            }
+        }
+    };
 }
 
 impl_virtualfile_on_std_streams!(Stdin {
@@ -190,16 +527,33 @@ impl_virtualfile_on_std_streams!(Stderr {
     readable: false,
     writable: true,
 });
+impl_virtualfile_on_std_streams!(impl sink for Stdout);
+impl_virtualfile_on_std_streams!(impl sink for Stderr);
+
+impl Stdin {
+    /// Reads the bytes from `source`, once those of `buf` are read.
+    pub fn set_source(&mut self, source: Box<dyn StdioSource>) {
+        self.plumbing.source = Some(source);
+    }
+
+    /// Writes the bytes read to `echo` when the line discipline says
+    /// so, usually the sink of `Stdout`.
+    pub fn set_echo(&mut self, echo: Box<dyn StdioSink>) {
+        self.plumbing.echo = Some(echo);
+    }
+}
 
 #[cfg(test)]
 mod test_read_write_seek {
     use crate::mem_fs::*;
     use std::io::{self, Read, Seek, Write};
+    use std::sync::{mpsc, Arc, Mutex};
 
     #[test]
     fn test_read_stdin() {
         let mut stdin = Stdin {
             buf: vec![b'f', b'o', b'o', b'b', b'a', b'r'],
+            ..Default::default()
         };
         let mut buffer = [0; 3];
 
@@ -235,7 +589,7 @@ mod test_read_write_seek {
 
     #[test]
     fn test_write_stdin() {
-        let mut stdin = Stdin { buf: vec![] };
+        let mut stdin = Stdin::default();
 
         assert!(stdin.write(b"bazqux").is_err(), "cannot write into `stdin`");
     }
@@ -244,6 +598,7 @@ mod test_read_write_seek {
     fn test_seek_stdin() {
         let mut stdin = Stdin {
             buf: vec![b'f', b'o', b'o', b'b', b'a', b'r'],
+            ..Default::default()
         };
 
         assert!(
@@ -256,6 +611,7 @@ mod test_read_write_seek {
     fn test_read_stdout() {
         let mut stdout = Stdout {
             buf: vec![b'f', b'o', b'o', b'b', b'a', b'r'],
+            ..Default::default()
         };
         let mut buffer = String::new();
 
@@ -267,7 +623,7 @@ mod test_read_write_seek {
 
     #[test]
     fn test_write_stdout() {
-        let mut stdout = Stdout { buf: vec![] };
+        let mut stdout = Stdout::default();
 
         assert!(
             matches!(stdout.write(b"baz"), Ok(3)),
@@ -288,6 +644,7 @@ mod test_read_write_seek {
     fn test_seek_stdout() {
         let mut stdout = Stdout {
             buf: vec![b'f', b'o', b'o', b'b', b'a', b'r'],
+            ..Default::default()
         };
 
         assert!(
@@ -300,6 +657,7 @@ mod test_read_write_seek {
     fn test_read_stderr() {
         let mut stderr = Stderr {
             buf: vec![b'f', b'o', b'o', b'b', b'a', b'r'],
+            ..Default::default()
         };
         let mut buffer = String::new();
 
@@ -311,7 +669,7 @@ mod test_read_write_seek {
 
     #[test]
     fn test_write_stderr() {
-        let mut stderr = Stderr { buf: vec![] };
+        let mut stderr = Stderr::default();
 
         assert!(
             matches!(stderr.write(b"baz"), Ok(3)),
@@ -332,6 +690,7 @@ mod test_read_write_seek {
     fn test_seek_stderr() {
         let mut stderr = Stderr {
             buf: vec![b'f', b'o', b'o', b'b', b'a', b'r'],
+            ..Default::default()
         };
 
         assert!(
@@ -339,4 +698,75 @@ mod test_read_write_seek {
             "cannot seek `stderr`",
         );
     }
+
+    #[test]
+    fn test_stdout_sink() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut stdout = Stdout::default();
+        stdout.set_sink(Box::new(CallbackSink::new({
+            let written = written.clone();
+            move |data: &[u8]| written.lock().unwrap().push(data.to_vec())
+        })));
+        stdout.set_line_discipline(LineDiscipline {
+            line_buffered: true,
+            crlf: true,
+            ..Default::default()
+        });
+
+        stdout.write_all(b"foo").unwrap();
+        assert!(written.lock().unwrap().is_empty(), "the line is incomplete");
+        stdout.write_all(b"bar\nba").unwrap();
+        stdout.flush().unwrap();
+
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec![b"foobar\r\n".to_vec(), b"ba".to_vec()],
+            "whole lines are written, with CRLF line endings",
+        );
+        assert!(stdout.buf.is_empty(), "nothing is buffered");
+    }
+
+    #[test]
+    fn test_stdin_source() {
+        let (sender, receiver) = mpsc::channel();
+        let echo = RingBuffer::new(64);
+        let mut stdin = Stdin::default();
+        stdin.set_source(Box::new(ChannelSource::new(receiver)));
+        stdin.set_echo(Box::new(echo.clone()));
+        stdin.set_line_discipline(LineDiscipline {
+            echo: true,
+            line_buffered: true,
+            crlf: true,
+        });
+
+        let mut buffer = String::new();
+        sender.send(b"ls -".to_vec()).unwrap();
+        assert!(
+            matches!(stdin.read_to_string(&mut buffer), Ok(0)),
+            "the line is incomplete",
+        );
+        sender.send(b"l\r\ncd\r".to_vec()).unwrap();
+        assert!(matches!(stdin.read_to_string(&mut buffer), Ok(9)));
+        assert_eq!(
+            buffer, "ls -l\ncd\n",
+            "`\\r\\n` and `\\r` are read as `\\n`"
+        );
+
+        let mut echoed = [0; 64];
+        let length = echo.clone().read(&mut echoed).unwrap();
+        assert_eq!(&echoed[..length], b"ls -l\r\ncd\r\n", "the input is echoed");
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut ring = RingBuffer::new(4);
+        StdioSink::write(&mut ring, b"abc").unwrap();
+        StdioSink::write(&mut ring, b"def").unwrap();
+        assert_eq!(ring.len(), 4);
+
+        let mut buffer = [0; 8];
+        assert_eq!(StdioSource::read(&mut ring, &mut buffer).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"cdef", "the oldest bytes are dropped");
+        assert!(ring.is_empty());
+    }
 }
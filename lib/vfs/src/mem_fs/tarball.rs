@@ -0,0 +1,417 @@
+//! This module contains the import and export of a [`FileSystem`]
+//! from and to a tar archive.
+//!
+//! Directories, regular files, symlinks and hard links are supported,
+//! along with their access, creation and modification times. Other
+//! kinds of entries, like devices, are skipped when importing.
+
+use super::*;
+use crate::{FileSystem as _, FsError, Result};
+use slab::Slab;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, Builder, EntryType, Header};
+
+impl FileSystem {
+    /// Creates a file system from the directories and files of a tar
+    /// archive.
+    ///
+    /// Paths of the archive are relative to the root of the file
+    /// system. Entries whose path goes above the root are rejected.
+    pub fn from_tar<R: Read>(reader: R) -> Result<Self> {
+        let fs = Self::default();
+        let mut archive = Archive::new(reader);
+
+        // Adding entries updates their parent directory, so the
+        // metadata of directories are set once all the entries are
+        // added.
+        let mut directory_metadata = Vec::new();
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = absolute_path(&entry.path()?)?;
+            let times = entry_times(entry.header())?;
+            let permissions = entry_permissions(entry.header());
+
+            match entry.header().entry_type() {
+                EntryType::Directory => {
+                    fs.create_dir_all(&path)?;
+                    directory_metadata.push((path, times, permissions));
+                }
+
+                EntryType::Regular | EntryType::Continuous => {
+                    if let Some(parent) = path.parent() {
+                        fs.create_dir_all(parent)?;
+                    }
+
+                    let mut file = fs
+                        .new_open_options()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(&path)?;
+                    io::copy(&mut entry, &mut file)?;
+                    fs.set_metadata(&path, times, permissions)?;
+                }
+
+                EntryType::Symlink => {
+                    if let Some(parent) = path.parent() {
+                        fs.create_dir_all(parent)?;
+                    }
+
+                    let target = entry.link_name()?.ok_or(FsError::InvalidData)?;
+                    fs.symlink(&target, &path)?;
+                    fs.set_metadata(&path, times, permissions)?;
+                }
+
+                // The file a hard link points to precedes it in the
+                // archive, and holds the metadata.
+                EntryType::Link => {
+                    if let Some(parent) = path.parent() {
+                        fs.create_dir_all(parent)?;
+                    }
+
+                    let target = entry.link_name()?.ok_or(FsError::InvalidData)?;
+                    fs.link(&absolute_path(&target)?, &path)?;
+                }
+
+                _ => continue,
+            }
+        }
+
+        for (path, times, permissions) in directory_metadata {
+            fs.set_metadata(&path, times, permissions)?;
+        }
+
+        Ok(fs)
+    }
+
+    /// Writes the directories and files of the file system to a tar
+    /// archive.
+    pub fn to_tar<W: Write>(&self, writer: W) -> Result<()> {
+        // Read lock.
+        let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+        let mut builder = Builder::new(writer);
+        let mut paths_of_files = HashMap::new();
+        let mut hard_links = Vec::new();
+        if let Some(Node::Directory { children, .. }) = fs.storage.get(ROOT_INODE) {
+            for child in children {
+                append_node(
+                    &fs.storage,
+                    *child,
+                    Path::new(""),
+                    &mut paths_of_files,
+                    &mut hard_links,
+                    &mut builder,
+                )?;
+            }
+        }
+
+        // Hard links are appended last, so that the files they point
+        // to precede them.
+        for (path, target) in hard_links {
+            let target = paths_of_files.get(&target).ok_or(FsError::UnknownError)?;
+
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Link);
+            header.set_mode(FILE_MODE);
+            header.set_size(0);
+            builder.append_link(&mut header, &path, target)?;
+        }
+        builder.finish()?;
+
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        // The root always exists, and can't be created.
+        let mut ancestors = path
+            .ancestors()
+            .filter(|ancestor| ancestor.parent().is_some())
+            .collect::<Vec<_>>();
+        ancestors.reverse();
+
+        for ancestor in ancestors {
+            match self.create_dir(ancestor) {
+                Ok(()) | Err(FsError::AlreadyExists) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_metadata(
+        &self,
+        path: &Path,
+        (accessed, created, modified): (u64, u64, u64),
+        (uid, gid, mode): (u32, u32, u32),
+    ) -> Result<()> {
+        // Write lock.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+        let inode = fs.inode_of_symlink(path)?;
+        let metadata = fs
+            .storage
+            .get_mut(inode)
+            .ok_or(FsError::UnknownError)?
+            .metadata_mut();
+        metadata.accessed = accessed;
+        metadata.created = created;
+        metadata.modified = modified;
+        metadata.uid = uid;
+        metadata.gid = gid;
+        metadata.mode = mode;
+
+        Ok(())
+    }
+}
+
+/// Turns a path of the archive into an absolute path of the file
+/// system.
+fn absolute_path(path: &Path) -> Result<PathBuf> {
+    let mut absolute_path = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => absolute_path.push(name),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => return Err(FsError::InvalidInput),
+        }
+    }
+
+    Ok(absolute_path)
+}
+
+/// Returns the access, creation and modification times of an entry,
+/// in nanoseconds. Only GNU archives record the first two, otherwise
+/// they are the modification time.
+fn entry_times(header: &Header) -> Result<(u64, u64, u64)> {
+    // Archives record times in seconds.
+    let nanos = |seconds: u64| seconds.saturating_mul(1_000_000_000);
+
+    let modified = header.mtime()?;
+    let (accessed, created) = match header.as_gnu() {
+        Some(gnu) => (
+            gnu.atime().unwrap_or(modified),
+            gnu.ctime().unwrap_or(modified),
+        ),
+        None => (modified, modified),
+    };
+
+    Ok((nanos(accessed), nanos(created), nanos(modified)))
+}
+
+/// Returns the owner, group and permission bits of an entry. Fields
+/// left empty by the archiver get the defaults of new nodes.
+fn entry_permissions(header: &Header) -> (u32, u32, u32) {
+    let id = |id: io::Result<u64>| id.ok().and_then(|id| u32::try_from(id).ok());
+    let mode =
+        header
+            .mode()
+            .map(|mode| mode & 0o7777)
+            .unwrap_or_else(|_| match header.entry_type() {
+                EntryType::Directory => DIRECTORY_MODE,
+                EntryType::Symlink => SYMLINK_MODE,
+                _ => FILE_MODE,
+            });
+
+    (
+        id(header.uid()).unwrap_or(0),
+        id(header.gid()).unwrap_or(0),
+        mode,
+    )
+}
+
+/// Appends a node and its descendants to the archive, except hard
+/// links, which are collected in `hard_links` along with the inodes
+/// of their targets. The paths of the appended files are collected in
+/// `paths_of_files`.
+fn append_node<W: Write>(
+    storage: &Slab<Node>,
+    inode: Inode,
+    parent_path: &Path,
+    paths_of_files: &mut HashMap<Inode, PathBuf>,
+    hard_links: &mut Vec<(PathBuf, Inode)>,
+    builder: &mut Builder<W>,
+) -> Result<()> {
+    let node = storage.get(inode).ok_or(FsError::UnknownError)?;
+    let path = parent_path.join(node.name());
+
+    if let Node::HardLink { target, .. } = node {
+        hard_links.push((path, *target));
+
+        return Ok(());
+    }
+
+    let metadata = node.metadata();
+
+    let mut header = Header::new_gnu();
+    // Archives record times in seconds.
+    header.set_mtime(metadata.modified / 1_000_000_000);
+    header.set_uid(metadata.uid.into());
+    header.set_gid(metadata.gid.into());
+    header.set_mode(metadata.mode);
+    if let Some(gnu) = header.as_gnu_mut() {
+        gnu.set_atime(metadata.accessed / 1_000_000_000);
+        gnu.set_ctime(metadata.created / 1_000_000_000);
+    }
+
+    match node {
+        Node::Directory { children, .. } => {
+            header.set_entry_type(EntryType::Directory);
+            header.set_size(0);
+            builder.append_data(&mut header, &path, io::empty())?;
+
+            for child in children {
+                append_node(storage, *child, &path, paths_of_files, hard_links, builder)?;
+            }
+        }
+
+        Node::File { file, .. } => {
+            let contents = file.contents();
+            header.set_entry_type(EntryType::Regular);
+            header.set_size(contents.len() as u64);
+            builder.append_data(&mut header, &path, &*contents)?;
+
+            paths_of_files.insert(inode, path);
+        }
+
+        Node::Symlink { target, .. } => {
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, &path, target)?;
+        }
+
+        Node::HardLink { .. } => unreachable!("hard links are appended last"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_tarball {
+    use crate::{mem_fs::*, FileSystem as FS, FsError};
+    use std::io::{Read, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+
+        (buf $path:expr) => {
+            std::path::PathBuf::from($path)
+        };
+    }
+
+    #[test]
+    fn test_to_tar_and_from_tar() {
+        let fs = FileSystem::default();
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/foo/bar")), Ok(()));
+        fs.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo/bar/baz.txt"))
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        assert_eq!(fs.symlink(path!("bar/baz.txt"), path!("/foo/link")), Ok(()));
+        assert_eq!(
+            fs.link(path!("/foo/bar/baz.txt"), path!("/hard.txt")),
+            Ok(())
+        );
+        assert_eq!(fs.chmod(path!("/foo/bar"), 0o700), Ok(()));
+        assert_eq!(
+            fs.chown(path!("/foo/bar/baz.txt"), Some(1000), Some(100)),
+            Ok(())
+        );
+
+        let mut archive = Vec::new();
+        fs.to_tar(&mut archive).unwrap();
+        let restored = FileSystem::from_tar(archive.as_slice()).unwrap();
+
+        let mut contents = String::new();
+        restored
+            .new_open_options()
+            .read(true)
+            .open(path!("/foo/bar/baz.txt"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello", "the file contents are imported");
+
+        // Archives record times in seconds.
+        let times = |fs: &FileSystem, path| {
+            fs.metadata(path).map(|metadata| {
+                (
+                    metadata.len,
+                    metadata.accessed / 1_000_000_000,
+                    metadata.created / 1_000_000_000,
+                    metadata.modified / 1_000_000_000,
+                    metadata.uid,
+                    metadata.gid,
+                    metadata.mode,
+                )
+            })
+        };
+        for path in [path!("/foo"), path!("/foo/bar"), path!("/foo/bar/baz.txt")] {
+            assert_eq!(
+                times(&restored, path),
+                times(&fs, path),
+                "the metadata of `{}` are imported",
+                path.display()
+            );
+        }
+        assert!(restored.metadata(path!("/foo/bar")).unwrap().is_dir());
+        assert_eq!(
+            restored.read_link(path!("/foo/link")),
+            Ok(path!(buf "bar/baz.txt")),
+            "the symlinks are imported"
+        );
+        assert_eq!(
+            restored
+                .metadata(path!("/hard.txt"))
+                .map(|metadata| (metadata.len, metadata.nlink)),
+            Ok((5, 2)),
+            "the hard links are imported"
+        );
+    }
+
+    #[test]
+    fn test_from_tar_creates_missing_directories() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_ustar();
+        header.set_size(2);
+        header.set_mtime(42);
+        builder
+            .append_data(&mut header, "./a/b/c.txt", &b"hi"[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let fs = FileSystem::from_tar(archive.as_slice()).unwrap();
+        assert!(fs.metadata(path!("/a/b")).unwrap().is_dir());
+        let metadata = fs.metadata(path!("/a/b/c.txt")).unwrap();
+        assert_eq!((metadata.len, metadata.modified), (2, 42_000_000_000));
+        assert_eq!(metadata.mode, 0o644, "the empty mode gets the default");
+    }
+
+    #[test]
+    fn test_from_tar_rejects_parent_paths() {
+        // `Builder` refuses `..`, so the path is written by hand.
+        let mut header = tar::Header::new_ustar();
+        header.as_ustar_mut().unwrap().name[..9].copy_from_slice(b"../escape");
+        header.set_size(0);
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append(&header, std::io::empty()).unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        assert!(matches!(
+            FileSystem::from_tar(archive.as_slice()),
+            Err(FsError::InvalidInput)
+        ));
+    }
+}
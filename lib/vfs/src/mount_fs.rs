@@ -0,0 +1,567 @@
+//! A file system made of other file systems, each one mounted at a
+//! path, like `/host` for a host file system, `/tmp` for a memory
+//! file system or `/usr` for a read-only image.
+//!
+//! Every path is served by the file system mounted at its longest
+//! prefix, or by the root file system if there's none. The parents
+//! of the mount points are directories, even when the file system
+//! below them doesn't have them, and the mount points are listed in
+//! their parents.
+//!
+//! A watch only covers the file system serving the watched path, not
+//! the ones mounted below it.
+
+use crate::{
+    normalize, DirEntry, FileOpener, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, Result, VirtualFile, WatchHandle,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+type Mounts = BTreeMap<PathBuf, Arc<dyn crate::FileSystem>>;
+
+/// The file system. It is cheap to clone, clones share the same
+/// mounts.
+#[derive(Clone, Default)]
+pub struct FileSystem {
+    /// The file system serving the paths outside of the mount
+    /// points, if any.
+    root: Option<Arc<dyn crate::FileSystem>>,
+    mounts: Arc<RwLock<Mounts>>,
+}
+
+impl FileSystem {
+    /// Creates a file system serving the paths outside of the mount
+    /// points from `root`.
+    pub fn new(root: Box<dyn crate::FileSystem>) -> Self {
+        Self::new_shared(Arc::from(root))
+    }
+
+    /// Like [`Self::new`], with a `root` shared with other file
+    /// systems.
+    pub fn new_shared(root: Arc<dyn crate::FileSystem>) -> Self {
+        Self {
+            root: Some(root),
+            mounts: Default::default(),
+        }
+    }
+
+    /// Mounts `fs` at `path`, which doesn't have to exist. Mounting
+    /// over another mount point fails with `AlreadyExists`.
+    pub fn mount(&self, path: &Path, fs: Box<dyn crate::FileSystem>) -> Result<()> {
+        let path = normalize(path)?;
+        if path == Path::new("/") {
+            return Err(FsError::InvalidInput);
+        }
+
+        let mut mounts = self.mounts.write().map_err(|_| FsError::Lock)?;
+        if mounts.contains_key(&path) {
+            return Err(FsError::AlreadyExists);
+        }
+        mounts.insert(path, Arc::from(fs));
+
+        Ok(())
+    }
+
+    /// Unmounts the file system mounted at `path`.
+    pub fn unmount(&self, path: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        let mut mounts = self.mounts.write().map_err(|_| FsError::Lock)?;
+        mounts
+            .remove(&path)
+            .map(|_| ())
+            .ok_or(FsError::EntityNotFound)
+    }
+
+    /// The mount points.
+    pub fn mount_points(&self) -> Result<Vec<PathBuf>> {
+        let mounts = self.mounts.read().map_err(|_| FsError::Lock)?;
+        Ok(mounts.keys().cloned().collect())
+    }
+
+    fn route(&self, path: &Path) -> Result<Route> {
+        route(
+            &*self.mounts.read().map_err(|_| FsError::Lock)?,
+            self.root.as_ref(),
+            path,
+        )
+    }
+}
+
+/// Where a path is served from.
+struct Route {
+    /// The normalized path.
+    path: PathBuf,
+    /// The mount point serving the path, or `/` for the root.
+    mount_point: PathBuf,
+    /// The file system serving the path, if any.
+    fs: Option<Arc<dyn crate::FileSystem>>,
+    /// The path inside of `fs`.
+    inner_path: PathBuf,
+    /// The names of the mount points right below the path.
+    children: Vec<PathBuf>,
+}
+
+impl Route {
+    /// The file system serving the path.
+    fn fs(&self) -> Result<&dyn crate::FileSystem> {
+        self.fs.as_deref().ok_or(FsError::EntityNotFound)
+    }
+
+    /// Whether the path is a mount point.
+    fn is_mount_point(&self) -> bool {
+        self.path == self.mount_point && self.path != Path::new("/")
+    }
+
+    /// Whether the path is a mount point, or one of its parents.
+    fn holds_mounts(&self) -> bool {
+        self.is_mount_point() || !self.children.is_empty()
+    }
+
+    /// Returns `result`, unless mount points are below the path,
+    /// which is then a directory whatever the file system below it
+    /// holds.
+    fn or_directory(&self, result: Result<Metadata>) -> Result<Metadata> {
+        match result {
+            Ok(metadata) if metadata.is_dir() => Ok(metadata),
+            _ if !self.children.is_empty() => Ok(directory()),
+            result => result,
+        }
+    }
+}
+
+fn route(mounts: &Mounts, root: Option<&Arc<dyn crate::FileSystem>>, path: &Path) -> Result<Route> {
+    let path = normalize(path)?;
+    let (mount_point, fs) = mounts
+        .iter()
+        .rev()
+        .find(|(mount_point, _)| path.starts_with(mount_point))
+        .map(|(mount_point, fs)| (mount_point.clone(), Some(fs.clone())))
+        .unwrap_or_else(|| (PathBuf::from("/"), root.cloned()));
+    let inner_path = Path::new("/").join(path.strip_prefix(&mount_point).unwrap());
+    let mut children = mounts
+        .keys()
+        .filter_map(|mount_point| {
+            mount_point
+                .strip_prefix(&path)
+                .ok()
+                .and_then(|rest| rest.components().next())
+                .map(|name| path.join(name))
+        })
+        .collect::<Vec<_>>();
+    children.dedup();
+
+    Ok(Route {
+        path,
+        mount_point,
+        fs,
+        inner_path,
+        children,
+    })
+}
+
+/// The metadata of the directories holding mount points.
+fn directory() -> Metadata {
+    Metadata {
+        ft: FileType {
+            dir: true,
+            ..Default::default()
+        },
+        nlink: 1,
+        mode: 0o555,
+        ..Default::default()
+    }
+}
+
+impl fmt::Debug for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("FileSystem");
+        debug.field("root", &self.root);
+        match self.mounts.read() {
+            Ok(mounts) => debug.field("mounts", &*mounts),
+            Err(_) => debug.field("mounts", &"<locked>"),
+        };
+        debug.finish()
+    }
+}
+
+impl crate::FileSystem for FileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let route = self.route(path)?;
+        let mut entries = match route.fs().and_then(|fs| fs.read_dir(&route.inner_path)) {
+            Ok(entries) => entries
+                .map(|entry| {
+                    entry.map(|mut entry| {
+                        entry.path = route.path.join(entry.file_name());
+                        entry
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            Err(_) if !route.children.is_empty() => vec![],
+            Err(error) => return Err(error),
+        };
+
+        // The mount points hide the entries of the same name.
+        entries.retain(|entry| !route.children.contains(&entry.path));
+        for child in &route.children {
+            entries.push(DirEntry {
+                metadata: self.metadata(child),
+                path: child.clone(),
+            });
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        let route = self.route(path)?;
+        if route.holds_mounts() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        route.fs()?.create_dir(&route.inner_path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        let route = self.route(path)?;
+        if route.holds_mounts() {
+            return Err(FsError::Busy);
+        }
+
+        route.fs()?.remove_dir(&route.inner_path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from, to) = (self.route(from)?, self.route(to)?);
+        if from.holds_mounts() || to.holds_mounts() {
+            return Err(FsError::Busy);
+        }
+        if from.mount_point != to.mount_point {
+            return Err(FsError::CrossDevice);
+        }
+
+        from.fs()?.rename(&from.inner_path, &to.inner_path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let route = self.route(path)?;
+        route.or_directory(route.fs().and_then(|fs| fs.metadata(&route.inner_path)))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        let route = self.route(path)?;
+        route.or_directory(
+            route
+                .fs()
+                .and_then(|fs| fs.symlink_metadata(&route.inner_path)),
+        )
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        let route = self.route(link)?;
+        if route.holds_mounts() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        route.fs()?.symlink(original, &route.inner_path)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        let route = self.route(path)?;
+        route.fs()?.read_link(&route.inner_path)
+    }
+
+    fn link(&self, original: &Path, link: &Path) -> Result<()> {
+        let (original, link) = (self.route(original)?, self.route(link)?);
+        if link.holds_mounts() {
+            return Err(FsError::AlreadyExists);
+        }
+        if original.mount_point != link.mount_point {
+            return Err(FsError::CrossDevice);
+        }
+
+        original.fs()?.link(&original.inner_path, &link.inner_path)
+    }
+
+    fn chmod(&self, path: &Path, mode: u32) -> Result<()> {
+        let route = self.route(path)?;
+        route.fs()?.chmod(&route.inner_path, mode)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let route = self.route(path)?;
+        route.fs()?.chown(&route.inner_path, uid, gid)
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<u64>, modified: Option<u64>) -> Result<()> {
+        let route = self.route(path)?;
+        route.fs()?.set_times(&route.inner_path, accessed, modified)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let route = self.route(path)?;
+        if route.holds_mounts() {
+            return Err(FsError::Busy);
+        }
+
+        route.fs()?.remove_file(&route.inner_path)
+    }
+
+    fn clone_file(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from, to) = (self.route(from)?, self.route(to)?);
+        if to.holds_mounts() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        if from.mount_point == to.mount_point {
+            from.fs()?.clone_file(&from.inner_path, &to.inner_path)
+        } else {
+            let mut source = from
+                .fs()?
+                .new_open_options()
+                .read(true)
+                .open(&from.inner_path)?;
+            let mut destination = to
+                .fs()?
+                .new_open_options()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&to.inner_path)?;
+            std::io::copy(&mut source, &mut destination)?;
+
+            Ok(())
+        }
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<WatchHandle> {
+        let served = self.route(path)?;
+        let handle = served.fs()?.watch(&served.inner_path, recursive)?;
+
+        // The paths of the events are moved below the mount point, and
+        // those hidden by another mount point are dropped.
+        let (mounts, root) = (self.mounts.clone(), self.root.clone());
+        let mount_point = served.mount_point;
+        Ok(handle.map(move |event| {
+            event.map_paths(|inner_path| {
+                let path = mount_point.join(inner_path.strip_prefix("/").ok()?);
+                let mounts = mounts.read().ok()?;
+                let served = route(&mounts, root.as_ref(), &path).ok()?;
+                Some(path).filter(|_| served.mount_point == mount_point)
+            })
+        }))
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl FileOpener for FileSystem {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let route = self.route(path)?;
+        if route.holds_mounts() {
+            return Err(FsError::NotAFile);
+        }
+
+        route
+            .fs()?
+            .new_open_options()
+            .options(conf.clone())
+            .open(&route.inner_path)
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_filesystem {
+    use super::FileSystem;
+    use crate::{mem_fs, FileSystem as FS, FsError};
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+
+    macro_rules! path {
+        ($path:expr) => {
+            Path::new($path)
+        };
+    }
+
+    fn write(fs: &dyn FS, path: &Path, contents: &[u8]) {
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open(path)
+            .unwrap()
+            .write_all(contents)
+            .unwrap();
+    }
+
+    fn read(fs: &dyn FS, path: &Path) -> Vec<u8> {
+        let mut contents = Vec::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    fn names(fs: &dyn FS, path: &Path) -> Vec<PathBuf> {
+        fs.read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path)
+            .collect()
+    }
+
+    #[test]
+    fn test_watch() {
+        use crate::WatchEvent::*;
+
+        let root = mem_fs::FileSystem::default();
+        let tmp = mem_fs::FileSystem::default();
+        let fs = FileSystem::new(Box::new(root.clone()));
+        assert_eq!(root.create_dir(path!("/tmp")), Ok(()));
+        assert_eq!(fs.mount(path!("/tmp"), Box::new(tmp)), Ok(()));
+
+        let all = fs.watch(path!("/"), true).unwrap();
+        let mounted = fs.watch(path!("/tmp"), false).unwrap();
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        assert_eq!(fs.create_dir(path!("/tmp/bar")), Ok(()));
+        assert_eq!(
+            root.create_dir(path!("/tmp/baz")),
+            Ok(()),
+            "creating a directory hidden by the mount point"
+        );
+
+        assert_eq!(
+            all.pending().collect::<Vec<_>>(),
+            vec![Created(PathBuf::from("/foo"))],
+            "the mounted file systems aren't watched"
+        );
+        assert_eq!(
+            mounted.pending().collect::<Vec<_>>(),
+            vec![Created(PathBuf::from("/tmp/bar"))],
+            "the paths are below the mount point"
+        );
+    }
+
+    #[test]
+    fn test_paths_are_routed_to_the_mounts() {
+        let root = mem_fs::FileSystem::default();
+        let tmp = mem_fs::FileSystem::default();
+        let fs = FileSystem::new(Box::new(root.clone()));
+        assert_eq!(fs.mount(path!("/tmp"), Box::new(tmp.clone())), Ok(()));
+        assert_eq!(
+            fs.mount(path!("/tmp/"), Box::new(tmp.clone())),
+            Err(FsError::AlreadyExists)
+        );
+        assert_eq!(
+            fs.mount_points(),
+            Ok(vec![PathBuf::from("/tmp")]),
+            "the mount points are normalized"
+        );
+
+        write(&fs, path!("/foo"), b"root");
+        write(&fs, path!("/tmp/foo"), b"tmp");
+        assert_eq!(read(&root, path!("/foo")), b"root");
+        assert_eq!(read(&tmp, path!("/foo")), b"tmp");
+        assert_eq!(read(&fs, path!("/tmp/../foo")), b"root");
+
+        assert_eq!(fs.create_dir(path!("/tmp/bar")), Ok(()));
+        assert!(tmp.metadata(path!("/bar")).unwrap().is_dir());
+
+        assert_eq!(fs.unmount(path!("/tmp")), Ok(()));
+        assert_eq!(fs.unmount(path!("/tmp")), Err(FsError::EntityNotFound));
+        assert!(fs.metadata(path!("/tmp/foo")).is_err());
+    }
+
+    #[test]
+    fn test_nested_mounts() {
+        let usr = mem_fs::FileSystem::default();
+        let local = mem_fs::FileSystem::default();
+        let fs = FileSystem::default();
+        fs.mount(path!("/usr"), Box::new(usr.clone())).unwrap();
+        fs.mount(path!("/usr/local"), Box::new(local.clone()))
+            .unwrap();
+
+        write(&fs, path!("/usr/lib"), b"usr");
+        write(&fs, path!("/usr/local/lib"), b"local");
+        assert_eq!(read(&usr, path!("/lib")), b"usr");
+        assert_eq!(read(&local, path!("/lib")), b"local");
+
+        assert_eq!(
+            fs.metadata(path!("/foo")).map(|_| ()),
+            Err(FsError::EntityNotFound),
+            "there's no root file system"
+        );
+    }
+
+    #[test]
+    fn test_read_dir_lists_the_mount_points() {
+        let root = mem_fs::FileSystem::default();
+        let fs = FileSystem::new(Box::new(root.clone()));
+        write(&root, path!("/foo"), b"");
+        root.create_dir(path!("/mnt")).unwrap();
+        write(&root, path!("/mnt/hidden"), b"");
+        fs.mount(path!("/mnt"), Box::new(mem_fs::FileSystem::default()))
+            .unwrap();
+        fs.mount(
+            path!("/deep/down/there"),
+            Box::new(mem_fs::FileSystem::default()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            names(&fs, path!("/")),
+            vec![
+                PathBuf::from("/deep"),
+                PathBuf::from("/foo"),
+                PathBuf::from("/mnt"),
+            ]
+        );
+        assert_eq!(
+            names(&fs, path!("/mnt")),
+            Vec::<PathBuf>::new(),
+            "the mount hides the directory below it"
+        );
+        assert_eq!(
+            names(&fs, path!("/deep/down")),
+            vec![PathBuf::from("/deep/down/there")]
+        );
+        assert!(fs.metadata(path!("/deep")).unwrap().is_dir());
+        assert_eq!(fs.create_dir(path!("/deep")), Err(FsError::AlreadyExists));
+        assert_eq!(fs.remove_dir(path!("/deep")), Err(FsError::Busy));
+        assert_eq!(fs.remove_dir(path!("/mnt")), Err(FsError::Busy));
+    }
+
+    #[test]
+    fn test_rename_across_mounts() {
+        let fs = FileSystem::new(Box::new(mem_fs::FileSystem::default()));
+        fs.mount(path!("/tmp"), Box::new(mem_fs::FileSystem::default()))
+            .unwrap();
+        write(&fs, path!("/foo"), b"foo");
+        write(&fs, path!("/tmp/bar"), b"bar");
+
+        assert_eq!(
+            fs.rename(path!("/foo"), path!("/tmp/foo")),
+            Err(FsError::CrossDevice)
+        );
+        assert_eq!(
+            fs.link(path!("/tmp/bar"), path!("/bar")),
+            Err(FsError::CrossDevice)
+        );
+        assert_eq!(fs.rename(path!("/tmp"), path!("/tmp2")), Err(FsError::Busy));
+        assert_eq!(fs.rename(path!("/tmp/bar"), path!("/tmp/baz")), Ok(()));
+        assert_eq!(read(&fs, path!("/tmp/baz")), b"bar");
+
+        assert_eq!(fs.clone_file(path!("/foo"), path!("/tmp/foo")), Ok(()));
+        assert_eq!(read(&fs, path!("/tmp/foo")), b"foo");
+    }
+}
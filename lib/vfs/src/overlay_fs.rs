@@ -0,0 +1,614 @@
+//! A copy-on-write file system, layering a writable file system over
+//! a base one, which is never modified, so that it can be shared.
+//!
+//! The upper layer hides the base one: a file of the base is copied
+//! into the upper layer before being modified, and a file removed
+//! from the base is hidden by a whiteout. A directory created where
+//! a removed one was is opaque, i.e. it doesn't show the entries of
+//! the base anymore.
+//!
+//! Symlinks are resolved in the layer holding them, and directories
+//! of the base can't be renamed, which fails with `CrossDevice`.
+//!
+//! Only the upper layer is watched, as the base one isn't modified:
+//! copying a file up is reported as its creation, and removing a file
+//! of the base only is not reported.
+
+use crate::{
+    normalize, FileOpener, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir, Result,
+    VirtualFile, WatchHandle,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// The paths hidden from the base layer.
+#[derive(Debug, Default)]
+struct Whiteouts {
+    /// The paths removed from the base, along with their children.
+    removed: HashSet<PathBuf>,
+    /// The directories whose children aren't taken from the base.
+    opaque: HashSet<PathBuf>,
+}
+
+impl Whiteouts {
+    /// Whether the base layer is visible at `path`.
+    fn base_is_visible(&self, path: &Path) -> bool {
+        path.ancestors().all(|ancestor| {
+            !self.removed.contains(ancestor)
+                && (ancestor == path || !self.opaque.contains(ancestor))
+        })
+    }
+
+    /// Records that `path` is created in the upper layer.
+    fn create(&mut self, path: &Path) {
+        if self.removed.remove(path) {
+            self.opaque.insert(path.to_path_buf());
+        }
+    }
+
+    /// Records that `path` is removed, hiding the base at `path` if
+    /// `in_base`.
+    fn remove(&mut self, path: &Path, in_base: bool) {
+        self.removed.retain(|removed| !removed.starts_with(path));
+        self.opaque.retain(|opaque| !opaque.starts_with(path));
+        if in_base {
+            self.removed.insert(path.to_path_buf());
+        }
+    }
+}
+
+/// The file system. It is cheap to clone, clones share the same
+/// layers.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    base: Arc<dyn crate::FileSystem>,
+    upper: Arc<dyn crate::FileSystem>,
+    whiteouts: Arc<RwLock<Whiteouts>>,
+}
+
+impl FileSystem {
+    /// Layers `upper` over `base`. Only `upper` is modified.
+    pub fn new(base: Box<dyn crate::FileSystem>, upper: Box<dyn crate::FileSystem>) -> Self {
+        Self {
+            base: Arc::from(base),
+            upper: Arc::from(upper),
+            whiteouts: Default::default(),
+        }
+    }
+
+    /// The base layer.
+    pub fn base(&self) -> &dyn crate::FileSystem {
+        &*self.base
+    }
+
+    /// The upper layer.
+    pub fn upper(&self) -> &dyn crate::FileSystem {
+        &*self.upper
+    }
+
+    fn in_upper(&self, path: &Path) -> bool {
+        self.upper.symlink_metadata(path).is_ok()
+    }
+
+    fn in_base(&self, path: &Path) -> Result<bool> {
+        let whiteouts = self.whiteouts.read().map_err(|_| FsError::Lock)?;
+        Ok(whiteouts.base_is_visible(path) && self.base.symlink_metadata(path).is_ok())
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool> {
+        Ok(self.in_upper(path) || self.in_base(path)?)
+    }
+
+    /// The layer serving `path`.
+    fn layer(&self, path: &Path) -> Result<&dyn crate::FileSystem> {
+        if self.in_upper(path) {
+            Ok(&*self.upper)
+        } else if self.in_base(path)? {
+            Ok(&*self.base)
+        } else {
+            Err(FsError::EntityNotFound)
+        }
+    }
+
+    /// Copies `path` from the base into the upper layer, along with
+    /// its parents, unless it's already there.
+    fn copy_up(&self, path: &Path) -> Result<()> {
+        if self.in_upper(path) {
+            return Ok(());
+        }
+        if !self.in_base(path)? {
+            return Err(FsError::EntityNotFound);
+        }
+
+        self.copy_up_parent(path)?;
+        let metadata = self.base.symlink_metadata(path)?;
+        let file_type = metadata.file_type();
+        if file_type.is_dir() {
+            self.upper.create_dir(path)?;
+        } else if file_type.is_symlink() {
+            self.upper.symlink(&self.base.read_link(path)?, path)?;
+            return Ok(());
+        } else {
+            let mut source = self.base.new_open_options().read(true).open(path)?;
+            let mut destination = self
+                .upper
+                .new_open_options()
+                .write(true)
+                .create_new(true)
+                .open(path)?;
+            io::copy(&mut source, &mut destination)?;
+        }
+
+        // The upper layer may not support them.
+        let _ = self.upper.chmod(path, metadata.mode);
+        let _ = self
+            .upper
+            .set_times(path, Some(metadata.accessed), Some(metadata.modified));
+
+        Ok(())
+    }
+
+    /// Copies the parent of `path` into the upper layer.
+    fn copy_up_parent(&self, path: &Path) -> Result<()> {
+        match path.parent() {
+            Some(parent) if parent != Path::new("/") => self.copy_up(parent),
+            _ => Ok(()),
+        }
+    }
+
+    /// Prepares the creation of `path` in the upper layer.
+    fn create(&self, path: &Path) -> Result<()> {
+        if self.exists(path)? {
+            return Err(FsError::AlreadyExists);
+        }
+
+        self.copy_up_parent(path)
+    }
+
+    /// Records the creation of `path` in the upper layer.
+    fn created(&self, path: &Path) -> Result<()> {
+        let mut whiteouts = self.whiteouts.write().map_err(|_| FsError::Lock)?;
+        whiteouts.create(path);
+        Ok(())
+    }
+
+    /// Records the removal of `path`.
+    fn removed(&self, path: &Path) -> Result<()> {
+        let in_base = self.base.symlink_metadata(path).is_ok();
+        let mut whiteouts = self.whiteouts.write().map_err(|_| FsError::Lock)?;
+        whiteouts.remove(path, in_base);
+        Ok(())
+    }
+}
+
+impl crate::FileSystem for FileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let path = normalize(path)?;
+        let in_upper = matches!(self.upper.metadata(&path), Ok(m) if m.is_dir());
+        let in_base = {
+            let whiteouts = self.whiteouts.read().map_err(|_| FsError::Lock)?;
+            whiteouts.base_is_visible(&path)
+                && !whiteouts.opaque.contains(&path)
+                && (in_upper || !self.in_upper(&path))
+        } && matches!(self.base.metadata(&path), Ok(m) if m.is_dir());
+        if !in_upper && !in_base {
+            return self.layer(&path)?.read_dir(&path);
+        }
+
+        let mut entries = BTreeMap::new();
+        if in_base {
+            for entry in self.base.read_dir(&path)? {
+                let entry = entry?;
+                let child = path.join(entry.file_name());
+                if self.in_base(&child)? {
+                    entries.insert(child, entry);
+                }
+            }
+        }
+        if in_upper {
+            for entry in self.upper.read_dir(&path)? {
+                let entry = entry?;
+                entries.insert(path.join(entry.file_name()), entry);
+            }
+        }
+
+        Ok(ReadDir::new(
+            entries
+                .into_iter()
+                .map(|(path, mut entry)| {
+                    entry.path = path;
+                    entry
+                })
+                .collect(),
+        ))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        self.create(&path)?;
+        self.upper.create_dir(&path)?;
+        self.created(&path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        if !self.layer(&path)?.symlink_metadata(&path)?.is_dir() {
+            return Err(FsError::BaseNotDirectory);
+        }
+        if self.read_dir(&path)?.next().is_some() {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+
+        if self.in_upper(&path) {
+            self.upper.remove_dir(&path)?;
+        }
+        self.removed(&path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from, to) = (normalize(from)?, normalize(to)?);
+        if self.layer(&from)?.symlink_metadata(&from)?.is_dir()
+            && (self.in_base(&from)? || self.in_base(&to)?)
+        {
+            return Err(FsError::CrossDevice);
+        }
+
+        self.copy_up(&from)?;
+        self.copy_up_parent(&to)?;
+        self.upper.rename(&from, &to)?;
+        self.created(&to)?;
+        self.removed(&from)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let path = normalize(path)?;
+        self.layer(&path)?.metadata(&path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        let path = normalize(path)?;
+        self.layer(&path)?.symlink_metadata(&path)
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        let link = normalize(link)?;
+        self.create(&link)?;
+        self.upper.symlink(original, &link)?;
+        self.created(&link)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        let path = normalize(path)?;
+        self.layer(&path)?.read_link(&path)
+    }
+
+    fn link(&self, original: &Path, link: &Path) -> Result<()> {
+        let (original, link) = (normalize(original)?, normalize(link)?);
+        self.create(&link)?;
+        self.copy_up(&original)?;
+        self.upper.link(&original, &link)?;
+        self.created(&link)
+    }
+
+    fn chmod(&self, path: &Path, mode: u32) -> Result<()> {
+        let path = normalize(path)?;
+        self.copy_up(&path)?;
+        self.upper.chmod(&path, mode)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let path = normalize(path)?;
+        self.copy_up(&path)?;
+        self.upper.chown(&path, uid, gid)
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<u64>, modified: Option<u64>) -> Result<()> {
+        let path = normalize(path)?;
+        self.copy_up(&path)?;
+        self.upper.set_times(&path, accessed, modified)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let path = normalize(path)?;
+        if self.layer(&path)?.symlink_metadata(&path)?.is_dir() {
+            return Err(FsError::NotAFile);
+        }
+
+        if self.in_upper(&path) {
+            self.upper.remove_file(&path)?;
+        }
+        self.removed(&path)
+    }
+
+    fn clone_file(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from, to) = (normalize(from)?, normalize(to)?);
+        if self.in_upper(&from) {
+            self.copy_up_parent(&to)?;
+            self.upper.clone_file(&from, &to)?;
+            return self.created(&to);
+        }
+
+        // The contents of the base can't be shared with the upper
+        // layer, they are copied.
+        let mut source = self
+            .layer(&from)?
+            .new_open_options()
+            .read(true)
+            .open(&from)?;
+        let mut destination = self
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&to)?;
+        io::copy(&mut source, &mut destination)?;
+
+        Ok(())
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<WatchHandle> {
+        let path = normalize(path)?;
+        self.layer(&path)?;
+
+        // The path may not be copied up yet: its closest ancestor in
+        // the upper layer is watched instead, keeping the events of
+        // the path.
+        let watched = path
+            .ancestors()
+            .find(|ancestor| self.in_upper(ancestor))
+            .ok_or(FsError::EntityNotFound)?;
+        let handle = self.upper.watch(watched, recursive || watched != path)?;
+        Ok(handle.map(move |event| {
+            event.map_paths(|changed| {
+                let concerned = changed == path
+                    || changed.parent() == Some(path.as_path())
+                    || (recursive && changed.starts_with(&path));
+                Some(changed.to_path_buf()).filter(|_| concerned)
+            })
+        }))
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl FileOpener for FileSystem {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let path = normalize(path)?;
+        let writes =
+            conf.write() || conf.append() || conf.truncate() || conf.create() || conf.create_new();
+        if !writes {
+            return self
+                .layer(&path)?
+                .new_open_options()
+                .options(conf.clone())
+                .open(&path);
+        }
+
+        if self.exists(&path)? {
+            if conf.create_new() {
+                return Err(FsError::AlreadyExists);
+            }
+            self.copy_up(&path)?;
+        } else {
+            if !conf.create() && !conf.create_new() {
+                return Err(FsError::EntityNotFound);
+            }
+            self.copy_up_parent(&path)?;
+        }
+
+        let file = self
+            .upper
+            .new_open_options()
+            .options(conf.clone())
+            .open(&path)?;
+        self.created(&path)?;
+        Ok(file)
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_filesystem {
+    use super::FileSystem;
+    use crate::{mem_fs, FileSystem as FS, FsError};
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+
+    macro_rules! path {
+        ($path:expr) => {
+            Path::new($path)
+        };
+    }
+
+    fn write(fs: &dyn FS, path: &Path, contents: &[u8]) {
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .unwrap()
+            .write_all(contents)
+            .unwrap();
+    }
+
+    fn read(fs: &dyn FS, path: &Path) -> Vec<u8> {
+        let mut contents = Vec::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    fn names(fs: &dyn FS, path: &Path) -> Vec<PathBuf> {
+        fs.read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path)
+            .collect()
+    }
+
+    /// Returns the base, with `/etc/motd`, `/etc/hosts` and
+    /// `/usr/lib/libc.a`, and the overlay over it.
+    fn overlay() -> (mem_fs::FileSystem, FileSystem) {
+        let base = mem_fs::FileSystem::default();
+        base.create_dir(path!("/etc")).unwrap();
+        write(&base, path!("/etc/motd"), b"hello");
+        write(&base, path!("/etc/hosts"), b"localhost");
+        base.create_dir(path!("/usr")).unwrap();
+        base.create_dir(path!("/usr/lib")).unwrap();
+        write(&base, path!("/usr/lib/libc.a"), b"libc");
+
+        let fs = FileSystem::new(
+            Box::new(base.clone()),
+            Box::new(mem_fs::FileSystem::default()),
+        );
+        (base, fs)
+    }
+
+    #[test]
+    fn test_clone_file() {
+        let (base, fs) = overlay();
+        assert_eq!(
+            fs.clone_file(path!("/etc/motd"), path!("/etc/motd.orig")),
+            Ok(())
+        );
+        write(&fs, path!("/etc/motd"), b"bye");
+        assert_eq!(
+            fs.clone_file(path!("/etc/motd"), path!("/etc/hosts")),
+            Ok(())
+        );
+
+        assert_eq!(read(&fs, path!("/etc/motd.orig")), b"hello");
+        assert_eq!(read(&fs, path!("/etc/hosts")), b"bye");
+        assert_eq!(read(&base, path!("/etc/hosts")), b"localhost");
+        assert!(base.metadata(path!("/etc/motd.orig")).is_err());
+    }
+
+    #[test]
+    fn test_watch() {
+        use crate::WatchEvent::*;
+
+        let (_, fs) = overlay();
+        let watch = fs.watch(path!("/usr"), true).unwrap();
+        assert_eq!(fs.create_dir(path!("/tmp")), Ok(()));
+        write(&fs, path!("/usr/lib/libm.a"), b"libm");
+
+        let events = watch.pending().collect::<Vec<_>>();
+        assert!(events.contains(&Created(PathBuf::from("/usr/lib/libm.a"))));
+        assert!(
+            events
+                .iter()
+                .all(|event| event.paths().iter().all(|path| path.starts_with("/usr"))),
+            "only the changes of the watched directory are reported"
+        );
+        assert!(matches!(
+            fs.watch(path!("/missing"), false),
+            Err(FsError::EntityNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_writes_are_copied_up() {
+        let (base, fs) = overlay();
+        assert_eq!(read(&fs, path!("/etc/motd")), b"hello");
+
+        fs.new_open_options()
+            .append(true)
+            .open(path!("/etc/motd"))
+            .unwrap()
+            .write_all(b", world")
+            .unwrap();
+        assert_eq!(read(&fs, path!("/etc/motd")), b"hello, world");
+        assert_eq!(read(&base, path!("/etc/motd")), b"hello");
+        assert_eq!(read(fs.upper(), path!("/etc/motd")), b"hello, world");
+
+        write(&fs, path!("/usr/lib/libm.a"), b"libm");
+        assert!(base.metadata(path!("/usr/lib/libm.a")).is_err());
+        assert_eq!(
+            names(&fs, path!("/usr/lib")),
+            vec![
+                PathBuf::from("/usr/lib/libc.a"),
+                PathBuf::from("/usr/lib/libm.a"),
+            ]
+        );
+
+        assert_eq!(fs.chmod(path!("/etc/hosts"), 0o600), Ok(()));
+        assert_eq!(fs.metadata(path!("/etc/hosts")).unwrap().mode(), 0o600);
+        assert_eq!(base.metadata(path!("/etc/hosts")).unwrap().mode(), 0o644);
+        assert_eq!(read(&fs, path!("/etc/hosts")), b"localhost");
+    }
+
+    #[test]
+    fn test_removals_are_whiteouts() {
+        let (base, fs) = overlay();
+
+        assert_eq!(fs.remove_file(path!("/etc/motd")), Ok(()));
+        assert!(fs.metadata(path!("/etc/motd")).is_err());
+        assert!(base.metadata(path!("/etc/motd")).is_ok());
+        assert_eq!(names(&fs, path!("/etc")), vec![PathBuf::from("/etc/hosts")]);
+        assert_eq!(
+            fs.remove_file(path!("/etc/motd")),
+            Err(FsError::EntityNotFound)
+        );
+
+        assert_eq!(
+            fs.remove_dir(path!("/usr")),
+            Err(FsError::DirectoryNotEmpty)
+        );
+        fs.remove_file(path!("/usr/lib/libc.a")).unwrap();
+        fs.remove_dir(path!("/usr/lib")).unwrap();
+        fs.remove_dir(path!("/usr")).unwrap();
+        assert_eq!(names(&fs, path!("/")), vec![PathBuf::from("/etc")]);
+        assert!(base.metadata(path!("/usr/lib/libc.a")).is_ok());
+
+        write(&fs, path!("/etc/motd"), b"bye");
+        assert_eq!(read(&fs, path!("/etc/motd")), b"bye");
+        assert_eq!(read(&base, path!("/etc/motd")), b"hello");
+    }
+
+    #[test]
+    fn test_recreated_directories_are_opaque() {
+        let (_base, fs) = overlay();
+
+        fs.remove_file(path!("/usr/lib/libc.a")).unwrap();
+        fs.remove_dir(path!("/usr/lib")).unwrap();
+        fs.create_dir(path!("/usr/lib")).unwrap();
+        assert_eq!(names(&fs, path!("/usr/lib")), Vec::<PathBuf>::new());
+        assert!(fs.metadata(path!("/usr/lib/libc.a")).is_err());
+
+        assert_eq!(
+            fs.create_dir(path!("/etc")),
+            Err(FsError::AlreadyExists),
+            "the directory of the base is visible"
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let (base, fs) = overlay();
+
+        assert_eq!(fs.rename(path!("/etc/motd"), path!("/motd")), Ok(()));
+        assert_eq!(read(&fs, path!("/motd")), b"hello");
+        assert!(fs.metadata(path!("/etc/motd")).is_err());
+        assert!(base.metadata(path!("/etc/motd")).is_ok());
+
+        assert_eq!(
+            fs.rename(path!("/usr"), path!("/opt")),
+            Err(FsError::CrossDevice)
+        );
+
+        fs.create_dir(path!("/tmp")).unwrap();
+        write(&fs, path!("/tmp/foo"), b"foo");
+        assert_eq!(fs.rename(path!("/tmp"), path!("/var")), Ok(()));
+        assert_eq!(read(&fs, path!("/var/foo")), b"foo");
+    }
+}
@@ -0,0 +1,400 @@
+//! The resolution of the paths given to the file systems, shared by
+//! all of them so that `.`, `..`, trailing slashes and empty
+//! components mean the same everywhere.
+//!
+//! The resolution is lexical, unless the symlinks are followed with
+//! [`PathResolver::resolve`]: `/a/b/..` is `/a`, even if `/a/b` is a
+//! symlink. [`PathResolver::join`] confines a relative path to a
+//! directory lexically, and the `sandbox_fs` file system confines
+//! a whole file system to one, symlinks included.
+
+use crate::{FsError, Result};
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+/// The number of symlinks [`PathResolver::resolve`] follows by default
+/// before failing with [`FsError::SymlinkLoop`].
+pub const MAX_SYMLINKS: usize = 40;
+
+/// What a `..` going above the root does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParentOfRoot {
+    /// It stays at the root, like on Unix: `/..` is `/`.
+    Root,
+    /// It fails with [`FsError::InvalidInput`].
+    Reject,
+}
+
+/// Resolves the paths. It is cheap to create and to copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathResolver {
+    parent_of_root: ParentOfRoot,
+    max_symlinks: usize,
+}
+
+impl Default for PathResolver {
+    fn default() -> Self {
+        Self {
+            parent_of_root: ParentOfRoot::Root,
+            max_symlinks: MAX_SYMLINKS,
+        }
+    }
+}
+
+impl PathResolver {
+    /// Creates a resolver where `/..` is `/`, following at most
+    /// [`MAX_SYMLINKS`] symlinks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets what a `..` going above the root does.
+    pub fn parent_of_root(mut self, parent_of_root: ParentOfRoot) -> Self {
+        self.parent_of_root = parent_of_root;
+        self
+    }
+
+    /// Sets the number of symlinks followed before failing with
+    /// [`FsError::SymlinkLoop`].
+    pub fn max_symlinks(mut self, max_symlinks: usize) -> Self {
+        self.max_symlinks = max_symlinks;
+        self
+    }
+
+    /// Returns the names of the components of the absolute `path`,
+    /// without `.` and `..`. The root has none.
+    ///
+    /// Fails with [`FsError::InvalidInput`] if `path` is relative or
+    /// has a Windows prefix, like `C:`.
+    pub fn names(&self, path: &Path) -> Result<Vec<OsString>> {
+        if !path.has_root() {
+            return Err(FsError::InvalidInput);
+        }
+
+        let mut names = Vec::new();
+        for component in path.components() {
+            self.push(&mut names, component)?;
+        }
+
+        Ok(names)
+    }
+
+    /// Makes the absolute `path` canonical: without `.`, `..`, empty
+    /// components nor trailing slash, e.g. `/a/./b//../c/` is `/a/c`.
+    ///
+    /// Fails like [`Self::names`].
+    pub fn normalize(&self, path: &Path) -> Result<PathBuf> {
+        Ok(to_path(self.names(path)?))
+    }
+
+    /// Resolves the relative `path` from the directory `root`,
+    /// without going out of it: a `..` going above `root`, or an
+    /// absolute `path`, fails with [`FsError::PermissionDenied`].
+    ///
+    /// The result is normalized, and so must be `root`.
+    pub fn join(&self, root: &Path, path: &Path) -> Result<PathBuf> {
+        let sandbox = Self {
+            parent_of_root: ParentOfRoot::Reject,
+            ..*self
+        };
+        let mut names = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::RootDir | Component::Prefix(_) => return Err(FsError::PermissionDenied),
+                component => sandbox
+                    .push(&mut names, component)
+                    .map_err(|_| FsError::PermissionDenied)?,
+            }
+        }
+
+        let mut joined = root.to_path_buf();
+        joined.extend(names);
+        Ok(joined)
+    }
+
+    /// Resolves the absolute `path` once the symlinks it goes through
+    /// are followed, `read_link` returning the target of the path it
+    /// is given if it is a symlink. The last component is followed
+    /// only if `follow_last` says so, or if `path` ends with a slash.
+    ///
+    /// A relative target is resolved from the directory of the
+    /// symlink, and a `..` after a symlink goes to the parent of its
+    /// target. Fails with [`FsError::SymlinkLoop`] once too many
+    /// symlinks are followed, and like [`Self::names`].
+    pub fn resolve<F>(&self, path: &Path, follow_last: bool, mut read_link: F) -> Result<PathBuf>
+    where
+        F: FnMut(&Path) -> Result<Option<PathBuf>>,
+    {
+        if !path.has_root() {
+            return Err(FsError::InvalidInput);
+        }
+
+        let follow_last = follow_last || is_directory_path(path);
+        // The components left to resolve, in reverse order.
+        let mut pending = Vec::new();
+        let mut names = Vec::new();
+        let mut followed_symlinks = 0;
+        schedule(path, &mut names, &mut pending)?;
+
+        while let Some(name) = pending.pop() {
+            if name == "." {
+                continue;
+            }
+            if name == ".." {
+                self.push(&mut names, Component::ParentDir)?;
+                continue;
+            }
+
+            names.push(name);
+            if pending.is_empty() && !follow_last {
+                continue;
+            }
+
+            let target = match read_link(&to_path(names.clone()))? {
+                Some(target) => target,
+                None => continue,
+            };
+            followed_symlinks += 1;
+            if followed_symlinks > self.max_symlinks {
+                return Err(FsError::SymlinkLoop);
+            }
+
+            names.pop();
+            schedule(&target, &mut names, &mut pending)?;
+        }
+
+        Ok(to_path(names))
+    }
+
+    /// Applies `component` to the `names` of a path.
+    fn push(&self, names: &mut Vec<OsString>, component: Component<'_>) -> Result<()> {
+        match component {
+            Component::Normal(name) => names.push(name.to_os_string()),
+            Component::RootDir => names.clear(),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if names.pop().is_none() && self.parent_of_root == ParentOfRoot::Reject {
+                    return Err(FsError::InvalidInput);
+                }
+            }
+            Component::Prefix(_) => return Err(FsError::InvalidInput),
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `path` designates a directory by its syntax: it ends with
+/// a slash, `.` or `..`. Such a path can't be a file, nor a symlink
+/// that isn't followed.
+pub fn is_directory_path(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    path.ends_with('/')
+        || path == "."
+        || path == ".."
+        || path.ends_with("/.")
+        || path.ends_with("/..")
+}
+
+/// Adds the components of `path` to the `pending` components of a
+/// resolution, in reverse order, going back to the root first if
+/// `path` is absolute.
+fn schedule(path: &Path, names: &mut Vec<OsString>, pending: &mut Vec<OsString>) -> Result<()> {
+    if path.has_root() {
+        names.clear();
+    }
+
+    for component in path.components().rev() {
+        match component {
+            Component::Prefix(_) => return Err(FsError::InvalidInput),
+            Component::RootDir => {}
+            component => pending.push(component.as_os_str().to_os_string()),
+        }
+    }
+
+    Ok(())
+}
+
+fn to_path(names: Vec<OsString>) -> PathBuf {
+    let mut path = PathBuf::from("/");
+    path.extend(names);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn normalize(path: &str) -> Result<PathBuf> {
+        PathResolver::new().normalize(Path::new(path))
+    }
+
+    #[test]
+    fn test_normalize() {
+        for (path, normalized) in [
+            ("/", "/"),
+            ("//", "/"),
+            ("/.", "/"),
+            ("/..", "/"),
+            ("/../..", "/"),
+            ("/a", "/a"),
+            ("/a/", "/a"),
+            ("/a//", "/a"),
+            ("//a//b//", "/a/b"),
+            ("/a/.", "/a"),
+            ("/a/./b", "/a/b"),
+            ("/a/..", "/"),
+            ("/a/../b", "/b"),
+            ("/a/b/../../c", "/c"),
+            ("/a/../../b", "/b"),
+            ("/./a/./../b/.", "/b"),
+            ("/a/.../b", "/a/.../b"),
+            ("/a/..b/b..", "/a/..b/b.."),
+            ("/a b/ c ", "/a b/ c "),
+        ] {
+            assert_eq!(
+                normalize(path),
+                Ok(PathBuf::from(normalized)),
+                "normalizing `{}`",
+                path
+            );
+        }
+
+        for path in ["", ".", "..", "a", "a/b", "./a", "../a"] {
+            assert_eq!(
+                normalize(path),
+                Err(FsError::InvalidInput),
+                "normalizing the relative `{}`",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_reject_parent_of_root() {
+        let resolver = PathResolver::new().parent_of_root(ParentOfRoot::Reject);
+
+        assert_eq!(
+            resolver.normalize(Path::new("/a/b/../..")),
+            Ok(PathBuf::from("/"))
+        );
+        for path in ["/..", "/a/../..", "/a/b/../../..", "/../a"] {
+            assert_eq!(
+                resolver.normalize(Path::new(path)),
+                Err(FsError::InvalidInput),
+                "normalizing `{}`",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        let resolver = PathResolver::new();
+
+        assert_eq!(resolver.names(Path::new("/")), Ok(vec![]));
+        assert_eq!(
+            resolver.names(Path::new("/a/./b/../c/")),
+            Ok(vec![OsString::from("a"), OsString::from("c")])
+        );
+    }
+
+    #[test]
+    fn test_join() {
+        let resolver = PathResolver::new();
+        let join = |path: &str| resolver.join(Path::new("/pre/opened"), Path::new(path));
+
+        for (path, joined) in [
+            ("", "/pre/opened"),
+            (".", "/pre/opened"),
+            ("a", "/pre/opened/a"),
+            ("a/", "/pre/opened/a"),
+            ("a//b", "/pre/opened/a/b"),
+            ("a/..", "/pre/opened"),
+            ("a/../b/./c", "/pre/opened/b/c"),
+        ] {
+            assert_eq!(join(path), Ok(PathBuf::from(joined)), "joining `{}`", path);
+        }
+
+        for path in [
+            "..",
+            "../opened",
+            "a/../..",
+            "a/../../b",
+            "/",
+            "/etc/passwd",
+        ] {
+            assert_eq!(
+                join(path),
+                Err(FsError::PermissionDenied),
+                "joining `{}` escapes",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let links: HashMap<PathBuf, PathBuf> = [
+            ("/link", "/a/b"),
+            ("/a/relative", "b/c"),
+            ("/a/up", "../d"),
+            ("/a/b/c/file", "/f"),
+            ("/loop", "/loop"),
+            ("/ping", "pong"),
+            ("/pong", "ping"),
+        ]
+        .iter()
+        .map(|(link, target)| (PathBuf::from(link), PathBuf::from(target)))
+        .collect();
+        let read_link = |path: &Path| Ok(links.get(path).cloned());
+        let resolve = |path: &str, follow_last| {
+            PathResolver::new().resolve(Path::new(path), follow_last, read_link)
+        };
+
+        for (path, resolved) in [
+            ("/", "/"),
+            ("/x/y", "/x/y"),
+            ("/link", "/a/b"),
+            ("/link/c", "/a/b/c"),
+            ("/link/..", "/a"),
+            ("/link/../..", "/"),
+            ("/a/relative/x", "/a/b/c/x"),
+            ("/a/up/x", "/d/x"),
+            ("/a/relative/file", "/f"),
+            ("/a/./relative/../x", "/a/b/x"),
+        ] {
+            assert_eq!(
+                resolve(path, true),
+                Ok(PathBuf::from(resolved)),
+                "resolving `{}`",
+                path
+            );
+        }
+
+        assert_eq!(resolve("/link", false), Ok(PathBuf::from("/link")));
+        assert_eq!(resolve("/link/", false), Ok(PathBuf::from("/a/b")));
+        assert_eq!(resolve("/link/c", false), Ok(PathBuf::from("/a/b/c")));
+        assert_eq!(resolve("/loop", true), Err(FsError::SymlinkLoop));
+        assert_eq!(resolve("/loop", false), Ok(PathBuf::from("/loop")));
+        assert_eq!(resolve("/ping", true), Err(FsError::SymlinkLoop));
+        assert_eq!(resolve("relative", true), Err(FsError::InvalidInput));
+
+        let once = PathResolver::new().max_symlinks(1);
+        assert_eq!(
+            once.resolve(Path::new("/a/relative/file"), true, read_link),
+            Err(FsError::SymlinkLoop)
+        );
+    }
+
+    #[test]
+    fn test_is_directory_path() {
+        for path in ["/", "/a/", "a/", ".", "..", "/a/.", "a/..", "/a//"] {
+            assert!(is_directory_path(Path::new(path)), "`{}`", path);
+        }
+        for path in ["", "/a", "a", "a/b", "/a/.b", "/a/b.", "/a/..."] {
+            assert!(!is_directory_path(Path::new(path)), "`{}`", path);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+//! Pipes connecting two files: the bytes written to one end are read
+//! from the other, like the standard I/O of two processes in a shell
+//! pipeline.
+//!
+//! [`Pipe::channel`] creates a one-way pipe, and [`Pipe::socket_pair`]
+//! a two-way one. A blocking end waits for the other one, which must
+//! then be used from another thread; a non-blocking end fails with
+//! [`io::ErrorKind::WouldBlock`] instead, e.g. on the main thread of a
+//! browser.
+
+use crate::{FsError, Result, VirtualFile};
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, Write};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// The number of bytes a pipe holds by default, like on Linux.
+pub const DEFAULT_PIPE_CAPACITY: usize = 64 * 1024;
+
+/// The options of the pipes to create.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pipe {
+    capacity: usize,
+    nonblocking: bool,
+}
+
+impl Default for Pipe {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_PIPE_CAPACITY,
+            nonblocking: false,
+        }
+    }
+}
+
+impl Pipe {
+    /// Creates the options of a blocking pipe holding
+    /// [`DEFAULT_PIPE_CAPACITY`] bytes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of bytes the pipe holds before the writes
+    /// block, at least one.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets whether the ends of the pipe are non-blocking.
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Creates a one-way pipe with the default options.
+    pub fn channel() -> (PipeReader, PipeWriter) {
+        Self::new().open()
+    }
+
+    /// Creates a two-way pipe with the default options.
+    pub fn socket_pair() -> (PipeSocket, PipeSocket) {
+        Self::new().open_pair()
+    }
+
+    /// Creates a one-way pipe.
+    pub fn open(&self) -> (PipeReader, PipeWriter) {
+        let buffer = Arc::new(Buffer {
+            state: Mutex::new(State {
+                bytes: VecDeque::new(),
+                capacity: self.capacity,
+                reader_closed: false,
+                writer_closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+
+        (
+            PipeReader {
+                buffer: buffer.clone(),
+                nonblocking: self.nonblocking,
+            },
+            PipeWriter {
+                buffer,
+                nonblocking: self.nonblocking,
+            },
+        )
+    }
+
+    /// Creates a two-way pipe, made of two one-way pipes.
+    pub fn open_pair(&self) -> (PipeSocket, PipeSocket) {
+        let (left_reader, right_writer) = self.open();
+        let (right_reader, left_writer) = self.open();
+
+        (
+            PipeSocket {
+                reader: left_reader,
+                writer: left_writer,
+            },
+            PipeSocket {
+                reader: right_reader,
+                writer: right_writer,
+            },
+        )
+    }
+}
+
+/// The bytes in transit in a one-way pipe.
+#[derive(Debug)]
+struct Buffer {
+    state: Mutex<State>,
+    /// Notified when bytes are read or written, or an end is closed.
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    bytes: VecDeque<u8>,
+    capacity: usize,
+    reader_closed: bool,
+    writer_closed: bool,
+}
+
+impl Buffer {
+    fn lock(&self) -> io::Result<MutexGuard<'_, State>> {
+        self.state
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "poisoned pipe"))
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, State>) -> io::Result<MutexGuard<'a, State>> {
+        self.changed
+            .wait(state)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "poisoned pipe"))
+    }
+
+    fn close(&self, close: impl FnOnce(&mut State)) {
+        if let Ok(mut state) = self.state.lock() {
+            close(&mut state);
+        }
+        self.changed.notify_all();
+    }
+}
+
+/// The end of a one-way pipe the bytes are read from. Reading returns
+/// 0 once the writer is dropped and the bytes are all read.
+#[derive(Debug)]
+pub struct PipeReader {
+    buffer: Arc<Buffer>,
+    nonblocking: bool,
+}
+
+impl PipeReader {
+    /// Sets whether reading fails with `WouldBlock` rather than waiting
+    /// for bytes.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.buffer.close(|state| state.reader_closed = true);
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut state = self.buffer.lock()?;
+        while state.bytes.is_empty() {
+            if state.writer_closed {
+                return Ok(0);
+            }
+            if self.nonblocking {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            state = self.buffer.wait(state)?;
+        }
+
+        let length = buf.len().min(state.bytes.len());
+        for (byte, read) in buf.iter_mut().zip(state.bytes.drain(..length)) {
+            *byte = read;
+        }
+        self.buffer.changed.notify_all();
+
+        Ok(length)
+    }
+}
+
+impl Write for PipeReader {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "cannot write to the reader of a pipe",
+        ))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The end of a one-way pipe the bytes are written to. Writing fails
+/// with `BrokenPipe` once the reader is dropped.
+#[derive(Debug)]
+pub struct PipeWriter {
+    buffer: Arc<Buffer>,
+    nonblocking: bool,
+}
+
+impl PipeWriter {
+    /// Sets whether writing fails with `WouldBlock` rather than waiting
+    /// for room.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.buffer.close(|state| state.writer_closed = true);
+    }
+}
+
+impl Read for PipeWriter {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "cannot read from the writer of a pipe",
+        ))
+    }
+}
+
+impl Write for PipeWriter {
+    /// Writes as many bytes as there's room for, waiting for some room
+    /// if there's none.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut state = self.buffer.lock()?;
+        loop {
+            if state.reader_closed {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            if state.bytes.len() < state.capacity {
+                break;
+            }
+            if self.nonblocking {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            state = self.buffer.wait(state)?;
+        }
+
+        let length = buf.len().min(state.capacity - state.bytes.len());
+        state.bytes.extend(&buf[..length]);
+        self.buffer.changed.notify_all();
+
+        Ok(length)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An end of a two-way pipe, reading what the other end writes.
+#[derive(Debug)]
+pub struct PipeSocket {
+    reader: PipeReader,
+    writer: PipeWriter,
+}
+
+impl PipeSocket {
+    /// Sets whether reading and writing fail with `WouldBlock` rather
+    /// than waiting.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.reader.set_nonblocking(nonblocking);
+        self.writer.set_nonblocking(nonblocking);
+    }
+
+    /// Splits the end into its reader and its writer.
+    pub fn split(self) -> (PipeReader, PipeWriter) {
+        (self.reader, self.writer)
+    }
+}
+
+impl Read for PipeSocket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl Write for PipeSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+macro_rules! impl_virtual_file {
+    ($name:ident { readable: $readable:expr, writable: $writable:expr $(,)* }) => {
+        impl Seek for $name {
+            fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    concat!("cannot seek `", stringify!($name), "`"),
+                ))
+            }
+        }
+
+        impl VirtualFile for $name {
+            fn last_accessed(&self) -> u64 {
+                0
+            }
+
+            fn last_modified(&self) -> u64 {
+                0
+            }
+
+            fn created_time(&self) -> u64 {
+                0
+            }
+
+            fn size(&self) -> u64 {
+                0
+            }
+
+            fn set_len(&mut self, _new_size: u64) -> Result<()> {
+                Err(FsError::PermissionDenied)
+            }
+
+            fn unlink(&mut self) -> Result<()> {
+                Ok(())
+            }
+
+            fn bytes_available_read(&self) -> Result<Option<usize>> {
+                $name::available_read(self)
+            }
+
+            fn bytes_available_write(&self) -> Result<Option<usize>> {
+                $name::available_write(self)
+            }
+
+            fn is_open(&self) -> bool {
+                $name::is_open(self)
+            }
+
+            fn is_readable(&self) -> bool {
+                $readable
+            }
+
+            fn is_writable(&self) -> bool {
+                $writable
+            }
+
+            fn is_seekable(&self) -> bool {
+                false
+            }
+        }
+    };
+}
+
+impl PipeReader {
+    fn available_read(&self) -> Result<Option<usize>> {
+        let state = self.buffer.lock()?;
+        Ok(Some(state.bytes.len()))
+    }
+
+    fn available_write(&self) -> Result<Option<usize>> {
+        Ok(Some(0))
+    }
+
+    /// Whether there are bytes left, or the writer may write some.
+    fn is_open(&self) -> bool {
+        self.buffer
+            .lock()
+            .map(|state| !state.writer_closed || !state.bytes.is_empty())
+            .unwrap_or(false)
+    }
+}
+
+impl PipeWriter {
+    fn available_read(&self) -> Result<Option<usize>> {
+        Ok(Some(0))
+    }
+
+    fn available_write(&self) -> Result<Option<usize>> {
+        let state = self.buffer.lock()?;
+        Ok(Some(state.capacity - state.bytes.len()))
+    }
+
+    /// Whether the reader may read what's written.
+    fn is_open(&self) -> bool {
+        self.buffer
+            .lock()
+            .map(|state| !state.reader_closed)
+            .unwrap_or(false)
+    }
+}
+
+impl PipeSocket {
+    fn available_read(&self) -> Result<Option<usize>> {
+        self.reader.available_read()
+    }
+
+    fn available_write(&self) -> Result<Option<usize>> {
+        self.writer.available_write()
+    }
+
+    fn is_open(&self) -> bool {
+        self.reader.is_open() || self.writer.is_open()
+    }
+}
+
+impl_virtual_file!(PipeReader {
+    readable: true,
+    writable: false,
+});
+impl_virtual_file!(PipeWriter {
+    readable: false,
+    writable: true,
+});
+impl_virtual_file!(PipeSocket {
+    readable: true,
+    writable: true,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_channel() {
+        let (mut reader, mut writer) = Pipe::channel();
+
+        let writing = thread::spawn(move || {
+            writer.write_all(b"hello, ").unwrap();
+            writer.write_all(b"world").unwrap();
+        });
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        writing.join().unwrap();
+
+        assert_eq!(contents, "hello, world", "read until the writer is dropped");
+    }
+
+    #[test]
+    fn test_capacity() {
+        let (mut reader, mut writer) = Pipe::new().capacity(4).open();
+
+        let writing = thread::spawn(move || {
+            assert_eq!(writer.write(b"abcdef").unwrap(), 4, "the pipe is full");
+            writer.write_all(b"ef").unwrap();
+        });
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).unwrap();
+        writing.join().unwrap();
+
+        assert_eq!(contents, b"abcdef");
+    }
+
+    #[test]
+    fn test_nonblocking() {
+        let (mut reader, mut writer) = Pipe::new().capacity(2).nonblocking(true).open();
+        let mut buffer = [0; 4];
+
+        assert_eq!(
+            reader.read(&mut buffer).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert_eq!(writer.write(b"abc").unwrap(), 2);
+        assert_eq!(writer.bytes_available_write(), Ok(Some(0)));
+        assert_eq!(
+            writer.write(b"c").unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert_eq!(reader.bytes_available_read(), Ok(Some(2)));
+        assert_eq!(reader.read(&mut buffer).unwrap(), 2);
+
+        drop(writer);
+        assert_eq!(reader.read(&mut buffer).unwrap(), 0, "end of file");
+        assert!(!reader.is_open());
+    }
+
+    #[test]
+    fn test_broken_pipe() {
+        let (reader, mut writer) = Pipe::channel();
+        drop(reader);
+
+        assert!(!writer.is_open());
+        assert_eq!(
+            writer.write(b"lost").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn test_socket_pair() {
+        let (mut left, mut right) = Pipe::new().nonblocking(true).open_pair();
+        let mut buffer = [0; 8];
+
+        left.write_all(b"ping").unwrap();
+        assert_eq!(right.read(&mut buffer).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"ping");
+
+        right.write_all(b"pong").unwrap();
+        assert_eq!(left.read(&mut buffer).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"pong");
+    }
+}
@@ -0,0 +1,367 @@
+//! A file system wrapping another one, read-only.
+//!
+//! Every operation modifying the file system, like creating,
+//! writing, renaming or removing a file, fails with
+//! `PermissionDenied`, so that a prepared file system can be given to
+//! an untrusted guest as is.
+
+use crate::{
+    FileDescriptor, FileMapping, FileOpener, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, Result, VirtualFile, WatchHandle,
+};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The permission bits allowing to write.
+const WRITE_MODE: u32 = 0o222;
+
+/// The file system. It is cheap to clone, clones share the same
+/// wrapped file system.
+#[derive(Debug)]
+pub struct FileSystem<F> {
+    inner: Arc<F>,
+}
+
+impl<F> FileSystem<F> {
+    /// Wraps `inner`, read-only.
+    pub fn new(inner: F) -> Self {
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// The wrapped file system.
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+}
+
+impl<F> Clone for FileSystem<F> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Removes the write permissions from `metadata`.
+fn read_only(mut metadata: Metadata) -> Metadata {
+    metadata.mode &= !WRITE_MODE;
+    metadata
+}
+
+impl<F: crate::FileSystem> crate::FileSystem for FileSystem<F> {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let entries = self
+            .inner
+            .read_dir(path)?
+            .map(|entry| {
+                entry.map(|mut entry| {
+                    entry.metadata = entry.metadata.map(read_only);
+                    entry
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(path).map(read_only)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.symlink_metadata(path).map(read_only)
+    }
+
+    fn symlink(&self, _original: &Path, _link: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.read_link(path)
+    }
+
+    fn link(&self, _original: &Path, _link: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn chmod(&self, _path: &Path, _mode: u32) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn chown(&self, _path: &Path, _uid: Option<u32>, _gid: Option<u32>) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn set_times(
+        &self,
+        _path: &Path,
+        _accessed: Option<u64>,
+        _modified: Option<u64>,
+    ) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn remove_file(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn clone_file(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<WatchHandle> {
+        // The wrapped file system can still be modified by others.
+        self.inner.watch(path, recursive)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl<F: crate::FileSystem> FileOpener for FileSystem<F> {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        if conf.write() || conf.append() || conf.truncate() || conf.create() || conf.create_new() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let inner = self
+            .inner
+            .new_open_options()
+            .options(conf.clone())
+            .open(path)?;
+        Ok(Box::new(File { inner }))
+    }
+}
+
+/// A file of the file system, read-only.
+#[derive(Debug)]
+struct File {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+}
+
+impl VirtualFile for File {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, _new_size: u64) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read_at(offset, buf)
+    }
+
+    fn map(&self) -> Option<FileMapping> {
+        self.inner.map()
+    }
+
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the file system is read-only",
+        ))
+    }
+
+    fn bytes_available(&self) -> Result<usize> {
+        self.inner.bytes_available()
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>> {
+        self.inner.bytes_available_read()
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>> {
+        Ok(None)
+    }
+
+    fn ioctl(&mut self, request: u32, data: &[u8]) -> Result<Vec<u8>> {
+        self.inner.ioctl(request, data)
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn is_readable(&self) -> bool {
+        self.inner.is_readable()
+    }
+
+    fn is_writable(&self) -> bool {
+        false
+    }
+
+    fn is_seekable(&self) -> bool {
+        self.inner.is_seekable()
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        self.inner.read_vectored(bufs)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the file system is read-only",
+        ))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_filesystem {
+    use super::FileSystem;
+    use crate::{mem_fs, FileSystem as FS, FsError, OpenOptions};
+    use std::io::{ErrorKind, Read, Write};
+    use std::path::Path;
+
+    macro_rules! path {
+        ($path:expr) => {
+            Path::new($path)
+        };
+    }
+
+    fn image() -> mem_fs::FileSystem {
+        let fs = mem_fs::FileSystem::default();
+        fs.create_dir(path!("/etc")).unwrap();
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/etc/motd"))
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_reading() {
+        let fs = FileSystem::new(image());
+
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/etc/motd"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello");
+
+        let metadata = fs.metadata(path!("/etc/motd")).unwrap();
+        assert_eq!(metadata.len(), 5);
+        assert_eq!(metadata.mode() & 0o222, 0, "the file isn't writable");
+        assert_eq!(
+            fs.read_dir(path!("/etc"))
+                .unwrap()
+                .map(|entry| entry.unwrap().metadata.unwrap().mode() & 0o222)
+                .collect::<Vec<_>>(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_modifying_is_denied() {
+        let fs = FileSystem::new(image());
+
+        for result in [
+            fs.create_dir(path!("/tmp")),
+            fs.remove_dir(path!("/etc")),
+            fs.rename(path!("/etc/motd"), path!("/etc/issue")),
+            fs.remove_file(path!("/etc/motd")),
+            fs.link(path!("/etc/motd"), path!("/etc/issue")),
+            fs.symlink(path!("motd"), path!("/etc/issue")),
+            fs.chmod(path!("/etc/motd"), 0o777),
+            fs.chown(path!("/etc/motd"), Some(0), None),
+            fs.set_times(path!("/etc/motd"), Some(0), None),
+            fs.clone_file(path!("/etc/motd"), path!("/etc/issue")),
+        ] {
+            assert_eq!(result, Err(FsError::PermissionDenied));
+        }
+
+        let configurations: [fn(&mut OpenOptions) -> &mut OpenOptions; 4] = [
+            |options| options.write(true),
+            |options| options.append(true),
+            |options| options.read(true).truncate(true),
+            |options| options.read(true).create(true),
+        ];
+        for configure in configurations {
+            assert_eq!(
+                configure(&mut fs.new_open_options())
+                    .open(path!("/etc/motd"))
+                    .map(|_| ()),
+                Err(FsError::PermissionDenied)
+            );
+        }
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/etc/motd"))
+            .unwrap();
+        assert_eq!(
+            file.write(b"bye").map_err(|err| err.kind()),
+            Err(ErrorKind::PermissionDenied)
+        );
+        assert_eq!(file.set_len(0), Err(FsError::PermissionDenied));
+        assert_eq!(file.unlink(), Err(FsError::PermissionDenied));
+        assert!(!file.is_writable());
+
+        assert_eq!(fs.inner().metadata(path!("/etc/motd")).unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,312 @@
+//! A file system confined to a directory of another one, like a
+//! pre-opened directory.
+//!
+//! Every path is resolved before being handed to the wrapped file
+//! system: `.` and `..` are applied, without going above the
+//! directory, and the symlinks are followed by the sandbox, an
+//! absolute target starting from the directory. The wrapped file
+//! system is thus only given paths without symlinks, except the last
+//! component when it must not be followed, so that no backend can be
+//! escaped through a symlink.
+//!
+//! A symlink replaced between the resolution and the operation can
+//! still be followed by the wrapped file system.
+
+use crate::{
+    is_directory_path, FileOpener, FsError, Metadata, OpenOptions, OpenOptionsConfig, PathResolver,
+    ReadDir, Result, VirtualFile, WatchHandle,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The file system. It is cheap to clone, clones share the same
+/// wrapped file system.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    inner: Arc<dyn crate::FileSystem>,
+    root: PathBuf,
+}
+
+impl FileSystem {
+    /// Confines `inner` to the directory `root`, an absolute path of
+    /// `inner`; the directory is `/` in the sandbox.
+    pub fn new(inner: Arc<dyn crate::FileSystem>, root: &Path) -> Result<Self> {
+        Ok(Self {
+            inner,
+            root: PathResolver::new().normalize(root)?,
+        })
+    }
+
+    /// The wrapped file system.
+    pub fn inner(&self) -> &dyn crate::FileSystem {
+        &*self.inner
+    }
+
+    /// The directory the sandbox is confined to, in the wrapped file
+    /// system.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves the absolute `path` of the sandbox into a path of the
+    /// wrapped file system, following its last component if
+    /// `follow_last` says so.
+    fn resolve(&self, path: &Path, follow_last: bool) -> Result<PathBuf> {
+        let resolved = PathResolver::new().resolve(path, follow_last, |path| {
+            let path = self.inner_path(path);
+            match self.inner.symlink_metadata(&path) {
+                Ok(metadata) if metadata.ft.is_symlink() => self.inner.read_link(&path).map(Some),
+                // The wrapped file system reports the errors, if any,
+                // once the path is resolved.
+                _ => Ok(None),
+            }
+        })?;
+
+        let mut inner_path = self.inner_path(&resolved);
+        if is_directory_path(path) {
+            inner_path.push("");
+        }
+
+        Ok(inner_path)
+    }
+
+    /// Returns the path of the wrapped file system of the normalized
+    /// `path` of the sandbox.
+    fn inner_path(&self, path: &Path) -> PathBuf {
+        let mut inner_path = self.root.clone();
+        inner_path.extend(path.components().skip(1));
+        inner_path
+    }
+
+    /// Returns the path of the sandbox of the `inner_path` of the
+    /// wrapped file system.
+    fn outer_path(&self, inner_path: &Path) -> Result<PathBuf> {
+        outer_path(&self.root, inner_path).ok_or(FsError::InvalidData)
+    }
+}
+
+/// Returns the path of the sandbox confined to `root` of the
+/// `inner_path` of the wrapped file system, if it is in the sandbox.
+fn outer_path(root: &Path, inner_path: &Path) -> Option<PathBuf> {
+    let path = inner_path.strip_prefix(root).ok()?;
+    Some(Path::new("/").join(path))
+}
+
+impl crate::FileSystem for FileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let entries = self
+            .inner
+            .read_dir(&self.resolve(path, true)?)?
+            .map(|entry| {
+                let mut entry = entry?;
+                entry.path = self.outer_path(&entry.path)?;
+                Ok(entry)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner.create_dir(&self.resolve(path, false)?)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.inner.remove_dir(&self.resolve(path, false)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner
+            .rename(&self.resolve(from, false)?, &self.resolve(to, false)?)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(&self.resolve(path, true)?)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.symlink_metadata(&self.resolve(path, false)?)
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        // The target is resolved by the sandbox when it's followed.
+        self.inner.symlink(original, &self.resolve(link, false)?)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.read_link(&self.resolve(path, false)?)
+    }
+
+    fn link(&self, original: &Path, link: &Path) -> Result<()> {
+        self.inner
+            .link(&self.resolve(original, false)?, &self.resolve(link, false)?)
+    }
+
+    fn chmod(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.chmod(&self.resolve(path, true)?, mode)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.inner.chown(&self.resolve(path, true)?, uid, gid)
+    }
+
+    fn set_times(&self, path: &Path, accessed: Option<u64>, modified: Option<u64>) -> Result<()> {
+        self.inner
+            .set_times(&self.resolve(path, true)?, accessed, modified)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.inner.remove_file(&self.resolve(path, false)?)
+    }
+
+    fn clone_file(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner
+            .clone_file(&self.resolve(from, true)?, &self.resolve(to, false)?)
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<WatchHandle> {
+        // The events must not tell the paths of the wrapped file
+        // system, nor the changes out of the sandbox.
+        let root = self.root.clone();
+        Ok(self
+            .inner
+            .watch(&self.resolve(path, true)?, recursive)?
+            .map(move |event| event.map_paths(|path| outer_path(&root, path))))
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl FileOpener for FileSystem {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let path = self.resolve(path, true)?;
+        self.inner
+            .new_open_options()
+            .options(conf.clone())
+            .open(path)
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_filesystem {
+    use super::FileSystem;
+    use crate::{mem_fs, FileSystem as FS, FsError, WatchEvent};
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    /// A memory file system with `/secret` and `/jail/file`, and the
+    /// sandbox confined to `/jail`.
+    fn jail() -> (mem_fs::FileSystem, FileSystem) {
+        let inner = mem_fs::FileSystem::default();
+        inner.create_dir(Path::new("/jail")).unwrap();
+        for path in ["/secret", "/jail/file"] {
+            inner
+                .new_open_options()
+                .write(true)
+                .create(true)
+                .open(Path::new(path))
+                .unwrap()
+                .write_all(path.as_bytes())
+                .unwrap();
+        }
+        let sandbox = FileSystem::new(Arc::new(inner.clone()), Path::new("/jail")).unwrap();
+
+        (inner, sandbox)
+    }
+
+    fn read(fs: &FileSystem, path: &str) -> Result<String, FsError> {
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(Path::new(path))?
+            .read_to_string(&mut contents)
+            .unwrap();
+        Ok(contents)
+    }
+
+    #[test]
+    fn test_parent_of_root() {
+        let (_, fs) = jail();
+
+        assert_eq!(read(&fs, "/file"), Ok("/jail/file".to_string()));
+        assert_eq!(read(&fs, "/../file"), Ok("/jail/file".to_string()));
+        assert!(read(&fs, "/../../jail/file").is_err());
+        assert!(read(&fs, "/../secret").is_err());
+        assert_eq!(
+            fs.read_dir(Path::new("/.."))
+                .unwrap()
+                .map(|entry| entry.unwrap().path)
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("/file")]
+        );
+    }
+
+    #[test]
+    fn test_symlinks() {
+        let (inner, fs) = jail();
+        inner
+            .symlink(Path::new("/secret"), Path::new("/jail/absolute"))
+            .unwrap();
+        inner
+            .symlink(Path::new("../../secret"), Path::new("/jail/relative"))
+            .unwrap();
+        inner
+            .symlink(Path::new("../jail/file"), Path::new("/jail/inside"))
+            .unwrap();
+
+        assert!(read(&fs, "/absolute").is_err());
+        assert!(read(&fs, "/relative").is_err());
+        assert!(read(&fs, "/inside").is_err());
+        assert!(fs
+            .symlink_metadata(Path::new("/absolute"))
+            .unwrap()
+            .ft
+            .is_symlink());
+        assert_eq!(
+            fs.read_link(Path::new("/absolute")),
+            Ok(PathBuf::from("/secret"))
+        );
+
+        // The absolute target starts from the root of the sandbox.
+        fs.symlink(Path::new("/file"), Path::new("/link")).unwrap();
+        assert_eq!(read(&fs, "/link"), Ok("/jail/file".to_string()));
+        fs.remove_file(Path::new("/link")).unwrap();
+        assert!(fs.metadata(Path::new("/file")).is_ok());
+    }
+
+    #[test]
+    fn test_watch() {
+        let (inner, fs) = jail();
+        let watch = fs.watch(Path::new("/"), true).unwrap();
+
+        fs.create_dir(Path::new("/dir")).unwrap();
+        inner
+            .rename(Path::new("/secret"), Path::new("/jail/secret"))
+            .unwrap();
+        inner
+            .rename(Path::new("/jail/file"), Path::new("/file"))
+            .unwrap();
+
+        assert_eq!(
+            watch.pending().collect::<Vec<_>>(),
+            vec![
+                WatchEvent::Created(PathBuf::from("/dir")),
+                WatchEvent::Created(PathBuf::from("/secret")),
+                WatchEvent::Removed(PathBuf::from("/file")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let (_, fs) = jail();
+
+        assert_eq!(read(&fs, "file"), Err(FsError::InvalidInput));
+    }
+}
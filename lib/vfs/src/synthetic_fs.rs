@@ -0,0 +1,391 @@
+//! A read-only file system whose files are generated on demand.
+//!
+//! Each file is given a generator, which produces its contents every
+//! time the file is opened or its metadata are read. Directories are
+//! created implicitly as the parents of the files. It is intended for
+//! introspection file systems, like `/proc`.
+
+use crate::{
+    time, DirEntry, FileOpener, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    PathResolver, ReadDir, Result, VirtualFile,
+};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Produces the contents of a file.
+pub type Generator = Arc<dyn Fn() -> Vec<u8> + Send + Sync>;
+
+enum Node {
+    Directory(BTreeMap<OsString, Node>),
+    File(Generator),
+}
+
+impl Node {
+    fn metadata(&self) -> Metadata {
+        let now = time();
+        // The files are generated, thus read-only.
+        let (ft, len, mode) = match self {
+            Self::Directory(_) => (
+                FileType {
+                    dir: true,
+                    ..Default::default()
+                },
+                0,
+                0o555,
+            ),
+            Self::File(generator) => (
+                FileType {
+                    file: true,
+                    ..Default::default()
+                },
+                generator().len() as u64,
+                0o444,
+            ),
+        };
+
+        Metadata {
+            ft,
+            accessed: now,
+            created: now,
+            modified: now,
+            len,
+            nlink: 1,
+            mode,
+            ..Default::default()
+        }
+    }
+}
+
+/// The file system. It is cheap to clone, clones share the same
+/// files.
+#[derive(Clone)]
+pub struct FileSystem {
+    root: Arc<RwLock<Node>>,
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        Self {
+            root: Arc::new(RwLock::new(Node::Directory(BTreeMap::new()))),
+        }
+    }
+}
+
+impl FileSystem {
+    /// Adds a file at `path`, whose contents are produced by
+    /// `generator`, creating its missing parent directories.
+    pub fn insert_file<F>(&self, path: &Path, generator: F) -> Result<()>
+    where
+        F: Fn() -> Vec<u8> + Send + Sync + 'static,
+    {
+        let names = names(path)?;
+        let (file_name, parent_names) = names.split_last().ok_or(FsError::AlreadyExists)?;
+
+        let mut root = self.root.write().map_err(|_| FsError::Lock)?;
+        let mut node = &mut *root;
+        for name in parent_names {
+            node = match node {
+                Node::Directory(children) => children
+                    .entry(name.clone())
+                    .or_insert_with(|| Node::Directory(BTreeMap::new())),
+                Node::File(_) => return Err(FsError::BaseNotDirectory),
+            };
+        }
+
+        match node {
+            Node::Directory(children) if children.contains_key(file_name) => {
+                Err(FsError::AlreadyExists)
+            }
+            Node::Directory(children) => {
+                children.insert(file_name.clone(), Node::File(Arc::new(generator)));
+                Ok(())
+            }
+            Node::File(_) => Err(FsError::BaseNotDirectory),
+        }
+    }
+
+    /// Runs `f` on the node at `path`.
+    fn with_node<T>(&self, path: &Path, f: impl FnOnce(&Node) -> Result<T>) -> Result<T> {
+        let root = self.root.read().map_err(|_| FsError::Lock)?;
+        let mut node = &*root;
+        for name in names(path)? {
+            node = match node {
+                Node::Directory(children) => children.get(&name).ok_or(FsError::EntityNotFound)?,
+                Node::File(_) => return Err(FsError::BaseNotDirectory),
+            };
+        }
+
+        f(node)
+    }
+}
+
+impl fmt::Debug for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn debug(
+            node: &Node,
+            name: &str,
+            indent: usize,
+            f: &mut fmt::Formatter<'_>,
+        ) -> fmt::Result {
+            match node {
+                Node::Directory(children) => {
+                    writeln!(f, "{:indent$}{}/", "", name, indent = indent)?;
+                    for (name, child) in children {
+                        debug(child, &name.to_string_lossy(), indent + 2, f)?;
+                    }
+                    Ok(())
+                }
+                Node::File(_) => writeln!(f, "{:indent$}{}", "", name, indent = indent),
+            }
+        }
+
+        match self.root.try_read() {
+            Ok(root) => debug(&root, "", 0, f),
+            Err(_) => write!(f, "synthetic_fs::FileSystem (locked)"),
+        }
+    }
+}
+
+impl crate::FileSystem for FileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.with_node(path, |node| match node {
+            Node::Directory(children) => Ok(ReadDir::new(
+                children
+                    .iter()
+                    .map(|(name, child)| DirEntry {
+                        path: path.join(name),
+                        metadata: Ok(child.metadata()),
+                    })
+                    .collect(),
+            )),
+            Node::File(_) => Err(FsError::BaseNotDirectory),
+        })
+    }
+
+    fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.with_node(path, |node| Ok(node.metadata()))
+    }
+
+    fn remove_file(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+}
+
+impl FileOpener for FileSystem {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        if conf.write() || conf.append() || conf.truncate() || conf.create_new() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let contents = self.with_node(path, |node| match node {
+            Node::File(generator) => Ok(generator()),
+            Node::Directory(_) => Err(FsError::NotAFile),
+        })?;
+
+        Ok(Box::new(File {
+            contents: Cursor::new(contents),
+            created: time(),
+        }))
+    }
+}
+
+/// An opened file. Its contents are generated once, when it's opened.
+#[derive(Debug)]
+pub struct File {
+    contents: Cursor<Vec<u8>>,
+    created: u64,
+}
+
+impl VirtualFile for File {
+    fn last_accessed(&self) -> u64 {
+        self.created
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.created
+    }
+
+    fn created_time(&self) -> u64 {
+        self.created
+    }
+
+    fn size(&self) -> u64 {
+        self.contents.get_ref().len() as u64
+    }
+
+    fn set_len(&mut self, _new_size: u64) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn bytes_available(&self) -> Result<usize> {
+        Ok(self
+            .contents
+            .get_ref()
+            .len()
+            .saturating_sub(self.contents.position() as usize))
+    }
+
+    fn is_writable(&self) -> bool {
+        false
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.contents.read(buf)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.contents.seek(position)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "synthetic files are read-only",
+        ))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Splits an absolute path into the names of its components.
+fn names(path: &Path) -> Result<Vec<OsString>> {
+    PathResolver::new().names(path)
+}
+
+#[cfg(test)]
+mod test_filesystem {
+    use super::FileSystem;
+    use crate::{FileSystem as FS, FsError};
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_insert_file() {
+        let fs = FileSystem::default();
+        assert_eq!(
+            fs.insert_file(Path::new("/self/status"), || b"ok".to_vec()),
+            Ok(())
+        );
+        assert_eq!(
+            fs.insert_file(Path::new("/self/status"), Vec::new),
+            Err(FsError::AlreadyExists)
+        );
+        assert_eq!(
+            fs.insert_file(Path::new("/self/status/nested"), Vec::new),
+            Err(FsError::BaseNotDirectory)
+        );
+
+        assert!(fs.metadata(Path::new("/self")).unwrap().is_dir());
+        assert_eq!(fs.metadata(Path::new("/self/status")).unwrap().len, 2);
+        assert!(matches!(
+            fs.metadata(Path::new("/missing")),
+            Err(FsError::EntityNotFound)
+        ));
+        assert_eq!(
+            fs.read_dir(Path::new("/self"))
+                .unwrap()
+                .map(|entry| entry.unwrap().path)
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("/self/status")]
+        );
+    }
+
+    #[test]
+    fn test_contents_are_generated_on_open() {
+        let fs = FileSystem::default();
+        let counter = Arc::new(AtomicUsize::new(0));
+        fs.insert_file(Path::new("/counter"), {
+            let counter = counter.clone();
+            move || {
+                counter
+                    .fetch_add(1, Ordering::SeqCst)
+                    .to_string()
+                    .into_bytes()
+            }
+        })
+        .unwrap();
+
+        let read = || {
+            let mut contents = String::new();
+            fs.new_open_options()
+                .read(true)
+                .open(Path::new("/counter"))
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            contents
+        };
+        assert_eq!(read(), "0");
+        assert_eq!(read(), "1");
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_read_only() {
+        let fs = FileSystem::default();
+        fs.insert_file(Path::new("/file"), || b"hello".to_vec())
+            .unwrap();
+
+        assert!(matches!(
+            fs.new_open_options().write(true).open(Path::new("/file")),
+            Err(FsError::PermissionDenied)
+        ));
+        assert!(matches!(
+            fs.new_open_options().read(true).open(Path::new("/")),
+            Err(FsError::NotAFile)
+        ));
+        assert_eq!(
+            fs.create_dir(Path::new("/dir")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.remove_file(Path::new("/file")),
+            Err(FsError::PermissionDenied)
+        );
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .open(Path::new("/file"))
+            .unwrap();
+        assert!(file.write_all(b"bye").is_err());
+    }
+}
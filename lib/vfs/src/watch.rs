@@ -0,0 +1,200 @@
+//! Notifications of the changes of a file system, see
+//! [`FileSystem::watch`](crate::FileSystem::watch).
+
+use std::any::Any;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// A change of a file system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// A file, directory or link was created.
+    Created(PathBuf),
+    /// The contents or the metadata of a file or directory changed.
+    Modified(PathBuf),
+    /// A file, directory or link was removed.
+    Removed(PathBuf),
+    /// A file, directory or link was moved.
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+impl WatchEvent {
+    /// The paths the event is about.
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            Self::Created(path) | Self::Modified(path) | Self::Removed(path) => vec![path],
+            Self::Renamed { from, to } => vec![from, to],
+        }
+    }
+
+    /// Rewrites the paths of the event with `map`, which returns `None`
+    /// for the paths out of sight. A rename whose only one side is in
+    /// sight becomes a creation or a removal; the other events are
+    /// dropped if their path is out of sight.
+    pub fn map_paths(self, map: impl Fn(&Path) -> Option<PathBuf>) -> Option<Self> {
+        Some(match self {
+            Self::Created(path) => Self::Created(map(&path)?),
+            Self::Modified(path) => Self::Modified(map(&path)?),
+            Self::Removed(path) => Self::Removed(map(&path)?),
+            Self::Renamed { from, to } => match (map(&from), map(&to)) {
+                (Some(from), Some(to)) => Self::Renamed { from, to },
+                (Some(from), None) => Self::Removed(from),
+                (None, Some(to)) => Self::Created(to),
+                (None, None) => return None,
+            },
+        })
+    }
+}
+
+type EventMap = Box<dyn Fn(WatchEvent) -> Option<WatchEvent> + Send>;
+
+/// Receives the events of a watch. The watch stops when the handle
+/// is dropped.
+pub struct WatchHandle {
+    receiver: Receiver<WatchEvent>,
+    /// Whatever must live as long as the watch, e.g. the watcher of
+    /// the host.
+    _watcher: Option<Box<dyn Any + Send>>,
+    /// The maps applied to the received events, in order.
+    maps: Vec<EventMap>,
+}
+
+impl WatchHandle {
+    /// Creates a handle, along with the sender of its events.
+    pub fn new() -> (Sender<WatchEvent>, Self) {
+        let (sender, receiver) = mpsc::channel();
+
+        (
+            sender,
+            Self {
+                receiver,
+                _watcher: None,
+                maps: Vec::new(),
+            },
+        )
+    }
+
+    /// Keeps `watcher` alive as long as the handle.
+    pub fn keep_alive(mut self, watcher: impl Any + Send) -> Self {
+        self._watcher = Some(Box::new(watcher));
+        self
+    }
+
+    /// Rewrites the events received from now on with `map`, which
+    /// returns `None` to drop an event. It is how a file system
+    /// wrapping another one translates the paths of its events.
+    pub fn map(mut self, map: impl Fn(WatchEvent) -> Option<WatchEvent> + Send + 'static) -> Self {
+        self.maps.push(Box::new(map));
+        self
+    }
+
+    /// Applies the maps of the handle to `event`.
+    fn apply(&self, event: WatchEvent) -> Option<WatchEvent> {
+        self.maps.iter().try_fold(event, |event, map| map(event))
+    }
+
+    /// Waits for the next event. It returns `None` when no more events
+    /// can come, e.g. once the file system is dropped.
+    pub fn recv(&self) -> Option<WatchEvent> {
+        loop {
+            if let Some(event) = self.apply(self.receiver.recv().ok()?) {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Returns the next event, if one is pending.
+    pub fn try_recv(&self) -> Option<WatchEvent> {
+        self.pending().next()
+    }
+
+    /// Waits for the next event, at most for `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<WatchEvent> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if let Some(event) = self.apply(self.receiver.recv_timeout(timeout).ok()?) {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Iterates over the pending events, without waiting.
+    pub fn pending(&self) -> impl Iterator<Item = WatchEvent> + '_ {
+        self.receiver
+            .try_iter()
+            .filter_map(move |event| self.apply(event))
+    }
+}
+
+impl fmt::Debug for WatchHandle {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("WatchHandle").finish()
+    }
+}
+
+/// The watches of a file system which sends the events itself.
+#[cfg(feature = "mem-fs")]
+#[derive(Debug, Default)]
+pub(crate) struct Watchers {
+    watchers: Vec<Watcher>,
+}
+
+#[cfg(feature = "mem-fs")]
+#[derive(Debug)]
+struct Watcher {
+    path: PathBuf,
+    recursive: bool,
+    sender: Sender<WatchEvent>,
+}
+
+#[cfg(feature = "mem-fs")]
+impl Watcher {
+    /// Whether a change of `path` concerns the watch, which covers
+    /// the immediate children of the watched path, or all of its
+    /// descendants when it is recursive.
+    fn is_concerned_by(&self, path: &Path) -> bool {
+        path == self.path
+            || path.parent() == Some(self.path.as_path())
+            || (self.recursive && path.starts_with(&self.path))
+    }
+}
+
+#[cfg(feature = "mem-fs")]
+impl Watchers {
+    /// Starts watching `path`, which must be normalized.
+    pub(crate) fn watch(&mut self, path: PathBuf, recursive: bool) -> WatchHandle {
+        let (sender, handle) = WatchHandle::new();
+        self.watchers.push(Watcher {
+            path,
+            recursive,
+            sender,
+        });
+
+        handle
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.watchers.is_empty()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.watchers.len()
+    }
+
+    /// Sends `event` to the watches it concerns. The watches whose
+    /// handle was dropped are removed.
+    pub(crate) fn notify(&mut self, event: WatchEvent) {
+        self.watchers.retain(|watcher| {
+            let concerned = event
+                .paths()
+                .into_iter()
+                .any(|path| watcher.is_concerned_by(path));
+
+            !concerned || watcher.sender.send(event.clone()).is_ok()
+        });
+    }
+}
@@ -12,8 +12,22 @@ use wasmer_vnet::{
     VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket, VirtualWebSocket,
 };
 
+mod proxy;
+
+pub use proxy::{NetProxy, NetProxyKind};
+
 #[derive(Debug, Default)]
-pub struct LocalNetworking {}
+pub struct LocalNetworking {
+    proxy: Option<NetProxy>,
+}
+
+impl LocalNetworking {
+    /// Creates a networking implementation which opens outbound TCP
+    /// connections through the given proxy.
+    pub fn with_proxy(proxy: NetProxy) -> Self {
+        Self { proxy: Some(proxy) }
+    }
+}
 
 #[allow(unused_variables)]
 impl VirtualNetworking for LocalNetworking {
@@ -131,6 +145,16 @@ impl VirtualNetworking for LocalNetworking {
         peer: SocketAddr,
         timeout: Option<Duration>,
     ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        if let Some(proxy) = self.proxy.as_ref() {
+            // The stream is connected to the proxy, so its peer
+            // address isn't the one of the peer.
+            return Ok(Box::new(LocalTcpStream {
+                stream: proxy.connect(peer, timeout)?,
+                addr: peer,
+                connect_timeout: None,
+            }));
+        }
+
         let stream = if let Some(timeout) = timeout {
             std::net::TcpStream::connect_timeout(&peer, timeout)
         } else {
@@ -0,0 +1,406 @@
+//! Running several WASI modules together, as tasks sharing the same
+//! services.
+//!
+//! A [`WasiGroup`] owns the runtime (networking, bus, threads) and
+//! optionally the file system given to each of its tasks. Tasks run
+//! on their own thread and never outlive the group: joining it waits
+//! for all of them, and a failing task shuts the others down.
+
+use crate::{
+    PluggableRuntimeImplementation, WasiError, WasiRuntimeImplementation, WasiStateBuilder,
+    WasiStateCreationError, WasiThreadError, WasiThreadId, WasiTtyState,
+};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use thiserror::Error;
+use wasmer::{ExportError, Instance, InstantiationError, Module, RuntimeError, Store};
+use wasmer_vbus::VirtualBus;
+use wasmer_vfs::{FileSystem, Metadata, OpenOptions, ReadDir, WatchHandle};
+use wasmer_vnet::VirtualNetworking;
+use wasmer_wasi_types::wasi::Errno;
+
+/// Error returned when a task can't be spawned in a [`WasiGroup`].
+#[derive(Error, Debug)]
+pub enum WasiGroupError {
+    #[error(transparent)]
+    State(#[from] WasiStateCreationError),
+    #[error(transparent)]
+    Wasi(#[from] WasiError),
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    #[error("failed to spawn the thread of the task: {0}")]
+    Spawn(#[from] std::io::Error),
+}
+
+/// The reason why a task of a [`WasiGroup`] failed.
+#[derive(Error, Debug)]
+pub enum WasiTaskError {
+    #[error("exited with code {0}")]
+    Exit(u32),
+    #[error(transparent)]
+    Wasi(WasiError),
+    #[error(transparent)]
+    Runtime(RuntimeError),
+    #[error("panicked")]
+    Panicked,
+}
+
+/// A task of a [`WasiGroup`] that failed.
+#[derive(Debug)]
+pub struct WasiTaskFailure {
+    /// The name the task was spawned with.
+    pub name: String,
+    pub error: WasiTaskError,
+}
+
+impl fmt::Display for WasiTaskFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` {}", self.name, self.error)
+    }
+}
+
+/// Error returned when joining a [`WasiGroup`], listing all the tasks
+/// that failed, in the order they failed.
+///
+/// Tasks shut down by the group are not part of it.
+#[derive(Error, Debug)]
+pub struct WasiGroupFailure {
+    pub failures: Vec<WasiTaskFailure>,
+}
+
+impl fmt::Display for WasiGroupFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} task(s) failed", self.failures.len())?;
+        for (i, failure) in self.failures.iter().enumerate() {
+            write!(f, "{} {}", if i == 0 { ":" } else { "," }, failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// A group of WASI modules running concurrently, sharing a runtime and
+/// a file system.
+///
+/// Tasks are shut down in the reverse order they were spawned, each
+/// one only once the tasks spawned after it are done, so that a task
+/// providing a service to the ones spawned after it outlives them.
+///
+/// Shutting a task down is cooperative: the task exits the next time
+/// it yields to the runtime, e.g. while sleeping, polling or calling
+/// `sched_yield`. A task that never yields is waited for.
+///
+/// ```ignore
+/// let mut group = WasiGroup::new();
+/// group.spawn("server", Store::default(), &server, &mut WasiState::new("server"))?;
+/// group.spawn("client", Store::default(), &client, &mut WasiState::new("client"))?;
+/// group.join()?;
+/// ```
+pub struct WasiGroup {
+    runtime: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+    fs: Option<Arc<dyn FileSystem>>,
+    tasks: Vec<WasiTask>,
+    done_sender: mpsc::Sender<(usize, Result<(), WasiTaskError>)>,
+    done: mpsc::Receiver<(usize, Result<(), WasiTaskError>)>,
+}
+
+struct WasiTask {
+    name: String,
+    cancelled: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WasiGroup {
+    /// Creates a group with the default runtime implementation.
+    pub fn new() -> Self {
+        Self::with_runtime(PluggableRuntimeImplementation::default())
+    }
+
+    /// Creates a group whose tasks all use `runtime`.
+    pub fn with_runtime<R>(runtime: R) -> Self
+    where
+        R: WasiRuntimeImplementation + Send + Sync + 'static,
+    {
+        let (done_sender, done) = mpsc::channel();
+
+        Self {
+            runtime: Arc::new(runtime),
+            fs: None,
+            tasks: Vec::new(),
+            done_sender,
+            done,
+        }
+    }
+
+    /// Sets the file system shared by the tasks spawned afterwards,
+    /// replacing the one of their [`WasiStateBuilder`].
+    pub fn set_fs(&mut self, fs: Box<dyn FileSystem>) -> &mut Self {
+        self.fs = Some(Arc::from(fs));
+
+        self
+    }
+
+    /// Instantiates `module` with the WASI state built by `builder`, and
+    /// runs its `_start` function on a new thread.
+    ///
+    /// Errors happening before `_start` is called are returned here,
+    /// the ones happening afterwards are reported by [`WasiGroup::join`].
+    pub fn spawn(
+        &mut self,
+        name: impl Into<String>,
+        mut store: Store,
+        module: &Module,
+        builder: &mut WasiStateBuilder,
+    ) -> Result<(), WasiGroupError> {
+        let name = name.into();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        if let Some(fs) = &self.fs {
+            builder.set_fs(Box::new(SharedFileSystem(fs.clone())));
+        }
+        builder.runtime(TaskRuntime {
+            inner: self.runtime.clone(),
+            cancelled: cancelled.clone(),
+        });
+
+        let mut wasi_env = builder.finalize(&mut store)?;
+        let import_object = wasi_env.import_object(&mut store, module)?;
+        let instance = Instance::new(&mut store, module, &import_object)?;
+        wasi_env.initialize(&mut store, &instance)?;
+        let start = instance.exports.get_function("_start")?.clone();
+
+        let index = self.tasks.len();
+        let done_sender = self.done_sender.clone();
+        let thread = thread::Builder::new().name(name.clone()).spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| start.call(&mut store, &[])));
+            let result = match result {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(err)) => match err.downcast::<WasiError>() {
+                    Ok(WasiError::Exit(0)) => Ok(()),
+                    Ok(WasiError::Exit(code)) => Err(WasiTaskError::Exit(code)),
+                    Ok(err) => Err(WasiTaskError::Wasi(err)),
+                    Err(err) => Err(WasiTaskError::Runtime(err)),
+                },
+                Err(_) => Err(WasiTaskError::Panicked),
+            };
+            drop(instance);
+
+            // The group waits for its tasks, even when dropped, so it
+            // is still listening.
+            let _ = done_sender.send((index, result));
+        })?;
+
+        self.tasks.push(WasiTask {
+            name,
+            cancelled,
+            thread: Some(thread),
+        });
+
+        Ok(())
+    }
+
+    /// Waits for all the tasks to finish.
+    ///
+    /// As soon as a task fails, the remaining ones are shut down.
+    pub fn join(mut self) -> Result<(), WasiGroupFailure> {
+        self.wait(false)
+    }
+
+    /// Shuts all the tasks down, and waits for them to finish.
+    pub fn shutdown(mut self) -> Result<(), WasiGroupFailure> {
+        self.wait(true)
+    }
+
+    fn wait(&mut self, mut shutting_down: bool) -> Result<(), WasiGroupFailure> {
+        let mut failures = Vec::new();
+
+        while let Some(last) = self.tasks.iter().rposition(|task| task.thread.is_some()) {
+            if shutting_down {
+                self.tasks[last].cancelled.store(true, Ordering::Release);
+            }
+
+            // A running task holds a sender, so this can't fail.
+            let (index, result) = self.done.recv().unwrap();
+            let task = &mut self.tasks[index];
+            if let Some(thread) = task.thread.take() {
+                let _ = thread.join();
+            }
+
+            match result {
+                Err(error) if !task.cancelled.load(Ordering::Acquire) => {
+                    failures.push(WasiTaskFailure {
+                        name: task.name.clone(),
+                        error,
+                    });
+                    shutting_down = true;
+                }
+                _ => {}
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(WasiGroupFailure { failures })
+        }
+    }
+}
+
+impl Default for WasiGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for WasiGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasiGroup")
+            .field("runtime", &self.runtime)
+            .field("fs", &self.fs)
+            .field(
+                "tasks",
+                &self.tasks.iter().map(|task| &task.name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Drop for WasiGroup {
+    /// Tasks don't outlive their group: the ones still running are
+    /// shut down.
+    fn drop(&mut self) {
+        let _ = self.wait(true);
+    }
+}
+
+/// The runtime of a task, which stops the task once the group shuts
+/// it down.
+#[derive(Debug)]
+struct TaskRuntime {
+    inner: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl WasiRuntimeImplementation for TaskRuntime {
+    fn bus(&self) -> &dyn VirtualBus {
+        self.inner.bus()
+    }
+
+    fn networking(&self) -> &dyn VirtualNetworking {
+        self.inner.networking()
+    }
+
+    fn thread_generate_id(&self) -> WasiThreadId {
+        self.inner.thread_generate_id()
+    }
+
+    fn tty_get(&self) -> WasiTtyState {
+        self.inner.tty_get()
+    }
+
+    fn tty_set(&self, tty_state: WasiTtyState) {
+        self.inner.tty_set(tty_state)
+    }
+
+    fn thread_spawn(
+        &self,
+        callback: Box<dyn FnOnce() + Send + 'static>,
+    ) -> Result<(), WasiThreadError> {
+        self.inner.thread_spawn(callback)
+    }
+
+    fn thread_parallelism(&self) -> Result<usize, WasiThreadError> {
+        self.inner.thread_parallelism()
+    }
+
+    fn yield_now(&self, id: WasiThreadId) -> Result<(), WasiError> {
+        if self.cancelled.load(Ordering::Acquire) {
+            return Err(WasiError::Exit(Errno::Canceled as u32));
+        }
+        self.inner.yield_now(id)
+    }
+
+    fn getpid(&self) -> Option<u32> {
+        self.inner.getpid()
+    }
+}
+
+/// The file system of a group, as seen by one of its tasks.
+#[derive(Debug)]
+struct SharedFileSystem(Arc<dyn FileSystem>);
+
+impl FileSystem for SharedFileSystem {
+    fn read_dir(&self, path: &Path) -> wasmer_vfs::Result<ReadDir> {
+        self.0.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> wasmer_vfs::Result<()> {
+        self.0.create_dir(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> wasmer_vfs::Result<()> {
+        self.0.remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> wasmer_vfs::Result<()> {
+        self.0.rename(from, to)
+    }
+
+    fn metadata(&self, path: &Path) -> wasmer_vfs::Result<Metadata> {
+        self.0.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> wasmer_vfs::Result<Metadata> {
+        self.0.symlink_metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> wasmer_vfs::Result<()> {
+        self.0.remove_file(path)
+    }
+
+    fn clone_file(&self, from: &Path, to: &Path) -> wasmer_vfs::Result<()> {
+        self.0.clone_file(from, to)
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> wasmer_vfs::Result<()> {
+        self.0.symlink(original, link)
+    }
+
+    fn read_link(&self, path: &Path) -> wasmer_vfs::Result<PathBuf> {
+        self.0.read_link(path)
+    }
+
+    fn link(&self, original: &Path, link: &Path) -> wasmer_vfs::Result<()> {
+        self.0.link(original, link)
+    }
+
+    fn chmod(&self, path: &Path, mode: u32) -> wasmer_vfs::Result<()> {
+        self.0.chmod(path, mode)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> wasmer_vfs::Result<()> {
+        self.0.chown(path, uid, gid)
+    }
+
+    fn set_times(
+        &self,
+        path: &Path,
+        accessed: Option<u64>,
+        modified: Option<u64>,
+    ) -> wasmer_vfs::Result<()> {
+        self.0.set_times(path, accessed, modified)
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> wasmer_vfs::Result<WatchHandle> {
+        self.0.watch(path, recursive)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        self.0.new_open_options()
+    }
+}
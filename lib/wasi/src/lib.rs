@@ -35,21 +35,33 @@ compile_error!(
 
 #[macro_use]
 mod macros;
+#[cfg(feature = "sys")]
+mod group;
 mod runtime;
 mod state;
 mod syscalls;
+mod timer;
 mod utils;
+mod working_set;
 
+use crate::state::{PendingOperationGuard, PendingOperations};
 use crate::syscalls::*;
 
+#[cfg(feature = "sys")]
+pub use crate::group::{
+    WasiGroup, WasiGroupError, WasiGroupFailure, WasiTaskError, WasiTaskFailure,
+};
 pub use crate::state::{
-    Fd, Pipe, Stderr, Stdin, Stdout, WasiFs, WasiInodes, WasiState, WasiStateBuilder,
-    WasiStateCreationError, ALL_RIGHTS, VIRTUAL_ROOT_FD,
+    AnsiEscapes, Fd, FsAccess, FsAuditHook, HostError, PathError, PathErrorFn, PendingOperation,
+    PendingOperationId, PendingOperationKind, Pipe, Stderr, Stdin, Stdout, WasiFs, WasiInodes,
+    WasiResource, WasiState, WasiStateBuilder, WasiStateCreationError, ALL_RIGHTS, VIRTUAL_ROOT_FD,
 };
 pub use crate::syscalls::types;
+pub use crate::timer::TimerWheel;
 #[cfg(feature = "wasix")]
 pub use crate::utils::is_wasix_module;
 pub use crate::utils::{get_wasi_version, get_wasi_versions, is_wasi_module, WasiVersion};
+pub use crate::working_set::{MemoryPressure, WorkingSet, WorkingSetCallback, WorkingSetMonitor};
 pub use wasmer_vbus::{UnsupportedVirtualBus, VirtualBus};
 #[deprecated(since = "2.1.0", note = "Please use `wasmer_vfs::FsError`")]
 pub use wasmer_vfs::FsError as WasiFsError;
@@ -57,21 +69,25 @@ pub use wasmer_vfs::FsError as WasiFsError;
 pub use wasmer_vfs::VirtualFile as WasiFile;
 pub use wasmer_vfs::{FsError, VirtualFile};
 pub use wasmer_vnet::{UnsupportedVirtualNetworking, VirtualNetworking};
+#[cfg(feature = "host-vnet")]
+pub use wasmer_wasi_local_networking::{LocalNetworking, NetProxy, NetProxyKind};
 
 use derivative::*;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use tracing::trace;
 use wasmer::{
-    imports, namespace, AsStoreMut, AsStoreRef, ExportError, Exports, Function, FunctionEnv,
-    Imports, Instance, Memory, Memory32, MemoryAccessError, MemorySize, MemoryView, Module,
-    TypedFunction,
+    imports, namespace, AsStoreMut, AsStoreRef, ExportError, Exports, Extern, Function,
+    FunctionEnv, FunctionEnvMut, Imports, Instance, Memory, Memory32, MemoryAccessError,
+    MemorySize, MemoryView, Module, RuntimeError, TypedFunction, Value,
 };
-use wasmer_wasi_types::wasi::{BusErrno, Errno, Snapshot0Clockid};
+use wasmer_wasi_types::wasi::{BusErrno, Errno, Fd as WasiFd};
 
 pub use runtime::{
     PluggableRuntimeImplementation, WasiRuntimeImplementation, WasiThreadError, WasiTtyState,
 };
+use std::collections::HashMap;
 use std::sync::{mpsc, Arc, Mutex, RwLockReadGuard, RwLockWriteGuard};
 use std::time::Duration;
 
@@ -226,6 +242,13 @@ impl WasiFunctionEnv {
     }
 }
 
+/// A handler replacing a syscall, see [`WasiEnv::override_syscall`].
+pub type SyscallHandler = Arc<
+    dyn Fn(FunctionEnvMut<'_, WasiEnv>, &[Value], &Function) -> Result<Vec<Value>, RuntimeError>
+        + Send
+        + Sync,
+>;
+
 /// The environment provided to the WASI imports.
 #[derive(Derivative, Clone)]
 #[derivative(Debug)]
@@ -251,6 +274,16 @@ pub struct WasiEnv {
     pub state: Arc<WasiState>,
     /// Implementation of the WASI runtime.
     pub(crate) runtime: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+    /// The last error of the host translated into an errno by this
+    /// thread.
+    last_host_error: Arc<Mutex<Option<HostError>>>,
+    /// Wakes the thread up when it sleeps.
+    timers: Arc<TimerWheel>,
+    /// Syscalls replaced by the embedder, by name.
+    #[derivative(Debug = "ignore")]
+    syscall_overrides: HashMap<String, SyscallHandler>,
+    /// Reports the working set to the embedder.
+    working_set_monitor: Option<WorkingSetMonitor>,
 }
 
 impl WasiEnv {
@@ -266,6 +299,10 @@ impl WasiEnv {
             malloc: None,
             free: None,
             runtime: Arc::new(PluggableRuntimeImplementation::default()),
+            last_host_error: Default::default(),
+            timers: TimerWheel::global(),
+            syscall_overrides: HashMap::new(),
+            working_set_monitor: None,
         }
     }
 
@@ -282,6 +319,63 @@ impl WasiEnv {
         self.runtime = Arc::new(runtime);
     }
 
+    /// Replaces the syscall `name` with `handler` in the imports
+    /// generated from now on, in every WASI version providing it.
+    ///
+    /// The handler receives the arguments of the syscall and its
+    /// default implementation, that it can call to delegate to it.
+    /// Its results must match the signature of the syscall.
+    pub fn override_syscall<F>(&mut self, name: &str, handler: F)
+    where
+        F: Fn(FunctionEnvMut<'_, WasiEnv>, &[Value], &Function) -> Result<Vec<Value>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.syscall_overrides
+            .insert(name.to_string(), Arc::new(handler));
+    }
+
+    /// Restores the default implementation of the syscall `name`,
+    /// returning whether it was overridden.
+    pub fn remove_syscall_override(&mut self, name: &str) -> bool {
+        self.syscall_overrides.remove(name).is_some()
+    }
+
+    /// Reports the working set of the instance to `monitor` after the
+    /// syscalls which may have changed it, in the imports generated
+    /// from now on.
+    ///
+    /// The growth of the linear memory is noticed at the next of
+    /// these syscalls, e.g. `poll_oneoff` or `sched_yield`, or
+    /// whenever the embedder calls [`WasiEnv::report_working_set`].
+    pub fn set_working_set_monitor(&mut self, monitor: WorkingSetMonitor) {
+        self.working_set_monitor = Some(monitor);
+    }
+
+    /// Gets the monitor the working set is reported to, if any.
+    pub fn working_set_monitor(&self) -> Option<&WorkingSetMonitor> {
+        self.working_set_monitor.as_ref()
+    }
+
+    /// Reports the working set of the instance to the monitor, if
+    /// any, returning it.
+    pub fn report_working_set(&self, store: &impl AsStoreRef) -> Option<WorkingSet> {
+        self.working_set_monitor
+            .as_ref()
+            .map(|monitor| monitor.report(store, self.memory.as_ref()))
+    }
+
+    /// Releases memory of the file system watched by the monitor, if
+    /// any, according to `pressure`, e.g. when the browser tab is short
+    /// of memory. Returns the number of bytes released.
+    pub fn relieve_memory_pressure(&self, pressure: MemoryPressure) -> Result<u64, FsError> {
+        match &self.working_set_monitor {
+            Some(monitor) => monitor.relieve(pressure),
+            None => Ok(0),
+        }
+    }
+
     /// Returns the current thread ID
     pub fn current_thread_id(&self) -> WasiThreadId {
         self.id
@@ -320,32 +414,63 @@ impl WasiEnv {
 
     // Sleeps for a period of time
     pub fn sleep(&self, duration: Duration) -> Result<(), WasiError> {
-        let duration = duration.as_nanos();
-        let start =
-            platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap() as u128;
+        let deadline = timer::now().saturating_add(duration.as_nanos() as u64);
         self.yield_now()?;
+        self.sleep_until(deadline, None)
+    }
+
+    /// Sleeps on the timer wheel until the monotonic host time
+    /// `deadline`, in nanoseconds, or until `operation` is cancelled.
+    pub(crate) fn sleep_until(
+        &self,
+        deadline: u64,
+        operation: Option<&PendingOperationGuard<'_>>,
+    ) -> Result<(), WasiError> {
+        let (waker, woken) = mpsc::channel();
+        if let Some(operation) = operation {
+            operation.set_waker(waker.clone());
+        }
+
         loop {
-            let now =
-                platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap() as u128;
-            let delta = match now.checked_sub(start) {
-                Some(a) => a,
-                None => {
-                    break;
-                }
-            };
-            if delta >= duration {
-                break;
+            let now = timer::now();
+            if now >= deadline || operation.map_or(false, |operation| operation.is_cancelled()) {
+                return Ok(());
             }
-            let remaining = match duration.checked_sub(delta) {
-                Some(a) => Duration::from_nanos(a as u64),
-                None => {
-                    break;
-                }
-            };
-            std::thread::sleep(remaining.min(Duration::from_millis(10)));
+
+            self.timers
+                .schedule(self.timers.wake_time(now, deadline), waker.clone());
+            let _ = woken.recv();
             self.yield_now()?;
         }
-        Ok(())
+    }
+
+    /// Uses `timers` to wake the threads up when they sleep, instead of
+    /// [`TimerWheel::global`].
+    pub fn set_timer_wheel(&mut self, timers: Arc<TimerWheel>) {
+        self.timers = timers;
+    }
+
+    /// Returns the system calls currently blocking the threads of
+    /// this instance, oldest first.
+    pub fn pending_operations(&self) -> Vec<PendingOperation> {
+        self.state.operations.lock().unwrap().list()
+    }
+
+    /// Cancels a pending operation, whose system call returns `EINTR`
+    /// to the guest. Returns `false` if the operation is no longer
+    /// pending.
+    pub fn cancel(&self, id: PendingOperationId) -> bool {
+        self.state.operations.lock().unwrap().cancel(id)
+    }
+
+    /// Registers a blocking system call of the current thread, until
+    /// the returned guard is dropped.
+    pub(crate) fn begin_operation(
+        &self,
+        fd: Option<WasiFd>,
+        kind: PendingOperationKind,
+    ) -> PendingOperationGuard<'_> {
+        PendingOperations::begin(&self.state.operations, self.id, fd, kind)
     }
 
     /// Accesses the virtual networking implementation
@@ -382,6 +507,68 @@ impl WasiEnv {
         &self.state
     }
 
+    /// Translates a path seen by the guest into the path of the host,
+    /// or of the backing file system, following the pre-opened and
+    /// mapped directories. See [`WasiFs::host_path_of`].
+    pub fn host_path_of(&self, guest_path: impl AsRef<Path>) -> Option<PathBuf> {
+        let inodes = self.state.inodes.read().unwrap();
+        self.state.fs.host_path_of(&inodes, guest_path.as_ref())
+    }
+
+    /// Translates a path of the host, or of the backing file system,
+    /// into the path seen by the guest, following the pre-opened and
+    /// mapped directories. See [`WasiFs::guest_path_of`].
+    pub fn guest_path_of(&self, host_path: impl AsRef<Path>) -> Option<PathBuf> {
+        let inodes = self.state.inodes.read().unwrap();
+        self.state.fs.guest_path_of(&inodes, host_path.as_ref())
+    }
+
+    /// Registers `resource` in the file descriptor table of the
+    /// guest, returning its file descriptor. See
+    /// [`WasiFs::register_resource`].
+    pub fn register_resource(
+        &self,
+        name: impl Into<String>,
+        resource: impl WasiResource,
+    ) -> Result<WasiFd, Errno> {
+        let mut inodes = self.state.inodes.write().unwrap();
+        self.state.fs.register_resource(&mut inodes, name, resource)
+    }
+
+    /// Sends the `request`, with its `data`, to the resource
+    /// registered as `fd`. See [`WasiResource::control`].
+    pub fn control_resource(
+        &self,
+        fd: WasiFd,
+        request: u32,
+        data: &[u8],
+    ) -> Result<Vec<u8>, Errno> {
+        let inodes = self.state.inodes.read().unwrap();
+        self.state.fs.control_resource(&inodes, fd, request, data)
+    }
+
+    /// Returns the last error of the host translated into an errno by
+    /// the syscalls of this thread, with the original error, which the
+    /// errno may not fully describe.
+    pub fn last_host_error(&self) -> Option<HostError> {
+        self.last_host_error.lock().unwrap().clone()
+    }
+
+    /// Translates an I/O error of the host into an errno, remembering
+    /// it as the last error of the host.
+    pub(crate) fn host_io_error(&self, error: std::io::Error) -> Errno {
+        let error = HostError::new(error);
+        let errno = error.errno();
+        self.record_host_error(error);
+        errno
+    }
+
+    /// Remembers `error` as the last error of the host.
+    pub(crate) fn record_host_error(&self, error: HostError) {
+        trace!("wasi::host_error: {}", error);
+        *self.last_host_error.lock().unwrap() = Some(error);
+    }
+
     pub(crate) fn get_memory_and_wasi_state<'a>(
         &'a self,
         store: &'a impl AsStoreRef,
@@ -421,7 +608,7 @@ pub fn generate_import_object_from_env(
     env: &FunctionEnv<WasiEnv>,
     version: WasiVersion,
 ) -> Imports {
-    match version {
+    let mut imports = match version {
         WasiVersion::Snapshot0 => generate_import_object_snapshot0(store, env),
         WasiVersion::Snapshot1 | WasiVersion::Latest => {
             generate_import_object_snapshot1(store, env)
@@ -432,6 +619,69 @@ pub fn generate_import_object_from_env(
         WasiVersion::Wasix64v1 => generate_import_object_wasix64_v1(store, env),
         #[cfg(not(feature = "wasix"))]
         _ => unimplemented!(),
+    };
+    apply_syscall_overrides(store, env, &mut imports);
+    apply_working_set_reporting(store, env, &mut imports);
+    imports
+}
+
+/// The namespaces of the syscalls, in every WASI version.
+const SYSCALL_NAMESPACES: [&str; 4] = [
+    "wasi_unstable",
+    "wasi_snapshot_preview1",
+    "wasix_32v1",
+    "wasix_64v1",
+];
+
+/// Replaces the syscalls overridden in the environment, see
+/// [`WasiEnv::override_syscall`].
+fn apply_syscall_overrides(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    imports: &mut Imports,
+) {
+    let overrides = env.as_ref(store).syscall_overrides.clone();
+    for (name, handler) in overrides {
+        for namespace in SYSCALL_NAMESPACES {
+            let default = match imports.get_export(namespace, &name) {
+                Some(Extern::Function(default)) => default,
+                _ => continue,
+            };
+            let ty = default.ty(store);
+            let handler = handler.clone();
+            let function = Function::new_with_env(store, env, ty, move |ctx, args| {
+                handler(ctx, args, &default)
+            });
+            imports.define(namespace, &name, function);
+        }
+    }
+}
+
+/// Reports the working set after the syscalls which may have changed
+/// it, see [`WasiEnv::set_working_set_monitor`].
+fn apply_working_set_reporting(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    imports: &mut Imports,
+) {
+    if env.as_ref(store).working_set_monitor.is_none() {
+        return;
+    }
+
+    for namespace in SYSCALL_NAMESPACES {
+        for name in working_set::REPORTING_SYSCALLS {
+            let syscall = match imports.get_export(namespace, name) {
+                Some(Extern::Function(syscall)) => syscall,
+                _ => continue,
+            };
+            let ty = syscall.ty(store);
+            let function = Function::new_with_env(store, env, ty, move |mut ctx, args| {
+                let results = syscall.call(&mut ctx, args)?;
+                ctx.data().report_working_set(&ctx);
+                Ok(results.into_vec())
+            });
+            imports.define(namespace, name, function);
+        }
     }
 }
 
@@ -545,10 +795,13 @@ pub fn import_object_for_all_wasi_versions(
 ) -> Imports {
     let wasi_unstable_exports = wasi_unstable_exports(store, env);
     let wasi_snapshot_preview1_exports = wasi_snapshot_preview1_exports(store, env);
-    imports! {
+    let mut imports = imports! {
         "wasi_unstable" => wasi_unstable_exports,
         "wasi_snapshot_preview1" => wasi_snapshot_preview1_exports,
-    }
+    };
+    apply_syscall_overrides(store, env, &mut imports);
+    apply_working_set_reporting(store, env, &mut imports);
+    imports
 }
 
 /// Combines a state generating function with the import list for legacy WASI
@@ -605,6 +858,7 @@ fn generate_import_object_wasix32_v1(
             "fd_readdir" => Function::new_typed_with_env(&mut store, env, fd_readdir),
             "fd_renumber" => Function::new_typed_with_env(&mut store, env, fd_renumber),
             "fd_dup" => Function::new_typed_with_env(&mut store, env, fd_dup),
+            "fd_ioctl" => Function::new_typed_with_env(&mut store, env, fd_ioctl),
             "fd_event" => Function::new_typed_with_env(&mut store, env, fd_event),
             "fd_seek" => Function::new_typed_with_env(&mut store, env, fd_seek),
             "fd_sync" => Function::new_typed_with_env(&mut store, env, fd_sync),
@@ -724,6 +978,7 @@ fn generate_import_object_wasix64_v1(
             "fd_readdir" => Function::new_typed_with_env(&mut store, env, fd_readdir),
             "fd_renumber" => Function::new_typed_with_env(&mut store, env, fd_renumber),
             "fd_dup" => Function::new_typed_with_env(&mut store, env, fd_dup),
+            "fd_ioctl" => Function::new_typed_with_env(&mut store, env, fd_ioctl),
             "fd_event" => Function::new_typed_with_env(&mut store, env, fd_event),
             "fd_seek" => Function::new_typed_with_env(&mut store, env, fd_seek),
             "fd_sync" => Function::new_typed_with_env(&mut store, env, fd_sync),
@@ -1,17 +1,26 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
-use crate::state::{default_fs_backing, WasiFs, WasiState};
+use crate::state::{
+    default_fs_backing, etc, join_env, validate_env, AnsiEscapes, FilteredOutput, FsAudit,
+    FsAuditHook, PathErrorFn, PathErrorHook, ProcFileSystem, ProcInfo, StringList, Tee, TimeZone,
+    VirtualClock, WasiFs, WasiState, ETC_PATH, PROC_PATH, ZONEINFO_PATH,
+};
 use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
 use crate::{WasiEnv, WasiFunctionEnv, WasiInodes};
 use generational_arena::Arena;
 use std::collections::HashMap;
+use std::io::Write;
+use std::net::IpAddr;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
 use thiserror::Error;
 use wasmer::AsStoreMut;
-use wasmer_vfs::{FsError, VirtualFile};
+use wasmer_vfs::{audit_fs, dev_fs, mount_fs, sandbox_fs, FsError, PathResolver, VirtualFile};
+
+/// Where the devices of [`WasiStateBuilder::dev_fs`] are mounted.
+const DEV_PATH: &str = "/dev";
 
 /// Creates an empty [`WasiStateBuilder`].
 ///
@@ -51,7 +60,21 @@ pub struct WasiStateBuilder {
     stderr_override: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     stdin_override: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     fs_override: Option<Box<dyn wasmer_vfs::FileSystem>>,
+    mounts: Vec<(PathBuf, Box<dyn wasmer_vfs::FileSystem>, bool)>,
     runtime_override: Option<Arc<dyn crate::WasiRuntimeImplementation + Send + Sync + 'static>>,
+    virtual_clock: Option<(i64, f64)>,
+    path_error_fn: Option<PathErrorFn>,
+    fs_audit_hook: Option<FsAuditHook>,
+    audit_fs: Option<audit_fs::AuditFn>,
+    proc_fs: bool,
+    dev_fs: bool,
+    etc_fs: bool,
+    nameservers: Vec<IpAddr>,
+    timezone: Option<String>,
+    locale: Option<String>,
+    output_ansi: AnsiEscapes,
+    force_color: Option<bool>,
+    tee: Option<Tee>,
 }
 
 impl std::fmt::Debug for WasiStateBuilder {
@@ -61,11 +84,32 @@ impl std::fmt::Debug for WasiStateBuilder {
             .field("args", &self.args)
             .field("envs", &self.envs)
             .field("preopens", &self.preopens)
+            .field(
+                "mounts",
+                &self
+                    .mounts
+                    .iter()
+                    .map(|(path, _, writable)| (path, writable))
+                    .collect::<Vec<_>>(),
+            )
             .field("setup_fs_fn exists", &self.setup_fs_fn.is_some())
+            .field("path_error_fn exists", &self.path_error_fn.is_some())
+            .field("fs_audit_hook exists", &self.fs_audit_hook.is_some())
+            .field("audit_fs exists", &self.audit_fs.is_some())
             .field("stdout_override exists", &self.stdout_override.is_some())
             .field("stderr_override exists", &self.stderr_override.is_some())
             .field("stdin_override exists", &self.stdin_override.is_some())
             .field("runtime_override_exists", &self.runtime_override.is_some())
+            .field("virtual_clock", &self.virtual_clock)
+            .field("proc_fs", &self.proc_fs)
+            .field("dev_fs", &self.dev_fs)
+            .field("etc_fs", &self.etc_fs)
+            .field("nameservers", &self.nameservers)
+            .field("timezone", &self.timezone)
+            .field("locale", &self.locale)
+            .field("output_ansi", &self.output_ansi)
+            .field("force_color", &self.force_color)
+            .field("tee exists", &self.tee.is_some())
             .finish()
     }
 }
@@ -89,6 +133,34 @@ pub enum WasiStateCreationError {
     WasiFsSetupError(String),
     #[error(transparent)]
     FileSystemError(FsError),
+    #[error("virtual clock error: `{0}`")]
+    VirtualClockError(String),
+    #[error("time zone error: `{0}`")]
+    TimeZoneError(String),
+}
+
+/// Returns where the sandbox of the pre-opened directory `path` is
+/// mounted: its normalized absolute path, a relative `path` being
+/// relative to the current directory of the host if `host_fs`, or to
+/// the root otherwise.
+///
+/// Returns `None` for a path the vfs can't resolve, like a Windows
+/// path with a drive prefix; the directory is then served as is.
+fn sandbox_root(path: &Path, host_fs: bool) -> Result<Option<PathBuf>, WasiStateCreationError> {
+    let path = if path.is_relative() && host_fs {
+        std::env::current_dir()
+            .map_err(|err| {
+                WasiStateCreationError::PreopenedDirectoryError(format!(
+                    "failed to get the current directory: {}",
+                    err
+                ))
+            })?
+            .join(path)
+    } else {
+        Path::new("/").join(path)
+    };
+
+    Ok(PathResolver::new().normalize(&path).ok())
 }
 
 fn validate_mapped_dir_alias(alias: &str) -> Result<(), WasiStateCreationError> {
@@ -171,6 +243,10 @@ impl WasiStateBuilder {
     ///
     /// This opens the given directory at the virtual root, `/`, and allows
     /// the WASI module to read and write to the given directory.
+    ///
+    /// The module is confined to the directory: neither `..` nor a
+    /// symlink lead out of it, an absolute symlink starting from the
+    /// directory itself.
     pub fn preopen_dir<FilePath>(
         &mut self,
         po_dir: FilePath,
@@ -326,6 +402,48 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Sets a function called each time a path-based syscall, like
+    /// `path_open`, fails.
+    ///
+    /// The [`PathError`](super::PathError) tells which directory the guest path was
+    /// relative to, so that the host can explain why it couldn't be
+    /// resolved, e.g. because it isn't inside a pre-opened directory.
+    pub fn on_path_error(&mut self, path_error_fn: PathErrorFn) -> &mut Self {
+        self.path_error_fn = Some(path_error_fn);
+
+        self
+    }
+
+    /// Sets a function called after each path-based syscall, like
+    /// `path_open` or `path_rename`, whether it succeeded or not.
+    ///
+    /// The [`FsAccess`](super::FsAccess) tells which path the guest
+    /// accessed, and where it lies in the file system backing, so that
+    /// the host can log what a program touches. Syscalls taking two
+    /// paths, like `path_rename`, call it once for each of them.
+    ///
+    /// The path is only resolved when a function is set, which is
+    /// otherwise free.
+    pub fn fs_audit_hook(&mut self, fs_audit_hook: FsAuditHook) -> &mut Self {
+        self.fs_audit_hook = Some(fs_audit_hook);
+
+        self
+    }
+
+    /// Sets a function called after each operation of the file
+    /// system, including the reads and writes of the opened files.
+    ///
+    /// Unlike [`Self::fs_audit_hook`], which sees the syscalls of the
+    /// guest, the [`Record`](wasmer_vfs::audit_fs::Record) tells what
+    /// the file system did: the normalized paths, the number of bytes
+    /// and the result, e.g. to keep a complete log of what an
+    /// untrusted program touched.
+    pub fn audit_fs(&mut self, audit: audit_fs::AuditFn) -> &mut Self {
+        self.audit_fs = Some(audit);
+
+        self
+    }
+
     /// Sets the WASI runtime implementation and overrides the default
     /// implementation
     pub fn runtime<R>(&mut self, runtime: R) -> &mut Self
@@ -336,6 +454,147 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Virtualize the clocks observed by the WASI program.
+    ///
+    /// The guest wall-clock and monotonic clocks are shifted by `offset`
+    /// nanoseconds (which may be negative), and all the guest clocks run
+    /// `scale` times faster than the host ones: with a scale of `10.0`,
+    /// a `sleep(10s)` returns after one second. Timeouts given to
+    /// `poll_oneoff` and `thread_sleep` are scaled accordingly.
+    ///
+    /// `scale` must be strictly positive.
+    pub fn virtual_clock(&mut self, offset: i64, scale: f64) -> &mut Self {
+        self.virtual_clock = Some((offset, scale));
+
+        self
+    }
+
+    /// Mount a read-only `/proc` file system, whose `self` directory
+    /// describes the running instance: its `status` (name, pid,
+    /// number of threads, memory size and number of open file
+    /// descriptors), `cmdline`, `environ`, `threads`, `fds`,
+    /// `preopens`, memory usage in `statm`, and `limits`.
+    ///
+    /// The files are generated when they are opened.
+    pub fn proc_fs(&mut self, enabled: bool) -> &mut Self {
+        self.proc_fs = enabled;
+
+        self
+    }
+
+    /// Mount `fs` at `path` of the virtual file system, and pre-open
+    /// it; the module can only write to it if `writable`.
+    ///
+    /// The mounted file system hides what is at `path`, e.g. a host
+    /// directory.
+    pub fn mount<FilePath>(
+        &mut self,
+        path: FilePath,
+        fs: Box<dyn wasmer_vfs::FileSystem>,
+        writable: bool,
+    ) -> &mut Self
+    where
+        FilePath: AsRef<Path>,
+    {
+        self.mounts
+            .push((path.as_ref().to_path_buf(), fs, writable));
+
+        self
+    }
+
+    /// Mount a `/dev` file system holding the `null`, `zero`,
+    /// `random` and `urandom` devices, and a `tty` which can't be
+    /// opened.
+    ///
+    /// The devices hide the `/dev` of the file system.
+    pub fn dev_fs(&mut self, enabled: bool) -> &mut Self {
+        self.dev_fs = enabled;
+
+        self
+    }
+
+    /// Mount a read-only `/etc` file system holding a minimal
+    /// `passwd` and `group`, a `resolv.conf` listing the name servers
+    /// given with [`Self::nameserver`], or the host ones, and the
+    /// `localtime` of the host time zone.
+    ///
+    /// The files are generated when the state is built, and hide the
+    /// `/etc` of the file system.
+    pub fn etc_fs(&mut self, enabled: bool) -> &mut Self {
+        self.etc_fs = enabled;
+
+        self
+    }
+
+    /// Add a name server to the `resolv.conf` of [`Self::etc_fs`].
+    pub fn nameserver(&mut self, address: IpAddr) -> &mut Self {
+        self.nameservers.push(address);
+
+        self
+    }
+
+    /// Run the program in the time zone `timezone` of the tz
+    /// database, like `Europe/Paris`, through the `TZ` environment
+    /// variable. The TZif file of the zone is read from the host, and
+    /// mounted with the one of `UTC` in a read-only
+    /// `/usr/share/zoneinfo`; it is also the `localtime` of
+    /// [`Self::etc_fs`].
+    ///
+    /// A `TZ` set explicitly with [`Self::env`] takes precedence.
+    pub fn timezone<Tz>(&mut self, timezone: Tz) -> &mut Self
+    where
+        Tz: Into<String>,
+    {
+        self.timezone = Some(timezone.into());
+
+        self
+    }
+
+    /// Run the program in the locale `locale`, like `C.UTF-8`, through
+    /// the `LANG` environment variable.
+    ///
+    /// A `LANG` set explicitly with [`Self::env`] takes precedence.
+    pub fn locale<Locale>(&mut self, locale: Locale) -> &mut Self
+    where
+        Locale: Into<String>,
+    {
+        self.locale = Some(locale.into());
+
+        self
+    }
+
+    /// Preserve or strip the ANSI escape sequences, like the colors,
+    /// written by the program to `stdout` and `stderr`, including
+    /// the overridden ones.
+    pub fn output_ansi(&mut self, ansi: AnsiEscapes) -> &mut Self {
+        self.output_ansi = ansi;
+
+        self
+    }
+
+    /// Ask the program to always, or never, use colors, through the
+    /// conventional environment variables: `CLICOLOR_FORCE=1` to
+    /// force them, `NO_COLOR=1` and `CLICOLOR=0` to disable them.
+    ///
+    /// The variables set explicitly with [`Self::env`] take
+    /// precedence.
+    pub fn force_color(&mut self, color: bool) -> &mut Self {
+        self.force_color = Some(color);
+
+        self
+    }
+
+    /// Copy what the program writes to `stdout` and `stderr` to
+    /// `tee`, e.g. a log file, while still writing it to the outputs.
+    ///
+    /// The copy is filtered like the outputs, see
+    /// [`Self::output_ansi`].
+    pub fn tee_output(&mut self, tee: Box<dyn Write + Send>) -> &mut Self {
+        self.tee = Some(Tee::new(tee));
+
+        self
+    }
+
     /// Consumes the [`WasiStateBuilder`] and produces a [`WasiState`]
     ///
     /// Returns the error from `WasiFs::new` if there's an error
@@ -372,53 +631,145 @@ impl WasiStateBuilder {
             }
         }
 
-        enum InvalidCharacter {
-            Nul,
-            Equal,
+        for (env_key, env_value) in self.envs.iter() {
+            validate_env(env_key, env_value)?;
         }
 
-        for (env_key, env_value) in self.envs.iter() {
-            match env_key.iter().find_map(|&ch| {
-                if ch == 0 {
-                    Some(InvalidCharacter::Nul)
-                } else if ch == b'=' {
-                    Some(InvalidCharacter::Equal)
-                } else {
-                    None
-                }
-            }) {
-                Some(InvalidCharacter::Nul) => {
-                    return Err(WasiStateCreationError::EnvironmentVariableFormatError(
-                        format!(
-                            "found nul byte in env var key \"{}\" (key=value)",
-                            String::from_utf8_lossy(env_key)
-                        ),
-                    ))
-                }
+        let clock = match self.virtual_clock {
+            Some((offset, scale)) => Some(VirtualClock::new(offset, scale).map_err(|_| {
+                WasiStateCreationError::VirtualClockError(format!(
+                    "the scale must be strictly positive, got {}",
+                    scale
+                ))
+            })?),
+            None => None,
+        };
 
-                Some(InvalidCharacter::Equal) => {
-                    return Err(WasiStateCreationError::EnvironmentVariableFormatError(
-                        format!(
-                            "found equal sign in env var key \"{}\" (key=value)",
-                            String::from_utf8_lossy(env_key)
-                        ),
-                    ))
-                }
+        let timezone = match self.timezone.as_deref() {
+            Some(timezone) => {
+                Some(TimeZone::load(timezone).map_err(WasiStateCreationError::TimeZoneError)?)
+            }
+            None => None,
+        };
 
-                None => (),
+        let mut default_envs = match self.force_color {
+            Some(true) => vec![("CLICOLOR_FORCE", "1")],
+            Some(false) => vec![("NO_COLOR", "1"), ("CLICOLOR", "0")],
+            None => vec![],
+        };
+        if let Some(timezone) = timezone.as_ref() {
+            default_envs.push(("TZ", timezone.name()));
+        }
+        if let Some(locale) = self.locale.as_deref() {
+            default_envs.push(("LANG", locale));
+        }
+        let default_envs = default_envs
+            .iter()
+            .filter(|(key, _)| !self.envs.iter().any(|(k, _)| k == key.as_bytes()))
+            .map(|(key, value)| (key.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect::<Vec<_>>();
+
+        let host_fs = self.fs_override.is_none() && cfg!(feature = "host-fs");
+        let mut fs_backing = self.fs_override.take().unwrap_or_else(default_fs_backing);
+
+        // The mounted file systems, and whether they are writable.
+        let mut mounts: Vec<(PathBuf, Box<dyn wasmer_vfs::FileSystem>, bool)> = Vec::new();
+        if self.dev_fs {
+            mounts.push((
+                DEV_PATH.into(),
+                Box::new(dev_fs::FileSystem::default()),
+                true,
+            ));
+        }
+        if self.etc_fs {
+            let localtime = timezone.as_ref().map(TimeZone::tzif);
+            let etc = etc::file_system(&self.nameservers, localtime);
+            mounts.push((ETC_PATH.into(), Box::new(etc), false));
+        }
+        if let Some(timezone) = timezone.as_ref() {
+            mounts.push((
+                ZONEINFO_PATH.into(),
+                Box::new(timezone.file_system()),
+                false,
+            ));
+        }
+        let proc = if self.proc_fs {
+            let proc = Arc::new(ProcInfo::default());
+            mounts.push((
+                PROC_PATH.into(),
+                Box::new(ProcFileSystem::new(&proc)),
+                false,
+            ));
+            Some(proc)
+        } else {
+            None
+        };
+        for (path, fs, writable) in self.mounts.drain(..) {
+            let path = PathResolver::new()
+                .normalize(&Path::new("/").join(&path))
+                .map_err(|_| {
+                    WasiStateCreationError::PreopenedDirectoryError(format!(
+                        "invalid mount point `{}`",
+                        path.display()
+                    ))
+                })?;
+            mounts.push((path, fs, writable));
+        }
+        let sandbox_roots = self
+            .preopens
+            .iter()
+            .map(|preopen| sandbox_root(&preopen.path, host_fs))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut preopens = self.preopens.clone();
+        if !mounts.is_empty() || sandbox_roots.iter().any(Option::is_some) {
+            let shared_fs_backing: Arc<dyn wasmer_vfs::FileSystem> = Arc::from(fs_backing);
+            let fs = mount_fs::FileSystem::new_shared(shared_fs_backing.clone());
+            let mut mount_points = Vec::with_capacity(mounts.len());
+            for (path, mounted, writable) in mounts {
+                fs.mount(&path, mounted)
+                    .map_err(WasiStateCreationError::FileSystemError)?;
+                mount_points.push((path, writable));
             }
 
-            if env_value.iter().any(|&ch| ch == 0) {
-                return Err(WasiStateCreationError::EnvironmentVariableFormatError(
-                    format!(
-                        "found nul byte in env var value \"{}\" (key=value)",
-                        String::from_utf8_lossy(env_value)
-                    ),
-                ));
+            // Each pre-opened directory is served by a sandbox confined
+            // to it, mounted over it unless something already is.
+            for (preopen, root) in preopens.iter_mut().zip(sandbox_roots) {
+                let root = match root {
+                    Some(root) => root,
+                    None => continue,
+                };
+                if root != Path::new("/") {
+                    let sandbox = sandbox_fs::FileSystem::new(shared_fs_backing.clone(), &root)
+                        .map_err(WasiStateCreationError::FileSystemError)?;
+                    match fs.mount(&root, Box::new(sandbox)) {
+                        Ok(()) | Err(FsError::AlreadyExists) => {}
+                        Err(err) => return Err(WasiStateCreationError::FileSystemError(err)),
+                    }
+                }
+                if preopen.alias.is_none() {
+                    preopen.alias = Some(preopen.path.to_string_lossy().into_owned());
+                }
+                preopen.path = root;
             }
+
+            preopens.extend(
+                mount_points
+                    .into_iter()
+                    .map(|(path, writable)| PreopenedDir {
+                        alias: Some(path.to_string_lossy().trim_start_matches('/').to_string()),
+                        path,
+                        read: true,
+                        write: writable,
+                        create: false,
+                    }),
+            );
+            fs_backing = Box::new(fs);
         }
 
-        let fs_backing = self.fs_override.take().unwrap_or_else(default_fs_backing);
+        if let Some(audit) = self.audit_fs.clone() {
+            fs_backing = Box::new(audit_fs::FileSystem::new(Arc::from(fs_backing), audit));
+        }
 
         // self.preopens are checked in [`PreopenDirBuilder::build`]
         let inodes = RwLock::new(crate::state::WasiInodes {
@@ -431,7 +782,7 @@ impl WasiStateBuilder {
             // self.preopens are checked in [`PreopenDirBuilder::build`]
             let mut wasi_fs = WasiFs::new_with_preopen(
                 inodes.deref_mut(),
-                &self.preopens,
+                &preopens,
                 &self.vfs_preopens,
                 fs_backing,
             )
@@ -456,30 +807,46 @@ impl WasiStateBuilder {
                     .map_err(WasiStateCreationError::FileSystemError)?;
             }
 
+            if self.output_ansi != AnsiEscapes::Preserve || self.tee.is_some() {
+                for fd in [__WASI_STDOUT_FILENO, __WASI_STDERR_FILENO] {
+                    let mut file = inodes
+                        .std_dev_get_mut(&wasi_fs.fd_map, fd)
+                        .map_err(WasiStateCreationError::FileSystemError)?;
+                    if let Some(output) = file.take() {
+                        *file = Some(Box::new(FilteredOutput::new(
+                            output,
+                            self.output_ansi,
+                            self.tee.clone(),
+                        )));
+                    }
+                }
+            }
+
             if let Some(f) = &self.setup_fs_fn {
                 f(inodes.deref_mut(), &mut wasi_fs)
                     .map_err(WasiStateCreationError::WasiFsSetupError)?;
             }
+
+            wasi_fs.path_error_hook = self.path_error_fn.clone().map(PathErrorHook);
+            wasi_fs.fs_audit = self.fs_audit_hook.clone().map(FsAudit);
+            wasi_fs.proc = proc;
             wasi_fs
         };
 
         Ok(WasiState {
             fs: wasi_fs,
             inodes: Arc::new(inodes),
-            args: self.args.clone(),
+            args: Mutex::new(StringList::new(self.args.clone())),
             threading: Default::default(),
-            envs: self
-                .envs
-                .iter()
-                .map(|(key, value)| {
-                    let mut env = Vec::with_capacity(key.len() + value.len() + 1);
-                    env.extend_from_slice(key);
-                    env.push(b'=');
-                    env.extend_from_slice(value);
-
-                    env
-                })
-                .collect(),
+            clock,
+            operations: Default::default(),
+            envs: Mutex::new(StringList::new(
+                self.envs
+                    .iter()
+                    .chain(default_envs.iter())
+                    .map(|(key, value)| join_env(key, value))
+                    .collect(),
+            )),
         })
     }
 
@@ -518,7 +885,7 @@ pub struct PreopenDirBuilder {
 }
 
 /// The built version of `PreopenDirBuilder`
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub(crate) struct PreopenedDir {
     pub(crate) path: PathBuf,
     pub(crate) alias: Option<String>,
@@ -656,6 +1023,114 @@ mod test {
         );
     }
 
+    #[test]
+    fn virtual_clock_scale() {
+        assert!(create_wasi_state("test_prog")
+            .virtual_clock(-1_000_000_000, 10.0)
+            .build()
+            .is_ok());
+        assert!(matches!(
+            create_wasi_state("test_prog")
+                .virtual_clock(0, -1.0)
+                .build(),
+            Err(WasiStateCreationError::VirtualClockError(_))
+        ));
+    }
+
+    #[test]
+    fn force_color() {
+        let state = create_wasi_state("test_prog")
+            .force_color(true)
+            .build()
+            .unwrap();
+        assert_eq!(state.envs(), vec![b"CLICOLOR_FORCE=1".to_vec()]);
+
+        // The explicit variables take precedence.
+        let state = create_wasi_state("test_prog")
+            .env("CLICOLOR", "1")
+            .force_color(false)
+            .build()
+            .unwrap();
+        assert_eq!(
+            state.envs(),
+            vec![b"CLICOLOR=1".to_vec(), b"NO_COLOR=1".to_vec()]
+        );
+    }
+
+    #[test]
+    fn timezone_and_locale() {
+        let state = create_wasi_state("test_prog")
+            .timezone("UTC")
+            .locale("C.UTF-8")
+            .build()
+            .unwrap();
+        assert_eq!(
+            state.envs(),
+            vec![b"TZ=UTC".to_vec(), b"LANG=C.UTF-8".to_vec()]
+        );
+
+        // The explicit variables take precedence.
+        let state = create_wasi_state("test_prog")
+            .env("TZ", "CET-1")
+            .timezone("UTC")
+            .build()
+            .unwrap();
+        assert_eq!(state.envs(), vec![b"TZ=CET-1".to_vec()]);
+
+        assert!(matches!(
+            create_wasi_state("test_prog").timezone("../passwd").build(),
+            Err(WasiStateCreationError::TimeZoneError(_))
+        ));
+    }
+
+    #[test]
+    fn update_envs() {
+        let state = create_wasi_state("test_prog")
+            .env("A", "1")
+            .env("AB", "2")
+            .build()
+            .unwrap();
+
+        state.set_env("A", "3").unwrap();
+        state.set_env("C", "").unwrap();
+        assert!(matches!(
+            state.set_env("D=", "4"),
+            Err(WasiStateCreationError::EnvironmentVariableFormatError(_))
+        ));
+        assert_eq!(
+            state.envs(),
+            vec![b"A=3".to_vec(), b"AB=2".to_vec(), b"C=".to_vec()]
+        );
+
+        assert!(state.remove_env("AB"));
+        assert!(!state.remove_env("AB"));
+        assert_eq!(state.envs(), vec![b"A=3".to_vec(), b"C=".to_vec()]);
+
+        state.set_args(&["other_prog", "--flag"]).unwrap();
+        assert_eq!(
+            state.args(),
+            vec![b"other_prog".to_vec(), b"--flag".to_vec()]
+        );
+    }
+
+    #[test]
+    fn mounted_file_system() {
+        use wasmer_vfs::FileSystem;
+
+        let assets = wasmer_vfs::mem_fs::FileSystem::default();
+        assets.create_dir(Path::new("/images")).unwrap();
+        let state = create_wasi_state("test_prog")
+            .mount("assets", Box::new(assets), false)
+            .build()
+            .unwrap();
+        assert!(state
+            .fs
+            .fs_backing
+            .metadata(Path::new("/assets/images"))
+            .unwrap()
+            .is_dir());
+    }
+
     #[test]
     fn nul_character_in_args() {
         let output = create_wasi_state("test_prog").arg("--h\0elp").build();
@@ -0,0 +1,103 @@
+use crate::syscalls::platform_clock_time_get;
+use crate::syscalls::types::wasi::{Errno, Snapshot0Clockid};
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A clock observed by the guest, shifted and scaled relatively to the
+/// host clocks.
+///
+/// The wall-clock and monotonic clocks start at the host time (plus the
+/// offset) when the clock is created, then advance `scale` times faster
+/// than the host ones. CPU-time clocks are only scaled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct VirtualClock {
+    /// Offset added to the wall-clock and monotonic clocks, in nanoseconds.
+    offset: i64,
+    /// How many nanoseconds pass for the guest during a host nanosecond.
+    scale: f64,
+    /// The host wall-clock time when the clock was created.
+    realtime_origin: i64,
+    /// The host monotonic time when the clock was created.
+    monotonic_origin: i64,
+}
+
+impl VirtualClock {
+    /// Creates a virtual clock starting now.
+    ///
+    /// `offset` is in nanoseconds and may be negative, `scale` must be
+    /// strictly positive.
+    pub fn new(offset: i64, scale: f64) -> Result<Self, Errno> {
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(Errno::Inval);
+        }
+        Ok(Self {
+            offset,
+            scale,
+            realtime_origin: platform_clock_time_get(Snapshot0Clockid::Realtime, 1)?,
+            monotonic_origin: platform_clock_time_get(Snapshot0Clockid::Monotonic, 1)?,
+        })
+    }
+
+    /// Converts the host time of `clock_id` to the guest time.
+    pub fn guest_time(&self, clock_id: Snapshot0Clockid, host_time: i64) -> i64 {
+        let origin = match clock_id {
+            Snapshot0Clockid::Realtime => self.realtime_origin,
+            Snapshot0Clockid::Monotonic => self.monotonic_origin,
+            _ => return self.scale_nanos(host_time),
+        };
+        origin
+            .wrapping_add(self.scale_nanos(host_time.wrapping_sub(origin)))
+            .wrapping_add(self.offset)
+    }
+
+    /// Converts the resolution of a host clock to the guest one.
+    pub fn guest_resolution(&self, host_resolution: i64) -> i64 {
+        self.scale_nanos(host_resolution).max(1)
+    }
+
+    /// Converts a duration observed by the guest, e.g. a timeout, to the
+    /// host duration.
+    pub fn host_duration(&self, guest_duration: Duration) -> Duration {
+        Duration::from_secs_f64(guest_duration.as_secs_f64() / self.scale)
+    }
+
+    fn scale_nanos(&self, nanos: i64) -> i64 {
+        if self.scale == 1.0 {
+            nanos
+        } else {
+            (nanos as f64 * self.scale) as i64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_clock() {
+        let clock = VirtualClock {
+            offset: 5,
+            scale: 10.0,
+            realtime_origin: 1_000,
+            monotonic_origin: 100,
+        };
+        assert_eq!(clock.guest_time(Snapshot0Clockid::Realtime, 1_000), 1_005);
+        assert_eq!(clock.guest_time(Snapshot0Clockid::Realtime, 1_010), 1_105);
+        assert_eq!(clock.guest_time(Snapshot0Clockid::Monotonic, 150), 605);
+        assert_eq!(
+            clock.guest_time(Snapshot0Clockid::ProcessCputimeId, 42),
+            420
+        );
+        assert_eq!(clock.guest_resolution(1), 10);
+        assert_eq!(
+            clock.host_duration(Duration::from_secs(10)),
+            Duration::from_secs(1)
+        );
+
+        assert_eq!(VirtualClock::new(0, 0.0), Err(Errno::Inval));
+        assert_eq!(VirtualClock::new(0, f64::NAN), Err(Errno::Inval));
+    }
+}
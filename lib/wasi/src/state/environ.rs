@@ -0,0 +1,157 @@
+//! The arguments and the environment variables of the guest, and
+//! their encoding in the guest memory.
+
+use super::WasiStateCreationError;
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+
+/// A list of strings, like the arguments or the environment
+/// variables, with its encoding in the guest memory.
+///
+/// The encoding is built by the first query, and kept until the list
+/// is updated, so that the sizes returned to the guest always match
+/// the data written afterwards.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub(crate) struct StringList {
+    strings: Vec<Vec<u8>>,
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    encoded: Option<EncodedStrings>,
+}
+
+/// The encoding of a [`StringList`] in the guest memory.
+#[derive(Debug)]
+pub(crate) struct EncodedStrings {
+    /// The offset of each string in `buffer`.
+    pub offsets: Vec<usize>,
+    /// The strings, each followed by a nul byte.
+    pub buffer: Vec<u8>,
+}
+
+impl StringList {
+    pub fn new(strings: Vec<Vec<u8>>) -> Self {
+        Self {
+            strings,
+            encoded: None,
+        }
+    }
+
+    pub fn strings(&self) -> &[Vec<u8>] {
+        &self.strings
+    }
+
+    /// Updates the strings, the encoding is rebuilt by the next
+    /// query.
+    pub fn update<T>(&mut self, update: impl FnOnce(&mut Vec<Vec<u8>>) -> T) -> T {
+        self.encoded = None;
+        update(&mut self.strings)
+    }
+
+    pub fn encoded(&mut self) -> &EncodedStrings {
+        let strings = &self.strings;
+
+        self.encoded.get_or_insert_with(|| {
+            let mut offsets = Vec::with_capacity(strings.len());
+            let mut buffer = Vec::with_capacity(strings.iter().map(|s| s.len() + 1).sum());
+
+            for string in strings {
+                offsets.push(buffer.len());
+                buffer.extend_from_slice(string);
+                buffer.push(0);
+            }
+
+            EncodedStrings { offsets, buffer }
+        })
+    }
+}
+
+/// Checks that `key=value` is a valid environment variable.
+pub(crate) fn validate_env(key: &[u8], value: &[u8]) -> Result<(), WasiStateCreationError> {
+    enum InvalidCharacter {
+        Nul,
+        Equal,
+    }
+
+    match key.iter().find_map(|&ch| {
+        if ch == 0 {
+            Some(InvalidCharacter::Nul)
+        } else if ch == b'=' {
+            Some(InvalidCharacter::Equal)
+        } else {
+            None
+        }
+    }) {
+        Some(InvalidCharacter::Nul) => {
+            return Err(WasiStateCreationError::EnvironmentVariableFormatError(
+                format!(
+                    "found nul byte in env var key \"{}\" (key=value)",
+                    String::from_utf8_lossy(key)
+                ),
+            ))
+        }
+
+        Some(InvalidCharacter::Equal) => {
+            return Err(WasiStateCreationError::EnvironmentVariableFormatError(
+                format!(
+                    "found equal sign in env var key \"{}\" (key=value)",
+                    String::from_utf8_lossy(key)
+                ),
+            ))
+        }
+
+        None => (),
+    }
+
+    if value.iter().any(|&ch| ch == 0) {
+        return Err(WasiStateCreationError::EnvironmentVariableFormatError(
+            format!(
+                "found nul byte in env var value \"{}\" (key=value)",
+                String::from_utf8_lossy(value)
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Joins `key` and `value` as `key=value`.
+pub(crate) fn join_env(key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut env = Vec::with_capacity(key.len() + value.len() + 1);
+    env.extend_from_slice(key);
+    env.push(b'=');
+    env.extend_from_slice(value);
+
+    env
+}
+
+/// Whether `env`, as `key=value`, is the variable `key`.
+pub(crate) fn is_env(env: &[u8], key: &[u8]) -> bool {
+    env.len() > key.len() && env.starts_with(key) && env[key.len()] == b'='
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding() {
+        let mut list = StringList::new(vec![b"A=1".to_vec(), b"BC=".to_vec()]);
+
+        let encoded = list.encoded();
+        assert_eq!(encoded.offsets, [0, 4]);
+        assert_eq!(encoded.buffer, b"A=1\0BC=\0");
+
+        list.update(|strings| strings.push(b"D=four".to_vec()));
+        let encoded = list.encoded();
+        assert_eq!(encoded.offsets, [0, 4, 8]);
+        assert_eq!(encoded.buffer, b"A=1\0BC=\0D=four\0");
+    }
+
+    #[test]
+    fn test_is_env() {
+        assert!(is_env(b"A=1", b"A"));
+        assert!(is_env(b"A=", b"A"));
+        assert!(!is_env(b"AB=1", b"A"));
+        assert!(!is_env(b"A", b"A"));
+    }
+}
@@ -0,0 +1,137 @@
+//! The synthetic `/etc` file system, holding the few configuration
+//! files that many ported programs expect: `passwd`, `group`,
+//! `resolv.conf` and `localtime`.
+//!
+//! The files are generated once, when the state is built.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use wasmer_vfs::synthetic_fs;
+
+/// Where the file system is mounted, for the guest and the host.
+pub(crate) const ETC_PATH: &str = "/etc";
+
+const PASSWD: &str = "root:x:0:0:root:/root:/bin/sh\n\
+                      nobody:x:65534:65534:nobody:/nonexistent:/sbin/nologin\n";
+
+const GROUP: &str = "root:x:0:\nnobody:x:65534:\n";
+
+/// A TZif file describing UTC, for when the host time zone is
+/// unknown.
+pub(super) const UTC_LOCALTIME: &[u8] = b"TZif\
+    \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\
+    \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x01\0\0\0\x04\
+    \0\0\0\0\0\0\
+    UTC\0";
+
+/// Creates the files of `/etc`. `resolv.conf` lists `nameservers`,
+/// or the name servers of the host if there are none, and `localtime`
+/// is the TZif file `localtime`, or the one of the host time zone.
+pub(crate) fn file_system(
+    nameservers: &[IpAddr],
+    localtime: Option<&[u8]>,
+) -> synthetic_fs::FileSystem {
+    let fs = synthetic_fs::FileSystem::default();
+    let insert = |name: &str, contents: Vec<u8>| {
+        fs.insert_file(&Path::new("/").join(name), move || contents.clone())
+            .expect("the `/etc` files are distinct");
+    };
+
+    insert("passwd", PASSWD.as_bytes().to_vec());
+    insert("group", GROUP.as_bytes().to_vec());
+    insert("resolv.conf", resolv_conf(nameservers).into_bytes());
+    insert(
+        "localtime",
+        localtime.map(<[u8]>::to_vec).unwrap_or_else(host_localtime),
+    );
+
+    fs
+}
+
+fn resolv_conf(nameservers: &[IpAddr]) -> String {
+    let host_nameservers;
+    let nameservers = if nameservers.is_empty() {
+        host_nameservers = fs::read_to_string("/etc/resolv.conf")
+            .map(|resolv_conf| parse_nameservers(&resolv_conf))
+            .unwrap_or_default();
+        &host_nameservers
+    } else {
+        nameservers
+    };
+
+    nameservers
+        .iter()
+        .fold(String::new(), |mut resolv_conf, nameserver| {
+            let _ = writeln!(resolv_conf, "nameserver {}", nameserver);
+            resolv_conf
+        })
+}
+
+/// Returns the name servers listed in `resolv_conf`.
+fn parse_nameservers(resolv_conf: &str) -> Vec<IpAddr> {
+    resolv_conf
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("nameserver"), Some(address)) => address.parse().ok(),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Returns the TZif file of the host time zone, from `TZ` if it
+/// names a time zone, or `/etc/localtime`, or UTC.
+fn host_localtime() -> Vec<u8> {
+    let from_tz = std::env::var("TZ").ok().and_then(|tz| {
+        let tz = tz.trim_start_matches(':');
+        let path = if Path::new(tz).is_absolute() {
+            PathBuf::from(tz)
+        } else {
+            Path::new("/usr/share/zoneinfo").join(tz)
+        };
+        fs::read(path).ok()
+    });
+
+    from_tz
+        .or_else(|| fs::read("/etc/localtime").ok())
+        .filter(|localtime| localtime.starts_with(b"TZif"))
+        .unwrap_or_else(|| UTC_LOCALTIME.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nameservers() {
+        let resolv_conf =
+            "# comment\nnameserver 1.1.1.1\nsearch lan\nnameserver ::1\nnameserver foo\n";
+
+        assert_eq!(
+            parse_nameservers(resolv_conf),
+            vec!["1.1.1.1".parse::<IpAddr>().unwrap(), "::1".parse().unwrap()],
+        );
+    }
+
+    #[test]
+    fn test_resolv_conf() {
+        let nameservers = ["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()];
+
+        assert_eq!(
+            resolv_conf(&nameservers),
+            "nameserver 10.0.0.1\nnameserver 10.0.0.2\n"
+        );
+    }
+
+    #[test]
+    fn test_utc_localtime() {
+        // The header, the counts, one local time type and its name.
+        assert_eq!(UTC_LOCALTIME.len(), 44 + 6 + 4);
+        assert_eq!(&UTC_LOCALTIME[36..40], &[0, 0, 0, 1], "one type");
+        assert_eq!(&UTC_LOCALTIME[40..44], &[0, 0, 0, 4], "of 4 characters");
+    }
+}
@@ -167,6 +167,26 @@ impl VirtualFile for WasiStateFileGuard {
         }
     }
 
+    fn ioctl(&mut self, request: u32, data: &[u8]) -> Result<Vec<u8>, FsError> {
+        let inodes = self.inodes.read().unwrap();
+        let mut guard = self.lock_write(&inodes);
+        if let Some(file) = guard.deref_mut() {
+            file.ioctl(request, data)
+        } else {
+            Err(FsError::IOError)
+        }
+    }
+
+    fn map(&self) -> Option<wasmer_vfs::FileMapping> {
+        let inodes = self.inodes.read().unwrap();
+        let guard = self.lock_read(&inodes);
+        if let Some(file) = guard.deref() {
+            file.map()
+        } else {
+            None
+        }
+    }
+
     fn bytes_available(&self) -> Result<usize, FsError> {
         let inodes = self.inodes.read().unwrap();
         let guard = self.lock_read(&inodes);
@@ -216,6 +236,36 @@ impl VirtualFile for WasiStateFileGuard {
             None
         }
     }
+
+    fn is_readable(&self) -> bool {
+        let inodes = self.inodes.read().unwrap();
+        let guard = self.lock_read(&inodes);
+        if let Some(file) = guard.deref() {
+            file.is_readable()
+        } else {
+            false
+        }
+    }
+
+    fn is_writable(&self) -> bool {
+        let inodes = self.inodes.read().unwrap();
+        let guard = self.lock_read(&inodes);
+        if let Some(file) = guard.deref() {
+            file.is_writable()
+        } else {
+            false
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        let inodes = self.inodes.read().unwrap();
+        let guard = self.lock_read(&inodes);
+        if let Some(file) = guard.deref() {
+            file.is_seekable()
+        } else {
+            false
+        }
+    }
 }
 
 impl Write for WasiStateFileGuard {
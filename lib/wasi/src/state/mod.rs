@@ -16,16 +16,37 @@
 #![allow(clippy::cognitive_complexity, clippy::too_many_arguments)]
 
 mod builder;
+mod clock;
+mod environ;
+mod etc;
 mod guard;
+mod operations;
+mod path_error;
+mod path_mapping;
 mod pipe;
+mod proc;
+mod resource;
 mod socket;
+mod stdio;
 mod types;
+mod tz;
 
 pub use self::builder::*;
+pub use self::clock::*;
+pub(crate) use self::environ::{is_env, join_env, validate_env, StringList};
+pub(crate) use self::etc::ETC_PATH;
 pub use self::guard::*;
+pub use self::operations::{PendingOperation, PendingOperationId, PendingOperationKind};
+pub(crate) use self::operations::{PendingOperationGuard, PendingOperations};
+pub use self::path_error::*;
 pub use self::pipe::*;
+pub(crate) use self::proc::{ProcFileSystem, ProcInfo, PROC_PATH};
+pub use self::resource::WasiResource;
 pub use self::socket::*;
+pub use self::stdio::AnsiEscapes;
+pub(crate) use self::stdio::{FilteredOutput, Tee};
 pub use self::types::*;
+pub(crate) use self::tz::{TimeZone, ZONEINFO_PATH};
 use crate::syscalls::types::*;
 use crate::utils::map_io_err;
 use crate::WasiBusProcessId;
@@ -40,6 +61,7 @@ use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{
     borrow::Borrow,
     io::Write,
@@ -54,6 +76,7 @@ use tracing::{debug, trace};
 use wasmer_vbus::BusSpawnedProcess;
 use wasmer_wasi_types::wasi::{
     Errno, Fd as WasiFd, Fdflags, Fdstat, Filesize, Filestat, Filetype, Preopentype, Rights,
+    Snapshot0Clockid,
 };
 use wasmer_wasi_types::wasi::{Prestat, PrestatEnum};
 
@@ -89,6 +112,22 @@ const STDOUT_DEFAULT_RIGHTS: Rights = {
 };
 const STDERR_DEFAULT_RIGHTS: Rights = STDOUT_DEFAULT_RIGHTS;
 
+/// The rights that can't be used on a file, given the capabilities of
+/// its handle, e.g. a file opened read-only can't be written to.
+fn unsupported_rights(handle: &dyn VirtualFile) -> Rights {
+    let mut rights = Rights::empty();
+    if !handle.is_readable() {
+        rights |= Rights::FD_READ;
+    }
+    if !handle.is_writable() {
+        rights |= Rights::FD_WRITE | Rights::FD_ALLOCATE | Rights::FD_FILESTAT_SET_SIZE;
+    }
+    if !handle.is_seekable() {
+        rights |= Rights::FD_SEEK | Rights::FD_TELL;
+    }
+    rights
+}
+
 /// A completely aribtrary "big enough" number used as the upper limit for
 /// the number of symlinks that can be traversed when resolving a path
 pub const MAX_SYMLINKS: u32 = 128;
@@ -315,7 +354,7 @@ impl WasiInodes {
     }
     /// Internal helper function to mutably get a standard device handle.
     /// Expects one of `__WASI_STDIN_FILENO`, `__WASI_STDOUT_FILENO`, `__WASI_STDERR_FILENO`.
-    fn std_dev_get_mut<'a>(
+    pub(crate) fn std_dev_get_mut<'a>(
         &'a self,
         fd_map: &RwLock<HashMap<u32, Fd>>,
         fd: WasiFd,
@@ -350,6 +389,12 @@ pub struct WasiFs {
     pub is_wasix: AtomicBool,
     #[cfg_attr(feature = "enable-serde", serde(skip, default = "default_fs_backing"))]
     pub fs_backing: Box<dyn FileSystem>,
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub(crate) path_error_hook: Option<PathErrorHook>,
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub(crate) fs_audit: Option<FsAudit>,
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub(crate) proc: Option<Arc<ProcInfo>>,
 }
 
 /// Returns the default filesystem backing
@@ -597,6 +642,9 @@ impl WasiFs {
             current_dir: Mutex::new("/".to_string()),
             is_wasix: AtomicBool::new(false),
             fs_backing,
+            path_error_hook: None,
+            fs_audit: None,
+            proc: None,
         };
         wasi_fs.create_stdin(inodes);
         wasi_fs.create_stdout(inodes);
@@ -828,7 +876,8 @@ impl WasiFs {
         fd: WasiFd,
     ) -> Result<Filesize, Errno> {
         let inode = self.get_fd_inode(fd)?;
-        let mut guard = inodes.arena[inode].write();
+        let inodeval = inodes.get_inodeval(inode)?;
+        let mut guard = inodeval.write();
         let deref_mut = guard.deref_mut();
         match deref_mut {
             Kind::File { handle, .. } => {
@@ -836,7 +885,7 @@ impl WasiFs {
                     let new_size = h.size();
                     drop(guard);
 
-                    inodes.arena[inode].stat.write().unwrap().st_size = new_size;
+                    inodeval.stat.write().unwrap().st_size = new_size;
                     Ok(new_size as Filesize)
                 } else {
                     Err(Errno::Badf)
@@ -980,13 +1029,23 @@ impl WasiFs {
                                 }
                             } else if file_type.is_symlink() {
                                 should_insert = false;
-                                let link_value = file.read_link().map_err(map_io_err)?;
+                                let link_value = self
+                                    .fs_backing
+                                    .read_link(&file)
+                                    .map_err(fs_error_into_wasi_err)?;
                                 debug!("attempting to decompose path {:?}", link_value);
 
                                 let (pre_open_dir_fd, relative_path) = if link_value.is_relative() {
-                                    self.path_into_pre_open_and_relative_path(inodes, &file)?
+                                    // the current directory may be the pre-opened one, so
+                                    // it must not be locked while looking for it
+                                    drop(guard);
+                                    let pre_open_and_relative_path =
+                                        self.path_into_pre_open_and_relative_path(inodes, &file);
+                                    guard = inodes.arena[cur_inode].write();
+                                    pre_open_and_relative_path?
                                 } else {
-                                    unimplemented!("Absolute symlinks are not yet supported");
+                                    // Absolute symlinks are not yet supported.
+                                    return Err(Errno::Notsup);
                                 };
                                 loop_for_symlink = true;
                                 symlink_count += 1;
@@ -1269,6 +1328,78 @@ impl WasiFs {
         self.get_inode_at_path_inner(inodes, start_inode, path, 0, follow_symlinks)
     }
 
+    /// Passes a failed path-based syscall to the function registered with
+    /// [`WasiStateBuilder::on_path_error`], if any.
+    pub(crate) fn report_path_error(
+        &self,
+        inodes: &WasiInodes,
+        syscall: &'static str,
+        base_fd: WasiFd,
+        path: &str,
+        errno: Errno,
+    ) {
+        let hook = match &self.path_error_hook {
+            Some(hook) => hook,
+            None => return,
+        };
+        let (base_name, base_host_path) = self.base_dir(inodes, base_fd);
+
+        (hook.0)(&PathError {
+            syscall,
+            base_fd,
+            base_name,
+            base_host_path,
+            path: path.to_string(),
+            errno,
+        });
+    }
+
+    /// Passes the path accessed by a path-based syscall to the function
+    /// registered with [`WasiStateBuilder::fs_audit_hook`], if any.
+    pub(crate) fn audit_path(
+        &self,
+        inodes: &WasiInodes,
+        syscall: &'static str,
+        base_fd: WasiFd,
+        path: &str,
+        errno: Errno,
+    ) {
+        let audit = match &self.fs_audit {
+            Some(audit) => audit,
+            None => return,
+        };
+        let (_, base_host_path) = self.base_dir(inodes, base_fd);
+
+        (audit.0)(&FsAccess {
+            syscall,
+            base_fd,
+            path: path.to_string(),
+            resolved_path: base_host_path.map(|base| base.join(path)),
+            errno,
+        });
+    }
+
+    /// Whether path-based syscalls are audited.
+    pub(crate) fn is_audited(&self) -> bool {
+        self.fs_audit.is_some()
+    }
+
+    /// Returns the name of the directory behind `fd`, and its path in
+    /// the file system backing, if it's not the virtual root.
+    fn base_dir(&self, inodes: &WasiInodes, fd: WasiFd) -> (String, Option<PathBuf>) {
+        match self.get_fd_inode(fd) {
+            Ok(inode) => {
+                let inode_val = &inodes.arena[inode];
+                let base_host_path = match inode_val.read().deref() {
+                    Kind::Dir { path, .. } => Some(path.clone()),
+                    _ => None,
+                };
+                (inode_val.name.clone(), base_host_path)
+            }
+            Err(_) => (String::new(), None),
+        }
+    }
+
     /// Returns the parent Dir or Root that the file at a given path is in and the file name
     /// stripped off
     pub(crate) fn get_parent_inode_at_path(
@@ -1313,7 +1444,7 @@ impl WasiFs {
 
     pub fn filestat_fd(&self, inodes: &WasiInodes, fd: WasiFd) -> Result<Filestat, Errno> {
         let inode = self.get_fd_inode(fd)?;
-        Ok(*inodes.arena[inode].stat.read().unwrap().deref())
+        Ok(*inodes.get_inodeval(inode)?.stat.read().unwrap().deref())
     }
 
     pub fn fdstat(&self, inodes: &WasiInodes, fd: WasiFd) -> Result<Fdstat, Errno> {
@@ -1356,8 +1487,19 @@ impl WasiFs {
         let fd = self.get_fd(fd)?;
         debug!("fdstat: {:?}", fd);
 
-        let guard = inodes.arena[fd.inode].read();
+        let guard = inodes.get_inodeval(fd.inode)?.read();
         let deref = guard.deref();
+
+        // Only report the rights that the underlying file supports.
+        let unsupported = match deref {
+            Kind::File {
+                handle: Some(handle),
+                ..
+            } => unsupported_rights(handle.as_ref()),
+            Kind::Pipe { .. } | Kind::Socket { .. } => Rights::FD_SEEK | Rights::FD_TELL,
+            _ => Rights::empty(),
+        };
+
         Ok(Fdstat {
             fs_filetype: match deref {
                 Kind::File { .. } => Filetype::RegularFile,
@@ -1366,7 +1508,7 @@ impl WasiFs {
                 _ => Filetype::Unknown,
             },
             fs_flags: fd.flags,
-            fs_rights_base: fd.rights,
+            fs_rights_base: fd.rights - unsupported,
             fs_rights_inheriting: fd.rights_inheriting, // TODO(lachlan): Is this right?
         })
     }
@@ -1416,7 +1558,7 @@ impl WasiFs {
                     return Err(Errno::Access);
                 }
 
-                let mut guard = inodes.arena[fd.inode].write();
+                let mut guard = inodes.get_inodeval(fd.inode)?.write();
                 let deref_mut = guard.deref_mut();
                 match deref_mut {
                     Kind::File {
@@ -1618,6 +1760,27 @@ impl WasiFs {
         );
     }
 
+    /// Sets the access and modification times of a file or a
+    /// directory in the backing file system, so that they survive
+    /// the cached stat of its inode. File systems unable to set times
+    /// are left untouched.
+    pub(crate) fn set_times_for_kind(
+        &self,
+        kind: &Kind,
+        accessed: Option<u64>,
+        modified: Option<u64>,
+    ) -> Result<(), Errno> {
+        let path = match kind {
+            Kind::File { path, .. } | Kind::Dir { path, .. } => path,
+            _ => return Ok(()),
+        };
+
+        match self.fs_backing.set_times(path, accessed, modified) {
+            Ok(()) | Err(FsError::Unsupported) => Ok(()),
+            Err(err) => Err(fs_error_into_wasi_err(err)),
+        }
+    }
+
     pub fn get_stat_for_kind(&self, inodes: &WasiInodes, kind: &Kind) -> Result<Filestat, Errno> {
         let md = match kind {
             Kind::File { handle, path, .. } => match handle {
@@ -1757,6 +1920,30 @@ impl WasiFs {
 
 // Implementations of direct to FS calls so that we can easily change their implementation
 impl WasiState {
+    /// Converts the host time of `clock_id` to the time observed by the guest.
+    pub(crate) fn guest_time(&self, clock_id: Snapshot0Clockid, host_time: i64) -> i64 {
+        match &self.clock {
+            Some(clock) => clock.guest_time(clock_id, host_time),
+            None => host_time,
+        }
+    }
+
+    /// Converts the resolution of a host clock to the one observed by the guest.
+    pub(crate) fn guest_resolution(&self, host_resolution: i64) -> i64 {
+        match &self.clock {
+            Some(clock) => clock.guest_resolution(host_resolution),
+            None => host_resolution,
+        }
+    }
+
+    /// Converts a duration given by the guest to the host duration.
+    pub(crate) fn host_duration(&self, guest_duration: Duration) -> Duration {
+        match &self.clock {
+            Some(clock) => clock.host_duration(guest_duration),
+            None => guest_duration,
+        }
+    }
+
     pub(crate) fn fs_read_dir<P: AsRef<Path>>(
         &self,
         path: P,
@@ -1855,8 +2042,11 @@ pub struct WasiState {
     pub fs: WasiFs,
     pub inodes: Arc<RwLock<WasiInodes>>,
     pub(crate) threading: Mutex<WasiStateThreading>,
-    pub args: Vec<Vec<u8>>,
-    pub envs: Vec<Vec<u8>>,
+    pub(crate) args: Mutex<StringList>,
+    pub(crate) envs: Mutex<StringList>,
+    pub(crate) clock: Option<VirtualClock>,
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub(crate) operations: Mutex<PendingOperations>,
 }
 
 impl WasiState {
@@ -1879,6 +2069,79 @@ impl WasiState {
         bincode::deserialize(bytes).ok()
     }
 
+    /// The arguments of the program, starting with its name.
+    pub fn args(&self) -> Vec<Vec<u8>> {
+        self.args.lock().unwrap().strings().to_vec()
+    }
+
+    /// Replaces the arguments of the program, starting with its name.
+    ///
+    /// The guest sees the new arguments the next time it calls
+    /// `args_sizes_get`, which usually happens once at its start.
+    pub fn set_args<I, Arg>(&self, args: I) -> Result<(), WasiStateCreationError>
+    where
+        I: IntoIterator<Item = Arg>,
+        Arg: AsRef<[u8]>,
+    {
+        let args = args
+            .into_iter()
+            .map(|arg| arg.as_ref().to_vec())
+            .collect::<Vec<_>>();
+
+        if let Some(arg) = args.iter().find(|arg| arg.contains(&0)) {
+            return Err(WasiStateCreationError::ArgumentContainsNulByte(
+                String::from_utf8_lossy(arg).into_owned(),
+            ));
+        }
+
+        self.args.lock().unwrap().update(|current| *current = args);
+
+        Ok(())
+    }
+
+    /// The environment variables, as `key=value`.
+    pub fn envs(&self) -> Vec<Vec<u8>> {
+        self.envs.lock().unwrap().strings().to_vec()
+    }
+
+    /// Sets the environment variable `key` to `value`, replacing its
+    /// current value if any.
+    ///
+    /// The guest sees the update the next time it calls
+    /// `environ_sizes_get`, which usually happens once at its start.
+    pub fn set_env<Key, Value>(&self, key: Key, value: Value) -> Result<(), WasiStateCreationError>
+    where
+        Key: AsRef<[u8]>,
+        Value: AsRef<[u8]>,
+    {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        validate_env(key, value)?;
+
+        self.envs.lock().unwrap().update(|envs| {
+            let env = join_env(key, value);
+
+            match envs.iter_mut().find(|env| is_env(env, key)) {
+                Some(current) => *current = env,
+                None => envs.push(env),
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Removes the environment variable `key`, and returns whether it
+    /// was set.
+    pub fn remove_env(&self, key: impl AsRef<[u8]>) -> bool {
+        let key = key.as_ref();
+
+        self.envs.lock().unwrap().update(|envs| {
+            let len = envs.len();
+            envs.retain(|env| !is_env(env, key));
+
+            envs.len() != len
+        })
+    }
+
     /// Get the `VirtualFile` object at stdout
     pub fn stdout(&self) -> Result<Option<Box<dyn VirtualFile + Send + Sync + 'static>>, FsError> {
         self.std_dev_get(__WASI_STDOUT_FILENO)
@@ -0,0 +1,179 @@
+//! Tracking of the system calls blocking the guest, so that a
+//! supervisor can tell what a wedged instance is waiting for, and
+//! unstick it without killing the whole process.
+
+use crate::syscalls::platform_clock_time_get;
+use crate::WasiThreadId;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+use wasmer_wasi_types::wasi::{Fd as WasiFd, Snapshot0Clockid};
+
+/// Represents the ID of a pending operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PendingOperationId(u64);
+
+impl From<u64> for PendingOperationId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+impl From<PendingOperationId> for u64 {
+    fn from(id: PendingOperationId) -> u64 {
+        id.0
+    }
+}
+
+/// The system call an operation is blocked in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingOperationKind {
+    /// `fd_read`, waiting for an event notification.
+    Read,
+    /// `poll_oneoff`, waiting for a subscription to trigger.
+    Poll,
+    /// `sock_accept`, waiting for an incoming connection.
+    Accept,
+    /// `thread_join`, waiting for another thread to exit.
+    ThreadJoin,
+}
+
+/// A description of a blocking system call.
+#[derive(Debug, Clone)]
+pub struct PendingOperation {
+    /// The ID to pass to [`WasiEnv::cancel`](crate::WasiEnv::cancel).
+    pub id: PendingOperationId,
+    /// The thread blocked in the system call.
+    pub thread: WasiThreadId,
+    /// The file descriptor the system call waits on, if any.
+    pub fd: Option<WasiFd>,
+    /// The system call.
+    pub kind: PendingOperationKind,
+    /// How long the system call has been blocking.
+    pub elapsed: Duration,
+}
+
+#[derive(Debug)]
+struct Operation {
+    thread: WasiThreadId,
+    fd: Option<WasiFd>,
+    kind: PendingOperationKind,
+    /// The monotonic time the operation started at, in nanoseconds.
+    started: i64,
+    cancelled: Arc<AtomicBool>,
+    /// Wakes the thread up when the operation is cancelled, if it's
+    /// sleeping.
+    waker: Option<mpsc::Sender<()>>,
+}
+
+/// The operations pending in an instance.
+#[derive(Debug, Default)]
+pub(crate) struct PendingOperations {
+    seed: u64,
+    operations: HashMap<PendingOperationId, Operation>,
+}
+
+fn now() -> i64 {
+    platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap_or(0)
+}
+
+impl PendingOperations {
+    /// Registers a new operation, which stays pending until the
+    /// returned guard is dropped.
+    pub(crate) fn begin(
+        operations: &Mutex<Self>,
+        thread: WasiThreadId,
+        fd: Option<WasiFd>,
+        kind: PendingOperationKind,
+    ) -> PendingOperationGuard<'_> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut guard = operations.lock().unwrap();
+
+        guard.seed += 1;
+        let id: PendingOperationId = guard.seed.into();
+        guard.operations.insert(
+            id,
+            Operation {
+                thread,
+                fd,
+                kind,
+                started: now(),
+                cancelled: cancelled.clone(),
+                waker: None,
+            },
+        );
+
+        PendingOperationGuard {
+            id,
+            operations,
+            cancelled,
+        }
+    }
+
+    /// Describes the pending operations, oldest first.
+    pub(crate) fn list(&self) -> Vec<PendingOperation> {
+        let now = now();
+        let mut operations = self
+            .operations
+            .iter()
+            .map(|(&id, operation)| PendingOperation {
+                id,
+                thread: operation.thread,
+                fd: operation.fd,
+                kind: operation.kind,
+                elapsed: Duration::from_nanos(now.saturating_sub(operation.started).max(0) as u64),
+            })
+            .collect::<Vec<_>>();
+        operations.sort_by_key(|operation| operation.id);
+
+        operations
+    }
+
+    /// Flags an operation as cancelled. Returns `false` if it's no
+    /// longer pending.
+    pub(crate) fn cancel(&self, id: PendingOperationId) -> bool {
+        match self.operations.get(&id) {
+            Some(operation) => {
+                operation.cancelled.store(true, Ordering::Release);
+                if let Some(waker) = &operation.waker {
+                    let _ = waker.send(());
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Keeps an operation pending while the system call blocks.
+pub(crate) struct PendingOperationGuard<'a> {
+    id: PendingOperationId,
+    operations: &'a Mutex<PendingOperations>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PendingOperationGuard<'_> {
+    /// Whether the operation has been cancelled, in which case the
+    /// system call must return `EINTR`.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Sets the channel waking the thread up, while it sleeps, when
+    /// the operation is cancelled.
+    pub(crate) fn set_waker(&self, waker: mpsc::Sender<()>) {
+        if let Ok(mut operations) = self.operations.lock() {
+            if let Some(operation) = operations.operations.get_mut(&self.id) {
+                operation.waker = Some(waker);
+            }
+        }
+    }
+}
+
+impl Drop for PendingOperationGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut operations) = self.operations.lock() {
+            operations.operations.remove(&self.id);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::syscalls::types::wasi::{Errno, Fd as WasiFd};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A path-based syscall that failed, along with how the path given by
+/// the guest was resolved.
+///
+/// It is passed to the function registered with
+/// [`WasiStateBuilder::on_path_error`](super::WasiStateBuilder::on_path_error),
+/// so that hosts can explain errors like `ENOENT` to their users.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError {
+    /// The name of the syscall, e.g. `path_open`.
+    pub syscall: &'static str,
+    /// The file descriptor the path is relative to.
+    pub base_fd: WasiFd,
+    /// The name of the directory behind `base_fd`, e.g. the alias of a
+    /// pre-opened directory, or `/` for the virtual root.
+    pub base_name: String,
+    /// The path of the directory behind `base_fd` in the file system
+    /// backing, if it isn't the virtual root.
+    pub base_host_path: Option<PathBuf>,
+    /// The path given by the guest.
+    pub path: String,
+    /// The error returned to the guest.
+    pub errno: Errno,
+}
+
+/// The function called on each [`PathError`].
+pub type PathErrorFn = Arc<dyn Fn(&PathError) + Send + Sync>;
+
+/// Holds the [`PathErrorFn`] in the file system state.
+#[derive(Clone)]
+pub(crate) struct PathErrorHook(pub(crate) PathErrorFn);
+
+impl fmt::Debug for PathErrorHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PathErrorHook")
+    }
+}
+
+/// An access of the guest to the file system through a path-based
+/// syscall, like `path_open` or `path_unlink_file`.
+///
+/// It is passed to the function registered with
+/// [`WasiStateBuilder::fs_audit_hook`](super::WasiStateBuilder::fs_audit_hook),
+/// so that hosts can log the paths touched by a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsAccess {
+    /// The name of the syscall, e.g. `path_open`.
+    pub syscall: &'static str,
+    /// The file descriptor the path is relative to.
+    pub base_fd: WasiFd,
+    /// The path given by the guest.
+    pub path: String,
+    /// The path in the file system backing, made of the path of the
+    /// directory behind `base_fd` and of `path`, unless `base_fd`
+    /// isn't a directory of the file system backing. It isn't
+    /// normalized, nor are its symlinks resolved.
+    pub resolved_path: Option<PathBuf>,
+    /// The result returned to the guest.
+    pub errno: Errno,
+}
+
+/// The function called on each [`FsAccess`].
+pub type FsAuditHook = Arc<dyn Fn(&FsAccess) + Send + Sync>;
+
+/// Holds the [`FsAuditHook`] in the file system state.
+#[derive(Clone)]
+pub(crate) struct FsAudit(pub(crate) FsAuditHook);
+
+impl fmt::Debug for FsAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FsAudit")
+    }
+}
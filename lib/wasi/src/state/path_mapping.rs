@@ -0,0 +1,138 @@
+//! Translation of the paths between the guest and the host, following
+//! the pre-opened directories.
+
+use super::{Kind, WasiFs, WasiInodes};
+use std::ops::Deref;
+use std::path::{Component, Path, PathBuf};
+
+impl WasiFs {
+    /// Translates a path seen by the guest into the path of the
+    /// backing file system, or `None` if the path is not under a
+    /// pre-opened directory.
+    ///
+    /// Relative paths are relative to the current directory of the
+    /// guest. The path is resolved lexically: symlinks are not
+    /// followed and the file doesn't need to exist.
+    pub fn host_path_of(&self, inodes: &WasiInodes, guest_path: &Path) -> Option<PathBuf> {
+        let guest_path = {
+            let current_dir = self.current_dir.lock().unwrap();
+            normalize(&Path::new("/").join(current_dir.as_str()).join(guest_path))
+        };
+
+        innermost(
+            self.preopen_mappings(inodes)
+                .into_iter()
+                .filter_map(|(guest_dir, host_dir)| {
+                    let rest = guest_path.strip_prefix(&guest_dir).ok()?;
+                    Some((guest_dir.as_os_str().len(), host_dir.join(rest)))
+                }),
+        )
+    }
+
+    /// Translates a path of the backing file system into the path
+    /// seen by the guest, or `None` if the path is not under a
+    /// pre-opened directory.
+    ///
+    /// Relative paths are relative to the current directory of the
+    /// host process. The path is resolved lexically, like in
+    /// [`Self::host_path_of`].
+    pub fn guest_path_of(&self, inodes: &WasiInodes, host_path: &Path) -> Option<PathBuf> {
+        let host_path = absolute(host_path);
+
+        innermost(
+            self.preopen_mappings(inodes)
+                .into_iter()
+                .filter_map(|(guest_dir, host_dir)| {
+                    let host_dir = absolute(&host_dir);
+                    let rest = host_path.strip_prefix(&host_dir).ok()?;
+                    Some((host_dir.as_os_str().len(), guest_dir.join(rest)))
+                }),
+        )
+    }
+
+    /// The pre-opened directories, as pairs of the normalized guest
+    /// path and the path in the backing file system.
+    fn preopen_mappings(&self, inodes: &WasiInodes) -> Vec<(PathBuf, PathBuf)> {
+        let fd_map = self.fd_map.read().unwrap();
+
+        self.preopen_fds
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|fd| {
+                let inode = &inodes.arena[fd_map.get(fd)?.inode];
+                match inode.read().deref() {
+                    Kind::Dir { path, .. } => {
+                        Some((normalize(&Path::new("/").join(&inode.name)), path.clone()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Picks the translation through the innermost pre-opened directory,
+/// given with the length of its prefix, or the last one among equals,
+/// like the resolution of the guest paths.
+fn innermost(translations: impl Iterator<Item = (usize, PathBuf)>) -> Option<PathBuf> {
+    translations
+        .fold(
+            None,
+            |best: Option<(usize, PathBuf)>, (len, path)| match best {
+                Some((best_len, _)) if best_len > len => best,
+                _ => Some((len, path)),
+            },
+        )
+        .map(|(_, path)| normalize(&path))
+}
+
+/// Resolves the `.` and `..` components of `path` lexically. `..`
+/// stops at the root, or is kept at the start of a relative path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) => {}
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+
+    normalized
+}
+
+/// Makes `path` absolute against the current directory of the host
+/// process, if possible, and normalizes it.
+fn absolute(path: &Path) -> PathBuf {
+    match std::env::current_dir() {
+        Ok(current_dir) if path.is_relative() => normalize(&current_dir.join(path)),
+        _ => normalize(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize;
+    use std::path::Path;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("/a/./b/../c")), Path::new("/a/c"));
+        assert_eq!(normalize(Path::new("/../a")), Path::new("/a"));
+        assert_eq!(normalize(Path::new("a/../../b")), Path::new("../b"));
+        assert_eq!(normalize(Path::new("./a/..")), Path::new("."));
+        assert_eq!(normalize(Path::new("../..")), Path::new("../.."));
+    }
+}
@@ -0,0 +1,250 @@
+//! The synthetic `/proc` file system, exposing information about the
+//! running instance to the guest.
+//!
+//! The files are generated from a snapshot of the instance, which is
+//! refreshed by the system calls that may read them (`path_open` and
+//! `path_filestat_get`), since the file system can't reach the store
+//! nor the environment by itself.
+
+use crate::WasiEnv;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use wasmer::AsStoreRef;
+use wasmer_vfs::{synthetic_fs, FileSystem, FsError, Metadata, OpenOptions, ReadDir, Result};
+
+/// Where the file system is mounted.
+pub(crate) const PROC_PATH: &str = "/proc";
+
+/// The information exposed by the `/proc` file system.
+#[derive(Debug, Default)]
+pub(crate) struct ProcInfo {
+    snapshot: Mutex<ProcSnapshot>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct ProcSnapshot {
+    args: Vec<String>,
+    envs: Vec<String>,
+    pid: u32,
+    threads: Vec<u32>,
+    memory_size: u64,
+    memory_maximum: Option<u64>,
+    fds: Vec<(u32, String)>,
+    preopens: Vec<(u32, String)>,
+}
+
+impl ProcInfo {
+    /// Takes a new snapshot of the instance. The inodes and file
+    /// descriptors of the state must not be locked.
+    pub(crate) fn refresh(&self, env: &WasiEnv, store: &impl AsStoreRef) {
+        let state = &env.state;
+
+        let mut threads = vec![0];
+        if let Ok(threading) = state.threading.lock() {
+            threads.extend(threading.threads.keys().map(|&id| u32::from(id)));
+        }
+        threads.sort_unstable();
+        threads.dedup();
+
+        let (mut fds, mut preopens) = (Vec::new(), Vec::new());
+        if let (Ok(inodes), Ok(fd_map)) = (state.inodes.read(), state.fs.fd_map.read()) {
+            let name = |fd: u32| {
+                fd_map
+                    .get(&fd)
+                    .and_then(|entry| inodes.arena.get(entry.inode))
+                    .map(|inode| inode.name.clone())
+                    .unwrap_or_default()
+            };
+            fds = fd_map.keys().map(|&fd| (fd, name(fd))).collect();
+            fds.sort_unstable();
+            if let Ok(preopen_fds) = state.fs.preopen_fds.read() {
+                preopens = preopen_fds.iter().map(|&fd| (fd, name(fd))).collect();
+            }
+        }
+
+        let (memory_size, memory_maximum) = match env.memory.as_ref() {
+            Some(memory) => (
+                memory.view(store).data_size(),
+                memory
+                    .ty(store)
+                    .maximum
+                    .map(|maximum| maximum.bytes().0 as u64),
+            ),
+            None => (0, None),
+        };
+
+        let snapshot = ProcSnapshot {
+            args: state
+                .args()
+                .iter()
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect(),
+            envs: state
+                .envs()
+                .iter()
+                .map(|env| String::from_utf8_lossy(env).into_owned())
+                .collect(),
+            pid: env.runtime().getpid().unwrap_or(1),
+            threads,
+            memory_size,
+            memory_maximum,
+            fds,
+            preopens,
+        };
+        if let Ok(mut current) = self.snapshot.lock() {
+            *current = snapshot;
+        }
+    }
+
+    fn snapshot(&self) -> ProcSnapshot {
+        self.snapshot
+            .lock()
+            .map(|snapshot| snapshot.clone())
+            .unwrap_or_default()
+    }
+
+    /// Creates the files of `/proc`.
+    fn file_system(self: &Arc<Self>) -> synthetic_fs::FileSystem {
+        let fs = synthetic_fs::FileSystem::default();
+        let insert = |name: &str, generate: fn(&ProcSnapshot) -> String| {
+            let info = self.clone();
+            fs.insert_file(&Path::new("/self").join(name), move || {
+                generate(&info.snapshot()).into_bytes()
+            })
+            .expect("the `/proc` files are distinct");
+        };
+
+        insert("status", |snapshot| {
+            format!(
+                "Name:\t{}\nPid:\t{}\nThreads:\t{}\nVmSize:\t{} kB\nFDSize:\t{}\n",
+                snapshot.args.first().map(String::as_str).unwrap_or(""),
+                snapshot.pid,
+                snapshot.threads.len(),
+                snapshot.memory_size / 1024,
+                snapshot.fds.len(),
+            )
+        });
+        insert("cmdline", |snapshot| {
+            snapshot
+                .args
+                .iter()
+                .fold(String::new(), |mut cmdline, arg| {
+                    cmdline.push_str(arg);
+                    cmdline.push('\0');
+                    cmdline
+                })
+        });
+        insert("environ", |snapshot| {
+            snapshot
+                .envs
+                .iter()
+                .fold(String::new(), |mut environ, env| {
+                    environ.push_str(env);
+                    environ.push('\0');
+                    environ
+                })
+        });
+        insert("threads", |snapshot| {
+            snapshot
+                .threads
+                .iter()
+                .fold(String::new(), |mut threads, id| {
+                    let _ = writeln!(threads, "{}", id);
+                    threads
+                })
+        });
+        insert("fds", |snapshot| {
+            snapshot
+                .fds
+                .iter()
+                .fold(String::new(), |mut fds, (fd, name)| {
+                    let _ = writeln!(fds, "{}\t{}", fd, name);
+                    fds
+                })
+        });
+        insert("preopens", |snapshot| {
+            snapshot
+                .preopens
+                .iter()
+                .fold(String::new(), |mut preopens, (fd, name)| {
+                    let _ = writeln!(preopens, "{}\t{}", fd, name);
+                    preopens
+                })
+        });
+        // The sizes are in pages of 4 KiB, like on Linux; the whole
+        // linear memory is resident and holds data.
+        insert("statm", |snapshot| {
+            let pages = snapshot.memory_size / 4096;
+            format!("{} {} 0 0 0 {} 0\n", pages, pages, pages)
+        });
+        insert("limits", |snapshot| {
+            let maximum = snapshot
+                .memory_maximum
+                .map(|maximum| maximum.to_string())
+                .unwrap_or_else(|| "unlimited".to_string());
+            format!(
+                "{:<26}{:<21}{:<21}{}\n{:<26}{:<21}{:<21}{}\n{:<26}{:<21}{:<21}{}\n",
+                "Limit",
+                "Soft Limit",
+                "Hard Limit",
+                "Units",
+                "Max address space",
+                maximum,
+                maximum,
+                "bytes",
+                "Max open files",
+                "unlimited",
+                "unlimited",
+                "files",
+            )
+        });
+
+        fs
+    }
+}
+
+/// The read-only file system mounted at `/proc`, see
+/// [`crate::WasiStateBuilder::proc_fs`].
+#[derive(Debug, Clone)]
+pub(crate) struct ProcFileSystem {
+    inner: synthetic_fs::FileSystem,
+}
+
+impl ProcFileSystem {
+    pub(crate) fn new(info: &Arc<ProcInfo>) -> Self {
+        Self {
+            inner: info.file_system(),
+        }
+    }
+}
+
+impl FileSystem for ProcFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn remove_file(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        self.inner.new_open_options()
+    }
+}
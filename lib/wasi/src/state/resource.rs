@@ -0,0 +1,235 @@
+//! Host resources, like database connections or GPU buffers, that the
+//! embedder registers in the file descriptor table of the guest, see
+//! [`WasiFs::register_resource`].
+
+use super::{fs_error_into_wasi_err, unsupported_rights, Fd, InodeVal, Kind, WasiFs, WasiInodes};
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
+use std::sync::RwLock;
+use wasmer_vfs::{FsError, VirtualFile};
+use wasmer_wasi_types::wasi::{Errno, Fd as WasiFd, Fdflags, Filestat, Filetype, Rights};
+
+/// A host object that the guest reads and writes through a file
+/// descriptor, with the usual `fd_read`, `fd_write`, `poll_oneoff`
+/// and `fd_close`.
+///
+/// Every hook has a default, so a resource implements only what it
+/// supports.
+pub trait WasiResource: fmt::Debug + Send + Sync + 'static {
+    /// Reads from the resource, see [`Read::read`].
+    /// Defaults to failing with [`io::ErrorKind::Unsupported`].
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Writes to the resource, see [`Write::write`].
+    /// Defaults to failing with [`io::ErrorKind::Unsupported`].
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Flushes what was written to the resource.
+    /// Defaults to doing nothing.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the number of bytes that can be read without
+    /// blocking, for `poll_oneoff`. This function must not block.
+    /// Defaults to `None` which means the number of bytes is unknown
+    fn bytes_available_read(&self) -> io::Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// Returns the number of bytes that can be written without
+    /// blocking, for `poll_oneoff`. This function must not block.
+    /// Defaults to `None` which means the number of bytes is unknown
+    fn bytes_available_write(&self) -> io::Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// Indicates if the resource is still open, a closed resource
+    /// hangs up the pollers. This function must not block.
+    /// Defaults to `true`
+    fn is_open(&self) -> bool {
+        true
+    }
+
+    /// Indicates if the resource can be read from.
+    /// Defaults to `true`
+    fn is_readable(&self) -> bool {
+        true
+    }
+
+    /// Indicates if the resource can be written to.
+    /// Defaults to `true`
+    fn is_writable(&self) -> bool {
+        true
+    }
+
+    /// Handles the `request`, in the manner of `ioctl`, with its
+    /// `data`, returning the data of the response. The resource
+    /// defines the requests it understands; they reach it through
+    /// the `fd_ioctl` syscall, or [`WasiFs::control_resource`], e.g.
+    /// from an import of the embedder.
+    /// Defaults to failing with [`Errno::Notsup`].
+    fn control(&mut self, _request: u32, _data: &[u8]) -> Result<Vec<u8>, Errno> {
+        Err(Errno::Notsup)
+    }
+}
+
+/// A [`WasiResource`] seen as a file, so that it lives in the file
+/// descriptor table like any other file.
+#[derive(Debug)]
+struct ResourceFile {
+    resource: Box<dyn WasiResource>,
+}
+
+impl ResourceFile {
+    fn new(resource: Box<dyn WasiResource>) -> Self {
+        Self { resource }
+    }
+}
+
+impl Read for ResourceFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.resource.read(buf)
+    }
+}
+
+impl Write for ResourceFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.resource.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.resource.flush()
+    }
+}
+
+impl Seek for ResourceFile {
+    /// A resource is a stream, the file descriptor moves its offset
+    /// before each read or write, which is ignored.
+    fn seek(&mut self, _position: io::SeekFrom) -> io::Result<u64> {
+        Ok(0)
+    }
+}
+
+impl VirtualFile for ResourceFile {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+
+    fn last_modified(&self) -> u64 {
+        0
+    }
+
+    fn created_time(&self) -> u64 {
+        0
+    }
+
+    fn size(&self) -> u64 {
+        0
+    }
+
+    fn set_len(&mut self, _new_size: u64) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self) -> Result<(), FsError> {
+        Ok(())
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>, FsError> {
+        self.resource.bytes_available_read().map_err(FsError::from)
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>, FsError> {
+        self.resource.bytes_available_write().map_err(FsError::from)
+    }
+
+    fn is_open(&self) -> bool {
+        self.resource.is_open()
+    }
+
+    fn is_readable(&self) -> bool {
+        self.resource.is_readable()
+    }
+
+    fn is_writable(&self) -> bool {
+        self.resource.is_writable()
+    }
+
+    fn is_seekable(&self) -> bool {
+        false
+    }
+}
+
+impl WasiFs {
+    /// Registers `resource` in the file descriptor table of the
+    /// guest, under `name`, returning its file descriptor. The guest
+    /// reads, writes, polls and closes it like a file; the rights
+    /// follow what the resource supports.
+    pub fn register_resource(
+        &self,
+        inodes: &mut WasiInodes,
+        name: impl Into<String>,
+        resource: impl WasiResource,
+    ) -> Result<WasiFd, Errno> {
+        let handle = ResourceFile::new(Box::new(resource));
+        let rights = (Rights::FD_READ
+            | Rights::FD_WRITE
+            | Rights::FD_FDSTAT_SET_FLAGS
+            | Rights::POLL_FD_READWRITE)
+            - unsupported_rights(&handle);
+
+        let stat = Filestat {
+            st_filetype: Filetype::CharacterDevice,
+            st_ino: self.get_next_inode_index(),
+            ..Filestat::default()
+        };
+        let kind = Kind::File {
+            handle: Some(Box::new(handle)),
+            path: "".into(),
+            fd: None,
+        };
+        let inode = inodes.arena.insert(InodeVal {
+            stat: RwLock::new(stat),
+            is_preopened: false,
+            name: name.into(),
+            kind: RwLock::new(kind),
+        });
+
+        self.create_fd(rights, Rights::empty(), Fdflags::empty(), 0, inode)
+    }
+
+    /// Sends the `request`, with its `data`, to the resource
+    /// registered as `fd`, returning the data of the response. See
+    /// [`WasiResource::control`].
+    ///
+    /// Any other file handles the request with
+    /// [`VirtualFile::ioctl`].
+    pub fn control_resource(
+        &self,
+        inodes: &WasiInodes,
+        fd: WasiFd,
+        request: u32,
+        data: &[u8],
+    ) -> Result<Vec<u8>, Errno> {
+        let Fd { inode, .. } = self.get_fd(fd)?;
+        let mut guard = inodes.arena[inode].write();
+        match &mut *guard {
+            Kind::File {
+                handle: Some(handle),
+                ..
+            } => {
+                // The errno of a resource is kept as is.
+                if let Some(file) = (**handle).upcast_any_mut().downcast_mut::<ResourceFile>() {
+                    return file.resource.control(request, data);
+                }
+                handle.ioctl(request, data).map_err(fs_error_into_wasi_err)
+            }
+            _ => Err(Errno::Notty),
+        }
+    }
+}
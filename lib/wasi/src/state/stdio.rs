@@ -0,0 +1,288 @@
+//! Filters and copies of the output of the program, see
+//! [`WasiStateBuilder::output_ansi`](super::WasiStateBuilder::output_ansi)
+//! and [`WasiStateBuilder::tee_output`](super::WasiStateBuilder::tee_output).
+
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
+use std::sync::{Arc, Mutex};
+use wasmer_vfs::{FileDescriptor, FsError, VirtualFile};
+
+/// What to do with the ANSI escape sequences, like the colors, written
+/// by the program to `stdout` and `stderr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiEscapes {
+    /// Write them as they are.
+    Preserve,
+    /// Remove them.
+    Strip,
+}
+
+impl Default for AnsiEscapes {
+    fn default() -> Self {
+        Self::Preserve
+    }
+}
+
+/// A writer shared by the outputs, e.g. a log file receiving both
+/// `stdout` and `stderr`.
+#[derive(Clone)]
+pub(crate) struct Tee(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl Tee {
+    pub(crate) fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self(Arc::new(Mutex::new(writer)))
+    }
+
+    fn write_all(&self, buf: &[u8]) -> io::Result<()> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "the tee is poisoned"))?
+            .write_all(buf)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "the tee is poisoned"))?
+            .flush()
+    }
+}
+
+/// An output of the program, whose ANSI escape sequences can be
+/// stripped, and whose bytes can be copied to a [`Tee`].
+pub(crate) struct FilteredOutput {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+    stripper: Option<AnsiStripper>,
+    tee: Option<Tee>,
+}
+
+impl FilteredOutput {
+    pub(crate) fn new(
+        inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+        ansi: AnsiEscapes,
+        tee: Option<Tee>,
+    ) -> Self {
+        Self {
+            inner,
+            stripper: match ansi {
+                AnsiEscapes::Preserve => None,
+                AnsiEscapes::Strip => Some(AnsiStripper::default()),
+            },
+            tee,
+        }
+    }
+}
+
+impl fmt::Debug for FilteredOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilteredOutput")
+            .field("inner", &self.inner)
+            .field("strip_ansi", &self.stripper.is_some())
+            .field("tee", &self.tee.is_some())
+            .finish()
+    }
+}
+
+impl Write for FilteredOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let filtered;
+        let bytes = match self.stripper.as_mut() {
+            Some(stripper) => {
+                filtered = stripper.strip(buf);
+                &filtered[..]
+            }
+            None => buf,
+        };
+
+        // The whole buffer is consumed, even when the escape sequences
+        // leave nothing to write.
+        self.inner.write_all(bytes)?;
+        if let Some(tee) = self.tee.as_ref() {
+            tee.write_all(bytes)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        if let Some(tee) = self.tee.as_ref() {
+            tee.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for FilteredOutput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for FilteredOutput {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl VirtualFile for FilteredOutput {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<(), FsError> {
+        self.inner.set_len(new_size)
+    }
+
+    fn unlink(&mut self) -> Result<(), FsError> {
+        self.inner.unlink()
+    }
+
+    fn sync_to_disk(&self) -> Result<(), FsError> {
+        self.inner.sync_to_disk()
+    }
+
+    fn ioctl(&mut self, request: u32, data: &[u8]) -> Result<Vec<u8>, FsError> {
+        self.inner.ioctl(request, data)
+    }
+
+    fn bytes_available(&self) -> Result<usize, FsError> {
+        self.inner.bytes_available()
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>, FsError> {
+        self.inner.bytes_available_read()
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>, FsError> {
+        self.inner.bytes_available_write()
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn is_readable(&self) -> bool {
+        self.inner.is_readable()
+    }
+
+    fn is_writable(&self) -> bool {
+        self.inner.is_writable()
+    }
+
+    fn is_seekable(&self) -> bool {
+        self.inner.is_seekable()
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+/// Removes the ANSI escape sequences of a stream, which can be split
+/// across writes.
+#[derive(Debug, Default)]
+struct AnsiStripper {
+    state: AnsiState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnsiState {
+    /// Outside of a sequence.
+    Text,
+    /// After `ESC`.
+    Escape,
+    /// In a control sequence, `ESC [`, until its final byte.
+    Csi,
+    /// After the intermediate bytes of `ESC`, until the final byte.
+    Intermediate,
+    /// In a string, like the operating system commands `ESC ]`,
+    /// until `BEL` or `ESC \`.
+    String,
+    /// After `ESC` in a string.
+    StringEscape,
+}
+
+impl Default for AnsiState {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+impl AnsiStripper {
+    fn strip(&mut self, buf: &[u8]) -> Vec<u8> {
+        let mut text = Vec::with_capacity(buf.len());
+
+        for &byte in buf {
+            self.state = match (self.state, byte) {
+                (AnsiState::Text, ESC) => AnsiState::Escape,
+                (AnsiState::Text, _) => {
+                    text.push(byte);
+                    AnsiState::Text
+                }
+
+                (AnsiState::Escape, b'[') => AnsiState::Csi,
+                (AnsiState::Escape, b']' | b'P' | b'X' | b'^' | b'_') => AnsiState::String,
+                (AnsiState::Escape, 0x20..=0x2f) => AnsiState::Intermediate,
+                (AnsiState::Escape, ESC) => AnsiState::Escape,
+                (AnsiState::Escape, _) => AnsiState::Text,
+
+                (AnsiState::Csi, 0x40..=0x7e) => AnsiState::Text,
+                (AnsiState::Csi, _) => AnsiState::Csi,
+
+                (AnsiState::Intermediate, 0x30..=0x7e) => AnsiState::Text,
+                (AnsiState::Intermediate, _) => AnsiState::Intermediate,
+
+                (AnsiState::String, BEL) => AnsiState::Text,
+                (AnsiState::String, ESC) => AnsiState::StringEscape,
+                (AnsiState::String, _) => AnsiState::String,
+
+                (AnsiState::StringEscape, b'\\') => AnsiState::Text,
+                (AnsiState::StringEscape, ESC) => AnsiState::StringEscape,
+                (AnsiState::StringEscape, _) => AnsiState::String,
+            };
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        let mut stripper = AnsiStripper::default();
+
+        assert_eq!(
+            stripper.strip(b"\x1b[1;31merror\x1b[0m: oops\n"),
+            b"error: oops\n"
+        );
+        assert_eq!(
+            stripper.strip(b"\x1b]0;title\x07\x1b]8;;http://a\x1b\\link\x1b(B!"),
+            b"link!"
+        );
+
+        // A sequence split across writes.
+        assert_eq!(stripper.strip(b"a\x1b"), b"a");
+        assert_eq!(stripper.strip(b"[3"), b"");
+        assert_eq!(stripper.strip(b"2mb"), b"b");
+    }
+}
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
 use std::{
     collections::VecDeque,
+    fmt,
     io::{self, Read, Seek, Write},
     sync::{Arc, Mutex},
     time::Duration,
@@ -42,6 +43,12 @@ pub fn fs_error_from_wasi_err(err: Errno) -> FsError {
         Errno::Again => FsError::WouldBlock,
         Errno::Nospc => FsError::WriteZero,
         Errno::Notempty => FsError::DirectoryNotEmpty,
+        Errno::Xdev => FsError::CrossDevice,
+        Errno::Busy => FsError::Busy,
+        Errno::Loop => FsError::SymlinkLoop,
+        Errno::Notsup => FsError::Unsupported,
+        Errno::Dquot => FsError::QuotaExceeded,
+        Errno::Notty => FsError::UnsupportedControl,
         _ => FsError::UnknownError,
     }
 }
@@ -71,6 +78,12 @@ pub fn fs_error_into_wasi_err(fs_error: FsError) -> Errno {
         FsError::WouldBlock => Errno::Again,
         FsError::WriteZero => Errno::Nospc,
         FsError::DirectoryNotEmpty => Errno::Notempty,
+        FsError::CrossDevice => Errno::Xdev,
+        FsError::Busy => Errno::Busy,
+        FsError::SymlinkLoop => Errno::Loop,
+        FsError::Unsupported => Errno::Notsup,
+        FsError::QuotaExceeded => Errno::Dquot,
+        FsError::UnsupportedControl => Errno::Notty,
         FsError::Lock | FsError::UnknownError => Errno::Io,
     }
 }
@@ -102,6 +115,150 @@ pub fn net_error_into_wasi_err(net_error: NetworkError) -> Errno {
     }
 }
 
+/// Translates an I/O error of the host into the closest errno.
+///
+/// The OS error code is used when there's one, then the error wrapped
+/// in `error`, from a virtual file system or network, or an already
+/// translated `HostError`, and finally its kind.
+pub fn io_error_into_wasi_err(error: &io::Error) -> Errno {
+    if let Some(errno) = error.raw_os_error().and_then(os_error_into_wasi_err) {
+        return errno;
+    }
+    if let Some(inner) = error.get_ref() {
+        if let Some(host_error) = inner.downcast_ref::<HostError>() {
+            return host_error.errno();
+        }
+        if let Some(fs_error) = inner.downcast_ref::<FsError>() {
+            return fs_error_into_wasi_err(*fs_error);
+        }
+        if let Some(net_error) = inner.downcast_ref::<NetworkError>() {
+            return net_error_into_wasi_err(*net_error);
+        }
+    }
+
+    match error.kind() {
+        io::ErrorKind::NotFound => Errno::Noent,
+        io::ErrorKind::PermissionDenied => Errno::Perm,
+        io::ErrorKind::ConnectionRefused => Errno::Connrefused,
+        io::ErrorKind::ConnectionReset => Errno::Connreset,
+        io::ErrorKind::ConnectionAborted => Errno::Connaborted,
+        io::ErrorKind::NotConnected => Errno::Notconn,
+        io::ErrorKind::AddrInUse => Errno::Addrinuse,
+        io::ErrorKind::AddrNotAvailable => Errno::Addrnotavail,
+        io::ErrorKind::BrokenPipe => Errno::Pipe,
+        io::ErrorKind::AlreadyExists => Errno::Exist,
+        io::ErrorKind::WouldBlock => Errno::Again,
+        io::ErrorKind::InvalidInput => Errno::Inval,
+        io::ErrorKind::TimedOut => Errno::Timedout,
+        io::ErrorKind::WriteZero => Errno::Nospc,
+        io::ErrorKind::Interrupted => Errno::Intr,
+        io::ErrorKind::Unsupported => Errno::Notsup,
+        io::ErrorKind::OutOfMemory => Errno::Nomem,
+        _ => Errno::Io,
+    }
+}
+
+/// Translates an `errno` of the host.
+#[cfg(unix)]
+fn os_error_into_wasi_err(code: i32) -> Option<Errno> {
+    Some(match code {
+        libc::E2BIG => Errno::Toobig,
+        libc::EACCES => Errno::Access,
+        libc::EADDRINUSE => Errno::Addrinuse,
+        libc::EADDRNOTAVAIL => Errno::Addrnotavail,
+        libc::EAGAIN => Errno::Again,
+        libc::EALREADY => Errno::Already,
+        libc::EBADF => Errno::Badf,
+        libc::EBUSY => Errno::Busy,
+        libc::ECONNABORTED => Errno::Connaborted,
+        libc::ECONNREFUSED => Errno::Connrefused,
+        libc::ECONNRESET => Errno::Connreset,
+        libc::EDQUOT => Errno::Dquot,
+        libc::EEXIST => Errno::Exist,
+        libc::EFAULT => Errno::Fault,
+        libc::EFBIG => Errno::Fbig,
+        libc::EHOSTUNREACH => Errno::Hostunreach,
+        libc::EINPROGRESS => Errno::Inprogress,
+        libc::EINTR => Errno::Intr,
+        libc::EINVAL => Errno::Inval,
+        libc::EIO => Errno::Io,
+        libc::EISCONN => Errno::Isconn,
+        libc::EISDIR => Errno::Isdir,
+        libc::ELOOP => Errno::Loop,
+        libc::EMFILE => Errno::Mfile,
+        libc::EMLINK => Errno::Mlink,
+        libc::ENAMETOOLONG => Errno::Nametoolong,
+        libc::ENETDOWN => Errno::Netdown,
+        libc::ENETUNREACH => Errno::Netunreach,
+        libc::ENFILE => Errno::Nfile,
+        libc::ENODEV => Errno::Nodev,
+        libc::ENOENT => Errno::Noent,
+        libc::ENOMEM => Errno::Nomem,
+        libc::ENOSPC => Errno::Nospc,
+        libc::ENOSYS => Errno::Nosys,
+        libc::ENOTCONN => Errno::Notconn,
+        libc::ENOTDIR => Errno::Notdir,
+        libc::ENOTEMPTY => Errno::Notempty,
+        libc::ENOTSUP => Errno::Notsup,
+        libc::ENOTTY => Errno::Notty,
+        libc::ENXIO => Errno::Nxio,
+        libc::EPERM => Errno::Perm,
+        libc::EPIPE => Errno::Pipe,
+        libc::EROFS => Errno::Rofs,
+        libc::ESPIPE => Errno::Spipe,
+        libc::ETIMEDOUT => Errno::Timedout,
+        libc::ETXTBSY => Errno::Txtbsy,
+        libc::EXDEV => Errno::Xdev,
+        _ => return None,
+    })
+}
+
+/// Translates an `errno` of the host, unknown on this platform.
+#[cfg(not(unix))]
+fn os_error_into_wasi_err(_code: i32) -> Option<Errno> {
+    None
+}
+
+/// An error of the host, preserved along with the errno it was
+/// translated into for the guest.
+#[derive(Debug, Clone)]
+pub struct HostError {
+    errno: Errno,
+    error: Arc<io::Error>,
+}
+
+impl HostError {
+    /// Translates `error`.
+    pub fn new(error: io::Error) -> Self {
+        Self {
+            errno: io_error_into_wasi_err(&error),
+            error: Arc::new(error),
+        }
+    }
+
+    /// The errno returned to the guest.
+    pub fn errno(&self) -> Errno {
+        self.errno
+    }
+
+    /// The original error.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (translated into {})", self.error, self.errno)
+    }
+}
+
+impl std::error::Error for HostError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
 pub fn bus_error_into_wasi_err(bus_error: BusError) -> BusErrno {
     use BusError::*;
     match bus_error {
@@ -448,6 +605,10 @@ impl VirtualFile for Pipe {
         let buffer = self.buffer.lock().unwrap();
         Ok(Some(buffer.len()))
     }
+
+    fn is_seekable(&self) -> bool {
+        false
+    }
 }
 
 /*
@@ -0,0 +1,103 @@
+//! The time zone of the guest, see
+//! [`WasiStateBuilder::timezone`](super::WasiStateBuilder::timezone):
+//! the TZif file of the zone, served from a synthetic
+//! `/usr/share/zoneinfo` holding only that zone and `UTC`.
+
+use super::etc::UTC_LOCALTIME;
+use std::fs;
+use std::path::{Component, Path};
+use wasmer_vfs::synthetic_fs;
+
+/// Where the file system is mounted, for the guest and the host.
+pub(crate) const ZONEINFO_PATH: &str = "/usr/share/zoneinfo";
+
+/// Where the tz database of the host is.
+const HOST_ZONEINFO_PATH: &str = "/usr/share/zoneinfo";
+
+/// A time zone of the tz database, like `Europe/Paris`.
+#[derive(Debug, Clone)]
+pub(crate) struct TimeZone {
+    name: String,
+    tzif: Vec<u8>,
+}
+
+impl TimeZone {
+    /// Loads the time zone `name` from the tz database of the host.
+    /// `UTC` is always available.
+    pub(crate) fn load(name: &str) -> Result<Self, String> {
+        let path = Path::new(name);
+        if name.is_empty()
+            || !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(format!("`{}` is not the name of a time zone", name));
+        }
+
+        let tzif = match fs::read(Path::new(HOST_ZONEINFO_PATH).join(path)) {
+            Ok(tzif) if tzif.starts_with(b"TZif") => tzif,
+            Ok(_) => return Err(format!("`{}` is not a TZif file", name)),
+            Err(_) if name == "UTC" => UTC_LOCALTIME.to_vec(),
+            Err(err) => return Err(format!("failed to read the time zone `{}`: {}", name, err)),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            tzif,
+        })
+    }
+
+    /// The name of the zone, the value of `TZ`.
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The TZif file describing the zone.
+    pub(crate) fn tzif(&self) -> &[u8] {
+        &self.tzif
+    }
+
+    /// Creates the files of `/usr/share/zoneinfo`.
+    pub(crate) fn file_system(&self) -> synthetic_fs::FileSystem {
+        let fs = synthetic_fs::FileSystem::default();
+        let tzif = self.tzif.clone();
+        fs.insert_file(&Path::new("/").join(&self.name), move || tzif.clone())
+            .expect("the file system is empty");
+        if self.name != "UTC" {
+            fs.insert_file(Path::new("/UTC"), || UTC_LOCALTIME.to_vec())
+                .expect("the zone isn't `UTC`");
+        }
+
+        fs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_vfs::FileSystem;
+
+    #[test]
+    fn test_load() {
+        assert!(TimeZone::load("../etc/passwd").is_err());
+        assert!(TimeZone::load("/etc/localtime").is_err());
+        assert!(TimeZone::load("").is_err());
+
+        let utc = TimeZone::load("UTC").unwrap();
+        assert_eq!(utc.name(), "UTC");
+        assert!(utc.tzif().starts_with(b"TZif"));
+    }
+
+    #[test]
+    fn test_file_system() {
+        let zone = TimeZone {
+            name: "Europe/Paris".to_string(),
+            tzif: b"TZif2".to_vec(),
+        };
+        let fs = zone.file_system();
+
+        assert_eq!(fs.metadata(Path::new("/Europe/Paris")).unwrap().len, 5);
+        assert!(fs.metadata(Path::new("/UTC")).unwrap().is_file());
+        assert!(fs.metadata(Path::new("/Europe/London")).is_err());
+    }
+}
@@ -30,27 +30,30 @@ use self::types::{
         Dirent, Errno, Event, EventEnum, EventFdReadwrite, Eventrwflags, Eventtype, Fd as WasiFd,
         Fdflags, Fdstat, Filesize, Filestat, Filetype, Fstflags, Linkcount, OptionFd, Pid, Prestat,
         Rights, Snapshot0Clockid, Sockoption, Sockstatus, Socktype, StdioMode as WasiStdioMode,
-        Streamsecurity, Subscription, SubscriptionEnum, SubscriptionFsReadwrite, Tid, Timestamp,
-        Tty, Whence,
+        Streamsecurity, Subclockflags, Subscription, SubscriptionClock, SubscriptionEnum,
+        SubscriptionFsReadwrite, Tid, Timestamp, Tty, Whence,
     },
     *,
 };
-use crate::state::{bus_error_into_wasi_err, wasi_error_into_bus_err, InodeHttpSocketType};
+use crate::state::{
+    bus_error_into_wasi_err, wasi_error_into_bus_err, InodeHttpSocketType, StringList,
+};
 use crate::utils::map_io_err;
 use crate::WasiBusProcessId;
 use crate::{
     mem_error_to_wasi,
     state::{
         self, fs_error_into_wasi_err, iterate_poll_events, net_error_into_wasi_err, poll,
-        virtual_file_type_to_wasi_file_type, Inode, InodeSocket, InodeSocketKind, InodeVal, Kind,
-        PollEvent, PollEventBuilder, WasiPipe, WasiState, MAX_SYMLINKS,
+        virtual_file_type_to_wasi_file_type, HostError, Inode, InodeSocket, InodeSocketKind,
+        InodeVal, Kind, PendingOperationKind, PollEvent, PollEventBuilder, WasiPipe, WasiState,
+        MAX_SYMLINKS,
     },
     Fd, WasiEnv, WasiError, WasiThread, WasiThreadId,
 };
 use bytes::Bytes;
 use std::borrow::{Borrow, Cow};
 use std::convert::{Infallible, TryInto};
-use std::io::{self, Read, Seek, Write};
+use std::io::{self, IoSlice, IoSliceMut, Read, Seek, Write};
 use std::mem::transmute;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::{Deref, DerefMut};
@@ -64,7 +67,7 @@ use wasmer::{
     MemorySize, MemoryView, Module, RuntimeError, Value, WasmPtr, WasmSlice,
 };
 use wasmer_vbus::{FileDescriptor, StdioMode};
-use wasmer_vfs::{FsError, VirtualFile};
+use wasmer_vfs::{FileMapping, FsError, VirtualFile};
 use wasmer_vnet::{SocketHttpRequest, StreamSecurity};
 
 #[cfg(any(
@@ -91,21 +94,123 @@ fn from_offset<M: MemorySize>(offset: M::Offset) -> Result<usize, Errno> {
     Ok(ret)
 }
 
+/// Reads or writes `inner`, remembering its errors as the last errors
+/// of the host in `env`.
+struct HostIo<'a, T> {
+    env: &'a WasiEnv,
+    inner: T,
+}
+
+impl<'a, T> HostIo<'a, T> {
+    fn new(env: &'a WasiEnv, inner: T) -> Self {
+        Self { env, inner }
+    }
+
+    /// Records `error`, returning an error translated into the same
+    /// errno.
+    fn record(&self, error: std::io::Error) -> std::io::Error {
+        let error = HostError::new(error);
+        self.env.record_host_error(error.clone());
+        std::io::Error::new(error.error().kind(), error)
+    }
+}
+
+impl<T: Read> Read for HostIo<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf).map_err(|err| self.record(err))
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+        self.inner
+            .read_vectored(bufs)
+            .map_err(|err| self.record(err))
+    }
+}
+
+impl<T: Write> Write for HostIo<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf).map_err(|err| self.record(err))
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        self.inner
+            .write_vectored(bufs)
+            .map_err(|err| self.record(err))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush().map_err(|err| self.record(err))
+    }
+}
+
+/// Reads or writes `file` from `offset`, without moving its cursor.
+struct PositionedIo<'a> {
+    file: &'a mut (dyn VirtualFile + Send + Sync + 'static),
+    offset: u64,
+}
+
+impl<'a> PositionedIo<'a> {
+    fn new(file: &'a mut (dyn VirtualFile + Send + Sync + 'static), offset: u64) -> Self {
+        Self { file, offset }
+    }
+}
+
+impl Read for PositionedIo<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.file.read_at(self.offset, buf)?;
+        self.offset += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Write for PositionedIo<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.file.write_at(self.offset, buf)?;
+        self.offset += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
 fn write_bytes_inner<T: Write, M: MemorySize>(
     mut write_loc: T,
     memory: &MemoryView,
     iovs_arr_cell: WasmSlice<__wasi_ciovec_t<M>>,
 ) -> Result<usize, Errno> {
-    let mut bytes_written = 0usize;
+    let mut buffers = Vec::with_capacity(iovs_arr_cell.len() as usize);
     for iov in iovs_arr_cell.iter() {
         let iov_inner = iov.read().map_err(mem_error_to_wasi)?;
         let bytes = WasmPtr::<u8, M>::new(iov_inner.buf)
             .slice(memory, iov_inner.buf_len)
             .map_err(mem_error_to_wasi)?;
-        let bytes = bytes.read_to_vec().map_err(mem_error_to_wasi)?;
-        write_loc.write_all(&bytes).map_err(map_io_err)?;
+        buffers.push(bytes.read_to_vec().map_err(mem_error_to_wasi)?);
+    }
+
+    // All the buffers are handed to the writer at once, then what
+    // it didn't write is handed again.
+    let to_write = buffers.iter().map(|buffer| buffer.len()).sum::<usize>();
+    let mut bytes_written = 0usize;
+    while bytes_written < to_write {
+        let mut skip = bytes_written;
+        let mut bufs = Vec::with_capacity(buffers.len());
+        for buffer in &buffers {
+            if skip >= buffer.len() {
+                skip -= buffer.len();
+                continue;
+            }
+            bufs.push(IoSlice::new(&buffer[skip..]));
+            skip = 0;
+        }
 
-        bytes_written += from_offset::<M>(iov_inner.buf_len)?;
+        match write_loc.write_vectored(&bufs).map_err(map_io_err)? {
+            0 => return Err(map_io_err(std::io::ErrorKind::WriteZero.into())),
+            written => bytes_written += written,
+        }
     }
     Ok(bytes_written)
 }
@@ -125,27 +230,78 @@ pub(crate) fn read_bytes<T: Read, M: MemorySize>(
     memory: &MemoryView,
     iovs_arr: WasmSlice<__wasi_iovec_t<M>>,
 ) -> Result<usize, Errno> {
+    let mut iovs = Vec::with_capacity(iovs_arr.len() as usize);
+    let mut buffers = Vec::with_capacity(iovs_arr.len() as usize);
+    for iov in iovs_arr.iter() {
+        let iov_inner = iov.read().map_err(mem_error_to_wasi)?;
+        buffers.push(vec![0; from_offset::<M>(iov_inner.buf_len)?]);
+        iovs.push(iov_inner);
+    }
+
+    // All the buffers are handed to the reader at once. Readers
+    // filling only some of them are handed the next ones, until a
+    // buffer isn't filled.
     let mut bytes_read = 0usize;
+    let mut filled = 0;
+    while filled < buffers.len() {
+        let mut bufs = buffers[filled..]
+            .iter_mut()
+            .map(|buffer| IoSliceMut::new(buffer))
+            .collect::<Vec<_>>();
+        let has_read = reader.read_vectored(&mut bufs).map_err(map_io_err)?;
+        bytes_read += has_read;
+
+        let mut rest = has_read;
+        while filled < buffers.len() && rest >= buffers[filled].len() {
+            rest -= buffers[filled].len();
+            filled += 1;
+        }
+        if has_read == 0 || rest > 0 {
+            break;
+        }
+    }
+
+    let mut to_copy = bytes_read;
+    for (iov_inner, buffer) in iovs.iter().zip(&buffers) {
+        if to_copy == 0 {
+            break;
+        }
+        let copied = to_copy.min(buffer.len());
+        let buf = WasmPtr::<u8, M>::new(iov_inner.buf)
+            .slice(memory, to_offset::<M>(copied)?)
+            .map_err(mem_error_to_wasi)?;
+        buf.write_slice(&buffer[..copied])
+            .map_err(mem_error_to_wasi)?;
+        to_copy -= copied;
+    }
+    Ok(bytes_read)
+}
 
-    // We allocate the raw_bytes first once instead of
-    // N times in the loop.
-    let mut raw_bytes: Vec<u8> = vec![0; 1024];
+/// Reads the mapped contents of a file from `offset` into the
+/// buffers, copying each part straight into the memory.
+pub(crate) fn read_mapped<M: MemorySize>(
+    mapping: &FileMapping,
+    offset: u64,
+    memory: &MemoryView,
+    iovs_arr: WasmSlice<__wasi_iovec_t<M>>,
+) -> Result<usize, Errno> {
+    let mut offset: usize = offset.try_into().unwrap_or(usize::MAX);
+    let mut bytes_read = 0usize;
 
     for iov in iovs_arr.iter() {
+        if offset >= mapping.len() {
+            break;
+        }
         let iov_inner = iov.read().map_err(mem_error_to_wasi)?;
-        raw_bytes.clear();
         let to_read = from_offset::<M>(iov_inner.buf_len)?;
-        raw_bytes.resize(to_read, 0);
-        let has_read = reader.read(&mut raw_bytes).map_err(map_io_err)?;
+        let bytes = mapping.get(offset..offset.saturating_add(to_read));
 
         let buf = WasmPtr::<u8, M>::new(iov_inner.buf)
-            .slice(memory, iov_inner.buf_len)
+            .slice(memory, to_offset::<M>(bytes.len())?)
             .map_err(mem_error_to_wasi)?;
-        buf.write_slice(&raw_bytes).map_err(mem_error_to_wasi)?;
-        bytes_read += has_read;
-        if has_read != to_read {
-            return Ok(bytes_read);
-        }
+        buf.write_slice(&bytes).map_err(mem_error_to_wasi)?;
+        offset += bytes.len();
+        bytes_read += bytes.len();
     }
     Ok(bytes_read)
 }
@@ -257,36 +413,51 @@ where
 }
 
 #[must_use]
-fn write_buffer_array<M: MemorySize>(
+/// Writes the pointers to the strings of `list`, and their data, in
+/// the guest memory.
+fn write_string_list<M: MemorySize>(
     memory: &MemoryView,
-    from: &[Vec<u8>],
+    list: &mut StringList,
     ptr_buffer: WasmPtr<WasmPtr<u8, M>, M>,
     buffer: WasmPtr<u8, M>,
 ) -> Errno {
-    let ptrs = wasi_try_mem!(ptr_buffer.slice(memory, wasi_try!(to_offset::<M>(from.len()))));
+    let encoded = list.encoded();
+    let ptrs =
+        wasi_try_mem!(ptr_buffer.slice(memory, wasi_try!(to_offset::<M>(encoded.offsets.len()))));
 
-    let mut current_buffer_offset = 0usize;
-    for ((i, sub_buffer), ptr) in from.iter().enumerate().zip(ptrs.iter()) {
-        trace!("ptr: {:?}, subbuffer: {:?}", ptr, sub_buffer);
+    for (offset, ptr) in encoded.offsets.iter().zip(ptrs.iter()) {
         let mut buf_offset = buffer.offset();
-        buf_offset += wasi_try!(to_offset::<M>(current_buffer_offset));
-        let new_ptr = WasmPtr::new(buf_offset);
-        wasi_try_mem!(ptr.write(new_ptr));
-
-        let data =
-            wasi_try_mem!(new_ptr.slice(memory, wasi_try!(to_offset::<M>(sub_buffer.len()))));
-        wasi_try_mem!(data.write_slice(sub_buffer));
-        wasi_try_mem!(wasi_try_mem!(
-            new_ptr.add_offset(wasi_try!(to_offset::<M>(sub_buffer.len())))
-        )
-        .write(memory, 0));
-
-        current_buffer_offset += sub_buffer.len() + 1;
+        buf_offset += wasi_try!(to_offset::<M>(*offset));
+        trace!("ptr: {:?}, offset: {:?}", ptr, buf_offset);
+        wasi_try_mem!(ptr.write(WasmPtr::new(buf_offset)));
     }
 
+    let data = wasi_try_mem!(buffer.slice(memory, wasi_try!(to_offset::<M>(encoded.buffer.len()))));
+    wasi_try_mem!(data.write_slice(&encoded.buffer));
+
     Errno::Success
 }
 
+/// Writes the number of strings of `list` and the size of their data
+/// in the guest memory.
+fn write_string_list_sizes<M: MemorySize>(
+    memory: &MemoryView,
+    list: &mut StringList,
+    count: WasmPtr<M::Offset, M>,
+    buffer_size: WasmPtr<M::Offset, M>,
+) -> Result<(M::Offset, M::Offset), Errno> {
+    let encoded = list.encoded();
+
+    let count_val: M::Offset = to_offset::<M>(encoded.offsets.len())?;
+    let buffer_size_val: M::Offset = to_offset::<M>(encoded.buffer.len())?;
+    count.write(memory, count_val).map_err(mem_error_to_wasi)?;
+    buffer_size
+        .write(memory, buffer_size_val)
+        .map_err(mem_error_to_wasi)?;
+
+    Ok((count_val, buffer_size_val))
+}
+
 fn get_current_time_in_nanos() -> Result<Timestamp, Errno> {
     let now = std::time::SystemTime::now();
     let duration = now
@@ -311,14 +482,14 @@ pub fn args_get<M: MemorySize>(
 ) -> Errno {
     debug!("wasi::args_get");
     let env = ctx.data();
-    let (memory, mut state) = env.get_memory_and_wasi_state(&ctx, 0);
+    let (memory, state) = env.get_memory_and_wasi_state(&ctx, 0);
+    let mut args = state.args.lock().unwrap();
 
-    let result = write_buffer_array(&memory, &state.args, argv, argv_buf);
+    let result = write_string_list(&memory, &mut args, argv, argv_buf);
 
     debug!(
         "=> args:\n{}",
-        state
-            .args
+        args.strings()
             .iter()
             .enumerate()
             .map(|(i, v)| format!("{:>20}: {}", i, ::std::str::from_utf8(v).unwrap()))
@@ -343,17 +514,14 @@ pub fn args_sizes_get<M: MemorySize>(
 ) -> Errno {
     debug!("wasi::args_sizes_get");
     let env = ctx.data();
-    let (memory, mut state) = env.get_memory_and_wasi_state(&ctx, 0);
-
-    let argc = argc.deref(&memory);
-    let argv_buf_size = argv_buf_size.deref(&memory);
+    let (memory, state) = env.get_memory_and_wasi_state(&ctx, 0);
 
-    let argc_val: M::Offset = wasi_try!(state.args.len().try_into().map_err(|_| Errno::Overflow));
-    let argv_buf_size_val: usize = state.args.iter().map(|v| v.len() + 1).sum();
-    let argv_buf_size_val: M::Offset =
-        wasi_try!(argv_buf_size_val.try_into().map_err(|_| Errno::Overflow));
-    wasi_try_mem!(argc.write(argc_val));
-    wasi_try_mem!(argv_buf_size.write(argv_buf_size_val));
+    let (argc_val, argv_buf_size_val) = wasi_try!(write_string_list_sizes(
+        &memory,
+        &mut state.args.lock().unwrap(),
+        argc,
+        argv_buf_size
+    ));
 
     debug!("=> argc={}, argv_buf_size={}", argc_val, argv_buf_size_val);
 
@@ -382,6 +550,7 @@ pub fn clock_res_get<M: MemorySize>(
         Snapshot0Clockid::from(clock_id),
         out_addr
     ));
+    let t_out = env.state.guest_resolution(t_out);
     wasi_try_mem!(resolution.write(&memory, t_out as Timestamp));
     Errno::Success
 }
@@ -409,10 +578,9 @@ pub fn clock_time_get<M: MemorySize>(
     let env = ctx.data();
     let memory = env.memory_view(&ctx);
 
-    let t_out = wasi_try!(platform_clock_time_get(
-        Snapshot0Clockid::from(clock_id),
-        precision
-    ));
+    let clock_id = Snapshot0Clockid::from(clock_id);
+    let t_out = wasi_try!(platform_clock_time_get(clock_id, precision));
+    let t_out = env.state.guest_time(clock_id, t_out);
     wasi_try_mem!(time.write(&memory, t_out as Timestamp));
 
     let result = Errno::Success;
@@ -442,10 +610,11 @@ pub fn environ_get<M: MemorySize>(
         environ, environ_buf
     );
     let env = ctx.data();
-    let (memory, mut state) = env.get_memory_and_wasi_state(&ctx, 0);
-    trace!(" -> State envs: {:?}", state.envs);
+    let (memory, state) = env.get_memory_and_wasi_state(&ctx, 0);
+    let mut envs = state.envs.lock().unwrap();
+    trace!(" -> State envs: {:?}", envs.strings());
 
-    write_buffer_array(&memory, &state.envs, environ, environ_buf)
+    write_string_list(&memory, &mut envs, environ, environ_buf)
 }
 
 /// ### `environ_sizes_get()`
@@ -462,17 +631,14 @@ pub fn environ_sizes_get<M: MemorySize>(
 ) -> Errno {
     trace!("wasi::environ_sizes_get");
     let env = ctx.data();
-    let (memory, mut state) = env.get_memory_and_wasi_state(&ctx, 0);
-
-    let environ_count = environ_count.deref(&memory);
-    let environ_buf_size = environ_buf_size.deref(&memory);
+    let (memory, state) = env.get_memory_and_wasi_state(&ctx, 0);
 
-    let env_var_count: M::Offset =
-        wasi_try!(state.envs.len().try_into().map_err(|_| Errno::Overflow));
-    let env_buf_size: usize = state.envs.iter().map(|v| v.len() + 1).sum();
-    let env_buf_size: M::Offset = wasi_try!(env_buf_size.try_into().map_err(|_| Errno::Overflow));
-    wasi_try_mem!(environ_count.write(env_var_count));
-    wasi_try_mem!(environ_buf_size.write(env_buf_size));
+    let (env_var_count, env_buf_size) = wasi_try!(write_string_list_sizes(
+        &memory,
+        &mut state.envs.lock().unwrap(),
+        environ_count,
+        environ_buf_size
+    ));
 
     trace!(
         "env_var_count: {}, env_buf_size: {}",
@@ -533,13 +699,16 @@ pub fn fd_allocate(
         return Errno::Access;
     }
     let new_size = wasi_try!(offset.checked_add(len).ok_or(Errno::Inval));
-    {
-        let mut guard = inodes.arena[inode].write();
+    // The allocation never shrinks the file.
+    let new_size = {
+        let mut guard = wasi_try!(inodes.get_inodeval(inode)).write();
         let deref_mut = guard.deref_mut();
         match deref_mut {
             Kind::File { handle, .. } => {
                 if let Some(handle) = handle {
+                    let new_size = new_size.max(handle.size());
                     wasi_try!(handle.set_len(new_size).map_err(fs_error_into_wasi_err));
+                    new_size
                 } else {
                     return Errno::Badf;
                 }
@@ -547,14 +716,20 @@ pub fn fd_allocate(
             Kind::Socket { .. } => return Errno::Badf,
             Kind::Pipe { .. } => return Errno::Badf,
             Kind::Buffer { buffer } => {
+                let new_size = new_size.max(buffer.len() as u64);
                 buffer.resize(new_size as usize, 0);
+                new_size
             }
             Kind::Symlink { .. } => return Errno::Badf,
             Kind::EventNotifications { .. } => return Errno::Badf,
             Kind::Dir { .. } | Kind::Root { .. } => return Errno::Isdir,
         }
-    }
-    inodes.arena[inode].stat.write().unwrap().st_size = new_size;
+    };
+    wasi_try!(inodes.get_inodeval(inode))
+        .stat
+        .write()
+        .unwrap()
+        .st_size = new_size;
     debug!("New file size: {}", new_size);
 
     Errno::Success
@@ -740,7 +915,7 @@ pub fn fd_filestat_set_size(
     }
 
     {
-        let mut guard = inodes.arena[inode].write();
+        let mut guard = wasi_try!(inodes.get_inodeval(inode)).write();
         let deref_mut = guard.deref_mut();
         match deref_mut {
             Kind::File { handle, .. } => {
@@ -760,7 +935,11 @@ pub fn fd_filestat_set_size(
             Kind::Dir { .. } | Kind::Root { .. } => return Errno::Isdir,
         }
     }
-    inodes.arena[inode].stat.write().unwrap().st_size = st_size;
+    wasi_try!(inodes.get_inodeval(inode))
+        .stat
+        .write()
+        .unwrap()
+        .st_size = st_size;
 
     Errno::Success
 }
@@ -797,29 +976,50 @@ pub fn fd_filestat_set_times(
     }
 
     let inode_idx = fd_entry.inode;
-    let inode = &inodes.arena[inode_idx];
+    let inode = wasi_try!(inodes.get_inodeval(inode_idx));
+    let (accessed, modified) = wasi_try!(times_to_set(st_atim, st_mtim, fst_flags));
 
-    if fst_flags.contains(Fstflags::SET_ATIM) || fst_flags.contains(Fstflags::SET_ATIM_NOW) {
-        let time_to_set = if fst_flags.contains(Fstflags::SET_ATIM) {
-            st_atim
-        } else {
-            wasi_try!(get_current_time_in_nanos())
-        };
-        inode.stat.write().unwrap().st_atim = time_to_set;
+    {
+        let guard = inode.read();
+        wasi_try!(state
+            .fs
+            .set_times_for_kind(guard.deref(), accessed, modified));
     }
 
-    if fst_flags.contains(Fstflags::SET_MTIM) || fst_flags.contains(Fstflags::SET_MTIM_NOW) {
-        let time_to_set = if fst_flags.contains(Fstflags::SET_MTIM) {
-            st_mtim
-        } else {
-            wasi_try!(get_current_time_in_nanos())
-        };
-        inode.stat.write().unwrap().st_mtim = time_to_set;
+    let mut stat = inode.stat.write().unwrap();
+    if let Some(accessed) = accessed {
+        stat.st_atim = accessed;
+    }
+    if let Some(modified) = modified {
+        stat.st_mtim = modified;
     }
 
     Errno::Success
 }
 
+/// Returns the access and modification times to set according to
+/// `fst_flags`, `None` leaving the time unchanged.
+fn times_to_set(
+    st_atim: Timestamp,
+    st_mtim: Timestamp,
+    fst_flags: Fstflags,
+) -> Result<(Option<Timestamp>, Option<Timestamp>), Errno> {
+    let time_to_set = |time, set, set_now| {
+        if fst_flags.contains(set) {
+            Ok(Some(time))
+        } else if fst_flags.contains(set_now) {
+            get_current_time_in_nanos().map(Some)
+        } else {
+            Ok(None)
+        }
+    };
+
+    Ok((
+        time_to_set(st_atim, Fstflags::SET_ATIM, Fstflags::SET_ATIM_NOW)?,
+        time_to_set(st_mtim, Fstflags::SET_MTIM, Fstflags::SET_MTIM_NOW)?,
+    ))
+}
+
 /// ### `fd_pread()`
 /// Read from the file at the given offset without updating the file cursor.
 /// This acts like a stateless version of Seek + Read
@@ -860,7 +1060,7 @@ pub fn fd_pread<M: MemorySize>(
                 env
             );
             if let Some(ref mut stdin) = guard.deref_mut() {
-                wasi_try_ok!(read_bytes(stdin, &memory, iovs), env)
+                wasi_try_ok!(read_bytes(HostIo::new(env, stdin), &memory, iovs), env)
             } else {
                 return Ok(Errno::Badf);
             }
@@ -877,17 +1077,23 @@ pub fn fd_pread<M: MemorySize>(
                 );
                 return Ok(Errno::Access);
             }
-            let mut guard = inodes.arena[inode].write();
+            let mut guard = wasi_try_ok!(inodes.get_inodeval(inode), env).write();
             let deref_mut = guard.deref_mut();
             match deref_mut {
                 Kind::File { handle, .. } => {
                     if let Some(h) = handle {
-                        wasi_try_ok!(
-                            h.seek(std::io::SeekFrom::Start(offset as u64))
-                                .map_err(map_io_err),
-                            env
-                        );
-                        wasi_try_ok!(read_bytes(h, &memory, iovs), env)
+                        if let Some(mapping) = h.map() {
+                            wasi_try_ok!(read_mapped(&mapping, offset, &memory, iovs), env)
+                        } else {
+                            wasi_try_ok!(
+                                read_bytes(
+                                    HostIo::new(env, PositionedIo::new(h.as_mut(), offset)),
+                                    &memory,
+                                    iovs
+                                ),
+                                env
+                            )
+                        }
                     } else {
                         return Ok(Errno::Inval);
                     }
@@ -1031,7 +1237,10 @@ pub fn fd_pwrite<M: MemorySize>(
                 env
             );
             if let Some(ref mut stdout) = guard.deref_mut() {
-                wasi_try_ok!(write_bytes(stdout, &memory, iovs_arr), env)
+                wasi_try_ok!(
+                    write_bytes(HostIo::new(env, stdout), &memory, iovs_arr),
+                    env
+                )
             } else {
                 return Ok(Errno::Badf);
             }
@@ -1044,7 +1253,10 @@ pub fn fd_pwrite<M: MemorySize>(
                 env
             );
             if let Some(ref mut stderr) = guard.deref_mut() {
-                wasi_try_ok!(write_bytes(stderr, &memory, iovs_arr), env)
+                wasi_try_ok!(
+                    write_bytes(HostIo::new(env, stderr), &memory, iovs_arr),
+                    env
+                )
             } else {
                 return Ok(Errno::Badf);
             }
@@ -1055,7 +1267,7 @@ pub fn fd_pwrite<M: MemorySize>(
             }
 
             let inode_idx = fd_entry.inode;
-            let inode = &inodes.arena[inode_idx];
+            let inode = wasi_try_ok!(inodes.get_inodeval(inode_idx), env);
 
             let mut guard = inode.write();
             let deref_mut = guard.deref_mut();
@@ -1063,12 +1275,13 @@ pub fn fd_pwrite<M: MemorySize>(
                 Kind::File { handle, .. } => {
                     if let Some(handle) = handle {
                         wasi_try_ok!(
-                            handle
-                                .seek(std::io::SeekFrom::Start(offset as u64))
-                                .map_err(map_io_err),
+                            write_bytes(
+                                HostIo::new(env, PositionedIo::new(handle.as_mut(), offset)),
+                                &memory,
+                                iovs_arr
+                            ),
                             env
-                        );
-                        wasi_try_ok!(write_bytes(handle, &memory, iovs_arr), env)
+                        )
                     } else {
                         return Ok(Errno::Inval);
                     }
@@ -1139,7 +1352,7 @@ pub fn fd_read<M: MemorySize>(
                 env
             );
             if let Some(ref mut stdin) = guard.deref_mut() {
-                wasi_try_ok!(read_bytes(stdin, &memory, iovs_arr), env)
+                wasi_try_ok!(read_bytes(HostIo::new(env, stdin), &memory, iovs_arr), env)
             } else {
                 return Ok(Errno::Badf);
             }
@@ -1154,7 +1367,7 @@ pub fn fd_read<M: MemorySize>(
             let is_non_blocking = fd_entry.flags.contains(Fdflags::NONBLOCK);
             let offset = fd_entry.offset as usize;
             let inode_idx = fd_entry.inode;
-            let inode = &inodes.arena[inode_idx];
+            let inode = wasi_try_ok!(inodes.get_inodeval(inode_idx), env);
 
             let bytes_read = {
                 let mut guard = inode.write();
@@ -1162,13 +1375,23 @@ pub fn fd_read<M: MemorySize>(
                 match deref_mut {
                     Kind::File { handle, .. } => {
                         if let Some(handle) = handle {
-                            wasi_try_ok!(
-                                handle
-                                    .seek(std::io::SeekFrom::Start(offset as u64))
-                                    .map_err(map_io_err),
-                                env
-                            );
-                            wasi_try_ok!(read_bytes(handle, &memory, iovs_arr), env)
+                            if let Some(mapping) = handle.map() {
+                                wasi_try_ok!(
+                                    read_mapped(&mapping, offset as u64, &memory, iovs_arr),
+                                    env
+                                )
+                            } else {
+                                wasi_try_ok!(
+                                    handle
+                                        .seek(std::io::SeekFrom::Start(offset as u64))
+                                        .map_err(|err| env.host_io_error(err)),
+                                    env
+                                );
+                                wasi_try_ok!(
+                                    read_bytes(HostIo::new(env, handle), &memory, iovs_arr),
+                                    env
+                                )
+                            }
                         } else {
                             return Ok(Errno::Inval);
                         }
@@ -1200,6 +1423,7 @@ pub fn fd_read<M: MemorySize>(
                             guard.push_front(tx);
                         }
 
+                        let operation = env.begin_operation(Some(fd), PendingOperationKind::Read);
                         let ret;
                         loop {
                             let val = counter.load(Ordering::Acquire);
@@ -1230,6 +1454,10 @@ pub fn fd_read<M: MemorySize>(
                                 return Ok(Errno::Again);
                             }
 
+                            if operation.is_cancelled() {
+                                return Ok(Errno::Intr);
+                            }
+
                             // Yield for a fixed period of time and then check again
                             env.yield_now()?;
                             if rx.recv_timeout(Duration::from_millis(5)).is_err() {
@@ -1486,6 +1714,52 @@ pub fn fd_event<M: MemorySize>(
     Errno::Success
 }
 
+/// ### `fd_ioctl()`
+/// Sends a control request to a file, in the manner of `ioctl`: a
+/// terminal, a device or a resource registered by the embedder
+/// defines the requests it understands
+/// Inputs:
+/// - `Fd fd`
+///   The file the request is sent to
+/// - `u32 request`
+///   The request, whose meaning depends on the file
+/// - `u8 *buf`
+///   The data of the request, replaced with the data of the response
+/// - `M::Offset buf_len`
+///   The length of the data of the request, and the maximum length of
+///   the data of the response
+/// Output:
+/// - `M::Offset *ret_len`
+///   The length of the data of the response. If it doesn't fit in the
+///   buffer, `Errno::Overflow` is returned and the buffer is left as is
+pub fn fd_ioctl<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    request: u32,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    ret_len: WasmPtr<M::Offset, M>,
+) -> Errno {
+    debug!("wasi::fd_ioctl: fd={}, request={:#x}", fd, request);
+
+    let env = ctx.data();
+    let (memory, state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
+    let buf = wasi_try_mem!(buf.slice(&memory, buf_len));
+    let data = wasi_try_mem!(buf.read_to_vec());
+
+    let response = wasi_try!(state.fs.control_resource(&inodes, fd, request, &data));
+    let response_len: M::Offset = wasi_try!(response.len().try_into().map_err(|_| Errno::Overflow));
+    wasi_try_mem!(ret_len.write(&memory, response_len));
+    if response.len() > data.len() {
+        return Errno::Overflow;
+    }
+    wasi_try_mem!(buf
+        .subslice(0..response.len() as u64)
+        .write_slice(&response));
+
+    Errno::Success
+}
+
 /// ### `fd_seek()`
 /// Update file descriptor offset
 /// Inputs:
@@ -1525,13 +1799,17 @@ pub fn fd_seek<M: MemorySize>(
         Whence::End => {
             use std::io::SeekFrom;
             let inode_idx = fd_entry.inode;
-            let mut guard = inodes.arena[inode_idx].write();
+            let mut guard = wasi_try_ok!(inodes.get_inodeval(inode_idx), env).write();
             let deref_mut = guard.deref_mut();
             match deref_mut {
                 Kind::File { ref mut handle, .. } => {
                     if let Some(handle) = handle {
-                        let end =
-                            wasi_try_ok!(handle.seek(SeekFrom::End(0)).map_err(map_io_err), env);
+                        let end = wasi_try_ok!(
+                            handle
+                                .seek(SeekFrom::End(0))
+                                .map_err(|err| env.host_io_error(err)),
+                            env
+                        );
 
                         // TODO: handle case if fd_entry.offset uses 64 bits of a u64
                         drop(guard);
@@ -1596,7 +1874,7 @@ pub fn fd_sync(ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd) -> Errno {
 
     // TODO: implement this for more than files
     {
-        let mut guard = inodes.arena[inode].write();
+        let mut guard = wasi_try!(inodes.get_inodeval(inode)).write();
         let deref_mut = guard.deref_mut();
         match deref_mut {
             Kind::File { handle, .. } => {
@@ -1685,7 +1963,10 @@ pub fn fd_write<M: MemorySize>(
                 env
             );
             if let Some(ref mut stdout) = guard.deref_mut() {
-                wasi_try_ok!(write_bytes(stdout, &memory, iovs_arr), env)
+                wasi_try_ok!(
+                    write_bytes(HostIo::new(env, stdout), &memory, iovs_arr),
+                    env
+                )
             } else {
                 return Ok(Errno::Badf);
             }
@@ -1698,7 +1979,10 @@ pub fn fd_write<M: MemorySize>(
                 env
             );
             if let Some(ref mut stderr) = guard.deref_mut() {
-                wasi_try_ok!(write_bytes(stderr, &memory, iovs_arr), env)
+                wasi_try_ok!(
+                    write_bytes(HostIo::new(env, stderr), &memory, iovs_arr),
+                    env
+                )
             } else {
                 return Ok(Errno::Badf);
             }
@@ -1710,7 +1994,7 @@ pub fn fd_write<M: MemorySize>(
 
             let offset = fd_entry.offset as usize;
             let inode_idx = fd_entry.inode;
-            let inode = &inodes.arena[inode_idx];
+            let inode = wasi_try_ok!(inodes.get_inodeval(inode_idx), env);
 
             let bytes_written = {
                 let mut guard = inode.write();
@@ -1721,10 +2005,13 @@ pub fn fd_write<M: MemorySize>(
                             wasi_try_ok!(
                                 handle
                                     .seek(std::io::SeekFrom::Start(offset as u64))
-                                    .map_err(map_io_err),
+                                    .map_err(|err| env.host_io_error(err)),
                                 env
                             );
-                            wasi_try_ok!(write_bytes(handle, &memory, iovs_arr), env)
+                            wasi_try_ok!(
+                                write_bytes(HostIo::new(env, handle), &memory, iovs_arr),
+                                env
+                            )
                         } else {
                             return Ok(Errno::Inval);
                         }
@@ -1833,6 +2120,29 @@ pub fn fd_pipe<M: MemorySize>(
     Errno::Success
 }
 
+/// Passes the path accessed by a path-based syscall to the audit hook
+/// of the file system, if any.
+fn audit_path<M: MemorySize>(
+    ctx: &FunctionEnvMut<'_, WasiEnv>,
+    syscall: &'static str,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    errno: Errno,
+) {
+    let env = ctx.data();
+    if !env.state.fs.is_audited() {
+        return;
+    }
+
+    let (memory, state, inodes) = env.get_memory_and_wasi_state_and_inodes(ctx, 0);
+    if let Ok(path) = unsafe { path.read_utf8_string(&memory, path_len) } {
+        state
+            .fs
+            .audit_path(inodes.deref(), syscall, fd, &path, errno);
+    }
+}
+
 /// ### `path_create_directory()`
 /// Create directory at a path
 /// Inputs:
@@ -1847,6 +2157,17 @@ pub fn fd_pipe<M: MemorySize>(
 /// - Rights::PATH_CREATE_DIRECTORY
 ///     This right must be set on the directory that the file is created in (TODO: verify that this is true)
 pub fn path_create_directory<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Errno {
+    let ret = path_create_directory_internal(ctx.as_mut(), fd, path, path_len);
+    audit_path(&ctx, "path_create_directory", fd, path, path_len, ret);
+    ret
+}
+
+fn path_create_directory_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     fd: WasiFd,
     path: WasmPtr<u8, M>,
@@ -1961,6 +2282,15 @@ pub fn path_create_directory<M: MemorySize>(
     Errno::Success
 }
 
+/// Refreshes the snapshot the `/proc` files are generated from, if
+/// it's mounted. The inodes must not be locked.
+fn refresh_proc(ctx: &FunctionEnvMut<'_, WasiEnv>) {
+    let env = ctx.data();
+    if let Some(proc) = env.state.fs.proc.as_ref() {
+        proc.refresh(env, ctx);
+    }
+}
+
 /// ### `path_filestat_get()`
 /// Access metadata about a file or directory
 /// Inputs:
@@ -1984,19 +2314,38 @@ pub fn path_filestat_get<M: MemorySize>(
     buf: WasmPtr<Filestat, M>,
 ) -> Errno {
     debug!("wasi::path_filestat_get (fd={})", fd);
+    refresh_proc(&ctx);
     let env = ctx.data();
     let (memory, mut state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(&ctx, 0);
 
     let path_string = unsafe { get_input_str!(&memory, path, path_len) };
 
-    let stat = wasi_try!(path_filestat_get_internal(
+    let stat = match path_filestat_get_internal(
         &memory,
         state,
         inodes.deref_mut(),
         fd,
         flags,
-        &path_string
-    ));
+        &path_string,
+    ) {
+        Ok(stat) => stat,
+        Err(err) => {
+            state
+                .fs
+                .report_path_error(inodes.deref(), "path_filestat_get", fd, &path_string, err);
+            state
+                .fs
+                .audit_path(inodes.deref(), "path_filestat_get", fd, &path_string, err);
+            return err;
+        }
+    };
+    state.fs.audit_path(
+        inodes.deref(),
+        "path_filestat_get",
+        fd,
+        &path_string,
+        Errno::Success,
+    );
 
     wasi_try_mem!(buf.deref(&memory).write(stat));
 
@@ -2064,6 +2413,30 @@ pub fn path_filestat_get_internal(
 /// - `Fstflags fst_flags`
 ///     A bitmask controlling which attributes are set
 pub fn path_filestat_set_times<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    st_atim: Timestamp,
+    st_mtim: Timestamp,
+    fst_flags: Fstflags,
+) -> Errno {
+    let ret = path_filestat_set_times_internal(
+        ctx.as_mut(),
+        fd,
+        flags,
+        path,
+        path_len,
+        st_atim,
+        st_mtim,
+        fst_flags,
+    );
+    audit_path(&ctx, "path_filestat_set_times", fd, path, path_len, ret);
+    ret
+}
+
+fn path_filestat_set_times_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     fd: WasiFd,
     flags: LookupFlags,
@@ -2077,7 +2450,6 @@ pub fn path_filestat_set_times<M: MemorySize>(
     let env = ctx.data();
     let (memory, mut state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(&ctx, 0);
     let fd_entry = wasi_try!(state.fs.get_fd(fd));
-    let fd_inode = fd_entry.inode;
     if !fd_entry.rights.contains(Rights::PATH_FILESTAT_SET_TIMES) {
         return Errno::Access;
     }
@@ -2101,23 +2473,22 @@ pub fn path_filestat_set_times<M: MemorySize>(
         wasi_try!(state.fs.get_stat_for_kind(inodes.deref(), guard.deref()))
     };
 
-    let inode = &inodes.arena[fd_inode];
+    let inode = &inodes.arena[file_inode];
+    let (accessed, modified) = wasi_try!(times_to_set(st_atim, st_mtim, fst_flags));
 
-    if fst_flags.contains(Fstflags::SET_ATIM) || fst_flags.contains(Fstflags::SET_ATIM_NOW) {
-        let time_to_set = if fst_flags.contains(Fstflags::SET_ATIM) {
-            st_atim
-        } else {
-            wasi_try!(get_current_time_in_nanos())
-        };
-        inode.stat.write().unwrap().st_atim = time_to_set;
+    {
+        let guard = inode.read();
+        wasi_try!(state
+            .fs
+            .set_times_for_kind(guard.deref(), accessed, modified));
     }
-    if fst_flags.contains(Fstflags::SET_MTIM) || fst_flags.contains(Fstflags::SET_MTIM_NOW) {
-        let time_to_set = if fst_flags.contains(Fstflags::SET_MTIM) {
-            st_mtim
-        } else {
-            wasi_try!(get_current_time_in_nanos())
-        };
-        inode.stat.write().unwrap().st_mtim = time_to_set;
+
+    let mut stat = inode.stat.write().unwrap();
+    if let Some(accessed) = accessed {
+        stat.st_atim = accessed;
+    }
+    if let Some(modified) = modified {
+        stat.st_mtim = modified;
     }
 
     Errno::Success
@@ -2141,6 +2512,31 @@ pub fn path_filestat_set_times<M: MemorySize>(
 /// - `u32 old_path_len`
 ///     Length of the `new_path` string
 pub fn path_link<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    old_fd: WasiFd,
+    old_flags: LookupFlags,
+    old_path: WasmPtr<u8, M>,
+    old_path_len: M::Offset,
+    new_fd: WasiFd,
+    new_path: WasmPtr<u8, M>,
+    new_path_len: M::Offset,
+) -> Errno {
+    let ret = path_link_internal(
+        ctx.as_mut(),
+        old_fd,
+        old_flags,
+        old_path,
+        old_path_len,
+        new_fd,
+        new_path,
+        new_path_len,
+    );
+    audit_path(&ctx, "path_link", old_fd, old_path, old_path_len, ret);
+    audit_path(&ctx, "path_link", new_fd, new_path, new_path_len, ret);
+    ret
+}
+
+fn path_link_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     old_fd: WasiFd,
     old_flags: LookupFlags,
@@ -2237,6 +2633,44 @@ pub fn path_link<M: MemorySize>(
 /// Possible Errors:
 /// - `Errno::Access`, `Errno::Badf`, `Errno::Fault`, `Errno::Fbig?`, `Errno::Inval`, `Errno::Io`, `Errno::Loop`, `Errno::Mfile`, `Errno::Nametoolong?`, `Errno::Nfile`, `Errno::Noent`, `Errno::Notdir`, `Errno::Rofs`, and `Errno::Notcapable`
 pub fn path_open<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    dirfd: WasiFd,
+    dirflags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    o_flags: Oflags,
+    fs_rights_base: Rights,
+    fs_rights_inheriting: Rights,
+    fs_flags: Fdflags,
+    fd: WasmPtr<WasiFd, M>,
+) -> Errno {
+    refresh_proc(&ctx);
+    let ret = path_open_internal(
+        ctx.as_mut(),
+        dirfd,
+        dirflags,
+        path,
+        path_len,
+        o_flags,
+        fs_rights_base,
+        fs_rights_inheriting,
+        fs_flags,
+        fd,
+    );
+    if ret != Errno::Success {
+        let env = ctx.data();
+        let (memory, state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
+        if let Ok(path_string) = unsafe { path.read_utf8_string(&memory, path_len) } {
+            state
+                .fs
+                .report_path_error(inodes.deref(), "path_open", dirfd, &path_string, ret);
+        }
+    }
+    audit_path(&ctx, "path_open", dirfd, path, path_len, ret);
+    ret
+}
+
+fn path_open_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     dirfd: WasiFd,
     dirflags: LookupFlags,
@@ -2482,6 +2916,20 @@ pub fn path_open<M: MemorySize>(
 /// - `u32 buf_used`
 ///     The number of bytes written to `buf`
 pub fn path_readlink<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    dir_fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    buf_used: WasmPtr<M::Offset, M>,
+) -> Errno {
+    let ret = path_readlink_internal(ctx.as_mut(), dir_fd, path, path_len, buf, buf_len, buf_used);
+    audit_path(&ctx, "path_readlink", dir_fd, path, path_len, ret);
+    ret
+}
+
+fn path_readlink_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     dir_fd: WasiFd,
     path: WasmPtr<u8, M>,
@@ -2532,6 +2980,17 @@ pub fn path_readlink<M: MemorySize>(
 
 /// Returns Errno::Notemtpy if directory is not empty
 pub fn path_remove_directory<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Errno {
+    let ret = path_remove_directory_internal(ctx.as_mut(), fd, path, path_len);
+    audit_path(&ctx, "path_remove_directory", fd, path, path_len, ret);
+    ret
+}
+
+fn path_remove_directory_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     fd: WasiFd,
     path: WasmPtr<u8, M>,
@@ -2619,6 +3078,29 @@ pub fn path_remove_directory<M: MemorySize>(
 /// - `u32 new_path_len`
 ///     The number of bytes to read from `new_path`
 pub fn path_rename<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    old_fd: WasiFd,
+    old_path: WasmPtr<u8, M>,
+    old_path_len: M::Offset,
+    new_fd: WasiFd,
+    new_path: WasmPtr<u8, M>,
+    new_path_len: M::Offset,
+) -> Errno {
+    let ret = path_rename_internal(
+        ctx.as_mut(),
+        old_fd,
+        old_path,
+        old_path_len,
+        new_fd,
+        new_path,
+        new_path_len,
+    );
+    audit_path(&ctx, "path_rename", old_fd, old_path, old_path_len, ret);
+    audit_path(&ctx, "path_rename", new_fd, new_path, new_path_len, ret);
+    ret
+}
+
+fn path_rename_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     old_fd: WasiFd,
     old_path: WasmPtr<u8, M>,
@@ -2804,6 +3286,26 @@ pub fn path_rename<M: MemorySize>(
 /// - `u32 new_path_len`
 ///     The number of bytes to read from `new_path`
 pub fn path_symlink<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    old_path: WasmPtr<u8, M>,
+    old_path_len: M::Offset,
+    fd: WasiFd,
+    new_path: WasmPtr<u8, M>,
+    new_path_len: M::Offset,
+) -> Errno {
+    let ret = path_symlink_internal(
+        ctx.as_mut(),
+        old_path,
+        old_path_len,
+        fd,
+        new_path,
+        new_path_len,
+    );
+    audit_path(&ctx, "path_symlink", fd, new_path, new_path_len, ret);
+    ret
+}
+
+fn path_symlink_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     old_path: WasmPtr<u8, M>,
     old_path_len: M::Offset,
@@ -2827,10 +3329,11 @@ pub fn path_symlink<M: MemorySize>(
         wasi_try!(state
             .fs
             .get_parent_inode_at_path(inodes.deref_mut(), fd, old_path_path, true));
+    // the parent may be `fd` itself, at depth 0
     let depth = wasi_try!(state
         .fs
         .path_depth_from_fd(inodes.deref(), fd, source_inode))
-        - 1;
+    .saturating_sub(1);
 
     let new_path_path = std::path::Path::new(&new_path_str);
     let (target_parent_inode, entry_name) =
@@ -2839,14 +3342,15 @@ pub fn path_symlink<M: MemorySize>(
             .get_parent_inode_at_path(inodes.deref_mut(), fd, new_path_path, true));
 
     // short circuit if anything is wrong, before we create an inode
-    {
+    let target_parent_path = {
         let guard = inodes.arena[target_parent_inode].read();
         let deref = guard.deref();
         match deref {
-            Kind::Dir { entries, .. } => {
+            Kind::Dir { entries, path, .. } => {
                 if entries.contains_key(&entry_name) {
                     return Errno::Exist;
                 }
+                path.clone()
             }
             Kind::Root { .. } => return Errno::Notcapable,
             Kind::Socket { .. } | Kind::Pipe { .. } | Kind::EventNotifications { .. } => {
@@ -2856,6 +3360,16 @@ pub fn path_symlink<M: MemorySize>(
                 unreachable!("get_parent_inode_at_path returned something other than a Dir or Root")
             }
         }
+    };
+
+    // store the symlink in the file system backing, if it supports
+    // symlinks, otherwise it only lives in the inode created below
+    match state.fs.fs_backing.symlink(
+        std::path::Path::new(&old_path_str),
+        &target_parent_path.join(&entry_name),
+    ) {
+        Ok(()) | Err(FsError::Unsupported) => {}
+        Err(err) => return fs_error_into_wasi_err(err),
     }
 
     let mut source_path = std::path::Path::new(&old_path_str);
@@ -2905,6 +3419,17 @@ pub fn path_symlink<M: MemorySize>(
 /// - `u32 path_len`
 ///     The number of bytes in the `path` array
 pub fn path_unlink_file<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Errno {
+    let ret = path_unlink_file_internal(ctx.as_mut(), fd, path, path_len);
+    audit_path(&ctx, "path_unlink_file", fd, path, path_len, ret);
+    ret
+}
+
+fn path_unlink_file_internal<M: MemorySize>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
     fd: WasiFd,
     path: WasmPtr<u8, M>,
@@ -3005,6 +3530,38 @@ pub fn path_unlink_file<M: MemorySize>(
     Errno::Success
 }
 
+/// How long `poll_oneoff` polls the file descriptors when there is no
+/// clock subscription.
+const POLL_WITHOUT_CLOCK: Duration = Duration::from_millis(5);
+
+/// How often `poll_oneoff` polls the file descriptors.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The monotonic host time, in nanoseconds, at which the clock
+/// subscription `clock_info`, made at `now`, expires.
+fn clock_deadline(
+    state: &WasiState,
+    clock_info: &SubscriptionClock,
+    now: u64,
+) -> Result<u64, Errno> {
+    let timeout = if clock_info
+        .flags
+        .contains(Subclockflags::SUBSCRIPTION_CLOCK_ABSTIME)
+    {
+        let clock_id = match clock_info.clock_id {
+            Clockid::Realtime => Snapshot0Clockid::Realtime,
+            _ => Snapshot0Clockid::Monotonic,
+        };
+        let guest_now = state.guest_time(clock_id, platform_clock_time_get(clock_id, 1)?);
+        clock_info.timeout.saturating_sub(guest_now.max(0) as u64)
+    } else {
+        clock_info.timeout
+    };
+    let timeout = state.host_duration(Duration::from_nanos(timeout));
+
+    Ok(now.saturating_add(timeout.as_nanos() as u64))
+}
+
 /// ### `poll_oneoff()`
 /// Concurrently poll for a set of events
 /// Inputs:
@@ -3037,7 +3594,7 @@ pub fn poll_oneoff<M: MemorySize>(
     let mut fd_guards = vec![];
     let mut clock_subs = vec![];
     let mut in_events = vec![];
-    let mut time_to_sleep = Duration::from_millis(5);
+    let now = crate::timer::now();
 
     for sub in subscription_array.iter() {
         let s: Subscription = wasi_try_mem_ok!(sub.read());
@@ -3072,10 +3629,8 @@ pub fn poll_oneoff<M: MemorySize>(
             }
             SubscriptionEnum::Clock(clock_info) => {
                 if matches!(clock_info.clock_id, Clockid::Realtime | Clockid::Monotonic) {
-                    // this is a hack
-                    // TODO: do this properly
-                    time_to_sleep = Duration::from_nanos(clock_info.timeout);
-                    clock_subs.push((clock_info, s.userdata));
+                    let deadline = wasi_try_ok!(clock_deadline(state, &clock_info, now), env);
+                    clock_subs.push((deadline, s.userdata));
                     None
                 } else {
                     unimplemented!("Polling not implemented for clocks yet");
@@ -3157,36 +3712,52 @@ pub fn poll_oneoff<M: MemorySize>(
 
     let mut seen_events = vec![Default::default(); in_events.len()];
 
-    let start = platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap() as u128;
+    // Without a clock, the file descriptors are only polled for a
+    // short while.
+    let deadline = clock_subs
+        .iter()
+        .map(|(deadline, _)| *deadline)
+        .min()
+        .unwrap_or_else(|| now.saturating_add(POLL_WITHOUT_CLOCK.as_nanos() as u64));
+
+    let operation = env.begin_operation(None, PendingOperationKind::Poll);
     let mut triggered = 0;
-    while triggered == 0 {
-        let now = platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap() as u128;
-        let delta = match now.checked_sub(start) {
-            Some(a) => Duration::from_nanos(a as u64),
-            None => Duration::ZERO,
-        };
-        match poll(
-            fds.as_slice(),
-            in_events.as_slice(),
-            seen_events.as_mut_slice(),
-            Duration::from_millis(1),
-        ) {
-            Ok(0) => {
-                env.yield_now()?;
-            }
-            Ok(a) => {
-                triggered = a;
-            }
-            Err(FsError::WouldBlock) => {
-                env.sleep(Duration::from_millis(1))?;
-            }
-            Err(err) => {
-                return Ok(fs_error_into_wasi_err(err));
-            }
-        };
-        if delta > time_to_sleep {
+    loop {
+        if operation.is_cancelled() {
+            return Ok(Errno::Intr);
+        }
+
+        if !fds.is_empty() {
+            match poll(
+                fds.as_slice(),
+                in_events.as_slice(),
+                seen_events.as_mut_slice(),
+                Duration::from_millis(1),
+            ) {
+                Ok(0) | Err(FsError::WouldBlock) => (),
+                Ok(a) => {
+                    triggered = a;
+                    break;
+                }
+                Err(err) => {
+                    return Ok(fs_error_into_wasi_err(err));
+                }
+            };
+        }
+
+        let now = crate::timer::now();
+        if now >= deadline {
             break;
         }
+
+        // The file descriptors can't wake the thread up, they are
+        // polled again on the next tick.
+        let wake_up = if fds.is_empty() {
+            deadline
+        } else {
+            deadline.min(now.saturating_add(POLL_INTERVAL.as_nanos() as u64))
+        };
+        env.sleep_until(wake_up, Some(&operation))?;
     }
 
     for (i, seen_event) in seen_events.into_iter().enumerate() {
@@ -3240,7 +3811,11 @@ pub fn poll_oneoff<M: MemorySize>(
         events_seen += 1;
     }
     if triggered == 0 {
-        for (clock_info, userdata) in clock_subs {
+        let now = crate::timer::now();
+        for (deadline, userdata) in clock_subs {
+            if deadline > now {
+                continue;
+            }
             let event = Event {
                 userdata,
                 error: Errno::Success,
@@ -3487,6 +4062,7 @@ pub fn thread_spawn<M: MemorySize>(
     let mut sub_env = env.clone();
     let mut sub_thread = env.new_thread();
     sub_env.id = sub_thread.id;
+    sub_env.last_host_error = Default::default();
 
     let child = {
         let id = sub_thread.id;
@@ -3545,7 +4121,9 @@ pub fn thread_sleep(
     debug!("wasi::thread_sleep");
 
     let env = ctx.data();
-    let duration = Duration::from_nanos(duration as u64);
+    let duration = env
+        .state
+        .host_duration(Duration::from_nanos(duration as u64));
     env.sleep(duration)?;
     Ok(Errno::Success)
 }
@@ -3583,10 +4161,14 @@ pub fn thread_join(ctx: FunctionEnvMut<'_, WasiEnv>, tid: Tid) -> Result<Errno,
         guard.threads.get(&tid).cloned()
     };
     if let Some(other_thread) = other_thread {
+        let operation = env.begin_operation(None, PendingOperationKind::ThreadJoin);
         loop {
             if other_thread.join(Duration::from_millis(5)) {
                 break;
             }
+            if operation.is_cancelled() {
+                return Ok(Errno::Intr);
+            }
             env.yield_now()?;
         }
         Ok(Errno::Success)
@@ -5134,7 +5716,11 @@ pub fn sock_accept<M: MemorySize>(
     let (child, addr) = {
         let mut ret;
         let (_, state) = env.get_memory_and_wasi_state(&ctx, 0);
+        let operation = env.begin_operation(Some(sock), PendingOperationKind::Accept);
         loop {
+            if operation.is_cancelled() {
+                return Ok(Errno::Intr);
+            }
             wasi_try_ok!(
                 match __sock_actor(&ctx, sock, Rights::SOCK_ACCEPT, |socket| socket
                     .accept_timeout(fd_flags, Duration::from_millis(5)))
@@ -5426,7 +6012,7 @@ pub unsafe fn sock_send_file<M: MemorySize>(
                     env
                 );
                 if let Some(ref mut stdin) = guard.deref_mut() {
-                    wasi_try_ok!(stdin.read(&mut buf).map_err(map_io_err))
+                    wasi_try_ok!(stdin.read(&mut buf).map_err(|err| env.host_io_error(err)))
                 } else {
                     return Ok(Errno::Badf);
                 }
@@ -5451,19 +6037,23 @@ pub unsafe fn sock_send_file<M: MemorySize>(
                                 wasi_try_ok!(
                                     handle
                                         .seek(std::io::SeekFrom::Start(offset as u64))
-                                        .map_err(map_io_err),
+                                        .map_err(|err| env.host_io_error(err)),
                                     env
                                 );
-                                wasi_try_ok!(handle.read(&mut buf).map_err(map_io_err))
+                                wasi_try_ok!(handle
+                                    .read(&mut buf)
+                                    .map_err(|err| env.host_io_error(err)))
                             } else {
                                 return Ok(Errno::Inval);
                             }
                         }
                         Kind::Socket { socket } => {
-                            wasi_try_ok!(socket.read(&mut buf).map_err(map_io_err))
+                            wasi_try_ok!(socket
+                                .read(&mut buf)
+                                .map_err(|err| env.host_io_error(err)))
                         }
                         Kind::Pipe { pipe } => {
-                            wasi_try_ok!(pipe.read(&mut buf).map_err(map_io_err))
+                            wasi_try_ok!(pipe.read(&mut buf).map_err(|err| env.host_io_error(err)))
                         }
                         Kind::Dir { .. } | Kind::Root { .. } => {
                             return Ok(Errno::Isdir);
@@ -429,6 +429,17 @@ pub(crate) fn fd_pipe(
     super::fd_pipe::<MemoryType>(ctx, ro_fd1, ro_fd2)
 }
 
+pub(crate) fn fd_ioctl(
+    ctx: FunctionEnvMut<WasiEnv>,
+    fd: Fd,
+    request: u32,
+    buf: WasmPtr<u8, MemoryType>,
+    buf_len: MemoryOffset,
+    ret_len: WasmPtr<MemoryOffset, MemoryType>,
+) -> Errno {
+    super::fd_ioctl::<MemoryType>(ctx, fd, request, buf, buf_len, ret_len)
+}
+
 pub(crate) fn tty_get(ctx: FunctionEnvMut<WasiEnv>, tty_state: WasmPtr<Tty, MemoryType>) -> Errno {
     super::tty_get::<MemoryType>(ctx, tty_state)
 }
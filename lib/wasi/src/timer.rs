@@ -0,0 +1,275 @@
+//! A timer wheel waking the threads sleeping in the system calls, e.g.
+//! in `poll_oneoff`, shared by the instances so that their timeouts
+//! are coalesced into few wakeups.
+
+use crate::syscalls::platform_clock_time_get;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use wasmer_wasi_types::wasi::Snapshot0Clockid;
+
+/// The number of slots of a wheel. A timer further away than this
+/// many ticks stays in its slot for the next rounds.
+const SLOTS: u64 = 256;
+
+/// The tick of the global wheel.
+const DEFAULT_TICK: Duration = Duration::from_millis(1);
+
+/// How often the threads sleeping on the global wheel yield to the
+/// runtime.
+const DEFAULT_YIELD_INTERVAL: Duration = Duration::from_millis(50);
+
+lazy_static::lazy_static! {
+    static ref GLOBAL: Arc<TimerWheel> =
+        Arc::new(TimerWheel::new(DEFAULT_TICK, DEFAULT_YIELD_INTERVAL));
+}
+
+/// The monotonic host time, in nanoseconds.
+pub(crate) fn now() -> u64 {
+    platform_clock_time_get(Snapshot0Clockid::Monotonic, 1)
+        .unwrap_or(0)
+        .max(0) as u64
+}
+
+/// A hashed timer wheel.
+///
+/// The timers are rounded up to the next tick, and kept in the slot of
+/// their tick. A single driver thread sleeps until the earliest tick,
+/// then wakes up all the timers of the tick at once, so that a sleeping
+/// thread is woken up once, and thousands of sleeping threads cost a
+/// wakeup of the driver per tick at most.
+///
+/// The threads sleeping on the wheel also wake up every
+/// `yield_interval` to yield to the runtime, which may shut them down;
+/// these wakeups are aligned on the same instants for all the threads.
+///
+/// By default, all the instances share [`TimerWheel::global`].
+#[derive(Debug)]
+pub struct TimerWheel {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    /// The length of a tick, in nanoseconds.
+    tick: u64,
+    /// The interval of the yields to the runtime, in ticks.
+    yield_ticks: u64,
+    wheel: Mutex<Wheel>,
+    /// Notifies the driver that the earliest tick changed.
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+struct Wheel {
+    slots: Vec<Vec<Timer>>,
+    /// The earliest tick of the timers, if any.
+    earliest: Option<u64>,
+    /// The last tick whose timers were woken up.
+    fired: u64,
+    /// Whether the driver thread runs.
+    driving: bool,
+    /// Whether the wheel was dropped, which stops the driver.
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct Timer {
+    tick: u64,
+    waker: mpsc::Sender<()>,
+}
+
+impl TimerWheel {
+    /// Creates a wheel whose timers are rounded up to `tick`, and
+    /// whose sleeping threads yield to the runtime every
+    /// `yield_interval`.
+    pub fn new(tick: Duration, yield_interval: Duration) -> Self {
+        let tick = (tick.as_nanos() as u64).max(1);
+        let yield_ticks = (yield_interval.as_nanos() as u64 / tick).max(1);
+
+        Self {
+            shared: Arc::new(Shared {
+                tick,
+                yield_ticks,
+                wheel: Mutex::new(Wheel {
+                    slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+                    earliest: None,
+                    fired: now() / tick,
+                    driving: false,
+                    closed: false,
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// The wheel shared by the instances, unless they are given
+    /// another one with
+    /// [`WasiEnv::set_timer_wheel`](crate::WasiEnv::set_timer_wheel).
+    pub fn global() -> Arc<Self> {
+        GLOBAL.clone()
+    }
+
+    /// The number of timers waiting to be woken up.
+    pub fn pending(&self) -> usize {
+        let wheel = self.shared.wheel.lock().unwrap();
+        wheel.slots.iter().map(Vec::len).sum()
+    }
+
+    /// The monotonic host time, in nanoseconds, at which a thread
+    /// sleeping until `deadline` is woken up: the deadline, or the
+    /// next yield to the runtime if it comes first.
+    pub(crate) fn wake_time(&self, now: u64, deadline: u64) -> u64 {
+        let interval = self.shared.tick * self.shared.yield_ticks;
+        let next_yield = (now / interval + 1).saturating_mul(interval);
+
+        deadline.min(next_yield)
+    }
+
+    /// Wakes `waker` up once the monotonic host time reaches
+    /// `deadline`, in nanoseconds, rounded up to the next tick.
+    pub(crate) fn schedule(&self, deadline: u64, waker: mpsc::Sender<()>) {
+        let shared = &self.shared;
+        let tick = (deadline / shared.tick) + (deadline % shared.tick != 0) as u64;
+        let mut wheel = shared.wheel.lock().unwrap();
+
+        if tick <= wheel.fired {
+            let _ = waker.send(());
+            return;
+        }
+
+        wheel.slots[(tick % SLOTS) as usize].push(Timer { tick, waker });
+        if wheel.earliest.map_or(true, |earliest| tick < earliest) {
+            wheel.earliest = Some(tick);
+            shared.changed.notify_one();
+        }
+
+        if !wheel.driving {
+            wheel.driving = true;
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("wasi-timers".to_string())
+                .spawn(move || drive(&shared))
+                .expect("failed to spawn the timer thread");
+        }
+    }
+}
+
+impl Drop for TimerWheel {
+    fn drop(&mut self) {
+        if let Ok(mut wheel) = self.shared.wheel.lock() {
+            wheel.closed = true;
+            self.shared.changed.notify_one();
+        }
+    }
+}
+
+/// Wakes up the timers as their ticks pass, until the wheel is
+/// dropped.
+fn drive(shared: &Shared) {
+    let mut wheel = shared.wheel.lock().unwrap();
+
+    while !wheel.closed {
+        let earliest = match wheel.earliest {
+            Some(earliest) => earliest,
+            None => {
+                wheel = shared.changed.wait(wheel).unwrap();
+                continue;
+            }
+        };
+
+        let now = now();
+        let until = earliest.saturating_mul(shared.tick);
+        if now < until {
+            let timeout = Duration::from_nanos(until - now);
+            wheel = shared.changed.wait_timeout(wheel, timeout).unwrap().0;
+            continue;
+        }
+
+        wheel.fire(now / shared.tick);
+    }
+}
+
+impl Wheel {
+    /// Wakes up the timers of the ticks up to `now`.
+    fn fire(&mut self, now: u64) {
+        let passed = now.saturating_sub(self.fired);
+        let slots = if passed >= SLOTS {
+            0..SLOTS
+        } else {
+            self.fired + 1..now + 1
+        };
+
+        for slot in slots {
+            self.slots[(slot % SLOTS) as usize].retain(|timer| {
+                if timer.tick <= now {
+                    let _ = timer.waker.send(());
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        self.fired = now;
+        self.earliest = self.slots.iter().flatten().map(|timer| timer.tick).min();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_timers() {
+        let wheel = TimerWheel::new(Duration::from_millis(1), Duration::from_secs(1));
+        let start = Instant::now();
+        let (waker, woken) = mpsc::channel();
+
+        wheel.schedule(now() + 20_000_000, waker.clone());
+        wheel.schedule(now() + 5_000_000, waker.clone());
+        assert_eq!(wheel.pending(), 2);
+
+        woken.recv().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(5));
+        assert_eq!(wheel.pending(), 1, "the earliest timer was woken up");
+
+        woken.recv().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(wheel.pending(), 0);
+
+        wheel.schedule(0, waker);
+        assert!(
+            woken.try_recv().is_ok(),
+            "a timer in the past is woken up at once"
+        );
+    }
+
+    #[test]
+    fn test_timers_of_a_tick_are_coalesced() {
+        let wheel = TimerWheel::new(Duration::from_millis(50), Duration::from_secs(1));
+        let (first, first_woken) = mpsc::channel();
+        let (second, second_woken) = mpsc::channel();
+
+        let tick = 50_000_000;
+        let deadline = (now() / tick + 2) * tick;
+        wheel.schedule(deadline - 20_000_000, first);
+        wheel.schedule(deadline, second);
+
+        first_woken.recv().unwrap();
+        assert!(
+            second_woken.try_recv().is_ok(),
+            "both timers are woken up with their tick"
+        );
+    }
+
+    #[test]
+    fn test_wake_time() {
+        let wheel = TimerWheel::new(Duration::from_nanos(10), Duration::from_nanos(100));
+
+        assert_eq!(wheel.wake_time(120, 150), 150);
+        assert_eq!(wheel.wake_time(120, 1_000), 200, "yielding first");
+        assert_eq!(wheel.wake_time(200, 1_000), 300);
+    }
+}
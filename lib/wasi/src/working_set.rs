@@ -0,0 +1,230 @@
+//! Reports the memory used by an instance to the embedder, and
+//! releases some of it on demand, see [`WorkingSetMonitor`].
+//!
+//! In a browser, a tab has a memory budget that the linear memory of
+//! the guest and the in-memory file system count against. The
+//! embedder is told when they grow or shrink, and forwards the memory
+//! pressure it notices, from the browser or its own budget, to have
+//! the file system compacted.
+
+use std::sync::{Arc, Mutex};
+use wasmer::{AsStoreRef, Memory};
+#[cfg(feature = "mem-fs")]
+use wasmer_vfs::mem_fs;
+use wasmer_vfs::FsError;
+
+/// The syscalls after which the working set is measured: the ones
+/// growing or shrinking the file system, and the ones a guest calls
+/// when idle, which the growth of its linear memory is noticed at.
+pub(crate) const REPORTING_SYSCALLS: [&str; 11] = [
+    "fd_allocate",
+    "fd_close",
+    "fd_filestat_set_size",
+    "fd_pwrite",
+    "fd_write",
+    "path_open",
+    "path_remove_directory",
+    "path_rename",
+    "path_unlink_file",
+    "poll_oneoff",
+    "sched_yield",
+];
+
+/// The memory used by an instance, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkingSet {
+    /// The size of the linear memory of the guest.
+    pub memory_bytes: u64,
+    /// The size of the contents of the files of the in-memory file
+    /// system watched by the monitor, if any.
+    pub fs_bytes: Option<u64>,
+}
+
+impl WorkingSet {
+    /// The total size.
+    pub fn total_bytes(&self) -> u64 {
+        self.memory_bytes + self.fs_bytes.unwrap_or(0)
+    }
+}
+
+/// How short of memory the host is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryPressure {
+    /// The unreachable files are removed.
+    Moderate,
+    /// The unreachable files are removed, and the memory allocated
+    /// beyond the contents of the files is released.
+    Critical,
+}
+
+/// The callback of a [`WorkingSetMonitor`].
+pub type WorkingSetCallback = Arc<dyn Fn(&WorkingSet) + Send + Sync>;
+
+/// Calls back the embedder when the working set of an instance
+/// changes, see [`WasiEnv::set_working_set_monitor`].
+///
+/// Clones share the same callback and the last reported working set.
+///
+/// [`WasiEnv::set_working_set_monitor`]: crate::WasiEnv::set_working_set_monitor
+#[derive(Clone)]
+pub struct WorkingSetMonitor {
+    callback: WorkingSetCallback,
+    #[cfg(feature = "mem-fs")]
+    fs: Option<mem_fs::FileSystem>,
+    threshold: u64,
+    last: Arc<Mutex<Option<WorkingSet>>>,
+}
+
+impl std::fmt::Debug for WorkingSetMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkingSetMonitor")
+            .field("threshold", &self.threshold)
+            .field("last", &self.last)
+            .finish()
+    }
+}
+
+impl WorkingSetMonitor {
+    /// Creates a monitor calling `callback` with the working set each
+    /// time it changes by one page of memory or more.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&WorkingSet) + Send + Sync + 'static,
+    {
+        Self {
+            callback: Arc::new(callback),
+            #[cfg(feature = "mem-fs")]
+            fs: None,
+            threshold: wasmer::WASM_PAGE_SIZE as u64,
+            last: Default::default(),
+        }
+    }
+
+    /// Sets the change of the total size, in bytes, that triggers a
+    /// report. Zero reports every change.
+    pub fn threshold(mut self, threshold: u64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Measures `fs` too, and compacts it under memory pressure.
+    ///
+    /// Measuring walks the nodes of the file system, which a large
+    /// file system makes slower.
+    #[cfg(feature = "mem-fs")]
+    pub fn fs(mut self, fs: mem_fs::FileSystem) -> Self {
+        self.fs = Some(fs);
+        self
+    }
+
+    /// The working set last reported, if any.
+    pub fn last(&self) -> Option<WorkingSet> {
+        *self.last.lock().unwrap()
+    }
+
+    /// Measures the working set, given the linear memory of the guest.
+    pub fn measure(&self, store: &impl AsStoreRef, memory: Option<&Memory>) -> WorkingSet {
+        WorkingSet {
+            memory_bytes: memory.map_or(0, |memory| memory.view(store).data_size()),
+            fs_bytes: self.fs_bytes(),
+        }
+    }
+
+    #[cfg(feature = "mem-fs")]
+    fn fs_bytes(&self) -> Option<u64> {
+        // A locked file system is measured next time.
+        self.fs.as_ref().and_then(|fs| fs.used_bytes().ok())
+    }
+
+    #[cfg(not(feature = "mem-fs"))]
+    fn fs_bytes(&self) -> Option<u64> {
+        None
+    }
+
+    /// Measures the working set, and calls the callback if it changed
+    /// enough since the last report, or was never reported.
+    pub fn report(&self, store: &impl AsStoreRef, memory: Option<&Memory>) -> WorkingSet {
+        let working_set = self.measure(store, memory);
+
+        let changed = {
+            let mut last = self.last.lock().unwrap();
+            let changed = match *last {
+                Some(last) => {
+                    working_set != last
+                        && working_set.total_bytes().abs_diff(last.total_bytes()) >= self.threshold
+                }
+                None => true,
+            };
+            if changed {
+                *last = Some(working_set);
+            }
+            changed
+        };
+        // The lock is released for the callback to call `last`.
+        if changed {
+            (self.callback)(&working_set);
+        }
+
+        working_set
+    }
+
+    /// Releases memory of the file system according to `pressure`,
+    /// returning the number of bytes released.
+    pub fn relieve(&self, pressure: MemoryPressure) -> Result<u64, FsError> {
+        #[cfg(feature = "mem-fs")]
+        if let Some(fs) = &self.fs {
+            return match pressure {
+                MemoryPressure::Moderate => fs.gc().map(|report| report.bytes),
+                MemoryPressure::Critical => fs.compact(),
+            };
+        }
+
+        let _ = pressure;
+        Ok(0)
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::Path;
+    use wasmer::Store;
+    use wasmer_vfs::FileSystem;
+
+    #[test]
+    fn test_report_changes() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let fs = mem_fs::FileSystem::default();
+        let monitor = {
+            let reports = reports.clone();
+            WorkingSetMonitor::new(move |working_set| reports.lock().unwrap().push(*working_set))
+                .threshold(4)
+                .fs(fs.clone())
+        };
+        let store = Store::default();
+
+        monitor.report(&store, None);
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(Path::new("/file"))
+            .unwrap();
+        file.write_all(b"abc").unwrap();
+        monitor.report(&store, None);
+        file.write_all(b"d").unwrap();
+        monitor.report(&store, None);
+
+        let working_set = |fs_bytes| WorkingSet {
+            memory_bytes: 0,
+            fs_bytes: Some(fs_bytes),
+        };
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![working_set(0), working_set(4)],
+            "a change below the threshold isn't reported"
+        );
+        assert_eq!(monitor.last(), Some(working_set(4)));
+    }
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "sys")]
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use wasmer::{Instance, Module, Store};
+use wasmer_vfs::mem_fs;
+use wasmer_wasi::types::wasi::Errno;
+use wasmer_wasi::{FsAccess, WasiState};
+
+/// Creates `/foo.txt` and the `/bar` directory, removes the latter,
+/// then unlinks the missing `/baz.txt`, `/` being pre-opened as the
+/// file descriptor 4.
+const ACCESSES: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_create_directory" (func $path_create_directory (param i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_remove_directory" (func $path_remove_directory (param i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_unlink_file" (func $path_unlink_file (param i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 0) "foo.txt")
+    (data (i32.const 16) "bar")
+    (data (i32.const 32) "baz.txt")
+
+    (func $main (export "_start")
+        (drop (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 7) (i32.const 1) (i64.const 70) (i64.const 0) (i32.const 0) (i32.const 100)))
+        (drop (call $path_create_directory (i32.const 4) (i32.const 16) (i32.const 3)))
+        (drop (call $path_remove_directory (i32.const 4) (i32.const 16) (i32.const 3)))
+        (drop (call $path_unlink_file (i32.const 4) (i32.const 32) (i32.const 7)))
+    )
+)
+"#;
+
+#[test]
+fn test_path_accesses_are_audited() {
+    let accesses = Arc::new(Mutex::new(Vec::new()));
+
+    let mut store = Store::default();
+    let module = Module::new(&store, ACCESSES).unwrap();
+
+    let mut wasi_env = WasiState::new("accesses")
+        .set_fs(Box::new(mem_fs::FileSystem::default()))
+        .preopen_dir("/")
+        .unwrap()
+        .fs_audit_hook({
+            let accesses = accesses.clone();
+            Arc::new(move |access: &FsAccess| accesses.lock().unwrap().push(access.clone()))
+        })
+        .finalize(&mut store)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let access = |syscall, path: &str, errno| FsAccess {
+        syscall,
+        base_fd: 4,
+        path: path.to_string(),
+        resolved_path: Some(PathBuf::from("/").join(path)),
+        errno,
+    };
+    assert_eq!(
+        *accesses.lock().unwrap(),
+        vec![
+            access("path_open", "foo.txt", Errno::Success),
+            access("path_create_directory", "bar", Errno::Success),
+            access("path_remove_directory", "bar", Errno::Success),
+            access("path_unlink_file", "baz.txt", Errno::Noent),
+        ]
+    );
+}
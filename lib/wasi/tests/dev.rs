@@ -0,0 +1,66 @@
+#![cfg(feature = "sys")]
+
+use wasmer::{Instance, Module, Store};
+use wasmer_wasi::WasiState;
+
+/// Reads 16 bytes of `/dev/urandom` into 1024 and writes them to
+/// `/dev/null`, `/dev` being pre-opened as the file descriptor 4. The
+/// errnos of the opens go to 500 and 504, the number of bytes read to
+/// 320, and written to 408.
+const URANDOM_TO_NULL: &str = r#"
+(module
+    (import "wasi_unstable" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_unstable" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_unstable" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 0) "urandom")
+    (data (i32.const 16) "null")
+
+    (func $main (export "_start")
+        ;; Open `urandom` with `FD_READ`, its file descriptor goes to 100.
+        (i32.store (i32.const 500) (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 7) (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 100)))
+        ;; Open `null` with `FD_WRITE`, its file descriptor goes to 104.
+        (i32.store (i32.const 504) (call $path_open (i32.const 4) (i32.const 0) (i32.const 16) (i32.const 4) (i32.const 0) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 104)))
+
+        (i32.store (i32.const 300) (i32.const 1024))
+        (i32.store (i32.const 304) (i32.const 16))
+        (drop (call $fd_read (i32.load (i32.const 100)) (i32.const 300) (i32.const 1) (i32.const 320)))
+        (drop (call $fd_write (i32.load (i32.const 104)) (i32.const 300) (i32.const 1) (i32.const 408)))
+    )
+)
+"#;
+
+#[test]
+fn test_dev_urandom_to_null() {
+    let mut store = Store::default();
+    let module = Module::new(&store, URANDOM_TO_NULL).unwrap();
+
+    let mut wasi_env = WasiState::new("dev")
+        .dev_fs(true)
+        .finalize(&mut store)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let view = memory.view(&store);
+    let read_u32 = |offset| {
+        let mut bytes = [0; 4];
+        view.read(offset, &mut bytes).unwrap();
+        u32::from_le_bytes(bytes)
+    };
+    assert_eq!((read_u32(500), read_u32(504)), (0, 0), "opened");
+    assert_eq!(read_u32(320), 16, "read from urandom");
+    assert_eq!(read_u32(408), 16, "written to null");
+
+    let mut random = [0; 16];
+    view.read(1024, &mut random).unwrap();
+    assert_ne!(random, [0; 16]);
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "sys")]
+
+use std::io::Read;
+
+use wasmer::{Instance, Module, Store};
+use wasmer_wasi::{Pipe, WasiState};
+
+/// Prints `/etc/resolv.conf`, `/etc` being pre-opened as the file
+/// descriptor 4.
+const READ_RESOLV_CONF: &str = r#"
+(module
+    (import "wasi_unstable" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_unstable" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_unstable" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1 2)
+    (export "memory" (memory 0))
+
+    (data (i32.const 0) "resolv.conf")
+
+    (func $main (export "_start")
+        ;; Open the file, with `FD_READ`, its file descriptor goes to 100.
+        (drop (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 11) (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 100)))
+
+        ;; Read the file into 1024, its length goes to 320.
+        (i32.store (i32.const 300) (i32.const 1024))
+        (i32.store (i32.const 304) (i32.const 1024))
+        (drop (call $fd_read (i32.load (i32.const 100)) (i32.const 300) (i32.const 1) (i32.const 320)))
+        (i32.store (i32.const 400) (i32.const 1024))
+        (i32.store (i32.const 404) (i32.load (i32.const 320)))
+        (drop (call $fd_write (i32.const 1) (i32.const 400) (i32.const 1) (i32.const 408)))
+    )
+)
+"#;
+
+#[test]
+fn test_etc_resolv_conf() {
+    let mut store = Store::default();
+    let module = Module::new(&store, READ_RESOLV_CONF).unwrap();
+
+    let mut stdout = Pipe::new();
+    let mut wasi_env = WasiState::new("resolv")
+        .etc_fs(true)
+        .nameserver("192.0.2.53".parse().unwrap())
+        .stdout(Box::new(stdout.clone()))
+        .finalize(&mut store)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "nameserver 192.0.2.53\n");
+}
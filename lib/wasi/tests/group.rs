@@ -0,0 +1,129 @@
+#![cfg(feature = "sys")]
+
+use std::io::Read;
+
+use wasmer::{Module, Store};
+use wasmer_wasi::{Pipe, WasiGroup, WasiState, WasiTaskError};
+
+/// Writes its first argument to stdout.
+const ECHO: &str = r#"
+(module
+    (import "wasi_unstable" "args_get" (func $args_get (param i32 i32) (result i32)))
+    (import "wasi_unstable" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $main (export "_start")
+        ;; argv is stored at 0, the strings at 64.
+        (drop (call $args_get (i32.const 0) (i32.const 64)))
+
+        ;; Write "argv[1]\0" minus the null terminator, which is
+        ;; right before argv[2], or 5 bytes long in these tests.
+        (i32.store (i32.const 16) (i32.load (i32.const 4)))
+        (i32.store (i32.const 20) (i32.const 5))
+        (drop (call $fd_write (i32.const 1) (i32.const 16) (i32.const 1) (i32.const 24)))
+    )
+)
+"#;
+
+/// Yields forever.
+const SPIN: &str = r#"
+(module
+    (import "wasi_unstable" "sched_yield" (func $sched_yield (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $main (export "_start")
+        (loop $spin
+            (drop (call $sched_yield))
+            (br $spin)
+        )
+    )
+)
+"#;
+
+/// Exits with code 3.
+const FAIL: &str = r#"
+(module
+    (import "wasi_unstable" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $main (export "_start")
+        (call $proc_exit (i32.const 3))
+    )
+)
+"#;
+
+fn spawn(
+    group: &mut WasiGroup,
+    name: &str,
+    wat: &str,
+    builder: &mut wasmer_wasi::WasiStateBuilder,
+) {
+    let store = Store::default();
+    let module = Module::new(&store, wat).unwrap();
+    group.spawn(name, store, &module, builder).unwrap();
+}
+
+#[test]
+fn test_join() {
+    let mut group = WasiGroup::new();
+    let mut first = Pipe::new();
+    let mut second = Pipe::new();
+    spawn(
+        &mut group,
+        "first",
+        ECHO,
+        WasiState::new("first")
+            .arg("hello")
+            .stdout(Box::new(first.clone())),
+    );
+    spawn(
+        &mut group,
+        "second",
+        ECHO,
+        WasiState::new("second")
+            .arg("world")
+            .stdout(Box::new(second.clone())),
+    );
+
+    group.join().unwrap();
+
+    let mut output = String::new();
+    first.read_to_string(&mut output).unwrap();
+    second.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "helloworld");
+}
+
+#[test]
+fn test_failure_shuts_down_the_group() {
+    let mut group = WasiGroup::new();
+    spawn(&mut group, "spin", SPIN, &mut WasiState::new("spin"));
+    spawn(&mut group, "fail", FAIL, &mut WasiState::new("fail"));
+
+    let failure = group.join().unwrap_err();
+    assert_eq!(
+        failure.failures.len(),
+        1,
+        "the spinning task isn't a failure"
+    );
+    assert_eq!(failure.failures[0].name, "fail");
+    assert!(matches!(failure.failures[0].error, WasiTaskError::Exit(3)));
+    assert_eq!(
+        failure.to_string(),
+        "1 task(s) failed: `fail` exited with code 3"
+    );
+}
+
+#[test]
+fn test_shutdown() {
+    let mut group = WasiGroup::new();
+    spawn(&mut group, "first", SPIN, &mut WasiState::new("first"));
+    spawn(&mut group, "second", SPIN, &mut WasiState::new("second"));
+
+    group.shutdown().unwrap();
+}
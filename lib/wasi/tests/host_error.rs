@@ -0,0 +1,125 @@
+#![cfg(feature = "sys")]
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use wasmer::{Instance, Module, Store};
+use wasmer_vfs::{FsError, VirtualFile};
+use wasmer_wasi::types::wasi::Errno;
+use wasmer_wasi::WasiState;
+
+/// Writes `hello` to the standard output, returning the errno.
+const WRITE_STDOUT: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 16) "hello")
+
+    (func $main (export "_start") (result i32)
+        (i32.store (i32.const 300) (i32.const 16))
+        (i32.store (i32.const 304) (i32.const 5))
+        (call $fd_write (i32.const 1) (i32.const 300) (i32.const 1) (i32.const 308))
+    )
+)
+"#;
+
+/// A file failing with `error` when written.
+#[derive(Debug)]
+struct Failing {
+    error: fn() -> io::Error,
+}
+
+impl VirtualFile for Failing {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+
+    fn last_modified(&self) -> u64 {
+        0
+    }
+
+    fn created_time(&self) -> u64 {
+        0
+    }
+
+    fn size(&self) -> u64 {
+        0
+    }
+
+    fn set_len(&mut self, _new_size: u64) -> Result<(), FsError> {
+        Ok(())
+    }
+
+    fn unlink(&mut self) -> Result<(), FsError> {
+        Ok(())
+    }
+}
+
+impl Read for Failing {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Seek for Failing {
+    fn seek(&mut self, _position: SeekFrom) -> io::Result<u64> {
+        Ok(0)
+    }
+}
+
+impl Write for Failing {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err((self.error)())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes to a standard output failing with `error`, returning the
+/// errno and the last error of the host.
+fn write_stdout(error: fn() -> io::Error) -> (i32, Option<wasmer_wasi::HostError>) {
+    let mut store = Store::default();
+    let module = Module::new(&store, WRITE_STDOUT).unwrap();
+
+    let mut wasi_env = WasiState::new("write-stdout")
+        .stdout(Box::new(Failing { error }))
+        .finalize(&mut store)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+    assert!(wasi_env.data_mut(&mut store).last_host_error().is_none());
+
+    let start = instance.exports.get_function("_start").unwrap();
+    let errno = start.call(&mut store, &[]).unwrap()[0].unwrap_i32();
+    (errno, wasi_env.data_mut(&mut store).last_host_error())
+}
+
+#[test]
+fn test_host_errors_are_translated_and_preserved() {
+    let (errno, host_error) =
+        write_stdout(|| io::Error::new(io::ErrorKind::Other, "the disk is on fire"));
+    assert_eq!(errno, Errno::Io as i32);
+    let host_error = host_error.unwrap();
+    assert_eq!(host_error.errno(), Errno::Io);
+    assert_eq!(host_error.error().to_string(), "the disk is on fire");
+
+    let (errno, host_error) = write_stdout(|| io::Error::new(io::ErrorKind::Other, FsError::Busy));
+    assert_eq!(errno, Errno::Busy as i32, "the file system error is kept");
+    assert_eq!(host_error.unwrap().errno(), Errno::Busy);
+
+    let (errno, _) = write_stdout(|| io::ErrorKind::InvalidInput.into());
+    assert_eq!(errno, Errno::Inval as i32);
+
+    #[cfg(target_os = "linux")]
+    {
+        // `ENOSPC`.
+        let (errno, host_error) = write_stdout(|| io::Error::from_raw_os_error(28));
+        assert_eq!(errno, Errno::Nospc as i32);
+        assert_eq!(host_error.unwrap().error().raw_os_error(), Some(28));
+    }
+}
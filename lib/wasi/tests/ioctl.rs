@@ -0,0 +1,87 @@
+#![cfg(feature = "sys")]
+
+use wasmer::{Instance, Module, Store};
+use wasmer_wasi::types::wasi::Errno;
+use wasmer_wasi::{WasiResource, WasiState};
+
+/// Sends the request given as parameter to the file descriptor given
+/// as parameter, with the data `abc` at 16, in a buffer of the length
+/// given as parameter, returning the errno; the length of the
+/// response is at 8.
+const IOCTL: &str = r#"
+(module
+    (import "wasix_32v1" "fd_ioctl" (func $fd_ioctl (param i32 i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func (export "ioctl") (param $fd i32) (param $request i32) (param $len i32) (result i32)
+        (i32.store (i32.const 16) (i32.const 0x636261))
+        (call $fd_ioctl (local.get $fd) (local.get $request) (i32.const 16) (local.get $len) (i32.const 8))
+    )
+)
+"#;
+
+/// Answers the request 1 with its data reversed, and the request 2
+/// with its data repeated.
+#[derive(Debug)]
+struct Device;
+
+impl WasiResource for Device {
+    fn control(&mut self, request: u32, data: &[u8]) -> Result<Vec<u8>, Errno> {
+        match request {
+            1 => Ok(data.iter().rev().copied().collect()),
+            2 => Ok(data.repeat(2)),
+            _ => Err(Errno::Inval),
+        }
+    }
+}
+
+#[test]
+fn test_fd_ioctl() {
+    let mut store = Store::default();
+    let module = Module::new(&store, IOCTL).unwrap();
+
+    let mut wasi_env = WasiState::new("ioctl").finalize(&mut store).unwrap();
+    let device = wasi_env
+        .data_mut(&mut store)
+        .register_resource("device", Device)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let ioctl = instance.exports.get_function("ioctl").unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut ioctl = |fd: u32, request: i32, len: i32| {
+        let errno = ioctl
+            .call(
+                &mut store,
+                &[(fd as i32).into(), request.into(), len.into()],
+            )
+            .unwrap()[0]
+            .unwrap_i32();
+        let view = memory.view(&store);
+        let (mut ret_len, mut buf) = ([0; 4], [0; 3]);
+        view.read(8, &mut ret_len).unwrap();
+        view.read(16, &mut buf).unwrap();
+        (errno, u32::from_le_bytes(ret_len), buf)
+    };
+
+    assert_eq!(
+        ioctl(device, 1, 3),
+        (Errno::Success as i32, 3, *b"cba"),
+        "the response replaces the request"
+    );
+    assert_eq!(
+        ioctl(device, 2, 3),
+        (Errno::Overflow as i32, 6, *b"abc"),
+        "the response doesn't fit"
+    );
+    assert_eq!(ioctl(device, 3, 3).0, Errno::Inval as i32);
+    assert_eq!(
+        ioctl(1, 1, 3).0,
+        Errno::Notty as i32,
+        "the standard output handles no request"
+    );
+}
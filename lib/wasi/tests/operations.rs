@@ -0,0 +1,67 @@
+#![cfg(feature = "sys")]
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use wasmer::{Instance, Module, Store, Value};
+use wasmer_wasi::{PendingOperationKind, WasiState};
+
+/// Creates an event file descriptor without any pending event, and
+/// returns the error of reading from it.
+const READ_AN_EVENT: &str = r#"
+(module
+    (import "wasix_32v1" "fd_event" (func $fd_event (param i64 i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $run (export "run") (result i32)
+        ;; Create the event, its file descriptor goes to 100.
+        (drop (call $fd_event (i64.const 0) (i32.const 0) (i32.const 100)))
+
+        ;; Read the counter into 200.
+        (i32.store (i32.const 300) (i32.const 200))
+        (i32.store (i32.const 304) (i32.const 8))
+        (call $fd_read (i32.load (i32.const 100)) (i32.const 300) (i32.const 1) (i32.const 308))
+    )
+)
+"#;
+
+#[test]
+fn test_cancel_pending_operation() {
+    let mut store = Store::default();
+    let module = Module::new(&store, READ_AN_EVENT).unwrap();
+
+    let mut wasi_env = WasiState::new("read-event").finalize(&mut store).unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let supervisor = wasi_env.data_mut(&mut store).clone();
+    assert!(supervisor.pending_operations().is_empty());
+
+    let cancelling = thread::spawn(move || {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            if let Some(operation) = supervisor.pending_operations().first() {
+                assert_eq!(operation.kind, PendingOperationKind::Read);
+                assert!(operation.fd.is_some());
+                assert!(supervisor.cancel(operation.id), "the operation is pending");
+                return supervisor;
+            }
+            assert!(Instant::now() < deadline, "`fd_read` never blocked");
+            thread::sleep(Duration::from_millis(10));
+        }
+    });
+
+    let run = instance.exports.get_function("run").unwrap();
+    let result = run.call(&mut store, &[]).unwrap();
+    assert_eq!(result.to_vec(), vec![Value::I32(27)], "`EINTR` is returned");
+
+    let supervisor = cancelling.join().unwrap();
+    assert!(
+        supervisor.pending_operations().is_empty(),
+        "the operation is no longer pending once it returned",
+    );
+}
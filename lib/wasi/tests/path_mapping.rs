@@ -0,0 +1,49 @@
+#![cfg(feature = "sys")]
+
+use std::path::{Path, PathBuf};
+
+use wasmer::Store;
+use wasmer_wasi::WasiState;
+
+#[test]
+fn test_path_mapping() {
+    let mut store = Store::default();
+    let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+
+    let wasi_env = WasiState::new("command-name")
+        .map_dir("/data", &tests)
+        .unwrap()
+        .map_dir("/data/src", &src)
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+    let env = wasi_env.data_mut(&mut store);
+
+    assert_eq!(
+        env.host_path_of("/data/stdio.rs"),
+        Some(tests.join("stdio.rs"))
+    );
+    // The innermost directory wins.
+    assert_eq!(
+        env.host_path_of("/data/src/lib.rs"),
+        Some(src.join("lib.rs"))
+    );
+    // Relative to the current directory of the guest, `/`.
+    assert_eq!(
+        env.host_path_of("data/./x/../proc.rs"),
+        Some(tests.join("proc.rs"))
+    );
+    assert_eq!(env.host_path_of("/elsewhere"), None);
+    assert_eq!(env.host_path_of("/data/../etc/passwd"), None);
+
+    assert_eq!(
+        env.guest_path_of(tests.join("stdio.rs")),
+        Some(PathBuf::from("/data/stdio.rs"))
+    );
+    assert_eq!(
+        env.guest_path_of(src.join("state/mod.rs")),
+        Some(PathBuf::from("/data/src/state/mod.rs"))
+    );
+    assert_eq!(env.guest_path_of(tests.join("..").join("Cargo.toml")), None);
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "sys")]
+
+use std::collections::VecDeque;
+use std::io;
+
+use wasmer::{Instance, Module, Store};
+use wasmer_wasi::types::wasi::Errno;
+use wasmer_wasi::{WasiResource, WasiState};
+
+/// Writes `ping` to the file descriptor given as parameter, then
+/// reads it back at 100, returning the errnos; the number of bytes
+/// read is at 308.
+const PING: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 16) "ping")
+
+    (func (export "write") (param $fd i32) (result i32)
+        (i32.store (i32.const 300) (i32.const 16))
+        (i32.store (i32.const 304) (i32.const 4))
+        (call $fd_write (local.get $fd) (i32.const 300) (i32.const 1) (i32.const 308))
+    )
+
+    (func (export "read") (param $fd i32) (result i32)
+        (i32.store (i32.const 300) (i32.const 100))
+        (i32.store (i32.const 304) (i32.const 16))
+        (call $fd_read (local.get $fd) (i32.const 300) (i32.const 1) (i32.const 308))
+    )
+)
+"#;
+
+/// Answers what is written to it in upper case.
+#[derive(Debug, Default)]
+struct Upper {
+    pending: VecDeque<u8>,
+    writes: u8,
+}
+
+impl WasiResource for Upper {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = buf.len().min(self.pending.len());
+        for (byte, pending) in buf.iter_mut().zip(self.pending.drain(..read)) {
+            *byte = pending;
+        }
+        Ok(read)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend(buf.to_ascii_uppercase());
+        self.writes += 1;
+        Ok(buf.len())
+    }
+
+    fn bytes_available_read(&self) -> io::Result<Option<usize>> {
+        Ok(Some(self.pending.len()))
+    }
+
+    fn control(&mut self, request: u32, _data: &[u8]) -> Result<Vec<u8>, Errno> {
+        match request {
+            0 => Ok(vec![self.writes]),
+            _ => Err(Errno::Inval),
+        }
+    }
+}
+
+/// Can only be read from.
+#[derive(Debug)]
+struct ReadOnly;
+
+impl WasiResource for ReadOnly {
+    fn is_writable(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_resources() {
+    let mut store = Store::default();
+    let module = Module::new(&store, PING).unwrap();
+
+    let mut wasi_env = WasiState::new("resource").finalize(&mut store).unwrap();
+    let (upper, read_only) = {
+        let env = wasi_env.data_mut(&mut store);
+        (
+            env.register_resource("upper", Upper::default()).unwrap(),
+            env.register_resource("read-only", ReadOnly).unwrap(),
+        )
+    };
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let write = instance.exports.get_function("write").unwrap();
+    let read = instance.exports.get_function("read").unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+
+    let errno = write.call(&mut store, &[(upper as i32).into()]).unwrap()[0].unwrap_i32();
+    assert_eq!(errno, Errno::Success as i32, "writing to the resource");
+    let errno = read.call(&mut store, &[(upper as i32).into()]).unwrap()[0].unwrap_i32();
+    assert_eq!(errno, Errno::Success as i32, "reading from the resource");
+
+    let (mut nread, mut answer) = ([0; 4], [0; 4]);
+    memory.view(&store).read(308, &mut nread).unwrap();
+    memory.view(&store).read(100, &mut answer).unwrap();
+    assert_eq!(u32::from_le_bytes(nread), 4);
+    assert_eq!(&answer, b"PING");
+
+    let env = wasi_env.data_mut(&mut store);
+    assert_eq!(env.control_resource(upper, 0, &[]), Ok(vec![1]));
+    assert_eq!(env.control_resource(upper, 1, &[]), Err(Errno::Inval));
+    assert_eq!(
+        env.control_resource(read_only, 0, &[]),
+        Err(Errno::Notsup),
+        "the resource doesn't handle requests",
+    );
+    assert_eq!(
+        env.control_resource(1, 0, &[]),
+        Err(Errno::Notty),
+        "the standard output is not a resource",
+    );
+
+    let errno = write
+        .call(&mut store, &[(read_only as i32).into()])
+        .unwrap()[0]
+        .unwrap_i32();
+    assert_eq!(
+        errno,
+        Errno::Access as i32,
+        "writing to a read-only resource"
+    );
+}
@@ -1,7 +1,7 @@
 use std::io::{Read, Write};
 
 use wasmer::{Instance, Module, Store};
-use wasmer_wasi::{Pipe, WasiState};
+use wasmer_wasi::{AnsiEscapes, Pipe, WasiState};
 
 mod sys {
     #[test]
@@ -9,6 +9,11 @@ mod sys {
         super::test_stdout()
     }
 
+    #[test]
+    fn test_stdout_filtered() {
+        super::test_stdout_filtered()
+    }
+
     #[test]
     fn test_stdin() {
         super::test_stdin()
@@ -29,6 +34,11 @@ mod js {
         super::test_stdout()
     }
 
+    #[wasm_bindgen_test]
+    fn test_stdout_filtered() {
+        super::test_stdout_filtered()
+    }
+
     #[wasm_bindgen_test]
     fn test_stdin() {
         super::test_stdin()
@@ -98,6 +108,69 @@ fn test_stdout() {
     assert_eq!(stdout_as_str, "hello world\n");
 }
 
+fn test_stdout_filtered() {
+    let mut store = Store::default();
+    let module = Module::new(
+        &mut store,
+        br#"
+    (module
+        (import "wasi_unstable" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+        (memory 1)
+        (export "memory" (memory 0))
+
+        ;; A red 'hello', then ' world\n' in the default color.
+        (data (i32.const 8) "\1b[31mhello\1b[0m world\n")
+
+        (func $main (export "_start")
+            (i32.store (i32.const 0) (i32.const 8))
+            (i32.store (i32.const 4) (i32.const 21))
+
+            ;; Write to stdout, then stderr.
+            (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 32))
+            drop
+            (call $fd_write (i32.const 2) (i32.const 0) (i32.const 1) (i32.const 32))
+            drop
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    // Create the `WasiEnv`, stripping the colors and copying the
+    // outputs to `tee`.
+    let mut stdout = Pipe::new();
+    let mut stderr = Pipe::new();
+    let mut tee = Pipe::new();
+    let wasi_env = WasiState::new("command-name")
+        .stdout(Box::new(stdout.clone()))
+        .stderr(Box::new(stderr.clone()))
+        .output_ansi(AnsiEscapes::Strip)
+        .tee_output(Box::new(tee.clone()))
+        .finalize(&mut store)
+        .unwrap();
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let mut stdout_str = String::new();
+    stdout.read_to_string(&mut stdout_str).unwrap();
+    assert_eq!(stdout_str, "hello world\n");
+
+    let mut stderr_str = String::new();
+    stderr.read_to_string(&mut stderr_str).unwrap();
+    assert_eq!(stderr_str, "hello world\n");
+
+    let mut tee_str = String::new();
+    tee.read_to_string(&mut tee_str).unwrap();
+    assert_eq!(tee_str, "hello world\nhello world\n");
+}
+
 fn test_env() {
     let mut store = Store::default();
     let module = Module::new(&store, include_bytes!("envvar.wasm")).unwrap();
@@ -0,0 +1,137 @@
+#![cfg(feature = "sys")]
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use wasmer::{Instance, Module, Store};
+use wasmer_vfs::{mem_fs, FileSystem};
+use wasmer_wasi::{Pipe, WasiState};
+
+/// Prints the target of `/link` then the contents of the file it
+/// points to, and creates `/link2`, `/` being pre-opened as the file
+/// descriptor 4.
+const READ_LINK: &str = r#"
+(module
+    (import "wasi_unstable" "path_readlink" (func $path_readlink (param i32 i32 i32 i32 i32 i32) (result i32)))
+    (import "wasi_unstable" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_unstable" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_unstable" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_unstable" "path_symlink" (func $path_symlink (param i32 i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 0) "link")
+    (data (i32.const 500) "hello.txt")
+    (data (i32.const 520) "link2")
+
+    (func $main (export "_start")
+        ;; Read the link into 16, its length goes to 96.
+        (drop (call $path_readlink (i32.const 4) (i32.const 0) (i32.const 4) (i32.const 16) (i32.const 64) (i32.const 96)))
+        (i32.store (i32.const 400) (i32.const 16))
+        (i32.store (i32.const 404) (i32.load (i32.const 96)))
+        (drop (call $fd_write (i32.const 1) (i32.const 400) (i32.const 1) (i32.const 408)))
+
+        ;; Open the link, with `FD_READ`, its file descriptor goes to 100.
+        (drop (call $path_open (i32.const 4) (i32.const 1) (i32.const 0) (i32.const 4) (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 100)))
+
+        ;; Read the file into 200, its length goes to 320.
+        (i32.store (i32.const 300) (i32.const 200))
+        (i32.store (i32.const 304) (i32.const 64))
+        (drop (call $fd_read (i32.load (i32.const 100)) (i32.const 300) (i32.const 1) (i32.const 320)))
+        (i32.store (i32.const 400) (i32.const 200))
+        (i32.store (i32.const 404) (i32.load (i32.const 320)))
+        (drop (call $fd_write (i32.const 1) (i32.const 400) (i32.const 1) (i32.const 408)))
+
+        ;; Create `link2`, pointing to `hello.txt`.
+        (drop (call $path_symlink (i32.const 500) (i32.const 9) (i32.const 4) (i32.const 520) (i32.const 5)))
+    )
+)
+"#;
+
+#[test]
+fn test_mem_fs_symlink() {
+    let fs = mem_fs::FileSystem::default();
+    fs.new_open_options()
+        .write(true)
+        .create_new(true)
+        .open(Path::new("/hello.txt"))
+        .unwrap()
+        .write_all(b"hi")
+        .unwrap();
+    fs.symlink(Path::new("hello.txt"), Path::new("/link"))
+        .unwrap();
+
+    let mut store = Store::default();
+    let module = Module::new(&store, READ_LINK).unwrap();
+
+    let mut stdout = Pipe::new();
+    let mut wasi_env = WasiState::new("read-link")
+        .set_fs(Box::new(fs.clone()))
+        .preopen_dir("/")
+        .unwrap()
+        .stdout(Box::new(stdout.clone()))
+        .finalize(&mut store)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "hello.txthi");
+
+    assert_eq!(
+        fs.read_link(Path::new("/link2")),
+        Ok(Path::new("hello.txt").to_path_buf()),
+        "`path_symlink` creates the symlink in the file system",
+    );
+}
+
+#[test]
+fn test_sandboxed_symlink() {
+    let fs = mem_fs::FileSystem::default();
+    fs.create_dir(Path::new("/jail")).unwrap();
+    for (path, contents) in [("/hello.txt", b"no"), ("/jail/hello.txt", b"hi")] {
+        fs.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(Path::new(path))
+            .unwrap()
+            .write_all(contents)
+            .unwrap();
+    }
+    fs.symlink(Path::new("hello.txt"), Path::new("/jail/link"))
+        .unwrap();
+
+    let mut store = Store::default();
+    let module = Module::new(&store, READ_LINK).unwrap();
+
+    let mut stdout = Pipe::new();
+    let mut wasi_env = WasiState::new("read-link")
+        .set_fs(Box::new(fs.clone()))
+        .preopen_dir("/jail")
+        .unwrap()
+        .stdout(Box::new(stdout.clone()))
+        .finalize(&mut store)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "hello.txthi");
+
+    assert_eq!(
+        fs.read_link(Path::new("/jail/link2")),
+        Ok(Path::new("hello.txt").to_path_buf()),
+        "`path_symlink` creates the symlink in the pre-opened directory",
+    );
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "sys")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use wasmer::{Instance, Module, Store, Value};
+use wasmer_wasi::types::wasi::Errno;
+use wasmer_wasi::WasiState;
+
+/// Calls `args_sizes_get`, storing the number of arguments at 0, and
+/// `sched_yield`, returning their errnos.
+const ARGS_AND_YIELD: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sched_yield" (func $sched_yield (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func (export "args") (result i32)
+        (call $args_sizes_get (i32.const 0) (i32.const 4))
+    )
+
+    (func (export "yield") (result i32)
+        (call $sched_yield)
+    )
+)
+"#;
+
+#[test]
+fn test_syscall_overrides() {
+    let mut store = Store::default();
+    let module = Module::new(&store, ARGS_AND_YIELD).unwrap();
+
+    let mut wasi_env = WasiState::new("override").finalize(&mut store).unwrap();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    {
+        let calls = calls.clone();
+        let env = wasi_env.data_mut(&mut store);
+        env.override_syscall("args_sizes_get", move |mut ctx, args, default| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(default.call(&mut ctx, args)?.into_vec())
+        });
+        env.override_syscall("sched_yield", |_ctx, _args, _default| {
+            Ok(vec![Value::I32(Errno::Nosys as i32)])
+        });
+    }
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let args = instance.exports.get_function("args").unwrap();
+    let errno = args.call(&mut store, &[]).unwrap()[0].unwrap_i32();
+    assert_eq!(errno, Errno::Success as i32);
+
+    let yield_ = instance.exports.get_function("yield").unwrap();
+    let errno = yield_.call(&mut store, &[]).unwrap()[0].unwrap_i32();
+    assert_eq!(errno, Errno::Nosys as i32);
+    assert_eq!(
+        calls.load(Ordering::SeqCst),
+        1,
+        "the override delegated once"
+    );
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut argc = [0; 4];
+    memory.view(&store).read(0, &mut argc).unwrap();
+    assert_eq!(
+        u32::from_le_bytes(argc),
+        1,
+        "the default implementation ran"
+    );
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "sys")]
+
+use std::path::Path;
+
+use wasmer::{Instance, Module, Store};
+use wasmer_vfs::{mem_fs, FileSystem};
+use wasmer_wasi::WasiState;
+
+/// Creates `/foo.txt`, sets both of its times through its path, then
+/// its modification time through its file descriptor, `/` being
+/// pre-opened as the file descriptor 4.
+const SET_TIMES: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_filestat_set_times" (func $path_filestat_set_times (param i32 i32 i32 i32 i64 i64 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_filestat_set_times" (func $fd_filestat_set_times (param i32 i64 i64 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 0) "foo.txt")
+
+    (func $main (export "_start")
+        ;; Create the file, with `FD_WRITE` and `FD_FILESTAT_SET_TIMES`,
+        ;; its file descriptor goes to 100.
+        (drop (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 7) (i32.const 1) (i64.const 8388672) (i64.const 0) (i32.const 0) (i32.const 100)))
+
+        ;; `SET_ATIM | SET_MTIM`.
+        (drop (call $path_filestat_set_times (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 7) (i64.const 1500000001) (i64.const 2500000002) (i32.const 5)))
+
+        ;; `SET_MTIM`.
+        (drop (call $fd_filestat_set_times (i32.load (i32.const 100)) (i64.const 0) (i64.const 3500000003) (i32.const 4)))
+    )
+)
+"#;
+
+#[test]
+fn test_set_times_reaches_the_file_system() {
+    let fs = mem_fs::FileSystem::default();
+
+    let mut store = Store::default();
+    let module = Module::new(&store, SET_TIMES).unwrap();
+
+    let mut wasi_env = WasiState::new("set-times")
+        .set_fs(Box::new(fs.clone()))
+        .preopen_dir("/")
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let metadata = fs.metadata(Path::new("/foo.txt")).unwrap();
+    assert_eq!(
+        (metadata.accessed, metadata.modified),
+        (1_500_000_001, 3_500_000_003),
+        "the times are set to the nanosecond"
+    );
+}
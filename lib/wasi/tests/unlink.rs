@@ -0,0 +1,85 @@
+#![cfg(feature = "sys")]
+
+use std::io::Read;
+use std::path::Path;
+
+use wasmer::{Instance, Module, Store};
+use wasmer_vfs::{mem_fs, FileSystem};
+use wasmer_wasi::{Pipe, WasiState};
+
+/// Creates `/foo.txt`, writes `foo` to it, unlinks it, writes `bar`
+/// to it, then prints its contents, `/` being pre-opened as the file
+/// descriptor 4.
+const WRITE_UNLINKED: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_unlink_file" (func $path_unlink_file (param i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_seek" (func $fd_seek (param i32 i64 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 0) "foo.txt")
+    (data (i32.const 16) "foobar")
+
+    (func $main (export "_start")
+        ;; Create the file, with `FD_READ`, `FD_SEEK` and `FD_WRITE`,
+        ;; its file descriptor goes to 100.
+        (drop (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 7) (i32.const 1) (i64.const 70) (i64.const 0) (i32.const 0) (i32.const 100)))
+
+        ;; Write `foo`.
+        (i32.store (i32.const 300) (i32.const 16))
+        (i32.store (i32.const 304) (i32.const 3))
+        (drop (call $fd_write (i32.load (i32.const 100)) (i32.const 300) (i32.const 1) (i32.const 308)))
+
+        ;; Unlink the file, then write `bar`.
+        (drop (call $path_unlink_file (i32.const 4) (i32.const 0) (i32.const 7)))
+        (i32.store (i32.const 300) (i32.const 19))
+        (i32.store (i32.const 304) (i32.const 3))
+        (drop (call $fd_write (i32.load (i32.const 100)) (i32.const 300) (i32.const 1) (i32.const 308)))
+
+        ;; Read the file from the start into 1024, its length goes to 320.
+        (drop (call $fd_seek (i32.load (i32.const 100)) (i64.const 0) (i32.const 0) (i32.const 312)))
+        (i32.store (i32.const 300) (i32.const 1024))
+        (i32.store (i32.const 304) (i32.const 64))
+        (drop (call $fd_read (i32.load (i32.const 100)) (i32.const 300) (i32.const 1) (i32.const 320)))
+        (i32.store (i32.const 400) (i32.const 1024))
+        (i32.store (i32.const 404) (i32.load (i32.const 320)))
+        (drop (call $fd_write (i32.const 1) (i32.const 400) (i32.const 1) (i32.const 408)))
+    )
+)
+"#;
+
+#[test]
+fn test_mem_fs_unlinked_file_stays_readable() {
+    let fs = mem_fs::FileSystem::default();
+
+    let mut store = Store::default();
+    let module = Module::new(&store, WRITE_UNLINKED).unwrap();
+
+    let mut stdout = Pipe::new();
+    let mut wasi_env = WasiState::new("write-unlinked")
+        .set_fs(Box::new(fs.clone()))
+        .preopen_dir("/")
+        .unwrap()
+        .stdout(Box::new(stdout.clone()))
+        .finalize(&mut store)
+        .unwrap();
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "foobar", "the unlinked file is still usable");
+
+    assert!(
+        fs.metadata(Path::new("/foo.txt")).is_err(),
+        "the file is unlinked from the file system",
+    );
+}
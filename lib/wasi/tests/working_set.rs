@@ -0,0 +1,66 @@
+#![cfg(feature = "sys")]
+
+use std::sync::{Arc, Mutex};
+
+use wasmer::{Instance, Module, Store, WASM_PAGE_SIZE};
+use wasmer_wasi::{MemoryPressure, WasiState, WorkingSet, WorkingSetMonitor};
+
+/// Grows the memory by a page, then calls `sched_yield`.
+const GROW_AND_YIELD: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "sched_yield" (func $sched_yield (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func (export "grow") (result i32)
+        (drop (memory.grow (i32.const 1)))
+        (call $sched_yield)
+    )
+)
+"#;
+
+#[test]
+fn test_working_set_reports() {
+    let mut store = Store::default();
+    let module = Module::new(&store, GROW_AND_YIELD).unwrap();
+
+    let mut wasi_env = WasiState::new("working-set").finalize(&mut store).unwrap();
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let monitor = {
+        let reports = reports.clone();
+        WorkingSetMonitor::new(move |working_set| reports.lock().unwrap().push(*working_set))
+    };
+    wasi_env
+        .data_mut(&mut store)
+        .set_working_set_monitor(monitor);
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let pages = |pages: usize| WorkingSet {
+        memory_bytes: (pages * WASM_PAGE_SIZE) as u64,
+        fs_bytes: None,
+    };
+    let env = wasi_env.env.as_ref(&store);
+    assert_eq!(env.report_working_set(&store), Some(pages(1)));
+
+    let grow = instance.exports.get_function("grow").unwrap();
+    grow.call(&mut store, &[]).unwrap();
+    grow.call(&mut store, &[]).unwrap();
+
+    assert_eq!(
+        *reports.lock().unwrap(),
+        vec![pages(1), pages(2), pages(3)],
+        "the growth is reported at sched_yield"
+    );
+
+    let env = wasi_env.env.as_ref(&store);
+    assert_eq!(
+        env.relieve_memory_pressure(MemoryPressure::Critical),
+        Ok(0),
+        "no file system to compact"
+    );
+}
@@ -124,3 +124,39 @@ fn run_no_start_wasm_report_error() -> anyhow::Result<()> {
     assert_eq!(result.contains("Can not find any export functions."), true);
     Ok(())
 }
+
+#[test]
+fn run_artifact_for_another_target_reports_error() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let artifact = temp_dir.path().join("fib.wasmu");
+    let target = if cfg!(target_arch = "aarch64") {
+        "x86_64-unknown-linux-gnu"
+    } else {
+        "aarch64-unknown-linux-gnu"
+    };
+    let output = Command::new(get_wasmer_path())
+        .arg("compile")
+        .arg(test_no_imports_wat_path())
+        .arg("--target")
+        .arg(target)
+        .arg("-o")
+        .arg(&artifact)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "compilation failed with: stderr: {}",
+            std::str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&artifact)
+        .output()?;
+
+    assert!(!output.status.success());
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.contains(&format!("the artifact was compiled for `{}`", target)));
+    assert!(stderr.contains("WSM2102"));
+    Ok(())
+}